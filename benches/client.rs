@@ -0,0 +1,139 @@
+//! Benchmarks for the hot paths of the client, so that optimizations such as parallel
+//! verification or caching can be measured against a baseline.
+//!
+//! Run them with `cargo bench`. Like the node binary, most of these measure code that you write
+//! in the exercises, so they will panic until you have completed the relevant sections.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use diy_blockchain::c1_state_machine::{StateMachine, User};
+use diy_blockchain::c3_consensus::{Consensus, Header, Pow};
+use diy_blockchain::c4_client::fee_pool::{FeeInfo, FeePool};
+use diy_blockchain::c4_client::network::ChainHeader;
+use diy_blockchain::c4_client::runtime::{Runtime, RuntimeState};
+use diy_blockchain::c4_client::{
+    FullClient, ImportBlock, LongestChain, SimplePool, TransactionPool,
+};
+use diy_blockchain::hashing::H256;
+
+type BenchClient = FullClient<(), Runtime, LongestChain, SimplePool<Runtime>>;
+
+fn genesis_state() -> RuntimeState {
+    RuntimeState::genesis(&[(User::Alice, 1_000_000), (User::Bob, 1_000_000)])
+}
+
+fn block_import(c: &mut Criterion) {
+    let mut author = BenchClient::new(genesis_state());
+    let hash = author
+        .author_and_import_automatic_block()
+        .expect("the trivial engine always seals");
+    let block = || author.get_block(hash).expect("the block was just imported");
+
+    c.bench_function("import single block", |b| {
+        b.iter_batched(
+            || (BenchClient::new(genesis_state()), block()),
+            |(mut client, block)| assert!(client.import_block(block)),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+/// A chain of PoW headers on top of a made up genesis header.
+fn pow_headers(engine: &Pow, count: u64) -> (u64, Vec<Header<u64>>) {
+    let genesis = Header::new(H256::zero(), 0, H256::zero(), H256::zero(), 0);
+    let mut headers = Vec::with_capacity(count as usize);
+    let mut parent = genesis.clone();
+    for height in 1..=count {
+        let partial = Header::new(
+            parent.hash(),
+            height,
+            H256::from(height),
+            H256::from(height),
+            (),
+        );
+        let header = engine
+            .seal(parent.consensus_digest(), partial)
+            .expect("pow can always seal eventually");
+        parent = header.clone();
+        headers.push(header);
+    }
+    (*genesis.consensus_digest(), headers)
+}
+
+fn header_verification(c: &mut Criterion) {
+    // An easy threshold, so that mining the chain for the benchmark does not take long.
+    let engine = Pow::new(u64::MAX / 4);
+    let (genesis_digest, headers) = pow_headers(&engine, 10_000);
+
+    c.bench_function("verify 10k headers", |b| {
+        b.iter(|| assert!(engine.verify_sub_chain(&genesis_digest, &headers)))
+    });
+}
+
+fn pow_mining(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pow mining");
+    for difficulty in [1, 16, 256, 4096] {
+        let engine = Pow::new(u64::MAX / difficulty);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(difficulty),
+            &engine,
+            |b, engine| {
+                let mut height = 0;
+                b.iter(|| {
+                    // A different header every time, so that every iteration does fresh work.
+                    height += 1;
+                    engine.seal(
+                        &0,
+                        Header::new(H256::zero(), height, H256::zero(), H256::zero(), ()),
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Payments of `(sender, nonce, fee)`. The state machine is never run, the pool only needs
+/// its transaction type.
+struct Payments;
+
+impl StateMachine for Payments {
+    type State = ();
+    type Transition = (u32, u64, u64);
+
+    fn next_state(_: &(), _: &(u32, u64, u64)) {}
+}
+
+fn pool_insertion(c: &mut Criterion) {
+    let transactions: Vec<(u32, u64, u64)> = (0..1_000u64)
+        .map(|i| ((i % 100) as u32, i / 100, i.wrapping_mul(7919) % 1_000))
+        .collect();
+    let fee_info = |&(sender, nonce, fee): &(u32, u64, u64)| FeeInfo {
+        sender,
+        nonce,
+        fee,
+        weight: 1,
+    };
+
+    c.bench_function("insert 1k transactions into fee pool", |b| {
+        b.iter_batched(
+            || FeePool::new(fee_info, 512),
+            |mut pool| {
+                for &t in &transactions {
+                    TransactionPool::<Payments>::try_insert(&mut pool, t);
+                }
+                pool
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    pool_insertion,
+    pow_mining,
+    header_verification,
+    block_import
+);
+criterion_main!(benches);
@@ -0,0 +1,74 @@
+//! Chapter 1 in action: walk up to the ATM from part 3, and take some cash out of it.
+//!
+//! Every step is a single transition of the state machine, so the output shows the machine's
+//! whole state after each one. Run it with `cargo run --example atm_demo`.
+
+use diy_blockchain::c1_state_machine::{Action, Atm, Key, StateMachine};
+use diy_blockchain::hashing::pin_hash;
+
+/// Apply a single action to the ATM, and say what happened.
+fn step(atm: &Atm, action: Action, what: &str) -> Atm {
+    let next = Atm::next_state(atm, &action);
+    println!("{what:<40} -> {next:?}");
+    next
+}
+
+/// Press every key in turn, and then `Enter`.
+fn key_in(mut atm: Atm, keys: &[Key], what: &str) -> Atm {
+    for key in keys {
+        atm = step(
+            &atm,
+            Action::PressKey(key.clone()),
+            &format!("  press {key:?}"),
+        );
+    }
+    step(
+        &atm,
+        Action::PressKey(Key::Enter),
+        &format!("  press Enter ({what})"),
+    )
+}
+
+fn main() {
+    let pin = [Key::One, Key::Two, Key::Three, Key::Four];
+    let card = pin_hash(&pin);
+
+    let atm = Atm::new(100);
+    println!(
+        "A fresh ATM with {} in cash:\n  {atm:?}\n",
+        atm.cash_inside()
+    );
+
+    println!("Keys pressed before a card is swiped are ignored:");
+    let atm = step(&atm, Action::PressKey(Key::One), "  press One");
+    println!();
+
+    println!("Swipe the card, but get the pin wrong. The card comes straight back:");
+    let atm = step(
+        &atm,
+        Action::SwipeCard(card),
+        "  swipe a card with pin 1234",
+    );
+    let atm = key_in(atm, &[Key::Four, Key::Three], "wrong pin");
+    println!();
+
+    println!("Try again with the right pin, and withdraw 42:");
+    let atm = step(
+        &atm,
+        Action::SwipeCard(card),
+        "  swipe a card with pin 1234",
+    );
+    let atm = key_in(atm, &pin, "right pin");
+    let atm = key_in(atm, &[Key::Four, Key::Two], "amount");
+    println!("The ATM has {} left.\n", atm.cash_inside());
+
+    println!("Ask for more than the ATM holds. Nothing comes out:");
+    let atm = step(
+        &atm,
+        Action::SwipeCard(card),
+        "  swipe a card with pin 1234",
+    );
+    let atm = key_in(atm, &pin, "right pin");
+    let atm = key_in(atm, &[Key::One, Key::One, Key::One], "amount");
+    println!("The ATM still has {} left.", atm.cash_inside());
+}
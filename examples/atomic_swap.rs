@@ -0,0 +1,133 @@
+//! Swap coins between two independent chains, without trusting anybody.
+//!
+//! Two devnets run side by side: on chain A Alice has 100 coins, and on chain B Bob has 50. They
+//! swap them with hash time locked contracts, following the steps described in the `htlc` module.
+//! Neither chain ever hears of the other. All that connects them is Alice's secret, which she has
+//! to reveal on chain B to get Bob's coins, and which Bob then uses on chain A to get hers.
+//!
+//! The chains here are run by the minimal `HtlcNode`, not by the client of chapter 4, so this
+//! example works right away. Run it with `cargo run --example atomic_swap`.
+
+use std::time::Duration;
+
+use diy_blockchain::c1_state_machine::User;
+use diy_blockchain::c4_client::devnet::Devnet;
+use diy_blockchain::c4_client::htlc::{
+    hashlock, lock_id, lock_payload, HtlcExtrinsic, HtlcNode, HtlcState, HtlcTerms,
+};
+use diy_blockchain::c4_client::keystore::Keystore;
+use diy_blockchain::c4_client::runtime::RuntimeState;
+use diy_blockchain::hashing::H256;
+
+/// How often the authoring node of each chain authors a block.
+const BLOCK_TIME: Duration = Duration::from_millis(50);
+
+/// How long to wait for any one step to make it on chain.
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn launch(genesis: &[(User, u64)]) -> Devnet<HtlcNode> {
+    let genesis = HtlcState::genesis(RuntimeState::genesis(genesis));
+    Devnet::launch(2, BLOCK_TIME, |info| {
+        HtlcNode::new(genesis.clone(), info.account == User::Alice)
+    })
+}
+
+/// Hand an extrinsic to the authoring node of a chain, and return that chain's current height.
+fn submit(chain: &Devnet<HtlcNode>, extrinsic: HtlcExtrinsic) -> u64 {
+    let mut node = chain.node(0).lock().unwrap();
+    node.submit(extrinsic);
+    node.height()
+}
+
+fn lock(sender: User, terms: HtlcTerms) -> HtlcExtrinsic {
+    let signature = Keystore::dev()
+        .sign(sender, lock_payload(sender, 0, &terms))
+        .expect("the dev keystore holds every dev account's key");
+    HtlcExtrinsic::Lock {
+        sender,
+        nonce: 0,
+        terms,
+        signature,
+    }
+}
+
+fn balances(chain: &str, devnet: &Devnet<HtlcNode>) {
+    let node = devnet.node(0).lock().unwrap();
+    let runtime = &node.state().runtime;
+    println!(
+        "  chain {chain} at #{}: Alice has {}, Bob has {}, {} locks open",
+        node.height(),
+        runtime.account(User::Alice).balance,
+        runtime.account(User::Bob).balance,
+        node.state().locks.len()
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    let chain_a = launch(&[(User::Alice, 100)]);
+    let chain_b = launch(&[(User::Bob, 50)]);
+    let secret = H256::from(0x5ec2e7);
+
+    println!("1. Alice locks 100 on chain A for Bob, under the hash of her secret");
+    let height = chain_a.node(0).lock().unwrap().height();
+    let alice_terms = HtlcTerms {
+        receiver: User::Bob,
+        amount: 100,
+        hashlock: hashlock(&secret),
+        timeout: height + 40,
+    };
+    submit(&chain_a, lock(User::Alice, alice_terms));
+    chain_a
+        .wait_until(STEP_TIMEOUT, |node| !node.state().locks.is_empty())
+        .await;
+    balances("A", &chain_a);
+
+    println!("2. Bob locks 50 on chain B for Alice, under the same hash, with a shorter timeout");
+    let height = chain_b.node(0).lock().unwrap().height();
+    let bob_terms = HtlcTerms {
+        receiver: User::Alice,
+        amount: 50,
+        hashlock: alice_terms.hashlock,
+        timeout: height + 20,
+    };
+    submit(&chain_b, lock(User::Bob, bob_terms));
+    chain_b
+        .wait_until(STEP_TIMEOUT, |node| !node.state().locks.is_empty())
+        .await;
+    balances("B", &chain_b);
+
+    println!("3. Alice claims on chain B, and reveals her secret doing so");
+    let claim = HtlcExtrinsic::Claim {
+        id: lock_id(User::Bob, 0),
+        preimage: secret,
+    };
+    submit(&chain_b, claim);
+    chain_b
+        .wait_until(STEP_TIMEOUT, |node| node.state().locks.is_empty())
+        .await;
+    balances("B", &chain_b);
+
+    println!("4. Bob reads the secret off chain B, and claims on chain A with it");
+    let Some(preimage) = chain_b
+        .node(1)
+        .lock()
+        .unwrap()
+        .find_preimage(alice_terms.hashlock)
+    else {
+        println!("  the secret never made it onto chain B");
+        return;
+    };
+    let claim = HtlcExtrinsic::Claim {
+        id: lock_id(User::Alice, 0),
+        preimage,
+    };
+    submit(&chain_a, claim);
+    chain_a
+        .wait_until(STEP_TIMEOUT, |node| node.state().locks.is_empty())
+        .await;
+    balances("A", &chain_a);
+
+    chain_a.shutdown().await;
+    chain_b.shutdown().await;
+}
@@ -0,0 +1,136 @@
+//! Chapter 4 in action: grow two competing forks in one client, and watch the best chain move
+//! from one to the other.
+//!
+//! Alice's transfer goes into the first fork, and Bob's into the second. Once the second fork
+//! gets longer, the client reorganizes onto it: Alice's transfer is no longer on the best
+//! chain, and Bob's is.
+//!
+//! The client is built by _you_ throughout chapter 4, so this example panics at the first
+//! unimplemented client method until you have completed those exercises. Run it with
+//! `cargo run --example fork_and_reorg`.
+
+use diy_blockchain::c1_state_machine::{AccountingTransaction, User};
+use diy_blockchain::c4_client::keystore::Keystore;
+use diy_blockchain::c4_client::reorg::tree_route;
+use diy_blockchain::c4_client::runtime::{signing_payload, Runtime, RuntimeState, SignedExtrinsic};
+use diy_blockchain::c4_client::{FullClient, ImportBlock, LongestChain, SimplePool};
+use diy_blockchain::hashing::H256;
+
+type Client = FullClient<(), Runtime, LongestChain, SimplePool<Runtime>>;
+
+fn transfer(sender: User, receiver: User, amount: u64) -> SignedExtrinsic {
+    let call = AccountingTransaction::Transfer {
+        sender,
+        receiver,
+        amount,
+    };
+    let signature = Keystore::dev()
+        .sign(sender, signing_payload(sender, 0, &call))
+        .expect("the dev keystore holds every dev account's key");
+    SignedExtrinsic {
+        signer: sender,
+        nonce: 0,
+        call,
+        signature,
+    }
+}
+
+/// Author a block on top of the given parent, and return its hash.
+fn author_on(
+    client: &mut Client,
+    parent: H256,
+    transactions: Vec<SignedExtrinsic>,
+    name: &str,
+) -> H256 {
+    let leaves = client.all_leaves();
+    client.author_and_import_manual_block(transactions, parent);
+    let hash = client
+        .all_leaves()
+        .into_iter()
+        .find(|leaf| !leaves.contains(leaf))
+        .expect("a freshly authored block is a leaf");
+    println!(
+        "  authored {name} {hash}, best block is now {}",
+        describe(client, client.best_block())
+    );
+    hash
+}
+
+fn describe(client: &Client, hash: H256) -> String {
+    let height = client
+        .get_block(hash)
+        .map_or(0, |block| block.header().height());
+    format!("#{height} {hash}")
+}
+
+fn balances(client: &Client) {
+    let state = client
+        .get_state(client.best_block())
+        .expect("the best block has a state");
+    for who in [User::Alice, User::Bob, User::Charlie] {
+        println!("  {who:?} has {}", state.account(who).balance);
+    }
+}
+
+fn main() {
+    let genesis = RuntimeState::genesis(&[(User::Alice, 100), (User::Bob, 100)]);
+    let mut client = Client::new(genesis);
+    let genesis = client.best_block();
+    println!("genesis is {genesis}\n");
+
+    println!("Fork A, in which Alice pays Charlie 10:");
+    let a1 = author_on(
+        &mut client,
+        genesis,
+        vec![transfer(User::Alice, User::Charlie, 10)],
+        "A1",
+    );
+    let a2 = author_on(&mut client, a1, vec![], "A2");
+    balances(&client);
+
+    println!("\nFork B, in which Bob pays Charlie 20. It takes over once it is longer than A:");
+    let b1 = author_on(
+        &mut client,
+        genesis,
+        vec![transfer(User::Bob, User::Charlie, 20)],
+        "B1",
+    );
+    let b2 = author_on(&mut client, b1, vec![], "B2");
+    let b3 = author_on(&mut client, b2, vec![], "B3");
+    balances(&client);
+
+    let route = tree_route(a2, b3, |hash| {
+        client.get_block(hash).map(|block| block.header().clone())
+    })
+    .expect("both forks are in the client");
+    println!("\nThe reorganization from A2 to B3:");
+    println!("  common ancestor {}", route.common_ancestor);
+    for hash in &route.retracted {
+        println!("  retracted {}", describe(&client, *hash));
+    }
+    for hash in &route.enacted {
+        println!("  enacted   {}", describe(&client, *hash));
+    }
+    // An import that changes the best block maintains the pool, see `FullClient::maintain_pool`,
+    // so Alice's transfer is waiting to be included again.
+    println!("  transactions back in the pool: {}", client.pool_size());
+
+    println!("\nEvery branch of the block tree:");
+    for fork in client.forks().unwrap_or_default() {
+        println!(
+            "  {} branch ending in #{} {}, {} blocks after {}",
+            if fork.is_best { "best " } else { "stale" },
+            fork.height,
+            fork.head,
+            fork.length,
+            fork.fork_point
+        );
+    }
+
+    if let Some(tree) = client.block_tree() {
+        println!(
+            "\nThe same tree in Graphviz's DOT format:\n{}",
+            tree.to_dot()
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Chapter 3 in action: mine a short proof of work chain, one header at a time, and then check
+//! it the way a node that just received it would.
+//!
+//! Mining and checking seals are exercises in chapter 3, so this example panics until you have
+//! completed them. Run it with `cargo run --example mine_a_chain`, or with
+//! `cargo run --example mine_a_chain --features solutions` to watch the reference solutions.
+
+use std::time::Instant;
+
+use diy_blockchain::c3_consensus::{Consensus, Header, Pow};
+use diy_blockchain::difficulty::difficulty_to_threshold;
+use diy_blockchain::hashing::{hash, header_hash, H256};
+use diy_blockchain::merkle::empty_root;
+
+/// On average, one in this many nonces gives a valid seal.
+const DIFFICULTY: u128 = 10_000;
+
+/// How many blocks to mine on top of genesis.
+const BLOCKS: u64 = 5;
+
+fn main() {
+    let pow = Pow::new(difficulty_to_threshold(DIFFICULTY));
+    println!("Mining {BLOCKS} blocks at difficulty {DIFFICULTY}.\n");
+
+    // Consensus engines never look at the state, so a made up state root serves just as well.
+    let genesis = Header::new(H256::zero(), 0, hash(&0u64), empty_root(), 0u64);
+    println!("genesis   {}", header_hash(&genesis));
+
+    let mut chain = Vec::new();
+    let mut parent = genesis.clone();
+    for height in 1..=BLOCKS {
+        let partial = Header::new(
+            header_hash(&parent),
+            height,
+            hash(&height),
+            empty_root(),
+            (),
+        );
+        let started = Instant::now();
+        let header = pow
+            .seal(parent.consensus_digest(), partial)
+            .expect("proof of work can always find a seal, given enough time");
+        println!(
+            "block #{height}  {}  nonce {:>10}  mined in {:?}",
+            header_hash(&header),
+            header.consensus_digest(),
+            started.elapsed()
+        );
+        parent = header.clone();
+        chain.push(header);
+    }
+
+    println!("\nA node that receives these headers checks every seal:");
+    let valid = pow.verify_sub_chain(genesis.consensus_digest(), &chain);
+    println!("  the chain as mined is {}", verdict(valid));
+
+    println!(
+        "\nChanging anything in a sealed header changes its hash, so the work no longer counts:"
+    );
+    let tampered = &chain[2];
+    chain[2] = Header::new(
+        tampered.parent(),
+        tampered.height(),
+        hash(&"a state in which I am rich"),
+        tampered.extrinsics_root(),
+        *tampered.consensus_digest(),
+    );
+    let valid = pow.verify_sub_chain(genesis.consensus_digest(), &chain);
+    println!(
+        "  the chain with block #3's state root changed is {}",
+        verdict(valid)
+    );
+}
+
+fn verdict(valid: bool) -> &'static str {
+    if valid {
+        "valid"
+    } else {
+        "rejected"
+    }
+}
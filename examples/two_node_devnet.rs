@@ -0,0 +1,102 @@
+//! Chapter 4 in action: start two full clients, connect them, and watch them build one chain
+//! together.
+//!
+//! Both nodes try to author a block on every tick, and pass every block they author on to the
+//! other. Whenever both author a block at the same height, the chain forks for a moment, until
+//! one side gets ahead and the fork choice rule brings the other node over to it.
+//!
+//! The client is built by _you_ throughout chapter 4, so this example panics at the first
+//! unimplemented client method until you have completed those exercises. Run it with
+//! `cargo run --example two_node_devnet`.
+
+use std::time::Duration;
+
+use diy_blockchain::c1_state_machine::{AccountingTransaction, User};
+use diy_blockchain::c4_client::devnet::Devnet;
+use diy_blockchain::c4_client::keystore::Keystore;
+use diy_blockchain::c4_client::runtime::{signing_payload, Runtime, RuntimeState, SignedExtrinsic};
+use diy_blockchain::c4_client::{FullClient, ImportBlock, LongestChain, SimplePool};
+
+type Client = FullClient<(), Runtime, LongestChain, SimplePool<Runtime>>;
+
+/// How often every node tries to author a block.
+const BLOCK_TIME: Duration = Duration::from_millis(200);
+
+/// How many blocks to wait for.
+const TARGET_HEIGHT: u64 = 5;
+
+fn height(client: &Client) -> u64 {
+    client
+        .get_block(client.best_block())
+        .map_or(0, |block| block.header().height())
+}
+
+#[tokio::main]
+async fn main() {
+    let genesis = RuntimeState::genesis(&[(User::Alice, 1_000), (User::Bob, 1_000)]);
+
+    let devnet = Devnet::launch(2, BLOCK_TIME, |info| {
+        println!(
+            "starting node {}, which holds {:?}'s key",
+            info.index, info.account
+        );
+        Client::new(genesis.clone())
+    });
+
+    // Give the first node something to put in its blocks, so that its blocks differ from the
+    // empty ones that the second node authors.
+    let call = AccountingTransaction::Transfer {
+        sender: User::Alice,
+        receiver: User::Bob,
+        amount: 100,
+    };
+    let signature = Keystore::dev()
+        .sign(User::Alice, signing_payload(User::Alice, 0, &call))
+        .expect("the dev keystore holds every dev account's key");
+    devnet
+        .node(0)
+        .lock()
+        .unwrap()
+        .submit_transaction(SignedExtrinsic {
+            signer: User::Alice,
+            nonce: 0,
+            call,
+            signature,
+        });
+    println!("submitted a transfer of 100 from Alice to Bob to node 0\n");
+
+    println!("waiting for both nodes to reach height {TARGET_HEIGHT}...");
+    let reached = devnet
+        .wait_until(BLOCK_TIME * 10 * TARGET_HEIGHT as u32, |client| {
+            height(client) >= TARGET_HEIGHT
+        })
+        .await;
+    if !reached {
+        println!("the nodes did not get there in time");
+    }
+
+    // One more tick, so that the last blocks either node authored reach the other one.
+    tokio::time::sleep(BLOCK_TIME).await;
+
+    for node in devnet.shutdown().await {
+        let client = node.node.lock().unwrap();
+        let best = client.best_block();
+        let state = client.get_state(best).expect("the best block has a state");
+        println!("\nnode {}:", node.info.index);
+        println!("  best block #{} {}", height(&client), best);
+        println!(
+            "  Alice has {}, Bob has {}",
+            state.account(User::Alice).balance,
+            state.account(User::Bob).balance
+        );
+        for fork in client.forks().unwrap_or_default() {
+            println!(
+                "  {} branch ending in #{} {}, {} blocks since it forked",
+                if fork.is_best { "best" } else { "stale" },
+                fork.height,
+                fork.head,
+                fork.length
+            );
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Drive the ATM with arbitrary sequences of card swipes and key presses, checking that it never
+//! panics, never makes cash out of nothing, and only ever pays out when enter is pressed.
+
+#![no_main]
+
+use diy_blockchain::c1_state_machine::{Action, Atm, Key, StateMachine};
+use diy_blockchain::hashing::pin_hash;
+use libfuzzer_sys::fuzz_target;
+
+const KEYS: [Key; 4] = [Key::One, Key::Two, Key::Three, Key::Four];
+
+/// Turn one byte into an action. The low three bits pick the action, and for a card swipe the
+/// remaining bits pick a pin of one to four keys.
+fn action(byte: u8) -> Action {
+    match byte % 8 {
+        key @ 0..=3 => Action::PressKey(KEYS[key as usize].clone()),
+        4 | 5 => Action::PressKey(Key::Enter),
+        _ => {
+            let pin: Vec<Key> = (0..1 + (byte >> 6))
+                .map(|i| KEYS[((byte >> (3 + i)) & 3) as usize].clone())
+                .collect();
+            Action::SwipeCard(pin_hash(&pin))
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut atm = Atm::new(u64::from(data.first().copied().unwrap_or(100)));
+    for &byte in data.iter().skip(1) {
+        let action = action(byte);
+        let next = Atm::next_state(&atm, &action);
+        assert!(next.cash_inside() <= atm.cash_inside());
+        if next.cash_inside() < atm.cash_inside() {
+            assert!(matches!(action, Action::PressKey(Key::Enter)));
+        }
+        atm = next;
+    }
+});
@@ -0,0 +1,31 @@
+//! Feed arbitrary bytes into the decoders for everything a stranger can send us: framed network
+//! messages and discovery beacons. Decoding may fail, but it must never panic, and whatever does
+//! decode must survive a round trip.
+
+#![no_main]
+
+use diy_blockchain::c3_consensus::{Header, Pow};
+use diy_blockchain::c4_client::network::discovery::Beacon;
+use diy_blockchain::c4_client::network::message::{decode, encode, NetworkMessage};
+use diy_blockchain::c4_client::runtime::{Runtime, SignedExtrinsic};
+use diy_blockchain::c4_client::Block;
+use libfuzzer_sys::fuzz_target;
+
+/// The messages that the node binary exchanges with its peers.
+type NodeMessage = NetworkMessage<Header<u64>, Block<Pow, Runtime>, SignedExtrinsic>;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(Some((message, used))) = decode::<NodeMessage>(data) {
+        assert!(used <= data.len());
+        let frame = encode(&message).expect("decoded messages encode again");
+        let (again, _) = decode::<NodeMessage>(&frame)
+            .expect("encoded messages decode")
+            .expect("the frame is complete");
+        // Blocks can not be compared directly, but their encodings can.
+        assert_eq!(encode(&again).ok(), Some(frame));
+    }
+
+    if let Some(beacon) = Beacon::decode(data) {
+        assert_eq!(Beacon::decode(&beacon.encode()), Some(beacon));
+    }
+});
@@ -0,0 +1,50 @@
+//! Decode arbitrary bytes into blocks and import them into a full client, the way blocks from
+//! the network are. The client must reject what is invalid without panicking, and every block
+//! it does accept must keep the books straight: the issuance only changes by what the block's
+//! extrinsics mint and burn.
+//!
+//! This runs your client from chapter 4, so it needs the importing exercises to be done.
+
+#![no_main]
+
+use diy_blockchain::c1_state_machine::{AccountingTransaction, User};
+use diy_blockchain::c3_consensus::Pow;
+use diy_blockchain::c4_client::runtime::{Runtime, RuntimeState};
+use diy_blockchain::c4_client::{Block, FullClient, ImportBlock, LongestChain, SimplePool};
+use diy_blockchain::hashing::header_hash;
+use libfuzzer_sys::fuzz_target;
+
+type Client = FullClient<Pow, Runtime, LongestChain, SimplePool<Runtime>>;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(blocks) = bincode::deserialize::<Vec<Block<Pow, Runtime>>>(data) else {
+        return;
+    };
+
+    let mut client = Client::new(RuntimeState::genesis(&[(User::Alice, 1_000)]));
+    for block in blocks {
+        let hash = header_hash(block.header());
+        let parent = block.header().parent();
+        let (mut minted, mut burned) = (0u128, 0u128);
+        for extrinsic in block.body() {
+            match extrinsic.call {
+                AccountingTransaction::Mint { amount, .. } => minted += u128::from(amount),
+                AccountingTransaction::Burn { amount, .. } => burned += u128::from(amount),
+                AccountingTransaction::Transfer { .. } => {}
+            }
+        }
+        if !client.import_block(block) {
+            continue;
+        }
+
+        let before = client
+            .get_state(parent)
+            .expect("the parent of an imported block is known")
+            .total_issuance();
+        let after = client
+            .get_state(hash)
+            .expect("an imported block has a state")
+            .total_issuance();
+        assert!(after <= before + minted && before <= after + burned);
+    }
+});
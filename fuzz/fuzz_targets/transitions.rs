@@ -0,0 +1,48 @@
+//! Decode arbitrary bytes into a list of extrinsics and run them through the runtime, checking
+//! that no money appears or disappears except through minting and burning.
+//!
+//! Hardly any random bytes carry a valid signature, so every extrinsic is signed again with the
+//! dev key of its signer. That way the fuzzer spends its time on the calls themselves, and on
+//! your accounted currency from chapter 1, rather than on the signature check.
+
+#![no_main]
+
+use diy_blockchain::c1_state_machine::{AccountingTransaction, StateMachine, User};
+use diy_blockchain::c4_client::keystore::Keystore;
+use diy_blockchain::c4_client::runtime::{signing_payload, Runtime, RuntimeState, SignedExtrinsic};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(extrinsics) = bincode::deserialize::<Vec<SignedExtrinsic>>(data) else {
+        return;
+    };
+
+    let keystore = Keystore::dev();
+    let mut state = RuntimeState::genesis(&[(User::Alice, 1_000), (User::Bob, 1_000)]);
+    for mut extrinsic in extrinsics {
+        let payload = signing_payload(extrinsic.signer, extrinsic.nonce, &extrinsic.call);
+        extrinsic.signature = keystore
+            .sign(extrinsic.signer, payload)
+            .expect("the dev keystore holds every user's key");
+
+        let next = Runtime::next_state(&state, &extrinsic);
+        let (before, after) = (state.total_issuance(), next.total_issuance());
+        match extrinsic.call {
+            AccountingTransaction::Mint { amount, .. } => {
+                assert!(before <= after && after <= before + u128::from(amount))
+            }
+            AccountingTransaction::Burn { amount, .. } => {
+                assert!(after <= before && before <= after + u128::from(amount))
+            }
+            AccountingTransaction::Transfer { .. } => assert_eq!(before, after),
+        }
+        if next != state {
+            assert_eq!(
+                next.account(extrinsic.signer).nonce,
+                state.account(extrinsic.signer).nonce + 1,
+                "every executed extrinsic bumps its signer's nonce"
+            );
+        }
+        state = next;
+    }
+});
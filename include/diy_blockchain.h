@@ -0,0 +1,63 @@
+/*
+ * The C interface to the verification core of diy-blockchain. Build the library with
+ * `cargo build --release --features ffi`, and link against the cdylib it produces.
+ *
+ * Every function returns one of the DIY_* status codes below. See src/ffi.rs for the details.
+ */
+
+#ifndef DIY_BLOCKCHAIN_H
+#define DIY_BLOCKCHAIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The call succeeded, and the answer is yes where there is a question. */
+#define DIY_OK 0
+/* The call succeeded, but the header or block is invalid. */
+#define DIY_INVALID 1
+/* A pointer argument was null. */
+#define DIY_ERR_NULL (-1)
+/* The bytes could not be decoded. */
+#define DIY_ERR_DECODE (-2)
+/* The Rust code panicked, most likely on an exercise that is not done yet. */
+#define DIY_ERR_PANIC (-3)
+
+/* A header sealed with proof of work. */
+typedef struct DiyPowHeader {
+    uint8_t parent[32];
+    uint64_t height;
+    uint8_t state_root[32];
+    uint8_t extrinsics_root[32];
+    uint64_t nonce;
+} DiyPowHeader;
+
+/* A client running the signed currency runtime with proof of work. */
+typedef struct DiyClient DiyClient;
+
+/* Hash a header into the 32 bytes at `out`. */
+int32_t diy_header_hash(const DiyPowHeader *header, uint8_t (*out)[32]);
+
+/* Check the seal of a header against a proof of work threshold. DIY_OK if it is valid. */
+int32_t diy_pow_verify_seal(const DiyPowHeader *header, uint64_t threshold);
+
+/* Start a client whose genesis endows Alice, Bob and Charlie with the given balances. */
+int32_t diy_client_new(const uint64_t (*balances)[3], DiyClient **out);
+
+/* Free a client. Null handles are ignored. */
+void diy_client_free(DiyClient *client);
+
+/* Import a bincode encoded block. DIY_OK if the client imported it. */
+int32_t diy_client_import_block(DiyClient *client, const uint8_t *block, size_t len);
+
+/* Write the hash of the client's best block to the 32 bytes at `out`. */
+int32_t diy_client_best_block(const DiyClient *client, uint8_t (*out)[32]);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,4 @@
+//! The bindings themselves live in `diy_blockchain::wasm`. Re-exporting them links them into
+//! this cdylib, where wasm-bindgen finds them.
+
+pub use diy_blockchain::wasm::*;
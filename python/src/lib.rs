@@ -0,0 +1,331 @@
+//! The `blockchain_from_scratch` Python module, so that instructors can demo the crate from a
+//! notebook, and write assignments that script against it.
+//!
+//! ```python
+//! import blockchain_from_scratch as bfs
+//!
+//! balances = bfs.next_state({}, bfs.Call.mint("alice", 100))
+//! balances = bfs.next_state(balances, bfs.Call.transfer("alice", "bob", 30))
+//! assert balances == {"alice": 70, "bob": 30}
+//!
+//! chain = bfs.PowChain(100)
+//! for _ in range(5):
+//!     chain.mine()
+//! assert chain.verify()
+//! ```
+//!
+//! Everything runs the exercises of whoever built the module. Code that is not written yet
+//! raises a `PanicException` in Python, instead of taking the interpreter down. Build with the
+//! `solutions` feature to run the reference solutions instead.
+
+use std::collections::{BTreeMap, HashMap};
+
+use diy_blockchain::c1_state_machine::{
+    self as c1, AccountedCurrency, AccountingTransaction, Action, Key, StateMachine, User,
+};
+use diy_blockchain::c3_consensus::{Consensus, Header, Pow};
+use diy_blockchain::c4_client::keystore::Keystore;
+use diy_blockchain::c4_client::runtime::{Runtime, RuntimeState, ACCOUNTS};
+use diy_blockchain::c4_client::wallet::{Wallet, WalletError};
+use diy_blockchain::c4_client::{FullClient, ImportBlock, LongestChain, SimplePool};
+use diy_blockchain::difficulty::difficulty_to_threshold;
+use diy_blockchain::hashing::{header_hash, pin_hash, H256};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+type ChainClient = FullClient<Pow, Runtime, LongestChain, SimplePool<Runtime>>;
+
+fn parse_user(name: &str) -> PyResult<User> {
+    name.parse().map_err(PyValueError::new_err)
+}
+
+fn user_name(user: User) -> String {
+    format!("{user:?}").to_lowercase()
+}
+
+/// A transaction of the accounted currency from chapter 1.
+#[pyclass(frozen)]
+#[derive(Clone)]
+struct Call(AccountingTransaction);
+
+#[pymethods]
+impl Call {
+    #[staticmethod]
+    fn mint(minter: &str, amount: u64) -> PyResult<Self> {
+        let minter = parse_user(minter)?;
+        Ok(Call(AccountingTransaction::Mint { minter, amount }))
+    }
+
+    #[staticmethod]
+    fn burn(burner: &str, amount: u64) -> PyResult<Self> {
+        let burner = parse_user(burner)?;
+        Ok(Call(AccountingTransaction::Burn { burner, amount }))
+    }
+
+    #[staticmethod]
+    fn transfer(sender: &str, receiver: &str, amount: u64) -> PyResult<Self> {
+        Ok(Call(AccountingTransaction::Transfer {
+            sender: parse_user(sender)?,
+            receiver: parse_user(receiver)?,
+            amount,
+        }))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+/// The accounted currency's state after the given call, with balances keyed by user name, such
+/// as `{"alice": 100}`. Users without an account are left out.
+#[pyfunction]
+fn next_state(balances: HashMap<String, u64>, call: &Call) -> PyResult<BTreeMap<String, u64>> {
+    let balances = balances
+        .into_iter()
+        .map(|(name, balance)| Ok((parse_user(&name)?, balance)))
+        .collect::<PyResult<_>>()?;
+    let next = AccountedCurrency::next_state(&balances, &call.0);
+    Ok(next
+        .into_iter()
+        .map(|(user, balance)| (user_name(user), balance))
+        .collect())
+}
+
+/// The ATM from chapter 1.
+#[pyclass]
+struct Atm(c1::Atm);
+
+#[pymethods]
+impl Atm {
+    #[new]
+    fn new(cash_inside: u64) -> Self {
+        Atm(c1::Atm::new(cash_inside))
+    }
+
+    /// Swipe a card whose pin is made of the keys 1 to 4, such as `"1234"`.
+    fn swipe(&mut self, pin: &str) -> PyResult<()> {
+        let pin = parse_keys(pin)?;
+        self.apply(&Action::SwipeCard(pin_hash(&pin)));
+        Ok(())
+    }
+
+    /// Press the given number keys, such as `"42"`, one after the other.
+    fn press(&mut self, keys: &str) -> PyResult<()> {
+        for key in parse_keys(keys)? {
+            self.apply(&Action::PressKey(key));
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self) {
+        self.apply(&Action::PressKey(Key::Enter));
+    }
+
+    #[getter]
+    fn cash_inside(&self) -> u64 {
+        self.0.cash_inside()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+impl Atm {
+    fn apply(&mut self, action: &Action) {
+        self.0 = c1::Atm::next_state(&self.0, action);
+    }
+}
+
+/// Parse a string of digits into keypad keys.
+fn parse_keys(digits: &str) -> PyResult<Vec<Key>> {
+    digits
+        .chars()
+        .map(|digit| match digit {
+            '1' => Ok(Key::One),
+            '2' => Ok(Key::Two),
+            '3' => Ok(Key::Three),
+            '4' => Ok(Key::Four),
+            _ => Err(PyValueError::new_err(format!(
+                "the keypad has no key `{digit}`"
+            ))),
+        })
+        .collect()
+}
+
+/// A chain of empty headers sealed with proof of work, from chapter 3. Blocks take about
+/// `difficulty` attempts to mine.
+#[pyclass]
+struct PowChain {
+    engine: Pow,
+    /// Every header from genesis to the tip.
+    headers: Vec<Header<u64>>,
+}
+
+#[pymethods]
+impl PowChain {
+    #[new]
+    fn new(difficulty: u64) -> Self {
+        PowChain {
+            engine: Pow::new(difficulty_to_threshold(difficulty.into())),
+            headers: vec![Header::new(H256::zero(), 0, H256::zero(), H256::zero(), 0)],
+        }
+    }
+
+    /// Mine a header on top of the tip, and return its hash.
+    fn mine(&mut self) -> PyResult<String> {
+        let tip = self
+            .headers
+            .last()
+            .expect("there is always a genesis header");
+        let partial = Header::new(
+            header_hash(tip),
+            tip.height() + 1,
+            H256::zero(),
+            H256::zero(),
+            (),
+        );
+        let header = self
+            .engine
+            .seal(tip.consensus_digest(), partial)
+            .ok_or_else(|| PyValueError::new_err("the engine could not seal the header"))?;
+        let hash = header_hash(&header).to_string();
+        self.headers.push(header);
+        Ok(hash)
+    }
+
+    /// The height of the tip.
+    #[getter]
+    fn height(&self) -> u64 {
+        self.headers.len() as u64 - 1
+    }
+
+    /// The hash of the header at the given height.
+    fn hash(&self, height: usize) -> Option<String> {
+        Some(header_hash(self.headers.get(height)?).to_string())
+    }
+
+    /// The nonce of the header at the given height.
+    fn nonce(&self, height: usize) -> Option<u64> {
+        Some(*self.headers.get(height)?.consensus_digest())
+    }
+
+    /// Replace the nonce of the header at the given height, which most likely breaks its seal.
+    fn set_nonce(&mut self, height: usize, nonce: u64) -> PyResult<()> {
+        let header = self.headers.get_mut(height).ok_or_else(|| {
+            PyValueError::new_err(format!("there is no header at height {height}"))
+        })?;
+        *header = Header::new(
+            header.parent(),
+            header.height(),
+            header.state_root(),
+            header.extrinsics_root(),
+            nonce,
+        );
+        Ok(())
+    }
+
+    /// Whether every header is the child of the one before it, and sealed according to the
+    /// proof of work rules.
+    fn verify(&self) -> bool {
+        let linked = self.headers.windows(2).all(|pair| {
+            pair[1].parent() == header_hash(&pair[0]) && pair[1].height() == pair[0].height() + 1
+        });
+        linked
+            && self
+                .engine
+                .verify_sub_chain(self.headers[0].consensus_digest(), &self.headers[1..])
+    }
+}
+
+/// A full client from chapter 4, running the signed currency runtime with proof of work. Every
+/// play user has a wallet that signs their calls with their dev key.
+#[pyclass(unsendable)]
+struct Client {
+    client: ChainClient,
+    wallets: BTreeMap<User, Wallet>,
+}
+
+#[pymethods]
+impl Client {
+    /// A client whose genesis state endows the given users, such as `{"alice": 100}`.
+    #[new]
+    fn new(endowments: HashMap<String, u64>) -> PyResult<Self> {
+        let endowments = endowments
+            .into_iter()
+            .map(|(name, balance)| Ok((parse_user(&name)?, balance)))
+            .collect::<PyResult<Vec<_>>>()?;
+        let wallets = ACCOUNTS
+            .iter()
+            .map(|&who| {
+                let wallet =
+                    Wallet::new(who, Keystore::dev()).expect("the dev keystore has every key");
+                (who, wallet)
+            })
+            .collect();
+        Ok(Client {
+            client: ChainClient::new(RuntimeState::genesis(&endowments)),
+            wallets,
+        })
+    }
+
+    /// Sign the given call on behalf of its origin, and send it to the pool. Returns its nonce.
+    fn submit(&mut self, call: &Call) -> PyResult<u64> {
+        let call = call.0.clone();
+        let origin = match call {
+            AccountingTransaction::Mint { minter, .. } => minter,
+            AccountingTransaction::Burn { burner, .. } => burner,
+            AccountingTransaction::Transfer { sender, .. } => sender,
+        };
+        let wallet = self
+            .wallets
+            .get_mut(&origin)
+            .expect("there is a wallet for every user");
+        let error = |e: WalletError| PyValueError::new_err(format!("{e:?}"));
+        wallet.sync(&mut self.client).map_err(error)?;
+        let extrinsic = wallet.submit(&mut self.client, call).map_err(error)?;
+        Ok(extrinsic.nonce)
+    }
+
+    /// Author a block on top of the best block, and return its hash.
+    fn author(&mut self) -> Option<String> {
+        let hash = self.client.author_and_import_automatic_block()?;
+        Some(hash.to_string())
+    }
+
+    /// Finalize the best block. Returns whether that worked.
+    fn finalize(&mut self) -> bool {
+        let best = self.client.best_block();
+        self.client.manually_finalize_block(best)
+    }
+
+    #[getter]
+    fn best_block(&self) -> String {
+        self.client.best_block().to_string()
+    }
+
+    #[getter]
+    fn finalized_block(&self) -> String {
+        self.client.finalized_block().to_string()
+    }
+
+    /// The balance of the given user at the best block.
+    fn balance(&self, user: &str) -> PyResult<u64> {
+        let user = parse_user(user)?;
+        let state = self
+            .client
+            .get_state(self.client.best_block())
+            .ok_or_else(|| PyValueError::new_err("the best block has no state"))?;
+        Ok(state.account(user).balance)
+    }
+}
+
+#[pymodule]
+fn blockchain_from_scratch(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(next_state, m)?)?;
+    m.add_class::<Call>()?;
+    m.add_class::<Atm>()?;
+    m.add_class::<PowChain>()?;
+    m.add_class::<Client>()?;
+    Ok(())
+}
@@ -0,0 +1,318 @@
+//! A scorecard for the exercises. It runs the tests of a chapter, or of a single part of one,
+//! and prints how many of them pass, along with a hint for every problem it finds: which
+//! exercise is still a `todo!()`, or which test got a wrong answer.
+//!
+//! ```text
+//! cargo run --bin check c1
+//! cargo run --bin check c2 p3
+//! cargo run --bin check progress
+//! ```
+//!
+//! `check progress` is the dashboard for the whole crate. It runs every test once, and shows how
+//! far along each part of chapters 1 to 4 is, along with the exercise to work on next.
+//!
+//! Students can use it to see how far along they are, and instructors to grade a whole class in
+//! the same way. Next to the tests that `cargo test` runs, `check` runs an extended suite, which
+//! lives in the `check` modules of the exercises and is only built with the `check` feature.
+//! Those tests probe the corners that the regular tests leave open, so that an exercise that was
+//! tuned until `cargo test` passed can still fail its grade. `check` runs everything through
+//! cargo, and only adds up the results.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+
+const USAGE: &str = "\
+usage:
+  check <chapter> [part]    for example `check c1` or `check c2 p3`
+  check progress            every part of every chapter at once";
+
+/// The number of characters in a progress bar.
+const BAR: usize = 20;
+
+/// What went wrong in a failed test.
+struct Failure {
+    /// Where the test panicked.
+    location: String,
+    /// The first line of the panic message.
+    message: String,
+}
+
+/// The result of a single test.
+struct Outcome {
+    /// The full path of the test, such as `c1_state_machine::p1_switches::sm_1_...`.
+    name: String,
+    passed: bool,
+}
+
+/// Run every library test whose name contains the given filter, the extended suite included.
+fn run_tests(filter: &str) -> Result<(Vec<Outcome>, BTreeMap<String, Failure>), String> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    let output = Command::new(cargo)
+        .args(["test", "--lib", "--features", "check", "--", filter])
+        // The hints need the panic messages, not the backtraces.
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .map_err(|e| format!("could not run cargo: {e}"))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let mut outcomes = Vec::new();
+    let mut failures = BTreeMap::new();
+    let mut lines = stdout.lines();
+    while let Some(line) = lines.next() {
+        if let Some(test) = line.strip_prefix("test ") {
+            if let Some((name, result)) = test.split_once(" ... ") {
+                outcomes.push(Outcome {
+                    name: name.to_string(),
+                    passed: result == "ok",
+                });
+            }
+        } else if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            // The panic is reported as a line with the location, followed by the message.
+            let Some(panic) = lines.by_ref().find(|line| line.contains(" panicked at ")) else {
+                continue;
+            };
+            let location = panic
+                .split(" panicked at ")
+                .nth(1)
+                .unwrap_or_default()
+                .trim_end_matches(':');
+            failures.insert(
+                name.to_string(),
+                Failure {
+                    location: location.to_string(),
+                    message: lines.next().unwrap_or_default().to_string(),
+                },
+            );
+        }
+    }
+
+    if outcomes.is_empty() && !output.status.success() {
+        // Most likely the crate does not compile. Cargo has explained why on stderr.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let errors: Vec<&str> = stderr
+            .lines()
+            .filter(|line| line.starts_with("error"))
+            .collect();
+        return Err(format!(
+            "the tests could not be built:\n{}",
+            errors.join("\n")
+        ));
+    }
+    Ok((outcomes, failures))
+}
+
+/// A hint for the given failure. Failures that have the same cause get the same hint.
+fn hint(failure: &Failure) -> String {
+    // Panic locations look like `src/c1_state_machine/p4_accounted_currency.rs:50:9`. The
+    // column is of no use to anybody.
+    let location = match failure.location.rsplit_once(':') {
+        Some((file_and_line, _column)) => file_and_line,
+        None => &failure.location,
+    };
+    if let Some(exercise) = failure.message.strip_prefix("not yet implemented: ") {
+        format!("{exercise} is not done yet, see {location}")
+    } else if failure.message.starts_with("assertion") {
+        format!("wrong answer, see the check at {location}")
+    } else {
+        format!("{} at {location}", failure.message)
+    }
+}
+
+/// Whether a module name is numbered with the given letter, such as `c2_blockchain` for `c` or
+/// `p3_consensus` for `p`.
+fn is_numbered(module: &str, letter: char) -> bool {
+    let mut chars = module.chars();
+    chars.next() == Some(letter) && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// The names of the modules in the given directory that are numbered with the given letter,
+/// whether they are directories or files.
+fn read_modules(dir: &std::path::Path, letter: char) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let module = name.strip_suffix(".rs").unwrap_or(&name).to_string();
+            is_numbered(&module, letter).then_some(module)
+        })
+        .collect()
+}
+
+/// Print how far along every exercise part of every chapter is. Supporting modules, such as the
+/// node's RPC server in chapter 4, have tests of their own, but they are not exercises.
+fn progress() -> i32 {
+    let (outcomes, failures) = match run_tests("") {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("{e}");
+            return 2;
+        }
+    };
+
+    // Parts that have no tests of their own still show up, since they are exercises all the same.
+    let mut chapters: BTreeMap<String, BTreeMap<String, Vec<Outcome>>> = BTreeMap::new();
+    let src = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    for chapter in read_modules(&src, 'c') {
+        let parts = chapters.entry(chapter.clone()).or_default();
+        for part in read_modules(&src.join(&chapter), 'p') {
+            parts.insert(part, Vec::new());
+        }
+    }
+    for outcome in outcomes {
+        let mut segments = outcome.name.split("::");
+        let (Some(chapter), Some(part)) = (segments.next(), segments.next()) else {
+            continue;
+        };
+        if is_numbered(chapter, 'c') && is_numbered(part, 'p') {
+            let (chapter, part) = (chapter.to_string(), part.to_string());
+            chapters
+                .entry(chapter)
+                .or_default()
+                .entry(part)
+                .or_default()
+                .push(outcome);
+        }
+    }
+
+    let width = chapters
+        .values()
+        .flat_map(|parts| parts.keys().map(String::len))
+        .max()
+        .unwrap_or(0);
+    let mut next_up = None;
+    for (chapter, parts) in &chapters {
+        let tested = parts.values().filter(|outcomes| !outcomes.is_empty());
+        let done = tested
+            .clone()
+            .filter(|outcomes| outcomes.iter().all(|outcome| outcome.passed))
+            .count();
+        println!("{chapter}  {done}/{} tested parts done", tested.count());
+        for (part, outcomes) in parts {
+            if outcomes.is_empty() {
+                println!("  {part:width$}  no tests");
+                continue;
+            }
+            let passed = outcomes.iter().filter(|outcome| outcome.passed).count();
+            let filled = passed * BAR / outcomes.len();
+            let bar = format!("{}{}", "#".repeat(filled), ".".repeat(BAR - filled));
+            let mark = if passed == outcomes.len() { "ok" } else { "" };
+            let line = format!(
+                "  {part:width$}  [{bar}] {passed:>3}/{:<3} {mark}",
+                outcomes.len()
+            );
+            println!("{}", line.trim_end());
+
+            if next_up.is_none() {
+                next_up = outcomes
+                    .iter()
+                    .find(|outcome| !outcome.passed)
+                    .map(|outcome| match failures.get(&outcome.name) {
+                        Some(failure) => format!("{chapter} {part}: {}", hint(failure)),
+                        None => format!("{chapter} {part}: {} failed", outcome.name),
+                    });
+            }
+        }
+        println!();
+    }
+
+    match next_up {
+        Some(next_up) => {
+            println!("next up: {next_up}");
+            1
+        }
+        None => {
+            println!("every exercise passes, well done!");
+            0
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (chapter, part) = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["progress"] => std::process::exit(progress()),
+        [chapter] => (chapter.to_string(), None),
+        [chapter, part] => (chapter.to_string(), Some(part.to_string())),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    // Test paths start with the chapter module, such as `c2_blockchain`, followed by the part,
+    // such as `p3_consensus`. Users only type the short prefixes.
+    let chapter_prefix = format!("{chapter}_");
+    let (outcomes, failures) = match run_tests(&chapter_prefix) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let mut parts: BTreeMap<String, Vec<Outcome>> = BTreeMap::new();
+    for outcome in outcomes {
+        let mut segments = outcome.name.split("::");
+        if !segments
+            .next()
+            .is_some_and(|m| m.starts_with(&chapter_prefix))
+        {
+            continue;
+        }
+        let Some(name) = segments.next() else {
+            continue;
+        };
+        if part
+            .as_ref()
+            .is_some_and(|part| !name.starts_with(&format!("{part}_")))
+        {
+            continue;
+        }
+        parts.entry(name.to_string()).or_default().push(outcome);
+    }
+    if parts.is_empty() {
+        eprintln!("there are no tests for {}", args.join(" "));
+        std::process::exit(2);
+    }
+
+    let width = parts.keys().map(String::len).max().unwrap_or(0);
+    let (mut passed, mut total) = (0, 0);
+    for (name, outcomes) in &parts {
+        let part_passed = outcomes.iter().filter(|outcome| outcome.passed).count();
+        let mark = if part_passed == outcomes.len() {
+            "ok"
+        } else {
+            ""
+        };
+        let line = format!(
+            "{name:width$}  {part_passed:>3}/{:<3} {mark}",
+            outcomes.len()
+        );
+        println!("{}", line.trim_end());
+
+        let mut hints: BTreeMap<String, usize> = BTreeMap::new();
+        for outcome in outcomes.iter().filter(|outcome| !outcome.passed) {
+            let hint = failures
+                .get(&outcome.name)
+                .map_or_else(|| "failed without a panic message".into(), hint);
+            *hints.entry(hint).or_default() += 1;
+        }
+        for (hint, count) in hints {
+            let tests = if count == 1 { "test" } else { "tests" };
+            println!("    {count} {tests}: {hint}");
+        }
+
+        passed += part_passed;
+        total += outcomes.len();
+    }
+    println!("\nscore: {passed}/{total}");
+
+    if passed < total {
+        std::process::exit(1);
+    }
+}
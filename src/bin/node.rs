@@ -0,0 +1,526 @@
+//! The node binary. It runs a full client for the signed currency runtime and exposes it over
+//! JSON-RPC, and it doubles as a command line wallet that talks to a running node.
+//!
+//! A node stores every block its client accepts in the configured storage backend, and
+//! restores its chain from there when it is started again.
+//!
+//! The node runs the development chain, so it also serves a faucet that pays out of Alice's
+//! account. Fund a new account with `node wallet fund`.
+//!
+//! Stop a running node with Ctrl-C or SIGTERM. It finishes what it is doing, disconnects from
+//! the network, and saves its chain and its transaction pool, so that nothing is lost on
+//! restart.
+//!
+//! Run a node with `--trace FILE` to record everything that happens to its client, and hand the
+//! file to `node replay` to make a fresh client go through the exact same events again.
+//!
+//! A running node logs what it is doing to stderr. How much it logs is controlled by the
+//! `RUST_LOG` environment variable, which defaults to `info`. For example,
+//! `RUST_LOG=diy_blockchain::c4_client::network=debug` shows every request the networking code
+//! makes, and `RUST_LOG=trace` even shows the consensus engine checking every seal.
+//!
+//! Remember that the client is built by _you_ throughout chapter 4. Until you have completed
+//! those exercises, running a node will stop at the first unimplemented client method.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use diy_blockchain::c1_state_machine::User;
+use diy_blockchain::c3_consensus::Pow;
+use diy_blockchain::c4_client::authoring::{authoring_task, AuthorBlocks, Signed};
+use diy_blockchain::c4_client::backend::{self, ChainDb};
+use diy_blockchain::c4_client::config::NodeConfig;
+use diy_blockchain::c4_client::faucet::{Faucet, FaucetNode};
+use diy_blockchain::c4_client::issuance::Issuing;
+use diy_blockchain::c4_client::keystore::{self, Keystore};
+use diy_blockchain::c4_client::metrics::{self, Metrics};
+use diy_blockchain::c4_client::network::discovery::{self, Beacon, Discovery};
+use diy_blockchain::c4_client::network::identity;
+use diy_blockchain::c4_client::network::ChainHeader;
+use diy_blockchain::c4_client::persist;
+use diy_blockchain::c4_client::recorder::{self, Recorder, ReplayError};
+use diy_blockchain::c4_client::rpc::{self, NodeApi, RpcClient, RpcPolicy};
+use diy_blockchain::c4_client::runtime::{Runtime, RuntimeState, SignedExtrinsic};
+use diy_blockchain::c4_client::telemetry::{Telemetry, TelemetryEvent};
+use diy_blockchain::c4_client::timeline::Timeline;
+use diy_blockchain::c4_client::wallet::Wallet;
+use diy_blockchain::c4_client::weight::{WeightLimit, MAX_BLOCK_WEIGHT};
+use diy_blockchain::c4_client::{Block, FullClient, LongestChain, SimplePool};
+use diy_blockchain::clock::{Clock, SystemClock};
+use diy_blockchain::crypto::address::Address;
+use diy_blockchain::crypto::mnemonic;
+use diy_blockchain::crypto::sig::Keypair;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "\
+usage:
+  node run [--config FILE] [--rpc ADDR] [--metrics ADDR] [--trace FILE]
+  node replay <trace> [--config FILE]
+  node chain graph [--config FILE] [--rpc ADDR]
+  node chain timeline [--config FILE] [--rpc ADDR]
+  node wallet balance <account> [--config FILE] [--rpc ADDR]
+  node wallet transfer <from> <to> <amount> [--config FILE] [--rpc ADDR]
+  node wallet fund <account> [--config FILE] [--rpc ADDR]
+  node key generate [--words 12|24]
+  node key inspect <mnemonic>[//path]
+  node key insert <mnemonic>[//path] [--config FILE]
+
+Accounts are given by address, or by the name of a play user such as alice.
+Every setting in the config file can also be overridden with a NODE_* environment variable.";
+
+type NodeClient = FullClient<Signed<Pow>, Runtime, LongestChain, SimplePool<Runtime>>;
+type NodeRecorder = Recorder<Signed<Pow>, Runtime, LongestChain, SimplePool<Runtime>>;
+
+/// How much the faucet of a development node gives every account that asks.
+const FAUCET_DRIP: u64 = 1_000;
+/// How many blocks an account has to wait before the faucet funds it again.
+const FAUCET_COOLDOWN: u64 = 10;
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(args) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run(mut args: Vec<String>) -> Result<(), String> {
+    let config_path = take_option(&mut args, "--config");
+    let mut config =
+        NodeConfig::load(config_path.as_deref().map(Path::new)).map_err(|e| format!("{e:?}"))?;
+
+    // Command line flags take precedence over both the config file and the environment.
+    if let Some(addr) = take_option(&mut args, "--rpc") {
+        config.rpc.addr = addr
+            .parse()
+            .map_err(|e| format!("invalid rpc address: {e}"))?;
+    }
+    if let Some(addr) = take_option(&mut args, "--metrics") {
+        config.rpc.metrics_addr = addr
+            .parse()
+            .map_err(|e| format!("invalid metrics address: {e}"))?;
+    }
+    let rpc_addr = config.rpc.addr;
+    let words = take_option(&mut args, "--words");
+    let trace = take_option(&mut args, "--trace");
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["run"] => run_node(&config, trace.as_deref().map(Path::new)),
+        ["replay", trace] => replay(&config, Path::new(trace)),
+        ["wallet", "balance", account] => {
+            let mut node = RpcClient::new(rpc_addr);
+            let mut wallet = Wallet::new(parse_account(account)?, Keystore::dev())
+                .map_err(|e| format!("{e:?}"))?;
+            wallet.sync(&mut node).map_err(|e| format!("{e:?}"))?;
+            println!(
+                "{:?} ({}): balance {}, nonce {}",
+                wallet.account(),
+                keystore::dev_address(wallet.account()),
+                wallet.balance(),
+                wallet.nonce()
+            );
+            Ok(())
+        }
+        ["wallet", "transfer", from, to, amount] => {
+            let receiver = parse_account(to)?;
+            let amount: u64 = amount.parse().map_err(|e| format!("invalid amount: {e}"))?;
+            let mut node = RpcClient::new(rpc_addr);
+            let mut wallet =
+                Wallet::new(parse_account(from)?, Keystore::dev()).map_err(|e| format!("{e:?}"))?;
+            wallet.sync(&mut node).map_err(|e| format!("{e:?}"))?;
+            let extrinsic = wallet
+                .transfer(&mut node, receiver, amount)
+                .map_err(|e| format!("{e:?}"))?;
+            println!("submitted transfer with nonce {}", extrinsic.nonce);
+            Ok(())
+        }
+        ["wallet", "fund", account] => {
+            let mut node = RpcClient::new(rpc_addr);
+            let mut wallet = Wallet::new(parse_account(account)?, Keystore::dev())
+                .map_err(|e| format!("{e:?}"))?;
+            let amount = wallet
+                .request_funds(&mut node)
+                .map_err(|e| format!("{e:?}"))?;
+            println!(
+                "the faucet is sending {amount} to {:?} ({})",
+                wallet.account(),
+                keystore::dev_address(wallet.account())
+            );
+            Ok(())
+        }
+        ["chain", "graph"] => {
+            // Meant to be piped into Graphviz, as in `node chain graph | dot -Tsvg > forks.svg`.
+            let tree = RpcClient::new(rpc_addr)
+                .block_tree()
+                .map_err(|e| format!("{e:?}"))?;
+            print!("{}", tree.to_dot());
+            Ok(())
+        }
+        ["chain", "timeline"] => {
+            let tree = RpcClient::new(rpc_addr)
+                .block_tree()
+                .map_err(|e| format!("{e:?}"))?;
+            print!("{}", Timeline::from_tree(&tree).to_svg());
+            Ok(())
+        }
+        ["key", "generate"] => {
+            let words = match words {
+                Some(words) => words
+                    .parse()
+                    .map_err(|e| format!("invalid word count: {e}"))?,
+                None => 12,
+            };
+            let phrase = mnemonic::generate(words).map_err(|e| e.to_string())?;
+            println!("mnemonic:   {}", phrase.as_str());
+            print_key(&mnemonic::keypair(&phrase).map_err(|e| e.to_string())?);
+            println!(
+                "Write the mnemonic down and keep it safe. It is the only way to restore the key."
+            );
+            Ok(())
+        }
+        ["key", "inspect", phrase] => {
+            print_key(&mnemonic::from_uri(phrase).map_err(|e| e.to_string())?);
+            Ok(())
+        }
+        ["key", "insert", phrase] => {
+            let keypair =
+                keystore::insert_key_file(&config.data_dir, phrase).map_err(|e| e.to_string())?;
+            print_key(&keypair);
+            println!(
+                "inserted into {}",
+                config.data_dir.join(keystore::KEYSTORE_DIR).display()
+            );
+            Ok(())
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+/// Parse an account given on the command line, either by address or by the name of a play user.
+fn parse_account(s: &str) -> Result<User, String> {
+    if let Ok(user) = s.parse() {
+        return Ok(user);
+    }
+    let address: Address = s
+        .parse()
+        .map_err(|e| format!("{s:?} is neither a user nor an address: {e}"))?;
+    keystore::dev_user(&address).ok_or_else(|| format!("no account with address {address}"))
+}
+
+fn print_key(keypair: &Keypair) {
+    let public = keypair.public();
+    println!("public key: {public}");
+    println!("address:    {}", Address::from_public(&public));
+}
+
+/// Remove `name VALUE` from the arguments and return the value, if present.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let position = args.iter().position(|arg| arg == name)?;
+    args.remove(position);
+    (position < args.len()).then(|| args.remove(position))
+}
+
+/// The genesis state of the development chain, with every play user endowed as configured.
+fn genesis(config: &NodeConfig) -> RuntimeState {
+    let endowment = config.consensus.dev_endowment;
+    RuntimeState::genesis(&[
+        (User::Alice, endowment),
+        (User::Bob, endowment),
+        (User::Charlie, endowment),
+    ])
+}
+
+/// A fresh client for the development chain, that signs the blocks it authors with the given
+/// key. Without a key, it only imports blocks.
+fn new_client(config: &NodeConfig, key: Option<Keypair>) -> NodeClient {
+    let executive = Issuing::new(config.issuance, WeightLimit(MAX_BLOCK_WEIGHT));
+    let client = NodeClient::new(genesis(config))
+        .with_executive(executive)
+        .with_state_pruning(config.storage.mode, config.storage.keep_states);
+    match key {
+        Some(key) => client.with_consensus_engine(Signed::new(Pow::default(), key)),
+        None => client,
+    }
+}
+
+/// Replay a trace recorded by `node run --trace` into a fresh client.
+fn replay(config: &NodeConfig, trace: &Path) -> Result<(), String> {
+    let file = File::open(trace).map_err(|e| format!("failed to open {}: {e}", trace.display()))?;
+    let mut client = new_client(config, None);
+    match recorder::replay(&mut client, BufReader::new(file)) {
+        Ok(replayed) => {
+            println!("replayed {replayed} events, the client decided the same way every time");
+            Ok(())
+        }
+        Err(ReplayError::Io(e)) => Err(format!("failed to read {}: {e}", trace.display())),
+        Err(ReplayError::Malformed { line, error }) => {
+            Err(format!("line {line} of the trace is malformed: {error}"))
+        }
+        Err(ReplayError::Diverged {
+            line,
+            expected,
+            actual,
+        }) => Err(format!(
+            "the replay diverged at line {line}: expected {expected}, but got {actual}"
+        )),
+    }
+}
+
+fn run_node(config: &NodeConfig, trace: Option<&Path>) -> Result<(), String> {
+    // The first key in the keystore signs the blocks we author. A development node that has
+    // none authors as Alice, like Substrate's `--dev` nodes do.
+    let authoring_keys = keystore::load_key_files(&config.data_dir).map_err(|e| e.to_string())?;
+    for key in &authoring_keys {
+        tracing::info!(address = %Address::from_public(&key.public()), "authoring key");
+    }
+    let authoring_key = match authoring_keys.into_iter().next() {
+        Some(key) => Some(key),
+        None if config.consensus.author => {
+            tracing::warn!("no key in the keystore, authoring with Alice's development key");
+            Some(keystore::dev_keypair(User::Alice))
+        }
+        None => None,
+    };
+    let client = new_client(config, authoring_key);
+    let genesis_hash = client.best_block();
+    let client = match trace {
+        Some(path) => {
+            tracing::info!(path = %path.display(), "recording a trace");
+            Recorder::create(client, path).map_err(|e| e.to_string())?
+        }
+        None => Recorder::new(client, None),
+    };
+    let metrics = Arc::new(Metrics::new());
+    let mut client = client.with_metrics(metrics.clone());
+
+    // Pick up where we left off, by importing every block we stored before we were stopped.
+    // The trace records these imports like any other, so it still replays from genesis.
+    let mut chain_db = ChainDb::new(
+        backend::open(config.storage.backend, &config.data_dir).map_err(|e| format!("{e:?}"))?,
+    )
+    .with_state_pruning(config.storage.mode, config.storage.keep_states);
+    let restored = chain_db
+        .restore(&mut client)
+        .map_err(|e| format!("failed to restore the chain: {e:?}"))?;
+    if restored > 0 {
+        let best = client.client().best_block();
+        tracing::info!(blocks = restored, %best, "restored the chain");
+    }
+    let telemetry = match &config.telemetry.url {
+        Some(url) => {
+            tracing::info!(%url, node = %config.telemetry.node_name, "sending telemetry");
+            Telemetry::connect(
+                url,
+                &config.telemetry.node_name,
+                config.telemetry.secret.as_deref(),
+            )?
+        }
+        None => Telemetry::disabled(),
+    };
+
+    let mut client = client
+        .with_chain_db(chain_db)
+        .with_telemetry(telemetry.clone());
+
+    // Resubmit whatever was still waiting in the pool when the node was last stopped.
+    let pending: Vec<SignedExtrinsic> =
+        persist::load_pool(&config.data_dir).map_err(|e| e.to_string())?;
+    if !pending.is_empty() {
+        tracing::info!(
+            transactions = pending.len(),
+            "restoring pending transactions"
+        );
+    }
+    for extrinsic in pending {
+        client.submit_transaction(extrinsic);
+    }
+    let node_key = identity::load_or_generate(&config.data_dir).map_err(|e| e.to_string())?;
+    tracing::info!(peer = %node_key.peer_id(), "node identity");
+    // We only ever run the development chain, so we serve a faucet that pays out of Alice's
+    // account, which is endowed at genesis.
+    let faucet_wallet = Wallet::new(User::Alice, Keystore::dev()).map_err(|e| format!("{e:?}"))?;
+    let client = Arc::new(Mutex::new(FaucetNode {
+        node: client,
+        faucet: Faucet::new(faucet_wallet, FAUCET_DRIP, FAUCET_COOLDOWN),
+    }));
+
+    let policy = RpcPolicy {
+        unsafe_token: config.rpc.unsafe_token.clone(),
+        rate_limit: config.rpc.rate_limit,
+    };
+    let (addr, _server) = rpc::serve_with_policy(config.rpc.addr, client.clone(), policy)
+        .map_err(|e| e.to_string())?;
+    tracing::info!(%addr, "JSON-RPC listening");
+    if !addr.ip().is_loopback() && config.rpc.unsafe_token.is_none() {
+        tracing::warn!("unsafe RPC methods are disabled, set rpc.unsafe_token to enable them");
+    }
+
+    let (addr, _metrics_server) =
+        metrics::serve(config.rpc.metrics_addr, metrics.clone()).map_err(|e| e.to_string())?;
+    tracing::info!("Prometheus metrics on http://{addr}/metrics");
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(async {
+        // We keep our own sender alive, so that a node which does not author keeps running too.
+        let (announce, mut announcements) = tokio::sync::mpsc::unbounded_channel();
+        let authoring = config.consensus.author.then(|| {
+            let block_time = config.consensus.block_time();
+            let author = Arc::new(Mutex::new(MeteredAuthor {
+                client: client.clone(),
+                metrics: metrics.clone(),
+            }));
+            tokio::spawn(authoring_task(author, block_time, announce.clone()))
+        });
+        let mut network = Vec::new();
+        if config.network.discovery {
+            let local = Beacon {
+                peer: node_key.peer_id(),
+                genesis: genesis_hash,
+                port: config.network.port,
+            };
+            match Discovery::bind(local) {
+                Ok(discovery) => network.push(tokio::spawn(discover_peers(
+                    discovery,
+                    metrics.clone(),
+                    telemetry.clone(),
+                ))),
+                Err(e) => tracing::warn!("local peer discovery disabled: {e}"),
+            }
+        }
+
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                Some(block) = announcements.recv() => {
+                    // We have no peers to tell yet, so we just let the operator know.
+                    let height = block.header().height();
+                    let hash = block.header().hash();
+                    let pool_size = client
+                        .lock()
+                        .expect("client mutex poisoned")
+                        .node
+                        .client()
+                        .pool_size();
+                    metrics.block_height.set(height);
+                    metrics.pool_size.set(pool_size as u64);
+                    tracing::info!(%hash, height, "authored block");
+                    telemetry.emit(TelemetryEvent::BlockAuthored { hash, height });
+                }
+            }
+        }
+
+        // Stop authoring. The authoring task never holds the client lock across an await, so
+        // aborting it can not interrupt a block import halfway through.
+        tracing::info!("shutting down");
+        if let Some(task) = authoring {
+            task.abort();
+            let _ = task.await;
+        }
+        // Stop talking to the network, so that peers stop hearing from us before we are gone.
+        for task in network {
+            task.abort();
+            let _ = task.await;
+        }
+    });
+
+    // Taking the lock waits for any RPC call that is still in flight. After that, nothing else
+    // touches the client, so the chain and the pool can be saved safely.
+    let mut node = client.lock().expect("client mutex poisoned");
+    let client = &mut node.node;
+    let best = client.client().best_block();
+    if let Some(chain_db) = client.chain_db() {
+        // Losing the chain is no reason to lose the pool as well, so we carry on either way.
+        match chain_db.set_best(best).and_then(|()| chain_db.flush()) {
+            Ok(()) => tracing::info!(%best, "saved the chain"),
+            Err(e) => tracing::error!("failed to save the chain: {e:?}"),
+        }
+    }
+    let pending = client.drain_pool();
+    persist::save_pool(&config.data_dir, &pending).map_err(|e| e.to_string())?;
+    tracing::info!(transactions = pending.len(), "saved pending transactions");
+    if let Some(e) = client.error() {
+        tracing::error!("the trace is incomplete, writing it failed: {e}");
+    }
+    Ok(())
+}
+
+/// Authors blocks with the node's client for the authoring task, and keeps track of how fast
+/// the node mines.
+struct MeteredAuthor {
+    client: Arc<Mutex<FaucetNode<NodeRecorder>>>,
+    metrics: Arc<Metrics>,
+}
+
+impl AuthorBlocks for MeteredAuthor {
+    type Block = Block<Signed<Pow>, Runtime>;
+
+    fn try_author_block(&mut self) -> Option<Self::Block> {
+        let start = Instant::now();
+        let block = self
+            .client
+            .lock()
+            .expect("client mutex poisoned")
+            .node
+            .try_author_block()?;
+        // Sealing tries every nonce from zero up, so the nonce tells how many hashes it took.
+        let hashes = block.header().consensus_digest().inner.saturating_add(1);
+        self.metrics.record_hashes(hashes, start.elapsed());
+        Some(block)
+    }
+}
+
+/// Keep announcing ourselves on the local network, and report the peers we find. We have no
+/// connections of our own yet, so a peer counts as connected as soon as we discover it.
+async fn discover_peers(mut discovery: Discovery, metrics: Arc<Metrics>, telemetry: Telemetry) {
+    let mut ticker = tokio::time::interval(discovery::ANNOUNCE_INTERVAL);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = discovery.announce() {
+            tracing::warn!("failed to announce ourselves on the local network: {e}");
+        }
+        match discovery.poll(SystemClock.now()) {
+            Ok(peers) => {
+                for addr in peers {
+                    tracing::info!(%addr, "discovered peer");
+                    let discovered = discovery.peers().peers().find(|(_, at)| *at == addr);
+                    if let Some((peer, _)) = discovered {
+                        telemetry.emit(TelemetryEvent::PeerConnected { peer });
+                    }
+                }
+                metrics
+                    .peer_count
+                    .set(discovery.peers().peers().count() as u64);
+            }
+            Err(e) => tracing::warn!("failed to listen for local peers: {e}"),
+        }
+    }
+}
+
+/// Wait until the operator asks the node to stop, either with Ctrl-C or with SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -0,0 +1,637 @@
+//! An interactive simulator for learners. Pick one of the state machines from chapter 1, or the
+//! full client from chapter 4, issue transitions one line at a time, and watch the state, the
+//! fork tree and the transaction pool change after every step.
+//!
+//! `tui debug <block>` steps through a block that a node has stored, one extrinsic at a time,
+//! and shows the receipts and the accounts along the way. It reads the node's config from the
+//! `NODE_*` environment variables, just like the node does, to find the data directory.
+//!
+//! The screen is drawn with ratatui: the state panels on the left, the history of commands and
+//! the help on the right, and the command line at the bottom. Type a command and press enter to
+//! issue it. Escape, or `quit`, leaves the simulator.
+//!
+//! Whatever you have not implemented yet shows up as "not yet implemented" on the screen,
+//! instead of taking the simulator down. Finish the exercise and run the simulator again. The
+//! full client can only be started once you have written its constructor in chapter 4.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+
+use diy_blockchain::c1_state_machine::{
+    AccountedCurrency, AccountingTransaction, Action, Atm, Key, StateMachine, User,
+};
+use diy_blockchain::c3_consensus::Pow;
+use diy_blockchain::c4_client::authoring::Signed;
+use diy_blockchain::c4_client::backend::{self, ChainDb};
+use diy_blockchain::c4_client::config::NodeConfig;
+use diy_blockchain::c4_client::debugger::{self, BlockDebugger};
+use diy_blockchain::c4_client::keystore::Keystore;
+use diy_blockchain::c4_client::runtime::{Runtime, RuntimeState, ACCOUNTS};
+use diy_blockchain::c4_client::wallet::Wallet;
+use diy_blockchain::c4_client::{Block, FullClient, ImportBlock, LongestChain, SimplePool};
+use diy_blockchain::hashing::{pin_hash, H256};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Text};
+use ratatui::widgets::{Block as Panel, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+const USAGE: &str = "\
+usage:
+  tui atm        an ATM with 100 in cash
+  tui currency   the accounted currency
+  tui chain      a full client for the signed currency runtime
+  tui debug <block>
+                 step through a block that the node stored in its data directory";
+
+/// The number of past commands shown below the state.
+const HISTORY: usize = 8;
+
+type ChainClient = FullClient<Pow, Runtime, LongestChain, SimplePool<Runtime>>;
+
+/// Something that can be driven one command at a time.
+trait Simulation {
+    /// The name shown at the top of the screen.
+    fn title(&self) -> String;
+
+    /// The commands this simulation understands, one per line.
+    fn help(&self) -> &'static str;
+
+    /// Carry out a single command, given as its words. Returns what happened, to show the user.
+    fn apply(&mut self, words: &[&str]) -> Result<String, String>;
+
+    /// The current state, as a list of titled panels.
+    fn panels(&self) -> Vec<(&'static str, Vec<String>)>;
+}
+
+struct AtmSimulation {
+    atm: Atm,
+}
+
+impl Simulation for AtmSimulation {
+    fn title(&self) -> String {
+        "ATM".into()
+    }
+
+    fn help(&self) -> &'static str {
+        "swipe <pin>           swipe a card whose pin is made of the keys 1 to 4, e.g. `swipe 1234`
+press <keys> [enter]  press keys on the keypad, e.g. `press 42 enter`"
+    }
+
+    fn apply(&mut self, words: &[&str]) -> Result<String, String> {
+        let actions = match words {
+            ["swipe", pin] => vec![Action::SwipeCard(pin_hash(&parse_keys(pin)?))],
+            ["press", keys @ ..] if !keys.is_empty() => {
+                let mut actions = Vec::new();
+                for keys in keys {
+                    if *keys == "enter" {
+                        actions.push(Action::PressKey(Key::Enter));
+                    } else {
+                        actions.extend(parse_keys(keys)?.into_iter().map(Action::PressKey));
+                    }
+                }
+                actions
+            }
+            _ => return Err("unknown command".into()),
+        };
+        let count = actions.len();
+        self.atm = actions.iter().fold(self.atm.clone(), |atm, action| {
+            Atm::next_state(&atm, action)
+        });
+        Ok(match count {
+            1 => "applied 1 transition".into(),
+            count => format!("applied {count} transitions"),
+        })
+    }
+
+    fn panels(&self) -> Vec<(&'static str, Vec<String>)> {
+        vec![("State", debug_lines(&self.atm))]
+    }
+}
+
+/// Parse a string of digits into keypad keys.
+fn parse_keys(digits: &str) -> Result<Vec<Key>, String> {
+    digits
+        .chars()
+        .map(|digit| match digit {
+            '1' => Ok(Key::One),
+            '2' => Ok(Key::Two),
+            '3' => Ok(Key::Three),
+            '4' => Ok(Key::Four),
+            _ => Err(format!("the keypad has no key `{digit}`")),
+        })
+        .collect()
+}
+
+struct CurrencySimulation {
+    balances: HashMap<User, u64>,
+}
+
+impl Simulation for CurrencySimulation {
+    fn title(&self) -> String {
+        "Accounted Currency".into()
+    }
+
+    fn help(&self) -> &'static str {
+        CALL_HELP
+    }
+
+    fn apply(&mut self, words: &[&str]) -> Result<String, String> {
+        let call = parse_call(words)?;
+        self.balances = AccountedCurrency::next_state(&self.balances, &call);
+        Ok(format!("{call:?}"))
+    }
+
+    fn panels(&self) -> Vec<(&'static str, Vec<String>)> {
+        let balances = ACCOUNTS
+            .iter()
+            .map(|who| match self.balances.get(who) {
+                Some(balance) => format!("{who:?}: {balance}"),
+                None => format!("{who:?}: no account"),
+            })
+            .collect();
+        vec![("Balances", balances)]
+    }
+}
+
+const CALL_HELP: &str = "\
+mint <user> <amount>
+burn <user> <amount>
+transfer <from> <to> <amount>";
+
+/// Parse a currency transaction, such as `transfer alice bob 5`.
+fn parse_call(words: &[&str]) -> Result<AccountingTransaction, String> {
+    let amount = |amount: &str| {
+        amount
+            .parse::<u64>()
+            .map_err(|e| format!("invalid amount: {e}"))
+    };
+    match words {
+        ["mint", minter, value] => Ok(AccountingTransaction::Mint {
+            minter: minter.parse()?,
+            amount: amount(value)?,
+        }),
+        ["burn", burner, value] => Ok(AccountingTransaction::Burn {
+            burner: burner.parse()?,
+            amount: amount(value)?,
+        }),
+        ["transfer", sender, receiver, value] => Ok(AccountingTransaction::Transfer {
+            sender: sender.parse()?,
+            receiver: receiver.parse()?,
+            amount: amount(value)?,
+        }),
+        _ => Err("unknown command".into()),
+    }
+}
+
+/// The account on whose behalf a call is made, which must also sign it.
+fn origin(call: &AccountingTransaction) -> User {
+    match call {
+        AccountingTransaction::Mint { minter, .. } => *minter,
+        AccountingTransaction::Burn { burner, .. } => *burner,
+        AccountingTransaction::Transfer { sender, .. } => *sender,
+    }
+}
+
+struct ChainSimulation {
+    client: ChainClient,
+    /// One wallet per play user, so that nonces keep counting up while extrinsics wait in the
+    /// pool.
+    wallets: BTreeMap<User, Wallet>,
+}
+
+impl ChainSimulation {
+    fn new() -> Self {
+        let genesis = RuntimeState::genesis(&ACCOUNTS.map(|who| (who, 1_000)));
+        let wallets = ACCOUNTS
+            .iter()
+            .map(|&who| {
+                let wallet =
+                    Wallet::new(who, Keystore::dev()).expect("the dev keystore has every key");
+                (who, wallet)
+            })
+            .collect();
+        ChainSimulation {
+            client: ChainClient::new(genesis),
+            wallets,
+        }
+    }
+}
+
+impl Simulation for ChainSimulation {
+    fn title(&self) -> String {
+        "Full client".into()
+    }
+
+    fn help(&self) -> &'static str {
+        "mint, burn and transfer as in the currency, signed and sent to the pool
+author                 author a block on top of the best block
+finalize               finalize the best block"
+    }
+
+    fn apply(&mut self, words: &[&str]) -> Result<String, String> {
+        match words {
+            ["author"] => match self.client.author_and_import_automatic_block() {
+                Some(hash) => Ok(format!("authored {hash}")),
+                None => Err("could not author a block".into()),
+            },
+            ["finalize"] => {
+                let best = self.client.best_block();
+                if self.client.manually_finalize_block(best) {
+                    Ok(format!("finalized {best}"))
+                } else {
+                    Err(format!("could not finalize {best}"))
+                }
+            }
+            _ => {
+                let call = parse_call(words)?;
+                let wallet = self
+                    .wallets
+                    .get_mut(&origin(&call))
+                    .expect("there is a wallet for every user");
+                wallet
+                    .sync(&mut self.client)
+                    .map_err(|e| format!("{e:?}"))?;
+                let extrinsic = wallet
+                    .submit(&mut self.client, call)
+                    .map_err(|e| format!("{e:?}"))?;
+                Ok(format!(
+                    "submitted {:?} with nonce {}",
+                    extrinsic.call, extrinsic.nonce
+                ))
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<(&'static str, Vec<String>)> {
+        let client = &self.client;
+        let best = client.best_block();
+        let chain = vec![
+            format!("best:      {best}"),
+            format!("finalized: {}", client.finalized_block()),
+        ];
+
+        let accounts = match client.get_state(best) {
+            Some(state) => ACCOUNTS
+                .iter()
+                .map(|&who| {
+                    let info = state.account(who);
+                    format!("{who:?}: {} (nonce {})", info.balance, info.nonce)
+                })
+                .collect(),
+            None => vec!["the best block has no state".into()],
+        };
+
+        let pool = vec![format!("{} waiting", client.pool_size())];
+
+        let forks = match client.forks() {
+            Some(forks) => forks
+                .iter()
+                .map(|fork| {
+                    format!(
+                        "{} #{} {} blocks after {}{}",
+                        fork.head,
+                        fork.height,
+                        fork.length,
+                        fork.fork_point,
+                        if fork.is_best { " (best)" } else { "" }
+                    )
+                })
+                .collect(),
+            None => vec!["the block tree is inconsistent".into()],
+        };
+
+        vec![
+            ("Chain", chain),
+            ("Accounts at the best block", accounts),
+            ("Pool", pool),
+            ("Forks", forks),
+        ]
+    }
+}
+
+struct DebugSimulation {
+    debugger: BlockDebugger<Runtime>,
+    hash: H256,
+    height: u64,
+}
+
+impl DebugSimulation {
+    /// Load the given block, and the state of its parent, from the node's data directory. The
+    /// node does not store the genesis block, so the children of genesis start from the genesis
+    /// state of the config.
+    fn new(hash: &str) -> Result<Self, String> {
+        let hash: H256 = hash.parse()?;
+        let config = NodeConfig::load(None).map_err(|e| format!("{e:?}"))?;
+        let chain_db = ChainDb::new(
+            backend::open(config.storage.backend, &config.data_dir)
+                .map_err(|e| format!("{e:?}"))?,
+        );
+        let block: Block<Signed<Pow>, Runtime> = chain_db
+            .block(hash)
+            .map_err(|e| format!("{e:?}"))?
+            .ok_or_else(|| {
+                format!(
+                    "block {hash} is not stored in {}",
+                    config.data_dir.display()
+                )
+            })?;
+        let header = block.header();
+        let parent_state = match chain_db
+            .state(header.parent())
+            .map_err(|e| format!("{e:?}"))?
+        {
+            Some(state) => state,
+            None if header.height() == 1 => {
+                RuntimeState::genesis(&ACCOUNTS.map(|who| (who, config.consensus.dev_endowment)))
+            }
+            None => {
+                return Err(format!(
+                    "the state of parent {} was pruned",
+                    header.parent()
+                ))
+            }
+        };
+        Ok(DebugSimulation {
+            debugger: BlockDebugger::for_block(parent_state, &block),
+            hash,
+            height: header.height(),
+        })
+    }
+}
+
+impl Simulation for DebugSimulation {
+    fn title(&self) -> String {
+        format!("Block #{} {}", self.height, self.hash)
+    }
+
+    fn help(&self) -> &'static str {
+        "step                   execute the next extrinsic
+back                   undo the last extrinsic
+run                    execute every remaining extrinsic"
+    }
+
+    fn apply(&mut self, words: &[&str]) -> Result<String, String> {
+        match words {
+            ["step"] => match self.debugger.step() {
+                Some(receipt) if receipt.applied => Ok(format!("applied #{}", receipt.index)),
+                Some(receipt) => Ok(format!("#{} changed nothing", receipt.index)),
+                None => Err("every extrinsic has been executed".into()),
+            },
+            ["back"] => match self.debugger.back() {
+                true => Ok(format!("back at #{}", self.debugger.position())),
+                false => Err("nothing has been executed yet".into()),
+            },
+            ["run"] => Ok(format!(
+                "executed {} extrinsics",
+                self.debugger.run_to_end()
+            )),
+            _ => Err("unknown command".into()),
+        }
+    }
+
+    fn panels(&self) -> Vec<(&'static str, Vec<String>)> {
+        let debugger = &self.debugger;
+        let mut progress = vec![format!(
+            "executed {} of {} extrinsics",
+            debugger.position(),
+            debugger.extrinsics().len()
+        )];
+        match debugger.matches_header() {
+            Some(true) => progress.push("the state root matches the header".into()),
+            Some(false) => progress.push("the state root does NOT match the header".into()),
+            None => {}
+        }
+
+        let next = match debugger.next_extrinsic() {
+            Some(extrinsic) => vec![format!(
+                "{:?} signed by {:?} with nonce {}",
+                extrinsic.call, extrinsic.signer, extrinsic.nonce
+            )],
+            None => vec!["none".into()],
+        };
+
+        let receipts = debugger
+            .receipts()
+            .iter()
+            .map(|receipt| {
+                format!(
+                    "#{} {} {} state root {}",
+                    receipt.index,
+                    receipt.extrinsic,
+                    if receipt.applied {
+                        "applied"
+                    } else {
+                        "changed nothing"
+                    },
+                    receipt.state_root_after
+                )
+            })
+            .collect();
+
+        let state = debugger.state();
+        let accounts = ACCOUNTS
+            .iter()
+            .map(|&who| {
+                let info = state.account(who);
+                format!("{who:?}: {} (nonce {})", info.balance, info.nonce)
+            })
+            .collect();
+        let changes = debugger::account_changes(debugger.starting_state(), state)
+            .into_iter()
+            .map(|(who, before, after)| {
+                format!(
+                    "{who:?}: {} -> {} (nonce {} -> {})",
+                    before.balance, after.balance, before.nonce, after.nonce
+                )
+            })
+            .collect();
+
+        vec![
+            ("Progress", progress),
+            ("Next extrinsic", next),
+            ("Receipts", receipts),
+            ("Accounts", accounts),
+            ("Changed since the start of the block", changes),
+        ]
+    }
+}
+
+/// Split the `Debug` output of a value into lines.
+fn debug_lines(value: &impl std::fmt::Debug) -> Vec<String> {
+    format!("{value:#?}").lines().map(String::from).collect()
+}
+
+/// Run the given closure, turning a panic, such as a `todo!()` in an unfinished exercise, into
+/// an error.
+fn catch<T>(f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "the simulation panicked".into());
+        Err(message)
+    })
+}
+
+/// Everything the screen shows besides the simulation itself.
+#[derive(Default)]
+struct Console {
+    /// Every command so far, with what came of it.
+    history: Vec<(String, Result<String, String>)>,
+    /// The command being typed.
+    input: String,
+}
+
+impl Console {
+    /// Issue the command that was typed. Returns false if it was the one to quit.
+    fn submit(&mut self, simulation: &mut dyn Simulation) -> bool {
+        let line = std::mem::take(&mut self.input);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let outcome = match words[..] {
+            [] => return true,
+            ["quit"] | ["exit"] => return false,
+            ["help"] => Ok(simulation.help().into()),
+            _ => catch(|| simulation.apply(&words)),
+        };
+        self.history.push((line.clone(), outcome));
+        true
+    }
+}
+
+fn draw(frame: &mut Frame, simulation: &dyn Simulation, console: &Console) {
+    let [title, main, input] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(3),
+    ])
+    .areas(frame.area());
+    let [state, side] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+
+    frame.render_widget(Line::from(simulation.title()).bold().centered(), title);
+
+    let panels = catch(|| Ok(simulation.panels()))
+        .unwrap_or_else(|e| vec![("State", vec![format!("The state can not be shown: {e}")])]);
+    draw_panels(frame, state, panels);
+
+    let help_lines = simulation.help().lines().count() as u16 + 1;
+    let [history, help] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(help_lines + 2)]).areas(side);
+    let mut lines = Vec::new();
+    for (command, outcome) in console.history.iter().rev().take(HISTORY).rev() {
+        lines.push(Line::from(format!("> {command}")).bold());
+        for note in outcome.as_ref().unwrap_or_else(|e| e).lines() {
+            let line = Line::from(format!("  {note}"));
+            lines.push(match outcome {
+                Ok(_) => line,
+                Err(_) => line.style(Style::new().fg(Color::Red)),
+            });
+        }
+    }
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Panel::bordered().title("History")),
+        history,
+    );
+    let help_text = format!("{}\nhelp, quit", simulation.help());
+    frame.render_widget(
+        Paragraph::new(help_text).block(Panel::bordered().title("Commands")),
+        help,
+    );
+
+    frame.render_widget(
+        Paragraph::new(format!("> {}", console.input)).block(Panel::bordered()),
+        input,
+    );
+    let cursor = input.x + 3 + console.input.chars().count() as u16;
+    frame.set_cursor_position((cursor.min(input.right().saturating_sub(2)), input.y + 1));
+}
+
+/// Stack the panels on top of each other, each as tall as its lines. The last one takes up
+/// whatever room is left.
+fn draw_panels(frame: &mut Frame, area: Rect, panels: Vec<(&'static str, Vec<String>)>) {
+    let constraints = panels
+        .iter()
+        .map(|(_, lines)| Constraint::Length(lines.len() as u16 + 2))
+        .chain([Constraint::Min(0)]);
+    let areas = Layout::vertical(constraints).split(area);
+    for ((title, lines), area) in panels.into_iter().zip(areas.iter()) {
+        let text = Text::from_iter(lines.into_iter().map(Line::from));
+        frame.render_widget(
+            Paragraph::new(text).block(Panel::bordered().title(title)),
+            *area,
+        );
+    }
+}
+
+/// Redraw the screen and handle key presses until the user quits.
+fn run(terminal: &mut DefaultTerminal, simulation: &mut dyn Simulation) -> io::Result<()> {
+    let mut console = Console::default();
+    loop {
+        terminal.draw(|frame| draw(frame, simulation, &console))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let quit = match key.code {
+            KeyCode::Esc => true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => true,
+            KeyCode::Char(c) => {
+                console.input.push(c);
+                false
+            }
+            KeyCode::Backspace => {
+                console.input.pop();
+                false
+            }
+            KeyCode::Enter => !console.submit(simulation),
+            _ => false,
+        };
+        if quit {
+            return Ok(());
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let simulation = catch(|| {
+        let simulation: Box<dyn Simulation> =
+            match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                ["atm"] => Box::new(AtmSimulation { atm: Atm::new(100) }),
+                ["currency"] => Box::new(CurrencySimulation {
+                    balances: HashMap::new(),
+                }),
+                ["chain"] => Box::new(ChainSimulation::new()),
+                ["debug", hash] => Box::new(DebugSimulation::new(hash)?),
+                _ => return Err(USAGE.into()),
+            };
+        Ok(simulation)
+    });
+    let mut simulation = match simulation {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut terminal = ratatui::init();
+    // Panics are shown on the screen, so the default message on stderr would only be in the way.
+    // This replaces the hook that ratatui installs, which would leave the alternate screen.
+    panic::set_hook(Box::new(|_| {}));
+    let result = run(&mut terminal, simulation.as_mut());
+    ratatui::restore();
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
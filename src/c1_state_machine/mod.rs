@@ -5,8 +5,43 @@ mod p1_switches;
 mod p2_laundry_machine;
 mod p3_atm;
 mod p4_accounted_currency;
+#[cfg(feature = "std")]
 mod p5_digital_cash;
 mod p6_open_ended;
+mod p7_sealed_guess;
+mod p8_one_time_login;
+mod p9_confidential_cash;
+mod p10_stack_vm;
+mod p11_governance;
+mod p12_name_registry;
+mod p13_auction;
+mod p14_nft;
+mod p15_escrow;
+#[cfg(feature = "std")]
+mod p16_payment_channel;
+#[cfg(feature = "std")]
+mod p17_bridge;
+mod p18_sudo;
+mod p19_faucet;
+mod p20_identity;
+mod p21_dex;
+mod p22_lottery;
+mod p23_storage_rent;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/mod.rs"]
+mod solution;
+
+// We make the accounted currency publicly visible so that the client chapter can build a
+// real node runtime on top of it. The simulator binary drives it, and the ATM, too. The
+// non-fungible tokens are tracked by the rich state of chapter 2.
+pub use p3_atm::{Action, Atm, Key};
+pub use p4_accounted_currency::{AccountedCurrency, AccountingTransaction};
+pub use p14_nft::{Nft, NftState, NftTransaction, Token, TokenId};
+
+use serde::{Deserialize, Serialize};
+use alloc::{format, string::String};
+use core::str::FromStr;
 
 /// A state machine - Generic over the transition type
 pub trait StateMachine {
@@ -27,13 +62,36 @@ pub trait StateMachine {
     }
 }
 
+/// Feed the given transitions to a state machine one after the other, starting from the given
+/// state. Most exercises use this to set up the scenarios of their tests.
+#[cfg(test)]
+fn apply_all<SM: StateMachine>(state: SM::State, transitions: &[SM::Transition]) -> SM::State {
+    transitions
+        .iter()
+        .fold(state, |state, t| SM::next_state(&state, t))
+}
+
 /// A set of play users for experimenting with the multi-user state machines
-#[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
+#[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum User {
     Alice,
     Bob,
     Charlie,
 }
 
+/// Users can be parsed from their (case-insensitive) names. This is handy for command line tools.
+impl FromStr for User {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "alice" => Ok(User::Alice),
+            "bob" => Ok(User::Bob),
+            "charlie" => Ok(User::Charlie),
+            _ => Err(format!("unknown user `{s}`")),
+        }
+    }
+}
+
 //TODO Some kind of main program that allows users to interact with their state machine in a repl-like way.
 // Might require From<String> implementation for the transition type.
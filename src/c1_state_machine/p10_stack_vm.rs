@@ -0,0 +1,275 @@
+//! So far, every state machine knew all of its rules in advance. Smart contract platforms such as
+//! Ethereum let users add rules of their own: a user deploys a program, a _contract_, and anybody
+//! can then call it. The program runs on a virtual machine that is part of the state machine, and
+//! whatever the program writes to its storage becomes part of the state.
+//!
+//! Our virtual machine is a tiny stack machine. Every instruction pops its operands from the
+//! stack, and pushes its result back onto it. Contracts can keep numbers in a storage of their
+//! own, which maps numbers to numbers and outlives the call.
+//!
+//! A contract could loop forever, or simply take a very long time, and every node would have to
+//! run it. So every instruction costs _gas_, which the caller pays for from their balance. The
+//! caller sets a gas limit, and a call that runs out of gas is stopped. Just like a call that
+//! fails for any other reason, it changes nothing in the contract's storage, but the caller still
+//! pays for the gas that was used up to that point: the nodes did the work after all.
+
+use alloc::collections::BTreeMap;
+use alloc::{string::String, vec::Vec};
+
+use super::{StateMachine, User};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p10_stack_vm.rs"]
+mod solution;
+
+/// A virtual machine that runs contracts, and the accounts that pay for it.
+pub struct StackVm;
+
+/// The gas that deploying a contract costs, for each of its instructions.
+pub const DEPLOY_GAS_PER_INSTRUCTION: u64 = 10;
+
+/// A single instruction for the virtual machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// Push the given number onto the stack.
+    Push(u64),
+    /// Pop two numbers and push their sum. Sums wrap around on overflow.
+    Add,
+    /// Pop two numbers and push 1 if they are equal, 0 otherwise.
+    Eq,
+    /// Pop `b`, then `a`, and push 1 if `a < b`, 0 otherwise.
+    Lt,
+    /// Pop a key and push the value stored under it. Keys that were never written hold 0.
+    Load,
+    /// Pop a value, then a key, and store the value under the key.
+    Store,
+    /// Stop successfully. Running past the last instruction stops successfully, too.
+    Halt,
+}
+
+impl Instruction {
+    /// The gas that running this instruction costs. Touching storage is the expensive part,
+    /// since every node has to keep what is written around forever.
+    pub fn gas(&self) -> u64 {
+        match self {
+            Instruction::Push(_)
+            | Instruction::Add
+            | Instruction::Eq
+            | Instruction::Lt
+            | Instruction::Halt => 1,
+            Instruction::Load => 10,
+            Instruction::Store => 100,
+        }
+    }
+}
+
+/// A contract's storage.
+pub type Storage = BTreeMap<u64, u64>;
+
+/// Why a call failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VmError {
+    /// An instruction needed more operands than there were on the stack.
+    StackUnderflow,
+    /// The next instruction would have cost more gas than was left.
+    OutOfGas,
+}
+
+/// What running a contract did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Execution {
+    /// The gas used. A call that runs out of gas uses all of it.
+    pub gas_used: u64,
+    /// The contract's storage after a successful call, or why the call failed.
+    pub result: Result<Storage, VmError>,
+}
+
+/// A deployed contract.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Contract {
+    pub code: Vec<Instruction>,
+    pub storage: Storage,
+}
+
+/// The state of the chain: what everybody can pay for gas with, and every contract that was
+/// ever deployed. A contract is addressed by its index in `contracts`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VmState {
+    pub balances: BTreeMap<User, u64>,
+    pub contracts: Vec<Contract>,
+}
+
+/// The extrinsics that users can submit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VmTransaction {
+    /// Deploy a new contract. The deployer pays `DEPLOY_GAS_PER_INSTRUCTION` for every
+    /// instruction, and the contract starts out with empty storage.
+    Deploy {
+        deployer: User,
+        code: Vec<Instruction>,
+    },
+    /// Run a contract's code from the start, with an empty stack. The caller must be able to pay
+    /// for the whole gas limit, and pays for the gas that the call actually uses.
+    Call {
+        caller: User,
+        contract: usize,
+        gas_limit: u64,
+    },
+}
+
+/// Run the given code against the given storage, with the given amount of gas.
+pub fn execute(code: &[Instruction], storage: &Storage, gas_limit: u64) -> Execution {
+    exercise!("Exercise 1", solution::execute(code, storage, gas_limit))
+}
+
+impl StateMachine for StackVm {
+    type State = VmState;
+    type Transition = VmTransaction;
+
+    /// Deploys that the deployer can not pay for, and calls to unknown contracts or with a gas
+    /// limit that the caller can not pay for, leave the state untouched.
+    fn next_state(starting_state: &VmState, t: &VmTransaction) -> VmState {
+        exercise!("Exercise 2", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Stack VM".into()
+    }
+}
+
+/// A contract that counts how often it was called, in storage key 0.
+#[cfg(test)]
+fn counter() -> Vec<Instruction> {
+    use Instruction::*;
+    vec![Push(0), Push(0), Load, Push(1), Add, Store, Halt]
+}
+
+/// Alice with 1000 to spend on gas, and the counter deployed by her.
+#[cfg(test)]
+fn counter_deployed() -> VmState {
+    let state = VmState {
+        balances: BTreeMap::from([(User::Alice, 1000)]),
+        contracts: Vec::new(),
+    };
+    StackVm::next_state(
+        &state,
+        &VmTransaction::Deploy {
+            deployer: User::Alice,
+            code: counter(),
+        },
+    )
+}
+
+#[cfg(test)]
+fn call_counter(gas_limit: u64) -> VmTransaction {
+    VmTransaction::Call {
+        caller: User::Alice,
+        contract: 0,
+        gas_limit,
+    }
+}
+
+#[test]
+fn sm_10_arithmetic_and_comparison() {
+    use Instruction::*;
+    // Only storage outlives a call, so store every result to see it.
+    let equal = [Push(0), Push(7), Push(2), Push(5), Add, Eq, Store];
+    let less = [Push(1), Push(3), Push(2), Lt, Store];
+    let wrapping = [Push(2), Push(u64::MAX), Push(2), Add, Store];
+    let code = [&equal[..], &less, &wrapping].concat();
+    let execution = execute(&code, &Storage::new(), 1_000);
+    assert_eq!(
+        execution.result,
+        Ok(Storage::from([(0, 1), (1, 0), (2, 1)]))
+    );
+    assert_eq!(
+        execution.gas_used,
+        code.iter().map(Instruction::gas).sum::<u64>()
+    );
+}
+
+#[test]
+fn sm_10_halt_stops_execution() {
+    use Instruction::*;
+    let code = [Push(0), Push(1), Store, Halt, Push(0), Push(2), Store];
+    let execution = execute(&code, &Storage::new(), 1_000);
+    assert_eq!(execution.result, Ok(Storage::from([(0, 1)])));
+    assert_eq!(execution.gas_used, 1 + 1 + 100 + 1);
+}
+
+#[test]
+fn sm_10_failures() {
+    use Instruction::*;
+    let storage = Storage::from([(0, 5)]);
+
+    let underflow = execute(&[Push(1), Add], &storage, 1_000);
+    assert_eq!(underflow.result, Err(VmError::StackUnderflow));
+    assert_eq!(underflow.gas_used, 2);
+
+    // The store alone needs 100 gas.
+    let out_of_gas = execute(&counter(), &storage, 100);
+    assert_eq!(out_of_gas.result, Err(VmError::OutOfGas));
+    assert_eq!(out_of_gas.gas_used, 100);
+}
+
+#[test]
+fn sm_10_deploy_charges_deployer() {
+    let state = counter_deployed();
+    assert_eq!(
+        state.contracts,
+        vec![Contract {
+            code: counter(),
+            storage: Storage::new(),
+        }]
+    );
+    assert_eq!(
+        state.balances[&User::Alice],
+        1000 - 7 * DEPLOY_GAS_PER_INSTRUCTION
+    );
+
+    // Bob has nothing to pay with.
+    let broke = VmTransaction::Deploy {
+        deployer: User::Bob,
+        code: counter(),
+    };
+    assert_eq!(StackVm::next_state(&state, &broke), state);
+}
+
+#[test]
+fn sm_10_call_writes_storage_and_charges_gas() {
+    let state = counter_deployed();
+    let once = StackVm::next_state(&state, &call_counter(500));
+    let twice = StackVm::next_state(&once, &call_counter(500));
+
+    assert_eq!(twice.contracts[0].storage, Storage::from([(0, 2)]));
+    let gas: u64 = counter().iter().map(Instruction::gas).sum();
+    assert_eq!(
+        twice.balances[&User::Alice],
+        state.balances[&User::Alice] - 2 * gas
+    );
+}
+
+#[test]
+fn sm_10_failed_call_reverts_storage_but_charges_gas() {
+    let state = counter_deployed();
+    let end = StackVm::next_state(&state, &call_counter(50));
+
+    assert_eq!(end.contracts, state.contracts);
+    assert_eq!(
+        end.balances[&User::Alice],
+        state.balances[&User::Alice] - 50
+    );
+}
+
+#[test]
+fn sm_10_unaffordable_or_unknown_calls_rejected() {
+    let state = counter_deployed();
+    assert_eq!(StackVm::next_state(&state, &call_counter(1_000_000)), state);
+
+    let unknown = VmTransaction::Call {
+        caller: User::Alice,
+        contract: 1,
+        gas_limit: 500,
+    };
+    assert_eq!(StackVm::next_state(&state, &unknown), state);
+}
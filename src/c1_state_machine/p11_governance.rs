@@ -0,0 +1,435 @@
+//! A blockchain's rules are written into its code, but the numbers in them don't have to be.
+//! Chains such as Polkadot keep parameters like the block reward in their state, and let the
+//! token holders change them through on-chain governance, without anybody shipping new code.
+//!
+//! Any token holder can propose a change. A proposal is open for voting for `VOTING_PERIOD`
+//! blocks, during which token holders vote for or against it, and every vote weighs as much as
+//! the voter's balance. When the voting period is over, the proposal passes if more tokens voted
+//! for it than against it. A proposal that passed is not enacted right away, but
+//! `ENACTMENT_DELAY` blocks later, so that everybody who doesn't like the change has time to
+//! prepare, or to leave.
+//!
+//! Not everybody has the time to study every proposal. Token holders can delegate their vote to
+//! somebody they trust instead, who may delegate further, and so on: this is known as _liquid
+//! democracy_. A delegation applies to every proposal, and can be withdrawn at any time.
+//! Delegations are only resolved when voting ends: an account's tokens count for the first
+//! account along its chain of delegations that voted on the proposal. Whoever votes directly
+//! overrides their own delegation for that proposal. Delegations that would form a cycle are
+//! rejected, so that every chain ends somewhere.
+//!
+//! The state machine can not look at a clock, so the passing of time is a transition of its own:
+//! `NextBlock`, which the block author includes at the start of every block.
+
+use alloc::collections::BTreeMap;
+use alloc::{string::String, vec::Vec};
+
+use super::{StateMachine, User};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p11_governance.rs"]
+mod solution;
+
+/// A chain whose parameters are changed by referendum.
+pub struct Governance;
+
+/// For how many blocks a proposal is open for voting.
+pub const VOTING_PERIOD: u64 = 10;
+
+/// How many blocks after the voting period a passed proposal is enacted.
+pub const ENACTMENT_DELAY: u64 = 5;
+
+/// The parameters that governance can change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Parameter {
+    /// How much a block author is paid for every block.
+    BlockReward,
+    /// How much every transaction costs.
+    TransactionFee,
+}
+
+/// What a proposal wants to change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ParameterChange {
+    pub parameter: Parameter,
+    pub value: u64,
+}
+
+/// The current values of all parameters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Parameters {
+    pub block_reward: u64,
+    pub transaction_fee: u64,
+}
+
+/// A proposal that is open for voting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proposal {
+    pub proposer: User,
+    pub change: ParameterChange,
+    /// The block at which voting ends. Votes are accepted before this block only.
+    pub voting_ends: u64,
+    /// How every voter voted, `true` being for the change. Voters may change their minds until
+    /// voting ends, and only their last vote counts.
+    pub votes: BTreeMap<User, bool>,
+}
+
+/// The state of the chain.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GovernanceState {
+    /// The current block number.
+    pub block: u64,
+    /// The token holders. Nobody else may propose or vote.
+    pub balances: BTreeMap<User, u64>,
+    pub parameters: Parameters,
+    /// The proposals that are open for voting, by their index.
+    pub proposals: BTreeMap<u64, Proposal>,
+    /// The index that the next proposal gets.
+    pub next_proposal: u64,
+    /// The changes that passed, by the block at which they are enacted, in the order they passed.
+    pub scheduled: BTreeMap<u64, Vec<ParameterChange>>,
+    /// To whom token holders delegated their vote.
+    pub delegations: BTreeMap<User, User>,
+}
+
+/// The transitions of the governance state machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GovernanceTransaction {
+    /// Propose a change. Voting on it ends `VOTING_PERIOD` blocks from now.
+    Propose {
+        proposer: User,
+        change: ParameterChange,
+    },
+    /// Vote for, or against, an open proposal.
+    Vote {
+        voter: User,
+        proposal: u64,
+        aye: bool,
+    },
+    /// Delegate the vote to another token holder, replacing any previous delegation. Rejected if
+    /// the chain of delegations from `to` leads back to `who`.
+    Delegate { who: User, to: User },
+    /// Withdraw the delegation, and stop voting through anybody else.
+    Undelegate { who: User },
+    /// Move on to the next block. Every proposal whose voting ends at the new block is closed,
+    /// and scheduled for enactment if it passed. Then every change that is scheduled for the new
+    /// block is enacted.
+    NextBlock,
+}
+
+/// Whether the proposal passes: whether the balances of those who voted for it add up to more
+/// than the balances of those who voted against it. A tie does not pass.
+pub fn passes(proposal: &Proposal, balances: &BTreeMap<User, u64>) -> bool {
+    exercise!("Exercise 1", solution::passes(proposal, balances))
+}
+
+/// How many tokens every voter on the proposal votes with: their own balance, and the balance of
+/// everybody whose chain of delegations reaches them before it reaches any other voter. Tokens
+/// whose chain ends without reaching a voter don't count.
+pub fn voting_power(
+    proposal: &Proposal,
+    balances: &BTreeMap<User, u64>,
+    delegations: &BTreeMap<User, User>,
+) -> BTreeMap<User, u64> {
+    exercise!(
+        "Exercise 2",
+        solution::voting_power(proposal, balances, delegations)
+    )
+}
+
+impl StateMachine for Governance {
+    type State = GovernanceState;
+    type Transition = GovernanceTransaction;
+
+    /// Proposals, votes and delegations by users who hold no tokens, votes on proposals that are
+    /// not open, delegations to users who hold no tokens or that would form a cycle, and
+    /// withdrawing a delegation that does not exist, leave the state untouched.
+    fn next_state(starting_state: &GovernanceState, t: &GovernanceTransaction) -> GovernanceState {
+        exercise!("Exercise 3", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Governance".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+
+/// Alice holds 60 tokens, Bob 30 and Charlie 20, and the block reward is 10.
+#[cfg(test)]
+fn token_holders() -> GovernanceState {
+    GovernanceState {
+        balances: BTreeMap::from([(User::Alice, 60), (User::Bob, 30), (User::Charlie, 20)]),
+        parameters: Parameters {
+            block_reward: 10,
+            transaction_fee: 1,
+        },
+        ..GovernanceState::default()
+    }
+}
+
+#[cfg(test)]
+const DOUBLE_REWARD: ParameterChange = ParameterChange {
+    parameter: Parameter::BlockReward,
+    value: 20,
+};
+
+#[cfg(test)]
+fn vote(voter: User, aye: bool) -> GovernanceTransaction {
+    GovernanceTransaction::Vote {
+        voter,
+        proposal: 0,
+        aye,
+    }
+}
+
+#[cfg(test)]
+fn propose(proposer: User) -> GovernanceTransaction {
+    GovernanceTransaction::Propose {
+        proposer,
+        change: DOUBLE_REWARD,
+    }
+}
+
+#[cfg(test)]
+fn next_blocks(count: u64) -> Vec<GovernanceTransaction> {
+    (0..count)
+        .map(|_| GovernanceTransaction::NextBlock)
+        .collect()
+}
+
+#[test]
+fn sm_11_votes_are_weighted_by_balance() {
+    let balances = token_holders().balances;
+    let mut proposal = Proposal {
+        proposer: User::Bob,
+        change: DOUBLE_REWARD,
+        voting_ends: VOTING_PERIOD,
+        votes: BTreeMap::from([(User::Bob, true), (User::Charlie, true)]),
+    };
+    assert!(passes(&proposal, &balances));
+
+    // Alice alone outweighs both of them.
+    proposal.votes.insert(User::Alice, false);
+    assert!(!passes(&proposal, &balances));
+
+    // A tie, or no votes at all, does not pass.
+    proposal.votes = BTreeMap::from([(User::Alice, true), (User::Bob, false)]);
+    assert!(!passes(
+        &proposal,
+        &BTreeMap::from([(User::Alice, 30), (User::Bob, 30)])
+    ));
+    proposal.votes.clear();
+    assert!(!passes(&proposal, &balances));
+}
+
+#[test]
+fn sm_11_propose_opens_voting() {
+    let state = apply_all::<Governance>(
+        token_holders(),
+        &[GovernanceTransaction::NextBlock, propose(User::Bob)],
+    );
+    assert_eq!(
+        state.proposals,
+        BTreeMap::from([(
+            0,
+            Proposal {
+                proposer: User::Bob,
+                change: DOUBLE_REWARD,
+                voting_ends: 1 + VOTING_PERIOD,
+                votes: BTreeMap::new(),
+            }
+        )])
+    );
+    assert_eq!(state.next_proposal, 1);
+
+    // Only token holders may propose.
+    let mut no_tokens = token_holders();
+    no_tokens.balances.remove(&User::Bob);
+    assert_eq!(
+        apply_all::<Governance>(no_tokens.clone(), &[propose(User::Bob)]),
+        no_tokens
+    );
+}
+
+#[test]
+fn sm_11_passed_proposal_is_enacted_after_delay() {
+    let voted = apply_all::<Governance>(
+        token_holders(),
+        &[
+            propose(User::Bob),
+            vote(User::Bob, true),
+            vote(User::Charlie, true),
+        ],
+    );
+
+    let closed = apply_all::<Governance>(voted, &next_blocks(VOTING_PERIOD));
+    assert!(closed.proposals.is_empty());
+    assert_eq!(
+        closed.scheduled,
+        BTreeMap::from([(VOTING_PERIOD + ENACTMENT_DELAY, vec![DOUBLE_REWARD])])
+    );
+    assert_eq!(closed.parameters.block_reward, 10);
+
+    let almost = apply_all::<Governance>(closed, &next_blocks(ENACTMENT_DELAY - 1));
+    assert_eq!(almost.parameters.block_reward, 10);
+    let enacted = apply_all::<Governance>(almost, &next_blocks(1));
+    assert_eq!(enacted.parameters.block_reward, 20);
+    assert_eq!(enacted.parameters.transaction_fee, 1);
+    assert!(enacted.scheduled.is_empty());
+}
+
+#[test]
+fn sm_11_rejected_proposal_is_dropped() {
+    let voted = apply_all::<Governance>(
+        token_holders(),
+        &[
+            propose(User::Bob),
+            vote(User::Bob, true),
+            vote(User::Alice, false),
+        ],
+    );
+    let closed = apply_all::<Governance>(voted, &next_blocks(VOTING_PERIOD + ENACTMENT_DELAY));
+    assert!(closed.proposals.is_empty());
+    assert!(closed.scheduled.is_empty());
+    assert_eq!(closed.parameters.block_reward, 10);
+}
+
+#[test]
+fn sm_11_last_vote_counts() {
+    let state = apply_all::<Governance>(
+        token_holders(),
+        &[
+            propose(User::Bob),
+            vote(User::Alice, false),
+            vote(User::Alice, true),
+        ],
+    );
+    assert_eq!(
+        state.proposals[&0].votes,
+        BTreeMap::from([(User::Alice, true)])
+    );
+}
+
+#[test]
+fn sm_11_invalid_votes_rejected() {
+    let open = apply_all::<Governance>(token_holders(), &[propose(User::Bob)]);
+
+    let unknown = GovernanceTransaction::Vote {
+        voter: User::Alice,
+        proposal: 1,
+        aye: true,
+    };
+    assert_eq!(Governance::next_state(&open, &unknown), open);
+
+    let mut no_tokens = open.clone();
+    no_tokens.balances.remove(&User::Charlie);
+    assert_eq!(
+        Governance::next_state(&no_tokens, &vote(User::Charlie, true)),
+        no_tokens
+    );
+
+    // Once voting is over, the proposal is gone, and so is the chance to vote on it.
+    let closed = apply_all::<Governance>(open, &next_blocks(VOTING_PERIOD));
+    assert_eq!(
+        Governance::next_state(&closed, &vote(User::Alice, true)),
+        closed
+    );
+}
+
+#[cfg(test)]
+fn delegate(who: User, to: User) -> GovernanceTransaction {
+    GovernanceTransaction::Delegate { who, to }
+}
+
+#[test]
+fn sm_11_delegation_chains_resolve_to_voters() {
+    let balances = token_holders().balances;
+    let mut proposal = Proposal {
+        proposer: User::Bob,
+        change: DOUBLE_REWARD,
+        voting_ends: VOTING_PERIOD,
+        votes: BTreeMap::from([(User::Bob, true)]),
+    };
+    // Charlie delegates to Alice, who delegates to Bob.
+    let delegations = BTreeMap::from([(User::Charlie, User::Alice), (User::Alice, User::Bob)]);
+    assert_eq!(
+        voting_power(&proposal, &balances, &delegations),
+        BTreeMap::from([(User::Bob, 110)])
+    );
+
+    // Once Alice votes directly, Charlie's tokens stop at Alice.
+    proposal.votes.insert(User::Alice, false);
+    assert_eq!(
+        voting_power(&proposal, &balances, &delegations),
+        BTreeMap::from([(User::Alice, 80), (User::Bob, 30)])
+    );
+
+    // Nobody voted at the end of Charlie's chain, so Charlie's tokens don't count.
+    proposal.votes = BTreeMap::from([(User::Bob, true)]);
+    let delegations = BTreeMap::from([(User::Charlie, User::Alice)]);
+    assert_eq!(
+        voting_power(&proposal, &balances, &delegations),
+        BTreeMap::from([(User::Bob, 30)])
+    );
+}
+
+#[test]
+fn sm_11_delegations_resolved_when_voting_ends() {
+    // Alice does not vote, but delegates to Charlie, who votes against.
+    let voted = apply_all::<Governance>(
+        token_holders(),
+        &[
+            propose(User::Bob),
+            vote(User::Bob, true),
+            vote(User::Charlie, false),
+            delegate(User::Alice, User::Charlie),
+        ],
+    );
+    let rejected = apply_all::<Governance>(voted.clone(), &next_blocks(VOTING_PERIOD));
+    assert!(rejected.scheduled.is_empty());
+
+    // Withdrawing the delegation before voting ends takes Alice's tokens out of the tally.
+    let undelegated = apply_all::<Governance>(
+        voted,
+        &[GovernanceTransaction::Undelegate { who: User::Alice }],
+    );
+    assert!(undelegated.delegations.is_empty());
+    let passed = apply_all::<Governance>(undelegated, &next_blocks(VOTING_PERIOD));
+    assert_eq!(passed.scheduled.len(), 1);
+}
+
+#[test]
+fn sm_11_delegation_cycles_rejected() {
+    let state = apply_all::<Governance>(
+        token_holders(),
+        &[
+            delegate(User::Alice, User::Bob),
+            delegate(User::Bob, User::Charlie),
+        ],
+    );
+    assert_eq!(
+        state.delegations,
+        BTreeMap::from([(User::Alice, User::Bob), (User::Bob, User::Charlie)])
+    );
+    for invalid in [
+        delegate(User::Charlie, User::Alice),
+        delegate(User::Charlie, User::Charlie),
+        GovernanceTransaction::Undelegate { who: User::Charlie },
+    ] {
+        assert_eq!(Governance::next_state(&state, &invalid), state);
+    }
+
+    // Once Bob undelegates, the chain is broken, and Charlie may delegate to Alice.
+    let state = apply_all::<Governance>(
+        state,
+        &[
+            GovernanceTransaction::Undelegate { who: User::Bob },
+            delegate(User::Charlie, User::Alice),
+        ],
+    );
+    assert_eq!(
+        state.delegations,
+        BTreeMap::from([(User::Alice, User::Bob), (User::Charlie, User::Alice)])
+    );
+}
@@ -0,0 +1,242 @@
+//! Hashes and public keys are hard to remember, so blockchains such as Ethereum have name
+//! registries, which map human-readable names to them. The registry is a state machine like any
+//! other: whoever registers a free name first owns it, and can point it at any value they like.
+//!
+//! Names are not sold forever, but rented for `REGISTRATION_PERIOD` blocks at a time, so that
+//! names whose owners lost interest, or their keys, eventually become free again. An owner who
+//! wants to keep their name renews it before it expires. Once a name has expired, anybody can
+//! register it, or clear it out of the state with `Expire`.
+//!
+//! Short names are scarce, and everybody wants them. To keep squatters from grabbing all of them,
+//! the fee doubles for every character that a name is shorter than `SHORT_NAME`.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use super::{StateMachine, User};
+use crate::hashing::H256;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p12_name_registry.rs"]
+mod solution;
+
+/// A registry of names.
+pub struct NameRegistry;
+
+/// For how many blocks a registration, or a renewal, lasts.
+pub const REGISTRATION_PERIOD: u64 = 100;
+
+/// What registering, or renewing, a name that is not short costs.
+pub const BASE_FEE: u64 = 10;
+
+/// Names with fewer characters than this are short, and cost more.
+pub const SHORT_NAME: usize = 5;
+
+/// A registered name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NameRecord {
+    pub owner: User,
+    /// Whatever the owner wants the name to point at.
+    pub value: H256,
+    /// The block at which the name expires. It belongs to its owner before this block only.
+    pub expires: u64,
+}
+
+/// The state of the registry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegistryState {
+    /// The current block number.
+    pub block: u64,
+    /// What everybody can pay fees with. Fees are burned.
+    pub balances: BTreeMap<User, u64>,
+    /// Every registered name, including the expired ones that were not cleared out yet.
+    pub names: BTreeMap<String, NameRecord>,
+}
+
+/// The transitions of the registry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegistryTransaction {
+    /// Register a name that is free or expired, and point it at the given value.
+    Register {
+        who: User,
+        name: String,
+        value: H256,
+    },
+    /// Extend the owner's registration by another `REGISTRATION_PERIOD`, for the same fee as
+    /// registering it. Only names that have not expired yet can be renewed.
+    Renew { who: User, name: String },
+    /// Hand a name that has not expired over to somebody else, free of charge. The expiry stays
+    /// the same.
+    Transfer { from: User, to: User, name: String },
+    /// Remove an expired name. Anybody may do this.
+    Expire { name: String },
+    /// Move on to the next block.
+    NextBlock,
+}
+
+/// The fee for registering, or renewing, the given name: `BASE_FEE` for names with at least
+/// `SHORT_NAME` characters, and twice as much for every character less. The empty name can not
+/// be registered at all, so it has no fee.
+pub fn registration_fee(name: &str) -> Option<u64> {
+    exercise!("Exercise 1", solution::registration_fee(name))
+}
+
+impl StateMachine for NameRegistry {
+    type State = RegistryState;
+    type Transition = RegistryTransaction;
+
+    /// Transitions by users who can not pay the fee, or who don't own the name they act on, and
+    /// transitions on names that are in the wrong state, leave the state untouched.
+    fn next_state(starting_state: &RegistryState, t: &RegistryTransaction) -> RegistryState {
+        exercise!("Exercise 2", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Name Registry".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+
+#[cfg(test)]
+fn next_blocks(state: RegistryState, count: u64) -> RegistryState {
+    let blocks: Vec<_> = (0..count).map(|_| RegistryTransaction::NextBlock).collect();
+    apply_all::<NameRegistry>(state, &blocks)
+}
+
+#[cfg(test)]
+fn register(who: User, name: &str) -> RegistryTransaction {
+    RegistryTransaction::Register {
+        who,
+        name: name.into(),
+        value: H256::from(who as u64),
+    }
+}
+
+/// Alice and Bob with 100 each, and "alice.dot" registered to Alice.
+#[cfg(test)]
+fn alice_registered() -> RegistryState {
+    let state = RegistryState {
+        balances: BTreeMap::from([(User::Alice, 100), (User::Bob, 100)]),
+        ..RegistryState::default()
+    };
+    apply_all::<NameRegistry>(state, &[register(User::Alice, "alice.dot")])
+}
+
+#[test]
+fn sm_12_short_names_cost_more() {
+    assert_eq!(registration_fee(""), None);
+    assert_eq!(registration_fee("alice.dot"), Some(BASE_FEE));
+    assert_eq!(registration_fee("alice"), Some(BASE_FEE));
+    assert_eq!(registration_fee("bob"), Some(4 * BASE_FEE));
+    assert_eq!(registration_fee("x"), Some(16 * BASE_FEE));
+    // Fees count characters, not bytes.
+    assert_eq!(registration_fee("ünï"), Some(4 * BASE_FEE));
+}
+
+#[test]
+fn sm_12_register_free_name() {
+    let state = alice_registered();
+    assert_eq!(
+        state.names["alice.dot"],
+        NameRecord {
+            owner: User::Alice,
+            value: H256::from(User::Alice as u64),
+            expires: REGISTRATION_PERIOD,
+        }
+    );
+    assert_eq!(state.balances[&User::Alice], 100 - BASE_FEE);
+
+    // Taken names and unaffordable names can not be registered.
+    assert_eq!(
+        apply_all::<NameRegistry>(state.clone(), &[register(User::Bob, "alice.dot")]),
+        state
+    );
+    assert_eq!(
+        apply_all::<NameRegistry>(state.clone(), &[register(User::Bob, "x")]),
+        state
+    );
+    assert_eq!(
+        apply_all::<NameRegistry>(state.clone(), &[register(User::Bob, "")]),
+        state
+    );
+}
+
+#[test]
+fn sm_12_renew_extends_registration() {
+    let renew = |who| RegistryTransaction::Renew {
+        who,
+        name: "alice.dot".into(),
+    };
+    let state = next_blocks(alice_registered(), 50);
+    let renewed = apply_all::<NameRegistry>(state.clone(), &[renew(User::Alice)]);
+    assert_eq!(renewed.names["alice.dot"].expires, 2 * REGISTRATION_PERIOD);
+    assert_eq!(renewed.balances[&User::Alice], 100 - 2 * BASE_FEE);
+
+    // Only the owner may renew, and only before the name expires.
+    assert_eq!(
+        apply_all::<NameRegistry>(state.clone(), &[renew(User::Bob)]),
+        state
+    );
+    let expired = next_blocks(state, 50);
+    assert_eq!(
+        apply_all::<NameRegistry>(expired.clone(), &[renew(User::Alice)]),
+        expired
+    );
+}
+
+#[test]
+fn sm_12_transfer_changes_owner() {
+    let transfer = |from, to| RegistryTransaction::Transfer {
+        from,
+        to,
+        name: "alice.dot".into(),
+    };
+    let state = alice_registered();
+    let transferred =
+        apply_all::<NameRegistry>(state.clone(), &[transfer(User::Alice, User::Charlie)]);
+    assert_eq!(transferred.names["alice.dot"].owner, User::Charlie);
+    assert_eq!(transferred.names["alice.dot"].expires, REGISTRATION_PERIOD);
+
+    assert_eq!(
+        apply_all::<NameRegistry>(state.clone(), &[transfer(User::Bob, User::Bob)]),
+        state
+    );
+    let expired = next_blocks(state, REGISTRATION_PERIOD);
+    assert_eq!(
+        apply_all::<NameRegistry>(expired.clone(), &[transfer(User::Alice, User::Charlie)]),
+        expired
+    );
+}
+
+#[test]
+fn sm_12_expired_names_become_free() {
+    let expire = RegistryTransaction::Expire {
+        name: "alice.dot".into(),
+    };
+    let almost = next_blocks(alice_registered(), REGISTRATION_PERIOD - 1);
+    assert_eq!(
+        apply_all::<NameRegistry>(almost.clone(), core::slice::from_ref(&expire)),
+        almost
+    );
+    assert_eq!(
+        apply_all::<NameRegistry>(almost.clone(), &[register(User::Bob, "alice.dot")]),
+        almost
+    );
+
+    let expired = next_blocks(almost, 1);
+    let cleared = apply_all::<NameRegistry>(expired.clone(), &[expire]);
+    assert!(cleared.names.is_empty());
+
+    // An expired name can be registered right away, without clearing it first.
+    let taken = apply_all::<NameRegistry>(expired, &[register(User::Bob, "alice.dot")]);
+    assert_eq!(
+        taken.names["alice.dot"],
+        NameRecord {
+            owner: User::Bob,
+            value: H256::from(User::Bob as u64),
+            expires: 2 * REGISTRATION_PERIOD,
+        }
+    );
+}
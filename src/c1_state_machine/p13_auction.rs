@@ -0,0 +1,244 @@
+//! An English auction sells an item to whoever bids the most. Bids are public, every bid must
+//! beat the one before it, and when the auction ends, the highest bidder pays their bid and gets
+//! the item.
+//!
+//! On a blockchain, nobody has to trust the auctioneer. The state machine itself holds the item
+//! while it is up for auction, and every bid is escrowed: the bidder's money is locked away as
+//! soon as they bid, so that the winner can not back out of paying. Whoever is outbid gets their
+//! money back right away. When the auction's end height is reached, the auction settles on its
+//! own: the item goes to the winner, and the winning bid goes to the seller.
+//!
+//! The items are the non-fungible tokens from the next exercise. The auction house tracks both
+//! the tokens and everybody's balance.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use super::p14_nft::{NftState, TokenId};
+use super::{StateMachine, User};
+use crate::hashing::H256;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p13_auction.rs"]
+mod solution;
+
+/// An auction house for non-fungible tokens.
+pub struct AuctionHouse;
+
+/// An item that is up for auction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Auction {
+    pub seller: User,
+    /// The metadata of the token that is for sale. The token itself is held by the auction house.
+    pub metadata: H256,
+    /// The lowest bid that the seller accepts.
+    pub min_bid: u64,
+    /// The block at which the auction ends. Bids are accepted before this block only.
+    pub ends: u64,
+    /// The highest bid so far, if any, and who made it.
+    pub highest: Option<(User, u64)>,
+}
+
+/// The state of the auction house.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuctionState {
+    /// The current block number.
+    pub block: u64,
+    /// Everybody's money, not counting the bids that are escrowed.
+    pub balances: BTreeMap<User, u64>,
+    /// Every token, except for the ones that are up for auction.
+    pub nfts: NftState,
+    /// The tokens that are up for auction.
+    pub auctions: BTreeMap<TokenId, Auction>,
+}
+
+/// The transitions of the auction house.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuctionTransaction {
+    /// Put a token up for auction, for `duration` blocks. Only the token's owner may do this, and
+    /// the token is held by the auction house until the auction ends.
+    Create {
+        seller: User,
+        item: TokenId,
+        min_bid: u64,
+        duration: u64,
+    },
+    /// Bid on a token. The bid must be at least the minimum bid, and more than the highest bid
+    /// so far. Sellers may not bid on their own tokens. The bid is taken from the bidder's
+    /// balance, and the bid that it beats is paid back.
+    Bid {
+        bidder: User,
+        item: TokenId,
+        amount: u64,
+    },
+    /// Move on to the next block, and settle every auction that ends at the new block. Tokens
+    /// that nobody bid on go back to their sellers.
+    NextBlock,
+}
+
+impl StateMachine for AuctionHouse {
+    type State = AuctionState;
+    type Transition = AuctionTransaction;
+
+    /// Transitions that break any of the rules above leave the state untouched.
+    fn next_state(starting_state: &AuctionState, t: &AuctionTransaction) -> AuctionState {
+        exercise!("Exercise 1", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "English Auction".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+#[cfg(test)]
+use super::p14_nft::Token;
+
+/// The token that Alice sells.
+#[cfg(test)]
+const ITEM: TokenId = 7;
+
+#[cfg(test)]
+fn owned_by(owner: User) -> NftState {
+    NftState {
+        tokens: BTreeMap::from([(
+            ITEM,
+            Token {
+                owner,
+                metadata: H256::from(1),
+            },
+        )]),
+        next_id: ITEM + 1,
+    }
+}
+
+#[cfg(test)]
+fn bid(bidder: User, amount: u64) -> AuctionTransaction {
+    AuctionTransaction::Bid {
+        bidder,
+        item: ITEM,
+        amount,
+    }
+}
+
+/// Alice auctions off her item for 10 blocks, with a minimum bid of 20. Bob and Charlie have 100
+/// each to bid with.
+#[cfg(test)]
+fn auction_started() -> AuctionState {
+    let state = AuctionState {
+        balances: BTreeMap::from([(User::Bob, 100), (User::Charlie, 100)]),
+        nfts: owned_by(User::Alice),
+        ..AuctionState::default()
+    };
+    apply_all::<AuctionHouse>(
+        state,
+        &[AuctionTransaction::Create {
+            seller: User::Alice,
+            item: ITEM,
+            min_bid: 20,
+            duration: 10,
+        }],
+    )
+}
+
+#[cfg(test)]
+fn next_blocks(count: u64) -> Vec<AuctionTransaction> {
+    (0..count).map(|_| AuctionTransaction::NextBlock).collect()
+}
+
+#[test]
+fn sm_13_create_escrows_item() {
+    let state = auction_started();
+    assert!(state.nfts.tokens.is_empty());
+    assert_eq!(
+        state.auctions,
+        BTreeMap::from([(
+            ITEM,
+            Auction {
+                seller: User::Alice,
+                metadata: H256::from(1),
+                min_bid: 20,
+                ends: 10,
+                highest: None,
+            }
+        )])
+    );
+
+    // The token is out of Alice's hands now, so she can not auction it twice.
+    let again = AuctionTransaction::Create {
+        seller: User::Alice,
+        item: ITEM,
+        min_bid: 1,
+        duration: 10,
+    };
+    assert_eq!(apply_all::<AuctionHouse>(state.clone(), &[again]), state);
+}
+
+#[test]
+fn sm_13_bids_are_escrowed_and_outbid_refunded() {
+    let state = apply_all::<AuctionHouse>(auction_started(), &[bid(User::Bob, 30)]);
+    assert_eq!(state.balances[&User::Bob], 70);
+    assert_eq!(state.auctions[&ITEM].highest, Some((User::Bob, 30)));
+
+    let state = apply_all::<AuctionHouse>(state, &[bid(User::Charlie, 40)]);
+    assert_eq!(state.balances[&User::Bob], 100);
+    assert_eq!(state.balances[&User::Charlie], 60);
+    assert_eq!(state.auctions[&ITEM].highest, Some((User::Charlie, 40)));
+}
+
+#[test]
+fn sm_13_invalid_bids_rejected() {
+    let state = apply_all::<AuctionHouse>(auction_started(), &[bid(User::Bob, 30)]);
+    for invalid in [
+        bid(User::Charlie, 30),  // does not beat Bob
+        bid(User::Charlie, 101), // more than Charlie has
+        bid(User::Alice, 50),    // the seller
+        AuctionTransaction::Bid {
+            bidder: User::Charlie,
+            item: ITEM + 1,
+            amount: 50,
+        },
+    ] {
+        assert_eq!(apply_all::<AuctionHouse>(state.clone(), &[invalid]), state);
+    }
+
+    let too_low = apply_all::<AuctionHouse>(auction_started(), &[bid(User::Bob, 19)]);
+    assert_eq!(too_low, auction_started());
+
+    let over = apply_all::<AuctionHouse>(state, &next_blocks(10));
+    assert_eq!(
+        apply_all::<AuctionHouse>(over.clone(), &[bid(User::Charlie, 50)]),
+        over
+    );
+}
+
+#[test]
+fn sm_13_settlement_pays_seller_and_hands_over_item() {
+    let state = apply_all::<AuctionHouse>(
+        auction_started(),
+        &[
+            bid(User::Bob, 30),
+            bid(User::Charlie, 40),
+            bid(User::Bob, 50),
+        ],
+    );
+    let almost = apply_all::<AuctionHouse>(state, &next_blocks(9));
+    assert!(almost.auctions.contains_key(&ITEM));
+
+    let settled = apply_all::<AuctionHouse>(almost, &next_blocks(1));
+    assert!(settled.auctions.is_empty());
+    assert_eq!(settled.nfts, owned_by(User::Bob));
+    assert_eq!(
+        settled.balances,
+        BTreeMap::from([(User::Alice, 50), (User::Bob, 50), (User::Charlie, 100)])
+    );
+}
+
+#[test]
+fn sm_13_unsold_item_returns_to_seller() {
+    let settled = apply_all::<AuctionHouse>(auction_started(), &next_blocks(10));
+    assert!(settled.auctions.is_empty());
+    assert_eq!(settled.nfts, owned_by(User::Alice));
+    assert_eq!(settled.balances, auction_started().balances);
+}
@@ -0,0 +1,168 @@
+//! The currencies so far were fungible: one coin is as good as any other, and all that matters
+//! is how many of them everybody has. A non-fungible token, or NFT, is one of a kind. Every token
+//! has its own id and belongs to exactly one user, like the deed to a house or a ticket for a
+//! particular seat.
+//!
+//! What the token stands for is described by its metadata, which usually lives off chain, since
+//! it can be large. The chain only stores a hash of it, which is enough for anybody to check
+//! that the metadata they were handed is the real one.
+//!
+//! Tokens are used by other exercises, too: the auction house sells them, the escrow trades
+//! them, and the rich state of chapter 2 tracks them alongside its numbers.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use super::{StateMachine, User};
+use crate::hashing::H256;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p14_nft.rs"]
+mod solution;
+
+/// Non-fungible tokens.
+pub struct Nft;
+
+/// The id of a token.
+pub type TokenId = u64;
+
+/// A single token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Token {
+    pub owner: User,
+    /// The hash of the token's metadata.
+    pub metadata: H256,
+}
+
+/// Every token in existence.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NftState {
+    pub tokens: BTreeMap<TokenId, Token>,
+    /// The id that the next token gets. Ids are never reused, not even those of burned tokens,
+    /// so an id always refers to the same token.
+    pub next_id: TokenId,
+}
+
+/// The transitions of the token state machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NftTransaction {
+    /// Create a new token with the given metadata, owned by the minter. It gets the next id.
+    Mint { minter: User, metadata: H256 },
+    /// Give a token to somebody else. Only the owner may do this.
+    Transfer { from: User, to: User, id: TokenId },
+    /// Destroy a token for good. Only the owner may do this.
+    Burn { owner: User, id: TokenId },
+}
+
+impl StateMachine for Nft {
+    type State = NftState;
+    type Transition = NftTransaction;
+
+    /// Transfers and burns of tokens that don't exist, or that belong to somebody else, leave
+    /// the state untouched.
+    fn next_state(starting_state: &NftState, t: &NftTransaction) -> NftState {
+        exercise!("Exercise 1", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Non-fungible Tokens".into()
+    }
+}
+
+/// Alice has minted token 0, and Bob token 1.
+#[cfg(test)]
+fn two_tokens() -> NftState {
+    let state = Nft::next_state(
+        &NftState::default(),
+        &NftTransaction::Mint {
+            minter: User::Alice,
+            metadata: H256::from(10),
+        },
+    );
+    Nft::next_state(
+        &state,
+        &NftTransaction::Mint {
+            minter: User::Bob,
+            metadata: H256::from(11),
+        },
+    )
+}
+
+#[test]
+fn sm_14_mint_gives_unique_ids() {
+    let state = two_tokens();
+    assert_eq!(
+        state.tokens,
+        BTreeMap::from([
+            (
+                0,
+                Token {
+                    owner: User::Alice,
+                    metadata: H256::from(10)
+                }
+            ),
+            (
+                1,
+                Token {
+                    owner: User::Bob,
+                    metadata: H256::from(11)
+                }
+            ),
+        ])
+    );
+    assert_eq!(state.next_id, 2);
+}
+
+#[test]
+fn sm_14_owner_transfers() {
+    let transfer = NftTransaction::Transfer {
+        from: User::Alice,
+        to: User::Charlie,
+        id: 0,
+    };
+    let end = Nft::next_state(&two_tokens(), &transfer);
+    assert_eq!(end.tokens[&0].owner, User::Charlie);
+    assert_eq!(end.tokens[&0].metadata, H256::from(10));
+    assert_eq!(end.tokens[&1], two_tokens().tokens[&1]);
+}
+
+#[test]
+fn sm_14_only_owner_transfers_or_burns() {
+    let state = two_tokens();
+    for invalid in [
+        NftTransaction::Transfer {
+            from: User::Alice,
+            to: User::Charlie,
+            id: 1,
+        },
+        NftTransaction::Transfer {
+            from: User::Alice,
+            to: User::Charlie,
+            id: 2,
+        },
+        NftTransaction::Burn {
+            owner: User::Bob,
+            id: 0,
+        },
+    ] {
+        assert_eq!(Nft::next_state(&state, &invalid), state);
+    }
+}
+
+#[test]
+fn sm_14_burned_ids_are_not_reused() {
+    let burn = NftTransaction::Burn {
+        owner: User::Alice,
+        id: 0,
+    };
+    let burned = Nft::next_state(&two_tokens(), &burn);
+    assert!(!burned.tokens.contains_key(&0));
+
+    let mint = NftTransaction::Mint {
+        minter: User::Charlie,
+        metadata: H256::from(12),
+    };
+    let minted = Nft::next_state(&burned, &mint);
+    assert!(!minted.tokens.contains_key(&0));
+    assert_eq!(minted.tokens[&2].owner, User::Charlie);
+}
@@ -0,0 +1,358 @@
+//! When strangers trade, somebody has to go first. If the buyer pays first, the seller may never
+//! deliver, and if the seller delivers first, the buyer may never pay. An escrow solves this: the
+//! buyer locks the money away where neither of them can touch it alone, and it is only paid out
+//! once the deal is done.
+//!
+//! The goods are a non-fungible token from exercise 14, so that the state machine can hold them,
+//! too. The buyer opens the escrow for one of the seller's tokens, with a deadline, and the seller
+//! delivers the token into the escrow before it passes. Then the money can be paid out in two ways. If buyer and seller agree on how to split
+//! it, usually all of it to the seller, they both release it with the same split. If they can't
+//! agree, the arbiter that they picked when opening the escrow decides the split for them. Unless
+//! the buyer gets all of their money back, they get the token, and otherwise it goes back to the
+//! seller.
+//!
+//! A seller who never delivers must not be able to hold the buyer's money hostage. So an escrow
+//! that was not delivered by its deadline is refunded to the buyer automatically.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use super::p14_nft::{NftState, TokenId};
+use super::{StateMachine, User};
+use crate::hashing::H256;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p15_escrow.rs"]
+mod solution;
+
+/// Escrows with an arbiter.
+pub struct EscrowService;
+
+/// A single escrow.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Escrow {
+    pub buyer: User,
+    pub seller: User,
+    pub arbiter: User,
+    /// The token that the buyer pays for.
+    pub item: TokenId,
+    /// The locked money.
+    pub amount: u64,
+    /// The block by which the seller must deliver. The escrow is refunded at this block unless
+    /// the item was delivered before it.
+    pub deadline: u64,
+    /// The metadata of the item, once the seller delivered it. From then on, the token is held
+    /// by the escrow.
+    pub delivered: Option<H256>,
+    /// How much of the money buyer and seller released to the seller, if they did.
+    pub releases: BTreeMap<User, u64>,
+}
+
+/// The state of the escrow service.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EscrowState {
+    /// The current block number.
+    pub block: u64,
+    /// Everybody's money, not counting what is locked in escrows.
+    pub balances: BTreeMap<User, u64>,
+    /// Every token, except for the ones that are held by escrows.
+    pub nfts: NftState,
+    /// The open escrows, by their id.
+    pub escrows: BTreeMap<u64, Escrow>,
+    /// The id that the next escrow gets.
+    pub next_id: u64,
+}
+
+/// The transitions of the escrow service.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EscrowTransaction {
+    /// Lock the given amount of the buyer's money in a new escrow for the given item, which the
+    /// seller must own, and must deliver within `duration` blocks. Buyer, seller and arbiter must
+    /// be three different users.
+    Open {
+        buyer: User,
+        seller: User,
+        arbiter: User,
+        item: TokenId,
+        amount: u64,
+        duration: u64,
+    },
+    /// The seller hands the item over to the escrow. This only works before the deadline, and
+    /// only while the seller still owns the item.
+    MarkDelivered { seller: User, id: u64 },
+    /// The buyer or the seller agrees to pay `to_seller` of the money to the seller, and the
+    /// rest back to the buyer. As soon as both agree on the same split, it is paid out. Either
+    /// of them may change their mind until then.
+    Release { who: User, id: u64, to_seller: u64 },
+    /// The arbiter decides the split, and it is paid out right away. The arbiter can only step
+    /// in once the item is delivered: before that, there is nothing to dispute.
+    Arbitrate {
+        arbiter: User,
+        id: u64,
+        to_seller: u64,
+    },
+    /// Move on to the next block, and refund every escrow whose deadline is the new block and
+    /// whose item was not delivered.
+    NextBlock,
+}
+
+impl StateMachine for EscrowService {
+    type State = EscrowState;
+    type Transition = EscrowTransaction;
+
+    /// Transitions by the wrong users, on escrows that don't exist, with splits of more money
+    /// than is locked, or that the buyer can not pay for, leave the state untouched.
+    fn next_state(starting_state: &EscrowState, t: &EscrowTransaction) -> EscrowState {
+        exercise!("Exercise 1", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Escrow".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+#[cfg(test)]
+use super::p14_nft::Token;
+
+/// The token that Bob sells.
+#[cfg(test)]
+const ITEM: TokenId = 7;
+
+#[cfg(test)]
+fn owned_by(owner: User) -> NftState {
+    NftState {
+        tokens: BTreeMap::from([(
+            ITEM,
+            Token {
+                owner,
+                metadata: H256::from(1),
+            },
+        )]),
+        next_id: ITEM + 1,
+    }
+}
+
+#[cfg(test)]
+fn open(amount: u64) -> EscrowTransaction {
+    EscrowTransaction::Open {
+        buyer: User::Alice,
+        seller: User::Bob,
+        arbiter: User::Charlie,
+        item: ITEM,
+        amount,
+        duration: 10,
+    }
+}
+
+/// Alice buys Bob's token for 60, with Charlie as the arbiter and a deadline in 10 blocks.
+#[cfg(test)]
+fn opened() -> EscrowState {
+    let state = EscrowState {
+        balances: BTreeMap::from([(User::Alice, 100)]),
+        nfts: owned_by(User::Bob),
+        ..EscrowState::default()
+    };
+    apply_all::<EscrowService>(state, &[open(60)])
+}
+
+#[cfg(test)]
+fn delivered() -> EscrowState {
+    apply_all::<EscrowService>(
+        opened(),
+        &[EscrowTransaction::MarkDelivered {
+            seller: User::Bob,
+            id: 0,
+        }],
+    )
+}
+
+#[cfg(test)]
+fn release(who: User, to_seller: u64) -> EscrowTransaction {
+    EscrowTransaction::Release {
+        who,
+        id: 0,
+        to_seller,
+    }
+}
+
+#[cfg(test)]
+fn next_blocks(state: EscrowState, count: u64) -> EscrowState {
+    let blocks: Vec<_> = (0..count).map(|_| EscrowTransaction::NextBlock).collect();
+    apply_all::<EscrowService>(state, &blocks)
+}
+
+#[test]
+fn sm_15_open_locks_funds() {
+    let state = opened();
+    assert_eq!(state.balances[&User::Alice], 40);
+    assert_eq!(
+        state.escrows[&0],
+        Escrow {
+            buyer: User::Alice,
+            seller: User::Bob,
+            arbiter: User::Charlie,
+            item: ITEM,
+            amount: 60,
+            deadline: 10,
+            delivered: None,
+            releases: BTreeMap::new(),
+        }
+    );
+    assert_eq!(state.next_id, 1);
+    // The item stays with the seller until they deliver it.
+    assert_eq!(state.nfts, owned_by(User::Bob));
+
+    assert_eq!(
+        apply_all::<EscrowService>(state.clone(), &[open(41)]),
+        state
+    );
+
+    let own_arbiter = EscrowTransaction::Open {
+        buyer: User::Alice,
+        seller: User::Bob,
+        arbiter: User::Alice,
+        item: ITEM,
+        amount: 10,
+        duration: 10,
+    };
+    assert_eq!(
+        apply_all::<EscrowService>(state.clone(), &[own_arbiter]),
+        state
+    );
+
+    // Nobody can sell a token that they do not own.
+    let not_owned = EscrowState {
+        nfts: owned_by(User::Charlie),
+        ..opened()
+    };
+    assert_eq!(
+        apply_all::<EscrowService>(not_owned.clone(), &[open(10)]),
+        not_owned
+    );
+}
+
+#[test]
+fn sm_15_delivery_locks_item() {
+    let state = delivered();
+    assert!(state.nfts.tokens.is_empty());
+    assert_eq!(state.escrows[&0].delivered, Some(H256::from(1)));
+
+    // A seller who gave the item away in the meantime can not deliver it.
+    let given_away = EscrowState {
+        nfts: owned_by(User::Charlie),
+        ..opened()
+    };
+    let deliver = EscrowTransaction::MarkDelivered {
+        seller: User::Bob,
+        id: 0,
+    };
+    assert_eq!(
+        apply_all::<EscrowService>(given_away.clone(), &[deliver]),
+        given_away
+    );
+}
+
+#[test]
+fn sm_15_mutual_release_pays_out() {
+    let buyer_only = apply_all::<EscrowService>(delivered(), &[release(User::Alice, 60)]);
+    assert!(buyer_only.escrows.contains_key(&0));
+
+    // The seller agrees to a different split first, then comes around.
+    let paid = apply_all::<EscrowService>(
+        buyer_only,
+        &[release(User::Bob, 50), release(User::Bob, 60)],
+    );
+    assert!(paid.escrows.is_empty());
+    assert_eq!(
+        paid.balances,
+        BTreeMap::from([(User::Alice, 40), (User::Bob, 60)])
+    );
+    assert_eq!(paid.nfts, owned_by(User::Alice));
+}
+
+#[test]
+fn sm_15_arbiter_decides() {
+    let arbitrate = |arbiter, to_seller| EscrowTransaction::Arbitrate {
+        arbiter,
+        id: 0,
+        to_seller,
+    };
+
+    // Nothing to decide before delivery, and nobody but the arbiter decides.
+    let state = opened();
+    assert_eq!(
+        apply_all::<EscrowService>(state.clone(), &[arbitrate(User::Charlie, 30)]),
+        state
+    );
+    let state = delivered();
+    assert_eq!(
+        apply_all::<EscrowService>(state.clone(), &[arbitrate(User::Bob, 60)]),
+        state
+    );
+    assert_eq!(
+        apply_all::<EscrowService>(state.clone(), &[arbitrate(User::Charlie, 61)]),
+        state
+    );
+
+    let decided = apply_all::<EscrowService>(state.clone(), &[arbitrate(User::Charlie, 20)]);
+    assert!(decided.escrows.is_empty());
+    assert_eq!(
+        decided.balances,
+        BTreeMap::from([(User::Alice, 80), (User::Bob, 20)])
+    );
+    assert_eq!(decided.nfts, owned_by(User::Alice));
+
+    // A buyer who gets all of their money back does not get the item.
+    let refunded = apply_all::<EscrowService>(state, &[arbitrate(User::Charlie, 0)]);
+    assert_eq!(refunded.balances, BTreeMap::from([(User::Alice, 100)]));
+    assert_eq!(refunded.nfts, owned_by(User::Bob));
+}
+
+#[test]
+fn sm_15_undelivered_escrow_refunded_at_deadline() {
+    let almost = next_blocks(opened(), 9);
+    assert!(almost.escrows.contains_key(&0));
+
+    let late = EscrowTransaction::MarkDelivered {
+        seller: User::Bob,
+        id: 0,
+    };
+    let refunded = next_blocks(almost.clone(), 1);
+    assert!(refunded.escrows.is_empty());
+    assert_eq!(refunded.balances[&User::Alice], 100);
+    assert_eq!(refunded.nfts, owned_by(User::Bob));
+    assert_eq!(
+        apply_all::<EscrowService>(refunded.clone(), core::slice::from_ref(&late)),
+        refunded
+    );
+
+    // Delivered escrows wait for a release or a decision, no matter how long it takes.
+    let waiting = next_blocks(apply_all::<EscrowService>(almost, &[late]), 100);
+    assert!(waiting.escrows[&0].delivered.is_some());
+    assert_eq!(waiting.balances[&User::Alice], 40);
+}
+
+#[test]
+fn sm_15_only_parties_act() {
+    let state = opened();
+    let not_seller = EscrowTransaction::MarkDelivered {
+        seller: User::Charlie,
+        id: 0,
+    };
+    assert_eq!(
+        apply_all::<EscrowService>(state.clone(), &[not_seller]),
+        state
+    );
+
+    let state = delivered();
+    assert_eq!(
+        apply_all::<EscrowService>(state.clone(), &[release(User::Charlie, 60)]),
+        state
+    );
+    assert_eq!(
+        apply_all::<EscrowService>(state.clone(), &[release(User::Alice, 61)]),
+        state
+    );
+}
@@ -0,0 +1,317 @@
+//! Every transaction on chain costs a fee and has to wait for a block. Two parties who pay each
+//! other often, like a customer and a coffee shop, can do better with a payment channel. They
+//! lock some money on chain once, and from then on pay each other off chain, by signing updates
+//! that say how the locked money is split between them. Only the last update ever goes on chain,
+//! when the channel is closed, no matter how many payments were made.
+//!
+//! Every update carries a nonce that grows with each payment, and is signed by both parties, so
+//! neither of them can make one up alone. But either of them could try to close the channel with
+//! an older update that they liked better. That is why closing takes time: for a challenge period
+//! after a party starts to close the channel, the other party can prove them wrong by submitting
+//! an update with a higher nonce. When the period is over, the newest update that was submitted
+//! is paid out.
+//!
+//! The signatures are real ones, made with the development keys of the keystore, which is why
+//! this exercise needs the `std` feature.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use super::{StateMachine, User};
+use crate::c4_client::keystore::Signature;
+use crate::hashing::{hash_of, H256};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p16_payment_channel.rs"]
+mod solution;
+
+/// The number of blocks that the other party has to challenge a close.
+pub const CHALLENGE_PERIOD: u64 = 10;
+
+/// Payment channels between pairs of users.
+pub struct PaymentChannels;
+
+/// An off-chain split of a channel's money between its two parties.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChannelUpdate {
+    /// The id of the channel that this update is for, so that it can't be replayed on another
+    /// channel between the same parties.
+    pub channel: u64,
+    /// Grows with every update. Higher nonces replace lower ones.
+    pub nonce: u64,
+    pub opener_balance: u64,
+    pub counterparty_balance: u64,
+}
+
+impl ChannelUpdate {
+    /// The hash that both parties sign.
+    pub fn hash(&self) -> H256 {
+        hash_of("channel update", self)
+    }
+}
+
+/// An update, signed by both parties.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SignedUpdate {
+    pub update: ChannelUpdate,
+    pub by_opener: Signature,
+    pub by_counterparty: Signature,
+}
+
+/// A channel that somebody started to close.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Closing {
+    /// The newest update submitted so far.
+    pub update: ChannelUpdate,
+    /// The block at which the update is paid out.
+    pub ends: u64,
+}
+
+/// A single channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Channel {
+    /// The user who opened the channel and locked the money.
+    pub opener: User,
+    pub counterparty: User,
+    /// The money locked in the channel.
+    pub deposit: u64,
+    pub closing: Option<Closing>,
+}
+
+/// The on-chain state of the payment channels.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChannelState {
+    /// The current block number.
+    pub block: u64,
+    /// Everybody's money, not counting what is locked in channels.
+    pub balances: BTreeMap<User, u64>,
+    /// The open channels, by their id.
+    pub channels: BTreeMap<u64, Channel>,
+    /// The id that the next channel gets.
+    pub next_id: u64,
+}
+
+/// The on-chain transitions of the payment channels. Payments themselves never show up here.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelTransaction {
+    /// Lock the given amount of the opener's money in a new channel with the counterparty, who
+    /// must be somebody else. At first, all of it belongs to the opener.
+    Open {
+        opener: User,
+        counterparty: User,
+        deposit: u64,
+    },
+    /// One of the parties starts to close the channel named in the update, with that update.
+    /// A channel that is already closing can't be closed again.
+    Close { who: User, update: SignedUpdate },
+    /// One of the parties replaces the update of a closing channel with a newer one, before the
+    /// challenge period ends. This does not extend the challenge period.
+    Challenge { who: User, update: SignedUpdate },
+    /// Move on to the next block, and pay out every channel whose challenge period ends at the
+    /// new block.
+    NextBlock,
+}
+
+/// Check that the update is signed by both parties of the channel, and splits exactly the
+/// channel's deposit between them.
+pub fn is_valid(update: &SignedUpdate, channel: &Channel) -> bool {
+    exercise!("Exercise 1", solution::is_valid(update, channel))
+}
+
+impl StateMachine for PaymentChannels {
+    type State = ChannelState;
+    type Transition = ChannelTransaction;
+
+    /// Transitions by the wrong users, on channels that don't exist, with invalid or outdated
+    /// updates, or that the opener can not pay for, leave the state untouched.
+    fn next_state(starting_state: &ChannelState, t: &ChannelTransaction) -> ChannelState {
+        exercise!("Exercise 2", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Payment Channels".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+#[cfg(test)]
+use crate::c4_client::keystore::Keystore;
+
+/// Alice has opened channel 0 with Bob, and locked 100 in it.
+#[cfg(test)]
+fn opened() -> ChannelState {
+    let state = ChannelState {
+        balances: BTreeMap::from([(User::Alice, 150)]),
+        ..ChannelState::default()
+    };
+    apply_all::<PaymentChannels>(
+        state,
+        &[ChannelTransaction::Open {
+            opener: User::Alice,
+            counterparty: User::Bob,
+            deposit: 100,
+        }],
+    )
+}
+
+/// The update of channel 0 with the given nonce that pays Bob the given amount.
+#[cfg(test)]
+fn update(nonce: u64, to_bob: u64) -> ChannelUpdate {
+    ChannelUpdate {
+        channel: 0,
+        nonce,
+        opener_balance: 100 - to_bob,
+        counterparty_balance: to_bob,
+    }
+}
+
+/// The update, signed by the given users in the place of the opener and the counterparty.
+#[cfg(test)]
+fn signed_by(update: ChannelUpdate, signers: [User; 2]) -> SignedUpdate {
+    let keystore = Keystore::dev();
+    let sign = |user| keystore.sign(user, update.hash()).unwrap();
+    SignedUpdate {
+        update,
+        by_opener: sign(signers[0]),
+        by_counterparty: sign(signers[1]),
+    }
+}
+
+#[cfg(test)]
+fn signed(nonce: u64, to_bob: u64) -> SignedUpdate {
+    signed_by(update(nonce, to_bob), [User::Alice, User::Bob])
+}
+
+#[cfg(test)]
+fn next_blocks(state: ChannelState, count: u64) -> ChannelState {
+    let blocks: Vec<_> = (0..count).map(|_| ChannelTransaction::NextBlock).collect();
+    apply_all::<PaymentChannels>(state, &blocks)
+}
+
+#[test]
+fn sm_16_open_locks_deposit() {
+    let state = opened();
+    assert_eq!(state.balances[&User::Alice], 50);
+    assert_eq!(
+        state.channels[&0],
+        Channel {
+            opener: User::Alice,
+            counterparty: User::Bob,
+            deposit: 100,
+            closing: None,
+        }
+    );
+    assert_eq!(state.next_id, 1);
+
+    let too_much = ChannelTransaction::Open {
+        opener: User::Alice,
+        counterparty: User::Bob,
+        deposit: 51,
+    };
+    assert_eq!(
+        apply_all::<PaymentChannels>(state.clone(), &[too_much]),
+        state
+    );
+}
+
+#[test]
+fn sm_16_close_pays_out_after_challenge_period() {
+    let closing = apply_all::<PaymentChannels>(
+        opened(),
+        &[ChannelTransaction::Close {
+            who: User::Bob,
+            update: signed(3, 30),
+        }],
+    );
+    let channel = closing.channels[&0];
+    assert_eq!(channel.closing.unwrap().ends, CHALLENGE_PERIOD);
+
+    let almost = next_blocks(closing, CHALLENGE_PERIOD - 1);
+    assert!(almost.channels.contains_key(&0));
+
+    let closed = next_blocks(almost, 1);
+    assert!(closed.channels.is_empty());
+    assert_eq!(
+        closed.balances,
+        BTreeMap::from([(User::Alice, 120), (User::Bob, 30)])
+    );
+}
+
+#[test]
+fn sm_16_newer_update_wins_challenge() {
+    // Alice tries to close with an old update that paid Bob less.
+    let closing = apply_all::<PaymentChannels>(
+        opened(),
+        &[ChannelTransaction::Close {
+            who: User::Alice,
+            update: signed(1, 10),
+        }],
+    );
+    let challenge = |nonce, to_bob| ChannelTransaction::Challenge {
+        who: User::Bob,
+        update: signed(nonce, to_bob),
+    };
+
+    // Bob can't challenge with an update that is not newer.
+    assert_eq!(
+        apply_all::<PaymentChannels>(closing.clone(), &[challenge(1, 90)]),
+        closing
+    );
+
+    let challenged = apply_all::<PaymentChannels>(closing.clone(), &[challenge(2, 40)]);
+    assert_eq!(challenged.channels[&0].closing.unwrap().update.nonce, 2);
+    let closed = next_blocks(challenged, CHALLENGE_PERIOD);
+    assert_eq!(
+        closed.balances,
+        BTreeMap::from([(User::Alice, 110), (User::Bob, 40)])
+    );
+
+    // Once the challenge period is over, it is too late.
+    let late = next_blocks(closing, CHALLENGE_PERIOD);
+    assert_eq!(
+        apply_all::<PaymentChannels>(late.clone(), &[challenge(2, 40)]),
+        late
+    );
+}
+
+#[test]
+fn sm_16_rejects_invalid_updates() {
+    let state = opened();
+    let alice_and_bob = [User::Alice, User::Bob];
+    let wrong_split = ChannelUpdate {
+        opener_balance: 80,
+        ..update(1, 30)
+    };
+    let other_channel = ChannelUpdate {
+        channel: 1,
+        ..update(1, 30)
+    };
+    let mut tampered = signed(1, 30);
+    tampered.update.nonce = 2;
+
+    for invalid in [
+        signed_by(update(1, 30), [User::Alice, User::Alice]),
+        signed_by(update(1, 30), [User::Alice, User::Charlie]),
+        signed_by(update(1, 30), [User::Bob, User::Alice]),
+        signed_by(wrong_split, alice_and_bob),
+        signed_by(other_channel, alice_and_bob),
+        tampered,
+    ] {
+        let close = ChannelTransaction::Close {
+            who: User::Alice,
+            update: invalid,
+        };
+        assert_eq!(apply_all::<PaymentChannels>(state.clone(), &[close]), state);
+    }
+
+    // Only the parties may close.
+    let stranger = ChannelTransaction::Close {
+        who: User::Charlie,
+        update: signed(1, 30),
+    };
+    assert_eq!(
+        apply_all::<PaymentChannels>(state.clone(), &[stranger]),
+        state
+    );
+}
@@ -0,0 +1,298 @@
+//! A bridge moves tokens from one chain to another. Users lock their tokens on chain A, and get
+//! the same amount of wrapped tokens on chain B, which stand in for the locked ones. This module
+//! is chain B's side of the bridge.
+//!
+//! The hard part is for chain B to learn what happened on chain A without trusting anybody who
+//! tells it. So chain B's state machine embeds a light client of chain A. Anybody can submit
+//! chain A's headers, and the light client checks that they extend the chain it knows. Headers
+//! alone could still be on a fork that will be abandoned, though, and minting for a lock that
+//! ends up reverted would create wrapped tokens out of nothing. So the bridge only trusts
+//! headers that chain A's finality committee has sealed as final, and their ancestors.
+//!
+//! To claim wrapped tokens, a user shows the bridge their lock event, along with a Merkle proof
+//! that it is one of the extrinsics of a final block of chain A. Every lock event can be claimed
+//! once.
+//!
+//! The light client has to start from somewhere. It trusts the genesis header it is created
+//! with, and the committee it is told about, just like a light client that syncs from a
+//! checkpoint. Real bridges also follow changes to the committee, which we leave out here.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{StateMachine, User};
+use crate::c3_consensus::Header;
+use crate::crypto::aggregate::{CommitteeSeal, PublicKey};
+use crate::hashing::{header_hash, H256};
+use crate::merkle::MerkleProof;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p17_bridge.rs"]
+mod solution;
+
+/// Chain B's side of a bridge from chain A.
+pub struct Bridge;
+
+/// A lock of tokens on chain A, for the given recipient on chain B. The bodies of chain A's
+/// blocks are lists of these, as far as the bridge is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LockEvent {
+    /// Identifies the lock. Chain A gives every lock its own id.
+    pub id: u64,
+    pub recipient: User,
+    pub amount: u64,
+}
+
+/// The bridge's view of chain A, and the wrapped tokens it has minted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgeState {
+    /// The public keys of chain A's finality committee.
+    pub committee: Vec<PublicKey>,
+    /// How many members of the committee must seal a header for it to be final.
+    pub threshold: usize,
+    /// Every header of chain A the light client has imported, by hash. Forks included.
+    pub headers: BTreeMap<H256, Header<()>>,
+    /// The hash of the latest final header.
+    pub finalized: H256,
+    /// The ids of the lock events that wrapped tokens were minted for already.
+    pub claimed: BTreeSet<u64>,
+    /// Everybody's wrapped tokens.
+    pub wrapped: BTreeMap<User, u64>,
+}
+
+impl BridgeState {
+    /// A bridge that trusts the given genesis header of chain A and the given committee.
+    pub fn new(genesis: Header<()>, committee: Vec<PublicKey>, threshold: usize) -> Self {
+        let finalized = header_hash(&genesis);
+        BridgeState {
+            committee,
+            threshold,
+            headers: BTreeMap::from([(finalized, genesis)]),
+            finalized,
+            claimed: BTreeSet::new(),
+            wrapped: BTreeMap::new(),
+        }
+    }
+
+    /// Whether the header with the given hash is final: whether it is the latest final header,
+    /// or one of its ancestors.
+    pub fn is_final(&self, hash: H256) -> bool {
+        exercise!("Exercise 1", solution::is_final(self, hash))
+    }
+}
+
+/// The transitions of chain B's side of the bridge.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BridgeTransaction {
+    /// Import a header of chain A. Its parent must have been imported already.
+    ImportHeader(Header<()>),
+    /// Mark an imported header as final, with the seal of the finality committee over its hash.
+    /// It must be a descendant of the latest final header: finality never goes back, and never
+    /// switches forks.
+    Finalize { hash: H256, seal: CommitteeSeal },
+    /// Mint wrapped tokens for the lock event, which the proof shows to be in the final block
+    /// with the given hash.
+    Mint {
+        block: H256,
+        event: LockEvent,
+        proof: MerkleProof,
+    },
+}
+
+impl StateMachine for Bridge {
+    type State = BridgeState;
+    type Transition = BridgeTransaction;
+
+    /// Headers that don't extend a known header, seals that don't check out, and mints for
+    /// lock events that are not proven to be final or that were claimed before, leave the
+    /// state untouched.
+    fn next_state(starting_state: &BridgeState, t: &BridgeTransaction) -> BridgeState {
+        exercise!("Exercise 2", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Bridge".into()
+    }
+}
+
+#[cfg(test)]
+use crate::crypto::aggregate::SecretKey;
+#[cfg(test)]
+use crate::merkle::{build_proof, merkle_root};
+
+/// Chain A's finality committee, which has three members.
+#[cfg(test)]
+fn committee() -> Vec<SecretKey> {
+    (0..3).map(|i| SecretKey::from_seed([i; 32])).collect()
+}
+
+#[cfg(test)]
+fn header(parent: &Header<()>, events: &[LockEvent]) -> Header<()> {
+    Header::new(
+        header_hash(parent),
+        parent.height() + 1,
+        H256::default(),
+        merkle_root(events),
+        (),
+    )
+}
+
+/// A seal over the given header's hash by the given members of the committee.
+#[cfg(test)]
+fn seal(header: &Header<()>, members: &[usize]) -> CommitteeSeal {
+    let committee = committee();
+    let signatures: Vec<_> = members
+        .iter()
+        .map(|&i| (i, committee[i].sign(header_hash(header).as_bytes())))
+        .collect();
+    CommitteeSeal::new(committee.len(), &signatures)
+}
+
+#[cfg(test)]
+fn events() -> [LockEvent; 2] {
+    [
+        LockEvent {
+            id: 0,
+            recipient: User::Alice,
+            amount: 30,
+        },
+        LockEvent {
+            id: 1,
+            recipient: User::Bob,
+            amount: 20,
+        },
+    ]
+}
+
+/// Chain A's genesis header, block 1 with the two lock events, and an empty block 2.
+#[cfg(test)]
+fn chain_a() -> [Header<()>; 3] {
+    let genesis = Header::new(
+        H256::default(),
+        0,
+        H256::default(),
+        merkle_root::<LockEvent>(&[]),
+        (),
+    );
+    let one = header(&genesis, &events());
+    let two = header(&one, &[]);
+    [genesis, one, two]
+}
+
+/// A bridge that knows all of chain A, but only trusts its genesis.
+#[cfg(test)]
+fn synced() -> BridgeState {
+    let [genesis, one, two] = chain_a();
+    let keys = committee().iter().map(SecretKey::public).collect();
+    let mut state = BridgeState::new(genesis, keys, 2);
+    for header in [one, two] {
+        state = Bridge::next_state(&state, &BridgeTransaction::ImportHeader(header));
+    }
+    state
+}
+
+#[cfg(test)]
+fn finalize(header: &Header<()>, members: &[usize]) -> BridgeTransaction {
+    BridgeTransaction::Finalize {
+        hash: header_hash(header),
+        seal: seal(header, members),
+    }
+}
+
+#[cfg(test)]
+fn mint(index: usize) -> BridgeTransaction {
+    BridgeTransaction::Mint {
+        block: header_hash(&chain_a()[1]),
+        event: events()[index],
+        proof: build_proof(&events(), index).unwrap(),
+    }
+}
+
+#[test]
+fn sm_17_imports_only_connected_headers() {
+    let state = synced();
+    assert_eq!(state.headers.len(), 3);
+
+    let [genesis, _, two] = chain_a();
+    let orphan = header(&header(&two, &[]), &[]);
+    let import = BridgeTransaction::ImportHeader(orphan);
+    assert_eq!(Bridge::next_state(&state, &import), state);
+
+    let wrong_height = Header::new(
+        header_hash(&genesis),
+        5,
+        H256::default(),
+        merkle_root::<LockEvent>(&[]),
+        (),
+    );
+    let import = BridgeTransaction::ImportHeader(wrong_height);
+    assert_eq!(Bridge::next_state(&state, &import), state);
+}
+
+#[test]
+fn sm_17_finality_needs_threshold_of_committee() {
+    let state = synced();
+    let [genesis, one, two] = chain_a();
+    assert!(state.is_final(header_hash(&genesis)));
+    assert!(!state.is_final(header_hash(&one)));
+
+    assert_eq!(Bridge::next_state(&state, &finalize(&two, &[1])), state);
+
+    let finalized = Bridge::next_state(&state, &finalize(&two, &[0, 2]));
+    assert_eq!(finalized.finalized, header_hash(&two));
+    assert!(finalized.is_final(header_hash(&one)));
+
+    // Finality never goes back, and never switches forks.
+    assert_eq!(
+        Bridge::next_state(&finalized, &finalize(&one, &[0, 1, 2])),
+        finalized
+    );
+    let fork = header(&genesis, &[events()[0]]);
+    let forked = Bridge::next_state(&finalized, &BridgeTransaction::ImportHeader(fork.clone()));
+    assert_eq!(
+        Bridge::next_state(&forked, &finalize(&fork, &[0, 1])),
+        forked
+    );
+}
+
+#[test]
+fn sm_17_mints_for_final_lock_events() {
+    let two = chain_a()[2].clone();
+    let state = synced();
+    assert_eq!(Bridge::next_state(&state, &mint(1)), state);
+
+    let state = Bridge::next_state(&state, &finalize(&two, &[0, 1]));
+    let minted = Bridge::next_state(&state, &mint(1));
+    assert_eq!(minted.wrapped, BTreeMap::from([(User::Bob, 20)]));
+    assert!(minted.claimed.contains(&1));
+
+    // Each lock event is only claimed once.
+    assert_eq!(Bridge::next_state(&minted, &mint(1)), minted);
+}
+
+#[test]
+fn sm_17_rejects_forged_lock_events() {
+    let two = chain_a()[2].clone();
+    let state = Bridge::next_state(&synced(), &finalize(&two, &[0, 1]));
+
+    let BridgeTransaction::Mint { block, proof, .. } = mint(0) else {
+        unreachable!()
+    };
+    let inflated = BridgeTransaction::Mint {
+        block,
+        event: LockEvent {
+            amount: 3_000,
+            ..events()[0]
+        },
+        proof: proof.clone(),
+    };
+    assert_eq!(Bridge::next_state(&state, &inflated), state);
+
+    let wrong_block = BridgeTransaction::Mint {
+        block: header_hash(&two),
+        event: events()[0],
+        proof,
+    };
+    assert_eq!(Bridge::next_state(&state, &wrong_block), state);
+}
@@ -0,0 +1,201 @@
+//! Young chains often need to be fixed in a hurry: a parameter was set badly, or a bug locked
+//! somebody's money away. Before they have proper governance, many of them give a single key,
+//! the _sudo_ key, the power to do what the rules would not otherwise allow. Privileged calls
+//! can only be dispatched by the holder of that key, who acts as the chain's root origin.
+//!
+//! A key this powerful should not sit in one place forever. Its holder can hand it over to a
+//! new holder, for example when the team that launched the chain changes. And once the chain is
+//! ready to govern itself, the holder removes the key altogether. Removal is permanent: with the
+//! key gone, nobody is left who could bring it back.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use super::{StateMachine, User};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p18_sudo.rs"]
+mod solution;
+
+/// A currency with a sudo key.
+pub struct SudoCurrency;
+
+/// The state of the currency.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SudoState {
+    /// The holder of the sudo key, if the key was not removed yet.
+    pub key: Option<User>,
+    pub balances: BTreeMap<User, u64>,
+    /// The fee that every ordinary transfer pays on top of the amount. It is burned.
+    pub transfer_fee: u64,
+    /// Whether ordinary transfers are paused, for example while a bug is being fixed.
+    pub paused: bool,
+}
+
+/// The calls that only the holder of the sudo key may dispatch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PrivilegedCall {
+    SetTransferFee(u64),
+    SetPaused(bool),
+    /// Move money from one account to another without the owner's consent. It pays no fee,
+    /// and it works while transfers are paused. It can not move more than the account has.
+    ForceTransfer {
+        from: User,
+        to: User,
+        amount: u64,
+    },
+}
+
+/// The transitions of the currency.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SudoTransaction {
+    /// An ordinary transfer, which pays the transfer fee.
+    Transfer {
+        sender: User,
+        receiver: User,
+        amount: u64,
+    },
+    /// Dispatch a privileged call as root. Only the holder of the sudo key may do this.
+    Sudo { who: User, call: PrivilegedCall },
+    /// Hand the sudo key over to a new holder.
+    SetKey { who: User, new: User },
+    /// Remove the sudo key for good.
+    RemoveKey { who: User },
+}
+
+impl StateMachine for SudoCurrency {
+    type State = SudoState;
+    type Transition = SudoTransaction;
+
+    /// Transfers the sender can't pay for, transfers while paused, and anything to do with the
+    /// sudo key by somebody who does not hold it, leave the state untouched.
+    fn next_state(starting_state: &SudoState, t: &SudoTransaction) -> SudoState {
+        exercise!("Exercise 1", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Sudo Currency".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+
+/// Alice holds the sudo key, Bob has 100, and transfers cost 1.
+#[cfg(test)]
+fn launched() -> SudoState {
+    SudoState {
+        key: Some(User::Alice),
+        balances: BTreeMap::from([(User::Bob, 100)]),
+        transfer_fee: 1,
+        paused: false,
+    }
+}
+
+#[cfg(test)]
+fn transfer(amount: u64) -> SudoTransaction {
+    SudoTransaction::Transfer {
+        sender: User::Bob,
+        receiver: User::Charlie,
+        amount,
+    }
+}
+
+#[cfg(test)]
+fn sudo(who: User, call: PrivilegedCall) -> SudoTransaction {
+    SudoTransaction::Sudo { who, call }
+}
+
+#[test]
+fn sm_18_transfers_pay_fee() {
+    let state = apply_all::<SudoCurrency>(launched(), &[transfer(50)]);
+    assert_eq!(
+        state.balances,
+        BTreeMap::from([(User::Bob, 49), (User::Charlie, 50)])
+    );
+
+    // Bob can send 48 and pay the fee, but not 49.
+    assert_eq!(
+        apply_all::<SudoCurrency>(state.clone(), &[transfer(49)]),
+        state
+    );
+    let emptied = apply_all::<SudoCurrency>(state, &[transfer(48)]);
+    assert_eq!(emptied.balances, BTreeMap::from([(User::Charlie, 98)]));
+}
+
+#[test]
+fn sm_18_only_sudo_dispatches_privileged_calls() {
+    let state = launched();
+    let steal = PrivilegedCall::ForceTransfer {
+        from: User::Bob,
+        to: User::Charlie,
+        amount: 100,
+    };
+    assert_eq!(
+        apply_all::<SudoCurrency>(state.clone(), &[sudo(User::Bob, steal.clone())]),
+        state
+    );
+
+    let state = apply_all::<SudoCurrency>(
+        state,
+        &[
+            sudo(User::Alice, PrivilegedCall::SetTransferFee(5)),
+            sudo(User::Alice, PrivilegedCall::SetPaused(true)),
+        ],
+    );
+    assert_eq!((state.transfer_fee, state.paused), (5, true));
+    assert_eq!(
+        apply_all::<SudoCurrency>(state.clone(), &[transfer(10)]),
+        state
+    );
+
+    // Forced transfers pay no fee and ignore the pause.
+    let forced = apply_all::<SudoCurrency>(state, &[sudo(User::Alice, steal)]);
+    assert_eq!(forced.balances, BTreeMap::from([(User::Charlie, 100)]));
+}
+
+#[test]
+fn sm_18_sudo_key_rotates() {
+    let rotate = |who, new| SudoTransaction::SetKey { who, new };
+    let state = launched();
+    assert_eq!(
+        apply_all::<SudoCurrency>(state.clone(), &[rotate(User::Bob, User::Bob)]),
+        state
+    );
+
+    let rotated = apply_all::<SudoCurrency>(state, &[rotate(User::Alice, User::Charlie)]);
+    assert_eq!(rotated.key, Some(User::Charlie));
+    let old_key = sudo(User::Alice, PrivilegedCall::SetTransferFee(0));
+    assert_eq!(
+        apply_all::<SudoCurrency>(rotated.clone(), &[old_key]),
+        rotated
+    );
+    let new_key = sudo(User::Charlie, PrivilegedCall::SetTransferFee(0));
+    assert_eq!(
+        apply_all::<SudoCurrency>(rotated, &[new_key]).transfer_fee,
+        0
+    );
+}
+
+#[test]
+fn sm_18_removed_key_is_gone_for_good() {
+    let state = launched();
+    let remove = |who| SudoTransaction::RemoveKey { who };
+    assert_eq!(
+        apply_all::<SudoCurrency>(state.clone(), &[remove(User::Bob)]),
+        state
+    );
+
+    let removed = apply_all::<SudoCurrency>(state, &[remove(User::Alice)]);
+    assert_eq!(removed.key, None);
+    for t in [
+        sudo(User::Alice, PrivilegedCall::SetPaused(true)),
+        SudoTransaction::SetKey {
+            who: User::Alice,
+            new: User::Alice,
+        },
+        remove(User::Alice),
+    ] {
+        assert_eq!(apply_all::<SudoCurrency>(removed.clone(), &[t]), removed);
+    }
+}
@@ -0,0 +1,135 @@
+//! Test networks need money too, or nobody could pay for a transfer to try them out. But money
+//! on a test network is worthless, so nobody will sell it. Instead, test networks run a
+//! _faucet_: anybody may ask it for a small amount, and it mints that amount for them.
+//!
+//! A faucet that hands out money to whoever asks will soon be drained by a script that asks a
+//! million times. So every account may only claim once in a while: after claiming, it has to
+//! wait a number of blocks before it can claim again. A determined abuser can still spread out
+//! over many accounts, but they can no longer get rich from a single one.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use super::{StateMachine, User};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p19_faucet.rs"]
+mod solution;
+
+/// A currency whose money comes out of a faucet.
+pub struct Faucet;
+
+/// The state of the faucet.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FaucetState {
+    /// The current block number.
+    pub block: u64,
+    /// How much every claim mints.
+    pub drip: u64,
+    /// How many blocks an account has to wait after claiming before it can claim again.
+    pub cooldown: u64,
+    /// The block in which every account claimed last.
+    pub last_claims: BTreeMap<User, u64>,
+    pub balances: BTreeMap<User, u64>,
+}
+
+/// The transitions of the faucet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FaucetTransaction {
+    /// Move on to the next block.
+    NewBlock,
+    /// Mint the drip for the given user. This only works if they never claimed before, or if
+    /// at least `cooldown` blocks passed since their last claim.
+    Claim { who: User },
+}
+
+impl StateMachine for Faucet {
+    type State = FaucetState;
+    type Transition = FaucetTransaction;
+
+    /// Claims before the cooldown is over, and claims that would overflow the claimant's
+    /// balance, leave the state untouched.
+    fn next_state(starting_state: &FaucetState, t: &FaucetTransaction) -> FaucetState {
+        exercise!("Exercise 1", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Faucet".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+
+/// A faucet that drips 10 at a time, once every 3 blocks.
+#[cfg(test)]
+fn faucet() -> FaucetState {
+    FaucetState {
+        drip: 10,
+        cooldown: 3,
+        ..FaucetState::default()
+    }
+}
+
+#[cfg(test)]
+fn claim(who: User) -> FaucetTransaction {
+    FaucetTransaction::Claim { who }
+}
+
+#[cfg(test)]
+const NEW_BLOCK: FaucetTransaction = FaucetTransaction::NewBlock;
+
+#[test]
+fn sm_19_first_claim_mints_drip() {
+    let state = apply_all::<Faucet>(faucet(), &[NEW_BLOCK, claim(User::Alice), claim(User::Bob)]);
+    assert_eq!(
+        state.balances,
+        BTreeMap::from([(User::Alice, 10), (User::Bob, 10)])
+    );
+    assert_eq!(
+        state.last_claims,
+        BTreeMap::from([(User::Alice, 1), (User::Bob, 1)])
+    );
+}
+
+#[test]
+fn sm_19_claims_wait_for_cooldown() {
+    let state = apply_all::<Faucet>(faucet(), &[claim(User::Alice)]);
+    assert_eq!(
+        apply_all::<Faucet>(state.clone(), &[claim(User::Alice)]),
+        state
+    );
+
+    // Two blocks later is too early, three blocks later is fine.
+    let state = apply_all::<Faucet>(state, &[NEW_BLOCK, NEW_BLOCK]);
+    assert_eq!(
+        apply_all::<Faucet>(state.clone(), &[claim(User::Alice)]),
+        state
+    );
+    let state = apply_all::<Faucet>(state, &[NEW_BLOCK, claim(User::Alice)]);
+    assert_eq!(state.balances, BTreeMap::from([(User::Alice, 20)]));
+    assert_eq!(state.last_claims, BTreeMap::from([(User::Alice, 3)]));
+}
+
+#[test]
+fn sm_19_cooldowns_are_per_account() {
+    let state = apply_all::<Faucet>(faucet(), &[claim(User::Alice), NEW_BLOCK, claim(User::Bob)]);
+    let state = apply_all::<Faucet>(
+        state,
+        &[NEW_BLOCK, NEW_BLOCK, claim(User::Alice), claim(User::Bob)],
+    );
+    assert_eq!(
+        state.balances,
+        BTreeMap::from([(User::Alice, 20), (User::Bob, 10)])
+    );
+}
+
+#[test]
+fn sm_19_claims_do_not_overflow() {
+    let mut state = faucet();
+    state.balances.insert(User::Alice, u64::MAX - 5);
+    assert_eq!(
+        apply_all::<Faucet>(state.clone(), &[claim(User::Alice)]),
+        state
+    );
+}
@@ -0,0 +1,304 @@
+//! Accounts are just keys, and a key says nothing about who holds it. An identity registry lets
+//! account holders tell the world who they are, and lets others vouch for it. Every account may
+//! set an identity: the hash of its metadata, like a display name, a website and an email
+//! address. The metadata itself lives off chain. Setting an identity reserves a deposit, so that
+//! the state isn't filled with junk, and the deposit comes back when the identity is cleared.
+//!
+//! Anybody can claim to be anybody, so an identity on its own proves nothing. That is what
+//! _registrars_ are for. An account holder asks a registrar for a judgement and pays their fee up
+//! front. The registrar checks the metadata off chain, and then judges it on chain. The fee is
+//! only paid out to the registrar once they do. A judgement is about the metadata that was
+//! judged, so changing the identity throws away every judgement of it. A registrar who learns
+//! that they judged wrongly can revoke their judgement.
+//!
+//! Registrars are trusted, so not just anybody can become one. A council of users decides: any
+//! councillor can propose to add or remove a registrar, and the motion is enacted as soon as
+//! more than half of the council approves it.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+
+use super::{StateMachine, User};
+use crate::hashing::H256;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p20_identity.rs"]
+mod solution;
+
+/// An identity registry with registrars.
+pub struct IdentityRegistry;
+
+/// How much of their balance an account reserves while it has an identity.
+pub const IDENTITY_DEPOSIT: u64 = 10;
+
+/// What a registrar thinks of an identity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Judgement {
+    /// The metadata looks right, but the registrar couldn't check everything.
+    Reasonable,
+    /// The registrar checked the metadata, and it is right.
+    KnownGood,
+    /// The metadata is wrong.
+    Erroneous,
+}
+
+/// The identity of an account.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Identity {
+    /// The hash of the metadata.
+    pub info: H256,
+    /// The fees paid to registrars that were asked for a judgement, but did not judge yet.
+    pub requests: BTreeMap<User, u64>,
+    /// The judgements of the metadata, by registrar.
+    pub judgements: BTreeMap<User, Judgement>,
+}
+
+/// A change to the registrars, which the council has to approve.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RegistrarMotion {
+    /// Make the given user a registrar that charges the given fee for a judgement.
+    Add { registrar: User, fee: u64 },
+    /// Stop the given user from being a registrar. Every request they did not answer yet is
+    /// refunded. Their past judgements stand.
+    Remove { registrar: User },
+}
+
+/// The state of the registry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdentityState {
+    /// Everybody's money, not counting deposits and fees that are held for requests.
+    pub balances: BTreeMap<User, u64>,
+    pub identities: BTreeMap<User, Identity>,
+    /// The registrars, with the fee each of them charges.
+    pub registrars: BTreeMap<User, u64>,
+    pub council: BTreeSet<User>,
+    /// The open motions, with the councillors who approved them so far.
+    pub motions: BTreeMap<RegistrarMotion, BTreeSet<User>>,
+}
+
+/// The transitions of the registry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdentityTransaction {
+    /// Set, or change, the identity of the given user. A new identity reserves the deposit. A
+    /// changed identity loses all of its judgements.
+    SetIdentity { who: User, info: H256 },
+    /// Clear the identity of the given user. The deposit, and the fees of every open request,
+    /// are given back.
+    ClearIdentity { who: User },
+    /// Ask a registrar to judge the given user's identity, paying the registrar's fee as long as
+    /// it is no more than `max_fee`. Every registrar can only be asked once at a time.
+    RequestJudgement {
+        who: User,
+        registrar: User,
+        max_fee: u64,
+    },
+    /// The registrar judges an identity that they were asked to judge, and is paid their fee.
+    /// The judgement is only accepted if `info` is still the identity's metadata, so that a
+    /// registrar never judges metadata they did not check.
+    ProvideJudgement {
+        registrar: User,
+        target: User,
+        info: H256,
+        judgement: Judgement,
+    },
+    /// The registrar takes back their judgement of the given user's identity.
+    RevokeJudgement { registrar: User, target: User },
+    /// A councillor proposes, or approves, a motion. It is enacted right away once more than
+    /// half of the council approved it.
+    Approve {
+        councillor: User,
+        motion: RegistrarMotion,
+    },
+}
+
+impl StateMachine for IdentityRegistry {
+    type State = IdentityState;
+    type Transition = IdentityTransaction;
+
+    /// Anything that somebody can't pay for, requests that don't go to a registrar or to a user
+    /// without identity, judgements nobody asked for, and approvals by users outside of the
+    /// council, leave the state untouched.
+    fn next_state(starting_state: &IdentityState, t: &IdentityTransaction) -> IdentityState {
+        exercise!("Exercise 1", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Identity Registry".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+
+/// Everybody is on the council, Alice and Bob have 100, and Charlie is a registrar who charges 5.
+#[cfg(test)]
+fn registry() -> IdentityState {
+    IdentityState {
+        balances: BTreeMap::from([(User::Alice, 100), (User::Bob, 100)]),
+        registrars: BTreeMap::from([(User::Charlie, 5)]),
+        council: BTreeSet::from([User::Alice, User::Bob, User::Charlie]),
+        ..IdentityState::default()
+    }
+}
+
+#[cfg(test)]
+fn set_identity(who: User, info: u64) -> IdentityTransaction {
+    IdentityTransaction::SetIdentity {
+        who,
+        info: H256::from(info),
+    }
+}
+
+#[cfg(test)]
+fn request(who: User) -> IdentityTransaction {
+    IdentityTransaction::RequestJudgement {
+        who,
+        registrar: User::Charlie,
+        max_fee: 5,
+    }
+}
+
+#[cfg(test)]
+fn judge(target: User, info: u64) -> IdentityTransaction {
+    IdentityTransaction::ProvideJudgement {
+        registrar: User::Charlie,
+        target,
+        info: H256::from(info),
+        judgement: Judgement::KnownGood,
+    }
+}
+
+#[test]
+fn sm_20_identity_reserves_deposit() {
+    let state = apply_all::<IdentityRegistry>(registry(), &[set_identity(User::Alice, 1)]);
+    assert_eq!(state.balances[&User::Alice], 100 - IDENTITY_DEPOSIT);
+    assert_eq!(state.identities[&User::Alice].info, H256::from(1));
+
+    // Changing the identity does not reserve another deposit.
+    let state = apply_all::<IdentityRegistry>(state, &[set_identity(User::Alice, 2)]);
+    assert_eq!(state.balances[&User::Alice], 100 - IDENTITY_DEPOSIT);
+
+    // Charlie has nothing to reserve.
+    assert_eq!(
+        apply_all::<IdentityRegistry>(state.clone(), &[set_identity(User::Charlie, 3)]),
+        state
+    );
+
+    let cleared = apply_all::<IdentityRegistry>(
+        state,
+        &[IdentityTransaction::ClearIdentity { who: User::Alice }],
+    );
+    assert_eq!(cleared, registry());
+}
+
+#[test]
+fn sm_20_judgements_are_paid_on_delivery() {
+    let state = registry();
+    // Nobody can be judged without an identity, or without asking.
+    assert_eq!(
+        apply_all::<IdentityRegistry>(state.clone(), &[request(User::Alice)]),
+        state
+    );
+    let state = apply_all::<IdentityRegistry>(state, &[set_identity(User::Alice, 1)]);
+    assert_eq!(
+        apply_all::<IdentityRegistry>(state.clone(), &[judge(User::Alice, 1)]),
+        state
+    );
+    let too_cheap = IdentityTransaction::RequestJudgement {
+        who: User::Alice,
+        registrar: User::Charlie,
+        max_fee: 4,
+    };
+    assert_eq!(
+        apply_all::<IdentityRegistry>(state.clone(), &[too_cheap]),
+        state
+    );
+
+    let state = apply_all::<IdentityRegistry>(state, &[request(User::Alice)]);
+    assert_eq!(state.balances[&User::Alice], 100 - IDENTITY_DEPOSIT - 5);
+    assert_eq!(state.balances.get(&User::Charlie), None);
+    assert_eq!(
+        apply_all::<IdentityRegistry>(state.clone(), &[request(User::Alice)]),
+        state
+    );
+
+    // The registrar must judge the metadata that is actually registered.
+    assert_eq!(
+        apply_all::<IdentityRegistry>(state.clone(), &[judge(User::Alice, 2)]),
+        state
+    );
+    let state = apply_all::<IdentityRegistry>(state, &[judge(User::Alice, 1)]);
+    assert_eq!(state.balances[&User::Charlie], 5);
+    assert_eq!(
+        state.identities[&User::Alice].judgements,
+        BTreeMap::from([(User::Charlie, Judgement::KnownGood)])
+    );
+}
+
+#[test]
+fn sm_20_judgements_go_away() {
+    let judged = apply_all::<IdentityRegistry>(
+        registry(),
+        &[
+            set_identity(User::Alice, 1),
+            request(User::Alice),
+            judge(User::Alice, 1),
+        ],
+    );
+    let revoke = IdentityTransaction::RevokeJudgement {
+        registrar: User::Charlie,
+        target: User::Alice,
+    };
+    let revoked = apply_all::<IdentityRegistry>(judged.clone(), &[revoke]);
+    assert!(revoked.identities[&User::Alice].judgements.is_empty());
+
+    let changed = apply_all::<IdentityRegistry>(judged, &[set_identity(User::Alice, 2)]);
+    assert!(changed.identities[&User::Alice].judgements.is_empty());
+
+    // A request that was not answered yet is refunded when the identity is cleared.
+    let state = apply_all::<IdentityRegistry>(
+        changed,
+        &[
+            request(User::Alice),
+            IdentityTransaction::ClearIdentity { who: User::Alice },
+        ],
+    );
+    assert_eq!(state.balances[&User::Alice], 95);
+}
+
+#[test]
+fn sm_20_council_manages_registrars() {
+    let approve = |councillor, motion| IdentityTransaction::Approve { councillor, motion };
+    let add_bob = RegistrarMotion::Add {
+        registrar: User::Bob,
+        fee: 1,
+    };
+    let mut outsiders = registry();
+    outsiders.council = BTreeSet::from([User::Alice]);
+    let state = apply_all::<IdentityRegistry>(outsiders.clone(), &[approve(User::Bob, add_bob)]);
+    assert_eq!(state, outsiders);
+
+    // One of three councillors is not a majority, two are.
+    let state = apply_all::<IdentityRegistry>(registry(), &[approve(User::Alice, add_bob)]);
+    assert_eq!(state.registrars.get(&User::Bob), None);
+    let state = apply_all::<IdentityRegistry>(state, &[approve(User::Charlie, add_bob)]);
+    assert_eq!(state.registrars[&User::Bob], 1);
+    assert!(state.motions.is_empty());
+
+    // Removing a registrar refunds the requests they did not answer.
+    let remove = RegistrarMotion::Remove {
+        registrar: User::Charlie,
+    };
+    let state = apply_all::<IdentityRegistry>(
+        state,
+        &[
+            set_identity(User::Alice, 1),
+            request(User::Alice),
+            approve(User::Alice, remove),
+            approve(User::Bob, remove),
+        ],
+    );
+    assert_eq!(state.registrars.get(&User::Charlie), None);
+    assert_eq!(state.balances[&User::Alice], 100 - IDENTITY_DEPOSIT);
+    assert!(state.identities[&User::Alice].requests.is_empty());
+}
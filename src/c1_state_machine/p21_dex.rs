@@ -0,0 +1,260 @@
+//! An exchange usually matches buyers with sellers through an order book. On chain, every order
+//! costs a transaction, and an order book with few traders has wide spreads or no price at all.
+//! Uniswap showed a simpler way: an _automated market maker_. Anybody who wants to provide
+//! liquidity deposits two assets into a pool, and traders swap against the pool directly.
+//!
+//! The pool prices swaps with the constant product rule. It holds reserves `x` and `y` of its
+//! two assets, and a swap may only change them so that `x * y` stays the same. Buying much of one
+//! asset drains its reserve, which drives its price up, so the pool never runs dry. On top of
+//! that, every swap pays a fee of `SWAP_FEE_PER_MILLE` of what goes in. The fee stays in the pool,
+//! so `x * y` actually grows with every swap, and that growth is what liquidity providers earn.
+//!
+//! Providers get shares of the pool in return for their deposit, and redeem them for their part
+//! of both reserves. The first provider sets the price. Everybody after them deposits in
+//! proportion to the reserves, and gets shares in proportion to their deposit. Rounding always
+//! goes the pool's way, so no sequence of deposits and withdrawals drains it.
+//!
+//! The assets live in a tiny multi-asset currency: every user has a balance of every asset, and
+//! can transfer any of them.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use super::{StateMachine, User};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p21_dex.rs"]
+mod solution;
+
+/// An exchange of automated market makers.
+pub struct Dex;
+
+/// Identifies an asset.
+pub type AssetId = u32;
+
+/// How much of every swap's input, in thousandths, is paid as a fee to the pool.
+pub const SWAP_FEE_PER_MILLE: u64 = 3;
+
+/// A pool of two assets.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pool {
+    /// The reserves of the pool's two assets, the lower asset id first.
+    pub reserves: (u64, u64),
+    /// The shares of the pool, by provider.
+    pub shares: BTreeMap<User, u64>,
+    /// All shares together.
+    pub total_shares: u64,
+}
+
+/// The state of the exchange.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DexState {
+    /// Everybody's balance of every asset, not counting what is in pools.
+    pub balances: BTreeMap<(User, AssetId), u64>,
+    /// The pools, by their two assets, the lower asset id first.
+    pub pools: BTreeMap<(AssetId, AssetId), Pool>,
+}
+
+/// The transitions of the exchange.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DexTransaction {
+    /// Transfer an amount of an asset.
+    Transfer {
+        sender: User,
+        receiver: User,
+        asset: AssetId,
+        amount: u64,
+    },
+    /// Provide liquidity to the pool of the two given assets, in the same order as the amounts.
+    /// If there is no pool yet, all of both amounts go into a new one, and the provider gets the
+    /// square root of their product in shares. Otherwise, the provider gets as many shares as
+    /// the more scarce of the two amounts buys, and only deposits what these shares are worth.
+    AddLiquidity {
+        who: User,
+        assets: (AssetId, AssetId),
+        amounts: (u64, u64),
+    },
+    /// Redeem shares of the pool of the two given assets for their part of both reserves. The
+    /// pool is removed when its last share is redeemed.
+    RemoveLiquidity {
+        who: User,
+        assets: (AssetId, AssetId),
+        shares: u64,
+    },
+    /// Swap an amount of one asset for another, through their pool. The swap fails if it would
+    /// give less than `min_out`, which protects the trader from prices that moved in the meantime.
+    Swap {
+        who: User,
+        asset_in: AssetId,
+        asset_out: AssetId,
+        amount_in: u64,
+        min_out: u64,
+    },
+}
+
+/// How much a swap of `amount_in` gives out of a pool with the given reserves. The fee is taken
+/// from the input, and what is left of it must keep the product of the reserves constant. The
+/// result is rounded down.
+pub fn swap_output(reserve_in: u64, reserve_out: u64, amount_in: u64) -> u64 {
+    exercise!(
+        "Exercise 1",
+        solution::swap_output(reserve_in, reserve_out, amount_in)
+    )
+}
+
+impl StateMachine for Dex {
+    type State = DexState;
+    type Transition = DexTransaction;
+
+    /// Anything somebody can't pay for, pools of an asset with itself, swaps through pools that
+    /// don't exist or that give less than asked for, and deposits or withdrawals that round to
+    /// nothing, leave the state untouched.
+    fn next_state(starting_state: &DexState, t: &DexTransaction) -> DexState {
+        exercise!("Exercise 2", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Decentralized Exchange".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+
+/// Alice and Bob have 10_000 of assets 0 and 1 each, and Alice opened a pool with 1000 of asset
+/// 0 and 4000 of asset 1.
+#[cfg(test)]
+fn exchange() -> DexState {
+    let mut balances = BTreeMap::new();
+    for who in [User::Alice, User::Bob] {
+        for asset in [0, 1] {
+            balances.insert((who, asset), 10_000);
+        }
+    }
+    let state = DexState {
+        balances,
+        pools: BTreeMap::new(),
+    };
+    apply_all::<Dex>(state, &[add(User::Alice, 1_000, 4_000)])
+}
+
+#[cfg(test)]
+fn add(who: User, amount_0: u64, amount_1: u64) -> DexTransaction {
+    DexTransaction::AddLiquidity {
+        who,
+        assets: (0, 1),
+        amounts: (amount_0, amount_1),
+    }
+}
+
+#[cfg(test)]
+fn swap(asset_in: AssetId, amount_in: u64, min_out: u64) -> DexTransaction {
+    DexTransaction::Swap {
+        who: User::Bob,
+        asset_in,
+        asset_out: 1 - asset_in,
+        amount_in,
+        min_out,
+    }
+}
+
+/// The product of the reserves of the pool.
+#[cfg(test)]
+fn product(state: &DexState) -> u128 {
+    let (x, y) = state.pools[&(0, 1)].reserves;
+    u128::from(x) * u128::from(y)
+}
+
+#[test]
+fn sm_21_first_provider_sets_price() {
+    let state = exchange();
+    let pool = &state.pools[&(0, 1)];
+    assert_eq!(pool.reserves, (1_000, 4_000));
+    assert_eq!(pool.total_shares, 2_000);
+    assert_eq!(pool.shares, BTreeMap::from([(User::Alice, 2_000)]));
+    assert_eq!(state.balances[&(User::Alice, 0)], 9_000);
+
+    // The order of the assets does not matter.
+    let reversed = DexTransaction::AddLiquidity {
+        who: User::Alice,
+        assets: (1, 0),
+        amounts: (4_000, 1_000),
+    };
+    let mut empty = exchange();
+    empty.pools.clear();
+    empty.balances.insert((User::Alice, 0), 10_000);
+    empty.balances.insert((User::Alice, 1), 10_000);
+    assert_eq!(apply_all::<Dex>(empty, &[reversed]), state);
+}
+
+#[test]
+fn sm_21_swaps_pay_fee_and_respect_slippage() {
+    // 0.3% of 100 is kept as a fee, and 99.7 buy 4000 * 99.7 / 1099.7 of asset 1.
+    assert_eq!(swap_output(1_000, 4_000, 100), 362);
+    assert_eq!(swap_output(1_000, 4_000, 0), 0);
+
+    let state = exchange();
+    assert_eq!(apply_all::<Dex>(state.clone(), &[swap(0, 100, 363)]), state);
+    let swapped = apply_all::<Dex>(state, &[swap(0, 100, 362)]);
+    assert_eq!(swapped.pools[&(0, 1)].reserves, (1_100, 3_638));
+    assert_eq!(swapped.balances[&(User::Bob, 0)], 9_900);
+    assert_eq!(swapped.balances[&(User::Bob, 1)], 10_362);
+}
+
+#[test]
+fn sm_21_liquidity_is_proportional() {
+    // Bob offers too much of asset 1, and only deposits what matches his 100 of asset 0.
+    let state = apply_all::<Dex>(exchange(), &[add(User::Bob, 100, 1_000)]);
+    let pool = &state.pools[&(0, 1)];
+    assert_eq!(pool.reserves, (1_100, 4_400));
+    assert_eq!(pool.shares[&User::Bob], 200);
+    assert_eq!(state.balances[&(User::Bob, 1)], 9_600);
+
+    let remove = |who, shares| DexTransaction::RemoveLiquidity {
+        who,
+        assets: (0, 1),
+        shares,
+    };
+    assert_eq!(
+        apply_all::<Dex>(state.clone(), &[remove(User::Bob, 201)]),
+        state
+    );
+    let state = apply_all::<Dex>(state, &[remove(User::Bob, 200)]);
+    assert_eq!(state.balances[&(User::Bob, 0)], 10_000);
+    assert_eq!(state.balances[&(User::Bob, 1)], 10_000);
+
+    let drained = apply_all::<Dex>(state, &[remove(User::Alice, 2_000)]);
+    assert!(drained.pools.is_empty());
+    assert_eq!(drained.balances[&(User::Alice, 1)], 10_000);
+}
+
+#[test]
+fn sm_21_product_never_decreases_but_by_withdrawals() {
+    let mut state = exchange();
+    for i in 1..=100u64 {
+        let before = state.clone();
+        let (k, shares) = (product(&before), before.pools[&(0, 1)].total_shares);
+        state = match i % 4 {
+            0 => apply_all::<Dex>(state, &[add(User::Bob, i, 4 * i + 3)]),
+            1 => apply_all::<Dex>(
+                state,
+                &[DexTransaction::RemoveLiquidity {
+                    who: User::Bob,
+                    assets: (0, 1),
+                    shares: i / 2,
+                }],
+            ),
+            _ => apply_all::<Dex>(state, &[swap((i % 2) as AssetId, i * 37 % 500 + 1, 0)]),
+        };
+
+        // Swaps only ever grow the product, by the fee.
+        let (k_after, shares_after) = (product(&state), state.pools[&(0, 1)].total_shares);
+        if shares_after == shares {
+            assert!(k_after >= k, "swap {i} shrank the product");
+        }
+        // Deposits and withdrawals move the product, but never the product per share squared.
+        let shares = u128::from(shares);
+        let shares_after = u128::from(shares_after);
+        assert!(k_after * shares * shares >= k * shares_after * shares_after);
+    }
+}
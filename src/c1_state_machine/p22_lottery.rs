@@ -0,0 +1,234 @@
+//! A lottery is only fair if nobody can influence who wins. That is hard on a blockchain, where
+//! every node must come to the same result, so the winner has to be picked from data that is on
+//! chain.
+//!
+//! The obvious choice is the hash of some block. But the author of that block chooses its hash:
+//! they can reorder extrinsics, or tweak a timestamp, and seal whichever variant makes them win.
+//! Even an author who can only seal or not seal gets to pick between two outcomes. The last test
+//! of this file grinds block hashes until the author's ticket wins, and it never takes long.
+//!
+//! This lottery uses the commit-reveal scheme from `crate::commit_reveal` instead. Every player
+//! buys a ticket with a commitment to a secret number. Once no more tickets are sold, the players
+//! reveal their numbers, and the winner is drawn from all revealed numbers together. As long as
+//! a single player picked their number at random, nobody could predict the result while they
+//! could still change their number.
+//!
+//! One lever is left: the last player to reveal knows the result with and without their number,
+//! and could keep it to themselves. That is why a ticket that is not revealed in time can't win,
+//! and the ticket price stays in the pot: withholding costs as much as a ticket, and only gives
+//! the withholder a second draw, not the win.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use super::{StateMachine, User};
+use crate::commit_reveal::Salt;
+use crate::hashing::H256;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p22_lottery.rs"]
+mod solution;
+
+/// A commit-reveal lottery.
+pub struct Lottery;
+
+/// What a ticket costs.
+pub const TICKET_PRICE: u64 = 10;
+
+/// The state of one round of the lottery.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LotteryState {
+    /// The current block number.
+    pub block: u64,
+    /// Everybody's money, not counting the pot.
+    pub balances: BTreeMap<User, u64>,
+    /// Tickets are sold before this block only, and revealed from it on.
+    pub commit_ends: u64,
+    /// Reveals are accepted before this block only, and the winner is drawn at it.
+    pub reveal_ends: u64,
+    /// The price of every ticket that was sold.
+    pub pot: u64,
+    /// The commitment of every player's ticket.
+    pub tickets: BTreeMap<User, H256>,
+    /// The numbers that were revealed so far, and matched their commitments.
+    pub reveals: BTreeMap<User, u64>,
+    /// Who won, once the winner was drawn.
+    pub winner: Option<User>,
+}
+
+/// The transitions of the lottery.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LotteryTransaction {
+    /// Buy a ticket with a commitment to a secret number, for `TICKET_PRICE`. Every player may
+    /// only buy one ticket.
+    BuyTicket { player: User, commitment: H256 },
+    /// Reveal the number of a ticket. A reveal that does not match the commitment is ignored.
+    Reveal {
+        player: User,
+        number: u64,
+        salt: Salt,
+    },
+    /// Move on to the next block. At `reveal_ends`, the winner is drawn from the reveals and
+    /// gets the whole pot. If nobody revealed, nobody wins, and the pot is lost.
+    NewBlock,
+}
+
+/// Pick a player at random, according to the given seed. None if there are no players.
+pub fn pick(seed: H256, players: &[User]) -> Option<User> {
+    let count = players.len() as u64;
+    (count > 0).then(|| players[(seed.low_u64() % count) as usize])
+}
+
+/// Draw the winner among the players who revealed. The seed is the hash of all of their numbers
+/// together, so that every single one of them changes the result.
+pub fn draw(reveals: &BTreeMap<User, u64>) -> Option<User> {
+    exercise!("Exercise 1", solution::draw(reveals))
+}
+
+impl StateMachine for Lottery {
+    type State = LotteryState;
+    type Transition = LotteryTransaction;
+
+    /// Tickets that are bought outside of the commit phase, a second time, or without the money
+    /// to pay for them, and reveals outside of the reveal phase, leave the state untouched.
+    fn next_state(starting_state: &LotteryState, t: &LotteryTransaction) -> LotteryState {
+        exercise!("Exercise 2", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Lottery".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+#[cfg(test)]
+use crate::commit_reveal::commit;
+#[cfg(test)]
+use crate::hashing::hash_of;
+
+/// Everybody has 100. Tickets are sold in blocks 0 and 1, and revealed in blocks 2 and 3.
+#[cfg(test)]
+fn lottery() -> LotteryState {
+    LotteryState {
+        balances: BTreeMap::from([(User::Alice, 100), (User::Bob, 100), (User::Charlie, 100)]),
+        commit_ends: 2,
+        reveal_ends: 4,
+        ..LotteryState::default()
+    }
+}
+
+/// Every player's secret number is their position in the alphabet, salted with it.
+#[cfg(test)]
+fn secret(player: User) -> (u64, Salt) {
+    let number = player as u64 + 1;
+    (number, [number as u8; 32])
+}
+
+#[cfg(test)]
+fn buy(player: User) -> LotteryTransaction {
+    let (number, salt) = secret(player);
+    LotteryTransaction::BuyTicket {
+        player,
+        commitment: commit(&number, &salt),
+    }
+}
+
+#[cfg(test)]
+fn reveal(player: User) -> LotteryTransaction {
+    let (number, salt) = secret(player);
+    LotteryTransaction::Reveal {
+        player,
+        number,
+        salt,
+    }
+}
+
+#[cfg(test)]
+const NEW_BLOCK: LotteryTransaction = LotteryTransaction::NewBlock;
+
+#[test]
+fn sm_22_tickets_sold_once_during_commit_phase() {
+    let state = apply_all::<Lottery>(lottery(), &[buy(User::Alice)]);
+    assert_eq!(state.balances[&User::Alice], 100 - TICKET_PRICE);
+    assert_eq!(state.pot, TICKET_PRICE);
+    assert_eq!(
+        apply_all::<Lottery>(state.clone(), &[buy(User::Alice)]),
+        state
+    );
+
+    let mut broke = state.clone();
+    broke.balances.remove(&User::Bob);
+    assert_eq!(
+        apply_all::<Lottery>(broke.clone(), &[buy(User::Bob)]),
+        broke
+    );
+
+    let late = apply_all::<Lottery>(state, &[NEW_BLOCK, NEW_BLOCK]);
+    assert_eq!(apply_all::<Lottery>(late.clone(), &[buy(User::Bob)]), late);
+}
+
+#[test]
+fn sm_22_reveals_must_match_during_reveal_phase() {
+    let state = apply_all::<Lottery>(lottery(), &[buy(User::Alice), buy(User::Bob)]);
+    assert_eq!(
+        apply_all::<Lottery>(state.clone(), &[reveal(User::Alice)]),
+        state
+    );
+
+    let state = apply_all::<Lottery>(state, &[NEW_BLOCK, NEW_BLOCK]);
+    let lie = LotteryTransaction::Reveal {
+        player: User::Alice,
+        number: 7,
+        salt: secret(User::Alice).1,
+    };
+    assert_eq!(apply_all::<Lottery>(state.clone(), &[lie]), state);
+    assert_eq!(
+        apply_all::<Lottery>(state.clone(), &[reveal(User::Charlie)]),
+        state
+    );
+
+    let revealed = apply_all::<Lottery>(state, &[reveal(User::Alice)]);
+    assert_eq!(revealed.reveals, BTreeMap::from([(User::Alice, 1)]));
+}
+
+#[test]
+fn sm_22_winner_drawn_from_reveals_takes_pot() {
+    let state = apply_all::<Lottery>(
+        lottery(),
+        &[buy(User::Alice), buy(User::Bob), buy(User::Charlie)],
+    );
+    // Charlie does not reveal, so Charlie can't win, and the ticket stays in the pot.
+    let state = apply_all::<Lottery>(
+        state,
+        &[NEW_BLOCK, NEW_BLOCK, reveal(User::Alice), reveal(User::Bob)],
+    );
+    let winner = draw(&state.reveals).unwrap();
+    assert_ne!(winner, User::Charlie);
+
+    let state = apply_all::<Lottery>(state.clone(), &[NEW_BLOCK]);
+    assert_eq!(state.winner, None);
+    let state = apply_all::<Lottery>(state, &[NEW_BLOCK]);
+    assert_eq!(state.winner, Some(winner));
+    assert_eq!(state.pot, 0);
+    assert_eq!(
+        state.balances[&winner],
+        100 - TICKET_PRICE + 3 * TICKET_PRICE
+    );
+    assert_eq!(
+        apply_all::<Lottery>(state.clone(), &[NEW_BLOCK]).balances,
+        state.balances
+    );
+}
+
+/// If the winner were picked by the hash of a block, its author would simply try block after
+/// block until they win.
+#[test]
+fn sm_22_block_hash_draw_can_be_ground() {
+    let players = [User::Alice, User::Bob, User::Charlie];
+    let author = User::Charlie;
+    let attempts = (0..64u64)
+        .map(|nonce| hash_of("block", &nonce))
+        .position(|block_hash| pick(block_hash, &players) == Some(author));
+    assert!(attempts.is_some());
+}
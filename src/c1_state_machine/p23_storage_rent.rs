@@ -0,0 +1,272 @@
+//! Every node keeps the whole state, forever. A fee paid once, when an entry is written, pays
+//! for the write, but not for the years of disk space that follow. Left alone, the state only
+//! grows, and with it the cost of running a node. This is known as _state bloat_.
+//!
+//! One answer is to make storage an ongoing cost. Here, every entry pays rent for every block it
+//! stays in the state, in proportion to its size. The rent comes out of a deposit that belongs to
+//! the entry itself, much like a contract pays out of its own balance. Whoever stores an entry
+//! pays the first deposit, and anybody may top it up. An owner who removes their entry gets back
+//! what is left of its deposit, so cleaning up pays.
+//!
+//! An entry whose deposit can't pay the rent of a block _expires_: its value is dropped from the
+//! state, and only a small tombstone with the hash of the value remains. From there:
+//! * Anybody who still knows the value can revive the entry, with a new deposit. The hash proves
+//!   that they did not change it.
+//! * Anybody can reap the tombstone, which removes the entry for good. The reaper gets what was
+//!   left of the entry's deposit, so that somebody always has a reason to clean up.
+//!
+//! Polkadot had rent for its contracts for a while, and Ethereum has discussed state expiry for
+//! years. Both ran into the same problem: users hate paying for state they already own, and
+//! forgetting to top up a deposit can break applications that other users depend on.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{StateMachine, User};
+use crate::hashing::H256;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p23_storage_rent.rs"]
+mod solution;
+
+/// Storage that pays rent.
+pub struct StorageRent;
+
+/// What every byte of an entry costs per block.
+pub const RENT_PER_BYTE: u64 = 1;
+
+/// How many bytes every entry takes up on top of its value, for its key and its deposit.
+pub const ENTRY_OVERHEAD: u64 = 32;
+
+/// Identifies an entry: the user who owns it, and a key of the owner's choice.
+pub type EntryKey = (User, u64);
+
+/// An entry in the state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub value: Vec<u8>,
+    /// What is left to pay the entry's rent with.
+    pub deposit: u64,
+}
+
+/// What remains of an entry that expired.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tombstone {
+    /// The hash of the entry's value, `crate::hashing::hash(&value)`.
+    pub hash: H256,
+    /// What was left of the entry's deposit when it expired.
+    pub deposit: u64,
+}
+
+/// The state of the storage.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RentState {
+    /// The current block number.
+    pub block: u64,
+    /// Everybody's money, not counting deposits.
+    pub balances: BTreeMap<User, u64>,
+    /// The entries that paid their rent so far.
+    pub entries: BTreeMap<EntryKey, Entry>,
+    /// The entries that expired, and were neither revived nor reaped yet.
+    pub tombstones: BTreeMap<EntryKey, Tombstone>,
+}
+
+/// The transitions of the storage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RentTransaction {
+    /// Store a value under a key of the signer's that is neither taken nor expired. The deposit
+    /// must pay at least one block of rent.
+    Store {
+        who: User,
+        key: u64,
+        value: Vec<u8>,
+        deposit: u64,
+    },
+    /// Add to the deposit of an entry. Anybody may pay for anybody's entry.
+    TopUp {
+        who: User,
+        entry: EntryKey,
+        amount: u64,
+    },
+    /// Remove one of the signer's entries, and get back what is left of its deposit.
+    Remove { who: User, key: u64 },
+    /// Bring an expired entry back with its original value. The new deposit, together with what
+    /// was left of the old one, must pay at least one block of rent.
+    Revive {
+        who: User,
+        entry: EntryKey,
+        value: Vec<u8>,
+        deposit: u64,
+    },
+    /// Remove an expired entry for good, and collect what was left of its deposit.
+    Reap { who: User, entry: EntryKey },
+    /// Move on to the next block. Every entry pays the rent for the new block out of its
+    /// deposit, and the rent is burned. Entries whose deposit is too small expire instead, and
+    /// keep their deposit in their tombstone.
+    NewBlock,
+}
+
+/// The rent that an entry with the given value pays for a single block: `RENT_PER_BYTE` for
+/// every byte of the value, and for the `ENTRY_OVERHEAD`.
+pub fn rent(value: &[u8]) -> u64 {
+    exercise!("Exercise 1", solution::rent(value))
+}
+
+impl StateMachine for StorageRent {
+    type State = RentState;
+    type Transition = RentTransaction;
+
+    /// Transitions by users who can't pay for them, on entries that are not in the right state,
+    /// and revivals with the wrong value, leave the state untouched.
+    fn next_state(starting_state: &RentState, t: &RentTransaction) -> RentState {
+        exercise!("Exercise 2", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Storage Rent".into()
+    }
+}
+
+#[cfg(test)]
+use super::apply_all;
+#[cfg(test)]
+use crate::hashing::hash;
+
+/// Alice and Bob have 1000 each.
+#[cfg(test)]
+fn storage() -> RentState {
+    RentState {
+        balances: BTreeMap::from([(User::Alice, 1_000), (User::Bob, 1_000)]),
+        ..RentState::default()
+    }
+}
+
+/// Alice stores eight bytes under key 0, which cost 40 per block.
+#[cfg(test)]
+fn store(deposit: u64) -> RentTransaction {
+    RentTransaction::Store {
+        who: User::Alice,
+        key: 0,
+        value: VALUE.to_vec(),
+        deposit,
+    }
+}
+
+#[cfg(test)]
+const VALUE: [u8; 8] = *b"contract";
+
+#[cfg(test)]
+const ENTRY: EntryKey = (User::Alice, 0);
+
+#[cfg(test)]
+const NEW_BLOCK: RentTransaction = RentTransaction::NewBlock;
+
+#[test]
+fn sm_23_rent_is_charged_every_block() {
+    assert_eq!(rent(&[]), ENTRY_OVERHEAD);
+    assert_eq!(rent(&VALUE), 40);
+
+    let state = apply_all::<StorageRent>(storage(), &[store(100)]);
+    assert_eq!(state.balances[&User::Alice], 900);
+    assert_eq!(state.entries[&ENTRY].deposit, 100);
+
+    let state = apply_all::<StorageRent>(state, &[NEW_BLOCK, NEW_BLOCK]);
+    assert_eq!(state.entries[&ENTRY].deposit, 20);
+
+    // 20 does not pay for another block.
+    let state = apply_all::<StorageRent>(state, &[NEW_BLOCK]);
+    assert!(state.entries.is_empty());
+    assert_eq!(
+        state.tombstones,
+        BTreeMap::from([(
+            ENTRY,
+            Tombstone {
+                hash: hash(&VALUE[..]),
+                deposit: 20
+            }
+        )])
+    );
+}
+
+#[test]
+fn sm_23_deposits_topped_up_and_refunded() {
+    let state = storage();
+    for invalid in [store(39), store(1_001)] {
+        assert_eq!(apply_all::<StorageRent>(state.clone(), &[invalid]), state);
+    }
+
+    let state = apply_all::<StorageRent>(state, &[store(40), store(100)]);
+    assert_eq!(state.balances[&User::Alice], 960);
+
+    let top_up = RentTransaction::TopUp {
+        who: User::Bob,
+        entry: ENTRY,
+        amount: 60,
+    };
+    let state = apply_all::<StorageRent>(state, &[top_up, NEW_BLOCK]);
+    assert_eq!(state.balances[&User::Bob], 940);
+    assert_eq!(state.entries[&ENTRY].deposit, 60);
+
+    // Only the owner may remove the entry, and gets back the deposit.
+    let remove = |who| RentTransaction::Remove { who, key: 0 };
+    assert_eq!(
+        apply_all::<StorageRent>(state.clone(), &[remove(User::Bob)]),
+        state
+    );
+    let state = apply_all::<StorageRent>(state, &[remove(User::Alice)]);
+    assert!(state.entries.is_empty());
+    assert_eq!(state.balances[&User::Alice], 1_020);
+}
+
+#[test]
+fn sm_23_expired_entries_revived_with_original_value() {
+    let expired = apply_all::<StorageRent>(storage(), &[store(50), NEW_BLOCK, NEW_BLOCK]);
+    assert_eq!(expired.tombstones[&ENTRY].deposit, 10);
+    // Expired keys can't be stored over.
+    assert_eq!(
+        apply_all::<StorageRent>(expired.clone(), &[store(100)]),
+        expired
+    );
+
+    let revive = |value: &[u8], deposit| RentTransaction::Revive {
+        who: User::Bob,
+        entry: ENTRY,
+        value: value.to_vec(),
+        deposit,
+    };
+    for invalid in [revive(b"tampered", 100), revive(&VALUE, 29)] {
+        assert_eq!(
+            apply_all::<StorageRent>(expired.clone(), &[invalid]),
+            expired
+        );
+    }
+
+    let revived = apply_all::<StorageRent>(expired, &[revive(&VALUE, 30)]);
+    assert!(revived.tombstones.is_empty());
+    assert_eq!(
+        revived.entries[&ENTRY],
+        Entry {
+            value: VALUE.to_vec(),
+            deposit: 40
+        }
+    );
+    assert_eq!(revived.balances[&User::Bob], 970);
+}
+
+#[test]
+fn sm_23_reapers_collect_leftover_deposit() {
+    let expired = apply_all::<StorageRent>(storage(), &[store(50), NEW_BLOCK, NEW_BLOCK]);
+    let reap = RentTransaction::Reap {
+        who: User::Charlie,
+        entry: ENTRY,
+    };
+    let reaped = apply_all::<StorageRent>(expired, core::slice::from_ref(&reap));
+    assert!(reaped.tombstones.is_empty());
+    assert_eq!(reaped.balances[&User::Charlie], 10);
+
+    // Once reaped, the entry is gone for good, and the key is free again.
+    assert_eq!(apply_all::<StorageRent>(reaped.clone(), &[reap]), reaped);
+    let stored = apply_all::<StorageRent>(reaped, &[store(40)]);
+    assert_eq!(stored.entries[&ENTRY].deposit, 40);
+}
@@ -45,7 +45,12 @@ impl StateMachine for ClothesMachine {
             return ClothesState::Tattered;
         }
 
-        if *starting_state == ClothesState::Clean(1) || *starting_state == ClothesState::Wet(1) || *starting_state == ClothesState::Dirty(1) {
+        // Clothes with no life left at all are worn out too. Taking one more from them would
+        // underflow.
+        if matches!(
+            starting_state,
+            ClothesState::Clean(0 | 1) | ClothesState::Wet(0 | 1) | ClothesState::Dirty(0 | 1)
+        ) {
             return ClothesState::Tattered;
         }
 
@@ -239,3 +244,11 @@ fn sm_2_dry_dirty_until_tattered() {
     let expected = ClothesState::Tattered;
     assert_eq!(end, expected);
 }
+
+#[test]
+fn sm_2_no_life_left_is_tattered() {
+    let start = ClothesState::Clean(0);
+    let end = ClothesMachine::next_state(&start, &ClothesAction::Wear);
+    let expected = ClothesState::Tattered;
+    assert_eq!(end, expected);
+}
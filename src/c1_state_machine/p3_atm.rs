@@ -2,9 +2,11 @@
 //! The atm may fail to give you cash if it is empty or you haven't swiped your card, or you have
 //! entered the wrong pin.
 
-use std::clone;
+use alloc::vec::Vec;
 
 use super::StateMachine;
+use crate::crypto::ct_eq;
+use crate::hashing::H256;
 
 /// The keys on the ATM keypad
 #[derive(Hash, Debug, PartialEq, Eq, Clone)]
@@ -20,7 +22,7 @@ pub enum Key {
 pub enum Action {
     /// Swipe your card at the ATM. The attached value is the hash of the pin
     /// that should be keyed in on the keypad next.
-    SwipeCard(u64),
+    SwipeCard(H256),
     /// Press a key on the keypad
     PressKey(Key),
 }
@@ -38,7 +40,7 @@ enum Auth {
     Waiting,
     /// The user has swiped their card, providing the enclosed PIN hash.
     /// Waiting for the user to key in their pin
-    Authenticating(u64),
+    Authenticating(H256),
     /// The user has authenticated. Waiting for them to key in the amount
     /// of cash to withdraw
     Authenticated,
@@ -60,6 +62,22 @@ pub struct Atm {
     keystroke_register: Vec<Key>,
 }
 
+impl Atm {
+    /// An ATM with the given amount of cash inside, waiting for a card to be swiped.
+    pub fn new(cash_inside: u64) -> Self {
+        Atm {
+            cash_inside,
+            expected_pin_hash: Auth::Waiting,
+            keystroke_register: Vec::new(),
+        }
+    }
+
+    /// How much cash is left in the ATM.
+    pub fn cash_inside(&self) -> u64 {
+        self.cash_inside
+    }
+}
+
 impl StateMachine for Atm {
     // Notice that we are using the same type for the state as we are using for the machine this time.
     type State = Self;
@@ -83,10 +101,10 @@ impl StateMachine for Atm {
                     return starting_state.clone();
                 }
                 let pin = starting_state.keystroke_register.clone();
-                let pin_hash = crate::hash(&pin);
+                let pin_hash = crate::hashing::pin_hash(&pin);
                 match &starting_state.expected_pin_hash {
                     Auth::Authenticating(expected_hash) => {
-                        if *expected_hash == pin_hash {
+                        if ct_eq(expected_hash.as_bytes(), pin_hash.as_bytes()) {
                             Atm {
                                 cash_inside: starting_state.cash_inside,
                                 expected_pin_hash: Auth::Authenticated,
@@ -102,12 +120,17 @@ impl StateMachine for Atm {
                     }
                     Auth::Authenticated => {
                         let amount_keys = starting_state.keystroke_register.clone();
-                        let amount = amount_keys.iter().fold(0, |acc, key| match key {
-                            Key::One => acc * 10 + 1,
-                            Key::Two => acc * 10 + 2,
-                            Key::Three => acc * 10 + 3,
-                            Key::Four => acc * 10 + 4,
-                            _ => acc,
+                        // Saturate, so that a very long amount is simply too much rather than
+                        // an overflow.
+                        let amount = amount_keys.iter().fold(0u64, |acc, key| {
+                            let digit = match key {
+                                Key::One => 1,
+                                Key::Two => 2,
+                                Key::Three => 3,
+                                Key::Four => 4,
+                                _ => return acc,
+                            };
+                            acc.saturating_mul(10).saturating_add(digit)
                         });
                         if amount > starting_state.cash_inside {
                             Atm {
@@ -206,10 +229,10 @@ fn sm_3_simple_swipe_card() {
         expected_pin_hash: Auth::Waiting,
         keystroke_register: Vec::new(),
     };
-    let end = Atm::next_state(&start, &Action::SwipeCard(1234));
+    let end = Atm::next_state(&start, &Action::SwipeCard(H256::from(1234)));
     let expected = Atm {
         cash_inside: 10,
-        expected_pin_hash: Auth::Authenticating(1234),
+        expected_pin_hash: Auth::Authenticating(H256::from(1234)),
         keystroke_register: Vec::new(),
     };
 
@@ -220,13 +243,13 @@ fn sm_3_simple_swipe_card() {
 fn sm_3_swipe_card_again_part_way_through() {
     let start = Atm {
         cash_inside: 10,
-        expected_pin_hash: Auth::Authenticating(1234),
+        expected_pin_hash: Auth::Authenticating(H256::from(1234)),
         keystroke_register: Vec::new(),
     };
-    let end = Atm::next_state(&start, &Action::SwipeCard(1234));
+    let end = Atm::next_state(&start, &Action::SwipeCard(H256::from(1234)));
     let expected = Atm {
         cash_inside: 10,
-        expected_pin_hash: Auth::Authenticating(1234),
+        expected_pin_hash: Auth::Authenticating(H256::from(1234)),
         keystroke_register: Vec::new(),
     };
 
@@ -234,13 +257,13 @@ fn sm_3_swipe_card_again_part_way_through() {
 
     let start = Atm {
         cash_inside: 10,
-        expected_pin_hash: Auth::Authenticating(1234),
+        expected_pin_hash: Auth::Authenticating(H256::from(1234)),
         keystroke_register: vec![Key::One, Key::Three],
     };
-    let end = Atm::next_state(&start, &Action::SwipeCard(1234));
+    let end = Atm::next_state(&start, &Action::SwipeCard(H256::from(1234)));
     let expected = Atm {
         cash_inside: 10,
-        expected_pin_hash: Auth::Authenticating(1234),
+        expected_pin_hash: Auth::Authenticating(H256::from(1234)),
         keystroke_register: vec![Key::One, Key::Three],
     };
 
@@ -268,13 +291,13 @@ fn sm_3_press_key_before_card_swipe() {
 fn sm_3_enter_single_digit_of_pin() {
     let start = Atm {
         cash_inside: 10,
-        expected_pin_hash: Auth::Authenticating(1234),
+        expected_pin_hash: Auth::Authenticating(H256::from(1234)),
         keystroke_register: Vec::new(),
     };
     let end = Atm::next_state(&start, &Action::PressKey(Key::One));
     let expected = Atm {
         cash_inside: 10,
-        expected_pin_hash: Auth::Authenticating(1234),
+        expected_pin_hash: Auth::Authenticating(H256::from(1234)),
         keystroke_register: vec![Key::One],
     };
 
@@ -282,13 +305,13 @@ fn sm_3_enter_single_digit_of_pin() {
 
     let start = Atm {
         cash_inside: 10,
-        expected_pin_hash: Auth::Authenticating(1234),
+        expected_pin_hash: Auth::Authenticating(H256::from(1234)),
         keystroke_register: vec![Key::One],
     };
     let end1 = Atm::next_state(&start, &Action::PressKey(Key::Two));
     let expected1 = Atm {
         cash_inside: 10,
-        expected_pin_hash: Auth::Authenticating(1234),
+        expected_pin_hash: Auth::Authenticating(H256::from(1234)),
         keystroke_register: vec![Key::One, Key::Two],
     };
 
@@ -299,7 +322,7 @@ fn sm_3_enter_single_digit_of_pin() {
 fn sm_3_enter_wrong_pin() {
     // Create hash of pin
     let pin = vec![Key::One, Key::Two, Key::Three, Key::Four];
-    let pin_hash = crate::hash(&pin);
+    let pin_hash = crate::hashing::pin_hash(&pin);
 
     let start = Atm {
         cash_inside: 10,
@@ -320,7 +343,7 @@ fn sm_3_enter_wrong_pin() {
 fn sm_3_enter_correct_pin() {
     // Create hash of pin
     let pin = vec![Key::One, Key::Two, Key::Three, Key::Four];
-    let pin_hash = crate::hash(&pin);
+    let pin_hash = crate::hashing::pin_hash(&pin);
 
     let start = Atm {
         cash_inside: 10,
@@ -385,6 +408,23 @@ fn sm_3_try_to_withdraw_too_much() {
     assert_eq!(end, expected);
 }
 
+#[test]
+fn sm_3_try_to_withdraw_more_than_fits_in_a_number() {
+    let start = Atm {
+        cash_inside: 10,
+        expected_pin_hash: Auth::Authenticated,
+        keystroke_register: vec![Key::Four; 30],
+    };
+    let end = Atm::next_state(&start, &Action::PressKey(Key::Enter));
+    let expected = Atm {
+        cash_inside: 10,
+        expected_pin_hash: Auth::Waiting,
+        keystroke_register: Vec::new(),
+    };
+
+    assert_eq!(end, expected);
+}
+
 #[test]
 fn sm_3_withdraw_acceptable_amount() {
     let start = Atm {
@@ -9,7 +9,12 @@
 //! Each user is associated with an account balance and users are able to send money to other users.
 
 use super::{StateMachine, User};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::collections::HashMap;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p4_accounted_currency.rs"]
+mod solution;
 
 /// This state machine models a multi-user currency system. It tracks the balance of each
 /// user and allows users to send funds to one another.
@@ -24,6 +29,7 @@ pub struct AccountedCurrency;
 type Balances = HashMap<User, u64>;
 
 /// The state transitions that users can make in an accounted currency system
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AccountingTransaction {
     /// Create some new money for the given minter in the given amount
     Mint { minter: User, amount: u64 },
@@ -45,7 +51,7 @@ impl StateMachine for AccountedCurrency {
     type Transition = AccountingTransaction;
 
     fn next_state(starting_state: &Balances, t: &AccountingTransaction) -> Balances {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::next_state(starting_state, t))
     }
 }
 
@@ -323,3 +329,66 @@ fn sm_4_transfer() {
 
     assert_eq!(end, expected);
 }
+
+/// The extended tests of the `check` binary, which `cargo test` leaves out.
+#[cfg(all(test, feature = "check"))]
+mod check {
+    use super::*;
+
+    #[test]
+    fn sm_4_check_transfers_conserve_money() {
+        let start = HashMap::from([(User::Alice, 100), (User::Bob, 50)]);
+        let transfers = [
+            (User::Alice, User::Bob, 30),
+            (User::Bob, User::Charlie, 80),
+            (User::Charlie, User::Charlie, 10),
+            (User::Charlie, User::Alice, 81),
+            (User::Bob, User::Alice, 0),
+            (User::Charlie, User::Alice, 80),
+        ];
+        let end = transfers
+            .iter()
+            .fold(start, |state, &(sender, receiver, amount)| {
+                AccountedCurrency::next_state(
+                    &state,
+                    &AccountingTransaction::Transfer {
+                        sender,
+                        receiver,
+                        amount,
+                    },
+                )
+            });
+
+        assert_eq!(end.values().sum::<u64>(), 150);
+        assert_eq!(end, HashMap::from([(User::Alice, 150)]));
+    }
+
+    #[test]
+    fn sm_4_check_transfer_opens_receiver_account() {
+        let start = HashMap::from([(User::Alice, 100)]);
+        let end = AccountedCurrency::next_state(
+            &start,
+            &AccountingTransaction::Transfer {
+                sender: User::Alice,
+                receiver: User::Charlie,
+                amount: 1,
+            },
+        );
+
+        assert_eq!(end, HashMap::from([(User::Alice, 99), (User::Charlie, 1)]));
+    }
+
+    #[test]
+    fn sm_4_check_burn_never_opens_an_account() {
+        let start = HashMap::from([(User::Alice, 100)]);
+        let end = AccountedCurrency::next_state(
+            &start,
+            &AccountingTransaction::Burn {
+                burner: User::Bob,
+                amount: 0,
+            },
+        );
+
+        assert_eq!(end, start);
+    }
+}
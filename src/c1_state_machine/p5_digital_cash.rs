@@ -2,9 +2,21 @@
 //! accounts, but rather, is modelled after a paper cash system. The system tracks individual
 //! cash bills. Each bill has an amount and an owner, and can be spent in its entirety.
 //! When a state transition spends bills, new bills are created in lesser or equal amount.
+//!
+//! Only the owner of a bill may spend it, so every spend is signed by the bill's owner. The
+//! signatures are real ones, made with the development keys of the keystore, which is why this
+//! exercise needs the `std` feature.
+
+use alloc::vec::Vec;
 
 use super::{StateMachine, User};
-use std::collections::HashSet;
+use crate::c4_client::keystore::Signature;
+use crate::collections::HashSet;
+use crate::hashing::{hash_of, H256};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p5_digital_cash.rs"]
+mod solution;
 
 /// This state machine models a multi-user currency system. It tracks a set of bills in
 /// circulation, and updates that set when money is transferred.
@@ -33,7 +45,7 @@ pub struct State {
 impl State {
     pub fn new() -> Self {
         State {
-            bills: HashSet::<Bill>::new(),
+            bills: HashSet::<Bill>::default(),
             next_serial: 0,
         }
     }
@@ -82,19 +94,49 @@ pub enum CashTransaction {
     /// The total amount received must be less than or equal to the amount spent.
     /// The discrepancy between the amount sent and received is destroyed. Therefore,
     /// no dedicated burn transaction is required.
+    ///
+    /// Every spent bill must be signed for by its owner: `signatures` holds one signature per
+    /// spent bill, in the same order, over the `transfer_hash` of the spends and receives.
     Transfer {
         spends: Vec<Bill>,
         receives: Vec<Bill>,
+        signatures: Vec<Signature>,
     },
 }
 
+impl CashTransaction {
+    /// The hash that the owners of the spent bills sign to authorize a transfer.
+    pub fn transfer_hash(spends: &[Bill], receives: &[Bill]) -> H256 {
+        hash_of("cash transfer", &(spends, receives))
+    }
+}
+
 /// We model this system as a state machine with two possible transitions
 impl StateMachine for DigitalCashSystem {
     type State = State;
     type Transition = CashTransaction;
 
     fn next_state(starting_state: &Self::State, t: &Self::Transition) -> Self::State {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::next_state(starting_state, t))
+    }
+}
+
+#[cfg(test)]
+use crate::c4_client::keystore::Keystore;
+
+/// A transfer, with every spend signed by the owner of the bill.
+#[cfg(test)]
+fn transfer(spends: Vec<Bill>, receives: Vec<Bill>) -> CashTransaction {
+    let keystore = Keystore::dev();
+    let hash = CashTransaction::transfer_hash(&spends, &receives);
+    let signatures = spends
+        .iter()
+        .map(|bill| keystore.sign(bill.owner, hash).unwrap())
+        .collect();
+    CashTransaction::Transfer {
+        spends,
+        receives,
+        signatures,
     }
 }
 
@@ -126,13 +168,13 @@ fn sm_5_overflow_receives_fails() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Alice,
                 amount: 42,
                 serial: 0,
             }],
-            receives: vec![
+            vec![
                 Bill {
                     owner: User::Alice,
                     amount: u64::MAX,
@@ -144,7 +186,7 @@ fn sm_5_overflow_receives_fails() {
                     serial: 2,
                 },
             ],
-        },
+        ),
     );
     let expected = State::from([Bill {
         owner: User::Alice,
@@ -163,14 +205,14 @@ fn sm_5_empty_spend_fails() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![],
-            receives: vec![Bill {
+        &transfer(
+            vec![],
+            vec![Bill {
                 owner: User::Alice,
                 amount: 15,
                 serial: 1,
             }],
-        },
+        ),
     );
     let expected = State::from([Bill {
         owner: User::Alice,
@@ -189,14 +231,14 @@ fn sm_5_empty_receive_fails() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Alice,
                 amount: 20,
                 serial: 0,
             }],
-            receives: vec![],
-        },
+            vec![],
+        ),
     );
     let mut expected = State::from([]);
     expected.set_serial(1);
@@ -212,18 +254,18 @@ fn sm_5_output_value_0_fails() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Alice,
                 amount: 20,
                 serial: 0,
             }],
-            receives: vec![Bill {
+            vec![Bill {
                 owner: User::Bob,
                 amount: 0,
                 serial: 1,
             }],
-        },
+        ),
     );
     let expected = State::from([Bill {
         owner: User::Alice,
@@ -242,18 +284,18 @@ fn sm_5_serial_number_already_seen_fails() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Alice,
                 amount: 20,
                 serial: 0,
             }],
-            receives: vec![Bill {
+            vec![Bill {
                 owner: User::Alice,
                 amount: 18,
                 serial: 0,
             }],
-        },
+        ),
     );
     let expected = State::from([Bill {
         owner: User::Alice,
@@ -272,18 +314,18 @@ fn sm_5_spending_and_receiving_same_bill_fails() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Alice,
                 amount: 20,
                 serial: 0,
             }],
-            receives: vec![Bill {
+            vec![Bill {
                 owner: User::Alice,
                 amount: 20,
                 serial: 0,
             }],
-        },
+        ),
     );
     let expected = State::from([Bill {
         owner: User::Alice,
@@ -302,13 +344,13 @@ fn sm_5_receiving_bill_with_incorrect_serial_fails() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Alice,
                 amount: 20,
                 serial: 0,
             }],
-            receives: vec![
+            vec![
                 Bill {
                     owner: User::Alice,
                     amount: 10,
@@ -320,7 +362,7 @@ fn sm_5_receiving_bill_with_incorrect_serial_fails() {
                     serial: 4000,
                 },
             ],
-        },
+        ),
     );
     let expected = State::from([Bill {
         owner: User::Alice,
@@ -339,18 +381,18 @@ fn sm_5_spending_bill_with_incorrect_amount_fails() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Alice,
                 amount: 40,
                 serial: 0,
             }],
-            receives: vec![Bill {
+            vec![Bill {
                 owner: User::Bob,
                 amount: 40,
                 serial: 1,
             }],
-        },
+        ),
     );
     let expected = State::from([Bill {
         owner: User::Alice,
@@ -369,8 +411,8 @@ fn sm_5_spending_same_bill_fails() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![
+        &transfer(
+            vec![
                 Bill {
                     owner: User::Alice,
                     amount: 40,
@@ -382,7 +424,7 @@ fn sm_5_spending_same_bill_fails() {
                     serial: 0,
                 },
             ],
-            receives: vec![
+            vec![
                 Bill {
                     owner: User::Bob,
                     amount: 20,
@@ -399,7 +441,7 @@ fn sm_5_spending_same_bill_fails() {
                     serial: 3,
                 },
             ],
-        },
+        ),
     );
     let expected = State::from([Bill {
         owner: User::Alice,
@@ -425,8 +467,8 @@ fn sm_5_spending_more_than_bill_fails() {
     ]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![
+        &transfer(
+            vec![
                 Bill {
                     owner: User::Alice,
                     amount: 40,
@@ -438,7 +480,7 @@ fn sm_5_spending_more_than_bill_fails() {
                     serial: 1,
                 },
             ],
-            receives: vec![
+            vec![
                 Bill {
                     owner: User::Bob,
                     amount: 20,
@@ -455,7 +497,7 @@ fn sm_5_spending_more_than_bill_fails() {
                     serial: 4,
                 },
             ],
-        },
+        ),
     );
     let expected = State::from([
         Bill {
@@ -481,18 +523,18 @@ fn sm_5_spending_non_existent_bill_fails() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Bob,
                 amount: 1000,
                 serial: 32,
             }],
-            receives: vec![Bill {
+            vec![Bill {
                 owner: User::Bob,
                 amount: 1000,
                 serial: 33,
             }],
-        },
+        ),
     );
     let expected = State::from([Bill {
         owner: User::Alice,
@@ -511,13 +553,13 @@ fn sm_5_spending_from_alice_to_all() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Alice,
                 amount: 42,
                 serial: 0,
             }],
-            receives: vec![
+            vec![
                 Bill {
                     owner: User::Alice,
                     amount: 10,
@@ -534,7 +576,7 @@ fn sm_5_spending_from_alice_to_all() {
                     serial: 3,
                 },
             ],
-        },
+        ),
     );
     let mut expected = State::from([
         Bill {
@@ -566,13 +608,13 @@ fn sm_5_spending_from_bob_to_all() {
     }]);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Bob,
                 amount: 42,
                 serial: 0,
             }],
-            receives: vec![
+            vec![
                 Bill {
                     owner: User::Alice,
                     amount: 10,
@@ -589,7 +631,7 @@ fn sm_5_spending_from_bob_to_all() {
                     serial: 3,
                 },
             ],
-        },
+        ),
     );
     let mut expected = State::from([
         Bill {
@@ -629,13 +671,13 @@ fn sm_5_spending_from_charlie_to_all() {
     start.set_serial(59);
     let end = DigitalCashSystem::next_state(
         &start,
-        &CashTransaction::Transfer {
-            spends: vec![Bill {
+        &transfer(
+            vec![Bill {
                 owner: User::Charlie,
                 amount: 68,
                 serial: 54,
             }],
-            receives: vec![
+            vec![
                 Bill {
                     owner: User::Alice,
                     amount: 42,
@@ -652,7 +694,7 @@ fn sm_5_spending_from_charlie_to_all() {
                     serial: 61,
                 },
             ],
-        },
+        ),
     );
     let mut expected = State::from([
         Bill {
@@ -679,3 +721,99 @@ fn sm_5_spending_from_charlie_to_all() {
     expected.set_serial(62);
     assert_eq!(end, expected);
 }
+
+#[test]
+fn sm_5_spending_someone_elses_bill_fails() {
+    let start = State::from([Bill {
+        owner: User::Alice,
+        amount: 20,
+        serial: 0,
+    }]);
+    let spends = vec![Bill {
+        owner: User::Alice,
+        amount: 20,
+        serial: 0,
+    }];
+    let receives = vec![Bill {
+        owner: User::Bob,
+        amount: 20,
+        serial: 1,
+    }];
+    let hash = CashTransaction::transfer_hash(&spends, &receives);
+    let forged = Keystore::dev().sign(User::Bob, hash).unwrap();
+
+    let end = DigitalCashSystem::next_state(
+        &start,
+        &CashTransaction::Transfer {
+            spends,
+            receives,
+            signatures: vec![forged],
+        },
+    );
+    assert_eq!(end, start);
+}
+
+#[test]
+fn sm_5_unsigned_spend_fails() {
+    let start = State::from([Bill {
+        owner: User::Alice,
+        amount: 20,
+        serial: 0,
+    }]);
+    let end = DigitalCashSystem::next_state(
+        &start,
+        &CashTransaction::Transfer {
+            spends: vec![Bill {
+                owner: User::Alice,
+                amount: 20,
+                serial: 0,
+            }],
+            receives: vec![Bill {
+                owner: User::Bob,
+                amount: 20,
+                serial: 1,
+            }],
+            signatures: vec![],
+        },
+    );
+    assert_eq!(end, start);
+}
+
+#[test]
+fn sm_5_signature_does_not_cover_other_receives() {
+    let start = State::from([Bill {
+        owner: User::Alice,
+        amount: 20,
+        serial: 0,
+    }]);
+    let spends = vec![Bill {
+        owner: User::Alice,
+        amount: 20,
+        serial: 0,
+    }];
+    let CashTransaction::Transfer { signatures, .. } = transfer(
+        spends.clone(),
+        vec![Bill {
+            owner: User::Bob,
+            amount: 20,
+            serial: 1,
+        }],
+    ) else {
+        unreachable!()
+    };
+
+    // Alice agreed to pay Bob, not Charlie.
+    let end = DigitalCashSystem::next_state(
+        &start,
+        &CashTransaction::Transfer {
+            spends,
+            receives: vec![Bill {
+                owner: User::Charlie,
+                amount: 20,
+                serial: 1,
+            }],
+            signatures,
+        },
+    );
+    assert_eq!(end, start);
+}
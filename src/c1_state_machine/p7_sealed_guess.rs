@@ -0,0 +1,175 @@
+//! A guessing game played in the open. The host thinks of a secret number, and every player
+//! gets one guess. The player whose guess is closest to the secret wins.
+//!
+//! If the guesses were made in the open, the last player to guess could simply look at the
+//! others and pick a number right next to the best one. And if the secret were known up front,
+//! there would be no game at all. So both the host and the players use the commit-reveal scheme
+//! from `crate::commit_reveal`: everyone first commits to their number, and only reveals it once
+//! every commitment is in.
+
+use alloc::collections::BTreeMap;
+
+use super::{StateMachine, User};
+use crate::commit_reveal::Salt;
+use crate::hashing::H256;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p7_sealed_guess.rs"]
+mod solution;
+
+/// A sealed-bid guessing game.
+pub struct SealedGuess;
+
+/// The state of one round of the game.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GameState {
+    /// Players may commit to their guesses.
+    Committing {
+        /// The host's commitment to the secret.
+        secret: H256,
+        /// Each player's commitment to their guess.
+        commitments: BTreeMap<User, H256>,
+    },
+    /// No more guesses are accepted, and players may reveal the guesses they committed to.
+    Revealing {
+        secret: H256,
+        commitments: BTreeMap<User, H256>,
+        /// The guesses that were revealed so far and matched their commitments.
+        guesses: BTreeMap<User, u64>,
+    },
+    /// The host has revealed the secret, and the round is over.
+    Finished {
+        /// The secret.
+        secret: u64,
+        /// The player whose revealed guess was closest to the secret. When two guesses are
+        /// equally close, the lower one wins. None if nobody revealed a guess.
+        winner: Option<User>,
+    },
+}
+
+/// Something that happens in the game.
+pub enum GameAction {
+    /// A player commits to a guess. Only allowed while committing, and only once per player.
+    Commit { player: User, commitment: H256 },
+    /// The host ends the commit phase.
+    CloseCommits,
+    /// A player reveals their guess. Only allowed while revealing. A reveal that does not match
+    /// the player's commitment is ignored.
+    Reveal { player: User, guess: u64, salt: Salt },
+    /// The host reveals the secret, which ends the round. A reveal that does not match the
+    /// host's commitment is ignored. Players who have not revealed by then lose their guess.
+    RevealSecret { secret: u64, salt: Salt },
+}
+
+impl SealedGuess {
+    /// Start a round with the host's commitment to the secret.
+    pub fn new_round(secret: H256) -> GameState {
+        GameState::Committing {
+            secret,
+            commitments: BTreeMap::new(),
+        }
+    }
+}
+
+impl StateMachine for SealedGuess {
+    type State = GameState;
+    type Transition = GameAction;
+
+    fn next_state(starting_state: &GameState, t: &GameAction) -> GameState {
+        exercise!("Exercise 1", solution::next_state(starting_state, t))
+    }
+}
+
+#[cfg(test)]
+use crate::commit_reveal::commit;
+
+/// A round in which the host committed to 50, and Alice and Bob committed to 40 and 57.
+#[cfg(test)]
+fn revealing_round() -> GameState {
+    let mut state = SealedGuess::new_round(commit(&50u64, &[0; 32]));
+    for (player, guess, salt) in [(User::Alice, 40u64, [1; 32]), (User::Bob, 57, [2; 32])] {
+        let commitment = commit(&guess, &salt);
+        state = SealedGuess::next_state(&state, &GameAction::Commit { player, commitment });
+    }
+    SealedGuess::next_state(&state, &GameAction::CloseCommits)
+}
+
+#[test]
+fn sm_7_commit_once_per_player() {
+    let start = SealedGuess::new_round(commit(&50u64, &[0; 32]));
+    let first = GameAction::Commit { player: User::Alice, commitment: commit(&40u64, &[1; 32]) };
+    let second = GameAction::Commit { player: User::Alice, commitment: commit(&50u64, &[1; 32]) };
+
+    let once = SealedGuess::next_state(&start, &first);
+    let twice = SealedGuess::next_state(&once, &second);
+    assert_eq!(once, twice);
+    assert_eq!(
+        once,
+        GameState::Committing {
+            secret: commit(&50u64, &[0; 32]),
+            commitments: BTreeMap::from([(User::Alice, commit(&40u64, &[1; 32]))]),
+        }
+    );
+}
+
+#[test]
+fn sm_7_no_commits_after_closing() {
+    let start = revealing_round();
+    let late = GameAction::Commit { player: User::Charlie, commitment: commit(&50u64, &[3; 32]) };
+    assert_eq!(SealedGuess::next_state(&start, &late), start);
+}
+
+#[test]
+fn sm_7_reveal_must_match_commitment() {
+    let start = revealing_round();
+    let lie = GameAction::Reveal { player: User::Alice, guess: 49, salt: [1; 32] };
+    assert_eq!(SealedGuess::next_state(&start, &lie), start);
+
+    let truth = GameAction::Reveal { player: User::Alice, guess: 40, salt: [1; 32] };
+    let GameState::Revealing { guesses, .. } = SealedGuess::next_state(&start, &truth) else {
+        panic!("still revealing");
+    };
+    assert_eq!(guesses, BTreeMap::from([(User::Alice, 40)]));
+}
+
+#[test]
+fn sm_7_closest_revealed_guess_wins() {
+    let mut state = revealing_round();
+    for reveal in [
+        GameAction::Reveal { player: User::Alice, guess: 40, salt: [1; 32] },
+        GameAction::Reveal { player: User::Bob, guess: 57, salt: [2; 32] },
+        GameAction::RevealSecret { secret: 50, salt: [0; 32] },
+    ] {
+        state = SealedGuess::next_state(&state, &reveal);
+    }
+    assert_eq!(state, GameState::Finished { secret: 50, winner: Some(User::Bob) });
+}
+
+#[test]
+fn sm_7_unrevealed_guesses_lose() {
+    let mut state = revealing_round();
+    for reveal in [
+        GameAction::Reveal { player: User::Alice, guess: 40, salt: [1; 32] },
+        GameAction::RevealSecret { secret: 50, salt: [0; 32] },
+    ] {
+        state = SealedGuess::next_state(&state, &reveal);
+    }
+    assert_eq!(state, GameState::Finished { secret: 50, winner: Some(User::Alice) });
+}
+
+#[test]
+fn sm_7_host_cannot_change_the_secret() {
+    let start = revealing_round();
+    let reveal = GameAction::Reveal { player: User::Alice, guess: 40, salt: [1; 32] };
+    let start = SealedGuess::next_state(&start, &reveal);
+
+    let lie = GameAction::RevealSecret { secret: 41, salt: [0; 32] };
+    assert_eq!(SealedGuess::next_state(&start, &lie), start);
+}
+
+#[test]
+fn sm_7_nobody_revealed() {
+    let start = revealing_round();
+    let end = SealedGuess::next_state(&start, &GameAction::RevealSecret { secret: 50, salt: [0; 32] });
+    assert_eq!(end, GameState::Finished { secret: 50, winner: None });
+}
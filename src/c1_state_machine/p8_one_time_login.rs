@@ -0,0 +1,180 @@
+//! A login server that never learns a password it could leak. Every user registers the anchor of
+//! a hash chain, from `crate::hash_chain`, and logs in by revealing the value before the anchor
+//! they registered. Each password works exactly once: after a successful login, the revealed
+//! value becomes the user's new anchor, and the next login needs the value before that one.
+//!
+//! An eavesdropper who sees a login, or an attacker who steals the server's whole state, only
+//! ever learns values that have already been used.
+
+use alloc::string::String;
+use alloc::collections::BTreeMap;
+
+use super::{StateMachine, User};
+use crate::hash_chain::verify_preimage;
+use crate::hashing::H256;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p8_one_time_login.rs"]
+mod solution;
+
+/// A login server that accepts hash chain one-time passwords.
+pub struct OneTimeLogin;
+
+/// What the server knows about a single user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoginAccount {
+    /// The value the next password must hash to.
+    pub anchor: H256,
+    /// The number of successful logins so far.
+    pub logins: u64,
+}
+
+/// Something a user asks of the server.
+pub enum LoginAction {
+    /// Register a new user with the anchor of their hash chain. Users who are already registered
+    /// are not allowed to register again: anybody could then take over their account.
+    Register { user: User, anchor: H256 },
+    /// Log in with the next password. A wrong password is ignored.
+    Login { user: User, password: H256 },
+    /// Log in, and switch to a new hash chain in the same step. Users do this when their chain
+    /// is about to run out. A wrong password is ignored, and the old chain stays in place.
+    Rotate {
+        user: User,
+        password: H256,
+        new_anchor: H256,
+    },
+}
+
+impl StateMachine for OneTimeLogin {
+    type State = BTreeMap<User, LoginAccount>;
+    type Transition = LoginAction;
+
+    fn next_state(starting_state: &Self::State, t: &LoginAction) -> Self::State {
+        exercise!("Exercise 1", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "One-time Login".into()
+    }
+}
+
+#[cfg(test)]
+use crate::hash_chain::HashChain;
+
+/// Alice's hash chain, and a server that she has registered its anchor with.
+#[cfg(test)]
+fn alice_registered() -> (HashChain, BTreeMap<User, LoginAccount>) {
+    let chain = HashChain::new(H256::from(1), 10);
+    let register = LoginAction::Register {
+        user: User::Alice,
+        anchor: chain.anchor(),
+    };
+    let state = OneTimeLogin::next_state(&BTreeMap::new(), &register);
+    (chain, state)
+}
+
+#[test]
+fn sm_8_register_once() {
+    let (chain, state) = alice_registered();
+    assert_eq!(
+        state,
+        BTreeMap::from([(
+            User::Alice,
+            LoginAccount {
+                anchor: chain.anchor(),
+                logins: 0
+            }
+        )])
+    );
+
+    let takeover = LoginAction::Register {
+        user: User::Alice,
+        anchor: H256::from(2),
+    };
+    assert_eq!(OneTimeLogin::next_state(&state, &takeover), state);
+}
+
+#[test]
+fn sm_8_login_with_next_password() {
+    let (mut chain, state) = alice_registered();
+    let password = chain.reveal().unwrap();
+    let login = LoginAction::Login {
+        user: User::Alice,
+        password,
+    };
+    let end = OneTimeLogin::next_state(&state, &login);
+    assert_eq!(
+        end[&User::Alice],
+        LoginAccount {
+            anchor: password,
+            logins: 1
+        }
+    );
+}
+
+#[test]
+fn sm_8_passwords_work_only_once() {
+    let (mut chain, state) = alice_registered();
+    let login = LoginAction::Login {
+        user: User::Alice,
+        password: chain.reveal().unwrap(),
+    };
+    let once = OneTimeLogin::next_state(&state, &login);
+    assert_eq!(OneTimeLogin::next_state(&once, &login), once);
+
+    let next = LoginAction::Login {
+        user: User::Alice,
+        password: chain.reveal().unwrap(),
+    };
+    assert_eq!(OneTimeLogin::next_state(&once, &next)[&User::Alice].logins, 2);
+}
+
+#[test]
+fn sm_8_wrong_password_or_user() {
+    let (mut chain, state) = alice_registered();
+    chain.reveal();
+    let skipped = LoginAction::Login {
+        user: User::Alice,
+        password: chain.reveal().unwrap(),
+    };
+    assert_eq!(OneTimeLogin::next_state(&state, &skipped), state);
+
+    let anchor = LoginAction::Login {
+        user: User::Alice,
+        password: chain.anchor(),
+    };
+    assert_eq!(OneTimeLogin::next_state(&state, &anchor), state);
+
+    let stranger = LoginAction::Login {
+        user: User::Bob,
+        password: chain.reveal().unwrap(),
+    };
+    assert_eq!(OneTimeLogin::next_state(&state, &stranger), state);
+}
+
+#[test]
+fn sm_8_rotate_to_a_new_chain() {
+    let (mut chain, state) = alice_registered();
+    let new_chain = HashChain::new(H256::from(2), 10);
+
+    let wrong = LoginAction::Rotate {
+        user: User::Alice,
+        password: H256::from(3),
+        new_anchor: new_chain.anchor(),
+    };
+    assert_eq!(OneTimeLogin::next_state(&state, &wrong), state);
+
+    let rotate = LoginAction::Rotate {
+        user: User::Alice,
+        password: chain.reveal().unwrap(),
+        new_anchor: new_chain.anchor(),
+    };
+    let end = OneTimeLogin::next_state(&state, &rotate);
+    assert_eq!(
+        end[&User::Alice],
+        LoginAccount {
+            anchor: new_chain.anchor(),
+            logins: 1
+        }
+    );
+}
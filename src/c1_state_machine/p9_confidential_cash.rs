@@ -0,0 +1,187 @@
+//! Digital cash that keeps its amounts secret. Like the digital cash from earlier, the state is
+//! a set of coins, and a transfer spends some coins to create new ones. But here a coin is only a
+//! Pedersen commitment to its amount, from `crate::crypto::pedersen`. Only the owner, who knows
+//! the amount and the blinding factor, can open it.
+//!
+//! Nobody else learns the amounts, but everybody can still check that a transfer creates no
+//! money. The sender reveals the excess of the transfer: the blinding factors of the inputs minus
+//! those of the outputs. The transfer is balanced exactly when the inputs minus the outputs are
+//! a commitment to zero with the excess as its blinding factor.
+//!
+//! Remember that the toy commitments have no range proofs, so this cash is not actually safe:
+//! an output of "minus one" is just a very large number. Real confidential transactions prove
+//! that every output is in range.
+
+use alloc::{string::String, vec::Vec};
+use alloc::collections::BTreeSet;
+
+use super::StateMachine;
+use crate::crypto::pedersen::{Blinding, Commitment};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c1_state_machine/p9_confidential_cash.rs"]
+mod solution;
+
+/// Cash with secret amounts.
+pub struct ConfidentialCash;
+
+/// Something that can happen to the cash.
+pub enum CashTransaction {
+    /// Create a new coin with a public amount. The blinding factor is public too, so the new
+    /// coin's owner should spend it to themselves before their balance is secret.
+    Mint { amount: u64, blinding: Blinding },
+    /// Spend some coins to create new ones.
+    Transfer {
+        inputs: Vec<Commitment>,
+        outputs: Vec<Commitment>,
+        /// The blinding factors of the inputs minus those of the outputs.
+        excess: Blinding,
+    },
+}
+
+/// Check that the inputs and outputs of a transfer hold the same amount, given its excess.
+pub fn is_balanced(inputs: &[Commitment], outputs: &[Commitment], excess: Blinding) -> bool {
+    exercise!("Exercise 1", solution::is_balanced(inputs, outputs, excess))
+}
+
+impl StateMachine for ConfidentialCash {
+    /// The coins that have not been spent yet.
+    type State = BTreeSet<Commitment>;
+    type Transition = CashTransaction;
+
+    /// A transfer is only valid if it spends at least one coin and creates at least one coin, if
+    /// every coin it spends exists and is spent only once, if it creates no coin that exists
+    /// already, and if it is balanced. Invalid transfers leave the state untouched.
+    fn next_state(starting_state: &Self::State, t: &CashTransaction) -> Self::State {
+        exercise!("Exercise 2", solution::next_state(starting_state, t))
+    }
+
+    fn human_name() -> String {
+        "Confidential Cash".into()
+    }
+}
+
+#[cfg(test)]
+use crate::crypto::pedersen::{commit, excess};
+
+/// A state with two coins of 50, with blinding factors 11 and 21.
+#[cfg(test)]
+fn two_coins() -> BTreeSet<Commitment> {
+    let state = ConfidentialCash::next_state(
+        &BTreeSet::new(),
+        &CashTransaction::Mint {
+            amount: 50,
+            blinding: 11,
+        },
+    );
+    ConfidentialCash::next_state(
+        &state,
+        &CashTransaction::Mint {
+            amount: 50,
+            blinding: 21,
+        },
+    )
+}
+
+#[test]
+fn sm_9_balanced_transfers() {
+    let inputs = [commit(50, 11), commit(50, 21)];
+    let outputs = [commit(70, 3), commit(30, 4)];
+    assert!(is_balanced(&inputs, &outputs, excess(&[11, 21], &[3, 4])));
+    assert!(!is_balanced(&inputs, &outputs, excess(&[11, 21], &[3, 5])));
+
+    let greedy = [commit(71, 3), commit(30, 4)];
+    assert!(!is_balanced(&inputs, &greedy, excess(&[11, 21], &[3, 4])));
+}
+
+#[test]
+fn sm_9_mint_creates_coin() {
+    assert_eq!(
+        two_coins(),
+        BTreeSet::from([commit(50, 11), commit(50, 21)])
+    );
+}
+
+#[test]
+fn sm_9_transfer_replaces_coins() {
+    let transfer = CashTransaction::Transfer {
+        inputs: vec![commit(50, 11), commit(50, 21)],
+        outputs: vec![commit(70, 3), commit(30, 4)],
+        excess: excess(&[11, 21], &[3, 4]),
+    };
+    assert_eq!(
+        ConfidentialCash::next_state(&two_coins(), &transfer),
+        BTreeSet::from([commit(70, 3), commit(30, 4)])
+    );
+
+    // Coins that are not spent stay around.
+    let partial = CashTransaction::Transfer {
+        inputs: vec![commit(50, 11)],
+        outputs: vec![commit(20, 1), commit(30, 2)],
+        excess: excess(&[11], &[1, 2]),
+    };
+    assert_eq!(
+        ConfidentialCash::next_state(&two_coins(), &partial),
+        BTreeSet::from([commit(50, 21), commit(20, 1), commit(30, 2)])
+    );
+}
+
+#[test]
+fn sm_9_unbalanced_transfer_rejected() {
+    let transfer = CashTransaction::Transfer {
+        inputs: vec![commit(50, 11), commit(50, 21)],
+        outputs: vec![commit(71, 3), commit(30, 4)],
+        excess: excess(&[11, 21], &[3, 4]),
+    };
+    assert_eq!(
+        ConfidentialCash::next_state(&two_coins(), &transfer),
+        two_coins()
+    );
+}
+
+#[test]
+fn sm_9_missing_or_repeated_coins_rejected() {
+    let missing = CashTransaction::Transfer {
+        inputs: vec![commit(50, 12)],
+        outputs: vec![commit(50, 3)],
+        excess: excess(&[12], &[3]),
+    };
+    assert_eq!(
+        ConfidentialCash::next_state(&two_coins(), &missing),
+        two_coins()
+    );
+
+    let twice = CashTransaction::Transfer {
+        inputs: vec![commit(50, 11), commit(50, 11)],
+        outputs: vec![commit(100, 3)],
+        excess: excess(&[11, 11], &[3]),
+    };
+    assert_eq!(
+        ConfidentialCash::next_state(&two_coins(), &twice),
+        two_coins()
+    );
+}
+
+#[test]
+fn sm_9_no_inputs_or_existing_outputs_rejected() {
+    // A coin worth nothing, made out of nothing, is balanced, but still not allowed.
+    let from_nothing = CashTransaction::Transfer {
+        inputs: vec![],
+        outputs: vec![commit(0, 5)],
+        excess: excess(&[], &[5]),
+    };
+    assert_eq!(
+        ConfidentialCash::next_state(&two_coins(), &from_nothing),
+        two_coins()
+    );
+
+    let existing = CashTransaction::Transfer {
+        inputs: vec![commit(50, 11)],
+        outputs: vec![commit(50, 21)],
+        excess: excess(&[11], &[21]),
+    };
+    assert_eq!(
+        ConfidentialCash::next_state(&two_coins(), &existing),
+        two_coins()
+    );
+}
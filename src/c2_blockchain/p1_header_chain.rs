@@ -3,11 +3,11 @@
 //! start with that.
 //!
 
-use crate::hash;
+use crate::hashing::{hash, H256};
 
-// We will use Rust's built-in hashing where the output type is u64. I'll make an alias
+// Hashes are 32 bytes, as on real chains. I'll make an alias
 // so the code is slightly more readable.
-type Hash = u64;
+type Hash = H256;
 
 /// The most basic blockchain header possible. We learned its basic structure from lecture.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -26,7 +26,7 @@ impl Header {
     /// Returns a new valid genesis header.
     fn genesis() -> Self {
         Header {
-            parent: 0,
+            parent: H256::zero(),
             height: 0,
             extrinsics_root: (),
             state_root: (),
@@ -108,7 +108,7 @@ fn bc_1_genesis_block_height() {
 #[test]
 fn bc_1_genesis_block_parent() {
     let g = Header::genesis();
-    assert!(g.parent == 0);
+    assert!(g.parent == H256::zero());
 }
 
 #[test]
@@ -158,7 +158,7 @@ fn bc_1_cant_verify_invalid_parent() {
     // not to give away the solution to writing that function.
     let g = Header::genesis();
     let mut b1 = g.child();
-    b1.parent = 10;
+    b1.parent = H256::from(10);
 
     assert!(!g.verify_sub_chain(&[b1]))
 }
@@ -178,3 +178,47 @@ fn bc_1_invalid_chain_is_really_invalid() {
     let invalid_chain = build_an_invalid_chain();
     assert!(!invalid_chain[0].verify_sub_chain(&invalid_chain[1..]))
 }
+
+/// The extended tests of the `check` binary, which `cargo test` leaves out.
+#[cfg(all(test, feature = "check"))]
+mod check {
+    use super::*;
+
+    fn chain(length: usize) -> Vec<Header> {
+        let mut chain = vec![Header::genesis()];
+        for _ in 1..length {
+            chain.push(chain.last().unwrap().child());
+        }
+        chain
+    }
+
+    #[test]
+    fn bc_1_check_long_chain() {
+        let chain = chain(100);
+        assert!(chain[0].verify_sub_chain(&chain[1..]));
+        assert_eq!(chain[99].height, 99);
+    }
+
+    #[test]
+    fn bc_1_check_verify_from_the_middle() {
+        let chain = chain(10);
+        assert!(chain[4].verify_sub_chain(&chain[5..]));
+        // The first block must be a child of the block we verify from.
+        assert!(!chain[4].verify_sub_chain(&chain[6..]));
+    }
+
+    #[test]
+    fn bc_1_check_gap_in_the_chain() {
+        let mut chain = chain(6);
+        chain.remove(3);
+        assert!(!chain[0].verify_sub_chain(&chain[1..]));
+    }
+
+    #[test]
+    fn bc_1_check_tampered_block_breaks_its_child() {
+        let mut chain = chain(5);
+        // Heights still count up, but block 3 no longer hashes to what block 4 says.
+        chain[3].parent = H256::from(3);
+        assert!(!chain[0].verify_sub_chain(&chain[1..]));
+    }
+}
@@ -9,11 +9,11 @@
 
 use std::process::Child;
 
-use crate::hash;
+use crate::hashing::{hash, H256};
 
-// We will use Rust's built-in hashing where the output type is u64. I'll make an alias
+// Hashes are 32 bytes, as on real chains. I'll make an alias
 // so the code is slightly more readable.
-type Hash = u64;
+type Hash = H256;
 
 /// The header is now expanded to contain an extrinsic and a state. Note that we are not
 /// using roots yet, but rather directly embedding some minimal extrinsic and state info
@@ -34,7 +34,7 @@ impl Header {
     /// Returns a new valid genesis header.
     fn genesis() -> Self {
         Header {
-            parent: 0,
+            parent: H256::zero(),
             height: 0,
             extrinsic: 0,
             state: 0,
@@ -170,7 +170,7 @@ fn bc_2_genesis_block_height() {
 #[test]
 fn bc_2_genesis_block_parent() {
     let g = Header::genesis();
-    assert!(g.parent == 0);
+    assert!(g.parent == H256::zero());
 }
 
 #[test]
@@ -236,7 +236,7 @@ fn bc_2_verify_three_blocks() {
 fn bc_2_cant_verify_invalid_parent() {
     let g = Header::genesis();
     let mut b1 = g.child(5);
-    b1.parent = 10;
+    b1.parent = H256::from(10);
 
     assert!(!g.verify_sub_chain(&[b1]));
 }
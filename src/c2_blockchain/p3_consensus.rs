@@ -4,15 +4,20 @@
 //! 1. Rules to throttle authoring. In this case we will use a simple PoW.
 //! 2. Arbitrary / Political rules. Here we will implement two alternate validity rules
 
-use crate::hash;
+use crate::hashing::{hash, H256};
 
-// We will use Rust's built-in hashing where the output type is u64. I'll make an alias
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c2_blockchain/p3_consensus.rs"]
+mod solution;
+
+// Hashes are 32 bytes, as on real chains. I'll make an alias
 // so the code is slightly more readable.
-type Hash = u64;
+type Hash = H256;
 
 /// In this lesson we are introducing proof of work onto our blocks. We need a hash threshold.
 /// You may change this as you see fit, and I encourage you to experiment. Probably best to start
 /// high so we aren't wasting time mining. I'll start with 1 in 100 blocks being valid.
+/// The threshold is compared with the last eight bytes of the hash. See `H256::low_u64`.
 const THRESHOLD: u64 = u64::max_value() / 100;
 
 /// In this lesson we introduce the concept of a contentious hard fork. The fork will happen at
@@ -37,12 +42,12 @@ pub struct Header {
 impl Header {
     /// Returns a new valid genesis header.
     fn genesis() -> Self {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::genesis())
     }
 
     /// Create and return a valid child header.
     fn child(&self, extrinsic: u64) -> Self {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", solution::child(self, extrinsic))
     }
 
     /// Verify that all the given headers form a valid chain from this header to the tip.
@@ -50,7 +55,7 @@ impl Header {
     /// In addition to all the rules we had before, we now need to check that the block hash
     /// is below a specific threshold.
     fn verify_sub_chain(&self, chain: &[Header]) -> bool {
-        todo!("Exercise 3")
+        exercise!("Exercise 3", solution::verify_sub_chain(self, chain))
     }
 
     // After the blockchain ran for a while, a political rift formed in the community.
@@ -62,13 +67,13 @@ impl Header {
     /// verify that the given headers form a valid chain.
     /// In this case "valid" means that the STATE MUST BE EVEN.
     fn verify_sub_chain_even(&self, chain: &[Header]) -> bool {
-        todo!("Exercise 4")
+        exercise!("Exercise 4", solution::verify_sub_chain_parity(self, chain, 0))
     }
 
     /// verify that the given headers form a valid chain.
     /// In this case "valid" means that the STATE MUST BE ODD.
     fn verify_sub_chain_odd(&self, chain: &[Header]) -> bool {
-        todo!("Exercise 5")
+        exercise!("Exercise 5", solution::verify_sub_chain_parity(self, chain, 1))
     }
 }
 
@@ -89,7 +94,7 @@ impl Header {
 /// G -- 1 -- 2
 ///            \-- 3'-- 4'
 fn build_contentious_forked_chain() -> (Vec<Header>, Vec<Header>, Vec<Header>) {
-    todo!("Exercise 6")
+    exercise!("Exercise 6", solution::build_contentious_forked_chain())
 }
 
 // To run these tests: `cargo test bc_3`
@@ -102,7 +107,7 @@ fn bc_3_genesis_block_height() {
 #[test]
 fn bc_3_genesis_block_parent() {
     let g = Header::genesis();
-    assert!(g.parent == 0);
+    assert!(g.parent == H256::zero());
 }
 
 #[test]
@@ -159,7 +164,7 @@ fn bc_3_child_block_state() {
 fn bc_3_child_block_consensus_digest() {
     let g = Header::genesis();
     let b1 = g.child(7);
-    assert!(hash(&b1) < THRESHOLD);
+    assert!(crate::difficulty::meets_threshold(hash(&b1), THRESHOLD));
 }
 
 #[test]
@@ -183,7 +188,7 @@ fn bc_3_verify_three_blocks() {
 fn bc_3_cant_verify_invalid_parent() {
     let g = Header::genesis();
     let mut b1 = g.child(5);
-    b1.parent = 10;
+    b1.parent = H256::from(10);
 
     assert!(!g.verify_sub_chain(&[b1]));
 }
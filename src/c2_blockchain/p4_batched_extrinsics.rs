@@ -1,8 +1,13 @@
 //! Until now, each block has contained just a single extrinsic. Really we would prefer to batch them.
 //! Now, we stop relying solely on headers, and instead, create complete blocks.
 
-use crate::hash;
-type Hash = u64;
+use crate::hashing::{hash, H256};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c2_blockchain/p4_batched_extrinsics.rs"]
+mod solution;
+
+type Hash = H256;
 
 /// The header no longer contains an extrinsic directly. Rather a vector of extrinsics will be stored in
 /// the block body. We are still storing the state in the header for now. This will change in an upcoming
@@ -28,14 +33,14 @@ pub struct Header {
 impl Header {
     /// Returns a new valid genesis header.
     pub fn genesis() -> Self {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::genesis_header())
     }
 
     /// Create and return a valid child header.
     /// Without the extrinsics themselves, we cannot calculate the final state
     /// so that information is passed in.
     pub fn child(&self, extrinsics_root: Hash, state: u64) -> Self {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", solution::child_header(self, extrinsics_root, state))
     }
 
     /// Verify a single child header.
@@ -46,7 +51,7 @@ impl Header {
     /// subtask of checking an entire block. So it doesn't make sense to check
     /// the entire header chain at once if the chain may be invalid at the second block.
     fn verify_child(&self, child: &Header) -> bool {
-        todo!("Exercise 3")
+        exercise!("Exercise 3", solution::verify_child(self, child))
     }
 
     /// Verify that all the given headers form a valid chain from this header to the tip.
@@ -57,7 +62,7 @@ impl Header {
     ///  * with head recursion
     ///  * with tail recursion
     fn verify_sub_chain(&self, chain: &[Header]) -> bool {
-        todo!("Exercise 4")
+        exercise!("Exercise 4", solution::verify_header_sub_chain(self, chain))
     }
 }
 
@@ -76,20 +81,20 @@ pub struct Block {
 impl Block {
     /// Returns a new valid genesis block. By convention this block has no extrinsics.
     pub fn genesis() -> Self {
-        todo!("Exercise 5")
+        exercise!("Exercise 5", solution::genesis_block())
     }
 
     /// Create and return a valid child block.
     /// The extrinsics are batched now, so we need to execute each of them.
     pub fn child(&self, extrinsics: Vec<u64>) -> Self {
-        todo!("Exercise 6")
+        exercise!("Exercise 6", solution::child_block(self, extrinsics))
     }
 
     /// Verify that all the given blocks form a valid chain from this block to the tip.
     ///
     /// We need to verify the headers as well as execute all transactions and check the final state.
     pub fn verify_sub_chain(&self, chain: &[Block]) -> bool {
-        todo!("Exercise 7")
+        exercise!("Exercise 7", solution::verify_block_sub_chain(self, chain))
     }
 }
 
@@ -102,14 +107,14 @@ impl Block {
 ///
 /// Notice that you do not need the entire parent block to do this. You only need the header.
 fn build_invalid_child_block_with_valid_header(parent: &Header) -> Block {
-    todo!("Exercise 8")
+    exercise!("Exercise 8", solution::build_invalid_child_block_with_valid_header(parent))
 }
 
 #[test]
 fn bc_4_genesis_header() {
     let g = Header::genesis();
     assert_eq!(g.height, 0);
-    assert_eq!(g.parent, 0);
+    assert_eq!(g.parent, H256::zero());
     assert_eq!(g.extrinsics_root, hash(&Vec::<u64>::new()));
     assert_eq!(g.state, 0);
 }
@@ -186,9 +191,9 @@ fn bc_4_verify_three_blocks() {
 fn bc_4_invalid_header_does_not_check() {
     let g = Header::genesis();
     let h1 = Header {
-        parent: 0,
+        parent: H256::zero(),
         height: 100,
-        extrinsics_root: 0,
+        extrinsics_root: H256::zero(),
         state: 100,
         consensus_digest: 0,
     };
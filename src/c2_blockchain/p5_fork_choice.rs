@@ -8,8 +8,13 @@
 //! we will import them from the previous lesson.
 
 use super::p4_batched_extrinsics::{Block, Header};
-use crate::hash;
+use crate::hashing::{hash, H256};
 
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c2_blockchain/p5_fork_choice.rs"]
+mod solution;
+
+// Work is judged by the last eight bytes of a block's hash, read as a number. See `H256::low_u64`.
 const THRESHOLD: u64 = u64::max_value() / 100;
 
 /// Judge which blockchain is "best" when there are multiple candidates. There are several
@@ -33,7 +38,7 @@ pub trait ForkChoice {
     /// two chains. Therefore this method has a provided implementation. However,
     /// it may be much more performant to write a fork-choice-specific implementation.
     fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> &'a [Header] {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::best_chain::<Self>(candidate_chains))
     }
 }
 
@@ -42,14 +47,14 @@ pub struct LongestChainRule;
 
 impl ForkChoice for LongestChainRule {
     fn first_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::longest_chain_is_better(chain_1, chain_2))
     }
 
     fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> &'a [Header] {
         // Remember, this method is provided. You _can_ solve the exercise by
         // simply deleting this block. It is up to you to decide whether this fork
         // choice warrants a custom implementation.
-        todo!("Exercise 3")
+        exercise!("Exercise 3", solution::best_chain::<Self>(candidate_chains))
     }
 }
 
@@ -68,17 +73,17 @@ pub struct HeaviestChainRule;
 /// usage is that you create a block using the normal `Block.child()` method
 /// and then pass the block to this helper for additional mining.
 fn mine_extra_hard(block: &mut Block, threshold: u64) {
-    todo!("Exercise 4")
+    exercise!("Exercise 4", solution::mine_extra_hard(block, threshold))
 }
 
 impl ForkChoice for HeaviestChainRule {
     fn first_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
-        todo!("Exercise 5")
+        exercise!("Exercise 5", solution::heaviest_chain_is_better(chain_1, chain_2))
     }
 
     fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> &'a [Header] {
         // Remember, this method is provided.
-        todo!("Exercise 6")
+        exercise!("Exercise 6", solution::best_chain::<Self>(candidate_chains))
     }
 }
 /// The best chain is the one with the most blocks that have even hashes.
@@ -99,12 +104,12 @@ pub struct MostBlocksWithEvenHash;
 
 impl ForkChoice for MostBlocksWithEvenHash {
     fn first_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
-        todo!("Exercise 7")
+        exercise!("Exercise 7", solution::most_even_hashes_is_better(chain_1, chain_2))
     }
 
     fn best_chain<'a>(candidate_chains: &[&'a [Header]]) -> &'a [Header] {
         // Remember, this method is provided.
-        todo!("Exercise 8")
+        exercise!("Exercise 8", solution::best_chain::<Self>(candidate_chains))
     }
 }
 
@@ -131,7 +136,7 @@ impl ForkChoice for MostBlocksWithEvenHash {
 /// 2. The suffix chain which is longer (non-overlapping with the common prefix)
 /// 3. The suffix chain with more work (non-overlapping with the common prefix)
 fn create_fork_one_side_longer_other_side_heavier() -> (Vec<Header>, Vec<Header>, Vec<Header>) {
-    todo!("Exercise 9")
+    exercise!("Exercise 9", solution::create_fork_one_side_longer_other_side_heavier())
 }
 
 #[test]
@@ -161,7 +166,7 @@ fn bc_5_mine_to_custom_difficulty() {
     let custom_threshold = u64::max_value() / 1000;
     mine_extra_hard(&mut b1, custom_threshold);
 
-    assert!(hash(&b1.header) < custom_threshold);
+    assert!(crate::difficulty::meets_threshold(hash(&b1.header), custom_threshold));
 }
 
 #[test]
@@ -172,7 +177,7 @@ fn bc_5_heaviest_chain() {
     let h_a1 = loop {
         let header = g.child(hash(&[i]), i);
         // Extrinsics root hash must be higher than threshold (less work done)
-        if hash(&header) > THRESHOLD {
+        if hash(&header).low_u64() > THRESHOLD {
             break header;
         }
         i += 1;
@@ -182,7 +187,7 @@ fn bc_5_heaviest_chain() {
     let h_b1 = loop {
         let header = g.child(hash(&[i]), i);
         // Extrinsics root hash must be lower than threshold (more work done)
-        if hash(&header) < THRESHOLD {
+        if hash(&header).low_u64() < THRESHOLD {
             break header;
         }
         i += 1;
@@ -198,33 +203,33 @@ fn bc_5_heaviest_chain() {
 fn bc_5_most_even_blocks() {
     let g = Header::genesis();
 
-    let mut h_a1 = g.child(2, 0);
+    let mut h_a1 = g.child(H256::from(2), 0);
     for i in 0..u64::max_value() {
-        h_a1 = g.child(2, i);
-        if hash(&h_a1) % 2 == 0 {
+        h_a1 = g.child(H256::from(2), i);
+        if hash(&h_a1).low_u64() % 2 == 0 {
             break;
         }
     }
-    let mut h_a2 = g.child(2, 0);
+    let mut h_a2 = g.child(H256::from(2), 0);
     for i in 0..u64::max_value() {
-        h_a2 = h_a1.child(2, i);
-        if hash(&h_a2) % 2 == 0 {
+        h_a2 = h_a1.child(H256::from(2), i);
+        if hash(&h_a2).low_u64() % 2 == 0 {
             break;
         }
     }
     let chain_1 = &[g.clone(), h_a1, h_a2];
 
-    let mut h_b1 = g.child(2, 0);
+    let mut h_b1 = g.child(H256::from(2), 0);
     for i in 0..u64::max_value() {
-        h_b1 = g.child(2, i);
-        if hash(&h_b1) % 2 != 0 {
+        h_b1 = g.child(H256::from(2), i);
+        if hash(&h_b1).low_u64() % 2 != 0 {
             break;
         }
     }
-    let mut h_b2 = g.child(2, 0);
+    let mut h_b2 = g.child(H256::from(2), 0);
     for i in 0..u64::max_value() {
-        h_b2 = h_b1.child(2, i);
-        if hash(&h_b2) % 2 != 0 {
+        h_b2 = h_b1.child(H256::from(2), i);
+        if hash(&h_b2).low_u64() % 2 != 0 {
             break;
         }
     }
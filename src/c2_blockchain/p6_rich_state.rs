@@ -11,8 +11,13 @@
 //! This notion of state may sound familiar from our previous work on state machines. Indeed this
 //! naming coincidence foreshadows a key abstraction that we will make in a coming chapter.
 
-type Hash = u64;
-use crate::hash;
+type Hash = H256;
+use crate::c1_state_machine::{NftState, NftTransaction};
+use crate::hashing::{hash, H256};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c2_blockchain/p6_rich_state.rs"]
+mod solution;
 
 /// In this section we will use sum and product together to be our state. While this is only a doubling of state size
 /// remember that in real world blockchains, the state is often really really large.
@@ -46,7 +51,7 @@ pub struct Header {
 impl Header {
     /// Returns a new valid genesis header.
     fn genesis(genesis_state_root: Hash) -> Self {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::genesis_header(genesis_state_root))
     }
 
     /// Create and return a valid child header.
@@ -54,17 +59,17 @@ impl Header {
     /// The state root is passed in similarly to how the complete state
     /// was in the previous section.
     fn child(&self, extrinsics_root: Hash, state_root: Hash) -> Self {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", solution::child_header(self, extrinsics_root, state_root))
     }
 
     /// Verify a single child header.
     fn verify_child(&self, child: &Header) -> bool {
-        todo!("Exercise 3")
+        exercise!("Exercise 3", solution::verify_child(self, child))
     }
 
     /// Verify that all the given headers form a valid chain from this header to the tip.
     fn verify_sub_chain(&self, chain: &[Header]) -> bool {
-        todo!("Exercise 4")
+        exercise!("Exercise 4", solution::verify_header_sub_chain(self, chain))
     }
 }
 
@@ -88,12 +93,12 @@ pub struct Block {
 impl Block {
     /// Returns a new valid genesis block. By convention this block has no extrinsics.
     pub fn genesis(genesis_state: &State) -> Self {
-        todo!("Exercise 5")
+        exercise!("Exercise 5", solution::genesis_block(genesis_state))
     }
 
     /// Create and return a valid child block.
     pub fn child(&self, pre_state: &State, extrinsics: Vec<u64>) -> Self {
-        todo!("Exercise 6")
+        exercise!("Exercise 6", solution::child_block(self, pre_state, extrinsics))
     }
 
     /// Verify that all the given blocks form a valid chain from this block to the tip.
@@ -102,7 +107,7 @@ impl Block {
     /// have been given a valid pre-state. And we still need to verify the headers,
     /// execute all transactions, and check the final state.
     pub fn verify_sub_chain(&self, pre_state: &State, chain: &[Block]) -> bool {
-        todo!("Exercise 7")
+        exercise!("Exercise 7", solution::verify_block_sub_chain(self, pre_state, chain))
     }
 }
 
@@ -118,7 +123,35 @@ impl Block {
 /// As before, you do not need the entire parent block to do this. You only need the header.
 /// You do, however, now need a pre-state as you have throughout much of this section.
 fn build_invalid_child_block_with_valid_header(parent: &Header, pre_state: &State) -> Block {
-    todo!("Exercise 8")
+    exercise!(
+        "Exercise 8",
+        solution::build_invalid_child_block_with_valid_header(parent, pre_state)
+    )
+}
+
+/// Numbers are far from all that a rich state can hold. Real chains keep balances, contracts and
+/// tokens in their state all at once, and every kind of extrinsic works on its own part of it.
+/// To see this, we put the non-fungible tokens from chapter 1 next to our numbers.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Extrinsic {
+    /// A number for the sum and the product, like the extrinsics above.
+    Number(u64),
+    /// A transaction on the tokens.
+    Nft(NftTransaction),
+}
+
+/// A state that holds both the numbers and the tokens. Since the state root is the hash of the
+/// whole state, it commits to who owns which token, too.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RichState {
+    pub numbers: State,
+    pub nfts: NftState,
+}
+
+/// Execute the given extrinsics on top of the given state. Numbers update the sum and the
+/// product just like before, and token transactions follow the rules of the `Nft` state machine.
+fn execute_rich(pre_state: &RichState, extrinsics: &[Extrinsic]) -> RichState {
+    exercise!("Exercise 9", solution::execute_rich(pre_state, extrinsics))
 }
 
 #[test]
@@ -126,7 +159,7 @@ fn bc_6_genesis_header() {
     let state = State { sum: 6, product: 9 };
     let g = Header::genesis(hash(&state));
     assert_eq!(g.height, 0);
-    assert_eq!(g.parent, 0);
+    assert_eq!(g.parent, H256::zero());
     assert_eq!(g.extrinsics_root, hash(&Vec::<u64>::new()));
     assert_eq!(g.state_root, hash(&state));
 }
@@ -223,9 +256,9 @@ fn bc_6_invalid_header_doesnt_check() {
     let state = State { sum: 6, product: 9 };
     let g = Header::genesis(hash(&state));
     let h1 = Header {
-        parent: 0,
+        parent: H256::zero(),
         height: 100,
-        extrinsics_root: 0,
+        extrinsics_root: H256::zero(),
         state_root: hash(&(State { sum: 0, product: 0 })),
         consensus_digest: 0,
     };
@@ -268,3 +301,48 @@ fn bc_6_student_invalid_block_really_is_invalid() {
     // Make sure that the block is not valid when executed.
     assert!(!gb.verify_sub_chain(&state, &[b1]));
 }
+
+#[test]
+fn bc_6_rich_state_tracks_tokens() {
+    use crate::c1_state_machine::User;
+
+    let pre_state = RichState {
+        numbers: State { sum: 6, product: 9 },
+        nfts: NftState::default(),
+    };
+    let extrinsics = vec![
+        Extrinsic::Number(2),
+        Extrinsic::Nft(NftTransaction::Mint {
+            minter: User::Alice,
+            metadata: H256::from(1),
+        }),
+        Extrinsic::Nft(NftTransaction::Transfer {
+            from: User::Alice,
+            to: User::Bob,
+            id: 0,
+        }),
+        // Alice no longer owns the token, so this does nothing.
+        Extrinsic::Nft(NftTransaction::Burn {
+            owner: User::Alice,
+            id: 0,
+        }),
+        Extrinsic::Number(3),
+    ];
+    let post_state = execute_rich(&pre_state, &extrinsics);
+
+    assert_eq!(
+        post_state.numbers,
+        State {
+            sum: 11,
+            product: 54
+        }
+    );
+    assert_eq!(post_state.nfts.tokens[&0].owner, User::Bob);
+
+    // A block that commits to this state commits to Bob owning the token.
+    let g = Header::genesis(hash(&pre_state));
+    let h1 = g.child(hash(&extrinsics), hash(&post_state));
+    let mut forged = post_state.clone();
+    forged.nfts.tokens.get_mut(&0).unwrap().owner = User::Charlie;
+    assert_ne!(h1.state_root, hash(&forged));
+}
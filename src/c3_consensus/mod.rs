@@ -8,12 +8,36 @@ mod p3_poa; // exercise: dictator is a special case of poa. Create dictator in t
 mod p4_even_only;
 mod p5_interleave;
 mod p6_forking;
+#[cfg(feature = "std")]
+mod p7_session_keys;
+#[cfg(feature = "std")]
+mod p8_proof_of_authority;
 
 // Re-export some individual consensus engines so they can be be re-used in the Client chapter.
 pub use p1_pow::Pow;
 pub use p3_poa::SimplePoa;
+#[cfg(feature = "std")]
+pub use p7_session_keys::{
+    epoch_of, set_keys_payload, unsealed, SessionKeys, SessionPoa, SessionSeal, SetKeys,
+    EPOCH_LENGTH,
+};
+#[cfg(feature = "std")]
+pub use p8_proof_of_authority::{
+    author_in_slot, equivocator, seal_payload, sudo_payload, Aura, AuraHeader, AuraSeal,
+    AuthorityRegistry, RegistryCall, RegistryTransaction, AUTHORITY_BOND,
+};
 
-type Hash = u64;
+use alloc::string::String;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::H256;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c3_consensus/mod.rs"]
+mod solution;
+
+type Hash = H256;
 
 /// A Block Header similar to prior chapters of this tutorial.
 ///
@@ -23,7 +47,7 @@ type Hash = u64;
 /// Consensus engines do not know or care about the blockchain's state machine,
 /// which means they can operate entirely at the header level. They never need to touch
 /// the complete blocks.
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Header<Digest> {
     parent: Hash,
     height: u64,
@@ -31,12 +55,59 @@ pub struct Header<Digest> {
     extrinsics_root: Hash,
     consensus_digest: Digest,
 }
+
+impl<Digest> Header<Digest> {
+    /// Assemble a header from its parts. The client builds its headers as children of their
+    /// parents instead, but tools such as benchmarks need to make up headers of their own.
+    pub fn new(
+        parent: Hash,
+        height: u64,
+        state_root: Hash,
+        extrinsics_root: Hash,
+        consensus_digest: Digest,
+    ) -> Self {
+        Header {
+            parent,
+            height,
+            state_root,
+            extrinsics_root,
+            consensus_digest,
+        }
+    }
+
+    /// The hash of this header's parent.
+    pub fn parent(&self) -> Hash {
+        self.parent
+    }
+
+    /// The height of this header. Genesis is at height 0.
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// The commitment to the state after this block. Light clients check storage proofs
+    /// against it.
+    pub fn state_root(&self) -> Hash {
+        self.state_root
+    }
+
+    /// The Merkle root of the extrinsics in this header's block. Light clients check
+    /// extrinsic proofs against it.
+    pub fn extrinsics_root(&self) -> Hash {
+        self.extrinsics_root
+    }
+
+    /// The consensus digest attached to this header.
+    pub fn consensus_digest(&self) -> &Digest {
+        &self.consensus_digest
+    }
+}
 /// A Consensus Engine. Responsible for Sealing blocks and verifying their seals
 ///
 /// Consensus exists independently of execution logic, and therefore operates
 /// only on the block headers.
 pub trait Consensus {
-    type Digest: Clone + core::fmt::Debug + Eq + PartialEq + std::hash::Hash;
+    type Digest: Clone + core::fmt::Debug + Eq + PartialEq + core::hash::Hash;
 
     /// Validates that a header is valid according to consensus rules. This
     /// function checks ONLY consensus-related aspects such as the signature
@@ -71,12 +142,20 @@ pub trait Consensus {
     /// This method assumes that the parent_digest is valid, and verifies all the
     /// following headers relative to the given parent digest. This is a provided method
     /// on the trait, so it must be general enough to work for any specific consensus engine.
+    #[tracing::instrument(level = "debug", skip_all, fields(headers = chain.len()))]
     fn verify_sub_chain(
         &self,
         parent_digest: &Self::Digest,
         chain: &[Header<Self::Digest>],
     ) -> bool {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::verify_sub_chain(self, parent_digest, chain))
+    }
+
+    /// How much work the given header represents, for fork choice rules and tools that weigh
+    /// chains by the effort that went into them. Engines where every block is as hard to author
+    /// as the next count each block as one unit.
+    fn work(&self, _header: &Header<Self::Digest>) -> u128 {
+        1
     }
 
     /// A human-readable name for this engine. This may be used in user-facing
@@ -94,12 +173,12 @@ impl Consensus for () {
 
     /// All blocks are considered valid
     fn validate(&self, _: &Self::Digest, _: &Header<Self::Digest>) -> bool {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", true)
     }
 
     /// No real sealing is required.
     fn seal(&self, _: &Self::Digest, partial_header: Header<()>) -> Option<Header<Self::Digest>> {
-        todo!("Exercise 3")
+        exercise!("Exercise 3", Some(partial_header))
     }
 }
 
@@ -6,6 +6,10 @@
 
 use super::{Consensus, Header};
 
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c3_consensus/p1_pow.rs"]
+mod solution;
+
 /// A Proof of Work consensus engine. This is the same consensus logic that we
 /// implemented in the previous chapter. Here we simply re-implement it in the
 /// consensus framework that will be used throughout this chapter.
@@ -13,30 +17,59 @@ pub struct Pow {
     threshold: u64,
 }
 
+impl Pow {
+    /// Create a PoW engine that accepts headers whose hash is below the given threshold.
+    /// Hashes are compared through their last eight bytes, see
+    /// `crate::difficulty::meets_threshold`.
+    pub fn new(threshold: u64) -> Self {
+        Pow { threshold }
+    }
+}
+
+/// The engine of a development chain, with `moderate_difficulty_pow`, so that clients can be
+/// started from nothing but a genesis state.
+impl Default for Pow {
+    fn default() -> Self {
+        moderate_difficulty_pow()
+    }
+}
+
 impl Consensus for Pow {
     type Digest = u64;
 
     /// Check that the provided header's hash is below the required threshold.
     /// This does not rely on the parent digest at all.
+    #[tracing::instrument(
+        level = "trace",
+        skip_all,
+        fields(height = header.height(), nonce = header.consensus_digest())
+    )]
     fn validate(&self, _: &Self::Digest, header: &Header<Self::Digest>) -> bool {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::validate(self, header))
     }
 
     /// Mine a new PoW seal for the partial header provided.
     /// This does not rely on the parent digest at all.
+    #[tracing::instrument(level = "trace", skip_all, fields(height = partial_header.height()))]
     fn seal(&self, _: &Self::Digest, partial_header: Header<()>) -> Option<Header<Self::Digest>> {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", solution::seal(self, partial_header))
+    }
+
+    /// The number of hashes it takes on average to find one below the threshold.
+    fn work(&self, _: &Header<Self::Digest>) -> u128 {
+        u128::from(u64::MAX) / u128::from(self.threshold.max(1))
     }
 }
 
 /// Create a PoW consensus engine that has a difficulty threshold such that roughly 1 in 100 blocks
-/// with randomly drawn nonces will be valid. That is: the threshold should be u64::max_value() / 100.
+/// with randomly drawn nonces will be valid. That is: the threshold should be u64::max_value() / 100,
+/// which is also what `crate::difficulty::difficulty_to_threshold(100)` gives.
 pub fn moderate_difficulty_pow() -> Pow {
-    todo!("Exercise 3")
+    exercise!("Exercise 3", solution::moderate_difficulty_pow())
 }
 
 /// Create an instance of the PoW Consensus that behaves identically to the trivial
 /// consensus implementation for `()` from the module level.
 pub fn trivial_always_valid_pow() -> Pow {
-    todo!("Exercise 4")
+    exercise!("Exercise 4", solution::trivial_always_valid_pow())
 }
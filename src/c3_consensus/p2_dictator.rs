@@ -9,6 +9,11 @@
 //! underlying consensus-related logic. Instead, we just use the `ConsensusAuthority` enum from the module root.
 
 use super::{Consensus, ConsensusAuthority, Header};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c3_consensus/p2_dictator.rs"]
+mod solution;
+
 /// Dictator consensus is an identity-based consensus algorithm. It specifies a single dictator
 /// identity who is the only identity authorized to sign valid blocks. Any block signed by the
 /// dictator is valid (at the consensus level), and any block not signed by the dictator is invalid.
@@ -21,11 +26,11 @@ impl Consensus for DictatorConsensus {
 
     /// Check that the header is signed by the dictator
     fn validate(&self, _: &Self::Digest, header: &Header<Self::Digest>) -> bool {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::validate(self, header))
     }
 
     /// Sign the given partial header by the dictator
     fn seal(&self, _: &Self::Digest, partial_header: Header<()>) -> Option<Header<Self::Digest>> {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", solution::seal(self, partial_header))
     }
 }
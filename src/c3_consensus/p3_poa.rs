@@ -9,8 +9,14 @@
 //! Even when using the Proof of Stake configuration, the underlying consensus logic is identical to
 //! the proof of authority we are writing here.
 
+use alloc::vec::Vec;
+
 use super::{Consensus, ConsensusAuthority, Header};
 
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c3_consensus/p3_poa.rs"]
+mod solution;
+
 /// A Proof of Authority consensus engine. If any of the authorities have signed the block, it is valid.
 pub struct SimplePoa {
     pub authorities: Vec<ConsensusAuthority>,
@@ -19,16 +25,22 @@ pub struct SimplePoa {
 impl Consensus for SimplePoa {
     type Digest = ConsensusAuthority;
 
+    #[tracing::instrument(
+        level = "trace",
+        skip_all,
+        fields(height = header.height(), author = ?header.consensus_digest())
+    )]
     fn validate(&self, parent_digest: &Self::Digest, header: &Header<Self::Digest>) -> bool {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::validate_simple(self, header))
     }
 
+    #[tracing::instrument(level = "trace", skip_all, fields(height = partial_header.height()))]
     fn seal(
         &self,
         parent_digest: &Self::Digest,
         partial_header: Header<()>,
     ) -> Option<Header<Self::Digest>> {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", solution::seal_simple(self, partial_header))
     }
 }
 
@@ -43,7 +55,7 @@ impl Consensus for PoaRoundRobinByHeight {
     type Digest = ConsensusAuthority;
 
     fn validate(&self, parent_digest: &Self::Digest, header: &Header<Self::Digest>) -> bool {
-        todo!("Exercise 3")
+        exercise!("Exercise 3", solution::validate_by_height(self, header))
     }
 
     fn seal(
@@ -51,7 +63,7 @@ impl Consensus for PoaRoundRobinByHeight {
         parent_digest: &Self::Digest,
         partial_header: Header<()>,
     ) -> Option<Header<Self::Digest>> {
-        todo!("Exercise 4")
+        exercise!("Exercise 4", solution::seal_by_height(self, partial_header))
     }
 }
 
@@ -80,7 +92,7 @@ impl Consensus for PoaRoundRobinBySlot {
     type Digest = SlotDigest;
 
     fn validate(&self, parent_digest: &Self::Digest, header: &Header<Self::Digest>) -> bool {
-        todo!("Exercise 5")
+        exercise!("Exercise 5", solution::validate_by_slot(self, parent_digest, header))
     }
 
     fn seal(
@@ -88,6 +100,67 @@ impl Consensus for PoaRoundRobinBySlot {
         parent_digest: &Self::Digest,
         partial_header: Header<()>,
     ) -> Option<Header<Self::Digest>> {
-        todo!("Exercise 6")
+        exercise!("Exercise 6", solution::seal_by_slot(self, parent_digest, partial_header))
+    }
+}
+
+/// The extended tests of the `check` binary, which `cargo test` leaves out.
+#[cfg(all(test, feature = "check"))]
+mod check {
+    use super::*;
+    use crate::hashing::H256;
+
+    fn partial(height: u64) -> Header<()> {
+        Header::new(H256::zero(), height, H256::zero(), H256::zero(), ())
+    }
+
+    #[test]
+    fn consensus_3_check_simple_poa_without_authorities() {
+        let engine = SimplePoa {
+            authorities: Vec::new(),
+        };
+        assert_eq!(engine.seal(&ConsensusAuthority::Alice, partial(1)), None);
+    }
+
+    #[test]
+    fn consensus_3_check_simple_poa_rejects_outsiders() {
+        let engine = SimplePoa {
+            authorities: vec![ConsensusAuthority::Bob, ConsensusAuthority::Charlie],
+        };
+        let sealed = engine.seal(&ConsensusAuthority::Bob, partial(1)).unwrap();
+        assert!(engine.validate(&ConsensusAuthority::Bob, &sealed));
+
+        let outsider = Header::new(
+            H256::zero(),
+            1,
+            H256::zero(),
+            H256::zero(),
+            ConsensusAuthority::Alice,
+        );
+        assert!(!engine.validate(&ConsensusAuthority::Bob, &outsider));
+    }
+
+    #[test]
+    fn consensus_3_check_round_robin_wraps_around() {
+        let authorities = vec![ConsensusAuthority::Alice, ConsensusAuthority::Bob];
+        let engine = PoaRoundRobinByHeight { authorities };
+        let authors: Vec<_> = (1..=5)
+            .map(|height| {
+                *engine
+                    .seal(&ConsensusAuthority::Alice, partial(height))
+                    .unwrap()
+                    .consensus_digest()
+            })
+            .collect();
+        assert_eq!(
+            authors,
+            [
+                ConsensusAuthority::Alice,
+                ConsensusAuthority::Bob,
+                ConsensusAuthority::Alice,
+                ConsensusAuthority::Bob,
+                ConsensusAuthority::Alice,
+            ]
+        );
     }
 }
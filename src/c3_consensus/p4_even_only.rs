@@ -2,22 +2,27 @@
 //! in order to be valid. Now we will express that logic here as a higher-order consensus engine. It is higher-
 //! order because it will wrap an inner consensus engine, such as PoW or PoA and work in either case.
 
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 
 use super::{Consensus, Header};
 
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c3_consensus/p4_even_only.rs"]
+mod solution;
+
 /// A Consensus engine that requires the state root to be even for the header to be valid.
 /// Wraps an inner consensus engine whose rules will also be enforced.
-struct EvenOnly<Inner: Consensus> {
+pub(super) struct EvenOnly<Inner: Consensus> {
     /// The inner consensus engine that will be used in addition to the even-only requirement.
-    inner: Inner,
+    pub(super) inner: Inner,
 }
 
 impl<Inner: Consensus> Consensus for EvenOnly<Inner> {
     type Digest = Inner::Digest;
 
     fn validate(&self, parent_digest: &Self::Digest, header: &Header<Self::Digest>) -> bool {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::validate(self, parent_digest, header))
     }
 
     fn seal(
@@ -25,7 +30,7 @@ impl<Inner: Consensus> Consensus for EvenOnly<Inner> {
         parent_digest: &Self::Digest,
         partial_header: Header<()>,
     ) -> Option<Header<Self::Digest>> {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", solution::seal(self, parent_digest, partial_header))
     }
 }
 
@@ -33,5 +38,5 @@ impl<Inner: Consensus> Consensus for EvenOnly<Inner> {
 /// create a PoW chain that is valid according to the inner consensus engine, but is not valid according to
 /// this engine because the state roots are not all even.
 fn almost_valid_but_not_all_even() -> Vec<Header<u64>> {
-    todo!("Exercise 3")
+    exercise!("Exercise 3", solution::almost_valid_but_not_all_even())
 }
@@ -10,6 +10,10 @@
 struct AlternatingPowPoa;
 use super::{Consensus, ConsensusAuthority, Header};
 
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c3_consensus/p5_interleave.rs"]
+mod solution;
+
 /// In order to implement a consensus that can be sealed with either work or a signature,
 /// we will need an enum that wraps the two individual digest types.
 #[derive(Hash, Debug, PartialEq, Eq, Clone, Copy)]
@@ -19,30 +23,30 @@ enum PowOrPoaDigest {
 }
 
 impl From<u64> for PowOrPoaDigest {
-    fn from(_: u64) -> Self {
-        todo!("Exercise 1")
+    fn from(_nonce: u64) -> Self {
+        exercise!("Exercise 1", solution::from_nonce(_nonce))
     }
 }
 
 impl TryFrom<PowOrPoaDigest> for u64 {
     type Error = ();
 
-    fn try_from(_: PowOrPoaDigest) -> Result<Self, Self::Error> {
-        todo!("Exercise 2")
+    fn try_from(_digest: PowOrPoaDigest) -> Result<Self, Self::Error> {
+        exercise!("Exercise 2", solution::to_nonce(_digest))
     }
 }
 
 impl From<ConsensusAuthority> for PowOrPoaDigest {
-    fn from(_: ConsensusAuthority) -> Self {
-        todo!("Exercise 3")
+    fn from(_authority: ConsensusAuthority) -> Self {
+        exercise!("Exercise 3", solution::from_authority(_authority))
     }
 }
 
 impl TryFrom<PowOrPoaDigest> for ConsensusAuthority {
     type Error = ();
 
-    fn try_from(_: PowOrPoaDigest) -> Result<Self, Self::Error> {
-        todo!("Exercise 4")
+    fn try_from(_digest: PowOrPoaDigest) -> Result<Self, Self::Error> {
+        exercise!("Exercise 4", solution::to_authority(_digest))
     }
 }
 
@@ -50,7 +54,7 @@ impl Consensus for AlternatingPowPoa {
     type Digest = PowOrPoaDigest;
 
     fn validate(&self, parent_digest: &Self::Digest, header: &Header<Self::Digest>) -> bool {
-        todo!("Exercise 5")
+        exercise!("Exercise 5", solution::validate(header))
     }
 
     fn seal(
@@ -58,6 +62,6 @@ impl Consensus for AlternatingPowPoa {
         parent_digest: &Self::Digest,
         partial_header: Header<()>,
     ) -> Option<Header<Self::Digest>> {
-        todo!("Exercise 6")
+        exercise!("Exercise 6", solution::seal(partial_header))
     }
 }
@@ -6,30 +6,37 @@
 //! be enforced before or after the fork, but rather delegates to existing consensus engines
 //! for that. Here we simply write the logic for detecting whether we are before or after the fork.
 
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 
 use super::{Consensus, ConsensusAuthority, Header};
 
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c3_consensus/p6_forking.rs"]
+mod solution;
+
 /// A Higher-order consensus engine that represents a change from one set of consensus rules (Before) to
-/// another set (After) at a specific block height
+/// another set (After) at a specific block height. Both sets of rules seal the same kind of digest.
 struct Forked<D, Before, After> {
     /// The first block height at which the new consensus rules apply
     fork_height: u64,
-    phdata: PhantomData<(D, Before, After)>,
+    /// The consensus engine whose rules apply before the fork
+    before: Before,
+    /// The consensus engine whose rules apply from the fork on
+    after: After,
+    phdata: PhantomData<D>,
 }
 
 impl<D, B, A> Consensus for Forked<D, B, A>
 where
-    D: Clone + core::fmt::Debug + Eq + PartialEq + std::hash::Hash,
-    B: Consensus,
-    A: Consensus,
-    B::Digest: Into<D>,
-    A::Digest: Into<D>,
+    D: Clone + core::fmt::Debug + Eq + PartialEq + core::hash::Hash,
+    B: Consensus<Digest = D>,
+    A: Consensus<Digest = D>,
 {
     type Digest = D;
 
     fn validate(&self, parent_digest: &Self::Digest, header: &Header<Self::Digest>) -> bool {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", solution::validate(self, parent_digest, header))
     }
 
     fn seal(
@@ -37,7 +44,7 @@ where
         parent_digest: &Self::Digest,
         partial_header: Header<()>,
     ) -> Option<Header<Self::Digest>> {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", solution::seal(self, parent_digest, partial_header))
     }
 }
 
@@ -48,7 +55,10 @@ fn change_authorities(
     initial_authorities: Vec<ConsensusAuthority>,
     final_authorities: Vec<ConsensusAuthority>,
 ) -> impl Consensus {
-    todo!("Exercise 3")
+    exercise!(
+        "Exercise 3",
+        solution::change_authorities(fork_height, initial_authorities, final_authorities)
+    )
 }
 
 /// Create a PoW consensus engine that changes the difficulty part way through the chain's history.
@@ -57,7 +67,10 @@ fn change_difficulty(
     initial_difficulty: u64,
     final_difficulty: u64,
 ) -> impl Consensus {
-    todo!("Exercise 4")
+    exercise!(
+        "Exercise 4",
+        solution::change_difficulty(fork_height, initial_difficulty, final_difficulty)
+    )
 }
 
 /// Earlier in this chapter we implemented a consensus rule in which blocks are only considered valid if
@@ -66,9 +79,15 @@ fn change_difficulty(
 ///
 /// Create a consensus engine that introduces the even-only logic only after the given fork height.
 /// Other than the evenness requirement, the consensus rules should not change at the fork. This function
-/// should work with either PoW, PoA, or anything else as the underlying consensus engine.
-fn even_after_given_height<Original: Consensus>(fork_height: u64) -> impl Consensus {
-    todo!("Exercise 5")
+/// should work with either PoW, PoA, or anything else as the original consensus engine.
+fn even_after_given_height<Original: Consensus + Clone>(
+    fork_height: u64,
+    original: Original,
+) -> impl Consensus {
+    exercise!(
+        "Exercise 5",
+        solution::even_after_given_height(fork_height, original)
+    )
 }
 
 /// In the spirit of Ethereum's recent switch from PoW to PoA, let us model a similar
@@ -82,5 +101,5 @@ fn pow_to_poa(
     difficulty: u64,
     authorities: Vec<ConsensusAuthority>,
 ) -> impl Consensus {
-    todo!("Exercise 6")
+    exercise!("Exercise 6", solution::pow_to_poa(fork_height, difficulty, authorities))
 }
@@ -0,0 +1,233 @@
+//! The authorities of the previous engines sign blocks with the same key that holds their money.
+//! That key has to be online all the time to author, which makes it the easiest key of all to
+//! steal. Real chains split the two:
+//! * The _stash_ key holds the authority's funds and identifies it. It stays offline.
+//! * The _session_ key signs blocks. It lives on the authoring node, and if it leaks, the
+//!   authority simply rotates it for a new one with a signature of their stash key.
+//!
+//! Rotations are extrinsics, so they are part of the chain's history, and every node must agree
+//! on which session key was active for which block. A rotation included in one epoch only takes
+//! effect from the start of the next one, so that every block of an epoch is checked against the
+//! same keys, no matter where in the epoch the rotation landed.
+//!
+//! A real engine would read the session keys from the state of each block's parent. To keep the
+//! `Consensus` interface as it is, our engine keeps its own copy of the registry instead.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::{Consensus, Header};
+use crate::crypto::sig::{Keypair, PublicKey, Signature};
+use crate::hashing::{hash_of, H256};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c3_consensus/p7_session_keys.rs"]
+mod solution;
+
+/// How many blocks each epoch lasts.
+pub const EPOCH_LENGTH: u64 = 10;
+
+/// The epoch the block at the given height belongs to. Genesis starts epoch 0.
+pub fn epoch_of(height: u64) -> u64 {
+    height / EPOCH_LENGTH
+}
+
+/// An extrinsic with which an authority sets a new session key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SetKeys {
+    /// The index of the authority in the registry.
+    pub authority: usize,
+    pub session_key: PublicKey,
+    /// How many times the authority set their keys before. This keeps anybody from replaying
+    /// an old rotation to switch the authority back to a key that leaked.
+    pub rotation: u64,
+    /// The signature of the authority's stash key over the `set_keys_payload`.
+    pub signature: Signature,
+}
+
+/// Calculate the hash that an authority's stash key must sign to set a new session key.
+pub fn set_keys_payload(authority: usize, session_key: &PublicKey, rotation: u64) -> H256 {
+    hash_of("set keys", &(authority, session_key, rotation))
+}
+
+/// The on-chain registry of every authority's keys.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SessionKeys {
+    /// The stash key of every authority. Authorities are known by their index in here.
+    pub stashes: Vec<PublicKey>,
+    /// The session keys of every authority, by the epoch they became active in. A key stays
+    /// active until the next one takes over.
+    pub keys: Vec<BTreeMap<u64, PublicKey>>,
+    /// How many times every authority set their keys.
+    pub rotations: Vec<u64>,
+}
+
+impl SessionKeys {
+    /// A registry of authorities, given by their stash key and the session key they start out
+    /// with, in the genesis epoch.
+    pub fn genesis(authorities: &[(PublicKey, PublicKey)]) -> Self {
+        SessionKeys {
+            stashes: authorities.iter().map(|(stash, _)| *stash).collect(),
+            keys: authorities
+                .iter()
+                .map(|(_, session)| BTreeMap::from([(0, *session)]))
+                .collect(),
+            rotations: alloc::vec![0; authorities.len()],
+        }
+    }
+
+    /// Apply a `SetKeys` extrinsic that was included in the block at the given height. The new
+    /// session key becomes active at the start of the next epoch. Extrinsics of an unknown
+    /// authority, with the wrong rotation count, or without a valid stash signature leave the
+    /// registry untouched.
+    pub fn set_keys(&self, height: u64, request: &SetKeys) -> SessionKeys {
+        exercise!("Exercise 1", solution::set_keys(self, height, request))
+    }
+
+    /// The session key the given authority seals with during the given epoch.
+    pub fn session_key(&self, authority: usize, epoch: u64) -> Option<PublicKey> {
+        exercise!("Exercise 2", solution::session_key(self, authority, epoch))
+    }
+}
+
+/// The seal of a block: which authority sealed it, and their session key's signature over the
+/// hash of the header without its seal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SessionSeal {
+    pub authority: usize,
+    pub signature: Signature,
+}
+
+/// The given header, without its seal. This is what authorities sign.
+pub fn unsealed<Digest>(header: &Header<Digest>) -> Header<()> {
+    Header::new(
+        header.parent(),
+        header.height(),
+        header.state_root(),
+        header.extrinsics_root(),
+        (),
+    )
+}
+
+/// A Proof of Authority engine in which any authority may seal a block, but only with the
+/// session key that is active for the block's epoch. Genesis has no seal.
+pub struct SessionPoa {
+    pub sessions: SessionKeys,
+    /// The index of the authority this node seals for, and its session key. Nodes that do not
+    /// author leave this out.
+    pub local: Option<(usize, Keypair)>,
+}
+
+impl Consensus for SessionPoa {
+    type Digest = Option<SessionSeal>;
+
+    fn validate(&self, parent_digest: &Self::Digest, header: &Header<Self::Digest>) -> bool {
+        exercise!("Exercise 3", solution::validate(self, header))
+    }
+
+    /// Sealing fails when the local session key is not the one that is active for the block's
+    /// epoch, for example right after a rotation that did not take effect yet.
+    fn seal(
+        &self,
+        parent_digest: &Self::Digest,
+        partial_header: Header<()>,
+    ) -> Option<Header<Self::Digest>> {
+        exercise!("Exercise 4", solution::seal(self, partial_header))
+    }
+}
+
+#[cfg(test)]
+use crate::hashing::header_hash;
+
+#[cfg(test)]
+fn key(seed: u8) -> Keypair {
+    Keypair::from_seed([seed; 32])
+}
+
+/// Authority 0 has stash key 1, and starts out with session key 2.
+#[cfg(test)]
+fn genesis() -> SessionKeys {
+    SessionKeys::genesis(&[(key(1).public(), key(2).public())])
+}
+
+/// Authority 0 rotating to the given session key, signed with the given stash key.
+#[cfg(test)]
+fn rotate(session: u8, rotation: u64, stash: u8) -> SetKeys {
+    let session_key = key(session).public();
+    SetKeys {
+        authority: 0,
+        session_key,
+        rotation,
+        signature: key(stash).sign(set_keys_payload(0, &session_key, rotation).as_bytes()),
+    }
+}
+
+#[cfg(test)]
+fn partial(height: u64) -> Header<()> {
+    Header::new(H256::from(height), height, H256::zero(), H256::zero(), ())
+}
+
+#[test]
+fn consensus_7_rotation_takes_effect_at_next_epoch() {
+    let rotated = genesis().set_keys(3, &rotate(3, 0, 1));
+    assert_eq!(rotated.rotations, alloc::vec![1]);
+    assert_eq!(rotated.session_key(0, 0), Some(key(2).public()));
+    assert_eq!(rotated.session_key(0, 1), Some(key(3).public()));
+    assert_eq!(rotated.session_key(0, 5), Some(key(3).public()));
+    assert_eq!(rotated.session_key(1, 0), None);
+
+    // The new key can not seal before its epoch starts, and the old one can not after.
+    let engine = |session| SessionPoa {
+        sessions: rotated.clone(),
+        local: Some((0, key(session))),
+    };
+    assert!(engine(3).seal(&None, partial(EPOCH_LENGTH - 1)).is_none());
+    assert!(engine(3).seal(&None, partial(EPOCH_LENGTH)).is_some());
+    assert!(engine(2).seal(&None, partial(EPOCH_LENGTH - 1)).is_some());
+    assert!(engine(2).seal(&None, partial(EPOCH_LENGTH)).is_none());
+}
+
+#[test]
+fn consensus_7_rotation_needs_stash_signature_and_fresh_count() {
+    assert_eq!(genesis().set_keys(3, &rotate(3, 0, 2)), genesis());
+    assert_eq!(genesis().set_keys(3, &rotate(3, 1, 1)), genesis());
+
+    // Replaying the first rotation after a second one can not switch back to the first key.
+    let first = rotate(3, 0, 1);
+    let twice = genesis()
+        .set_keys(3, &first)
+        .set_keys(EPOCH_LENGTH + 3, &rotate(4, 1, 1));
+    assert_eq!(twice.set_keys(2 * EPOCH_LENGTH, &first), twice);
+    assert_eq!(twice.session_key(0, 2), Some(key(4).public()));
+}
+
+#[test]
+fn consensus_7_old_blocks_verify_against_their_epoch_key() {
+    let before = SessionPoa {
+        sessions: genesis(),
+        local: Some((0, key(2))),
+    };
+    let old = before.seal(&None, partial(5)).unwrap();
+
+    let after = SessionPoa {
+        sessions: genesis().set_keys(7, &rotate(3, 0, 1)),
+        local: Some((0, key(3))),
+    };
+    assert!(after.validate(&None, &old));
+    let new = after.seal(&None, partial(EPOCH_LENGTH + 2)).unwrap();
+    assert!(after.validate(&None, &new));
+
+    // A block of the new epoch sealed with the old key does not verify.
+    let signature = key(2).sign(header_hash(&partial(EPOCH_LENGTH + 2)).as_bytes());
+    let stale = Header::new(
+        H256::from(EPOCH_LENGTH + 2),
+        EPOCH_LENGTH + 2,
+        H256::zero(),
+        H256::zero(),
+        Some(SessionSeal {
+            authority: 0,
+            signature,
+        }),
+    );
+    assert!(!after.validate(&None, &stale));
+}
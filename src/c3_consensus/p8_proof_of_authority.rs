@@ -0,0 +1,369 @@
+//! Let's put the pieces of this chapter together into a Proof of Authority chain like the ones
+//! that many test networks and consortium chains run in production.
+//!
+//! * Blocks are authored in slots, like `PoaRoundRobinBySlot`, which is how Substrate's Aura
+//!   works. Each slot belongs to one authority, and the seal is a real signature.
+//! * The authorities are kept in an on-chain registry. A sudo key, like the one of chapter 1,
+//!   adds and removes them. Changes take effect at the next epoch, like the session key
+//!   rotations of part 7, so the engine checks every block against the authorities of its own
+//!   epoch.
+//! * Authorities put down a bond, and they lose it when they misbehave. The misbehavior that
+//!   a slot based engine can prove is equivocation: sealing two different blocks in the same
+//!   slot. Anybody who sees both blocks can report them to the registry.
+//!
+//! The registry is the state of a tiny runtime. A real chain keeps it in its storage next to
+//! everything else, and the engine reads it from the state of each block's parent. Like the
+//! engine of part 7, ours keeps its own copy of the registry instead.
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use super::{epoch_of, Consensus, Header};
+use crate::crypto::sig::{Keypair, PublicKey, Signature};
+use crate::hashing::{hash_of, header_hash, H256};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c3_consensus/p8_proof_of_authority.rs"]
+mod solution;
+
+/// How much every authority bonds.
+pub const AUTHORITY_BOND: u64 = 1_000;
+
+/// A call that only the sudo key may make.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RegistryCall {
+    /// Add an authority from the next epoch on. Their bond is taken from their balance.
+    AddAuthority(PublicKey),
+    /// Remove an authority from the next epoch on. Their bond is given back once they are out.
+    RemoveAuthority(PublicKey),
+}
+
+/// Calculate the hash that the sudo key must sign to make a call.
+pub fn sudo_payload(call: &RegistryCall, nonce: u64) -> H256 {
+    hash_of("poa sudo", &(call, nonce))
+}
+
+/// The seal of a block: its slot, and the signature of the slot's authority over the
+/// `seal_payload`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AuraSeal {
+    pub slot: u64,
+    pub signature: Signature,
+}
+
+/// A header sealed by an authority. Genesis has no seal.
+pub type AuraHeader = Header<Option<AuraSeal>>;
+
+/// Calculate the hash that an authority signs to seal the given header in the given slot. It
+/// covers the slot, so that a sealed block can not be moved to another slot.
+pub fn seal_payload(header: &Header<()>, slot: u64) -> H256 {
+    hash_of("aura seal", &(header_hash(header), slot))
+}
+
+/// The authority that the given slot belongs to.
+pub fn author_in_slot(authorities: &[PublicKey], slot: u64) -> Option<PublicKey> {
+    let count = authorities.len() as u64;
+    (count > 0).then(|| authorities[(slot % count) as usize])
+}
+
+/// The transitions of the registry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegistryTransaction {
+    /// A call signed by the sudo key. The nonce keeps the signature from being replayed.
+    Sudo {
+        call: RegistryCall,
+        nonce: u64,
+        signature: Signature,
+    },
+    /// Report two different blocks that the same authority sealed in the same slot. The headers
+    /// are boxed, so that the other transactions do not take up as much room as two headers.
+    ReportEquivocation {
+        first: Box<AuraHeader>,
+        second: Box<AuraHeader>,
+    },
+}
+
+/// The registry of authorities, and the money they bond.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorityRegistry {
+    pub sudo: PublicKey,
+    /// How many calls the sudo key has made.
+    pub sudo_nonce: u64,
+    /// Everybody's money, not counting their bonds.
+    pub balances: BTreeMap<PublicKey, u64>,
+    /// The bonds of the authorities, and of those that were removed but are not out yet.
+    pub bonds: BTreeMap<PublicKey, u64>,
+    /// The authorities, by the epoch they took over in. A set stays in charge until the next one.
+    pub sets: BTreeMap<u64, Vec<PublicKey>>,
+    /// The authorities that the next epoch starts with.
+    pub next: Vec<PublicKey>,
+    /// The authorities that were punished, with the slot they equivocated in, so that nobody is
+    /// punished twice for the same offense.
+    pub punished: BTreeSet<(PublicKey, u64)>,
+}
+
+impl AuthorityRegistry {
+    /// A registry with the given sudo key, balances and genesis authorities. Every genesis
+    /// authority starts out with `AUTHORITY_BOND` bonded, on top of its balance.
+    pub fn genesis(
+        sudo: PublicKey,
+        balances: BTreeMap<PublicKey, u64>,
+        authorities: Vec<PublicKey>,
+    ) -> Self {
+        AuthorityRegistry {
+            sudo,
+            sudo_nonce: 0,
+            balances,
+            bonds: authorities.iter().map(|&a| (a, AUTHORITY_BOND)).collect(),
+            sets: BTreeMap::from([(0, authorities.clone())]),
+            next: authorities,
+            punished: BTreeSet::new(),
+        }
+    }
+
+    /// The authorities in charge of the block at the given height.
+    pub fn authorities_at(&self, height: u64) -> &[PublicKey] {
+        self.sets
+            .range(..=epoch_of(height))
+            .next_back()
+            .map_or(&[], |(_, set)| set)
+    }
+
+    /// Execute the registry transactions of the block at the given height. The first block of
+    /// every epoch puts the next set of authorities in charge before its transactions, and gives
+    /// the authorities that are now out their bonds back.
+    ///
+    /// Invalid transactions leave the registry untouched: calls without a valid sudo signature,
+    /// additions of authorities that can't pay the bond, removals of the last authority, and
+    /// reports that don't prove an equivocation or that were made before. A proven equivocation
+    /// costs the offender their whole bond, and their place in the next set.
+    pub fn execute_block(&self, height: u64, transactions: &[RegistryTransaction]) -> Self {
+        exercise!(
+            "Exercise 1",
+            solution::execute_block(self, height, transactions)
+        )
+    }
+}
+
+/// The authority that equivocated, if the two headers are different blocks that they both
+/// validly sealed in the same slot.
+pub fn equivocator(
+    registry: &AuthorityRegistry,
+    first: &AuraHeader,
+    second: &AuraHeader,
+) -> Option<PublicKey> {
+    exercise!("Exercise 2", solution::equivocator(registry, first, second))
+}
+
+/// The Aura engine. Slots must increase from block to block, and each block must be sealed by
+/// the authority its slot belongs to, among the authorities of its epoch.
+pub struct Aura {
+    pub registry: AuthorityRegistry,
+    /// The key this node seals with. Nodes that do not author leave this out.
+    pub local: Option<Keypair>,
+}
+
+impl Consensus for Aura {
+    type Digest = Option<AuraSeal>;
+
+    fn validate(&self, parent_digest: &Self::Digest, header: &AuraHeader) -> bool {
+        exercise!(
+            "Exercise 3",
+            solution::validate(self, parent_digest, header)
+        )
+    }
+
+    /// Seal in the first slot after the parent's that belongs to the local key. Slots of other
+    /// authorities are skipped, just like real slots pass by when their authority is offline.
+    fn seal(&self, parent_digest: &Self::Digest, partial_header: Header<()>) -> Option<AuraHeader> {
+        exercise!(
+            "Exercise 4",
+            solution::seal(self, parent_digest, partial_header)
+        )
+    }
+}
+
+#[cfg(test)]
+use super::EPOCH_LENGTH;
+
+#[cfg(test)]
+fn key(seed: u8) -> Keypair {
+    Keypair::from_seed([seed; 32])
+}
+
+#[cfg(test)]
+fn sudo(call: RegistryCall, nonce: u64) -> RegistryTransaction {
+    RegistryTransaction::Sudo {
+        call,
+        nonce,
+        signature: key(0).sign(sudo_payload(&call, nonce).as_bytes()),
+    }
+}
+
+/// Key 0 is the sudo key, and keys 1 and 2 are the genesis authorities. Key 3 has enough money
+/// for a bond and a bit more.
+#[cfg(test)]
+fn registry() -> AuthorityRegistry {
+    AuthorityRegistry::genesis(
+        key(0).public(),
+        BTreeMap::from([(key(3).public(), AUTHORITY_BOND + 5)]),
+        alloc::vec![key(1).public(), key(2).public()],
+    )
+}
+
+#[cfg(test)]
+fn partial(height: u64) -> Header<()> {
+    Header::new(H256::from(height), height, H256::zero(), H256::zero(), ())
+}
+
+/// The given header, sealed by the given key in the given slot, whether that is their slot or
+/// not.
+#[cfg(test)]
+fn sealed(header: Header<()>, signer: &Keypair, slot: u64) -> AuraHeader {
+    let signature = signer.sign(seal_payload(&header, slot).as_bytes());
+    Header::new(
+        header.parent(),
+        header.height(),
+        header.state_root(),
+        header.extrinsics_root(),
+        Some(AuraSeal { slot, signature }),
+    )
+}
+
+#[test]
+fn consensus_8_added_authority_takes_over_at_next_epoch() {
+    let new = key(3).public();
+    let added = registry().execute_block(3, &[sudo(RegistryCall::AddAuthority(new), 0)]);
+    assert_eq!(added.sudo_nonce, 1);
+    assert_eq!(added.balances[&new], 5);
+    assert_eq!(added.bonds[&new], AUTHORITY_BOND);
+    assert!(!added.authorities_at(EPOCH_LENGTH - 1).contains(&new));
+
+    let next_epoch = added.execute_block(EPOCH_LENGTH, &[]);
+    assert_eq!(
+        next_epoch.authorities_at(EPOCH_LENGTH),
+        &[key(1).public(), key(2).public(), new]
+    );
+    // Old blocks are still checked against the authorities of their own epoch.
+    assert_eq!(
+        next_epoch.authorities_at(EPOCH_LENGTH - 1),
+        &[key(1).public(), key(2).public()]
+    );
+}
+
+#[test]
+fn consensus_8_removed_authority_is_refunded_at_next_epoch() {
+    let gone = key(2).public();
+    let removed = registry().execute_block(3, &[sudo(RegistryCall::RemoveAuthority(gone), 0)]);
+    assert!(removed.authorities_at(EPOCH_LENGTH - 1).contains(&gone));
+    assert_eq!(removed.bonds[&gone], AUTHORITY_BOND);
+
+    let next_epoch = removed.execute_block(EPOCH_LENGTH, &[]);
+    assert_eq!(next_epoch.authorities_at(EPOCH_LENGTH), &[key(1).public()]);
+    assert!(!next_epoch.bonds.contains_key(&gone));
+    assert_eq!(next_epoch.balances[&gone], AUTHORITY_BOND);
+
+    // The last authority stays, no matter what sudo says.
+    let last = key(1).public();
+    assert_eq!(
+        next_epoch.execute_block(11, &[sudo(RegistryCall::RemoveAuthority(last), 1)]),
+        next_epoch
+    );
+}
+
+#[test]
+fn consensus_8_sudo_calls_need_valid_signature_and_fresh_nonce() {
+    let call = RegistryCall::AddAuthority(key(3).public());
+    let forged = RegistryTransaction::Sudo {
+        call,
+        nonce: 0,
+        signature: key(1).sign(sudo_payload(&call, 0).as_bytes()),
+    };
+    assert_eq!(registry().execute_block(1, &[forged]), registry());
+
+    let once = registry().execute_block(1, &[sudo(call, 0)]);
+    let remove = RegistryCall::RemoveAuthority(key(3).public());
+    assert_eq!(once.execute_block(2, &[sudo(remove, 0)]), once);
+
+    // Nobody can bond without the money for it.
+    let poor = RegistryCall::AddAuthority(key(4).public());
+    assert_eq!(registry().execute_block(1, &[sudo(poor, 0)]), registry());
+}
+
+#[test]
+fn consensus_8_equivocation_is_punished_once() {
+    // Slot 4 belongs to key 1, who seals two different blocks in it.
+    let first = sealed(partial(5), &key(1), 4);
+    let second = sealed(partial(6), &key(1), 4);
+    assert_eq!(
+        equivocator(&registry(), &first, &second),
+        Some(key(1).public())
+    );
+    assert_eq!(equivocator(&registry(), &first, &first), None);
+    // Sealing in the other authority's slot is no valid seal, and so proves nothing.
+    let not_theirs = sealed(partial(6), &key(1), 5);
+    assert_eq!(equivocator(&registry(), &first, &not_theirs), None);
+
+    let report = RegistryTransaction::ReportEquivocation {
+        first: Box::new(first),
+        second: Box::new(second),
+    };
+    let punished = registry().execute_block(7, core::slice::from_ref(&report));
+    assert!(!punished.bonds.contains_key(&key(1).public()));
+    assert_eq!(punished.next, &[key(2).public()]);
+    assert!(punished.punished.contains(&(key(1).public(), 4)));
+
+    assert_eq!(punished.execute_block(8, &[report]), punished);
+}
+
+#[test]
+fn consensus_8_validate_checks_slot_and_author() {
+    let engine = Aura {
+        registry: registry(),
+        local: None,
+    };
+    let header = sealed(partial(1), &key(1), 4);
+    assert!(engine.validate(&None, &header));
+
+    let parent = |slot| {
+        Some(AuraSeal {
+            slot,
+            signature: key(1).sign(b"parent"),
+        })
+    };
+    assert!(engine.validate(&parent(3), &header));
+    assert!(!engine.validate(&parent(4), &header));
+    assert!(!engine.validate(&parent(6), &header));
+
+    // The right slot, but the wrong authority.
+    assert!(!engine.validate(&None, &sealed(partial(1), &key(2), 4)));
+    let unsealed = Header::new(H256::from(1), 1, H256::zero(), H256::zero(), None);
+    assert!(!engine.validate(&None, &unsealed));
+}
+
+#[test]
+fn consensus_8_seal_takes_next_own_slot() {
+    let engine = |seed| Aura {
+        registry: registry(),
+        local: Some(key(seed)),
+    };
+    let slot = |seed, parent_slot: Option<u64>| {
+        let parent = parent_slot.map(|slot| AuraSeal {
+            slot,
+            signature: key(1).sign(b"parent"),
+        });
+        let header = engine(seed).seal(&parent, partial(1))?;
+        assert!(engine(seed).validate(&parent, &header));
+        header.consensus_digest().map(|seal| seal.slot)
+    };
+
+    // Key 1 has the even slots, and key 2 the odd ones.
+    assert_eq!(slot(1, None), Some(2));
+    assert_eq!(slot(2, None), Some(1));
+    assert_eq!(slot(1, Some(4)), Some(6));
+    assert_eq!(slot(2, Some(4)), Some(5));
+    assert_eq!(slot(2, Some(5)), Some(7));
+    // Only authorities seal.
+    assert_eq!(slot(3, None), None);
+}
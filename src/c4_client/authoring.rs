@@ -0,0 +1,320 @@
+//! In section 5 we taught the client to author a single block on demand. A real node does not
+//! wait for somebody to ask it. It runs a background task that keeps trying to author blocks
+//! for as long as the node is running, and tells its peers about every block it authors.
+//!
+//! Whether the node is actually eligible to author at any given moment is up to the consensus
+//! engine. A PoW node is always eligible, it just has to do the work. A PoA node may only author
+//! when it is its turn. Either way, when the engine refuses to seal a block, the task simply
+//! tries again at the next tick.
+//!
+//! A node also signs every block it authors with its authoring key, the first key in its
+//! keystore, so that everybody can tell who authored a block. Chains whose engine has no room
+//! for a signature, like PoW, wrap their engine in `Signed`, which adds the author's signature
+//! on top of the engine's own seal. Only an engine that holds a key can seal, and every block
+//! but genesis must carry a valid signature to be imported.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{Block, Consensus, ForkChoice, FullClient, ImportBlock, StateMachine, TransactionPool};
+use crate::c3_consensus::Header;
+use crate::crypto::sig::{Keypair, PublicKey, Signature};
+use crate::hashing::{hash_of, header_hash, H256};
+
+/// Anything that is able to author blocks. The client is the main example, but keeping this
+/// as a trait lets us test the authoring task on its own.
+pub trait AuthorBlocks {
+    /// The type of block that is authored.
+    type Block;
+
+    /// Try to author and import a block on top of the current best block.
+    /// Returns None when we are not eligible to author right now.
+    fn try_author_block(&mut self) -> Option<Self::Block>;
+
+    /// What to tell the given peer about a block we just authored. An honest node tells every
+    /// peer the same thing, which is what happens unless this is overridden. The nodes in the
+    /// `byzantine` module override it to lie to some of their peers.
+    fn announcement(&mut self, block: &Self::Block, _peer: usize) -> Option<Self::Block>
+    where
+        Self::Block: Clone,
+    {
+        Some(block.clone())
+    }
+}
+
+impl<A: AuthorBlocks + ?Sized> AuthorBlocks for Box<A> {
+    type Block = A::Block;
+
+    fn try_author_block(&mut self) -> Option<A::Block> {
+        (**self).try_author_block()
+    }
+
+    fn announcement(&mut self, block: &A::Block, peer: usize) -> Option<A::Block>
+    where
+        A::Block: Clone,
+    {
+        (**self).announcement(block, peer)
+    }
+}
+
+impl<C, SM, FC, P> AuthorBlocks for FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + PartialEq + std::hash::Hash,
+    SM::Transition: Clone + PartialEq + std::hash::Hash,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+{
+    type Block = Block<C, SM>;
+
+    fn try_author_block(&mut self) -> Option<Block<C, SM>> {
+        let hash = self.author_and_import_automatic_block()?;
+        self.get_block(hash)
+    }
+}
+
+impl<C: Consensus, SM: StateMachine, FC, P> FullClient<C, SM, FC, P> {
+    /// Seal and validate blocks with the given engine instead of a default one. Engines that
+    /// need more than a default to author, such as one that signs with a key, are set up here.
+    pub fn with_consensus_engine(mut self, engine: C) -> Self {
+        self.consensus_engine = engine;
+        self
+    }
+}
+
+/// The digest of a block sealed by a `Signed` engine.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SignedDigest<D> {
+    /// The seal of the wrapped engine.
+    pub inner: D,
+    /// The author's public key, and their signature over the header with the inner seal. Only
+    /// genesis has none.
+    pub author: Option<(PublicKey, Signature)>,
+}
+
+/// Another engine, whose seals are signed by the author of the block.
+pub struct Signed<C> {
+    pub inner: C,
+    /// The key this node signs with. Nodes that do not author leave this out.
+    key: Option<Keypair>,
+}
+
+impl<C> Signed<C> {
+    /// The given engine, signing every block it seals with the given key.
+    pub fn new(inner: C, key: Keypair) -> Self {
+        Signed {
+            inner,
+            key: Some(key),
+        }
+    }
+
+    /// The public key that this engine signs with, if it authors at all.
+    pub fn author(&self) -> Option<PublicKey> {
+        self.key.as_ref().map(Keypair::public)
+    }
+}
+
+/// An engine that validates blocks, but can't author any.
+impl<C: Default> Default for Signed<C> {
+    fn default() -> Self {
+        Signed {
+            inner: C::default(),
+            key: None,
+        }
+    }
+}
+
+/// The header as the wrapped engine sealed it, without the author's signature.
+fn inner_header<D: Clone>(header: &Header<SignedDigest<D>>) -> Header<D> {
+    Header::new(
+        header.parent(),
+        header.height(),
+        header.state_root(),
+        header.extrinsics_root(),
+        header.consensus_digest().inner.clone(),
+    )
+}
+
+/// The hash that the author signs to seal a block, given the header as the wrapped engine
+/// sealed it.
+pub fn author_payload<D: std::hash::Hash>(inner: &Header<D>) -> H256 {
+    hash_of("author seal", &header_hash(inner))
+}
+
+impl<C: Consensus> Consensus for Signed<C> {
+    type Digest = SignedDigest<C::Digest>;
+
+    fn validate(&self, parent_digest: &Self::Digest, header: &Header<Self::Digest>) -> bool {
+        let Some((author, signature)) = &header.consensus_digest().author else {
+            return false;
+        };
+        let inner = inner_header(header);
+        author.verify(author_payload(&inner).as_bytes(), signature)
+            && self.inner.validate(&parent_digest.inner, &inner)
+    }
+
+    /// Seal with the wrapped engine, then sign the sealed header. Without a key, nothing is
+    /// sealed at all.
+    fn seal(
+        &self,
+        parent_digest: &Self::Digest,
+        partial_header: Header<()>,
+    ) -> Option<Header<Self::Digest>> {
+        let key = self.key.as_ref()?;
+        let inner = self.inner.seal(&parent_digest.inner, partial_header)?;
+        let signature = key.sign(author_payload(&inner).as_bytes());
+        Some(Header::new(
+            inner.parent(),
+            inner.height(),
+            inner.state_root(),
+            inner.extrinsics_root(),
+            SignedDigest {
+                inner: inner.consensus_digest().clone(),
+                author: Some((key.public(), signature)),
+            },
+        ))
+    }
+
+    fn work(&self, header: &Header<Self::Digest>) -> u128 {
+        self.inner.work(&inner_header(header))
+    }
+
+    fn human_name() -> String {
+        format!("Signed {}", C::human_name())
+    }
+}
+
+/// Keep trying to author a block once every `interval`, and announce every block that is
+/// authored on the given channel. The networking layer is expected to listen on the other end
+/// and pass the announcements on to peers.
+///
+/// The task runs until nobody is listening for announcements anymore.
+pub async fn authoring_task<A: AuthorBlocks>(
+    author: Arc<Mutex<A>>,
+    interval: Duration,
+    announcements: UnboundedSender<A::Block>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if announcements.is_closed() {
+            return;
+        }
+
+        // Make sure the lock is released before we send anything or wait for the next tick.
+        let authored = author
+            .lock()
+            .expect("authoring mutex poisoned")
+            .try_author_block();
+        if let Some(block) = authored {
+            if announcements.send(block).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Pretends to be eligible for every other block.
+#[cfg(test)]
+struct EveryOtherTick {
+    ticks: u64,
+}
+
+#[cfg(test)]
+impl AuthorBlocks for EveryOtherTick {
+    type Block = u64;
+
+    fn try_author_block(&mut self) -> Option<u64> {
+        self.ticks += 1;
+        self.ticks.is_multiple_of(2).then_some(self.ticks)
+    }
+}
+
+#[tokio::test]
+async fn authoring_announces_only_eligible_blocks() {
+    let author = Arc::new(Mutex::new(EveryOtherTick { ticks: 0 }));
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let task = tokio::spawn(authoring_task(author, Duration::from_millis(1), sender));
+
+    assert_eq!(receiver.recv().await, Some(2));
+    assert_eq!(receiver.recv().await, Some(4));
+    assert_eq!(receiver.recv().await, Some(6));
+
+    // Once nobody listens anymore, the task stops on its own.
+    drop(receiver);
+    task.await.unwrap();
+}
+
+#[cfg(test)]
+use super::runtime::{Runtime, RuntimeState};
+#[cfg(test)]
+use super::{LongestChain, SimplePool};
+
+#[cfg(test)]
+fn partial() -> Header<()> {
+    Header::new(H256::zero(), 1, H256::zero(), H256::zero(), ())
+}
+
+/// The given header, with the author's signature replaced.
+#[cfg(test)]
+fn with_author(
+    header: &Header<SignedDigest<()>>,
+    author: Option<(PublicKey, Signature)>,
+) -> Header<SignedDigest<()>> {
+    let digest = SignedDigest { inner: (), author };
+    Header::new(
+        header.parent(),
+        header.height(),
+        header.state_root(),
+        header.extrinsics_root(),
+        digest,
+    )
+}
+
+#[test]
+fn authoring_signed_seals_carry_the_authors_signature() {
+    let key = Keypair::from_seed([1; 32]);
+    let engine = Signed::new((), key.clone());
+    let parent = SignedDigest::default();
+    let header = engine.seal(&parent, partial()).unwrap();
+    assert!(engine.validate(&parent, &header));
+    let (author, _) = header.consensus_digest().author.unwrap();
+    assert_eq!(author, key.public());
+
+    // Somebody else's signature, or none at all, does not do.
+    let forged = Keypair::from_seed([2; 32]).sign(author_payload(&partial()).as_bytes());
+    assert!(!engine.validate(&parent, &with_author(&header, Some((author, forged)))));
+    assert!(!engine.validate(&parent, &with_author(&header, None)));
+
+    // Without a key, the engine still validates, but it does not seal.
+    let validator = Signed::<()>::default();
+    assert!(validator.validate(&parent, &header));
+    assert_eq!(validator.seal(&parent, partial()), None);
+}
+
+#[test]
+fn authoring_client_signs_with_its_key() {
+    type SignedClient = FullClient<Signed<()>, Runtime, LongestChain, SimplePool<Runtime>>;
+    let key = Keypair::from_seed([1; 32]);
+    let mut client = SignedClient::new(RuntimeState::default())
+        .with_consensus_engine(Signed::new((), key.clone()));
+
+    let block = client.try_author_block().unwrap();
+    assert_eq!(
+        block
+            .header()
+            .consensus_digest()
+            .author
+            .map(|(author, _)| author),
+        Some(key.public())
+    );
+
+    // Another node without a key imports the block, but can't author one of its own.
+    let mut other = SignedClient::new(RuntimeState::default());
+    assert!(other.import_block(block));
+    assert!(other.try_author_block().is_none());
+}
@@ -0,0 +1,484 @@
+//! Everything the node stores for the long term, such as blocks and states, ends up in a key
+//! value database. Which database is a detail that the rest of the node should not care about,
+//! so this module hides it behind the `Backend` trait.
+//!
+//! Two backends are provided:
+//! * `MemoryBackend` keeps everything in a map. It is fast and needs no cleanup, which makes it
+//!   ideal for tests, but everything is lost when the node stops.
+//! * `SledBackend` stores everything on disk using the sled embedded database. It is only
+//!   available with the `sled` feature enabled.
+//!
+//! On top of the raw backend, `ChainDb` stores blocks and states by hash. It keeps each kind of
+//! item under its own key prefix, so that they can share a single backend without clashing.
+//! Which states it keeps is decided by a `StateStore`, in the same storage mode as the client,
+//! so a pruned node frees the disk space of the states it no longer needs. A node stores every
+//! block its client imports, see `Recorder::with_chain_db`, and restores the client from the
+//! stored blocks when it starts again.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::network::sync::LocalChain;
+use super::network::{ChainBlock, ChainHeader};
+use super::storage::{StateStore, StorageMode};
+use crate::hashing::H256;
+
+type Hash = H256;
+
+/// Everything that can go wrong while talking to a backend.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BackendError {
+    /// The underlying database failed.
+    Database(String),
+    /// A stored value could not be decoded.
+    Corrupt(String),
+    /// The configured backend is not compiled into this binary.
+    Unsupported(&'static str),
+}
+
+/// Keys and their values, in key order.
+pub type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// A set of writes that are applied all at once, or not at all.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Batch {
+    /// Every write in the batch, in order. None deletes the key.
+    writes: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the key to the value.
+    pub fn put(&mut self, key: impl Into<Vec<u8>>, value: impl Into<Vec<u8>>) {
+        self.writes.push((key.into(), Some(value.into())));
+    }
+
+    /// Remove the key.
+    pub fn delete(&mut self, key: impl Into<Vec<u8>>) {
+        self.writes.push((key.into(), None));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+}
+
+/// A key value database.
+pub trait Backend {
+    /// The value stored under the given key.
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BackendError>;
+
+    /// Apply every write in the batch atomically.
+    fn commit(&mut self, batch: Batch) -> Result<(), BackendError>;
+
+    /// Make sure that everything committed so far survives a crash. Backends that do so on
+    /// every commit need not override this.
+    fn flush(&mut self) -> Result<(), BackendError> {
+        Ok(())
+    }
+
+    /// Every key that starts with the given prefix, and its value, in key order.
+    fn iter_prefix(&self, prefix: &[u8]) -> Result<Entries, BackendError>;
+
+    /// Set a single key to a value.
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), BackendError> {
+        let mut batch = Batch::new();
+        batch.put(key, value);
+        self.commit(batch)
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for Box<B> {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BackendError> {
+        (**self).get(key)
+    }
+
+    fn commit(&mut self, batch: Batch) -> Result<(), BackendError> {
+        (**self).commit(batch)
+    }
+
+    fn flush(&mut self) -> Result<(), BackendError> {
+        (**self).flush()
+    }
+
+    fn iter_prefix(&self, prefix: &[u8]) -> Result<Entries, BackendError> {
+        (**self).iter_prefix(prefix)
+    }
+}
+
+/// A backend that keeps everything in memory.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryBackend {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BackendError> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn commit(&mut self, batch: Batch) -> Result<(), BackendError> {
+        for (key, value) in batch.writes {
+            match value {
+                Some(value) => self.entries.insert(key, value),
+                None => self.entries.remove(&key),
+            };
+        }
+        Ok(())
+    }
+
+    fn iter_prefix(&self, prefix: &[u8]) -> Result<Entries, BackendError> {
+        Ok(self
+            .entries
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+/// A backend that stores everything on disk with sled.
+#[cfg(feature = "sled")]
+pub struct SledBackend {
+    db: sled::Db,
+}
+
+#[cfg(feature = "sled")]
+impl SledBackend {
+    /// Open the database in the given directory, creating it if necessary.
+    pub fn open(path: &Path) -> Result<Self, BackendError> {
+        let db = sled::open(path).map_err(|e| BackendError::Database(e.to_string()))?;
+        Ok(SledBackend { db })
+    }
+}
+
+#[cfg(feature = "sled")]
+impl Backend for SledBackend {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BackendError> {
+        self.db
+            .get(key)
+            .map(|value| value.map(|value| value.to_vec()))
+            .map_err(|e| BackendError::Database(e.to_string()))
+    }
+
+    fn commit(&mut self, batch: Batch) -> Result<(), BackendError> {
+        let mut sled_batch = sled::Batch::default();
+        for (key, value) in batch.writes {
+            match value {
+                Some(value) => sled_batch.insert(key, value),
+                None => sled_batch.remove(key),
+            }
+        }
+        self.db
+            .apply_batch(sled_batch)
+            .and_then(|_| self.db.flush().map(|_| ()))
+            .map_err(|e| BackendError::Database(e.to_string()))
+    }
+
+    fn flush(&mut self) -> Result<(), BackendError> {
+        self.db
+            .flush()
+            .map(|_| ())
+            .map_err(|e| BackendError::Database(e.to_string()))
+    }
+
+    fn iter_prefix(&self, prefix: &[u8]) -> Result<Entries, BackendError> {
+        self.db
+            .scan_prefix(prefix)
+            .map(|entry| {
+                entry
+                    .map(|(key, value)| (key.to_vec(), value.to_vec()))
+                    .map_err(|e| BackendError::Database(e.to_string()))
+            })
+            .collect()
+    }
+}
+
+/// Which backend a node stores its data in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Keep everything in memory, and lose it on shutdown.
+    #[default]
+    Memory,
+    /// Store everything on disk with sled. Requires the `sled` feature.
+    Sled,
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(BackendKind::Memory),
+            "sled" => Ok(BackendKind::Sled),
+            _ => Err(format!("unknown backend {s:?}, expected memory or sled")),
+        }
+    }
+}
+
+/// The name of the directory, inside the data directory, that on-disk backends use.
+pub const DB_DIR: &str = "db";
+
+/// Open a backend of the given kind, storing its files in the given data directory.
+pub fn open(kind: BackendKind, data_dir: &Path) -> Result<Box<dyn Backend + Send>, BackendError> {
+    match kind {
+        BackendKind::Memory => Ok(Box::new(MemoryBackend::new())),
+        #[cfg(feature = "sled")]
+        BackendKind::Sled => Ok(Box::new(SledBackend::open(&data_dir.join(DB_DIR))?)),
+        #[cfg(not(feature = "sled"))]
+        BackendKind::Sled => {
+            let _ = data_dir;
+            Err(BackendError::Unsupported(
+                "the sled backend requires building with the sled feature",
+            ))
+        }
+    }
+}
+
+const BLOCK_PREFIX: &[u8] = b"block/";
+const STATE_PREFIX: &[u8] = b"state/";
+const BEST_KEY: &[u8] = b"meta/best";
+
+fn key(prefix: &[u8], hash: Hash) -> Vec<u8> {
+    [prefix, hash.as_bytes()].concat()
+}
+
+fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    bincode::serialize(value).expect("chain data always serializes")
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, BackendError> {
+    bincode::deserialize(bytes).map_err(|e| BackendError::Corrupt(e.to_string()))
+}
+
+/// Blocks and states, stored by hash in some backend.
+pub struct ChainDb<B> {
+    backend: B,
+    /// The height of every block whose state is stored. Only used to decide which states to
+    /// prune, the states themselves are in the backend.
+    states: StateStore<()>,
+}
+
+impl<B: Backend> ChainDb<B> {
+    /// A database that keeps the state of every block.
+    pub fn new(backend: B) -> Self {
+        ChainDb {
+            backend,
+            states: StateStore::new(StorageMode::Archive, 1),
+        }
+    }
+
+    /// Keep states in the given mode, like `FullClient::with_state_pruning`.
+    pub fn with_state_pruning(mut self, mode: StorageMode, keep: u64) -> Self {
+        self.states.set_mode(mode, keep);
+        self
+    }
+
+    /// Store a block together with the state after it, and delete the states that are pruned
+    /// to make room, in a single atomic write.
+    pub fn insert_block<Blk: Serialize, S: Serialize>(
+        &mut self,
+        hash: Hash,
+        height: u64,
+        block: &Blk,
+        state: &S,
+    ) -> Result<(), BackendError> {
+        let mut batch = Batch::new();
+        batch.put(key(BLOCK_PREFIX, hash), encode(block));
+        batch.put(key(STATE_PREFIX, hash), encode(state));
+        for pruned in self.states.insert(hash, height, ()) {
+            batch.delete(key(STATE_PREFIX, pruned));
+        }
+        self.backend.commit(batch)
+    }
+
+    /// Keep the state of the given block from now on, like the client does once the block is
+    /// finalized. The state of the previously finalized block may be deleted.
+    pub fn finalize(&mut self, hash: Hash) -> Result<(), BackendError> {
+        let Ok(pruned) = self.states.finalize(hash) else {
+            return Ok(());
+        };
+        self.prune_states(&pruned)
+    }
+
+    pub fn block<Blk: DeserializeOwned>(&self, hash: Hash) -> Result<Option<Blk>, BackendError> {
+        self.backend
+            .get(&key(BLOCK_PREFIX, hash))?
+            .map(|bytes| decode(&bytes))
+            .transpose()
+    }
+
+    pub fn state<S: DeserializeOwned>(&self, hash: Hash) -> Result<Option<S>, BackendError> {
+        self.backend
+            .get(&key(STATE_PREFIX, hash))?
+            .map(|bytes| decode(&bytes))
+            .transpose()
+    }
+
+    /// Throw away the states of the given blocks, keeping the blocks themselves. This is how
+    /// pruned nodes free up space.
+    pub fn prune_states(&mut self, hashes: &[Hash]) -> Result<(), BackendError> {
+        let mut batch = Batch::new();
+        for &hash in hashes {
+            batch.delete(key(STATE_PREFIX, hash));
+        }
+        self.backend.commit(batch)
+    }
+
+    /// The hashes of every stored block.
+    pub fn block_hashes(&self) -> Result<Vec<Hash>, BackendError> {
+        self.backend
+            .iter_prefix(BLOCK_PREFIX)?
+            .into_iter()
+            .map(|(key, _)| {
+                key[BLOCK_PREFIX.len()..]
+                    .try_into()
+                    .map(H256)
+                    .map_err(|_| BackendError::Corrupt("malformed block key".into()))
+            })
+            .collect()
+    }
+
+    pub fn set_best(&mut self, hash: Hash) -> Result<(), BackendError> {
+        self.backend.put(BEST_KEY, hash.as_bytes())
+    }
+
+    /// The best block, as last recorded with `set_best`.
+    pub fn best(&self) -> Result<Option<Hash>, BackendError> {
+        self.backend
+            .get(BEST_KEY)?
+            .map(|bytes| decode_hash(&bytes))
+            .transpose()
+    }
+
+    /// Make sure that every block stored so far survives a crash.
+    pub fn flush(&mut self) -> Result<(), BackendError> {
+        self.backend.flush()
+    }
+
+    /// Import every stored block into the given chain, parents before children, and return
+    /// how many were new to it. The chain executes the blocks again, so it ends up with the
+    /// same states that were stored.
+    pub fn restore<Blk>(&mut self, chain: &mut impl LocalChain<Blk>) -> Result<usize, BackendError>
+    where
+        Blk: ChainBlock + DeserializeOwned,
+    {
+        let mut blocks = Vec::new();
+        for hash in self.block_hashes()? {
+            let block: Blk = self
+                .block(hash)?
+                .ok_or_else(|| BackendError::Corrupt("block vanished while restoring".into()))?;
+            blocks.push(block);
+        }
+        blocks.sort_by_key(|block| block.header().height());
+
+        let mut restored = 0;
+        let mut pruned = Vec::new();
+        for block in blocks {
+            let (hash, height) = (block.header().hash(), block.header().height());
+            if self.backend.get(&key(STATE_PREFIX, hash))?.is_some() {
+                pruned.extend(self.states.insert(hash, height, ()));
+            }
+            if !chain.is_known(hash) && chain.import(block) {
+                restored += 1;
+            }
+        }
+        self.prune_states(&pruned)?;
+        Ok(restored)
+    }
+}
+
+fn decode_hash(bytes: &[u8]) -> Result<Hash, BackendError> {
+    bytes
+        .try_into()
+        .map(H256)
+        .map_err(|_| BackendError::Corrupt("malformed hash".into()))
+}
+
+#[cfg(test)]
+fn exercise_backend(backend: &mut impl Backend) {
+    let mut batch = Batch::new();
+    batch.put(b"a/1".to_vec(), b"one".to_vec());
+    batch.put(b"a/2".to_vec(), b"two".to_vec());
+    batch.put(b"b/1".to_vec(), b"other".to_vec());
+    batch.delete(b"a/2".to_vec());
+    backend.commit(batch).unwrap();
+    backend.put(b"a/3", b"three").unwrap();
+
+    assert_eq!(backend.get(b"a/1").unwrap(), Some(b"one".to_vec()));
+    assert_eq!(backend.get(b"a/2").unwrap(), None);
+    let keys: Vec<Vec<u8>> = backend
+        .iter_prefix(b"a/")
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(keys, vec![b"a/1".to_vec(), b"a/3".to_vec()]);
+}
+
+#[test]
+fn backend_memory_get_put_and_prefix() {
+    exercise_backend(&mut MemoryBackend::new());
+}
+
+#[cfg(feature = "sled")]
+#[test]
+fn backend_sled_get_put_and_prefix() {
+    let dir = std::env::temp_dir().join(format!("backend-sled-{}", std::process::id()));
+    exercise_backend(&mut SledBackend::open(&dir).unwrap());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn backend_chain_db_stores_blocks_and_states() {
+    let mut db = ChainDb::new(open(BackendKind::Memory, Path::new("unused")).unwrap());
+    db.insert_block(H256::from(7), 1, &"block seven".to_string(), &70u64)
+        .unwrap();
+    db.insert_block(H256::from(8), 2, &"block eight".to_string(), &80u64)
+        .unwrap();
+    db.set_best(H256::from(8)).unwrap();
+    db.prune_states(&[H256::from(7)]).unwrap();
+
+    assert_eq!(
+        db.block::<String>(H256::from(7)).unwrap().as_deref(),
+        Some("block seven")
+    );
+    assert_eq!(db.state::<u64>(H256::from(7)).unwrap(), None);
+    assert_eq!(db.state::<u64>(H256::from(8)).unwrap(), Some(80));
+    assert_eq!(
+        db.block_hashes().unwrap(),
+        vec![H256::from(7), H256::from(8)]
+    );
+    assert_eq!(db.best().unwrap(), Some(H256::from(8)));
+}
+
+#[test]
+fn backend_chain_db_prunes_states_like_the_client() {
+    let mut db = ChainDb::new(MemoryBackend::new()).with_state_pruning(StorageMode::Pruned, 2);
+    for height in 0..3 {
+        db.insert_block(H256::from(height), height, &height, &height)
+            .unwrap();
+    }
+    db.finalize(H256::from(1)).unwrap();
+    for height in 3..6 {
+        db.insert_block(H256::from(height), height, &height, &height)
+            .unwrap();
+    }
+
+    let states: Vec<Option<u64>> = (0..6).map(|h| db.state(H256::from(h)).unwrap()).collect();
+    assert_eq!(states, [None, Some(1), None, None, Some(4), Some(5)]);
+    assert_eq!(db.block_hashes().unwrap().len(), 6);
+}
@@ -0,0 +1,265 @@
+//! The `MeteredRuntime` charges the same fee per unit of weight no matter how busy the chain is.
+//! When demand exceeds block space, users can only outbid each other with tips, and they have to
+//! guess how much is enough. Ethereum's EIP-1559 replaced such guessing with a _base fee_ that
+//! the chain sets itself, block by block:
+//! * Blocks aim for `TARGET_BLOCK_WEIGHT`, half of what they may weigh. The base fee of every
+//!   block follows from its parent: it rises if the parent weighed more than the target, and
+//!   falls if it weighed less, by at most one `BASE_FEE_CHANGE_DENOMINATOR`th.
+//! * Every extrinsic pays the base fee for each unit of its weight, and the base fee is burned.
+//!   If the author earned it, they could fill their own blocks with extrinsics that pay
+//!   themselves, and drive the base fee up for free.
+//! * Only tips go to the author, which is why the pool should rank extrinsics by their tips, see
+//!   `fee_info`.
+//!
+//! When demand is steady, the base fee settles where just enough extrinsics are willing to pay
+//! it to fill blocks up to the target. A burst of demand can still take up to the full
+//! `MAX_BLOCK_WEIGHT` for a while, until the base fee catches up. Substrate's fee multiplier
+//! works along the same lines.
+//!
+//! Unlike in Ethereum, extrinsics do not state the most they are willing to pay, so a signer
+//! pays whatever the base fee is when their extrinsic is included.
+
+use serde::{Deserialize, Serialize};
+
+use super::fee_pool::FeeInfo;
+use super::runtime::{AccountInfo, Runtime, RuntimeState};
+use super::tip::{tip_payload, TippedExtrinsic};
+use super::weight::{block_weight, is_authorized, Weighed, Weight, MAX_BLOCK_WEIGHT};
+use crate::c1_state_machine::{StateMachine, User};
+
+/// How much the extrinsics of a block should weigh together, on average.
+pub const TARGET_BLOCK_WEIGHT: Weight = MAX_BLOCK_WEIGHT / 2;
+
+/// The base fee never falls below this, so that block space is never free.
+pub const MIN_BASE_FEE: u64 = 1;
+
+/// The base fee changes by at most its own value divided by this from one block to the next.
+pub const BASE_FEE_CHANGE_DENOMINATOR: u64 = 8;
+
+/// The base fee of the block after one with the given base fee and weight. It changes in
+/// proportion to how far the weight is off the target, but always by at least one unit, unless
+/// the weight is right on target, or the base fee is at its minimum already.
+pub fn next_base_fee(base_fee: u64, weight: Weight) -> u64 {
+    let target = u128::from(TARGET_BLOCK_WEIGHT);
+    let weight = u128::from(weight);
+    let change = |off: u128| {
+        let change = u128::from(base_fee) * off / target / u128::from(BASE_FEE_CHANGE_DENOMINATOR);
+        u64::try_from(change).unwrap_or(u64::MAX).max(1)
+    };
+    match weight.cmp(&target) {
+        std::cmp::Ordering::Greater => base_fee.saturating_add(change(weight - target)),
+        std::cmp::Ordering::Less => base_fee
+            .saturating_sub(change(target - weight))
+            .max(MIN_BASE_FEE),
+        std::cmp::Ordering::Equal => base_fee,
+    }
+}
+
+/// Price a tipped extrinsic for the fee market of the `fee_pool` module. Every extrinsic pays
+/// the same base fee per weight, and the author does not get it anyway, so only the tip counts.
+pub fn fee_info(t: &TippedExtrinsic) -> FeeInfo<User> {
+    FeeInfo {
+        sender: t.extrinsic.signer,
+        nonce: t.extrinsic.nonce,
+        fee: t.tip_amount(),
+        weight: t.weight(),
+    }
+}
+
+/// The runtime state, along with the base fee for the next block.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BaseFeeState {
+    pub runtime: RuntimeState,
+    /// What every unit of weight costs in the next block.
+    pub base_fee: u64,
+}
+
+impl BaseFeeState {
+    /// A genesis state with the given runtime state and the minimum base fee.
+    pub fn genesis(runtime: RuntimeState) -> Self {
+        BaseFeeState {
+            runtime,
+            base_fee: MIN_BASE_FEE,
+        }
+    }
+
+    /// Execute a block authored by the given author on top of this state, at the current base
+    /// fee. Invalid extrinsics leave the state untouched, just like in the runtime. Then the base
+    /// fee is adjusted to the weight of the block.
+    pub fn execute_block(&self, author: User, extrinsics: &[TippedExtrinsic]) -> BaseFeeState {
+        let runtime = extrinsics.iter().fold(self.runtime.clone(), |state, t| {
+            apply(&state, self.base_fee, author, t)
+        });
+        BaseFeeState {
+            runtime,
+            base_fee: next_base_fee(self.base_fee, block_weight(extrinsics)),
+        }
+    }
+}
+
+/// Apply a tipped extrinsic at the given base fee. The signer pays the base fee for every unit
+/// of weight, which is burned, and the tip, which goes to the author. Extrinsics whose tip is not
+/// signed by their signer are invalid, and so are those whose signer can't pay both, and those
+/// that the runtime rejects. A call that fails still pays.
+pub fn apply(
+    starting_state: &RuntimeState,
+    base_fee: u64,
+    author: User,
+    t: &TippedExtrinsic,
+) -> RuntimeState {
+    let signer = t.extrinsic.signer;
+    if !is_authorized(starting_state, &t.extrinsic) {
+        return starting_state.clone();
+    }
+    if let Some(tip) = &t.tip {
+        if !tip
+            .signature
+            .verify(signer, tip_payload(&t.extrinsic, tip.amount))
+        {
+            return starting_state.clone();
+        }
+    }
+
+    let charge = base_fee
+        .checked_mul(t.weight())
+        .and_then(|fee| fee.checked_add(t.tip_amount()));
+    let mut payer = starting_state.account(signer);
+    let Some(balance) = charge.and_then(|charge| payer.balance.checked_sub(charge)) else {
+        return starting_state.clone();
+    };
+    payer.balance = balance;
+    let mut state = starting_state.clone();
+    state.set_account(signer, payer);
+
+    let mut state = Runtime::next_state(&state, &t.extrinsic);
+    let info = state.account(author);
+    state.set_account(
+        author,
+        AccountInfo {
+            balance: info.balance.saturating_add(t.tip_amount()),
+            ..info
+        },
+    );
+    state
+}
+
+#[cfg(test)]
+use super::tip::tipped;
+
+/// Simulate a market in which ten extrinsics that weigh 100 each want into every block, but
+/// only those whose value per weight is at least the base fee are submitted. Returns the base
+/// fee and the weight of every block.
+#[cfg(test)]
+fn simulate(mut base_fee: u64, values: &[u64], blocks: usize) -> Vec<(u64, Weight)> {
+    let mut history = Vec::new();
+    for _ in 0..blocks {
+        let willing = values.iter().filter(|&&value| value >= base_fee).count() as Weight;
+        let weight = (willing * 100).min(MAX_BLOCK_WEIGHT);
+        history.push((base_fee, weight));
+        base_fee = next_base_fee(base_fee, weight);
+    }
+    history
+}
+
+#[test]
+fn base_fee_follows_block_weight() {
+    assert_eq!(next_base_fee(80, TARGET_BLOCK_WEIGHT), 80);
+    assert_eq!(next_base_fee(80, MAX_BLOCK_WEIGHT), 90);
+    assert_eq!(next_base_fee(80, 0), 70);
+    assert_eq!(next_base_fee(80, 750), 85);
+
+    // Small fees still move, but never below the minimum.
+    assert_eq!(next_base_fee(1, MAX_BLOCK_WEIGHT), 2);
+    assert_eq!(next_base_fee(2, 0), 1);
+    assert_eq!(next_base_fee(MIN_BASE_FEE, 0), MIN_BASE_FEE);
+    assert_eq!(next_base_fee(u64::MAX, MAX_BLOCK_WEIGHT), u64::MAX);
+}
+
+#[test]
+fn base_fee_is_burned_and_tips_go_to_author() {
+    let mut state = BaseFeeState::genesis(RuntimeState::genesis(&[
+        (User::Alice, 1_000),
+        (User::Bob, 1_000),
+    ]));
+    state.base_fee = 2;
+    let block = [
+        tipped(User::Alice, 0, Some(30)),
+        tipped(User::Bob, 0, Some(0)),
+    ];
+
+    let next = state.execute_block(User::Charlie, &block);
+    assert_eq!(
+        next.runtime.account(User::Alice).balance,
+        1_000 - 400 - 30 - 10
+    );
+    assert_eq!(next.runtime.account(User::Bob).balance, 1_000 - 400 - 10);
+    assert_eq!(next.runtime.account(User::Charlie).balance, 10 + 30 + 10);
+    assert_eq!(
+        next.runtime.total_issuance(),
+        state.runtime.total_issuance() - 800
+    );
+    // The block weighed 400, less than the target.
+    assert_eq!(next.base_fee, 1);
+
+    // At a base fee of 5, Bob can't pay the tip on top of it.
+    let greedy = tipped(User::Bob, 0, Some(1));
+    assert_eq!(
+        apply(&state.runtime, 5, User::Charlie, &greedy),
+        state.runtime
+    );
+
+    // Replaying an extrinsic, or one with the largest nonce, costs nothing.
+    let replayed = next.execute_block(User::Charlie, &block[..1]);
+    assert_eq!(replayed.runtime, next.runtime);
+    let last = tipped(User::Alice, u64::MAX, Some(1));
+    assert_eq!(
+        apply(&state.runtime, 2, User::Charlie, &last),
+        state.runtime
+    );
+}
+
+#[test]
+fn base_fee_ranks_by_tip() {
+    let plain = fee_info(&tipped(User::Alice, 0, Some(0)));
+    let generous = fee_info(&tipped(User::Bob, 0, Some(50)));
+    assert_eq!((plain.fee, plain.weight), (0, 200));
+    assert_eq!((generous.fee, generous.weight), (50, 200));
+}
+
+#[test]
+fn base_fee_converges_to_target_under_steady_load() {
+    // Five of the extrinsics are worth more than 50 per weight, and fill a block to the target.
+    let values: Vec<u64> = (1..=10).map(|i| i * 10).collect();
+    let history = simulate(MIN_BASE_FEE, &values, 100);
+
+    // At first every extrinsic is willing to pay, and blocks are full.
+    assert_eq!(history[0], (MIN_BASE_FEE, MAX_BLOCK_WEIGHT));
+    assert!(history.windows(2).all(|w| w[0].0 <= w[1].0));
+    for &(base_fee, weight) in &history[60..] {
+        assert!(50 < base_fee && base_fee <= 60);
+        assert_eq!(weight, TARGET_BLOCK_WEIGHT);
+    }
+}
+
+#[test]
+fn base_fee_tracks_changes_in_demand() {
+    let values: Vec<u64> = (1..=10).map(|i| i * 10).collect();
+    let (settled, _) = *simulate(MIN_BASE_FEE, &values, 100).last().unwrap();
+
+    // Demand doubles: blocks overflow the target until the base fee doubles too.
+    let doubled: Vec<u64> = values.iter().map(|value| value * 2).collect();
+    let history = simulate(settled, &doubled, 100);
+    assert!(history[0].1 > TARGET_BLOCK_WEIGHT);
+    let (busy, weight) = *history.last().unwrap();
+    assert!(100 < busy && busy <= 120);
+    assert_eq!(weight, TARGET_BLOCK_WEIGHT);
+
+    // Demand drops back, and so does the base fee.
+    let (calm, weight) = *simulate(busy, &values, 100).last().unwrap();
+    assert!(50 < calm && calm <= 60);
+    assert_eq!(weight, TARGET_BLOCK_WEIGHT);
+
+    // Without any demand, it falls all the way to the minimum.
+    let (idle, weight) = *simulate(calm, &[], 100).last().unwrap();
+    assert_eq!((idle, weight), (MIN_BASE_FEE, 0));
+}
@@ -0,0 +1,371 @@
+//! A consensus engine is only as good as the misbehavior it survives. Honest nodes on a bad
+//! network are one thing, but a real network also has nodes that are out to break it. This
+//! module collects a few classic kinds of misbehaving nodes, so that we can throw them at each
+//! engine and see which attacks it tolerates.
+//!
+//! Each kind of misbehavior is a wrapper around an ordinary node. The wrapper passes most
+//! things through, and only misbehaves in one specific way:
+//!
+//! * [`Silent`] never authors a block. It is a node that crashed, or one that simply skips
+//!   its turns. A round robin PoA chain stalls for a slot whenever it is the silent node's turn.
+//! * [`Censoring`] refuses to import, and therefore to build on, blocks it does not like. Pair
+//!   it with a `CensoringPool` from chapter 4 to also keep the offending transactions out of
+//!   its own blocks.
+//! * [`Equivocator`] authors two conflicting blocks at the same height, and tells half of its
+//!   peers about one and the other half about the other.
+//! * [`SealForger`] authors blocks whenever it is not eligible to, with a seal it made up.
+//!
+//! The wrappers are nodes themselves, so they can take part in the simulator and in the devnet.
+//! Honest and misbehaving nodes are different types, though, so to put them in the same network,
+//! box them all as `Box<dyn Node<Block>>`. Parts of the misbehavior depend on the block type, such
+//! as how to make a conflicting block or forge a seal, so those are left to a closure.
+//!
+//! ```ignore
+//! let mut simulator = Simulator::new(4, Duration::from_secs(1), conditions, |info| {
+//!     let client = make_client(info);
+//!     if info.index == 0 {
+//!         Box::new(Silent::new(client)) as Box<dyn Node<_>>
+//!     } else {
+//!         Box::new(client)
+//!     }
+//! });
+//! ```
+
+use super::authoring::AuthorBlocks;
+use super::network::sync::LocalChain;
+use super::network::{ChainBlock, ChainHeader};
+use crate::hashing::H256;
+
+/// Anything that can take part in a network of nodes.
+pub trait Node<B>: AuthorBlocks<Block = B> + LocalChain<B> {}
+
+impl<B, N: AuthorBlocks<Block = B> + LocalChain<B> + ?Sized> Node<B> for N {}
+
+/// A node that never authors a block. It still imports the blocks of others.
+pub struct Silent<N> {
+    pub node: N,
+}
+
+impl<N> Silent<N> {
+    pub fn new(node: N) -> Self {
+        Silent { node }
+    }
+}
+
+impl<N: AuthorBlocks> AuthorBlocks for Silent<N> {
+    type Block = N::Block;
+
+    fn try_author_block(&mut self) -> Option<N::Block> {
+        None
+    }
+}
+
+impl<N: LocalChain<B>, B> LocalChain<B> for Silent<N> {
+    fn is_known(&self, hash: H256) -> bool {
+        self.node.is_known(hash)
+    }
+
+    fn import(&mut self, block: B) -> bool {
+        self.node.import(block)
+    }
+}
+
+/// A node that refuses to import any block for which `censored` returns true. Since it never
+/// imports such a block, it never builds on it either, and neither on anything that comes after
+/// it.
+pub struct Censoring<N, F> {
+    pub node: N,
+    censored: F,
+}
+
+impl<N, F> Censoring<N, F> {
+    pub fn new(node: N, censored: F) -> Self {
+        Censoring { node, censored }
+    }
+}
+
+impl<N: AuthorBlocks, F> AuthorBlocks for Censoring<N, F> {
+    type Block = N::Block;
+
+    fn try_author_block(&mut self) -> Option<N::Block> {
+        self.node.try_author_block()
+    }
+}
+
+impl<N, F, B> LocalChain<B> for Censoring<N, F>
+where
+    N: LocalChain<B>,
+    F: Fn(&B) -> bool,
+{
+    fn is_known(&self, hash: H256) -> bool {
+        self.node.is_known(hash)
+    }
+
+    fn import(&mut self, block: B) -> bool {
+        !(self.censored)(&block) && self.node.import(block)
+    }
+}
+
+/// A node that authors two conflicting blocks every time it authors at all. Every block it
+/// authors normally has a twin, made by `twin` from the original. Peers with an even index are
+/// told about the original, and peers with an odd index about the twin.
+pub struct Equivocator<N: AuthorBlocks, F> {
+    pub node: N,
+    twin: F,
+    /// The last block we authored, and its twin.
+    twins: Option<(H256, N::Block)>,
+}
+
+impl<N: AuthorBlocks, F> Equivocator<N, F> {
+    pub fn new(node: N, twin: F) -> Self {
+        Equivocator {
+            node,
+            twin,
+            twins: None,
+        }
+    }
+}
+
+impl<N, F> AuthorBlocks for Equivocator<N, F>
+where
+    N: AuthorBlocks,
+    N::Block: ChainBlock,
+    F: FnMut(&mut N, &N::Block) -> Option<N::Block>,
+{
+    type Block = N::Block;
+
+    fn try_author_block(&mut self) -> Option<N::Block> {
+        let block = self.node.try_author_block()?;
+        self.twins = (self.twin)(&mut self.node, &block).map(|twin| (block.header().hash(), twin));
+        Some(block)
+    }
+
+    fn announcement(&mut self, block: &N::Block, peer: usize) -> Option<N::Block>
+    where
+        N::Block: Clone,
+    {
+        match &self.twins {
+            Some((original, twin)) if peer % 2 == 1 && *original == block.header().hash() => {
+                Some(twin.clone())
+            }
+            _ => Some(block.clone()),
+        }
+    }
+}
+
+impl<N: AuthorBlocks + LocalChain<B>, F, B> LocalChain<B> for Equivocator<N, F> {
+    fn is_known(&self, hash: H256) -> bool {
+        self.node.is_known(hash)
+    }
+
+    fn import(&mut self, block: B) -> bool {
+        self.node.import(block)
+    }
+}
+
+/// A node that does not wait for its turn. Whenever it is not eligible to author a block, it
+/// makes one up with `forge`, which is expected to give it a seal that should not pass. The
+/// made up block is not imported locally, only announced.
+pub struct SealForger<N, F> {
+    pub node: N,
+    forge: F,
+}
+
+impl<N, F> SealForger<N, F> {
+    pub fn new(node: N, forge: F) -> Self {
+        SealForger { node, forge }
+    }
+}
+
+impl<N, F> AuthorBlocks for SealForger<N, F>
+where
+    N: AuthorBlocks,
+    F: FnMut(&mut N) -> Option<N::Block>,
+{
+    type Block = N::Block;
+
+    fn try_author_block(&mut self) -> Option<N::Block> {
+        self.node
+            .try_author_block()
+            .or_else(|| (self.forge)(&mut self.node))
+    }
+}
+
+impl<N: LocalChain<B>, F, B> LocalChain<B> for SealForger<N, F> {
+    fn is_known(&self, hash: H256) -> bool {
+        self.node.is_known(hash)
+    }
+
+    fn import(&mut self, block: B) -> bool {
+        self.node.import(block)
+    }
+}
+
+#[cfg(test)]
+use std::time::Duration;
+
+#[cfg(test)]
+use super::devnet::{NetworkConditions, NodeInfo};
+#[cfg(test)]
+use super::simulator::{LongestChainNode, Simulator, TestBlock, TraceEvent};
+
+/// A test node that only authors when the height of the next block modulo the number of nodes
+/// is its index, like a round robin PoA authority.
+#[cfg(test)]
+struct RoundRobin {
+    chain: LongestChainNode,
+    count: usize,
+}
+
+#[cfg(test)]
+impl AuthorBlocks for RoundRobin {
+    type Block = TestBlock;
+
+    fn try_author_block(&mut self) -> Option<TestBlock> {
+        let turn = (self.chain.best.height + 1) as usize % self.count;
+        if turn != self.chain.index {
+            return None;
+        }
+        self.chain.try_author_block()
+    }
+}
+
+#[cfg(test)]
+impl LocalChain<TestBlock> for RoundRobin {
+    fn is_known(&self, hash: H256) -> bool {
+        self.chain.is_known(hash)
+    }
+
+    fn import(&mut self, block: TestBlock) -> bool {
+        self.chain.import(block)
+    }
+}
+
+/// Simulate three nodes for ten seconds on a perfect network, where the last node is made to
+/// misbehave by `byzantine`.
+#[cfg(test)]
+fn simulate<N: Node<TestBlock> + 'static>(
+    honest: impl Fn(&NodeInfo) -> N,
+    byzantine: impl Fn(N) -> Box<dyn Node<TestBlock>>,
+) -> Simulator<Box<dyn Node<TestBlock>>> {
+    let mut simulator = Simulator::new(
+        3,
+        Duration::from_secs(1),
+        NetworkConditions::new(5),
+        |info| match info.index {
+            2 => byzantine(honest(info)),
+            _ => Box::new(honest(info)) as Box<dyn Node<TestBlock>>,
+        },
+    );
+    simulator.run_until(Duration::from_secs(10));
+    simulator
+}
+
+#[cfg(test)]
+fn round_robin(info: &NodeInfo) -> RoundRobin {
+    RoundRobin {
+        chain: LongestChainNode::new(info),
+        count: 3,
+    }
+}
+
+/// The blocks in the trace that the given node authored.
+#[cfg(test)]
+fn authored_by(simulator: &Simulator<Box<dyn Node<TestBlock>>>, author: usize) -> Vec<H256> {
+    simulator
+        .trace()
+        .iter()
+        .filter_map(|event| match event {
+            TraceEvent::Authored { node, block, .. } if *node == author => Some(*block),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn byzantine_silent_node_never_authors() {
+    let simulator = simulate(LongestChainNode::new, |node| Box::new(Silent::new(node)));
+
+    assert!(authored_by(&simulator, 2).is_empty());
+    let honest = authored_by(&simulator, 0);
+    assert!(honest.len() > 5);
+    // It still listens, though.
+    assert!(honest
+        .iter()
+        .all(|&block| simulator.node(2).is_known(block)));
+}
+
+#[test]
+fn byzantine_censoring_node_ignores_censored_blocks() {
+    let simulator = simulate(LongestChainNode::new, |node| {
+        Box::new(Censoring::new(node, |block: &TestBlock| block.author == 0))
+    });
+
+    let censored = authored_by(&simulator, 0);
+    assert!(censored.len() > 5);
+    assert!(censored
+        .iter()
+        .all(|&block| !simulator.node(2).is_known(block)));
+    assert!(censored
+        .iter()
+        .all(|&block| simulator.node(1).is_known(block)));
+    assert!(!authored_by(&simulator, 2).is_empty());
+}
+
+#[test]
+fn byzantine_equivocator_splits_its_peers() {
+    let simulator = simulate(round_robin, |node| {
+        let twin = |node: &mut RoundRobin, block: &TestBlock| {
+            let parent = &node.chain.blocks[&block.parent];
+            Some(TestBlock::child(parent, block.author, block.nonce + 1))
+        };
+        Box::new(Equivocator::new(node, twin))
+    });
+
+    // Node 0 and node 1 were told about different blocks at the height node 2 authored.
+    let told = |peer: usize| {
+        simulator.trace().iter().find_map(|event| match event {
+            TraceEvent::Delivered {
+                from: 2, to, block, ..
+            } if *to == peer => Some(*block),
+            _ => None,
+        })
+    };
+    let (even, odd) = (told(0).unwrap(), told(1).unwrap());
+    assert_ne!(even, odd);
+    assert!(simulator.node(0).is_known(even) && !simulator.node(0).is_known(odd));
+    assert!(simulator.node(1).is_known(odd) && !simulator.node(1).is_known(even));
+}
+
+#[test]
+fn byzantine_forged_seals_are_rejected() {
+    // Only nodes 0 and 1 are authorities, so every block node 2 authors is forged.
+    let authorities = |info: &NodeInfo| RoundRobin {
+        chain: LongestChainNode::new(info),
+        count: 2,
+    };
+    let forged = H256::from(7);
+    let simulator = simulate(authorities, |node| {
+        let forge = move |node: &mut RoundRobin| {
+            let mut block = TestBlock::child(&node.chain.best, 2, 0);
+            block.hash = forged;
+            Some(block)
+        };
+        Box::new(SealForger::new(node, forge))
+    });
+
+    // Every peer was told about the forged block, but none of them took it.
+    let deliveries: Vec<bool> = simulator
+        .trace()
+        .iter()
+        .filter_map(|event| match event {
+            TraceEvent::Delivered {
+                block, imported, ..
+            } if *block == forged => Some(*imported),
+            _ => None,
+        })
+        .collect();
+    assert!(!deliveries.is_empty());
+    assert!(deliveries.iter().all(|imported| !imported));
+    assert!(authored_by(&simulator, 0).len() > 2);
+}
@@ -0,0 +1,374 @@
+//! A node has quite a few knobs: which port to listen on, which peers to dial, where to keep its
+//! data, how often to author blocks, and so on. Rather than hardcoding them, we collect them all
+//! in a `NodeConfig` that is loaded from a TOML file.
+//!
+//! Every setting has a sensible default, so a config file only needs to mention the settings it
+//! changes. On top of the file, any setting can be overridden with an environment variable. This
+//! is handy when running several nodes from the same file, for example in containers.
+//!
+//! ```toml
+//! data_dir = "/var/lib/node"
+//!
+//! [network]
+//! port = 30333
+//! peers = ["10.0.0.2:30333"]
+//! discovery = false
+//!
+//! [consensus]
+//! block_time_ms = 6000
+//!
+//! [storage]
+//! backend = "sled"
+//! mode = "pruned"
+//! keep_states = 1024
+//!
+//! [rpc]
+//! addr = "0.0.0.0:9933"
+//! unsafe_token = "change me"
+//!
+//! [telemetry]
+//! url = "ws://dashboard.local:8000/submit"
+//! node_name = "alice-laptop"
+//! secret = "shared with the collector"
+//!
+//! [issuance]
+//! author_percent = 20
+//! schedule = { kind = "percent_of_stake", percent = 5, period = 100000 }
+//! ```
+//!
+//! The `[issuance]` section is part of the chain spec rather than a setting of the node: nodes
+//! with different schedules disagree about every block's state. That is why it can't be
+//! overridden from the environment.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::backend::BackendKind;
+use super::issuance::IssuanceConfig;
+use super::storage::StorageMode;
+
+/// Everything that can go wrong while loading a config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The config file could not be read.
+    Io(String),
+    /// The config file is not valid TOML, or contains unknown or mistyped settings.
+    Parse(String),
+    /// An environment variable override could not be parsed.
+    InvalidEnv { var: &'static str, reason: String },
+}
+
+/// Peer to peer networking settings.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// The port to listen for peers on.
+    pub port: u16,
+    /// The addresses of the peers to connect to on startup.
+    pub peers: Vec<String>,
+    /// Whether to find peers on the local network automatically.
+    pub discovery: bool,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            port: 30333,
+            peers: Vec::new(),
+            discovery: true,
+        }
+    }
+}
+
+/// Consensus and authoring settings.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConsensusConfig {
+    /// Whether this node tries to author blocks at all.
+    pub author: bool,
+    /// How often the node tries to author a block, in milliseconds.
+    pub block_time_ms: u64,
+    /// The balance every development account starts out with in the genesis state.
+    pub dev_endowment: u64,
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        ConsensusConfig {
+            author: true,
+            block_time_ms: 2000,
+            dev_endowment: 1_000_000,
+        }
+    }
+}
+
+impl ConsensusConfig {
+    /// The block time as a duration.
+    pub fn block_time(&self) -> Duration {
+        Duration::from_millis(self.block_time_ms)
+    }
+}
+
+/// Settings for how the node stores the chain.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// The database that blocks and states are kept in.
+    pub backend: BackendKind,
+    /// Whether to keep the state of every block, or only of recent and finalized ones.
+    pub mode: StorageMode,
+    /// How many of the most recent heights keep their states in pruned mode.
+    pub keep_states: u64,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            backend: BackendKind::Memory,
+            mode: StorageMode::Archive,
+            keep_states: 256,
+        }
+    }
+}
+
+/// Settings for the RPC and metrics servers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfig {
+    /// The address that JSON-RPC is served on.
+    pub addr: SocketAddr,
+    /// The address that Prometheus metrics are served on.
+    pub metrics_addr: SocketAddr,
+    /// The token that callers must present to use unsafe methods. Without it, unsafe methods
+    /// are only available when `addr` is a loopback address.
+    pub unsafe_token: Option<String>,
+    /// How many requests per second each client may make. Zero means no limit.
+    pub rate_limit: u32,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        RpcConfig {
+            addr: ([127, 0, 0, 1], 9933).into(),
+            metrics_addr: ([127, 0, 0, 1], 9615).into(),
+            unsafe_token: None,
+            rate_limit: 50,
+        }
+    }
+}
+
+/// Settings for streaming telemetry to a collector.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// The `ws://` url of the collector. Telemetry is disabled when this is not set.
+    pub url: Option<String>,
+    /// The name this node shows up under on the collector's dashboard.
+    pub node_name: String,
+    /// The secret shared with the collector. When it is set, every message carries an HMAC tag
+    /// made with it, so that the collector can tell our messages from forged ones.
+    pub secret: Option<String>,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        TelemetryConfig {
+            url: None,
+            node_name: "node".into(),
+            secret: None,
+        }
+    }
+}
+
+/// The complete configuration of a node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeConfig {
+    /// The directory that the node keeps its data in.
+    pub data_dir: PathBuf,
+    pub network: NetworkConfig,
+    pub consensus: ConsensusConfig,
+    pub storage: StorageConfig,
+    pub rpc: RpcConfig,
+    pub telemetry: TelemetryConfig,
+    /// How new coins are minted, and who gets them.
+    pub issuance: IssuanceConfig,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        NodeConfig {
+            data_dir: PathBuf::from("data"),
+            network: NetworkConfig::default(),
+            consensus: ConsensusConfig::default(),
+            storage: StorageConfig::default(),
+            rpc: RpcConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            issuance: IssuanceConfig::default(),
+        }
+    }
+}
+
+impl NodeConfig {
+    /// Parse a config from the contents of a TOML file.
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        toml::from_str(toml).map_err(|e| ConfigError::Parse(e.to_string()))
+    }
+
+    /// Load the config for a node. The config file is optional, and the defaults are used
+    /// when it is missing. Environment variables are applied on top either way.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut config = match path {
+            Some(path) => {
+                let toml = std::fs::read_to_string(path)
+                    .map_err(|e| ConfigError::Io(format!("{}: {e}", path.display())))?;
+                Self::from_toml(&toml)?
+            }
+            None => Self::default(),
+        };
+        config.apply_env(|var| std::env::var(var).ok())?;
+        Ok(config)
+    }
+
+    /// Apply environment variable overrides, looking each variable up with the given function.
+    ///
+    /// The variables are named after the settings they override, for example `NODE_RPC_ADDR`
+    /// overrides `rpc.addr`. Lists are comma separated.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(value) = var("NODE_DATA_DIR") {
+            self.data_dir = value.into();
+        }
+        override_with(&var, "NODE_NETWORK_PORT", &mut self.network.port)?;
+        if let Some(value) = var("NODE_NETWORK_PEERS") {
+            self.network.peers = value
+                .split(',')
+                .map(str::trim)
+                .filter(|peer| !peer.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        override_with(&var, "NODE_NETWORK_DISCOVERY", &mut self.network.discovery)?;
+        override_with(&var, "NODE_CONSENSUS_AUTHOR", &mut self.consensus.author)?;
+        override_with(
+            &var,
+            "NODE_CONSENSUS_BLOCK_TIME_MS",
+            &mut self.consensus.block_time_ms,
+        )?;
+        override_with(
+            &var,
+            "NODE_CONSENSUS_DEV_ENDOWMENT",
+            &mut self.consensus.dev_endowment,
+        )?;
+        override_with(&var, "NODE_STORAGE_BACKEND", &mut self.storage.backend)?;
+        override_with(&var, "NODE_STORAGE_MODE", &mut self.storage.mode)?;
+        override_with(
+            &var,
+            "NODE_STORAGE_KEEP_STATES",
+            &mut self.storage.keep_states,
+        )?;
+        override_with(&var, "NODE_RPC_ADDR", &mut self.rpc.addr)?;
+        override_with(&var, "NODE_RPC_METRICS_ADDR", &mut self.rpc.metrics_addr)?;
+        if let Some(value) = var("NODE_RPC_UNSAFE_TOKEN") {
+            self.rpc.unsafe_token = Some(value);
+        }
+        override_with(&var, "NODE_RPC_RATE_LIMIT", &mut self.rpc.rate_limit)?;
+        if let Some(value) = var("NODE_TELEMETRY_URL") {
+            self.telemetry.url = Some(value);
+        }
+        if let Some(value) = var("NODE_TELEMETRY_NODE_NAME") {
+            self.telemetry.node_name = value;
+        }
+        if let Some(value) = var("NODE_TELEMETRY_SECRET") {
+            self.telemetry.secret = Some(value);
+        }
+        Ok(())
+    }
+}
+
+/// Parse the given variable into the setting, if it is set.
+fn override_with<T>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &'static str,
+    setting: &mut T,
+) -> Result<(), ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    if let Some(value) = var(name) {
+        *setting = value.parse().map_err(|e: T::Err| ConfigError::InvalidEnv {
+            var: name,
+            reason: e.to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+use super::issuance::IssuanceSchedule;
+
+#[test]
+fn config_defaults_fill_in_missing_settings() {
+    let config = NodeConfig::from_toml("[consensus]\nblock_time_ms = 6000\n").unwrap();
+
+    assert_eq!(config.consensus.block_time(), Duration::from_secs(6));
+    assert_eq!(config.rpc, RpcConfig::default());
+    assert_eq!(config.network, NetworkConfig::default());
+}
+
+#[test]
+fn config_reads_issuance_schedule() {
+    let config = NodeConfig::from_toml(
+        "[issuance.schedule]\nkind = \"halving\"\ninitial = 64\nevery = 1000\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.issuance.schedule,
+        IssuanceSchedule::Halving {
+            initial: 64,
+            every: 1000
+        }
+    );
+    assert_eq!(config.issuance.author_percent, 100);
+}
+
+#[test]
+fn config_rejects_unknown_settings() {
+    assert!(matches!(
+        NodeConfig::from_toml("[network]\nprot = 1\n"),
+        Err(ConfigError::Parse(_))
+    ));
+}
+
+#[test]
+fn config_env_overrides_file() {
+    let mut config = NodeConfig::from_toml("[network]\nport = 1000\n").unwrap();
+    config
+        .apply_env(|var| match var {
+            "NODE_NETWORK_PORT" => Some("2000".into()),
+            "NODE_NETWORK_PEERS" => Some("a:1, b:2,".into()),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(config.network.port, 2000);
+    assert_eq!(config.network.peers, vec!["a:1", "b:2"]);
+}
+
+#[test]
+fn config_invalid_env_is_reported() {
+    let mut config = NodeConfig::default();
+    let result = config.apply_env(|var| (var == "NODE_RPC_ADDR").then(|| "nowhere".into()));
+
+    assert!(matches!(
+        result,
+        Err(ConfigError::InvalidEnv {
+            var: "NODE_RPC_ADDR",
+            ..
+        })
+    ));
+}
@@ -0,0 +1,276 @@
+//! When a block does not end up with the state root its header promises, the client only tells
+//! us that the block is invalid. It does not tell us which of the extrinsics went differently
+//! than the author thought. To find out, we have to execute the block ourselves, one extrinsic
+//! at a time, and look at the state in between.
+//!
+//! A `BlockDebugger` does exactly that. It starts from the state of the parent block, and every
+//! step executes the next extrinsic and writes a receipt for it. It keeps every intermediate
+//! state around, so it can step back as well as forward.
+//!
+//! ```ignore
+//! let mut debugger = BlockDebugger::<Runtime>::for_block(parent_state, &block);
+//! while let Some(receipt) = debugger.step() {
+//!     println!("{receipt:?}");
+//! }
+//! assert_eq!(debugger.matches_header(), Some(true));
+//! ```
+//!
+//! The `tui debug` mode of the learner TUI drives a debugger interactively on any block that a
+//! node stored in its data directory.
+
+use std::hash::Hash;
+
+use super::runtime::{AccountInfo, RuntimeState, ACCOUNTS};
+use super::Block;
+use crate::c1_state_machine::{StateMachine, User};
+use crate::c3_consensus::Consensus;
+use crate::hashing::{extrinsic_hash, hash, H256};
+
+/// What happened when a single extrinsic was executed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Receipt {
+    /// The position of the extrinsic in the block.
+    pub index: usize,
+    /// The hash of the extrinsic.
+    pub extrinsic: H256,
+    /// Whether the extrinsic changed the state at all. State machines leave the state untouched
+    /// for invalid transitions, so an extrinsic that changed nothing was most likely rejected.
+    pub applied: bool,
+    /// The root of the state before the extrinsic.
+    pub state_root_before: H256,
+    /// The root of the state after the extrinsic.
+    pub state_root_after: H256,
+}
+
+/// Executes the extrinsics of a block one at a time.
+pub struct BlockDebugger<SM: StateMachine> {
+    extrinsics: Vec<SM::Transition>,
+    /// The state before every executed extrinsic, followed by the current state. Its length is
+    /// always one more than the number of executed extrinsics.
+    states: Vec<SM::State>,
+    receipts: Vec<Receipt>,
+    /// The state root the header of the block promises, if we know of one.
+    expected_root: Option<H256>,
+}
+
+impl<SM> BlockDebugger<SM>
+where
+    SM: StateMachine,
+    SM::State: Clone + Hash + PartialEq,
+    SM::Transition: Hash,
+{
+    /// A debugger that executes the given extrinsics on top of the given state.
+    pub fn new(state: SM::State, extrinsics: Vec<SM::Transition>) -> Self {
+        BlockDebugger {
+            extrinsics,
+            states: vec![state],
+            receipts: Vec::new(),
+            expected_root: None,
+        }
+    }
+
+    /// A debugger that executes the body of the given block on top of the state of its parent.
+    pub fn for_block<C: Consensus>(parent_state: SM::State, block: &Block<C, SM>) -> Self
+    where
+        SM::Transition: Clone,
+    {
+        let mut debugger = Self::new(parent_state, block.body().to_vec());
+        debugger.expected_root = Some(block.header().state_root());
+        debugger
+    }
+
+    /// Execute the next extrinsic. Returns its receipt, or `None` when every extrinsic has
+    /// already been executed.
+    pub fn step(&mut self) -> Option<&Receipt> {
+        let index = self.position();
+        let extrinsic = self.extrinsics.get(index)?;
+        let before = self.state();
+        let after = SM::next_state(before, extrinsic);
+        self.receipts.push(Receipt {
+            index,
+            extrinsic: extrinsic_hash(extrinsic),
+            applied: after != *before,
+            state_root_before: hash(before),
+            state_root_after: hash(&after),
+        });
+        self.states.push(after);
+        self.receipts.last()
+    }
+
+    /// Undo the last executed extrinsic. Returns false when there is nothing to undo.
+    pub fn back(&mut self) -> bool {
+        if self.receipts.pop().is_none() {
+            return false;
+        }
+        self.states.pop();
+        true
+    }
+
+    /// Execute every remaining extrinsic, and return how many there were.
+    pub fn run_to_end(&mut self) -> usize {
+        let mut steps = 0;
+        while self.step().is_some() {
+            steps += 1;
+        }
+        steps
+    }
+
+    /// The number of extrinsics executed so far, which is also the index of the next one.
+    pub fn position(&self) -> usize {
+        self.receipts.len()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.position() == self.extrinsics.len()
+    }
+
+    /// The state after the extrinsics executed so far.
+    pub fn state(&self) -> &SM::State {
+        self.states
+            .last()
+            .expect("there is always a starting state")
+    }
+
+    /// The state the block started from.
+    pub fn starting_state(&self) -> &SM::State {
+        &self.states[0]
+    }
+
+    /// The extrinsic that the next step executes.
+    pub fn next_extrinsic(&self) -> Option<&SM::Transition> {
+        self.extrinsics.get(self.position())
+    }
+
+    pub fn extrinsics(&self) -> &[SM::Transition] {
+        &self.extrinsics
+    }
+
+    /// The receipts of the extrinsics executed so far, in order.
+    pub fn receipts(&self) -> &[Receipt] {
+        &self.receipts
+    }
+
+    /// Whether the final state matches the state root in the header of the block. `None` until
+    /// every extrinsic has been executed, and for debuggers that were not made from a block.
+    pub fn matches_header(&self) -> Option<bool> {
+        let expected = self.expected_root?;
+        self.is_done().then(|| hash(self.state()) == expected)
+    }
+}
+
+/// The accounts that differ between two runtime states, with their information before and
+/// after.
+pub fn account_changes(
+    before: &RuntimeState,
+    after: &RuntimeState,
+) -> Vec<(User, AccountInfo, AccountInfo)> {
+    ACCOUNTS
+        .iter()
+        .map(|&who| (who, before.account(who), after.account(who)))
+        .filter(|(_, before, after)| before != after)
+        .collect()
+}
+
+#[cfg(test)]
+use crate::c3_consensus::{Header, Pow};
+#[cfg(test)]
+use crate::merkle::merkle_root;
+
+/// A test state machine that adds up numbers, and refuses to go above 100.
+#[cfg(test)]
+struct Counter;
+
+#[cfg(test)]
+impl StateMachine for Counter {
+    type State = u64;
+    type Transition = u64;
+
+    fn next_state(starting_state: &u64, t: &u64) -> u64 {
+        match starting_state + t {
+            sum if sum > 100 => *starting_state,
+            sum => sum,
+        }
+    }
+}
+
+/// Counts up by 10, then tries to go above 100, then counts up by 20.
+#[cfg(test)]
+fn debugger() -> BlockDebugger<Counter> {
+    BlockDebugger::new(0, vec![10, 200, 20])
+}
+
+#[test]
+fn debugger_steps_through_every_extrinsic() {
+    let mut debugger = debugger();
+
+    let first = *debugger.step().unwrap();
+    assert_eq!(first.index, 0);
+    assert!(first.applied);
+    assert_eq!(first.extrinsic, extrinsic_hash(&10u64));
+    assert_eq!(first.state_root_before, hash(&0u64));
+    assert_eq!(first.state_root_after, hash(&10u64));
+    assert_eq!(*debugger.state(), 10);
+
+    // The second extrinsic is rejected, and leaves the state alone.
+    let second = *debugger.step().unwrap();
+    assert!(!second.applied);
+    assert_eq!(second.state_root_before, second.state_root_after);
+
+    assert_eq!(debugger.run_to_end(), 1);
+    assert!(debugger.is_done() && debugger.step().is_none());
+    assert_eq!(*debugger.state(), 30);
+    assert_eq!(debugger.receipts().len(), 3);
+    // Without a header, there is nothing to compare the final state to.
+    assert_eq!(debugger.matches_header(), None);
+}
+
+#[test]
+fn debugger_steps_back() {
+    let mut debugger = debugger();
+    assert!(!debugger.back());
+
+    debugger.run_to_end();
+    assert!(debugger.back() && debugger.back());
+    assert_eq!(debugger.position(), 1);
+    assert_eq!(debugger.receipts().len(), 1);
+    assert_eq!(*debugger.state(), 10);
+    assert_eq!(debugger.next_extrinsic(), Some(&200));
+
+    // Stepping forward again ends up in the same place.
+    debugger.run_to_end();
+    assert_eq!(*debugger.state(), 30);
+}
+
+#[test]
+fn debugger_checks_the_state_root_of_the_block() {
+    // Blocks can only be authored by the client, which is an exercise, so we go through JSON.
+    let block = |state_root| -> Block<Pow, Counter> {
+        let body = vec![10u64, 200, 20];
+        let header = Header::new(H256::zero(), 1, state_root, merkle_root(&body), 0u64);
+        serde_json::from_value(serde_json::json!({ "header": header, "body": body })).unwrap()
+    };
+
+    let mut debugger = BlockDebugger::for_block(0, &block(hash(&30u64)));
+    assert_eq!(debugger.matches_header(), None);
+    debugger.run_to_end();
+    assert_eq!(debugger.matches_header(), Some(true));
+
+    let mut debugger = BlockDebugger::for_block(0, &block(H256::zero()));
+    debugger.run_to_end();
+    assert_eq!(debugger.matches_header(), Some(false));
+}
+
+#[test]
+fn debugger_lists_changed_accounts() {
+    let before = RuntimeState::genesis(&[(User::Alice, 100), (User::Bob, 5)]);
+    let after = RuntimeState::genesis(&[(User::Alice, 90), (User::Bob, 5), (User::Charlie, 10)]);
+    let info = |balance| AccountInfo { balance, nonce: 0 };
+
+    assert_eq!(
+        account_changes(&before, &after),
+        vec![
+            (User::Alice, info(100), info(90)),
+            (User::Charlie, AccountInfo::default(), info(10)),
+        ]
+    );
+}
@@ -0,0 +1,569 @@
+//! Watching a single node author blocks on its own only tells us so much. To see forks, sync,
+//! and consensus in action, we need several nodes talking to each other. Starting several
+//! processes by hand gets old quickly, so this module starts a whole development network, or
+//! devnet, inside a single process.
+//!
+//! Every node in the devnet gets its own keys and its own config, and runs its own authoring
+//! task. Instead of sockets, the nodes are connected to each other by in-memory channels. Every
+//! block a node authors is announced to all other nodes, which import it right away.
+//!
+//! ```ignore
+//! let devnet = Devnet::launch(3, Duration::from_millis(100), |info| make_client(info));
+//! assert!(devnet.wait_until(Duration::from_secs(5), |node| node.best_block_height() >= 10).await);
+//! ```
+//!
+//! A perfect network never shows how consensus copes with a bad one. `Devnet::launch_with`
+//! takes the conditions of the network between the nodes: latency, jitter and drop rate per
+//! link, and partitions that split the nodes into groups for a while. Whether a message is
+//! dropped, and how long it is delayed, only depends on a seed and on the message itself, so
+//! the same seed shows the same behavior on every run.
+//!
+//! ```ignore
+//! let conditions = NetworkConditions::new(42)
+//!     .with_default(LinkConditions::new(Duration::from_millis(50)).with_drop_rate(0.1))
+//!     .with_partition(Partition::new(vec![vec![0], vec![1, 2]]).heal_at(Duration::from_secs(10)));
+//! let devnet = Devnet::launch_with(3, Duration::from_millis(100), conditions, make_client);
+//! ```
+//!
+//! The timing of the tasks themselves is still up to tokio, though. For runs that replay exactly,
+//! see the `simulator` module, which drives the same kind of nodes on a virtual clock.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use super::authoring::{authoring_task, AuthorBlocks};
+use super::config::NodeConfig;
+use super::keystore::Keystore;
+use super::network::sync::LocalChain;
+use super::network::PeerId;
+use crate::c1_state_machine::User;
+#[cfg(test)]
+use crate::hashing::H256;
+use crate::test_rng::TestRng;
+
+/// The accounts that devnet nodes hold keys for. Node `i` holds the key for account
+/// `i % DEV_ACCOUNTS.len()`.
+pub const DEV_ACCOUNTS: [User; 3] = [User::Alice, User::Bob, User::Charlie];
+
+/// Everything a devnet node is told about itself when it is created.
+pub struct NodeInfo {
+    /// The node's index in the devnet, which doubles as its peer id.
+    pub index: usize,
+    /// The account that this node holds the key for.
+    pub account: User,
+    /// The node's own keys.
+    pub keystore: Keystore,
+    /// The node's own config. No two nodes share ports or a data directory.
+    pub config: NodeConfig,
+}
+
+impl NodeInfo {
+    pub(super) fn new(index: usize, block_time: Duration) -> Self {
+        let account = DEV_ACCOUNTS[index % DEV_ACCOUNTS.len()];
+        let mut keystore = Keystore::new();
+        keystore.insert(account);
+
+        let offset = index as u16;
+        let mut config = NodeConfig::default();
+        config.data_dir = config.data_dir.join(format!("devnet-{index}"));
+        config.network.port += offset;
+        config.rpc.addr.set_port(config.rpc.addr.port() + offset);
+        config
+            .rpc
+            .metrics_addr
+            .set_port(config.rpc.metrics_addr.port() + offset);
+        config.consensus.block_time_ms = block_time.as_millis() as u64;
+
+        NodeInfo {
+            index,
+            account,
+            keystore,
+            config,
+        }
+    }
+}
+
+/// The conditions on the link from one node to another.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinkConditions {
+    /// How long every message takes to arrive.
+    pub latency: Duration,
+    /// Up to how much longer, or shorter, a message may take than `latency`.
+    pub jitter: Duration,
+    /// The share of messages that never arrive, between 0 and 1.
+    pub drop_rate: f64,
+}
+
+impl LinkConditions {
+    /// A link that delivers every message after the given latency.
+    pub fn new(latency: Duration) -> Self {
+        LinkConditions {
+            latency,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_drop_rate(mut self, drop_rate: f64) -> Self {
+        self.drop_rate = drop_rate;
+        self
+    }
+}
+
+/// A split of the devnet into groups of nodes that can not reach each other. Nodes that are in
+/// none of the groups form one more group together.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Partition {
+    pub groups: Vec<Vec<usize>>,
+    /// When the partition starts, counted from the launch of the devnet.
+    pub start: Duration,
+    /// When the partition heals, or None if it never does.
+    pub heal: Option<Duration>,
+}
+
+impl Partition {
+    /// A partition into the given groups of node indices that starts right away and never heals.
+    pub fn new(groups: Vec<Vec<usize>>) -> Self {
+        Partition {
+            groups,
+            start: Duration::ZERO,
+            heal: None,
+        }
+    }
+
+    pub fn starting_at(mut self, start: Duration) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub fn heal_at(mut self, heal: Duration) -> Self {
+        self.heal = Some(heal);
+        self
+    }
+
+    /// Whether the partition keeps a message from `from` to `to` that is sent `elapsed` after
+    /// the launch of the devnet.
+    pub fn separates(&self, from: usize, to: usize, elapsed: Duration) -> bool {
+        if elapsed < self.start || self.heal.is_some_and(|heal| elapsed >= heal) {
+            return false;
+        }
+        let group = |node| self.groups.iter().position(|group| group.contains(&node));
+        group(from) != group(to)
+    }
+}
+
+/// The conditions of the whole network between the devnet's nodes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetworkConditions {
+    seed: u64,
+    default: LinkConditions,
+    links: BTreeMap<(usize, usize), LinkConditions>,
+    partitions: Vec<Partition>,
+}
+
+impl NetworkConditions {
+    /// A perfect network, whose random decisions are derived from the given seed once it is
+    /// made less perfect.
+    pub fn new(seed: u64) -> Self {
+        NetworkConditions {
+            seed,
+            ..Self::default()
+        }
+    }
+
+    /// Use the given conditions on every link that has none of its own.
+    pub fn with_default(mut self, link: LinkConditions) -> Self {
+        self.default = link;
+        self
+    }
+
+    /// Use the given conditions on the link from `from` to `to`. The link back is not affected.
+    pub fn with_link(mut self, from: usize, to: usize, link: LinkConditions) -> Self {
+        self.links.insert((from, to), link);
+        self
+    }
+
+    pub fn with_partition(mut self, partition: Partition) -> Self {
+        self.partitions.push(partition);
+        self
+    }
+
+    /// The seed that all random decisions about the network are derived from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The conditions on the link from `from` to `to`.
+    pub fn link(&self, from: usize, to: usize) -> LinkConditions {
+        self.links.get(&(from, to)).copied().unwrap_or(self.default)
+    }
+
+    /// Decide the fate of the `sequence`th message on the link from `from` to `to`, sent
+    /// `elapsed` after the launch of the devnet. Returns how long the message takes to arrive,
+    /// or None if it never does.
+    pub fn delivery(
+        &self,
+        from: usize,
+        to: usize,
+        sequence: u64,
+        elapsed: Duration,
+    ) -> Option<Duration> {
+        if self
+            .partitions
+            .iter()
+            .any(|partition| partition.separates(from, to, elapsed))
+        {
+            return None;
+        }
+
+        let link = self.link(from, to);
+        let mut random = TestRng::seeded(
+            self.seed ^ (from as u64).rotate_left(48) ^ (to as u64).rotate_left(32) ^ sequence,
+        );
+        if random.unit() < link.drop_rate {
+            return None;
+        }
+        // Spread the delay evenly over latency - jitter ..= latency + jitter.
+        let spread = link.jitter.as_nanos() as f64 * (2.0 * random.unit() - 1.0);
+        let delay = link.latency.as_nanos() as f64 + spread;
+        Some(Duration::from_nanos(delay.max(0.0) as u64))
+    }
+}
+
+/// A single running node in the devnet.
+pub struct DevnetNode<N> {
+    pub id: PeerId,
+    pub info: NodeInfo,
+    pub node: Arc<Mutex<N>>,
+}
+
+/// A running devnet.
+pub struct Devnet<N> {
+    nodes: Vec<DevnetNode<N>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl<N> Devnet<N>
+where
+    N: AuthorBlocks + LocalChain<N::Block> + Send + 'static,
+    N::Block: Clone + Send + 'static,
+{
+    /// Start a devnet of `count` fully connected nodes, each of which tries to author a block
+    /// once every `block_time`. The nodes themselves are created by `make_node`.
+    ///
+    /// This must be called from within a tokio runtime, because the nodes are driven by tasks
+    /// spawned on that runtime.
+    pub fn launch(
+        count: usize,
+        block_time: Duration,
+        make_node: impl FnMut(&NodeInfo) -> N,
+    ) -> Self {
+        Self::launch_with(count, block_time, NetworkConditions::default(), make_node)
+    }
+
+    /// Start a devnet like `launch` does, but connect the nodes by a network with the given
+    /// conditions.
+    pub fn launch_with(
+        count: usize,
+        block_time: Duration,
+        conditions: NetworkConditions,
+        mut make_node: impl FnMut(&NodeInfo) -> N,
+    ) -> Self {
+        let network = Arc::new(Network {
+            conditions,
+            launched: Instant::now(),
+        });
+        let mut nodes = Vec::with_capacity(count);
+        let mut inboxes = Vec::with_capacity(count);
+        let mut senders = Vec::with_capacity(count);
+        for index in 0..count {
+            let info = NodeInfo::new(index, block_time);
+            let node = Arc::new(Mutex::new(make_node(&info)));
+            let (sender, inbox) = mpsc::unbounded_channel();
+            nodes.push(DevnetNode {
+                id: PeerId(index as u64),
+                info,
+                node,
+            });
+            inboxes.push(inbox);
+            senders.push(sender);
+        }
+
+        let mut tasks = Vec::with_capacity(3 * count);
+        for (node, inbox) in nodes.iter().zip(inboxes) {
+            let peers: Vec<_> = senders
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != node.info.index)
+                .map(|(index, sender)| (index, sender.clone()))
+                .collect();
+
+            let (announce, announcements) = mpsc::unbounded_channel();
+            tasks.push(tokio::spawn(authoring_task(
+                node.node.clone(),
+                block_time,
+                announce,
+            )));
+            tasks.push(tokio::spawn(relay(
+                network.clone(),
+                node.node.clone(),
+                node.info.index,
+                announcements,
+                peers,
+            )));
+            tasks.push(tokio::spawn(import(node.node.clone(), inbox)));
+        }
+
+        Devnet { nodes, tasks }
+    }
+
+    /// All nodes in the devnet.
+    pub fn nodes(&self) -> &[DevnetNode<N>] {
+        &self.nodes
+    }
+
+    /// The node with the given index.
+    pub fn node(&self, index: usize) -> &Arc<Mutex<N>> {
+        &self.nodes[index].node
+    }
+
+    /// Wait until the condition holds for every node, checking every few milliseconds.
+    /// Returns whether the condition was met before the timeout.
+    pub async fn wait_until(&self, timeout: Duration, condition: impl Fn(&N) -> bool) -> bool {
+        let check = async {
+            loop {
+                let done = self
+                    .nodes
+                    .iter()
+                    .all(|n| condition(&n.node.lock().expect("node mutex poisoned")));
+                if done {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+        tokio::time::timeout(timeout, check).await.is_ok()
+    }
+
+    /// Stop every node. The nodes themselves are handed back, so that their final state can
+    /// be inspected.
+    pub async fn shutdown(self) -> Vec<DevnetNode<N>> {
+        for task in self.tasks {
+            // None of the tasks hold a node's lock across an await, so aborting them can not
+            // leave a node halfway through an import.
+            task.abort();
+            let _ = task.await;
+        }
+        self.nodes
+    }
+}
+
+/// The network that connects the devnet's nodes.
+struct Network {
+    conditions: NetworkConditions,
+    launched: Instant,
+}
+
+/// Pass every block that the given node authors on to all of its peers, as far as the network
+/// lets it through.
+async fn relay<N, B>(
+    network: Arc<Network>,
+    node: Arc<Mutex<N>>,
+    from: usize,
+    mut announcements: UnboundedReceiver<B>,
+    peers: Vec<(usize, UnboundedSender<(PeerId, B)>)>,
+) where
+    N: AuthorBlocks<Block = B>,
+    B: Clone + Send + 'static,
+{
+    let id = PeerId(from as u64);
+    let mut sequence = 0;
+    while let Some(block) = announcements.recv().await {
+        let elapsed = network.launched.elapsed();
+        // Ask the node what to tell each peer up front, so the lock is released before we send.
+        let told: Vec<_> = {
+            let mut node = node.lock().expect("node mutex poisoned");
+            peers
+                .iter()
+                .map(|(to, _)| node.announcement(&block, *to))
+                .collect()
+        };
+        for ((to, peer), block) in peers.iter().zip(told) {
+            let Some(block) = block else {
+                continue;
+            };
+            let Some(delay) = network.conditions.delivery(from, *to, sequence, elapsed) else {
+                continue;
+            };
+            // A peer that has shut down simply misses out.
+            if delay.is_zero() {
+                let _ = peer.send((id, block));
+            } else {
+                let peer = peer.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let _ = peer.send((id, block));
+                });
+            }
+        }
+        sequence += 1;
+    }
+}
+
+/// Import every block that peers announce to the given node.
+async fn import<N: LocalChain<B>, B>(
+    node: Arc<Mutex<N>>,
+    mut inbox: UnboundedReceiver<(PeerId, B)>,
+) {
+    while let Some((_from, block)) = inbox.recv().await {
+        node.lock().expect("node mutex poisoned").import(block);
+    }
+}
+
+/// A node whose blocks are just numbers, and which only authors when it holds Alice's key.
+#[cfg(test)]
+struct CountingNode {
+    authors: bool,
+    known: Vec<u64>,
+}
+
+#[cfg(test)]
+impl AuthorBlocks for CountingNode {
+    type Block = u64;
+
+    fn try_author_block(&mut self) -> Option<u64> {
+        if !self.authors {
+            return None;
+        }
+        let block = self.known.len() as u64;
+        self.known.push(block);
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+impl LocalChain<u64> for CountingNode {
+    fn is_known(&self, hash: H256) -> bool {
+        self.known.iter().any(|&block| H256::from(block) == hash)
+    }
+
+    fn import(&mut self, block: u64) -> bool {
+        if self.known.contains(&block) {
+            return false;
+        }
+        self.known.push(block);
+        true
+    }
+}
+
+#[tokio::test]
+async fn devnet_blocks_reach_every_node() {
+    let devnet = Devnet::launch(3, Duration::from_millis(1), |info| CountingNode {
+        authors: info.account == User::Alice,
+        known: Vec::new(),
+    });
+
+    assert!(
+        devnet
+            .wait_until(Duration::from_secs(5), |node| node.known.len() >= 5)
+            .await
+    );
+
+    let nodes = devnet.shutdown().await;
+    let first: Vec<u64> = nodes[1].node.lock().unwrap().known[..5].to_vec();
+    assert_eq!(first, vec![0, 1, 2, 3, 4]);
+}
+
+#[tokio::test]
+async fn devnet_partition_heals() {
+    let conditions = NetworkConditions::new(0)
+        .with_partition(Partition::new(vec![vec![0]]).heal_at(Duration::from_millis(100)));
+    let devnet = Devnet::launch_with(3, Duration::from_millis(1), conditions, |info| {
+        CountingNode {
+            authors: info.account == User::Alice,
+            known: Vec::new(),
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(devnet.node(1).lock().unwrap().known.is_empty());
+    assert!(
+        devnet
+            .wait_until(Duration::from_secs(5), |node| node.known.len() >= 5)
+            .await
+    );
+
+    // Blocks that were authored during the partition never made it across.
+    let nodes = devnet.shutdown().await;
+    assert!(!nodes[2].node.lock().unwrap().known.contains(&0));
+}
+
+#[test]
+fn devnet_network_conditions_are_deterministic() {
+    let link = LinkConditions::new(Duration::from_millis(100))
+        .with_jitter(Duration::from_millis(20))
+        .with_drop_rate(0.25);
+    let conditions = NetworkConditions::new(7).with_default(link);
+    let fates = |conditions: &NetworkConditions| -> Vec<Option<Duration>> {
+        (0..1000)
+            .map(|sequence| conditions.delivery(0, 1, sequence, Duration::ZERO))
+            .collect()
+    };
+
+    let first = fates(&conditions);
+    assert_eq!(first, fates(&conditions.clone()));
+    assert_ne!(first, fates(&NetworkConditions::new(8).with_default(link)));
+
+    let dropped = first.iter().filter(|fate| fate.is_none()).count();
+    assert!((200..300).contains(&dropped), "dropped {dropped}");
+    for delay in first.into_iter().flatten() {
+        assert!(delay >= Duration::from_millis(80) && delay <= Duration::from_millis(120));
+    }
+
+    // Links without conditions of their own stay perfect, and other links are unaffected.
+    let conditions = NetworkConditions::new(7).with_link(0, 1, link);
+    assert_eq!(
+        conditions.delivery(1, 0, 0, Duration::ZERO),
+        Some(Duration::ZERO)
+    );
+}
+
+#[test]
+fn devnet_partitions_start_and_heal_on_time() {
+    let partition = Partition::new(vec![vec![0, 1], vec![2]])
+        .starting_at(Duration::from_secs(1))
+        .heal_at(Duration::from_secs(2));
+    let second = |s: f64| Duration::from_secs_f64(s);
+
+    assert!(!partition.separates(0, 2, second(0.5)));
+    assert!(partition.separates(0, 2, second(1.5)));
+    assert!(partition.separates(2, 1, second(1.5)));
+    assert!(!partition.separates(0, 1, second(1.5)));
+    assert!(!partition.separates(0, 2, second(2.0)));
+    // Nodes in no group end up together.
+    assert!(!partition.separates(3, 4, second(1.5)));
+    assert!(partition.separates(3, 2, second(1.5)));
+}
+
+#[test]
+fn devnet_nodes_get_their_own_keys_and_config() {
+    let first = NodeInfo::new(0, Duration::from_secs(1));
+    let second = NodeInfo::new(1, Duration::from_secs(1));
+
+    assert!(first.keystore.contains(User::Alice));
+    assert!(!first.keystore.contains(User::Bob));
+    assert!(second.keystore.contains(User::Bob));
+    assert_ne!(first.config.rpc.addr, second.config.rpc.addr);
+    assert_ne!(first.config.network.port, second.config.network.port);
+    assert_ne!(first.config.data_dir, second.config.data_dir);
+    assert_eq!(first.config.consensus.block_time_ms, 1000);
+}
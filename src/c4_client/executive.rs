@@ -0,0 +1,72 @@
+//! The client executes a block by handing its extrinsics to the state machine, one at a time.
+//! That is all a state machine can do, but a real runtime does more with a block as a whole. It
+//! limits how much a block may hold, pays the block's author, or switches to a new version of
+//! itself from some height on. None of that fits into `next_state`, which only ever sees a
+//! single extrinsic.
+//!
+//! An `Executive` does these things for the client, much like the executive pallet does in
+//! Substrate. The client asks it whether a body is acceptable at all, whether one more extrinsic
+//! still fits into a block it is authoring, and for the state after a whole block. It asks for
+//! the state both when it imports a block and when it authors one, so the two always agree. The
+//! executive also seals the blocks the client authors, in case it has to record something in
+//! their digest.
+//!
+//! A client without an executive accepts any body, and executes it one extrinsic at a time.
+
+use alloc::boxed::Box;
+
+use super::{Consensus, FullClient, Header, ImportError, StateMachine};
+
+/// Checks and executes whole blocks on behalf of the client.
+pub trait Executive<C: Consensus, SM: StateMachine> {
+    /// Check the body of a block before it is executed.
+    fn check_body(&self, _body: &[SM::Transition]) -> Result<(), ImportError> {
+        Ok(())
+    }
+
+    /// Whether `next` still fits into a block that already holds `body`. An author stops
+    /// filling its block at the first extrinsic that does not fit.
+    fn fits(&self, _body: &[SM::Transition], _next: &SM::Transition) -> bool {
+        true
+    }
+
+    /// The state after the block at the given height with the given body. The digest is the
+    /// block's seal, or None while the block is being authored and is not sealed yet, in which
+    /// case the engine is the one that is about to seal it.
+    fn execute_block(
+        &self,
+        _engine: &C,
+        pre_state: SM::State,
+        _height: u64,
+        _digest: Option<&C::Digest>,
+        body: &[SM::Transition],
+    ) -> Result<SM::State, ImportError> {
+        Ok(body
+            .iter()
+            .fold(pre_state, |state, t| SM::next_state(&state, t)))
+    }
+
+    /// Seal the header of a block this client authors on top of the given state. Most
+    /// executives leave this to the engine, but some record something in the digest first.
+    fn seal(
+        &self,
+        engine: &C,
+        parent_digest: &C::Digest,
+        _pre_state: &SM::State,
+        partial_header: Header<()>,
+    ) -> Option<Header<C::Digest>> {
+        engine.seal(parent_digest, partial_header)
+    }
+}
+
+/// No executive at all: any body is accepted, and executed one extrinsic at a time. Executives
+/// that wrap another one use this when they need no limits.
+impl<C: Consensus, SM: StateMachine> Executive<C, SM> for () {}
+
+impl<C: Consensus, SM: StateMachine, FC, P> FullClient<C, SM, FC, P> {
+    /// Let the given executive check and execute every block this client imports or authors.
+    pub fn with_executive(mut self, executive: impl Executive<C, SM> + Send + 'static) -> Self {
+        self.executive = Some(Box::new(executive));
+        self
+    }
+}
@@ -0,0 +1,245 @@
+//! The faucet of chapter 1, as a service of a development node. Devnet users who just created
+//! an account can ask the node for some money over RPC, with the `faucet_requestFunds` method,
+//! or from their wallet with `Wallet::request_funds`.
+//!
+//! Once the runtime is upgraded, only the root account may mint, so the node's faucet does not
+//! mint at all. Instead, it hands out money from an account that it holds the key for, which is
+//! usually endowed at genesis. Like in chapter 1, every account may only be funded once per
+//! `cooldown` blocks, counted by the node's best block. Requests that come too early are turned
+//! down with `RpcError::RateLimited`.
+//!
+//! `FaucetNode` wraps any node api, and serves the faucet on top of it. Every other call is
+//! passed through.
+
+use std::collections::HashMap;
+
+use super::forks::{ForkInfo, ForkTree};
+use super::network::reputation::PeerScore;
+use super::proof::StorageProof;
+use super::rpc::{Health, NodeApi, RpcError};
+use super::runtime::{AccountInfo, SignedExtrinsic};
+use super::wallet::{Wallet, WalletError};
+use crate::c1_state_machine::User;
+use crate::hashing::H256;
+
+/// The JSON-RPC error code for a faucet that can't pay out, because it ran dry.
+pub const FAUCET_DRY: i64 = 1010;
+
+/// A faucet that pays out of the account of the given wallet.
+pub struct Faucet {
+    wallet: Wallet,
+    /// How much every request is given.
+    drip: u64,
+    /// How many blocks an account has to wait after being funded before it can be funded again.
+    cooldown: u64,
+    /// The best block height at which every account was funded last.
+    last_funded: HashMap<User, u64>,
+}
+
+impl Faucet {
+    pub fn new(wallet: Wallet, drip: u64, cooldown: u64) -> Self {
+        Faucet {
+            wallet,
+            drip,
+            cooldown,
+            last_funded: HashMap::new(),
+        }
+    }
+
+    /// How much every request is given.
+    pub fn drip(&self) -> u64 {
+        self.drip
+    }
+
+    /// Transfer the drip to the given account through the given node, unless the account was
+    /// funded less than `cooldown` blocks ago. Returns the submitted transfer.
+    pub fn request(
+        &mut self,
+        node: &mut impl NodeApi,
+        who: User,
+    ) -> Result<SignedExtrinsic, RpcError> {
+        let height = node.health()?.best_height;
+        if self
+            .last_funded
+            .get(&who)
+            .is_some_and(|&last| height.saturating_sub(last) < self.cooldown)
+        {
+            return Err(RpcError::RateLimited);
+        }
+
+        self.wallet.sync(node).map_err(dry)?;
+        let extrinsic = self.wallet.transfer(node, who, self.drip).map_err(dry)?;
+        self.last_funded.insert(who, height);
+        Ok(extrinsic)
+    }
+}
+
+/// Report a failure of the faucet's wallet. Errors of the node are passed on as they are, and
+/// everything else means that the faucet can't pay out.
+fn dry(e: WalletError) -> RpcError {
+    match e {
+        WalletError::Rpc(e) => e,
+        e => RpcError::Remote {
+            code: FAUCET_DRY,
+            message: format!("the faucet can't pay out: {e:?}"),
+        },
+    }
+}
+
+/// A node api with a faucet on top.
+pub struct FaucetNode<A> {
+    pub node: A,
+    pub faucet: Faucet,
+}
+
+impl<A: NodeApi> NodeApi for FaucetNode<A> {
+    fn best_block_hash(&mut self) -> Result<H256, RpcError> {
+        self.node.best_block_hash()
+    }
+
+    fn account_info(&mut self, who: User) -> Result<AccountInfo, RpcError> {
+        self.node.account_info(who)
+    }
+
+    fn storage_proof(&mut self, who: User, at: H256) -> Result<StorageProof, RpcError> {
+        self.node.storage_proof(who, at)
+    }
+
+    fn submit_extrinsic(&mut self, extrinsic: SignedExtrinsic) -> Result<(), RpcError> {
+        self.node.submit_extrinsic(extrinsic)
+    }
+
+    fn health(&mut self) -> Result<Health, RpcError> {
+        self.node.health()
+    }
+
+    fn peer_scores(&mut self) -> Result<Vec<PeerScore>, RpcError> {
+        self.node.peer_scores()
+    }
+
+    fn forks(&mut self) -> Result<Vec<ForkInfo>, RpcError> {
+        self.node.forks()
+    }
+
+    fn block_tree(&mut self) -> Result<ForkTree, RpcError> {
+        self.node.block_tree()
+    }
+
+    fn insert_key(&mut self, who: User) -> Result<(), RpcError> {
+        self.node.insert_key(who)
+    }
+
+    fn purge_pool(&mut self) -> Result<usize, RpcError> {
+        self.node.purge_pool()
+    }
+
+    fn request_funds(&mut self, who: User) -> Result<u64, RpcError> {
+        self.faucet.request(&mut self.node, who)?;
+        Ok(self.faucet.drip())
+    }
+}
+
+#[cfg(test)]
+use super::keystore::{dev_address, Keystore};
+#[cfg(test)]
+use super::rpc::{dispatch, MockNode};
+#[cfg(test)]
+use serde_json::json;
+
+/// A node whose best block is at the given height, with a faucet that pays 10 out of Alice's
+/// 25, once every 5 blocks.
+#[cfg(test)]
+fn faucet_node(height: u64) -> FaucetNode<MockNode> {
+    let mut node = MockNode::default();
+    node.health.best_height = height;
+    node.accounts.insert(
+        User::Alice,
+        AccountInfo {
+            balance: 25,
+            nonce: 0,
+        },
+    );
+    let wallet = Wallet::new(User::Alice, Keystore::dev()).unwrap();
+    FaucetNode {
+        node,
+        faucet: Faucet::new(wallet, 10, 5),
+    }
+}
+
+#[test]
+fn faucet_funds_once_per_cooldown() {
+    let mut node = faucet_node(1);
+    assert_eq!(node.request_funds(User::Bob), Ok(10));
+    assert_eq!(node.request_funds(User::Bob), Err(RpcError::RateLimited));
+    assert_eq!(node.request_funds(User::Charlie), Ok(10));
+
+    node.node.health.best_height = 6;
+    assert_eq!(node.request_funds(User::Bob), Ok(10));
+
+    let nonces: Vec<u64> = node.node.submitted.iter().map(|x| x.nonce).collect();
+    assert_eq!(nonces, vec![0, 1, 2]);
+}
+
+#[test]
+fn faucet_running_dry_is_not_rate_limited() {
+    let mut node = faucet_node(1);
+    node.node.accounts.clear();
+    assert!(matches!(
+        node.request_funds(User::Bob),
+        Err(RpcError::Remote {
+            code: FAUCET_DRY,
+            ..
+        })
+    ));
+
+    // Once the faucet is refilled, the same account can ask again right away.
+    node.node.accounts.insert(
+        User::Alice,
+        AccountInfo {
+            balance: 10,
+            nonce: 0,
+        },
+    );
+    assert_eq!(node.request_funds(User::Bob), Ok(10));
+}
+
+#[test]
+fn faucet_served_over_rpc_and_wallet() {
+    let mut node = faucet_node(1);
+    let bob = dev_address(User::Bob).to_string();
+    assert_eq!(
+        dispatch(&mut node, "faucet_requestFunds", json!([bob])),
+        Ok(json!(10))
+    );
+
+    let mut wallet = Wallet::new(User::Charlie, Keystore::dev()).unwrap();
+    assert_eq!(wallet.request_funds(&mut node), Ok(10));
+    assert!(matches!(
+        wallet.request_funds(&mut MockNode::default()),
+        Err(WalletError::Rpc(RpcError::MethodNotFound(_)))
+    ));
+}
+
+#[test]
+fn faucet_pays_out_of_a_client() {
+    use super::runtime::{Runtime, RuntimeState};
+    use super::{FullClient, LongestChain, SimplePool};
+
+    let genesis = RuntimeState::genesis(&[(User::Alice, 25)]);
+    let client = FullClient::<(), Runtime, LongestChain, SimplePool<Runtime>>::new(genesis);
+    let wallet = Wallet::new(User::Alice, Keystore::dev()).unwrap();
+    let mut node = FaucetNode {
+        node: client,
+        faucet: Faucet::new(wallet, 10, 5),
+    };
+
+    assert_eq!(node.request_funds(User::Bob), Ok(10));
+    node.node.author_and_import_automatic_block().unwrap();
+    assert_eq!(
+        node.account_info(User::Bob),
+        Ok(AccountInfo {
+            balance: 10,
+            nonce: 0
+        })
+    );
+}
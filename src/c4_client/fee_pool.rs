@@ -0,0 +1,314 @@
+//! Block space is limited, so when more transactions arrive than fit in the next block, somebody
+//! has to decide which ones go first. Most chains leave that decision to a market: users attach
+//! a fee to their transactions, and authors include the best paying ones first.
+//!
+//! Paying a lot is not the same as paying well, though. A transaction that pays twice the fee
+//! but uses three times the block space is a worse deal for the author. So the pool compares
+//! transactions by their fee per unit of _weight_, the block space they use up.
+//!
+//! The fee market also decides what happens when the pool is full, and when a user changes
+//! their mind:
+//! * A full pool evicts its worst paying transaction to make room for a better paying one.
+//! * A user can replace a stuck transaction with a new one that uses the same nonce, as long as
+//!   it pays a noticeably higher fee. This is known as replace-by-fee.
+//!
+//! One thing the market must not do is reorder the transactions of a single sender. Their
+//! nonces must be executed in order, so only each sender's lowest nonce competes for the next
+//! spot in a block.
+
+use std::collections::BTreeMap;
+
+use super::{StateMachine, TransactionPool};
+
+/// How many transactions a pool holds, unless told otherwise.
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+/// By how many percent a replacement must raise the fee of the transaction it replaces.
+/// Without a minimum bump, users could spam the pool with replacements that pay one unit more.
+pub const MIN_REPLACEMENT_BUMP_PERCENT: u64 = 10;
+
+/// What the pool needs to know about a transaction to price it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeInfo<A> {
+    /// The account that signed, and pays for, the transaction.
+    pub sender: A,
+    /// The sender's nonce for this transaction.
+    pub nonce: u64,
+    /// The fee the transaction pays.
+    pub fee: u64,
+    /// How much block space the transaction uses. A weight of zero counts as one.
+    pub weight: u64,
+}
+
+impl<A> FeeInfo<A> {
+    /// Whether this transaction pays more per unit of weight than the other one.
+    fn pays_better_than(&self, other: &FeeInfo<A>) -> bool {
+        // Compare fee / weight without dividing, so that no precision is lost.
+        u128::from(self.fee) * u128::from(other.weight.max(1))
+            > u128::from(other.fee) * u128::from(self.weight.max(1))
+    }
+}
+
+struct Entry<T, A> {
+    transaction: T,
+    info: FeeInfo<A>,
+    /// When the transaction arrived. Among equally paying transactions, older ones go first.
+    arrival: u64,
+}
+
+/// A transaction pool that implements a fee market, see the module docs.
+pub struct FeePool<T, A, F: Fn(&T) -> FeeInfo<A>> {
+    /// A means of pricing transactions.
+    fee_info: F,
+    /// The most transactions the pool holds at once.
+    capacity: usize,
+    /// The waiting transactions of every sender, by nonce.
+    senders: BTreeMap<A, BTreeMap<u64, Entry<T, A>>>,
+    size: usize,
+    arrivals: u64,
+}
+
+impl<T, A: Ord + Clone, F: Fn(&T) -> FeeInfo<A>> FeePool<T, A, F> {
+    /// Create an empty pool that prices transactions with `fee_info` and holds at most
+    /// `capacity` of them.
+    pub fn new(fee_info: F, capacity: usize) -> Self {
+        FeePool {
+            fee_info,
+            capacity,
+            senders: BTreeMap::new(),
+            size: 0,
+            arrivals: 0,
+        }
+    }
+
+    /// One waiting transaction of every sender: the one with the lowest nonce if `first` is
+    /// set, and the one with the highest nonce otherwise.
+    fn candidates(&self, first: bool) -> impl Iterator<Item = &Entry<T, A>> {
+        self.senders.values().filter_map(move |queue| {
+            if first {
+                queue.values().next()
+            } else {
+                queue.values().next_back()
+            }
+        })
+    }
+
+    /// The worst paying transaction that can be evicted without leaving a gap in its sender's
+    /// nonces, which is to say the worst paying among every sender's highest nonce.
+    fn eviction_candidate(&self) -> Option<FeeInfo<A>> {
+        self.candidates(false)
+            .min_by(|a, b| {
+                if b.info.pays_better_than(&a.info) {
+                    std::cmp::Ordering::Less
+                } else if a.info.pays_better_than(&b.info) {
+                    std::cmp::Ordering::Greater
+                } else {
+                    // Among equals, evict the newest.
+                    b.arrival.cmp(&a.arrival)
+                }
+            })
+            .map(|entry| entry.info.clone())
+    }
+
+    fn take(&mut self, sender: &A, nonce: u64) -> Option<Entry<T, A>> {
+        let queue = self.senders.get_mut(sender)?;
+        let entry = queue.remove(&nonce)?;
+        if queue.is_empty() {
+            self.senders.remove(sender);
+        }
+        self.size -= 1;
+        Some(entry)
+    }
+}
+
+impl<SM, A, F> TransactionPool<SM> for FeePool<SM::Transition, A, F>
+where
+    SM: StateMachine,
+    SM::Transition: PartialEq,
+    A: Ord + Clone,
+    F: Fn(&SM::Transition) -> FeeInfo<A>,
+{
+    fn try_insert(&mut self, t: SM::Transition) -> bool {
+        let info = (self.fee_info)(&t);
+
+        // Replace-by-fee.
+        if let Some(existing) = self
+            .senders
+            .get(&info.sender)
+            .and_then(|queue| queue.get(&info.nonce))
+        {
+            let required =
+                u128::from(existing.info.fee) * u128::from(100 + MIN_REPLACEMENT_BUMP_PERCENT);
+            if info.fee <= existing.info.fee || u128::from(info.fee) * 100 < required {
+                tracing::debug!(
+                    nonce = info.nonce,
+                    fee = info.fee,
+                    "rejected a replacement that does not pay enough more"
+                );
+                return false;
+            }
+            self.take(&info.sender, info.nonce);
+        } else if self.size >= self.capacity {
+            match self.eviction_candidate() {
+                // Evicting one of the sender's own transactions would leave a gap before the
+                // new one, so that is never worth it.
+                Some(worst) if worst.sender != info.sender && info.pays_better_than(&worst) => {
+                    tracing::debug!(fee = worst.fee, "evicted the worst paying transaction");
+                    self.take(&worst.sender, worst.nonce);
+                }
+                _ => {
+                    tracing::debug!(fee = info.fee, "rejected a transaction, the pool is full");
+                    return false;
+                }
+            }
+        }
+
+        self.arrivals += 1;
+        self.size += 1;
+        self.senders.entry(info.sender.clone()).or_default().insert(
+            info.nonce,
+            Entry {
+                transaction: t,
+                info,
+                arrival: self.arrivals,
+            },
+        );
+        true
+    }
+
+    fn remove(&mut self, t: SM::Transition) {
+        let info = (self.fee_info)(&t);
+        let matches = self
+            .senders
+            .get(&info.sender)
+            .and_then(|queue| queue.get(&info.nonce))
+            .is_some_and(|entry| entry.transaction == t);
+        if matches {
+            self.take(&info.sender, info.nonce);
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn contains(&self, t: SM::Transition) -> bool {
+        let info = (self.fee_info)(&t);
+        self.senders
+            .get(&info.sender)
+            .and_then(|queue| queue.get(&info.nonce))
+            .is_some_and(|entry| entry.transaction == t)
+    }
+
+    /// The best paying transaction among every sender's lowest nonce.
+    fn next_from_pool(&mut self) -> Option<SM::Transition> {
+        let best = self
+            .candidates(true)
+            .reduce(|best, entry| {
+                let better = entry.info.pays_better_than(&best.info)
+                    || (!best.info.pays_better_than(&entry.info) && entry.arrival < best.arrival);
+                if better {
+                    entry
+                } else {
+                    best
+                }
+            })
+            .map(|entry| entry.info.clone())?;
+        self.take(&best.sender, best.nonce)
+            .map(|entry| entry.transaction)
+    }
+}
+
+/// Payments between accounts named by a single letter. The state machine itself is never run,
+/// the pool only needs its transaction type.
+#[cfg(test)]
+struct Payments;
+
+/// A payment is `(sender, nonce, fee, weight)`.
+#[cfg(test)]
+type Payment = (char, u64, u64, u64);
+
+#[cfg(test)]
+impl StateMachine for Payments {
+    type State = ();
+    type Transition = Payment;
+
+    fn next_state(_: &(), _: &Payment) {}
+}
+
+#[cfg(test)]
+fn test_pool(capacity: usize) -> FeePool<Payment, char, impl Fn(&Payment) -> FeeInfo<char>> {
+    FeePool::new(
+        |&(sender, nonce, fee, weight): &Payment| FeeInfo {
+            sender,
+            nonce,
+            fee,
+            weight,
+        },
+        capacity,
+    )
+}
+
+#[cfg(test)]
+fn drain(pool: &mut impl TransactionPool<Payments>) -> Vec<Payment> {
+    std::iter::from_fn(|| pool.next_from_pool()).collect()
+}
+
+#[test]
+fn fee_pool_orders_by_fee_per_weight() {
+    let mut pool = test_pool(DEFAULT_CAPACITY);
+    // b pays more in total, but less per unit of weight.
+    let a = ('a', 0, 10, 1);
+    let b = ('b', 0, 20, 4);
+    let c = ('c', 0, 9, 1);
+    for t in [b, c, a] {
+        assert!(TransactionPool::<Payments>::try_insert(&mut pool, t));
+    }
+
+    assert_eq!(drain(&mut pool), vec![a, c, b]);
+}
+
+#[test]
+fn fee_pool_keeps_each_senders_nonces_in_order() {
+    let mut pool = test_pool(DEFAULT_CAPACITY);
+    let cheap_first = ('a', 0, 1, 1);
+    let generous_second = ('a', 1, 100, 1);
+    let other = ('b', 0, 50, 1);
+    for t in [generous_second, cheap_first, other] {
+        assert!(TransactionPool::<Payments>::try_insert(&mut pool, t));
+    }
+
+    assert_eq!(drain(&mut pool), vec![other, cheap_first, generous_second]);
+}
+
+#[test]
+fn fee_pool_evicts_worst_paying_when_full() {
+    let mut pool = test_pool(2);
+    let insert = |pool: &mut FeePool<_, _, _>, t| TransactionPool::<Payments>::try_insert(pool, t);
+    assert!(insert(&mut pool, ('a', 0, 5, 1)));
+    assert!(insert(&mut pool, ('b', 0, 3, 1)));
+
+    // Not good enough to push anything out.
+    assert!(!insert(&mut pool, ('c', 0, 2, 1)));
+    assert!(insert(&mut pool, ('c', 0, 4, 1)));
+
+    assert_eq!(TransactionPool::<Payments>::size(&pool), 2);
+    assert!(!TransactionPool::<Payments>::contains(
+        &pool,
+        ('b', 0, 3, 1)
+    ));
+}
+
+#[test]
+fn fee_pool_replace_by_fee() {
+    let mut pool = test_pool(DEFAULT_CAPACITY);
+    let insert = |pool: &mut FeePool<_, _, _>, t| TransactionPool::<Payments>::try_insert(pool, t);
+    assert!(insert(&mut pool, ('a', 0, 100, 1)));
+
+    // A bump below the minimum is refused.
+    assert!(!insert(&mut pool, ('a', 0, 105, 1)));
+    assert!(insert(&mut pool, ('a', 0, 110, 1)));
+
+    assert_eq!(TransactionPool::<Payments>::size(&pool), 1);
+    assert_eq!(drain(&mut pool), vec![('a', 0, 110, 1)]);
+}
@@ -0,0 +1,427 @@
+//! Forks are a normal part of a blockchain's life, but they are hard to see. The client only
+//! reports its best block, and the other branches of the block tree stay invisible unless you go
+//! looking for them in the logs.
+//!
+//! This module summarizes every branch of the block tree, one per leaf, so that the node can
+//! expose them over RPC. Each branch is described from the point where it leaves the best chain,
+//! which is what an operator usually wants to know: how far did this fork get before it lost?
+//!
+//! When the summary is not enough, `ForkTree` holds the whole block tree, and renders it in the
+//! DOT language of Graphviz. A run with a lot of reorgs is much easier to follow as a picture:
+//!
+//! ```text
+//! node chain graph | dot -Tsvg > forks.svg
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use super::network::ChainHeader;
+use super::{Consensus, ForkChoice, FullClient, ImportBlock, StateMachine};
+use crate::hashing::H256;
+
+/// A summary of one branch of the block tree.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForkInfo {
+    /// The leaf at the tip of the branch.
+    pub head: H256,
+    /// The height of the head.
+    pub height: u64,
+    /// The last block that the branch has in common with the best chain. For the best branch
+    /// itself, that is genesis.
+    pub fork_point: H256,
+    /// The number of blocks on the branch after the fork point.
+    pub length: u64,
+    /// The work accumulated by every block from genesis up to the head.
+    pub work: u128,
+    /// Whether this branch ends in the best block.
+    pub is_best: bool,
+    /// Whether this branch contains the latest finalized block. Branches that do not can never
+    /// become the best branch.
+    pub is_finalized: bool,
+}
+
+/// Summarize the branch that ends in every one of the given leaves, looking headers up with the
+/// given function and weighing each block with `work`. The best branch comes first, and the
+/// others follow from the longest to the shortest.
+///
+/// Returns None if any of the blocks, or any of their ancestors, is unknown.
+pub fn fork_tree<H: ChainHeader>(
+    leaves: &[H256],
+    best: H256,
+    finalized: H256,
+    header: impl Fn(H256) -> Option<H>,
+    work: impl Fn(&H) -> u128,
+) -> Option<Vec<ForkInfo>> {
+    // The best chain, indexed by height.
+    let mut best_chain = Vec::new();
+    let mut current = header(best)?;
+    loop {
+        best_chain.push(current.hash());
+        if current.height() == 0 {
+            break;
+        }
+        current = header(current.parent_hash())?;
+    }
+    best_chain.reverse();
+    // The finalized block must be known too, or we could not tell where it is.
+    header(finalized)?;
+
+    let mut forks = Vec::with_capacity(leaves.len());
+    for &leaf in leaves {
+        let head = header(leaf)?;
+        let mut fork = ForkInfo {
+            head: leaf,
+            height: head.height(),
+            fork_point: best_chain[0],
+            length: head.height(),
+            work: 0,
+            is_best: leaf == best,
+            is_finalized: false,
+        };
+
+        let mut left_best_chain = leaf == best;
+        let mut current = head;
+        loop {
+            fork.work += work(&current);
+            let height = current.height() as usize;
+            if !left_best_chain && best_chain.get(height) == Some(&current.hash()) {
+                left_best_chain = true;
+                fork.fork_point = current.hash();
+                // Heights only decrease towards genesis in a chain that was verified, but this
+                // function takes its headers on trust.
+                fork.length = fork.height.saturating_sub(current.height());
+            }
+            if current.hash() == finalized {
+                fork.is_finalized = true;
+            }
+            if current.height() == 0 {
+                break;
+            }
+            current = header(current.parent_hash())?;
+        }
+        forks.push(fork);
+    }
+
+    forks.sort_by(|a, b| {
+        b.is_best
+            .cmp(&a.is_best)
+            .then(b.length.cmp(&a.length))
+            .then(a.head.cmp(&b.head))
+    });
+    Some(forks)
+}
+
+/// A single block in a `ForkTree`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeBlock {
+    pub hash: H256,
+    pub parent: H256,
+    pub height: u64,
+}
+
+/// The whole block tree: every leaf, and every block that one of them descends from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForkTree {
+    /// Every block in the tree, ordered by height, and blocks at the same height by hash.
+    pub blocks: Vec<TreeBlock>,
+    pub best: H256,
+    pub finalized: H256,
+}
+
+impl ForkTree {
+    /// Collect the tree that the given leaves span, looking headers up with the given function.
+    ///
+    /// Returns None if any of the blocks, or any of their ancestors, is unknown.
+    pub fn collect<H: ChainHeader>(
+        leaves: &[H256],
+        best: H256,
+        finalized: H256,
+        header: impl Fn(H256) -> Option<H>,
+    ) -> Option<Self> {
+        let mut blocks = BTreeMap::new();
+        for &leaf in leaves.iter().chain([&best, &finalized]) {
+            let mut current = header(leaf)?;
+            // Stop as soon as we reach a block that another leaf already led us to.
+            while !blocks.contains_key(&(current.height(), current.hash())) {
+                let block = TreeBlock {
+                    hash: current.hash(),
+                    parent: current.parent_hash(),
+                    height: current.height(),
+                };
+                blocks.insert((block.height, block.hash), block);
+                if current.height() == 0 {
+                    break;
+                }
+                current = header(current.parent_hash())?;
+            }
+        }
+        Some(ForkTree {
+            blocks: blocks.into_values().collect(),
+            best,
+            finalized,
+        })
+    }
+
+    /// Render the tree as a Graphviz graph, growing from genesis on the left. The best chain is
+    /// drawn in bold, and the best and finalized blocks are filled in.
+    pub fn to_dot(&self) -> String {
+        let parents: BTreeMap<H256, H256> = self
+            .blocks
+            .iter()
+            .filter(|block| block.height > 0)
+            .map(|block| (block.hash, block.parent))
+            .collect();
+        let mut best_chain = vec![self.best];
+        while let Some(&parent) = best_chain.last().and_then(|hash| parents.get(hash)) {
+            best_chain.push(parent);
+        }
+
+        let mut dot = String::from("digraph forks {\n    rankdir=LR;\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for block in &self.blocks {
+            let mut label = format!("#{}\\n{}", block.height, &block.hash.to_string()[..10]);
+            let mut style = Vec::new();
+            if block.hash == self.best {
+                label.push_str("\\nbest");
+            }
+            if block.hash == self.finalized {
+                label.push_str("\\nfinalized");
+                style.push("style=filled, fillcolor=palegreen");
+            } else if block.hash == self.best {
+                style.push("style=filled, fillcolor=lightblue");
+            }
+            if best_chain.contains(&block.hash) {
+                style.push("penwidth=2");
+            }
+            let style: String = style.iter().map(|s| format!(", {s}")).collect();
+            let _ = writeln!(dot, "    \"{}\" [label=\"{label}\"{style}];", block.hash);
+        }
+        for block in self.blocks.iter().filter(|block| block.height > 0) {
+            let style = if best_chain.contains(&block.hash) {
+                " [penwidth=2]"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" -> \"{}\"{style};",
+                block.parent, block.hash
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl<C, SM, FC, P> FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    FC: ForkChoice<C>,
+    Self: ImportBlock<C, SM>,
+{
+    /// Collect the client's whole block tree.
+    pub fn block_tree(&self) -> Option<ForkTree> {
+        ForkTree::collect(
+            &self.all_leaves(),
+            self.best_block(),
+            self.finalized_block(),
+            |hash| self.get_block(hash).map(|block| block.header().clone()),
+        )
+    }
+
+    /// Summarize every branch of the client's block tree, weighing each block by the work its
+    /// consensus engine says went into it.
+    pub fn forks(&self) -> Option<Vec<ForkInfo>> {
+        fork_tree(
+            &self.all_leaves(),
+            self.best_block(),
+            self.finalized_block(),
+            |hash| self.get_block(hash).map(|block| block.header().clone()),
+            |header| self.consensus_engine.work(header),
+        )
+    }
+}
+
+#[cfg(test)]
+struct TestHeader {
+    hash: H256,
+    parent: H256,
+    height: u64,
+}
+
+#[cfg(test)]
+impl ChainHeader for TestHeader {
+    fn hash(&self) -> H256 {
+        self.hash
+    }
+
+    fn parent_hash(&self) -> H256 {
+        self.parent
+    }
+
+    fn height(&self) -> u64 {
+        self.height
+    }
+}
+
+/// Genesis is 1 and the best chain is 1 -> 2 -> 3 -> 4. One fork leaves it after 2 and goes
+/// 32 -> 42, another leaves it right after genesis and goes 21.
+#[cfg(test)]
+fn test_tree(hash: H256) -> Option<TestHeader> {
+    let (parent, height) = match hash.low_u64() {
+        1 => (0, 0),
+        2 | 21 => (1, 1),
+        3 => (2, 2),
+        4 => (3, 3),
+        32 => (2, 2),
+        42 => (32, 3),
+        _ => return None,
+    };
+    Some(TestHeader {
+        hash,
+        parent: H256::from(parent),
+        height,
+    })
+}
+
+#[test]
+fn forks_describe_every_branch() {
+    let forks = fork_tree(
+        &[21, 42, 4].map(H256::from),
+        H256::from(4),
+        H256::from(2),
+        test_tree,
+        |h| u128::from(h.hash.low_u64()),
+    )
+    .unwrap();
+
+    assert_eq!(
+        forks,
+        vec![
+            ForkInfo {
+                head: 4.into(),
+                height: 3,
+                fork_point: 1.into(),
+                length: 3,
+                work: 1 + 2 + 3 + 4,
+                is_best: true,
+                is_finalized: true,
+            },
+            ForkInfo {
+                head: 42.into(),
+                height: 3,
+                fork_point: 2.into(),
+                length: 2,
+                work: 1 + 2 + 32 + 42,
+                is_best: false,
+                is_finalized: true,
+            },
+            ForkInfo {
+                head: 21.into(),
+                height: 1,
+                fork_point: 1.into(),
+                length: 1,
+                work: 1 + 21,
+                is_best: false,
+                is_finalized: false,
+            },
+        ]
+    );
+}
+
+#[test]
+fn forks_need_every_ancestor() {
+    assert_eq!(
+        fork_tree(
+            &[4, 99].map(H256::from),
+            H256::from(4),
+            H256::from(1),
+            test_tree,
+            |_| 1
+        ),
+        None
+    );
+    assert_eq!(
+        fork_tree(
+            &[H256::from(4)],
+            H256::from(4),
+            H256::from(99),
+            test_tree,
+            |_| 1
+        ),
+        None
+    );
+}
+
+#[test]
+fn forks_collect_the_whole_tree() {
+    let tree = ForkTree::collect(
+        &[21, 42, 4].map(H256::from),
+        H256::from(4),
+        H256::from(2),
+        test_tree,
+    )
+    .unwrap();
+
+    let hashes: Vec<u64> = tree.blocks.iter().map(|b| b.hash.low_u64()).collect();
+    assert_eq!(hashes, vec![1, 2, 21, 3, 32, 4, 42]);
+    assert_eq!(
+        ForkTree::collect(&[H256::from(99)], H256::from(4), H256::from(2), test_tree),
+        None
+    );
+}
+
+#[test]
+fn forks_render_to_dot() {
+    let tree = ForkTree::collect(
+        &[21, 42, 4].map(H256::from),
+        H256::from(4),
+        H256::from(2),
+        test_tree,
+    )
+    .unwrap();
+    let dot = tree.to_dot();
+    let line = |hash: u64| {
+        let hash = H256::from(hash).to_string();
+        dot.lines()
+            .find(|line| line.trim_start().starts_with(&format!("\"{hash}\" [")))
+            .unwrap()
+            .to_string()
+    };
+
+    assert!(dot.starts_with("digraph forks {"));
+    assert!(line(4).contains("best") && line(4).contains("lightblue"));
+    assert!(line(2).contains("finalized") && line(2).contains("penwidth=2"));
+    assert!(!line(42).contains("penwidth"));
+    // One edge per block but genesis, and the best chain's edges are bold.
+    assert_eq!(dot.matches(" -> ").count(), 6);
+    let edge = format!("\"{}\" -> \"{}\"", H256::from(3), H256::from(4));
+    assert!(dot.contains(&format!("{edge} [penwidth=2];")));
+}
+
+#[cfg(test)]
+type PowClient = FullClient<
+    crate::c3_consensus::Pow,
+    super::p1_data_structure::Adder,
+    super::LongestChain,
+    super::SimplePool<super::p1_data_structure::Adder>,
+>;
+
+#[test]
+fn forks_weigh_blocks_by_their_proof_of_work() {
+    // The default engine accepts about one hash in a hundred, so each block is worth a hundred.
+    let mut client = PowClient::new(0);
+    let genesis = client.best_block();
+    client.author_and_import_manual_block(vec![1], genesis);
+    let tip = client.best_block();
+    client.author_and_import_manual_block(vec![2], tip);
+
+    let forks = client.forks().unwrap();
+    assert_eq!(forks.len(), 1);
+    assert_eq!(forks[0].length, 2);
+    assert_eq!(forks[0].work, 300);
+}
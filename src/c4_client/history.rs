@@ -0,0 +1,219 @@
+//! A pruned node throws old states away, but it keeps every block. Since the state after a
+//! block is fully determined by the state before it and the block's extrinsics, no state is ever
+//! really lost. As long as some older state is still around, a _snapshot_, the node can travel
+//! back in time: start from the snapshot and execute every block after it again, up to the
+//! block in question.
+//!
+//! Replaying is slow compared to looking a state up, so the client only does it when it has to.
+//! A pruned node keeps the state of the most recently finalized block, see the `storage` module,
+//! so every block after it can be replayed from there. The states of blocks before it are only
+//! recoverable while some older snapshot is still stored. Once they are gone, the node answers
+//! with `StatePruned`, like it would without replaying.
+//!
+//! This is what lets a pruned node still answer `state_getStorageProof` at old blocks, and start
+//! the block debugger at any block it knows of.
+
+use super::debugger::BlockDebugger;
+use super::network::ChainHeader;
+use super::storage::StateError;
+use super::{Consensus, FullClient, ImportBlock, StateMachine};
+use crate::hashing::H256;
+
+/// Reconstruct the state after the block with the given hash.
+///
+/// `stored` returns the states that are still stored, and `block` the header and body of every
+/// known block. We walk back from the given block until we find a stored state, then replay the
+/// bodies of every block after it with `execute`, in the same way they were imported.
+///
+/// Fails with `UnknownBlock` if the block itself is unknown, and with `StatePruned` if no
+/// ancestor of it has a stored state.
+pub fn replay_state<SM, H>(
+    hash: H256,
+    block: impl Fn(H256) -> Option<(H, Vec<SM::Transition>)>,
+    stored: impl Fn(H256) -> Option<SM::State>,
+    execute: impl Fn(SM::State, &H, &[SM::Transition]) -> SM::State,
+) -> Result<SM::State, StateError>
+where
+    SM: StateMachine,
+    H: ChainHeader,
+{
+    // The blocks to replay, newest first.
+    let mut blocks = Vec::new();
+    let mut height = None;
+    let mut current = hash;
+    let mut state = loop {
+        if let Some(state) = stored(current) {
+            break state;
+        }
+        let pruned = |height: Option<u64>| match height {
+            Some(height) => StateError::StatePruned { hash, height },
+            None => StateError::UnknownBlock(hash),
+        };
+        let Some((header, body)) = block(current) else {
+            return Err(pruned(height));
+        };
+        height.get_or_insert(header.height());
+        if header.height() == 0 {
+            // Even the genesis state is gone.
+            return Err(pruned(height));
+        }
+        current = header.parent_hash();
+        blocks.push((header, body));
+    };
+
+    for (header, body) in blocks.iter().rev() {
+        state = execute(state, header, body);
+    }
+    Ok(state)
+}
+
+impl<C, SM, FC, P> FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::Transition: Clone,
+    Self: ImportBlock<C, SM>,
+{
+    /// The state after the block with the given hash, at any height. Stored states are used
+    /// as they are, and pruned ones are replayed from the nearest stored ancestor, by the
+    /// client's executive if it has one.
+    pub fn state_at(&self, block_hash: H256) -> Result<SM::State, StateError> {
+        let executive = self.executive.as_deref().unwrap_or(&());
+        replay_state::<SM, _>(
+            block_hash,
+            |hash| {
+                self.get_block(hash)
+                    .map(|block| (block.header().clone(), block.body().to_vec()))
+            },
+            |hash| self.get_state(hash),
+            |state, header, body| {
+                executive
+                    .execute_block(
+                        &self.consensus_engine,
+                        state,
+                        header.height(),
+                        Some(header.consensus_digest()),
+                        body,
+                    )
+                    .expect("blocks that were imported once execute the same way again")
+            },
+        )
+    }
+
+    /// A debugger that steps through the block with the given hash, starting from the state of
+    /// its parent.
+    pub fn debug_block(&self, block_hash: H256) -> Result<BlockDebugger<SM>, StateError>
+    where
+        SM::State: Clone + std::hash::Hash + PartialEq,
+        SM::Transition: std::hash::Hash,
+    {
+        let block = self
+            .get_block(block_hash)
+            .ok_or(StateError::UnknownBlock(block_hash))?;
+        let parent_state = self.state_at(block.header().parent())?;
+        Ok(BlockDebugger::for_block(parent_state, &block))
+    }
+}
+
+#[cfg(test)]
+struct TestHeader {
+    hash: H256,
+    parent: H256,
+    height: u64,
+}
+
+#[cfg(test)]
+impl ChainHeader for TestHeader {
+    fn hash(&self) -> H256 {
+        self.hash
+    }
+
+    fn parent_hash(&self) -> H256 {
+        self.parent
+    }
+
+    fn height(&self) -> u64 {
+        self.height
+    }
+}
+
+/// Adds up every number it is given.
+#[cfg(test)]
+struct Sum;
+
+#[cfg(test)]
+impl StateMachine for Sum {
+    type State = u64;
+    type Transition = u64;
+
+    fn next_state(state: &u64, t: &u64) -> u64 {
+        state + t
+    }
+}
+
+/// Replays a block of the test chain.
+#[cfg(test)]
+fn sum(state: u64, _: &TestHeader, body: &[u64]) -> u64 {
+    body.iter()
+        .fold(state, |state, t| Sum::next_state(&state, t))
+}
+
+/// A chain of five blocks. Block `n` has hash `n + 1`, sits at height `n`, and adds `n` to
+/// the sum.
+#[cfg(test)]
+fn test_block(hash: H256) -> Option<(TestHeader, Vec<u64>)> {
+    let height = hash.low_u64().checked_sub(1).filter(|&height| height < 5)?;
+    let header = TestHeader {
+        hash,
+        parent: H256::from(height),
+        height,
+    };
+    Some((header, vec![height]))
+}
+
+#[test]
+fn history_uses_stored_states_as_they_are() {
+    let stored = |hash: H256| (hash == H256::from(3)).then_some(100);
+
+    assert_eq!(
+        replay_state::<Sum, _>(H256::from(3), test_block, stored, sum),
+        Ok(100)
+    );
+}
+
+#[test]
+fn history_replays_from_nearest_snapshot() {
+    // Only genesis and block 2 still have their states. Block 2's is deliberately off, to show
+    // that replaying starts from the nearest snapshot rather than from genesis.
+    let stored = |hash: H256| match hash.low_u64() {
+        1 => Some(0),
+        3 => Some(1000),
+        _ => None,
+    };
+
+    assert_eq!(
+        replay_state::<Sum, _>(H256::from(2), test_block, stored, sum),
+        Ok(1)
+    );
+    assert_eq!(
+        replay_state::<Sum, _>(H256::from(5), test_block, stored, sum),
+        Ok(1000 + 3 + 4)
+    );
+}
+
+#[test]
+fn history_reports_unknown_and_unrecoverable_blocks() {
+    let nothing = |_: H256| None;
+
+    assert_eq!(
+        replay_state::<Sum, _>(H256::from(9), test_block, nothing, sum),
+        Err(StateError::UnknownBlock(H256::from(9)))
+    );
+    assert_eq!(
+        replay_state::<Sum, _>(H256::from(4), test_block, nothing, sum),
+        Err(StateError::StatePruned {
+            hash: H256::from(4),
+            height: 3
+        })
+    );
+}
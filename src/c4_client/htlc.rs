@@ -0,0 +1,503 @@
+//! Two chains that know nothing about each other can still trade coins with each other, without
+//! anybody going first and without a trusted middleman. The trick is a hash time locked contract,
+//! or HTLC: money that is locked for a receiver until either
+//! * the receiver claims it by revealing a secret preimage of the lock's hash, or
+//! * the lock's timeout passes, after which the sender can take it back.
+//!
+//! Say Alice has coins on chain A and wants Bob's coins on chain B. An atomic swap goes like this:
+//! 1. Alice picks a secret, and locks her coins on chain A for Bob under the hash of the secret,
+//!    with a long timeout.
+//! 2. Bob sees the lock on chain A, and locks his coins on chain B for Alice under the same hash,
+//!    with a shorter timeout.
+//! 3. Alice claims Bob's coins on chain B. To do so, she has to put her secret on chain B, for
+//!    everybody to see.
+//! 4. Bob reads the secret off chain B, and uses it to claim Alice's coins on chain A.
+//!
+//! If Bob never locks his coins, Alice waits for her timeout and takes her coins back. Once Alice
+//! has revealed her secret, Bob has until her timeout to claim, which is why his timeout must be
+//! the shorter one: Alice must not be able to claim on chain B so late that Bob can't claim on
+//! chain A anymore.
+//!
+//! Timeouts are block heights, so the extrinsics here are executed a whole block at a time, like
+//! those of the `upgrade` module. The `HtlcNode` runs a chain of such blocks in a devnet.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::authoring::AuthorBlocks;
+use super::keystore::Signature;
+use super::network::sync::LocalChain;
+use super::runtime::{Runtime, RuntimeState, SignedExtrinsic};
+use crate::c1_state_machine::{StateMachine, User};
+use crate::hashing::{hash_of, H256};
+
+/// The hash that a lock is locked under, for the given secret preimage.
+pub fn hashlock(preimage: &H256) -> H256 {
+    hash_of("htlc preimage", preimage)
+}
+
+/// What the sender of a lock agrees to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HtlcTerms {
+    /// Who can claim the money.
+    pub receiver: User,
+    pub amount: u64,
+    /// The receiver must reveal a preimage of this hash to claim the money.
+    pub hashlock: H256,
+    /// The height from which the receiver can no longer claim, and the sender can refund.
+    pub timeout: u64,
+}
+
+/// Money that is locked on chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Htlc {
+    pub sender: User,
+    pub terms: HtlcTerms,
+}
+
+/// An extrinsic that can be applied to a chain with HTLCs.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HtlcExtrinsic {
+    /// An ordinary currency extrinsic.
+    Apply(SignedExtrinsic),
+    /// Lock some of the sender's money under the given terms. The nonce is the sender's account
+    /// nonce, just like in a signed extrinsic, and the lock's id is derived from it.
+    Lock {
+        sender: User,
+        nonce: u64,
+        terms: HtlcTerms,
+        signature: Signature,
+    },
+    /// Pay a lock out to its receiver by revealing the preimage. The money can only ever go to
+    /// the receiver, so anybody may submit this.
+    Claim { id: H256, preimage: H256 },
+    /// Pay a lock whose timeout has passed back to its sender. Anybody may submit this, too.
+    Refund { id: H256 },
+}
+
+/// Calculate the hash that the sender must sign to lock money under the given terms.
+pub fn lock_payload(sender: User, nonce: u64, terms: &HtlcTerms) -> H256 {
+    hash_of("htlc lock", &(sender, nonce, terms))
+}
+
+/// The id of the lock that the given sender makes with the given nonce.
+pub fn lock_id(sender: User, nonce: u64) -> H256 {
+    hash_of("htlc id", &(sender, nonce))
+}
+
+/// The runtime state, along with the money that is locked.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HtlcState {
+    pub runtime: RuntimeState,
+    /// The open locks, by their id.
+    pub locks: BTreeMap<H256, Htlc>,
+}
+
+impl HtlcState {
+    /// A genesis state with the given runtime state and no locks.
+    pub fn genesis(runtime: RuntimeState) -> Self {
+        HtlcState {
+            runtime,
+            locks: BTreeMap::new(),
+        }
+    }
+
+    /// Execute the block at the given height on top of this state. Invalid extrinsics leave the
+    /// state untouched, just like in the runtime. That includes locks with a bad signature or
+    /// nonce, that the sender can't pay for, or whose timeout has already passed, claims with
+    /// the wrong preimage or after the timeout, and refunds before it.
+    pub fn execute_block(&self, height: u64, extrinsics: &[HtlcExtrinsic]) -> HtlcState {
+        let mut state = self.clone();
+        for extrinsic in extrinsics {
+            state.execute(height, extrinsic);
+        }
+        state
+    }
+
+    fn execute(&mut self, height: u64, extrinsic: &HtlcExtrinsic) {
+        match extrinsic {
+            HtlcExtrinsic::Apply(t) => self.runtime = Runtime::next_state(&self.runtime, t),
+            HtlcExtrinsic::Lock {
+                sender,
+                nonce,
+                terms,
+                signature,
+            } => {
+                let mut account = self.runtime.account(*sender);
+                if !signature.verify(*sender, lock_payload(*sender, *nonce, terms))
+                    || *nonce != account.nonce
+                    || account.balance < terms.amount
+                    || terms.timeout <= height
+                {
+                    return;
+                }
+                account.balance -= terms.amount;
+                account.nonce += 1;
+                self.runtime.set_account(*sender, account);
+                let lock = Htlc {
+                    sender: *sender,
+                    terms: *terms,
+                };
+                self.locks.insert(lock_id(*sender, *nonce), lock);
+            }
+            HtlcExtrinsic::Claim { id, preimage } => {
+                let Some(lock) = self.locks.get(id).copied() else {
+                    return;
+                };
+                if hashlock(preimage) == lock.terms.hashlock && height < lock.terms.timeout {
+                    self.pay_out(*id, lock.terms.receiver);
+                }
+            }
+            HtlcExtrinsic::Refund { id } => {
+                let Some(lock) = self.locks.get(id).copied() else {
+                    return;
+                };
+                if height >= lock.terms.timeout {
+                    self.pay_out(*id, lock.sender);
+                }
+            }
+        }
+    }
+
+    /// Remove the given lock, and pay its money to the given user.
+    fn pay_out(&mut self, id: H256, to: User) {
+        let lock = self
+            .locks
+            .remove(&id)
+            .expect("only existing locks are paid out");
+        let mut account = self.runtime.account(to);
+        account.balance += lock.terms.amount;
+        self.runtime.set_account(to, account);
+    }
+}
+
+/// A block of a chain with HTLCs. There is no consensus to speak of: the node that authors
+/// blocks is simply trusted to do so.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HtlcBlock {
+    pub parent: H256,
+    pub height: u64,
+    pub extrinsics: Vec<HtlcExtrinsic>,
+}
+
+impl HtlcBlock {
+    pub fn hash(&self) -> H256 {
+        hash_of("htlc block", self)
+    }
+}
+
+/// A minimal node for a chain with HTLCs, which can be run in a devnet. It only follows a
+/// single chain, and only authors if it was told to.
+#[derive(Clone, Debug)]
+pub struct HtlcNode {
+    authors: bool,
+    /// The state after every block, starting with the genesis state.
+    states: Vec<HtlcState>,
+    blocks: Vec<HtlcBlock>,
+    pool: Vec<HtlcExtrinsic>,
+}
+
+impl HtlcNode {
+    /// Create a node that starts from the given genesis state.
+    pub fn new(genesis: HtlcState, authors: bool) -> Self {
+        HtlcNode {
+            authors,
+            states: vec![genesis],
+            blocks: Vec::new(),
+            pool: Vec::new(),
+        }
+    }
+
+    /// Queue an extrinsic for the next block this node authors.
+    pub fn submit(&mut self, extrinsic: HtlcExtrinsic) {
+        self.pool.push(extrinsic);
+    }
+
+    /// The height of the best block. The genesis block has height 0.
+    pub fn height(&self) -> u64 {
+        self.blocks.len() as u64
+    }
+
+    /// The state after the best block.
+    pub fn state(&self) -> &HtlcState {
+        self.states
+            .last()
+            .expect("there is always the genesis state")
+    }
+
+    /// Look through the chain for a claim that revealed a preimage of the given hash. This is
+    /// how the receiver of the first lock of a swap learns the secret.
+    pub fn find_preimage(&self, hash: H256) -> Option<H256> {
+        self.blocks
+            .iter()
+            .flat_map(|block| &block.extrinsics)
+            .find_map(|extrinsic| match extrinsic {
+                HtlcExtrinsic::Claim { preimage, .. } if hashlock(preimage) == hash => {
+                    Some(*preimage)
+                }
+                _ => None,
+            })
+    }
+
+    fn best_hash(&self) -> H256 {
+        self.blocks.last().map_or(H256::default(), HtlcBlock::hash)
+    }
+
+    fn push(&mut self, block: HtlcBlock) {
+        let state = self.state().execute_block(block.height, &block.extrinsics);
+        self.pool
+            .retain(|extrinsic| !block.extrinsics.contains(extrinsic));
+        self.states.push(state);
+        self.blocks.push(block);
+    }
+}
+
+impl AuthorBlocks for HtlcNode {
+    type Block = HtlcBlock;
+
+    fn try_author_block(&mut self) -> Option<HtlcBlock> {
+        if !self.authors {
+            return None;
+        }
+        let block = HtlcBlock {
+            parent: self.best_hash(),
+            height: self.height() + 1,
+            extrinsics: self.pool.clone(),
+        };
+        self.push(block.clone());
+        Some(block)
+    }
+}
+
+impl LocalChain<HtlcBlock> for HtlcNode {
+    fn is_known(&self, hash: H256) -> bool {
+        self.blocks.iter().any(|block| block.hash() == hash)
+    }
+
+    fn import(&mut self, block: HtlcBlock) -> bool {
+        if block.parent != self.best_hash() || block.height != self.height() + 1 {
+            return false;
+        }
+        self.push(block);
+        true
+    }
+}
+
+#[cfg(test)]
+use std::time::Duration;
+
+#[cfg(test)]
+use super::devnet::Devnet;
+#[cfg(test)]
+use super::keystore::Keystore;
+
+#[cfg(test)]
+fn lock(sender: User, nonce: u64, terms: HtlcTerms) -> HtlcExtrinsic {
+    let signature = Keystore::dev()
+        .sign(sender, lock_payload(sender, nonce, &terms))
+        .unwrap();
+    HtlcExtrinsic::Lock {
+        sender,
+        nonce,
+        terms,
+        signature,
+    }
+}
+
+#[cfg(test)]
+fn alice_locks(timeout: u64) -> HtlcExtrinsic {
+    let terms = HtlcTerms {
+        receiver: User::Bob,
+        amount: 60,
+        hashlock: hashlock(&H256::from(7)),
+        timeout,
+    };
+    lock(User::Alice, 0, terms)
+}
+
+/// Alice has locked 60 of her 100 for Bob, until height 10.
+#[cfg(test)]
+fn locked() -> HtlcState {
+    let genesis = HtlcState::genesis(RuntimeState::genesis(&[(User::Alice, 100)]));
+    genesis.execute_block(1, &[alice_locks(10)])
+}
+
+#[test]
+fn htlc_lock_holds_money() {
+    let state = locked();
+    assert_eq!(state.runtime.account(User::Alice).balance, 40);
+    assert_eq!(state.runtime.account(User::Alice).nonce, 1);
+    assert_eq!(state.locks[&lock_id(User::Alice, 0)].terms.amount, 60);
+
+    // The same lock can't be replayed, and locks must not time out right away.
+    assert_eq!(state.execute_block(2, &[alice_locks(10)]), state);
+    let genesis = HtlcState::genesis(RuntimeState::genesis(&[(User::Alice, 100)]));
+    assert_eq!(genesis.execute_block(10, &[alice_locks(10)]), genesis);
+}
+
+#[test]
+fn htlc_claim_needs_preimage_before_timeout() {
+    let id = lock_id(User::Alice, 0);
+    let claim = |preimage| HtlcExtrinsic::Claim {
+        id,
+        preimage: H256::from(preimage),
+    };
+    let state = locked();
+    assert_eq!(state.execute_block(2, &[claim(8)]), state);
+    assert_eq!(state.execute_block(10, &[claim(7)]), state);
+
+    let claimed = state.execute_block(9, &[claim(7)]);
+    assert!(claimed.locks.is_empty());
+    assert_eq!(claimed.runtime.account(User::Bob).balance, 60);
+}
+
+#[test]
+fn htlc_refund_only_after_timeout() {
+    let refund = HtlcExtrinsic::Refund {
+        id: lock_id(User::Alice, 0),
+    };
+    let state = locked();
+    assert_eq!(state.execute_block(9, std::slice::from_ref(&refund)), state);
+
+    let refunded = state.execute_block(10, &[refund]);
+    assert!(refunded.locks.is_empty());
+    assert_eq!(refunded.runtime.account(User::Alice).balance, 100);
+}
+
+/// Launch a devnet of two nodes for a chain with the given genesis. The node holding Alice's
+/// key authors.
+#[cfg(test)]
+fn launch(genesis: &[(User, u64)]) -> Devnet<HtlcNode> {
+    let genesis = HtlcState::genesis(RuntimeState::genesis(genesis));
+    Devnet::launch(2, Duration::from_millis(2), |info| {
+        HtlcNode::new(genesis.clone(), info.account == User::Alice)
+    })
+}
+
+#[cfg(test)]
+fn submit(devnet: &Devnet<HtlcNode>, extrinsic: HtlcExtrinsic) -> u64 {
+    let mut node = devnet.node(0).lock().unwrap();
+    node.submit(extrinsic);
+    node.height()
+}
+
+#[tokio::test]
+async fn htlc_atomic_swap_between_devnets() {
+    let timeout = Duration::from_secs(5);
+    let chain_a = launch(&[(User::Alice, 100)]);
+    let chain_b = launch(&[(User::Bob, 50)]);
+    let secret = H256::from(42);
+
+    // Alice locks her coins on chain A, with plenty of time.
+    let height = chain_a.node(0).lock().unwrap().height();
+    let alice_terms = HtlcTerms {
+        receiver: User::Bob,
+        amount: 100,
+        hashlock: hashlock(&secret),
+        timeout: height + 2_000,
+    };
+    submit(&chain_a, lock(User::Alice, 0, alice_terms));
+    assert!(
+        chain_a
+            .wait_until(timeout, |node| node.state().locks.len() == 1)
+            .await
+    );
+
+    // Bob sees it, and locks his coins on chain B under the same hash, for less time.
+    let height = chain_b.node(0).lock().unwrap().height();
+    let bob_terms = HtlcTerms {
+        receiver: User::Alice,
+        amount: 50,
+        hashlock: alice_terms.hashlock,
+        timeout: height + 1_000,
+    };
+    submit(&chain_b, lock(User::Bob, 0, bob_terms));
+    assert!(
+        chain_b
+            .wait_until(timeout, |node| node.state().locks.len() == 1)
+            .await
+    );
+
+    // Alice claims on chain B, revealing the secret.
+    let claim = HtlcExtrinsic::Claim {
+        id: lock_id(User::Bob, 0),
+        preimage: secret,
+    };
+    submit(&chain_b, claim);
+    assert!(
+        chain_b
+            .wait_until(timeout, |node| node
+                .state()
+                .runtime
+                .account(User::Alice)
+                .balance
+                == 50)
+            .await
+    );
+
+    // Bob reads the secret off chain B, from either node, and claims on chain A.
+    let preimage = chain_b
+        .node(1)
+        .lock()
+        .unwrap()
+        .find_preimage(alice_terms.hashlock)
+        .expect("Alice revealed the secret on chain B");
+    let claim = HtlcExtrinsic::Claim {
+        id: lock_id(User::Alice, 0),
+        preimage,
+    };
+    submit(&chain_a, claim);
+    assert!(
+        chain_a
+            .wait_until(timeout, |node| node
+                .state()
+                .runtime
+                .account(User::Bob)
+                .balance
+                == 100)
+            .await
+    );
+
+    for chain in [chain_a, chain_b] {
+        for node in chain.shutdown().await {
+            assert!(node.node.lock().unwrap().state().locks.is_empty());
+        }
+    }
+}
+
+#[tokio::test]
+async fn htlc_refund_when_counterparty_never_locks() {
+    let chain_a = launch(&[(User::Alice, 100)]);
+    let height = chain_a.node(0).lock().unwrap().height();
+    let terms = HtlcTerms {
+        receiver: User::Bob,
+        amount: 100,
+        hashlock: hashlock(&H256::from(42)),
+        timeout: height + 10,
+    };
+    submit(&chain_a, lock(User::Alice, 0, terms));
+
+    // Bob never locks anything, so Alice waits out her timeout and takes her coins back.
+    assert!(
+        chain_a
+            .wait_until(Duration::from_secs(5), |node| node.height()
+                >= terms.timeout)
+            .await
+    );
+    submit(
+        &chain_a,
+        HtlcExtrinsic::Refund {
+            id: lock_id(User::Alice, 0),
+        },
+    );
+    assert!(
+        chain_a
+            .wait_until(Duration::from_secs(5), |node| {
+                node.state().runtime.account(User::Alice).balance == 100
+                    && node.state().locks.is_empty()
+            })
+            .await
+    );
+    chain_a.shutdown().await;
+}
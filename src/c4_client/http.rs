@@ -0,0 +1,244 @@
+//! A deliberately tiny HTTP/1.1 implementation. It supports exactly what our node needs to talk
+//! to the outside world: one request per connection, with an optional body whose length is
+//! given by the `Content-Length` header. Anything fancier belongs in a real HTTP library.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread::{self, JoinHandle};
+
+/// An incoming HTTP request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Request {
+    pub method: String,
+    pub path: String,
+    /// The value of the `Authorization` header, if any.
+    pub authorization: Option<String>,
+    /// The address the request came from. Only known for requests that arrived over a socket.
+    pub peer: Option<IpAddr>,
+    pub body: Vec<u8>,
+}
+
+/// An outgoing HTTP response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn ok(content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Response {
+            status: 200,
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Response {
+            status: 404,
+            content_type: "text/plain",
+            body: b"not found".to_vec(),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Read the body that follows a set of headers, using the content length header.
+/// The authorization header is returned alongside, as it is the only other one we care about.
+fn read_headers_and_body(reader: &mut impl BufRead) -> io::Result<(Option<String>, Vec<u8>)> {
+    let mut content_length = 0;
+    let mut authorization = None;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid("bad content length"))?;
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok((authorization, body))
+}
+
+/// Read a single request from the given reader.
+pub(crate) fn read_request(reader: &mut impl BufRead) -> io::Result<Request> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().ok_or_else(|| invalid("missing method"))?;
+    let path = parts.next().ok_or_else(|| invalid("missing path"))?;
+
+    let (authorization, body) = read_headers_and_body(reader)?;
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        authorization,
+        peer: None,
+        body,
+    })
+}
+
+/// Write a single response to the given writer.
+pub(crate) fn write_response(writer: &mut impl Write, response: &Response) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    )?;
+    writer.write_all(&response.body)?;
+    writer.flush()
+}
+
+/// Start serving HTTP on the given address in a background thread. Every request is passed to
+/// the handler, and the handler's response is sent back to the caller.
+///
+/// Returns the address that was actually bound, which is useful when binding port 0.
+pub(crate) fn serve<H>(
+    addr: impl ToSocketAddrs,
+    handler: H,
+) -> io::Result<(SocketAddr, JoinHandle<()>)>
+where
+    H: Fn(Request) -> Response + Send + 'static,
+{
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    let handle = thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // A misbehaving client should never bring the whole server down, so we simply
+            // drop connections that we fail to talk to.
+            let _ = handle_connection(stream, &handler);
+        }
+    });
+
+    Ok((local_addr, handle))
+}
+
+fn handle_connection<H: Fn(Request) -> Response>(stream: TcpStream, handler: &H) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut reader) {
+        Ok(mut request) => {
+            request.peer = stream.peer_addr().ok().map(|addr| addr.ip());
+            handler(request)
+        }
+        Err(_) => Response {
+            status: 400,
+            content_type: "text/plain",
+            body: b"bad request".to_vec(),
+        },
+    };
+    write_response(&mut &stream, &response)
+}
+
+/// Send a single POST request, with an optional authorization header, and return the
+/// response status and body.
+pub(crate) fn post(
+    addr: impl ToSocketAddrs,
+    path: &str,
+    authorization: Option<&str>,
+    body: &[u8],
+) -> io::Result<(u16, Vec<u8>)> {
+    send(addr, "POST", path, authorization, body)
+}
+
+/// Send a single GET request and return the response status and body.
+#[cfg(test)]
+pub(crate) fn get(addr: impl ToSocketAddrs, path: &str) -> io::Result<(u16, Vec<u8>)> {
+    send(addr, "GET", path, None, &[])
+}
+
+fn send(
+    addr: impl ToSocketAddrs,
+    method: &str,
+    path: &str,
+    authorization: Option<&str>,
+    body: &[u8],
+) -> io::Result<(u16, Vec<u8>)> {
+    let mut stream = TcpStream::connect(addr)?;
+    write!(stream, "{method} {path} HTTP/1.1\r\nHost: localhost\r\n")?;
+    if let Some(authorization) = authorization {
+        write!(stream, "Authorization: {authorization}\r\n")?;
+    }
+    write!(
+        stream,
+        "Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid("bad status line"))?;
+
+    let (_, body) = read_headers_and_body(&mut reader)?;
+    Ok((status, body))
+}
+
+#[test]
+fn http_request_round_trip() {
+    let raw = b"POST /rpc HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer t\r\nContent-Length: 5\r\n\r\nhello";
+    let request = read_request(&mut &raw[..]).unwrap();
+
+    assert_eq!(
+        request,
+        Request {
+            method: "POST".into(),
+            path: "/rpc".into(),
+            authorization: Some("Bearer t".into()),
+            peer: None,
+            body: b"hello".to_vec(),
+        }
+    );
+}
+
+#[test]
+fn http_serve_and_post() {
+    let (addr, _) = serve("127.0.0.1:0", |request: Request| {
+        Response::ok(
+            "text/plain",
+            [request.path.as_bytes(), &request.body].concat(),
+        )
+    })
+    .unwrap();
+
+    let (status, body) = post(addr, "/echo", None, b" works").unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(body, b"/echo works");
+}
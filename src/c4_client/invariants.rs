@@ -0,0 +1,423 @@
+//! Some things must hold after every single block, no matter what else is going on: money is
+//! neither created nor destroyed except by minting and burning, nobody's balance goes below
+//! zero, and the state matches the root its header commits to. Tests usually check such things
+//! at the very end of a run, if at all, and then it is anybody's guess which of hundreds of
+//! blocks broke them.
+//!
+//! A `ChainInvariant` states one such rule about a single imported block. The `Checked` wrapper
+//! checks a list of invariants every time the node it wraps imports or authors a block, and
+//! notes down every violation together with the offending block. Like the nodes in the
+//! `byzantine` module, it is a node itself, so it works in the simulator and the devnet alike.
+//!
+//! ```ignore
+//! let mut simulator = Simulator::new(4, block_time, conditions, |info| {
+//!     Checked::new(make_client(info), runtime_invariants())
+//! });
+//! simulator.run_until(Duration::from_secs(60));
+//! for node in 0..4 {
+//!     simulator.node(node).assert_held();
+//! }
+//! ```
+
+use std::hash::Hash;
+
+use super::authoring::AuthorBlocks;
+use super::network::sync::LocalChain;
+use super::network::{ChainBlock, ChainHeader};
+use super::runtime::{Runtime, RuntimeState, SignedExtrinsic, ACCOUNTS};
+use super::{Block, FullClient, ImportBlock};
+use crate::c1_state_machine::{AccountingTransaction, StateMachine};
+use crate::c3_consensus::Consensus;
+use crate::hashing::{hash, H256};
+
+/// A rule that must hold for every imported block.
+pub trait ChainInvariant<B, S> {
+    /// A short name, to tell which invariant was violated.
+    fn name(&self) -> &'static str;
+
+    /// Check a block that was just imported, given the state of its parent and its own state.
+    /// Returns what is wrong, if anything.
+    fn check(&self, block: &B, parent_state: &S, state: &S) -> Result<(), String>;
+}
+
+/// A node that knows the state after every block it imported.
+pub trait BlockStates {
+    type State;
+
+    fn state_at(&self, hash: H256) -> Option<Self::State>;
+}
+
+impl<C, SM, FC, P> BlockStates for FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    Self: ImportBlock<C, SM>,
+{
+    type State = SM::State;
+
+    fn state_at(&self, hash: H256) -> Option<SM::State> {
+        self.get_state(hash)
+    }
+}
+
+/// The header of every block commits to the state after it.
+pub struct StateRootMatchesHeader;
+
+impl<C, SM> ChainInvariant<Block<C, SM>, SM::State> for StateRootMatchesHeader
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Hash,
+{
+    fn name(&self) -> &'static str {
+        "state root matches header"
+    }
+
+    fn check(&self, block: &Block<C, SM>, _: &SM::State, state: &SM::State) -> Result<(), String> {
+        let (expected, actual) = (block.header().state_root(), hash(state));
+        if expected != actual {
+            return Err(format!(
+                "the header promises {expected}, but the state is {actual}"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The most a block could have minted and burned, had every one of its extrinsics been
+/// executed. Extrinsics that the runtime rejects do neither.
+fn minted_and_burned(body: &[SignedExtrinsic]) -> (u128, u128) {
+    body.iter()
+        .fold((0, 0), |(minted, burned), extrinsic| match extrinsic.call {
+            AccountingTransaction::Mint { amount, .. } => (minted + u128::from(amount), burned),
+            AccountingTransaction::Burn { amount, .. } => (minted, burned + u128::from(amount)),
+            AccountingTransaction::Transfer { .. } => (minted, burned),
+        })
+}
+
+/// Only minting and burning change the total issuance. Since some of a block's extrinsics may
+/// have been rejected, the issuance may change by less than the block's mints and burns, but
+/// never by more.
+pub struct IssuanceConserved;
+
+impl<C: Consensus> ChainInvariant<Block<C, Runtime>, RuntimeState> for IssuanceConserved {
+    fn name(&self) -> &'static str {
+        "issuance conserved"
+    }
+
+    fn check(
+        &self,
+        block: &Block<C, Runtime>,
+        parent_state: &RuntimeState,
+        state: &RuntimeState,
+    ) -> Result<(), String> {
+        let (minted, burned) = minted_and_burned(block.body());
+        let (before, after) = (parent_state.total_issuance(), state.total_issuance());
+        if after > before + minted || after + burned < before {
+            return Err(format!(
+                "the issuance went from {before} to {after}, with at most {minted} minted and \
+                 {burned} burned"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Balances are unsigned, so a balance that went below zero wraps around to a huge number
+/// instead. No account can legitimately hold more than all accounts held before the block,
+/// plus whatever the block minted.
+pub struct NoNegativeBalances;
+
+impl<C: Consensus> ChainInvariant<Block<C, Runtime>, RuntimeState> for NoNegativeBalances {
+    fn name(&self) -> &'static str {
+        "no negative balances"
+    }
+
+    fn check(
+        &self,
+        block: &Block<C, Runtime>,
+        parent_state: &RuntimeState,
+        state: &RuntimeState,
+    ) -> Result<(), String> {
+        let (minted, _) = minted_and_burned(block.body());
+        let limit = parent_state.total_issuance() + minted;
+        for who in ACCOUNTS {
+            let balance = state.account(who).balance;
+            if u128::from(balance) > limit {
+                return Err(format!(
+                    "{who:?} holds {balance}, more than the {limit} there can be"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every invariant of the runtime, for any consensus engine.
+pub fn runtime_invariants<C: Consensus>(
+) -> Vec<Box<dyn ChainInvariant<Block<C, Runtime>, RuntimeState> + Send>> {
+    vec![
+        Box::new(StateRootMatchesHeader),
+        Box::new(IssuanceConserved),
+        Box::new(NoNegativeBalances),
+    ]
+}
+
+/// An invariant that did not hold for some block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub invariant: &'static str,
+    pub block: H256,
+    pub height: u64,
+    pub message: String,
+}
+
+/// A node that checks every block it imports or authors against a list of invariants.
+pub struct Checked<N: AuthorBlocks + BlockStates> {
+    pub node: N,
+    invariants: Vec<Box<dyn ChainInvariant<N::Block, N::State> + Send>>,
+    violations: Vec<Violation>,
+}
+
+impl<N> Checked<N>
+where
+    N: AuthorBlocks + BlockStates,
+    N::Block: ChainBlock,
+{
+    pub fn new(
+        node: N,
+        invariants: Vec<Box<dyn ChainInvariant<N::Block, N::State> + Send>>,
+    ) -> Self {
+        Checked {
+            node,
+            invariants,
+            violations: Vec::new(),
+        }
+    }
+
+    /// Every violation so far, in the order the blocks were imported.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Panic with the first violation, if there was one.
+    pub fn assert_held(&self) {
+        if let Some(violation) = self.violations.first() {
+            panic!(
+                "invariant `{}` does not hold for block #{} {}: {}",
+                violation.invariant, violation.height, violation.block, violation.message
+            );
+        }
+    }
+
+    /// Check every invariant for the given block, which was just imported.
+    fn check(&mut self, block: &N::Block) {
+        let header = block.header();
+        let violation = |invariant, message| Violation {
+            invariant,
+            block: header.hash(),
+            height: header.height(),
+            message,
+        };
+        let states = (
+            self.node.state_at(header.parent_hash()),
+            self.node.state_at(header.hash()),
+        );
+        let (Some(parent_state), Some(state)) = states else {
+            let message = "the node has no state for the block or its parent".into();
+            self.violations.push(violation("states are known", message));
+            return;
+        };
+        for invariant in &self.invariants {
+            if let Err(message) = invariant.check(block, &parent_state, &state) {
+                self.violations.push(violation(invariant.name(), message));
+            }
+        }
+    }
+}
+
+impl<N> AuthorBlocks for Checked<N>
+where
+    N: AuthorBlocks + BlockStates,
+    N::Block: ChainBlock,
+{
+    type Block = N::Block;
+
+    fn try_author_block(&mut self) -> Option<N::Block> {
+        let block = self.node.try_author_block()?;
+        self.check(&block);
+        Some(block)
+    }
+
+    fn announcement(&mut self, block: &N::Block, peer: usize) -> Option<N::Block>
+    where
+        N::Block: Clone,
+    {
+        self.node.announcement(block, peer)
+    }
+}
+
+impl<N> LocalChain<N::Block> for Checked<N>
+where
+    N: AuthorBlocks + BlockStates + LocalChain<N::Block>,
+    N::Block: ChainBlock + Clone,
+{
+    fn is_known(&self, hash: H256) -> bool {
+        self.node.is_known(hash)
+    }
+
+    fn import(&mut self, block: N::Block) -> bool {
+        let imported = self.node.import(block.clone());
+        if imported {
+            self.check(&block);
+        }
+        imported
+    }
+}
+
+#[cfg(test)]
+use std::panic::AssertUnwindSafe;
+#[cfg(test)]
+use std::time::Duration;
+
+#[cfg(test)]
+use super::devnet::{NetworkConditions, NodeInfo};
+#[cfg(test)]
+use super::simulator::{LongestChainNode, Simulator, TestBlock};
+#[cfg(test)]
+use crate::c1_state_machine::User;
+#[cfg(test)]
+use crate::c3_consensus::{Header, Pow};
+
+/// A test node whose state is the height of its best block, until it goes wrong at the given
+/// height and counts one too many from there on.
+#[cfg(test)]
+struct HeightNode {
+    chain: LongestChainNode,
+    broken_at: u64,
+}
+
+#[cfg(test)]
+impl AuthorBlocks for HeightNode {
+    type Block = TestBlock;
+
+    fn try_author_block(&mut self) -> Option<TestBlock> {
+        self.chain.try_author_block()
+    }
+}
+
+#[cfg(test)]
+impl LocalChain<TestBlock> for HeightNode {
+    fn is_known(&self, hash: H256) -> bool {
+        self.chain.is_known(hash)
+    }
+
+    fn import(&mut self, block: TestBlock) -> bool {
+        self.chain.import(block)
+    }
+}
+
+#[cfg(test)]
+impl BlockStates for HeightNode {
+    type State = u64;
+
+    fn state_at(&self, hash: H256) -> Option<u64> {
+        let height = self.chain.blocks.get(&hash)?.height;
+        Some(height + u64::from(height >= self.broken_at))
+    }
+}
+
+/// Every block adds exactly one to the state.
+#[cfg(test)]
+struct CountsBlocks;
+
+#[cfg(test)]
+impl ChainInvariant<TestBlock, u64> for CountsBlocks {
+    fn name(&self) -> &'static str {
+        "counts blocks"
+    }
+
+    fn check(&self, _: &TestBlock, parent_state: &u64, state: &u64) -> Result<(), String> {
+        match *state == parent_state + 1 {
+            true => Ok(()),
+            false => Err(format!("went from {parent_state} to {state}")),
+        }
+    }
+}
+
+#[cfg(test)]
+fn run_checked(broken_at: impl Fn(usize) -> u64) -> Simulator<Checked<HeightNode>> {
+    let mut simulator = Simulator::new(
+        3,
+        Duration::from_secs(1),
+        NetworkConditions::new(1),
+        |info: &NodeInfo| {
+            let node = HeightNode {
+                chain: LongestChainNode::new(info),
+                broken_at: broken_at(info.index),
+            };
+            Checked::new(node, vec![Box::new(CountsBlocks)])
+        },
+    );
+    simulator.run_until(Duration::from_secs(10));
+    simulator
+}
+
+#[test]
+fn invariants_hold_for_honest_nodes() {
+    let simulator = run_checked(|_| u64::MAX);
+    for node in 0..3 {
+        simulator.node(node).assert_held();
+    }
+}
+
+#[test]
+fn invariants_point_at_the_offending_block() {
+    let simulator = run_checked(|index| if index == 1 { 4 } else { u64::MAX });
+    simulator.node(0).assert_held();
+
+    // Only the step onto height 4 is off by one, every block after it counts up as usual.
+    let violations = simulator.node(1).violations();
+    assert_eq!(violations.len(), 1);
+    let block = &simulator.node(1).node.chain.blocks[&violations[0].block];
+    assert_eq!(block.height, 4);
+    assert_eq!(violations[0].invariant, "counts blocks");
+    assert_eq!(violations[0].message, "went from 3 to 5");
+
+    let panic =
+        std::panic::catch_unwind(AssertUnwindSafe(|| simulator.node(1).assert_held())).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(
+        message.contains(&format!("block #4 {}", block.hash)),
+        "{message}"
+    );
+}
+
+#[test]
+fn invariants_of_the_runtime() {
+    let body = serde_json::json!([]);
+    let genesis = RuntimeState::genesis(&[(User::Alice, 100)]);
+    let block = |state: &RuntimeState| -> Block<Pow, Runtime> {
+        let header = Header::new(H256::zero(), 1, hash(state), H256::zero(), 0u64);
+        serde_json::from_value(serde_json::json!({ "header": header, "body": body })).unwrap()
+    };
+    let check = |state: &RuntimeState, block: &Block<Pow, Runtime>| {
+        runtime_invariants::<Pow>()
+            .iter()
+            .filter(|invariant| invariant.check(block, &genesis, state).is_err())
+            .map(|invariant| invariant.name())
+            .collect::<Vec<_>>()
+    };
+
+    assert!(check(&genesis, &block(&genesis)).is_empty());
+    // An empty block can not create money, let alone a balance that wrapped around.
+    let inflated = RuntimeState::genesis(&[(User::Alice, 100), (User::Bob, u64::MAX)]);
+    assert_eq!(
+        check(&inflated, &block(&inflated)),
+        ["issuance conserved", "no negative balances"]
+    );
+    assert_eq!(
+        check(&genesis, &block(&inflated)),
+        ["state root matches header"]
+    );
+}
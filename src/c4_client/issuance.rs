@@ -0,0 +1,332 @@
+//! So far the only way new money comes into existence is the permissionless mint of chapter 1.
+//! Real chains mint on a schedule instead, and pay the new money to the people who keep the
+//! chain running: the author of each block, and the stakers who back the authors.
+//!
+//! The schedule is part of the chain's rules, so it is set in the chain spec, the `[issuance]`
+//! section of the node config, and every node must use the same one. Chains pick very different
+//! schedules, and we support three of them:
+//! - A fixed reward per block, so that the supply grows linearly forever.
+//! - A reward that halves every so many blocks, like Bitcoin's, so that the supply approaches a
+//!   fixed cap.
+//! - A percentage of the stake, like most proof of stake chains, so that the supply grows
+//!   exponentially at a steady rate.
+//!
+//! The reward of a block is minted after its extrinsics are executed. The author gets their
+//! share, and the rest is split among the stakers in proportion to their stake. There is no
+//! bonding here, so every account stakes its whole balance, the author included.
+//!
+//! A client mints rewards once it is given the `Issuing` executive. The author of a block is
+//! whoever signed it, see `authoring::Signed`, so a chain with rewards needs signed blocks.
+//!
+//! ```toml
+//! [issuance]
+//! author_percent = 50
+//!
+//! [issuance.schedule]
+//! kind = "halving"
+//! initial = 64
+//! every = 1000
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::authoring::{Signed, SignedDigest};
+use super::executive::Executive;
+use super::keystore;
+use super::runtime::{AccountInfo, Runtime, RuntimeState, SignedExtrinsic, ACCOUNTS};
+use super::ImportError;
+use crate::c1_state_machine::{StateMachine, User};
+use crate::c3_consensus::{Consensus, Header};
+use crate::crypto::address::Address;
+
+/// How many new coins are minted at each height.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum IssuanceSchedule {
+    /// The same reward for every block.
+    Fixed { reward: u64 },
+    /// The initial reward, halved at every multiple of `every` blocks. A period of zero never
+    /// halves.
+    Halving { initial: u64, every: u64 },
+    /// The given percentage of the total stake is minted over every `period` blocks, a little
+    /// bit at each block.
+    PercentOfStake { percent: u64, period: u64 },
+}
+
+impl Default for IssuanceSchedule {
+    fn default() -> Self {
+        IssuanceSchedule::Fixed { reward: 0 }
+    }
+}
+
+impl IssuanceSchedule {
+    /// The reward for the block at the given height, on top of a state with the given total
+    /// stake. The genesis block has no author, so it has no reward either.
+    pub fn reward_at(&self, height: u64, total_stake: u128) -> u64 {
+        if height == 0 {
+            return 0;
+        }
+        match *self {
+            IssuanceSchedule::Fixed { reward } => reward,
+            IssuanceSchedule::Halving { initial, every } => {
+                let halvings = height.checked_div(every).unwrap_or(0);
+                u32::try_from(halvings)
+                    .ok()
+                    .and_then(|halvings| initial.checked_shr(halvings))
+                    .unwrap_or(0)
+            }
+            IssuanceSchedule::PercentOfStake { percent, period } => {
+                // A stake so large that the product overflows mints as much as it can.
+                let per_period = total_stake
+                    .checked_mul(u128::from(percent))
+                    .map_or(u128::MAX, |scaled| scaled / 100);
+                let reward = per_period.checked_div(u128::from(period)).unwrap_or(0);
+                u64::try_from(reward).unwrap_or(u64::MAX)
+            }
+        }
+    }
+}
+
+/// The chain spec's issuance settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IssuanceConfig {
+    pub schedule: IssuanceSchedule,
+    /// How many percent of each reward go to the block's author. The rest goes to the stakers.
+    pub author_percent: u64,
+}
+
+impl Default for IssuanceConfig {
+    fn default() -> Self {
+        IssuanceConfig {
+            schedule: IssuanceSchedule::default(),
+            author_percent: 100,
+        }
+    }
+}
+
+impl IssuanceConfig {
+    /// Who gets how much of the reward for the block at the given height, authored by the
+    /// given author on top of the given state. Rounding leftovers go to the author, so that the
+    /// shares always add up to the whole reward.
+    pub fn rewards(&self, state: &RuntimeState, height: u64, author: User) -> BTreeMap<User, u64> {
+        let total_stake = state.total_issuance();
+        let reward = self.schedule.reward_at(height, total_stake);
+        let for_stakers = if total_stake == 0 {
+            0
+        } else {
+            reward - (u128::from(reward) * u128::from(self.author_percent.min(100)) / 100) as u64
+        };
+
+        let mut rewards = BTreeMap::new();
+        let mut paid = 0;
+        for who in ACCOUNTS {
+            let stake = u128::from(state.account(who).balance);
+            let share = (u128::from(for_stakers) * stake / total_stake.max(1)) as u64;
+            if share > 0 {
+                rewards.insert(who, share);
+                paid += share;
+            }
+        }
+        if reward > paid {
+            *rewards.entry(author).or_default() += reward - paid;
+        }
+        rewards
+    }
+
+    /// Execute the block at the given height on top of the given state, and mint its rewards.
+    /// Stakes are taken before the block's extrinsics, so that nobody can grab a bigger share
+    /// by moving money around within the block.
+    pub fn execute_block(
+        &self,
+        state: &RuntimeState,
+        height: u64,
+        author: User,
+        extrinsics: &[SignedExtrinsic],
+    ) -> RuntimeState {
+        let rewards = self.rewards(state, height, author);
+        let mut state = extrinsics
+            .iter()
+            .fold(state.clone(), |state, t| Runtime::next_state(&state, t));
+        for (who, reward) in rewards {
+            let info = state.account(who);
+            state.set_account(
+                who,
+                AccountInfo {
+                    balance: info.balance.saturating_add(reward),
+                    ..info
+                },
+            );
+        }
+        state
+    }
+}
+
+/// An executive that mints the rewards of every block, on top of the limits of the executive
+/// it wraps.
+pub struct Issuing<E> {
+    pub config: IssuanceConfig,
+    pub limits: E,
+}
+
+impl<E> Issuing<E> {
+    pub fn new(config: IssuanceConfig, limits: E) -> Self {
+        Issuing { config, limits }
+    }
+}
+
+impl<C, E> Executive<Signed<C>, Runtime> for Issuing<E>
+where
+    C: Consensus,
+    E: Executive<Signed<C>, Runtime>,
+{
+    fn check_body(&self, body: &[SignedExtrinsic]) -> Result<(), ImportError> {
+        self.limits.check_body(body)
+    }
+
+    fn fits(&self, body: &[SignedExtrinsic], next: &SignedExtrinsic) -> bool {
+        self.limits.fits(body, next)
+    }
+
+    /// Pay the user who signed the block, or who is about to sign it. Blocks signed by a key
+    /// that is no user's have nobody to pay, and mint nothing.
+    fn execute_block(
+        &self,
+        engine: &Signed<C>,
+        pre_state: RuntimeState,
+        height: u64,
+        digest: Option<&SignedDigest<C::Digest>>,
+        body: &[SignedExtrinsic],
+    ) -> Result<RuntimeState, ImportError> {
+        let author = match digest {
+            Some(digest) => digest.author.map(|(author, _)| author),
+            None => engine.author(),
+        };
+        match author.and_then(|author| keystore::dev_user(&Address::from_public(&author))) {
+            Some(author) => Ok(self.config.execute_block(&pre_state, height, author, body)),
+            None => self
+                .limits
+                .execute_block(engine, pre_state, height, digest, body),
+        }
+    }
+
+    fn seal(
+        &self,
+        engine: &Signed<C>,
+        parent_digest: &SignedDigest<C::Digest>,
+        pre_state: &RuntimeState,
+        partial_header: Header<()>,
+    ) -> Option<Header<SignedDigest<C::Digest>>> {
+        self.limits
+            .seal(engine, parent_digest, pre_state, partial_header)
+    }
+}
+
+#[test]
+fn issuance_halves_at_period_boundaries() {
+    let schedule = IssuanceSchedule::Halving {
+        initial: 64,
+        every: 10,
+    };
+
+    assert_eq!(schedule.reward_at(0, 0), 0);
+    assert_eq!(schedule.reward_at(1, 0), 64);
+    assert_eq!(schedule.reward_at(9, 0), 64);
+    assert_eq!(schedule.reward_at(10, 0), 32);
+    assert_eq!(schedule.reward_at(25, 0), 16);
+    assert_eq!(schedule.reward_at(10 * 64, 0), 0);
+    assert_eq!(schedule.reward_at(u64::MAX, 0), 0);
+}
+
+#[test]
+fn issuance_percent_of_stake_spreads_over_period() {
+    let schedule = IssuanceSchedule::PercentOfStake {
+        percent: 10,
+        period: 100,
+    };
+
+    assert_eq!(schedule.reward_at(1, 1_000_000), 1_000);
+    assert_eq!(schedule.reward_at(1, 0), 0);
+    let never = IssuanceSchedule::PercentOfStake {
+        percent: 10,
+        period: 0,
+    };
+    assert_eq!(never.reward_at(1, 1_000_000), 0);
+    let huge = IssuanceSchedule::PercentOfStake {
+        percent: u64::MAX,
+        period: 1,
+    };
+    assert_eq!(huge.reward_at(1, u128::MAX), u64::MAX);
+}
+
+#[test]
+fn issuance_splits_reward_between_author_and_stakers() {
+    let config = IssuanceConfig {
+        schedule: IssuanceSchedule::Fixed { reward: 101 },
+        author_percent: 50,
+    };
+    let state = RuntimeState::genesis(&[(User::Alice, 300), (User::Bob, 100)]);
+
+    let rewards = config.rewards(&state, 1, User::Charlie);
+    // The stakers split 51 three to one, and the author gets 50 plus the rounding leftovers.
+    assert_eq!(
+        rewards,
+        BTreeMap::from([(User::Alice, 38), (User::Bob, 12), (User::Charlie, 51)])
+    );
+
+    let next = config.execute_block(&state, 1, User::Charlie, &[]);
+    assert_eq!(next.total_issuance(), 400 + 101);
+    assert_eq!(next.account(User::Alice).balance, 338);
+}
+
+#[test]
+fn issuance_without_stake_goes_to_author() {
+    let config = IssuanceConfig {
+        schedule: IssuanceSchedule::Fixed { reward: 10 },
+        author_percent: 0,
+    };
+    let state = RuntimeState::default();
+
+    let next = config.execute_block(&state, 1, User::Bob, &[]);
+    assert_eq!(next.account(User::Bob).balance, 10);
+    assert_eq!(config.execute_block(&state, 0, User::Bob, &[]), state);
+}
+
+#[cfg(test)]
+use super::{FullClient, ImportBlock, LongestChain, SimplePool};
+
+#[cfg(test)]
+type RewardingClient = FullClient<Signed<()>, Runtime, LongestChain, SimplePool<Runtime>>;
+
+#[test]
+fn issuance_client_pays_the_author_of_every_block() {
+    let config = IssuanceConfig {
+        schedule: IssuanceSchedule::Fixed { reward: 10 },
+        author_percent: 100,
+    };
+    let genesis = RuntimeState::genesis(&[(User::Bob, 100)]);
+    let mut author = RewardingClient::new(genesis.clone())
+        .with_executive(Issuing::new(config, ()))
+        .with_consensus_engine(Signed::new((), keystore::dev_keypair(User::Alice)));
+    let hash = author.author_and_import_automatic_block().unwrap();
+    assert_eq!(
+        author.get_state(hash).unwrap().account(User::Alice).balance,
+        10
+    );
+
+    // Other nodes with the same issuance agree, and those without it do not.
+    let mut follower =
+        RewardingClient::new(genesis.clone()).with_executive(Issuing::new(config, ()));
+    assert_eq!(
+        follower.try_import_block(author.get_block(hash).unwrap()),
+        Ok(())
+    );
+    assert_eq!(follower.get_state(hash), author.get_state(hash));
+    let mut stingy = RewardingClient::new(genesis);
+    assert_eq!(
+        stingy.try_import_block(author.get_block(hash).unwrap()),
+        Err(ImportError::StateRootMismatch)
+    );
+}
@@ -0,0 +1,248 @@
+//! Before strangers can share a currency, the currency must make sure that only the owner of an
+//! account can spend from it. Real blockchains achieve this with public key cryptography: users
+//! sign their transactions with a private key, and everyone else verifies the signature with the
+//! corresponding public key.
+//!
+//! Signatures are real ed25519 signatures over the hash of the signed payload. The keys are not
+//! secret though: every play user has a well-known development key, derived from their name,
+//! just like Alice and Bob on Substrate development chains. So anyone could still forge a
+//! signature for anyone else. What we _can_ model faithfully is the keystore: a node or wallet is
+//! only able to produce signatures for the accounts whose keys it holds.
+//!
+//! Alongside the development keys, the node keeps secret ed25519 keys in its data directory, which
+//! operators manage with the `node key` subcommands. See the `crypto` module.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::c1_state_machine::User;
+use crate::crypto::address::Address;
+use crate::crypto::{mnemonic, Secret};
+use crate::crypto::sig::{self, Keypair, PublicKey};
+use crate::hashing::{blake2_256, H256};
+
+/// The directory inside the data directory that `key insert` saves keys to. Every key is saved
+/// as its own file, named after the public key, holding the key's secret URI: its mnemonic,
+/// optionally followed by a derivation path. On unix, only the owner may read the directory and
+/// the files in it.
+pub const KEYSTORE_DIR: &str = "keystore";
+
+/// The well-known development key of the given user.
+pub fn dev_keypair(user: User) -> Keypair {
+    Keypair::from_seed(blake2_256(format!("//{user:?}").as_bytes()))
+}
+
+/// The public half of the given user's development key.
+pub fn dev_public(user: User) -> PublicKey {
+    dev_keypair(user).public()
+}
+
+/// The address of the given user's development key. This is how users are known to wallets
+/// and the RPC.
+pub fn dev_address(user: User) -> Address {
+    Address::from_public(&dev_public(user))
+}
+
+/// The user whose development key has the given address, if any.
+pub fn dev_user(address: &Address) -> Option<User> {
+    [User::Alice, User::Bob, User::Charlie]
+        .into_iter()
+        .find(|&user| dev_address(user) == *address)
+}
+
+/// A signature over the hash of some payload, made with the signer's development key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Signature {
+    signer: User,
+    signature: sig::Signature,
+}
+
+impl Signature {
+    /// Check that this signature was made by the given signer over the given payload hash.
+    pub fn verify(&self, signer: User, payload_hash: H256) -> bool {
+        self.signer == signer && dev_public(signer).verify(payload_hash.as_bytes(), &self.signature)
+    }
+}
+
+/// The set of keys that are available for signing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Keystore {
+    keys: BTreeSet<User>,
+}
+
+impl Keystore {
+    /// Create an empty keystore that can not sign for anyone.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a keystore holding the well-known development keys of every play user.
+    /// Handy for local testing. Never do anything like this on a real network.
+    pub fn dev() -> Self {
+        Keystore {
+            keys: BTreeSet::from([User::Alice, User::Bob, User::Charlie]),
+        }
+    }
+
+    /// Add the key for the given user to the keystore.
+    pub fn insert(&mut self, user: User) {
+        self.keys.insert(user);
+    }
+
+    /// Check whether the keystore holds the key for the given user.
+    pub fn contains(&self, user: User) -> bool {
+        self.keys.contains(&user)
+    }
+
+    /// Sign the given payload hash on behalf of the given user.
+    /// Returns None if the keystore does not hold that user's key.
+    pub fn sign(&self, user: User, payload_hash: H256) -> Option<Signature> {
+        self.contains(user).then(|| Signature {
+            signer: user,
+            signature: dev_keypair(user).sign(payload_hash.as_bytes()),
+        })
+    }
+}
+
+/// Save the key for the given secret URI to the keystore directory in the given data directory,
+/// so that the node can author with it. Returns the key.
+pub fn insert_key_file(data_dir: &Path, phrase: &str) -> io::Result<Keypair> {
+    let keypair = mnemonic::from_uri(phrase)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let file = data_dir
+        .join(KEYSTORE_DIR)
+        .join(keypair.public().to_string());
+    write_secret(&file, phrase.as_bytes())?;
+    Ok(keypair)
+}
+
+/// Write the given secret to the given path atomically, like `persist::write_atomically`, but
+/// such that nobody but the owner can read it: the file gets mode 0600, and its directory 0700.
+fn write_secret(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+        }
+    }
+
+    // A leftover from an interrupted write may have been created with other permissions.
+    let temporary = path.with_extension("tmp");
+    match fs::remove_file(&temporary) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temporary)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+/// Load every key from the keystore directory in the given data directory. A missing
+/// directory just means that no keys were inserted yet.
+pub fn load_key_files(data_dir: &Path) -> io::Result<Vec<Keypair>> {
+    let entries = match fs::read_dir(data_dir.join(KEYSTORE_DIR)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut keys = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some() {
+            // Left behind by an interrupted write.
+            continue;
+        }
+        let phrase = Secret::new(fs::read_to_string(&path)?);
+        let keypair = mnemonic::from_uri(&phrase).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {e}", path.display()),
+            )
+        })?;
+        keys.push(keypair);
+    }
+    keys.sort_by_key(|key| key.public());
+    Ok(keys)
+}
+
+#[test]
+fn keystore_empty_cannot_sign() {
+    let keystore = Keystore::new();
+    assert_eq!(keystore.sign(User::Alice, H256::from(42)), None);
+}
+
+#[test]
+fn keystore_signature_verifies() {
+    let mut keystore = Keystore::new();
+    keystore.insert(User::Alice);
+
+    let signature = keystore.sign(User::Alice, H256::from(42)).unwrap();
+    assert!(signature.verify(User::Alice, H256::from(42)));
+    assert_ne!(dev_public(User::Alice), dev_public(User::Bob));
+}
+
+#[test]
+fn keystore_dev_addresses_identify_users() {
+    for user in [User::Alice, User::Bob, User::Charlie] {
+        assert_eq!(dev_user(&dev_address(user)), Some(user));
+    }
+    let stranger = Address::from_public(&Keypair::from_seed([9; 32]).public());
+    assert_eq!(dev_user(&stranger), None);
+}
+
+#[test]
+fn keystore_signature_does_not_verify_for_other_payload_or_signer() {
+    let signature = Keystore::dev().sign(User::Bob, H256::from(42)).unwrap();
+
+    assert!(!signature.verify(User::Bob, H256::from(43)));
+    assert!(!signature.verify(User::Alice, H256::from(42)));
+}
+
+#[test]
+fn keystore_key_files_round_trip() {
+    let dir = std::env::temp_dir().join(format!("keystore-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    assert!(load_key_files(&dir).unwrap().is_empty());
+
+    let phrase = mnemonic::generate(12).unwrap();
+    let inserted = insert_key_file(&dir, &phrase).unwrap();
+    let derived = insert_key_file(&dir, &format!("{}//stash", phrase.as_str())).unwrap();
+    assert!(insert_key_file(&dir, "not a mnemonic").is_err());
+
+    let loaded = load_key_files(&dir).unwrap();
+    let mut expected = vec![inserted.public(), derived.public()];
+    expected.sort();
+    assert_eq!(
+        loaded.iter().map(Keypair::public).collect::<Vec<_>>(),
+        expected
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn keystore_key_files_are_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("keystore-private-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let keypair = insert_key_file(&dir, &mnemonic::generate(12).unwrap()).unwrap();
+
+    let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    let keystore = dir.join(KEYSTORE_DIR);
+    assert_eq!(mode(&keystore), 0o700);
+    assert_eq!(mode(&keystore.join(keypair.public().to_string())), 0o600);
+    fs::remove_dir_all(&dir).unwrap();
+}
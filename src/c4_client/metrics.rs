@@ -0,0 +1,215 @@
+//! Once several nodes are running, staring at their logs is no longer a good way to tell how
+//! the network is doing. Instead, every node keeps a few numbers about itself and exposes them
+//! on an HTTP `/metrics` endpoint in the Prometheus text format. Prometheus scrapes the
+//! endpoint periodically, and tools such as Grafana graph the results.
+//!
+//! The metrics are shared between all of the node's tasks behind an `Arc`, and every metric
+//! takes care of its own synchronization, so updating one never blocks the rest of the node
+//! for long.
+
+use std::fmt::Write;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::http::{self, Request, Response};
+
+/// A value that can go up and down.
+#[derive(Default)]
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    pub fn set(&self, value: u64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The upper bounds, in seconds, of the buckets that durations are sorted into.
+pub const DURATION_BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Counts how many observed durations fall into each bucket.
+#[derive(Default)]
+pub struct Histogram {
+    inner: Mutex<HistogramData>,
+}
+
+#[derive(Default)]
+struct HistogramData {
+    /// The number of observations that fell into each bucket. Observations larger than the
+    /// last bucket are only counted in the total.
+    buckets: [u64; DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    /// Record a single duration.
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let mut data = self.inner.lock().expect("histogram mutex poisoned");
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            data.buckets[bucket] += 1;
+        }
+        data.sum += seconds;
+        data.count += 1;
+    }
+
+    /// Run the given closure, and record how long it took.
+    pub fn time<T>(&self, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.observe(start.elapsed());
+        result
+    }
+
+    /// The total number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.inner.lock().expect("histogram mutex poisoned").count
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let data = self.inner.lock().expect("histogram mutex poisoned");
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+
+        // Prometheus buckets are cumulative.
+        let mut cumulative = 0;
+        for (bound, count) in DURATION_BUCKETS.iter().zip(data.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", data.count);
+        let _ = writeln!(out, "{name}_sum {}", data.sum);
+        let _ = writeln!(out, "{name}_count {}", data.count);
+    }
+}
+
+/// Every metric that the node keeps about itself.
+#[derive(Default)]
+pub struct Metrics {
+    /// The height of the best block.
+    pub block_height: Gauge,
+    /// How long it takes to import a block.
+    pub block_import_time: Histogram,
+    /// The number of transactions waiting in the pool.
+    pub pool_size: Gauge,
+    /// The number of connected peers.
+    pub peer_count: Gauge,
+    /// The number of hashes per second computed while mining.
+    pub hash_rate: Gauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the hash rate after the given number of hashes took the given time.
+    pub fn record_hashes(&self, hashes: u64, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.hash_rate.set((hashes as f64 / seconds) as u64);
+        }
+    }
+
+    /// Render every metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        render_gauge(
+            &mut out,
+            "node_block_height",
+            "Height of the best block",
+            &self.block_height,
+        );
+        self.block_import_time.render(
+            &mut out,
+            "node_block_import_seconds",
+            "Time taken to import a block",
+        );
+        render_gauge(
+            &mut out,
+            "node_pool_size",
+            "Transactions waiting in the pool",
+            &self.pool_size,
+        );
+        render_gauge(
+            &mut out,
+            "node_peer_count",
+            "Connected peers",
+            &self.peer_count,
+        );
+        render_gauge(
+            &mut out,
+            "node_hash_rate",
+            "Hashes per second while mining",
+            &self.hash_rate,
+        );
+        out
+    }
+}
+
+fn render_gauge(out: &mut String, name: &str, help: &str, gauge: &Gauge) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {}", gauge.get());
+}
+
+/// Serve the given metrics on `/metrics` in a background thread.
+pub fn serve(
+    addr: impl ToSocketAddrs,
+    metrics: Arc<Metrics>,
+) -> std::io::Result<(SocketAddr, JoinHandle<()>)> {
+    http::serve(addr, move |request: Request| {
+        if request.method != "GET" || request.path != "/metrics" {
+            return Response::not_found();
+        }
+        Response::ok("text/plain; version=0.0.4", metrics.render())
+    })
+}
+
+#[test]
+fn metrics_histogram_buckets_are_cumulative() {
+    let metrics = Metrics::new();
+    metrics.block_import_time.observe(Duration::from_millis(3));
+    metrics
+        .block_import_time
+        .observe(Duration::from_millis(300));
+    metrics.block_import_time.observe(Duration::from_secs(60));
+
+    let rendered = metrics.render();
+    assert!(rendered.contains("node_block_import_seconds_bucket{le=\"0.001\"} 0\n"));
+    assert!(rendered.contains("node_block_import_seconds_bucket{le=\"0.005\"} 1\n"));
+    assert!(rendered.contains("node_block_import_seconds_bucket{le=\"5\"} 2\n"));
+    assert!(rendered.contains("node_block_import_seconds_bucket{le=\"+Inf\"} 3\n"));
+    assert!(rendered.contains("node_block_import_seconds_count 3\n"));
+}
+
+#[test]
+fn metrics_hash_rate() {
+    let metrics = Metrics::new();
+    metrics.record_hashes(5000, Duration::from_millis(500));
+
+    assert_eq!(metrics.hash_rate.get(), 10_000);
+}
+
+#[test]
+fn metrics_served_over_http() {
+    let metrics = Arc::new(Metrics::new());
+    metrics.block_height.set(42);
+    let (addr, _) = serve("127.0.0.1:0", metrics).unwrap();
+
+    let (status, body) = http::get(addr, "/metrics").unwrap();
+    assert_eq!(status, 200);
+    assert!(String::from_utf8(body)
+        .unwrap()
+        .contains("node_block_height 42\n"));
+
+    let (status, _) = http::get(addr, "/other").unwrap();
+    assert_eq!(status, 404);
+}
@@ -15,9 +15,10 @@
 use crate::{
     c1_state_machine::StateMachine,
     c3_consensus::{Consensus, Header},
+    hashing::H256,
 };
-use p1_data_structure::Block;
-use p3_fork_choice::ForkChoice;
+pub use p1_data_structure::Block;
+pub use p3_fork_choice::ForkChoice;
 
 mod p1_data_structure;
 mod p2_importing_blocks;
@@ -26,7 +27,105 @@ mod p4_transaction_pool;
 mod p5_authoring_blocks;
 mod p6_finality;
 
-type Hash = u64;
+// How the client checks and executes whole blocks, and how it stores their states. The client
+// itself depends on these, so unlike the modules below they work without the standard library.
+pub mod executive;
+pub mod storage;
+
+// Supporting modules that turn the client into a node that people can actually use. Nodes need
+// an operating system, so all of them need the standard library.
+#[cfg(feature = "std")]
+pub mod authoring;
+#[cfg(feature = "std")]
+pub mod backend;
+#[cfg(feature = "std")]
+pub mod base_fee;
+#[cfg(feature = "std")]
+pub mod byzantine;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod debugger;
+#[cfg(feature = "std")]
+pub mod devnet;
+#[cfg(feature = "std")]
+pub mod faucet;
+#[cfg(feature = "std")]
+pub mod fee_pool;
+#[cfg(feature = "std")]
+pub mod forks;
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod htlc;
+#[cfg(feature = "std")]
+mod http;
+#[cfg(feature = "std")]
+pub mod invariants;
+#[cfg(feature = "std")]
+pub mod issuance;
+#[cfg(feature = "std")]
+pub mod keystore;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod mutation;
+#[cfg(feature = "std")]
+pub mod multisig;
+#[cfg(feature = "std")]
+pub mod network;
+#[cfg(feature = "std")]
+pub mod oracle;
+#[cfg(feature = "std")]
+pub mod persist;
+#[cfg(feature = "std")]
+pub mod proof;
+#[cfg(feature = "std")]
+pub mod randomness;
+#[cfg(feature = "std")]
+pub mod recorder;
+#[cfg(feature = "std")]
+pub mod relay;
+#[cfg(feature = "std")]
+pub mod reorg;
+#[cfg(feature = "std")]
+pub mod rpc;
+#[cfg(feature = "std")]
+pub mod runtime;
+#[cfg(feature = "std")]
+pub mod scenario;
+#[cfg(feature = "std")]
+pub mod scheduler;
+#[cfg(feature = "std")]
+pub mod simulator;
+#[cfg(feature = "std")]
+pub mod telemetry;
+#[cfg(feature = "std")]
+pub mod timeline;
+#[cfg(feature = "std")]
+pub mod tip;
+#[cfg(feature = "std")]
+pub mod treasury;
+#[cfg(feature = "std")]
+pub mod upgrade;
+#[cfg(feature = "std")]
+pub mod validators;
+#[cfg(feature = "std")]
+pub mod wallet;
+#[cfg(feature = "std")]
+pub mod weight;
+#[cfg(feature = "std")]
+pub mod workload;
+
+pub use p2_importing_blocks::{ImportBlock, ImportError};
+pub use p3_fork_choice::LongestChain;
+pub use p4_transaction_pool::{SimplePool, TransactionPool};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c4_client/mod.rs"]
+mod solution;
+
+type Hash = H256;
 
 /// A client represents one view of an evolving blockchain network. It knows of blocks,
 /// forks, state, and it also pools transactions waiting to be included in upcoming blocks.
@@ -42,14 +141,15 @@ type Hash = u64;
 /// by implementing more and more methods on it.
 /// 
 /// In practice the trait bounds here will always be the same:
-/// C: Client
+/// C: Consensus
 /// SM: StateMachine
 /// FC: ForkChoice<C>
 /// P: TransactionPool<SM>
 /// 
-/// But we leave them unconstrained here to avoid repeating many where clauses throughout the section.
-/// Instead we bind them on impl blocks.
-pub struct FullClient<C, SM, FC, P>
+/// The client stores blocks and states, which only exist for a consensus engine and a state machine,
+/// so the struct binds those two. We leave the others unconstrained here to avoid repeating many where
+/// clauses throughout the section. Instead we bind them on impl blocks.
+pub struct FullClient<C: Consensus, SM: StateMachine, FC, P>
 {
     /// The consensus engine used by this client.
     consensus_engine: C,
@@ -59,9 +159,15 @@ pub struct FullClient<C, SM, FC, P>
     fork_choice: FC,
     /// The transaction pool used by this client.
     transaction_pool: P,
+    /// The executive that checks and executes whole blocks, if any. See the `executive` module.
+    executive: Option<alloc::boxed::Box<dyn executive::Executive<C, SM> + Send>>,
 
     // TODO: You are free to add more fields here, and you will probably need to.
     // Please document them as you add them.
+
+    /// The blocks, states and leaves of the reference solution.
+    #[cfg(feature = "solutions")]
+    chain: solution::Chain<C, SM>,
 }
 
 //TODO Consider exploring LightClient as well. It may import headers but not blocks for example.
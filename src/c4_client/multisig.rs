@@ -0,0 +1,398 @@
+//! Money that belongs to a group, like a company's funds or a project's treasury, should not be
+//! in the hands of any single member, who might run off with it or lose their key. A multisig
+//! account is owned by a set of signatories instead, and a call on its behalf only goes through
+//! once a threshold of them approved it.
+//!
+//! Nobody holds a key for a multisig account. Its id is derived from its signatories and its
+//! threshold, like in Substrate's multisig pallet, so anybody can compute it, and the same group
+//! with the same threshold always ends up with the same account. Anybody can fund it.
+//!
+//! Any call can be made on behalf of a multisig account. The first signatory to approve it
+//! opens a proposal, the others approve the very same call, and the approval that reaches the
+//! threshold executes it. The origin named in the call is ignored: a multisig call always mints
+//! into, burns from, or transfers out of the multisig account. Every approval signs the multisig
+//! account's nonce, which moves on with every executed call, so approvals can't be replayed.
+//!
+//! Signatories may never agree. To keep the state from filling up with proposals that will never
+//! pass, every proposal names the height at which it expires, at most `MAX_MULTISIG_TIMEOUT`
+//! blocks ahead. A proposal that did not pass by then is dropped, and its approvals are void.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::keystore::Signature;
+use super::runtime::{Runtime, RuntimeState, SignedExtrinsic};
+use crate::c1_state_machine::{AccountingTransaction, StateMachine, User};
+use crate::hashing::{hash_of, H256};
+
+/// How many blocks ahead a proposal may expire at most.
+pub const MAX_MULTISIG_TIMEOUT: u64 = 100;
+
+/// The signatories of a multisig account, and how many of them must approve a call.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MultisigAccount {
+    signatories: BTreeSet<User>,
+    threshold: usize,
+}
+
+impl MultisigAccount {
+    /// A multisig account of the given signatories. None unless the threshold is at least one,
+    /// and no more than there are signatories.
+    pub fn new(signatories: BTreeSet<User>, threshold: usize) -> Option<Self> {
+        (threshold > 0 && threshold <= signatories.len()).then_some(MultisigAccount {
+            signatories,
+            threshold,
+        })
+    }
+
+    /// The id of the account, derived from its signatories and threshold.
+    pub fn id(&self) -> H256 {
+        hash_of("multisig account", self)
+    }
+
+    pub fn signatories(&self) -> &BTreeSet<User> {
+        &self.signatories
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
+/// What the runtime knows about a multisig account.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MultisigInfo {
+    pub balance: u64,
+    /// The number of calls executed on behalf of the account so far.
+    pub nonce: u64,
+}
+
+/// A call that was approved by some, but not enough, signatories yet.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Proposal {
+    /// The id of the multisig account the call is made on behalf of.
+    pub account: H256,
+    pub approvals: BTreeSet<User>,
+    /// The height from which on the proposal can no longer pass.
+    pub expires: u64,
+}
+
+/// An extrinsic that can be applied to a chain with multisig accounts.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MultisigExtrinsic {
+    /// An ordinary extrinsic.
+    Apply(SignedExtrinsic),
+    /// Transfer the given amount into a multisig account. The nonce is the sender's account
+    /// nonce, just like in a signed extrinsic.
+    Fund {
+        sender: User,
+        nonce: u64,
+        account: H256,
+        amount: u64,
+        signature: Signature,
+    },
+    /// A signatory approves a call on behalf of a multisig account, opening a proposal if it is
+    /// the first approval. It is signed over the `approve_payload`.
+    Approve {
+        signatory: User,
+        account: MultisigAccount,
+        call: AccountingTransaction,
+        expires: u64,
+        signature: Signature,
+    },
+}
+
+/// Calculate the hash that a sender must sign to fund a multisig account.
+pub fn fund_payload(sender: User, nonce: u64, account: H256, amount: u64) -> H256 {
+    hash_of("multisig fund", &(sender, nonce, account, amount))
+}
+
+/// Calculate the hash that a signatory must sign to approve a call on behalf of the given
+/// account, whose nonce is the given one. It doubles as the id of the proposal.
+pub fn approve_payload(
+    account: H256,
+    nonce: u64,
+    call: &AccountingTransaction,
+    expires: u64,
+) -> H256 {
+    hash_of("multisig approve", &(account, nonce, call, expires))
+}
+
+/// The runtime state, along with the multisig accounts.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MultisigState {
+    pub runtime: RuntimeState,
+    /// The multisig accounts that hold anything, by their id.
+    pub accounts: BTreeMap<H256, MultisigInfo>,
+    /// The open proposals, by their id.
+    pub proposals: BTreeMap<H256, Proposal>,
+}
+
+impl MultisigState {
+    /// A genesis state with the given runtime state and no multisig accounts.
+    pub fn genesis(runtime: RuntimeState) -> Self {
+        MultisigState {
+            runtime,
+            ..MultisigState::default()
+        }
+    }
+
+    /// The information about the multisig account with the given id.
+    pub fn account(&self, id: H256) -> MultisigInfo {
+        self.accounts.get(&id).copied().unwrap_or_default()
+    }
+
+    /// Execute the block at the given height on top of this state, and then drop every proposal
+    /// that expires at the next height. Invalid extrinsics leave the state untouched, just like
+    /// in the runtime: funding that the sender can't pay for, and approvals by somebody who is
+    /// no signatory, with a bad signature, or whose proposal expired or would expire too late.
+    ///
+    /// A call that does not go through once approved, like a transfer of more than the account
+    /// holds, still uses up the nonce, and its proposal is closed.
+    pub fn execute_block(&self, height: u64, extrinsics: &[MultisigExtrinsic]) -> MultisigState {
+        let mut state = self.clone();
+        for extrinsic in extrinsics {
+            state.execute(height, extrinsic);
+        }
+        state
+            .proposals
+            .retain(|_, proposal| proposal.expires > height + 1);
+        state
+    }
+
+    fn execute(&mut self, height: u64, extrinsic: &MultisigExtrinsic) {
+        match extrinsic {
+            MultisigExtrinsic::Apply(t) => {
+                self.runtime = Runtime::next_state(&self.runtime, t);
+            }
+            MultisigExtrinsic::Fund {
+                sender,
+                nonce,
+                account,
+                amount,
+                signature,
+            } => {
+                let mut info = self.runtime.account(*sender);
+                let payload = fund_payload(*sender, *nonce, *account, *amount);
+                if !signature.verify(*sender, payload)
+                    || *nonce != info.nonce
+                    || info.balance < *amount
+                {
+                    return;
+                }
+                let mut multisig = self.account(*account);
+                let Some(balance) = multisig.balance.checked_add(*amount) else {
+                    return;
+                };
+                info.balance -= amount;
+                info.nonce += 1;
+                self.runtime.set_account(*sender, info);
+                multisig.balance = balance;
+                self.set_account(*account, multisig);
+            }
+            MultisigExtrinsic::Approve {
+                signatory,
+                account,
+                call,
+                expires,
+                signature,
+            } => {
+                let id = account.id();
+                let proposal_id = approve_payload(id, self.account(id).nonce, call, *expires);
+                if !account.signatories.contains(signatory)
+                    || *expires <= height
+                    || *expires > height + MAX_MULTISIG_TIMEOUT
+                    || !signature.verify(*signatory, proposal_id)
+                {
+                    return;
+                }
+                let proposal = self.proposals.entry(proposal_id).or_insert(Proposal {
+                    account: id,
+                    approvals: BTreeSet::new(),
+                    expires: *expires,
+                });
+                proposal.approvals.insert(*signatory);
+                if proposal.approvals.len() >= account.threshold {
+                    self.proposals.remove(&proposal_id);
+                    self.dispatch(id, call);
+                }
+            }
+        }
+    }
+
+    /// Execute the call on behalf of the multisig account with the given id.
+    fn dispatch(&mut self, id: H256, call: &AccountingTransaction) {
+        let mut multisig = self.account(id);
+        multisig.nonce += 1;
+        match *call {
+            AccountingTransaction::Mint { amount, .. } => {
+                multisig.balance = multisig.balance.saturating_add(amount);
+            }
+            AccountingTransaction::Burn { amount, .. } => {
+                multisig.balance = multisig.balance.saturating_sub(amount);
+            }
+            AccountingTransaction::Transfer {
+                receiver, amount, ..
+            } => {
+                let mut info = self.runtime.account(receiver);
+                if let (Some(rest), Some(received)) = (
+                    multisig.balance.checked_sub(amount),
+                    info.balance.checked_add(amount),
+                ) {
+                    multisig.balance = rest;
+                    info.balance = received;
+                    self.runtime.set_account(receiver, info);
+                }
+            }
+        }
+        self.set_account(id, multisig);
+    }
+
+    fn set_account(&mut self, id: H256, info: MultisigInfo) {
+        if info == MultisigInfo::default() {
+            self.accounts.remove(&id);
+        } else {
+            self.accounts.insert(id, info);
+        }
+    }
+}
+
+#[cfg(test)]
+use super::keystore::Keystore;
+
+/// Alice, Bob and Charlie share a 2 of 3 multisig account.
+#[cfg(test)]
+fn two_of_three() -> MultisigAccount {
+    MultisigAccount::new(BTreeSet::from([User::Alice, User::Bob, User::Charlie]), 2).unwrap()
+}
+
+#[cfg(test)]
+fn pay_charlie(amount: u64) -> AccountingTransaction {
+    AccountingTransaction::Transfer {
+        sender: User::Alice,
+        receiver: User::Charlie,
+        amount,
+    }
+}
+
+/// An approval of the given call on behalf of the given account with the given nonce.
+#[cfg(test)]
+fn approve(
+    signatory: User,
+    account: &MultisigAccount,
+    nonce: u64,
+    call: AccountingTransaction,
+    expires: u64,
+) -> MultisigExtrinsic {
+    let payload = approve_payload(account.id(), nonce, &call, expires);
+    let signature = Keystore::dev().sign(signatory, payload).unwrap();
+    MultisigExtrinsic::Approve {
+        signatory,
+        account: account.clone(),
+        call,
+        expires,
+        signature,
+    }
+}
+
+/// The 2 of 3 account, which Alice funded with 50 out of her 100.
+#[cfg(test)]
+fn funded() -> MultisigState {
+    let account = two_of_three().id();
+    let signature = Keystore::dev()
+        .sign(User::Alice, fund_payload(User::Alice, 0, account, 50))
+        .unwrap();
+    let fund = MultisigExtrinsic::Fund {
+        sender: User::Alice,
+        nonce: 0,
+        account,
+        amount: 50,
+        signature,
+    };
+    MultisigState::genesis(RuntimeState::genesis(&[(User::Alice, 100)])).execute_block(1, &[fund])
+}
+
+#[test]
+fn multisig_account_is_derived_from_signatories() {
+    let reordered =
+        MultisigAccount::new(BTreeSet::from([User::Charlie, User::Bob, User::Alice]), 2).unwrap();
+    assert_eq!(reordered.id(), two_of_three().id());
+    let stricter =
+        MultisigAccount::new(BTreeSet::from([User::Alice, User::Bob, User::Charlie]), 3).unwrap();
+    assert_ne!(stricter.id(), two_of_three().id());
+
+    assert!(MultisigAccount::new(BTreeSet::from([User::Alice]), 0).is_none());
+    assert!(MultisigAccount::new(BTreeSet::from([User::Alice]), 2).is_none());
+
+    let state = funded();
+    assert_eq!(state.account(two_of_three().id()).balance, 50);
+    assert_eq!(state.runtime.account(User::Alice).balance, 50);
+}
+
+#[test]
+fn multisig_executes_at_threshold() {
+    let account = two_of_three();
+    let state =
+        funded().execute_block(2, &[approve(User::Alice, &account, 0, pay_charlie(20), 10)]);
+    assert_eq!(state.proposals.len(), 1);
+    assert_eq!(state.runtime.account(User::Charlie).balance, 0);
+
+    let executed = state.execute_block(3, &[approve(User::Bob, &account, 0, pay_charlie(20), 10)]);
+    assert_eq!(executed.runtime.account(User::Charlie).balance, 20);
+    assert_eq!(
+        executed.account(account.id()),
+        MultisigInfo {
+            balance: 30,
+            nonce: 1
+        }
+    );
+    assert!(executed.proposals.is_empty());
+
+    // The approvals were for nonce 0, so they can't be replayed.
+    let replayed = executed.execute_block(
+        4,
+        &[
+            approve(User::Alice, &account, 0, pay_charlie(20), 10),
+            approve(User::Bob, &account, 0, pay_charlie(20), 10),
+        ],
+    );
+    assert_eq!(replayed, executed);
+}
+
+#[test]
+fn multisig_rejects_invalid_approvals() {
+    let account = two_of_three();
+    let state = funded();
+    let two_of_two = MultisigAccount::new(BTreeSet::from([User::Alice, User::Bob]), 2).unwrap();
+    let outsider = approve(User::Charlie, &two_of_two, 0, pay_charlie(20), 10);
+    let too_late = approve(User::Alice, &account, 0, pay_charlie(20), 1);
+    let too_far = approve(
+        User::Alice,
+        &account,
+        0,
+        pay_charlie(20),
+        2 + MAX_MULTISIG_TIMEOUT + 1,
+    );
+    let mut forged = approve(User::Alice, &account, 0, pay_charlie(20), 10);
+    if let MultisigExtrinsic::Approve { call, .. } = &mut forged {
+        *call = pay_charlie(50);
+    }
+    for invalid in [outsider, too_late, too_far, forged] {
+        assert_eq!(state.execute_block(2, &[invalid]), state);
+    }
+}
+
+#[test]
+fn multisig_stale_proposals_expire() {
+    let account = two_of_three();
+    let state = funded().execute_block(2, &[approve(User::Alice, &account, 0, pay_charlie(20), 5)]);
+    let state = state.execute_block(3, &[]);
+    assert_eq!(state.proposals.len(), 1);
+
+    // Block 4 is the last one in which the proposal could have passed.
+    let state = state.execute_block(4, &[]);
+    assert!(state.proposals.is_empty());
+    let late = state.execute_block(5, &[approve(User::Bob, &account, 0, pay_charlie(20), 5)]);
+    assert_eq!(late, state);
+}
@@ -0,0 +1,429 @@
+//! An import pipeline is a long list of rules, and it is easy to forget one. A forgotten rule
+//! does not make any test fail, because every test block follows it anyway. The only way to see
+//! that a rule is checked is to import a block that breaks it, and _only_ it. A block that
+//! breaks two rules at once may be rejected for the wrong one, which hides the missing check.
+//!
+//! This module makes such blocks. A `Mutator` starts from a valid child of some parent block,
+//! and breaks exactly one `Rule` at a time. Everything else about the block, including its
+//! seal, is fixed up so that it still holds. `check_rules` then imports every mutated block into
+//! a fresh importer, and reports every block that was accepted, or rejected for the wrong
+//! reason.
+//!
+//! The client's `import_block` only says whether it imported a block, not why it did not, so
+//! the harness works with importers that implement `TryImport`. The full client implements it
+//! with `try_import_block`, so the harness can check your client's import pipeline directly.
+
+use std::hash::Hash;
+
+use super::{Block, Consensus, ForkChoice, FullClient, Header, StateMachine, TransactionPool};
+use crate::hashing::{hash, hash_of, header_hash, H256};
+use crate::merkle::merkle_root;
+
+pub use super::ImportError;
+
+/// How many other headers we seal in search of a seal that does not fit a given header.
+const SEAL_ATTEMPTS: u64 = 16;
+
+/// A rule that every imported block must follow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// The parent must be a block we know.
+    KnownParent,
+    /// The height must be one more than the parent's.
+    Height,
+    /// The seal must satisfy the consensus engine.
+    Seal,
+    /// The extrinsics root must be the Merkle root of the body.
+    ExtrinsicsRoot,
+    /// The state root must be the hash of the state after executing the body.
+    StateRoot,
+    /// The body must not hold more than the maximum number of extrinsics.
+    BodySize,
+}
+
+impl Rule {
+    /// Every rule, in the order the harness breaks them.
+    pub const ALL: [Rule; 6] = [
+        Rule::KnownParent,
+        Rule::Height,
+        Rule::Seal,
+        Rule::ExtrinsicsRoot,
+        Rule::StateRoot,
+        Rule::BodySize,
+    ];
+
+    /// The error that a block which breaks only this rule must be rejected with.
+    pub fn error(self) -> ImportError {
+        match self {
+            Rule::KnownParent => ImportError::UnknownParent,
+            Rule::Height => ImportError::WrongHeight,
+            Rule::Seal => ImportError::BadSeal,
+            Rule::ExtrinsicsRoot => ImportError::ExtrinsicsRootMismatch,
+            Rule::StateRoot => ImportError::StateRootMismatch,
+            Rule::BodySize => ImportError::BodyTooLarge,
+        }
+    }
+}
+
+/// Something that imports blocks, and says why when it rejects one.
+pub trait TryImport<B> {
+    fn try_import(&mut self, block: B) -> Result<(), ImportError>;
+}
+
+impl<C, SM, FC, P> TryImport<Block<C, SM>> for FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + PartialEq + Hash,
+    SM::Transition: Clone + PartialEq + Hash,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+{
+    fn try_import(&mut self, block: Block<C, SM>) -> Result<(), ImportError> {
+        self.try_import_block(block)
+    }
+}
+
+/// Makes children of a parent block that break exactly one rule each.
+pub struct Mutator<'a, C: Consensus, SM: StateMachine> {
+    consensus: &'a C,
+    parent: &'a Header<C::Digest>,
+    parent_state: &'a SM::State,
+    /// The most extrinsics a block may hold.
+    max_extrinsics: usize,
+}
+
+impl<'a, C, SM> Mutator<'a, C, SM>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Hash,
+    SM::Transition: Clone + Hash,
+{
+    /// A mutator for children of the given parent, sealed with the given engine. Blocks with
+    /// more than `max_extrinsics` extrinsics are too large.
+    pub fn new(
+        consensus: &'a C,
+        parent: &'a Header<C::Digest>,
+        parent_state: &'a SM::State,
+        max_extrinsics: usize,
+    ) -> Self {
+        Mutator {
+            consensus,
+            parent,
+            parent_state,
+            max_extrinsics,
+        }
+    }
+
+    /// A valid child of the parent with the given body. None if the engine can not seal it.
+    pub fn valid_child(&self, body: Vec<SM::Transition>) -> Option<Block<C, SM>> {
+        let header = self.seal(self.child_header(&body))?;
+        Some(Block::new(header, body))
+    }
+
+    /// A child of the parent with the given body that breaks the given rule, and only that
+    /// rule. None if that is not possible: if the engine can not seal the block, if the body
+    /// is empty and so can not be made too large, or if the engine's seals do not depend on the
+    /// header they seal, so that no seal is invalid for one header but not another.
+    pub fn break_rule(&self, rule: Rule, mut body: Vec<SM::Transition>) -> Option<Block<C, SM>> {
+        let mut header = self.child_header(&body);
+        match rule {
+            Rule::KnownParent => header.parent = hash_of("mutated parent", &header.parent),
+            Rule::Height => header.height += 1,
+            Rule::ExtrinsicsRoot => {
+                header.extrinsics_root = hash_of("mutated extrinsics", &header.extrinsics_root)
+            }
+            Rule::StateRoot => header.state_root = hash_of("mutated state", &header.state_root),
+            Rule::BodySize => {
+                if body.is_empty() {
+                    return None;
+                }
+                let original = body.clone();
+                while body.len() <= self.max_extrinsics {
+                    body.extend(original.iter().cloned());
+                }
+                header = self.child_header(&body);
+            }
+            Rule::Seal => {
+                // A seal made for some other header, that does not fit this one.
+                let header = (1..=SEAL_ATTEMPTS)
+                    .filter_map(|attempt| {
+                        let mut other = header.clone();
+                        other.height += attempt;
+                        other.state_root = hash_of("other seal", &attempt);
+                        let digest = self.seal(other)?.consensus_digest().clone();
+                        Some(header.with_digest(digest))
+                    })
+                    .find(|forged| {
+                        !self
+                            .consensus
+                            .validate(self.parent.consensus_digest(), forged)
+                    })?;
+                return Some(Block::new(header, body));
+            }
+        }
+        Some(Block::new(self.seal(header)?, body))
+    }
+
+    /// The unsealed header of a valid child with the given body.
+    fn child_header(&self, body: &[SM::Transition]) -> PartialHeader {
+        let mut state = None;
+        for t in body {
+            state = Some(SM::next_state(
+                state.as_ref().unwrap_or(self.parent_state),
+                t,
+            ));
+        }
+        PartialHeader {
+            parent: header_hash(self.parent),
+            height: self.parent.height() + 1,
+            state_root: state.as_ref().map_or_else(|| hash(self.parent_state), hash),
+            extrinsics_root: merkle_root(body),
+        }
+    }
+
+    fn seal(&self, header: PartialHeader) -> Option<Header<C::Digest>> {
+        self.consensus
+            .seal(self.parent.consensus_digest(), header.with_digest(()))
+    }
+}
+
+/// The fields of a header that the mutator changes, before it is sealed.
+#[derive(Clone)]
+struct PartialHeader {
+    parent: H256,
+    height: u64,
+    state_root: H256,
+    extrinsics_root: H256,
+}
+
+impl PartialHeader {
+    fn with_digest<Digest>(&self, digest: Digest) -> Header<Digest> {
+        Header::new(
+            self.parent,
+            self.height,
+            self.state_root,
+            self.extrinsics_root,
+            digest,
+        )
+    }
+}
+
+/// Something the harness found wrong with an importer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// The importer rejected the valid block, so nothing else it did means anything.
+    ValidRejected(ImportError),
+    /// The importer accepted a block that breaks the rule.
+    Accepted(Rule),
+    /// The importer rejected a block that breaks the rule, but for the wrong reason.
+    WrongError { rule: Rule, error: ImportError },
+    /// The rule can not be broken on its own with this engine and body, so it went untested.
+    Untestable(Rule),
+}
+
+/// Import a valid child with the given body, and then a child that breaks each rule in turn,
+/// each into a fresh importer from `importer`. Returns everything that did not go as it should.
+pub fn check_rules<C, SM, I>(
+    mutator: &Mutator<C, SM>,
+    body: Vec<SM::Transition>,
+    mut importer: impl FnMut() -> I,
+) -> Vec<Finding>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Hash,
+    SM::Transition: Clone + Hash,
+    I: TryImport<Block<C, SM>>,
+{
+    let Some(valid) = mutator.valid_child(body.clone()) else {
+        return Rule::ALL.into_iter().map(Finding::Untestable).collect();
+    };
+    if let Err(error) = importer().try_import(valid) {
+        return vec![Finding::ValidRejected(error)];
+    }
+
+    Rule::ALL
+        .into_iter()
+        .filter_map(|rule| {
+            let Some(block) = mutator.break_rule(rule, body.clone()) else {
+                return Some(Finding::Untestable(rule));
+            };
+            match importer().try_import(block) {
+                Ok(()) => Some(Finding::Accepted(rule)),
+                Err(error) if error == rule.error() => None,
+                Err(error) => Some(Finding::WrongError { rule, error }),
+            }
+        })
+        .collect()
+}
+
+/// A consensus engine whose seal is just the header's height, doubled. Unlike a real engine it
+/// is instant, but its seals still depend on the header they seal.
+#[cfg(test)]
+#[derive(Debug, Default, PartialEq)]
+struct Doubling;
+
+#[cfg(test)]
+impl Consensus for Doubling {
+    type Digest = u64;
+
+    fn validate(&self, _: &u64, header: &Header<u64>) -> bool {
+        *header.consensus_digest() == header.height() * 2
+    }
+
+    fn seal(&self, _: &u64, partial: Header<()>) -> Option<Header<u64>> {
+        Some(Header::new(
+            partial.parent(),
+            partial.height(),
+            partial.state_root(),
+            partial.extrinsics_root(),
+            partial.height() * 2,
+        ))
+    }
+}
+
+/// Adds up every number it is given.
+#[cfg(test)]
+#[derive(Debug, Default, PartialEq)]
+struct Sum;
+
+#[cfg(test)]
+impl StateMachine for Sum {
+    type State = u64;
+    type Transition = u64;
+
+    fn next_state(state: &u64, t: &u64) -> u64 {
+        state + t
+    }
+}
+
+/// An importer that knows a single parent block, and checks every rule, unless told to skip
+/// the state root.
+#[cfg(test)]
+struct TestImporter {
+    parent: Header<u64>,
+    parent_state: u64,
+    check_state_root: bool,
+}
+
+#[cfg(test)]
+impl TryImport<Block<Doubling, Sum>> for TestImporter {
+    fn try_import(&mut self, block: Block<Doubling, Sum>) -> Result<(), ImportError> {
+        let header = block.header();
+        if header.parent() != header_hash(&self.parent) {
+            return Err(ImportError::UnknownParent);
+        }
+        if header.height() != self.parent.height() + 1 {
+            return Err(ImportError::WrongHeight);
+        }
+        if !Doubling.validate(self.parent.consensus_digest(), header) {
+            return Err(ImportError::BadSeal);
+        }
+        if block.body().len() > 3 {
+            return Err(ImportError::BodyTooLarge);
+        }
+        if header.extrinsics_root() != merkle_root(block.body()) {
+            return Err(ImportError::ExtrinsicsRootMismatch);
+        }
+        let state: u64 = self.parent_state + block.body().iter().sum::<u64>();
+        if self.check_state_root && header.state_root() != hash(&state) {
+            return Err(ImportError::StateRootMismatch);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use super::{LongestChain, SimplePool};
+
+#[cfg(test)]
+fn test_parent() -> Header<u64> {
+    Header::new(H256::zero(), 4, hash(&10u64), merkle_root::<u64>(&[]), 8)
+}
+
+#[test]
+fn mutation_breaks_each_rule_alone() {
+    let parent = test_parent();
+    let mutator = Mutator::<Doubling, Sum>::new(&Doubling, &parent, &10, 3);
+    let valid = mutator.valid_child(vec![1, 2]).unwrap();
+
+    for rule in Rule::ALL {
+        let mutated = mutator.break_rule(rule, vec![1, 2]).unwrap();
+        assert_ne!(mutated, valid, "{rule:?}");
+        let seal_ok = Doubling.validate(&8, mutated.header());
+        assert_eq!(seal_ok, rule != Rule::Seal, "{rule:?}");
+    }
+    assert_eq!(
+        mutator
+            .break_rule(Rule::BodySize, vec![1, 2])
+            .unwrap()
+            .body(),
+        &[1, 2, 1, 2]
+    );
+    assert_eq!(mutator.break_rule(Rule::BodySize, vec![]), None);
+}
+
+#[test]
+fn mutation_passes_an_importer_that_checks_everything() {
+    let parent = test_parent();
+    let mutator = Mutator::<Doubling, Sum>::new(&Doubling, &parent, &10, 3);
+    let findings = check_rules(&mutator, vec![1, 2], || TestImporter {
+        parent: parent.clone(),
+        parent_state: 10,
+        check_state_root: true,
+    });
+
+    assert_eq!(findings, vec![]);
+}
+
+#[test]
+fn mutation_catches_an_unchecked_state_root() {
+    let parent = test_parent();
+    let mutator = Mutator::<Doubling, Sum>::new(&Doubling, &parent, &10, 3);
+    let findings = check_rules(&mutator, vec![1, 2], || TestImporter {
+        parent: parent.clone(),
+        parent_state: 10,
+        check_state_root: false,
+    });
+
+    assert_eq!(findings, vec![Finding::Accepted(Rule::StateRoot)]);
+}
+
+#[test]
+fn mutation_finds_the_full_client_has_no_body_size_limit() {
+    let genesis = Block::<Doubling, Sum>::genesis(&10);
+    let mutator = Mutator::<Doubling, Sum>::new(&Doubling, genesis.header(), &10, 3);
+    let findings = check_rules(&mutator, vec![1, 2], || {
+        FullClient::<Doubling, Sum, LongestChain, SimplePool<Sum>>::new(10)
+    });
+
+    assert_eq!(findings, vec![Finding::Accepted(Rule::BodySize)]);
+}
+
+/// An executive that limits how many extrinsics a block may hold.
+#[cfg(test)]
+struct MaxExtrinsics(usize);
+
+#[cfg(test)]
+impl super::executive::Executive<Doubling, Sum> for MaxExtrinsics {
+    fn check_body(&self, body: &[u64]) -> Result<(), ImportError> {
+        if body.len() > self.0 {
+            return Err(ImportError::BodyTooLarge);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn mutation_passes_the_full_client_with_a_body_size_limit() {
+    let genesis = Block::<Doubling, Sum>::genesis(&10);
+    let mutator = Mutator::<Doubling, Sum>::new(&Doubling, genesis.header(), &10, 3);
+    let findings = check_rules(&mutator, vec![1, 2], || {
+        FullClient::<Doubling, Sum, LongestChain, SimplePool<Sum>>::new(10)
+            .with_executive(MaxExtrinsics(3))
+    });
+
+    assert_eq!(findings, vec![]);
+}
@@ -0,0 +1,215 @@
+//! Before two nodes can sync, they have to find each other. On the open internet, that means
+//! somebody has to tell the node where its first peers are. On a local network, we can do
+//! better: every node periodically shouts "I am here" to everybody on the network, and listens
+//! for the others doing the same.
+//!
+//! This is the idea behind mDNS, which is what real nodes use for local discovery. Our version
+//! keeps the idea, and the multicast, but skips the DNS record format. Each announcement, or
+//! beacon, is a tiny bincode encoded message that says who the sender is, which chain it
+//! follows, and which port it accepts peers on.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::PeerId;
+use crate::hashing::H256;
+
+/// The multicast group that beacons are sent to. It lies in the range reserved for use within
+/// an organization, so beacons never leave the local network.
+pub const MULTICAST_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 70, 77);
+
+/// The UDP port that beacons are sent to.
+pub const DISCOVERY_PORT: u16 = 30334;
+
+/// How often a node sends its beacon.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a peer is remembered after its last beacon.
+pub const PEER_TTL: Duration = Duration::from_secs(30);
+
+/// Every beacon starts with these bytes, so that stray packets from other software on the same
+/// port are ignored.
+const MAGIC: &[u8; 4] = b"BFSD";
+
+/// What a node tells the local network about itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Beacon {
+    pub peer: PeerId,
+    /// The hash of the genesis block of the chain the node follows.
+    pub genesis: H256,
+    /// The port the node accepts peer connections on.
+    pub port: u16,
+}
+
+impl Beacon {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut packet = MAGIC.to_vec();
+        packet.extend(bincode::serialize(self).expect("beacons always serialize"));
+        packet
+    }
+
+    /// Decode a beacon, or return None if the packet is not a beacon at all.
+    pub fn decode(packet: &[u8]) -> Option<Self> {
+        let payload = packet.strip_prefix(MAGIC)?;
+        bincode::deserialize(payload).ok()
+    }
+}
+
+/// The peers that have been discovered so far.
+pub struct DiscoveredPeers {
+    /// Our own beacon. We hear our own announcements too, and must not connect to ourselves.
+    local: Beacon,
+    /// Where each peer can be reached, and when we last heard from them.
+    peers: BTreeMap<PeerId, (SocketAddr, Instant)>,
+}
+
+impl DiscoveredPeers {
+    pub fn new(local: Beacon) -> Self {
+        DiscoveredPeers {
+            local,
+            peers: BTreeMap::new(),
+        }
+    }
+
+    /// Handle a beacon that arrived from the given address. Returns the address to connect
+    /// to if this is a peer we did not know about yet.
+    pub fn on_beacon(
+        &mut self,
+        from: SocketAddr,
+        beacon: Beacon,
+        now: Instant,
+    ) -> Option<SocketAddr> {
+        if beacon.peer == self.local.peer || beacon.genesis != self.local.genesis {
+            return None;
+        }
+
+        let addr = SocketAddr::new(from.ip(), beacon.port);
+        let previous = self.peers.insert(beacon.peer, (addr, now));
+        match previous {
+            Some((previous_addr, _)) if previous_addr == addr => None,
+            _ => Some(addr),
+        }
+    }
+
+    /// Forget the peers that we have not heard from in a while.
+    pub fn expire(&mut self, now: Instant) {
+        self.peers
+            .retain(|_, (_, last_seen)| now.duration_since(*last_seen) < PEER_TTL);
+    }
+
+    /// Every peer we currently know about.
+    pub fn peers(&self) -> impl Iterator<Item = (PeerId, SocketAddr)> + '_ {
+        self.peers.iter().map(|(peer, (addr, _))| (*peer, *addr))
+    }
+}
+
+/// Local network discovery over UDP multicast.
+pub struct Discovery {
+    socket: UdpSocket,
+    known: DiscoveredPeers,
+}
+
+impl Discovery {
+    /// Start listening for beacons. Several nodes on the same machine can all do this at once.
+    pub fn bind(local: Beacon) -> io::Result<Self> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT).into())?;
+
+        let socket: UdpSocket = socket.into();
+        socket.join_multicast_v4(&MULTICAST_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+        // Loop our beacons back to ourselves, so that nodes on the same machine find each other.
+        socket.set_multicast_loop_v4(true)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Discovery {
+            socket,
+            known: DiscoveredPeers::new(local),
+        })
+    }
+
+    /// Tell the local network that we are here.
+    pub fn announce(&self) -> io::Result<()> {
+        let group = SocketAddrV4::new(MULTICAST_GROUP, DISCOVERY_PORT);
+        self.socket.send_to(&self.known.local.encode(), group)?;
+        Ok(())
+    }
+
+    /// Handle every beacon that has arrived since the last poll, and return the addresses of
+    /// newly discovered peers. This never blocks.
+    pub fn poll(&mut self, now: Instant) -> io::Result<Vec<SocketAddr>> {
+        let mut discovered = Vec::new();
+        let mut packet = [0; 512];
+        loop {
+            match self.socket.recv_from(&mut packet) {
+                Ok((len, from)) => {
+                    if let Some(beacon) = Beacon::decode(&packet[..len]) {
+                        discovered.extend(self.known.on_beacon(from, beacon, now));
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        self.known.expire(now);
+        Ok(discovered)
+    }
+
+    /// The peers discovered so far.
+    pub fn peers(&self) -> &DiscoveredPeers {
+        &self.known
+    }
+}
+
+#[cfg(test)]
+fn beacon(peer: u64, genesis: u64) -> Beacon {
+    Beacon {
+        peer: PeerId(peer),
+        genesis: H256::from(genesis),
+        port: 30333 + peer as u16,
+    }
+}
+
+#[test]
+fn discovery_beacon_round_trip() {
+    let original = beacon(3, 42);
+
+    assert_eq!(Beacon::decode(&original.encode()), Some(original));
+    assert_eq!(Beacon::decode(b"GET / HTTP/1.1"), None);
+}
+
+#[test]
+fn discovery_ignores_self_and_other_chains() {
+    let mut known = DiscoveredPeers::new(beacon(1, 42));
+    let from: SocketAddr = "192.168.1.20:30334".parse().unwrap();
+    let now = Instant::now();
+
+    assert_eq!(known.on_beacon(from, beacon(1, 42), now), None);
+    assert_eq!(known.on_beacon(from, beacon(2, 7), now), None);
+    assert_eq!(
+        known.on_beacon(from, beacon(2, 42), now),
+        Some("192.168.1.20:30335".parse().unwrap())
+    );
+    // Hearing from the same peer again is not news.
+    assert_eq!(known.on_beacon(from, beacon(2, 42), now), None);
+}
+
+#[test]
+fn discovery_forgets_silent_peers() {
+    let mut known = DiscoveredPeers::new(beacon(1, 42));
+    let from: SocketAddr = "10.0.0.2:30334".parse().unwrap();
+    let now = Instant::now();
+
+    known.on_beacon(from, beacon(2, 42), now);
+    known.expire(now + PEER_TTL / 2);
+    assert_eq!(known.peers().count(), 1);
+    known.expire(now + PEER_TTL);
+    assert_eq!(known.peers().count(), 0);
+}
@@ -0,0 +1,291 @@
+//! Once a node is in sync, it learns about new blocks through gossip. Whoever authors or imports
+//! a new block announces it to their peers, who in turn announce it to theirs. Left unchecked,
+//! this floods the network: every node hears about every block from every one of its peers, and
+//! would download it from all of them.
+//!
+//! To avoid that, we remember which blocks each peer already knows about, either because they
+//! announced it to us or because we announced it to them. We never announce a block to a peer
+//! that already knows it. And we only download a block from one peer at a time. If that peer
+//! does not deliver in time, we fall back to another peer that announced the same block.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use super::reputation::Offense;
+use super::sync::SyncRequest;
+use super::PeerId;
+use crate::hashing::H256;
+
+/// How many block hashes we remember per peer. The oldest ones are forgotten first.
+pub const MAX_KNOWN_BLOCKS: usize = 1024;
+
+/// How long a peer has to deliver a block we asked them for.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the gossip wants the networking layer to do next.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GossipAction {
+    /// Send a request to the given peer.
+    Request(PeerId, SyncRequest),
+    /// The given peer let us down.
+    Penalize(PeerId, Offense),
+}
+
+/// A bounded set of hashes that forgets its oldest entries first.
+#[derive(Default)]
+struct KnownBlocks {
+    set: HashSet<H256>,
+    order: VecDeque<H256>,
+}
+
+impl KnownBlocks {
+    fn contains(&self, hash: H256) -> bool {
+        self.set.contains(&hash)
+    }
+
+    fn insert(&mut self, hash: H256) {
+        if !self.set.insert(hash) {
+            return;
+        }
+        self.order.push_back(hash);
+        if self.order.len() > MAX_KNOWN_BLOCKS {
+            let oldest = self.order.pop_front().expect("the queue is not empty");
+            self.set.remove(&oldest);
+        }
+    }
+}
+
+/// A block we are downloading.
+struct InFlight {
+    /// The peer we asked.
+    peer: PeerId,
+    /// When we asked them.
+    since: Instant,
+    /// Other peers that announced the block, to fall back to, in the order they announced it.
+    fallbacks: VecDeque<PeerId>,
+}
+
+/// Keeps track of which peers know which blocks, and which blocks we are downloading from whom.
+#[derive(Default)]
+pub struct BlockGossip {
+    known: BTreeMap<PeerId, KnownBlocks>,
+    in_flight: BTreeMap<H256, InFlight>,
+}
+
+impl BlockGossip {
+    /// Create a gossip that knows no peers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the given peer is known to have the given block.
+    pub fn peer_knows(&self, peer: PeerId, hash: H256) -> bool {
+        self.known
+            .get(&peer)
+            .is_some_and(|known| known.contains(hash))
+    }
+
+    /// Whether we are currently downloading the given block.
+    pub fn is_requested(&self, hash: H256) -> bool {
+        self.in_flight.contains_key(&hash)
+    }
+
+    /// A peer announced a block at the given moment. `have_block` tells whether we already
+    /// imported it. Only the first announcement of a block we do not have results in a request.
+    pub fn on_announce(
+        &mut self,
+        peer: PeerId,
+        hash: H256,
+        have_block: bool,
+        now: Instant,
+    ) -> Vec<GossipAction> {
+        self.known.entry(peer).or_default().insert(hash);
+        if have_block {
+            return Vec::new();
+        }
+
+        if let Some(request) = self.in_flight.get_mut(&hash) {
+            if request.peer != peer && !request.fallbacks.contains(&peer) {
+                request.fallbacks.push_back(peer);
+            }
+            return Vec::new();
+        }
+
+        tracing::debug!(%peer, %hash, "requesting an announced block");
+        self.in_flight.insert(
+            hash,
+            InFlight {
+                peer,
+                since: now,
+                fallbacks: VecDeque::new(),
+            },
+        );
+        vec![request_block(peer, hash)]
+    }
+
+    /// A peer delivered a block. Returns whether we had asked them for it.
+    pub fn on_block(&mut self, peer: PeerId, hash: H256) -> bool {
+        self.known.entry(peer).or_default().insert(hash);
+        match self.in_flight.get(&hash) {
+            Some(request) if request.peer == peer => {
+                self.in_flight.remove(&hash);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Pick the peers, out of the given connected ones, that a block should be announced to,
+    /// and remember that they now know it.
+    pub fn announce_to(
+        &mut self,
+        hash: H256,
+        peers: impl IntoIterator<Item = PeerId>,
+    ) -> Vec<PeerId> {
+        // Now that we have the block, there is no point in downloading it any more.
+        self.in_flight.remove(&hash);
+
+        peers
+            .into_iter()
+            .filter(|&peer| {
+                let known = self.known.entry(peer).or_default();
+                let new = !known.contains(hash);
+                known.insert(hash);
+                new
+            })
+            .collect()
+    }
+
+    /// Move every request that has timed out by the given moment on to the next peer.
+    pub fn tick(&mut self, now: Instant) -> Vec<GossipAction> {
+        let mut actions = Vec::new();
+        let mut given_up = Vec::new();
+        for (&hash, request) in self.in_flight.iter_mut() {
+            if now.duration_since(request.since) < REQUEST_TIMEOUT {
+                continue;
+            }
+            tracing::warn!(peer = %request.peer, %hash, "block request timed out");
+            actions.push(GossipAction::Penalize(request.peer, Offense::Timeout));
+            match request.fallbacks.pop_front() {
+                Some(next) => {
+                    request.peer = next;
+                    request.since = now;
+                    actions.push(request_block(next, hash));
+                }
+                None => given_up.push(hash),
+            }
+        }
+        for hash in given_up {
+            self.in_flight.remove(&hash);
+        }
+        actions
+    }
+
+    /// Forget about a peer. Blocks we were downloading from them move on to the next peer.
+    pub fn peer_disconnected(&mut self, peer: PeerId, now: Instant) -> Vec<GossipAction> {
+        self.known.remove(&peer);
+
+        let mut actions = Vec::new();
+        self.in_flight.retain(|&hash, request| {
+            request.fallbacks.retain(|&p| p != peer);
+            if request.peer != peer {
+                return true;
+            }
+            match request.fallbacks.pop_front() {
+                Some(next) => {
+                    request.peer = next;
+                    request.since = now;
+                    actions.push(request_block(next, hash));
+                    true
+                }
+                None => false,
+            }
+        });
+        actions
+    }
+}
+
+fn request_block(peer: PeerId, hash: H256) -> GossipAction {
+    GossipAction::Request(peer, SyncRequest::Bodies { hashes: vec![hash] })
+}
+
+#[test]
+fn gossip_requests_each_block_once() {
+    let mut gossip = BlockGossip::new();
+    let now = Instant::now();
+
+    assert_eq!(
+        gossip.on_announce(PeerId(1), H256::from(7), false, now),
+        vec![request_block(PeerId(1), H256::from(7))]
+    );
+    // A second announcement of the same block does not lead to a second download.
+    assert!(gossip
+        .on_announce(PeerId(2), H256::from(7), false, now)
+        .is_empty());
+    // Nor does an announcement of a block we already have.
+    assert!(gossip
+        .on_announce(PeerId(2), H256::from(8), true, now)
+        .is_empty());
+
+    assert!(!gossip.on_block(PeerId(2), H256::from(7)));
+    assert!(gossip.is_requested(H256::from(7)));
+    assert!(gossip.on_block(PeerId(1), H256::from(7)));
+    assert!(!gossip.is_requested(H256::from(7)));
+}
+
+#[test]
+fn gossip_does_not_announce_known_blocks() {
+    let mut gossip = BlockGossip::new();
+    let now = Instant::now();
+    let peers = [PeerId(1), PeerId(2), PeerId(3)];
+
+    gossip.on_announce(PeerId(1), H256::from(7), false, now);
+    gossip.on_block(PeerId(1), H256::from(7));
+    assert_eq!(
+        gossip.announce_to(H256::from(7), peers),
+        vec![PeerId(2), PeerId(3)]
+    );
+    assert!(gossip.announce_to(H256::from(7), peers).is_empty());
+    assert!(gossip.peer_knows(PeerId(3), H256::from(7)));
+}
+
+#[test]
+fn gossip_falls_back_on_timeout() {
+    let mut gossip = BlockGossip::new();
+    let now = Instant::now();
+
+    gossip.on_announce(PeerId(1), H256::from(7), false, now);
+    gossip.on_announce(PeerId(2), H256::from(7), false, now);
+    assert!(gossip.tick(now + REQUEST_TIMEOUT / 2).is_empty());
+
+    let later = now + REQUEST_TIMEOUT;
+    assert_eq!(
+        gossip.tick(later),
+        vec![
+            GossipAction::Penalize(PeerId(1), Offense::Timeout),
+            request_block(PeerId(2), H256::from(7)),
+        ]
+    );
+    // The late block from the first peer is no longer what we are waiting for.
+    assert!(!gossip.on_block(PeerId(1), H256::from(7)));
+
+    // With nobody left to ask, we give up on the block.
+    assert_eq!(
+        gossip.tick(later + REQUEST_TIMEOUT),
+        vec![GossipAction::Penalize(PeerId(2), Offense::Timeout)]
+    );
+    assert!(!gossip.is_requested(H256::from(7)));
+}
+
+#[test]
+fn gossip_forgets_oldest_known_blocks() {
+    let mut gossip = BlockGossip::new();
+    let peer = PeerId(1);
+    gossip.announce_to(H256::from(0), [peer]);
+    for hash in 1..=MAX_KNOWN_BLOCKS as u64 {
+        gossip.announce_to(H256::from(hash), [peer]);
+    }
+
+    assert!(!gossip.peer_knows(peer, H256::from(0)));
+    assert!(gossip.peer_knows(peer, H256::from(1)));
+}
@@ -0,0 +1,134 @@
+//! Peers need a way to tell each other apart that survives restarts. Otherwise a peer that we
+//! banned could come back under a new name simply by restarting, and the logs of one run could
+//! not be matched up with those of the next.
+//!
+//! Every node therefore has an identity key. It is generated on first start, saved to the data
+//! directory, and loaded again on every later start. The peer id is derived from the public half
+//! of the key, and sent along in the handshake.
+//!
+//! Unlike the signatures in the keystore, we do not perform actual cryptography here. The
+//! public key is simply a hash of the secret key, and peers take each other's word for their ids.
+//! A real network would have each peer sign the handshake with their identity key, so that
+//! nobody can claim an id they do not own.
+
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::Path;
+
+use zeroize::Zeroize;
+
+use super::PeerId;
+use crate::c4_client::persist::write_atomically;
+use crate::crypto::Secret;
+use crate::hashing::hash_u64;
+
+/// The name of the file in the data directory that the identity key is saved to.
+pub const NODE_KEY_FILE: &str = "node_key";
+
+/// The secret identity key of a node.
+#[derive(Clone, PartialEq, Eq)]
+pub struct NodeKey {
+    secret: [u8; 32],
+}
+
+// The secret must never end up in the logs.
+impl std::fmt::Debug for NodeKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeKey")
+            .field("peer_id", &self.peer_id())
+            .finish_non_exhaustive()
+    }
+}
+
+// Wipe the secret, so that it does not linger in memory once the key is gone.
+impl Drop for NodeKey {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl NodeKey {
+    /// Generate a fresh key.
+    pub fn generate() -> Self {
+        // The standard library seeds the keys of its hash maps from the operating system's source
+        // of randomness. That is plenty for a toy key.
+        let mut secret = [0; 32];
+        for chunk in secret.chunks_mut(8) {
+            let random = RandomState::new().build_hasher().finish().to_le_bytes();
+            chunk.copy_from_slice(&random);
+        }
+        NodeKey { secret }
+    }
+
+    /// The public key that belongs to this secret key.
+    pub fn public(&self) -> u64 {
+        hash_u64(&("node identity", self.secret))
+    }
+
+    /// The peer id of the node that holds this key.
+    pub fn peer_id(&self) -> PeerId {
+        PeerId(self.public())
+    }
+
+    /// Encode the key as hex, the way it is saved to disk.
+    fn to_hex(&self) -> Secret<String> {
+        Secret::new(self.secret.iter().map(|b| format!("{b:02x}")).collect())
+    }
+
+    /// Decode a key that was saved with `to_hex`.
+    fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+        let mut secret = [0; 32];
+        for (i, byte) in secret.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+        }
+        Some(NodeKey { secret })
+    }
+}
+
+/// Load the identity key from the given data directory, generating and saving a new one if
+/// there is none yet.
+pub fn load_or_generate(data_dir: &Path) -> io::Result<NodeKey> {
+    let path = data_dir.join(NODE_KEY_FILE);
+    match fs::read_to_string(&path) {
+        Ok(hex) => NodeKey::from_hex(&Secret::new(hex)).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} does not hold a valid node key", path.display()),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let key = NodeKey::generate();
+            write_atomically(&path, key.to_hex().as_bytes())?;
+            Ok(key)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[test]
+fn identity_is_stable_across_restarts() {
+    let dir = std::env::temp_dir().join(format!("identity-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let first = load_or_generate(&dir).unwrap();
+    let second = load_or_generate(&dir).unwrap();
+    assert_eq!(first.peer_id(), second.peer_id());
+
+    fs::remove_dir_all(&dir).unwrap();
+    assert_ne!(load_or_generate(&dir).unwrap().peer_id(), first.peer_id());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn identity_rejects_corrupted_key_file() {
+    let key = NodeKey::generate();
+    assert_eq!(NodeKey::from_hex(&key.to_hex()), Some(key));
+    assert_eq!(NodeKey::from_hex("not a key"), None);
+    assert_eq!(NodeKey::from_hex(&"zz".repeat(32)), None);
+}
@@ -0,0 +1,304 @@
+//! Peers talk to each other by exchanging messages over a byte stream. This module defines the
+//! messages, and how they are turned into bytes and back.
+//!
+//! Every message travels as a frame: a four byte big-endian length, followed by that many bytes
+//! of bincode encoded message. The length prefix lets the receiver know where one message ends
+//! and the next begins, and lets it refuse absurdly large messages before reading them.
+//!
+//! The very first message on every connection is a handshake. It carries the protocol version,
+//! so that nodes running incompatible software notice right away, rather than failing in
+//! confusing ways later on. It also carries the sender's peer id, see the `identity` module.
+
+use std::io::{self, Read, Write};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::sync::SyncRequest;
+use super::{ChainHead, PeerId};
+use crate::hashing::H256;
+
+/// The version of the protocol spoken by this node. Bump it whenever the messages change in a
+/// way that older nodes would not understand.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// The oldest protocol version this node can still talk to. Version 1 handshakes did not carry
+/// a peer id, so they can not even be decoded any more.
+pub const MIN_SUPPORTED_VERSION: u32 = 2;
+
+/// The largest message we are willing to receive, in bytes.
+pub const MAX_MESSAGE_SIZE: u32 = 16 * 1024 * 1024;
+
+/// The first message on every connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    /// The protocol version spoken by the sender.
+    pub version: u32,
+    /// The stable identity of the sender.
+    pub peer: PeerId,
+    /// The hash of the sender's genesis block. Peers on different chains have nothing to say
+    /// to each other.
+    pub genesis: H256,
+    /// The sender's best block.
+    pub best: ChainHead,
+}
+
+/// Why a handshake was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandshakeError {
+    /// The peer speaks a protocol version that we do not support.
+    IncompatibleVersion { ours: u32, theirs: u32 },
+    /// The peer follows a different chain.
+    GenesisMismatch { ours: H256, theirs: H256 },
+    /// The peer has our own peer id. Most likely we connected to ourselves.
+    SelfConnection,
+}
+
+impl std::fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandshakeError::IncompatibleVersion { ours, theirs } => write!(
+                f,
+                "peer speaks protocol version {theirs}, but we speak version {ours} and support \
+                 versions {MIN_SUPPORTED_VERSION} and up"
+            ),
+            HandshakeError::GenesisMismatch { ours, theirs } => write!(
+                f,
+                "peer follows a chain with genesis {theirs}, but ours is {ours}"
+            ),
+            HandshakeError::SelfConnection => write!(f, "peer has our own peer id"),
+        }
+    }
+}
+
+impl Handshake {
+    /// Our own handshake, as the given peer, for the chain with the given genesis and best block.
+    pub fn new(peer: PeerId, genesis: H256, best: ChainHead) -> Self {
+        Handshake {
+            version: PROTOCOL_VERSION,
+            peer,
+            genesis,
+            best,
+        }
+    }
+
+    /// Check whether we can talk to the peer that sent the given handshake.
+    pub fn accept(&self, theirs: &Handshake) -> Result<(), HandshakeError> {
+        let newest_shared = self.version.min(theirs.version);
+        if newest_shared < MIN_SUPPORTED_VERSION {
+            return Err(HandshakeError::IncompatibleVersion {
+                ours: self.version,
+                theirs: theirs.version,
+            });
+        }
+        if self.genesis != theirs.genesis {
+            return Err(HandshakeError::GenesisMismatch {
+                ours: self.genesis,
+                theirs: theirs.genesis,
+            });
+        }
+        if self.peer == theirs.peer {
+            return Err(HandshakeError::SelfConnection);
+        }
+        Ok(())
+    }
+}
+
+/// The answer to a `SyncRequest`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockResponse<H, B> {
+    Headers(Vec<H>),
+    Bodies(Vec<B>),
+}
+
+/// Every message that peers exchange. Generic over the header, block and transaction types
+/// so that it works with any consensus engine and state machine.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetworkMessage<H, B, T> {
+    /// Sent once, as the first message on every connection.
+    Handshake(Handshake),
+    /// The sender has a new best block.
+    BlockAnnounce(B),
+    /// The sender would like some headers or blocks.
+    BlockRequest(SyncRequest),
+    /// The answer to the last request the receiver sent.
+    BlockResponse(BlockResponse<H, B>),
+    /// Transactions for the receiver's pool.
+    TxGossip(Vec<T>),
+}
+
+/// Everything that can go wrong while reading or writing messages.
+#[derive(Debug)]
+pub enum CodecError {
+    /// The underlying stream failed.
+    Io(io::Error),
+    /// The peer announced a message that is larger than we are willing to receive.
+    TooLarge { size: u32, max: u32 },
+    /// The bytes could not be decoded into a message. Peers that send these should be
+    /// penalized as having sent a malformed message.
+    Malformed(String),
+}
+
+impl From<io::Error> for CodecError {
+    fn from(e: io::Error) -> Self {
+        CodecError::Io(e)
+    }
+}
+
+/// Encode a message into a complete frame, including the length prefix.
+pub fn encode<M: Serialize>(message: &M) -> Result<Vec<u8>, CodecError> {
+    let payload = bincode::serialize(message).map_err(|e| CodecError::Malformed(e.to_string()))?;
+    let size = u32::try_from(payload.len())
+        .ok()
+        .filter(|&size| size <= MAX_MESSAGE_SIZE)
+        .ok_or(CodecError::TooLarge {
+            size: payload.len().try_into().unwrap_or(u32::MAX),
+            max: MAX_MESSAGE_SIZE,
+        })?;
+
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&size.to_be_bytes());
+    frame.extend_from_slice(&payload);
+    Ok(frame)
+}
+
+/// Try to decode a single frame from the start of the given buffer.
+///
+/// Returns the message and the number of bytes it took up, or None if the buffer does not yet
+/// hold a complete frame.
+pub fn decode<M: DeserializeOwned>(buffer: &[u8]) -> Result<Option<(M, usize)>, CodecError> {
+    let Some(prefix) = buffer.get(..4) else {
+        return Ok(None);
+    };
+    let size = u32::from_be_bytes(prefix.try_into().expect("prefix is four bytes long"));
+    if size > MAX_MESSAGE_SIZE {
+        return Err(CodecError::TooLarge {
+            size,
+            max: MAX_MESSAGE_SIZE,
+        });
+    }
+
+    let end = 4 + size as usize;
+    let Some(payload) = buffer.get(4..end) else {
+        return Ok(None);
+    };
+    let message =
+        bincode::deserialize(payload).map_err(|e| CodecError::Malformed(e.to_string()))?;
+    Ok(Some((message, end)))
+}
+
+/// Write a single message to the given stream.
+pub fn write_message<M: Serialize>(writer: &mut impl Write, message: &M) -> Result<(), CodecError> {
+    writer.write_all(&encode(message)?)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read a single message from the given stream, blocking until it has fully arrived.
+pub fn read_message<M: DeserializeOwned>(reader: &mut impl Read) -> Result<M, CodecError> {
+    let mut prefix = [0; 4];
+    reader.read_exact(&mut prefix)?;
+    let size = u32::from_be_bytes(prefix);
+    if size > MAX_MESSAGE_SIZE {
+        return Err(CodecError::TooLarge {
+            size,
+            max: MAX_MESSAGE_SIZE,
+        });
+    }
+
+    let mut payload = vec![0; size as usize];
+    reader.read_exact(&mut payload)?;
+    bincode::deserialize(&payload).map_err(|e| CodecError::Malformed(e.to_string()))
+}
+
+#[cfg(test)]
+type TestMessage = NetworkMessage<u64, (u64, Vec<u8>), String>;
+
+#[test]
+fn message_round_trip_through_stream() {
+    let messages: Vec<TestMessage> = vec![
+        NetworkMessage::Handshake(Handshake::new(
+            PeerId(3),
+            H256::from(1),
+            ChainHead {
+                hash: H256::from(5),
+                height: 2,
+            },
+        )),
+        NetworkMessage::BlockAnnounce((5, vec![1, 2, 3])),
+        NetworkMessage::BlockRequest(SyncRequest::Headers {
+            end: H256::from(5),
+            max: 10,
+        }),
+        NetworkMessage::BlockResponse(BlockResponse::Headers(vec![5, 4])),
+        NetworkMessage::TxGossip(vec!["transfer".into()]),
+    ];
+
+    let mut stream = Vec::new();
+    for message in &messages {
+        write_message(&mut stream, message).unwrap();
+    }
+    let mut reader = &stream[..];
+    for message in &messages {
+        assert_eq!(&read_message::<TestMessage>(&mut reader).unwrap(), message);
+    }
+    assert!(reader.is_empty());
+}
+
+#[test]
+fn message_decode_waits_for_complete_frame() {
+    let frame = encode(&TestMessage::TxGossip(vec!["a".into(), "b".into()])).unwrap();
+
+    assert!(decode::<TestMessage>(&frame[..3]).unwrap().is_none());
+    assert!(decode::<TestMessage>(&frame[..frame.len() - 1])
+        .unwrap()
+        .is_none());
+    let (_, used) = decode::<TestMessage>(&[&frame[..], &frame[..]].concat())
+        .unwrap()
+        .unwrap();
+    assert_eq!(used, frame.len());
+}
+
+#[test]
+fn message_rejects_oversized_and_malformed_frames() {
+    let oversized = (MAX_MESSAGE_SIZE + 1).to_be_bytes();
+    assert!(matches!(
+        decode::<TestMessage>(&oversized),
+        Err(CodecError::TooLarge { .. })
+    ));
+
+    let garbage = [0, 0, 0, 2, 0xff, 0xff];
+    assert!(matches!(
+        decode::<TestMessage>(&garbage),
+        Err(CodecError::Malformed(_))
+    ));
+}
+
+#[test]
+fn message_handshake_rejects_incompatible_peers() {
+    let best = ChainHead {
+        hash: H256::from(1),
+        height: 0,
+    };
+    let ours = Handshake::new(PeerId(1), H256::from(1), best);
+
+    assert_eq!(
+        ours.accept(&Handshake::new(PeerId(2), H256::from(1), best)),
+        Ok(())
+    );
+    assert_eq!(
+        ours.accept(&Handshake { version: 0, ..ours }),
+        Err(HandshakeError::IncompatibleVersion {
+            ours: PROTOCOL_VERSION,
+            theirs: 0
+        })
+    );
+    assert_eq!(
+        ours.accept(&Handshake::new(PeerId(2), H256::from(2), best)),
+        Err(HandshakeError::GenesisMismatch {
+            ours: H256::from(1),
+            theirs: H256::from(2)
+        })
+    );
+    assert_eq!(ours.accept(&ours), Err(HandshakeError::SelfConnection));
+}
@@ -0,0 +1,74 @@
+//! A single node following a blockchain on its own is not very interesting. The whole point
+//! of a blockchain is that many nodes, run by many different people, agree on the same history.
+//! To do that, nodes connect to one another as peers and exchange blocks and transactions.
+//!
+//! This module contains the networking logic of our node. Much like the consensus engines, the
+//! protocols here are written as plain data structures that react to events, rather than code
+//! that talks to sockets directly. This keeps them easy to reason about and to test.
+
+use serde::{Deserialize, Serialize};
+
+use super::{Block, Consensus, Header, StateMachine};
+use crate::hashing::{header_hash, H256};
+
+pub mod discovery;
+pub mod gossip;
+pub mod identity;
+pub mod message;
+pub mod reputation;
+pub mod sync;
+pub mod warp;
+
+/// Identifies a single peer on the network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PeerId(pub u64);
+
+impl std::fmt::Display for PeerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// A summary of the best block of some chain. This is what peers tell each other when they
+/// first connect, and whenever their best block changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChainHead {
+    pub hash: H256,
+    pub height: u64,
+}
+
+/// The few things the networking code needs to know about a header.
+pub trait ChainHeader {
+    fn hash(&self) -> H256;
+    fn parent_hash(&self) -> H256;
+    fn height(&self) -> u64;
+}
+
+impl<Digest: std::hash::Hash> ChainHeader for Header<Digest> {
+    fn hash(&self) -> H256 {
+        header_hash(self)
+    }
+
+    fn parent_hash(&self) -> H256 {
+        self.parent()
+    }
+
+    fn height(&self) -> u64 {
+        Header::height(self)
+    }
+}
+
+/// The few things the networking code needs to know about a block.
+pub trait ChainBlock {
+    type Header: ChainHeader;
+
+    fn header(&self) -> &Self::Header;
+}
+
+impl<C: Consensus, SM: StateMachine> ChainBlock for Block<C, SM> {
+    type Header = Header<C::Digest>;
+
+    fn header(&self) -> &Header<C::Digest> {
+        Block::header(self)
+    }
+}
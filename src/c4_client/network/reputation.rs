@@ -0,0 +1,232 @@
+//! Not every peer on the network is honest, and not every honest peer is reliable. To protect
+//! ourselves we keep a reputation score for every peer we talk to. Serving us invalid blocks,
+//! sending garbage, or simply not answering all cost reputation, while useful behaviour slowly
+//! earns it back.
+//!
+//! Once a peer's score drops to the ban threshold, we disconnect them and refuse to talk to them
+//! for a while. When the ban expires, they start over with a clean slate.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::sync::Misbehavior;
+use super::PeerId;
+
+/// A peer whose score drops to this value or below is banned.
+pub const BAN_THRESHOLD: i32 = -100;
+
+/// The highest score a peer can earn. Capping it keeps long-lived peers from building up
+/// enough credit to misbehave for a long time before being banned.
+pub const MAX_SCORE: i32 = 100;
+
+/// How long a ban lasts.
+pub const BAN_DURATION: Duration = Duration::from_secs(60);
+
+/// The things a peer can do that cost reputation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Offense {
+    /// The peer sent us a block that failed to import.
+    InvalidBlock,
+    /// The peer sent us a message that could not be decoded.
+    MalformedMessage,
+    /// The peer did not answer a request in time.
+    Timeout,
+    /// The peer misbehaved while we synced from them.
+    Sync(Misbehavior),
+}
+
+impl Offense {
+    /// How much reputation the offense costs.
+    ///
+    /// Anything that can only be explained by a malicious or badly broken peer costs enough to
+    /// get banned right away. Things that an honest peer on a bad connection might do are cheap.
+    pub fn cost(&self) -> i32 {
+        match self {
+            Offense::InvalidBlock => 100,
+            Offense::MalformedMessage => 50,
+            Offense::Timeout => 10,
+            Offense::Sync(Misbehavior::InvalidBlock | Misbehavior::UnknownGenesis) => 100,
+            Offense::Sync(Misbehavior::DisconnectedHeaders | Misbehavior::WrongBodies) => 50,
+            Offense::Sync(Misbehavior::UnrequestedResponse) => 20,
+            Offense::Sync(Misbehavior::EmptyResponse) => 10,
+        }
+    }
+}
+
+impl From<Misbehavior> for Offense {
+    fn from(misbehavior: Misbehavior) -> Self {
+        Offense::Sync(misbehavior)
+    }
+}
+
+/// What should happen to a peer after it has been reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The peer may stay connected.
+    Keep,
+    /// The peer must be disconnected, and is banned until the given moment.
+    Ban { until: Instant },
+}
+
+/// A snapshot of one peer's reputation, as exposed over RPC for debugging.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerScore {
+    pub peer: PeerId,
+    pub score: i32,
+    pub banned: bool,
+}
+
+/// The reputation of every peer we know about.
+#[derive(Default)]
+pub struct Reputation {
+    /// The current score of each peer. Peers that are not listed have a score of zero.
+    scores: BTreeMap<PeerId, i32>,
+    /// The peers that are currently banned, and when their ban expires.
+    bans: BTreeMap<PeerId, Instant>,
+}
+
+impl Reputation {
+    /// Create an empty reputation system in which every peer has a score of zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The current score of the given peer.
+    pub fn score(&self, peer: PeerId) -> i32 {
+        self.scores.get(&peer).copied().unwrap_or(0)
+    }
+
+    /// Whether the given peer is banned at the given moment. Expired bans are lifted here.
+    pub fn is_banned(&mut self, peer: PeerId, now: Instant) -> bool {
+        match self.bans.get(&peer) {
+            Some(&until) if until > now => true,
+            Some(_) => {
+                self.bans.remove(&peer);
+                self.scores.remove(&peer);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Report that the given peer committed an offense at the given moment.
+    pub fn report(&mut self, peer: PeerId, offense: impl Into<Offense>, now: Instant) -> Verdict {
+        if let Some(&until) = self.bans.get(&peer) {
+            if until > now {
+                return Verdict::Ban { until };
+            }
+            self.bans.remove(&peer);
+            self.scores.remove(&peer);
+        }
+
+        let score = self.scores.entry(peer).or_insert(0);
+        *score = score.saturating_sub(offense.into().cost());
+        if *score > BAN_THRESHOLD {
+            return Verdict::Keep;
+        }
+
+        let until = now + BAN_DURATION;
+        self.bans.insert(peer, until);
+        Verdict::Ban { until }
+    }
+
+    /// Reward the given peer for useful behaviour, such as serving us a valid block.
+    pub fn reward(&mut self, peer: PeerId, amount: i32) {
+        let score = self.scores.entry(peer).or_insert(0);
+        *score = score.saturating_add(amount).min(MAX_SCORE);
+    }
+
+    /// Forget about a peer entirely, unless they are banned. Bans outlive disconnects,
+    /// otherwise a peer could simply reconnect to get rid of them.
+    pub fn forget(&mut self, peer: PeerId) {
+        if !self.bans.contains_key(&peer) {
+            self.scores.remove(&peer);
+        }
+    }
+
+    /// A snapshot of every peer's reputation at the given moment.
+    pub fn scores(&self, now: Instant) -> Vec<PeerScore> {
+        let mut peers: Vec<PeerId> = self.scores.keys().copied().collect();
+        peers.extend(self.bans.keys().filter(|p| !self.scores.contains_key(p)));
+        peers.sort();
+
+        peers
+            .into_iter()
+            .map(|peer| PeerScore {
+                peer,
+                score: self.score(peer),
+                banned: self.bans.get(&peer).is_some_and(|&until| until > now),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn reputation_bans_below_threshold() {
+    let mut reputation = Reputation::new();
+    let now = Instant::now();
+    let peer = PeerId(1);
+
+    assert_eq!(
+        reputation.report(peer, Offense::MalformedMessage, now),
+        Verdict::Keep
+    );
+    assert_eq!(
+        reputation.report(peer, Offense::MalformedMessage, now),
+        Verdict::Ban {
+            until: now + BAN_DURATION
+        }
+    );
+    assert!(reputation.is_banned(peer, now));
+}
+
+#[test]
+fn reputation_bans_expire() {
+    let mut reputation = Reputation::new();
+    let now = Instant::now();
+    let peer = PeerId(2);
+
+    reputation.report(peer, Misbehavior::InvalidBlock, now);
+    assert!(reputation.is_banned(peer, now + BAN_DURATION / 2));
+    assert!(!reputation.is_banned(peer, now + BAN_DURATION));
+    assert_eq!(reputation.score(peer), 0);
+}
+
+#[test]
+fn reputation_rewards_are_capped() {
+    let mut reputation = Reputation::new();
+    let now = Instant::now();
+    let peer = PeerId(3);
+
+    reputation.reward(peer, 1000);
+    assert_eq!(reputation.score(peer), MAX_SCORE);
+
+    // Even a well-behaved peer is banned after enough invalid blocks.
+    reputation.report(peer, Offense::InvalidBlock, now);
+    assert!(matches!(
+        reputation.report(peer, Offense::InvalidBlock, now),
+        Verdict::Ban { .. }
+    ));
+}
+
+#[test]
+fn reputation_bans_survive_forget() {
+    let mut reputation = Reputation::new();
+    let now = Instant::now();
+
+    reputation.report(PeerId(4), Offense::InvalidBlock, now);
+    reputation.report(PeerId(5), Offense::Timeout, now);
+    reputation.forget(PeerId(4));
+    reputation.forget(PeerId(5));
+
+    assert_eq!(
+        reputation.scores(now),
+        vec![PeerScore {
+            peer: PeerId(4),
+            score: -100,
+            banned: true
+        }]
+    );
+}
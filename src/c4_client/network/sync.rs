@@ -0,0 +1,739 @@
+//! When a node first joins the network, or comes back online after some downtime, it is behind
+//! its peers. Catching up is known as syncing. Our sync protocol works in three phases.
+//!
+//! 1. When two peers connect, they tell each other about their best block.
+//! 2. If a peer knows a better chain than ours, we download that chain's headers from them,
+//!    walking backwards from their best block until we reach a block that we already know.
+//!    Downloading headers first is cheap, and lets us check that the chain is at least linked
+//!    together correctly before we bother with the much larger bodies.
+//! 3. We download the bodies for those headers, oldest first, and feed the complete blocks
+//!    through the client's normal import pipeline.
+//!
+//! A peer that serves us data that does not check out is aborted and penalized, and we move
+//! on to the next best peer.
+//!
+//! For simplicity, "better chain" means "higher best block" here. The client's fork choice rule
+//! still decides which of the imported chains is actually the best one.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use super::{ChainBlock, ChainHead, ChainHeader, PeerId};
+use crate::c4_client::rpc::Health;
+use crate::c4_client::{Block, Consensus, FullClient, ImportBlock, StateMachine};
+use crate::hashing::H256;
+
+/// The most headers we ask for in a single request.
+pub const MAX_HEADERS_PER_REQUEST: u64 = 128;
+
+/// The most bodies we ask for in a single request.
+pub const MAX_BODIES_PER_REQUEST: usize = 16;
+
+/// The local chain that the sync imports into.
+pub trait LocalChain<B> {
+    /// Whether the block with the given hash has already been imported.
+    fn is_known(&self, hash: H256) -> bool;
+
+    /// Import a block. Returns whether the block was valid and imported.
+    fn import(&mut self, block: B) -> bool;
+}
+
+impl<B, L: LocalChain<B> + ?Sized> LocalChain<B> for Box<L> {
+    fn is_known(&self, hash: H256) -> bool {
+        (**self).is_known(hash)
+    }
+
+    fn import(&mut self, block: B) -> bool {
+        (**self).import(block)
+    }
+}
+
+impl<C, SM, FC, P> LocalChain<Block<C, SM>> for FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    Self: ImportBlock<C, SM>,
+{
+    fn is_known(&self, hash: H256) -> bool {
+        self.get_block(hash).is_some()
+    }
+
+    fn import(&mut self, block: Block<C, SM>) -> bool {
+        self.import_block(block)
+    }
+}
+
+/// Something we would like to ask a peer for.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncRequest {
+    /// Up to `max` headers, starting at the block with hash `end` and walking towards genesis.
+    Headers { end: H256, max: u64 },
+    /// The complete blocks with the given hashes, in the given order.
+    Bodies { hashes: Vec<H256> },
+}
+
+/// The ways a peer can let us down while we sync from them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Misbehavior {
+    /// The peer sent a response we did not ask for.
+    UnrequestedResponse,
+    /// The peer claims a better chain but sent no headers for it.
+    EmptyResponse,
+    /// The headers do not link together, or do not lead to the requested block.
+    DisconnectedHeaders,
+    /// The headers lead all the way back to a genesis block other than ours.
+    UnknownGenesis,
+    /// The bodies do not match the blocks we asked for.
+    WrongBodies,
+    /// A block failed to import.
+    InvalidBlock,
+}
+
+/// What the sync wants the networking layer to do next.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncAction {
+    /// Send a request to the given peer.
+    Request(PeerId, SyncRequest),
+    /// The given peer misbehaved. Our current sync with them has been aborted.
+    Penalize(PeerId, Misbehavior),
+}
+
+enum Phase<H> {
+    /// We are not syncing from anybody.
+    Idle,
+    /// We are downloading headers from the given peer. The collected headers are newest first.
+    Headers {
+        peer: PeerId,
+        target: ChainHead,
+        collected: Vec<H>,
+    },
+    /// We are downloading bodies from the given peer. The remaining headers are oldest first.
+    Bodies {
+        peer: PeerId,
+        target: ChainHead,
+        remaining: VecDeque<H>,
+        requested: Vec<H256>,
+    },
+}
+
+/// The sync state machine. Generic over the header type so that it works with any
+/// consensus engine.
+pub struct ChainSync<H> {
+    /// Our own best block, as far as the sync knows.
+    best: ChainHead,
+    /// The best block of every connected peer.
+    peers: BTreeMap<PeerId, ChainHead>,
+    phase: Phase<H>,
+}
+
+impl<H: ChainHeader> ChainSync<H> {
+    /// Create a new sync that starts out at the given best block.
+    pub fn new(best: ChainHead) -> Self {
+        ChainSync {
+            best,
+            peers: BTreeMap::new(),
+            phase: Phase::Idle,
+        }
+    }
+
+    /// Our best block, as far as the sync knows.
+    pub fn best(&self) -> ChainHead {
+        self.best
+    }
+
+    /// Whether we are currently downloading from a peer.
+    pub fn is_syncing(&self) -> bool {
+        !matches!(self.phase, Phase::Idle)
+    }
+
+    /// The peer we are currently syncing from, if any.
+    pub fn syncing_from(&self) -> Option<PeerId> {
+        match self.phase {
+            Phase::Idle => None,
+            Phase::Headers { peer, .. } | Phase::Bodies { peer, .. } => Some(peer),
+        }
+    }
+
+    /// The highest best block reported by any connected peer.
+    pub fn best_peer_head(&self) -> Option<ChainHead> {
+        self.peers.values().copied().max_by_key(|head| head.height)
+    }
+
+    /// How far along we are, for the node's `system_health` RPC.
+    pub fn health(&self) -> Health {
+        let best_height = self.best.height;
+        Health {
+            peers: self.peers.len(),
+            is_syncing: self.is_syncing(),
+            best_height,
+            target_height: self
+                .best_peer_head()
+                .map_or(best_height, |head| head.height.max(best_height)),
+        }
+    }
+
+    /// Tell the sync that our best block changed for some other reason, for example because
+    /// we authored a block ourselves.
+    pub fn set_best(&mut self, best: ChainHead) {
+        self.best = best;
+    }
+
+    /// A peer connected, or told us about a new best block.
+    pub fn peer_head(&mut self, peer: PeerId, head: ChainHead) -> Vec<SyncAction> {
+        self.peers.insert(peer, head);
+        self.maybe_start()
+    }
+
+    /// A peer disconnected. If we were syncing from them, we move on to somebody else.
+    pub fn peer_disconnected(&mut self, peer: PeerId) -> Vec<SyncAction> {
+        self.peers.remove(&peer);
+        if self.syncing_from() == Some(peer) {
+            self.phase = Phase::Idle;
+        }
+        self.maybe_start()
+    }
+
+    /// A peer answered one of our header requests.
+    #[tracing::instrument(level = "debug", skip_all, fields(%peer, headers = headers.len()))]
+    pub fn on_headers<B>(
+        &mut self,
+        peer: PeerId,
+        headers: Vec<H>,
+        chain: &impl LocalChain<B>,
+    ) -> Vec<SyncAction> {
+        let Phase::Headers {
+            peer: expected_peer,
+            target,
+            mut collected,
+        } = std::mem::replace(&mut self.phase, Phase::Idle)
+        else {
+            return self.punish(peer, Misbehavior::UnrequestedResponse);
+        };
+        if peer != expected_peer {
+            // Put our actual sync back, and deal with the peer that spoke out of turn.
+            self.phase = Phase::Headers {
+                peer: expected_peer,
+                target,
+                collected,
+            };
+            return vec![SyncAction::Penalize(peer, Misbehavior::UnrequestedResponse)];
+        }
+        if headers.is_empty() {
+            return self.punish(peer, Misbehavior::EmptyResponse);
+        }
+
+        // The first new header must be the one we asked for, and each one after that
+        // must be the parent of the one before.
+        let (mut expected_hash, mut expected_height) = match collected.last() {
+            Some(oldest) => (oldest.parent_hash(), oldest.height().wrapping_sub(1)),
+            None => (target.hash, target.height),
+        };
+        let mut reached_known_block = false;
+        for header in headers {
+            if header.hash() != expected_hash || header.height() != expected_height {
+                return self.punish(peer, Misbehavior::DisconnectedHeaders);
+            }
+            if chain.is_known(header.hash()) {
+                reached_known_block = true;
+                break;
+            }
+            expected_hash = header.parent_hash();
+            expected_height = header.height().wrapping_sub(1);
+            collected.push(header);
+        }
+
+        let oldest = match collected.last() {
+            // Every header was already known. There is nothing left to download.
+            None => return self.finish(target),
+            Some(oldest) => oldest,
+        };
+        if reached_known_block || chain.is_known(oldest.parent_hash()) {
+            self.phase = Phase::Bodies {
+                peer,
+                target,
+                remaining: collected.into_iter().rev().collect(),
+                requested: Vec::new(),
+            };
+            return self.request_bodies();
+        }
+        if oldest.height() == 0 {
+            return self.punish(peer, Misbehavior::UnknownGenesis);
+        }
+
+        let end = oldest.parent_hash();
+        self.phase = Phase::Headers {
+            peer,
+            target,
+            collected,
+        };
+        vec![SyncAction::Request(
+            peer,
+            SyncRequest::Headers {
+                end,
+                max: MAX_HEADERS_PER_REQUEST,
+            },
+        )]
+    }
+
+    /// A peer answered one of our body requests. The blocks are imported right away.
+    #[tracing::instrument(level = "debug", skip_all, fields(%peer, bodies = blocks.len()))]
+    pub fn on_bodies<B: ChainBlock<Header = H>>(
+        &mut self,
+        peer: PeerId,
+        blocks: Vec<B>,
+        chain: &mut impl LocalChain<B>,
+    ) -> Vec<SyncAction> {
+        let Phase::Bodies {
+            peer: expected_peer,
+            target,
+            mut remaining,
+            requested,
+        } = std::mem::replace(&mut self.phase, Phase::Idle)
+        else {
+            return self.punish(peer, Misbehavior::UnrequestedResponse);
+        };
+        if peer != expected_peer {
+            self.phase = Phase::Bodies {
+                peer: expected_peer,
+                target,
+                remaining,
+                requested,
+            };
+            return vec![SyncAction::Penalize(peer, Misbehavior::UnrequestedResponse)];
+        }
+
+        let matches_request = blocks.len() == requested.len()
+            && blocks
+                .iter()
+                .zip(&requested)
+                .all(|(block, hash)| block.header().hash() == *hash);
+        if !matches_request {
+            return self.punish(peer, Misbehavior::WrongBodies);
+        }
+
+        for block in blocks {
+            if !chain.import(block) {
+                return self.punish(peer, Misbehavior::InvalidBlock);
+            }
+            remaining.pop_front();
+        }
+
+        self.phase = Phase::Bodies {
+            peer,
+            target,
+            remaining,
+            requested: Vec::new(),
+        };
+        self.request_bodies()
+    }
+
+    /// Start syncing from the peer with the best chain, if we are idle and anybody is ahead.
+    fn maybe_start(&mut self) -> Vec<SyncAction> {
+        if self.is_syncing() {
+            return Vec::new();
+        }
+        let Some((&peer, &target)) = self
+            .peers
+            .iter()
+            .filter(|(_, head)| head.height > self.best.height)
+            .max_by_key(|(_, head)| head.height)
+        else {
+            return Vec::new();
+        };
+
+        tracing::info!(%peer, hash = %target.hash, height = target.height, "syncing");
+        self.phase = Phase::Headers {
+            peer,
+            target,
+            collected: Vec::new(),
+        };
+        vec![SyncAction::Request(
+            peer,
+            SyncRequest::Headers {
+                end: target.hash,
+                max: MAX_HEADERS_PER_REQUEST,
+            },
+        )]
+    }
+
+    /// Ask for the next batch of bodies, or finish if there are none left.
+    fn request_bodies(&mut self) -> Vec<SyncAction> {
+        let Phase::Bodies {
+            peer,
+            target,
+            remaining,
+            requested,
+        } = &mut self.phase
+        else {
+            return Vec::new();
+        };
+        if remaining.is_empty() {
+            let target = *target;
+            return self.finish(target);
+        }
+
+        *requested = remaining
+            .iter()
+            .take(MAX_BODIES_PER_REQUEST)
+            .map(|header| header.hash())
+            .collect();
+        vec![SyncAction::Request(
+            *peer,
+            SyncRequest::Bodies {
+                hashes: requested.clone(),
+            },
+        )]
+    }
+
+    /// We have caught up to the given target.
+    fn finish(&mut self, target: ChainHead) -> Vec<SyncAction> {
+        tracing::info!(hash = %target.hash, height = target.height, "caught up");
+        self.phase = Phase::Idle;
+        if target.height > self.best.height {
+            self.best = target;
+        }
+        self.maybe_start()
+    }
+
+    /// Abort the current sync because the given peer misbehaved. We will not sync from that
+    /// peer again unless they reconnect.
+    fn punish(&mut self, peer: PeerId, misbehavior: Misbehavior) -> Vec<SyncAction> {
+        tracing::warn!(%peer, ?misbehavior, "aborted the sync");
+        self.phase = Phase::Idle;
+        self.peers.remove(&peer);
+
+        let mut actions = vec![SyncAction::Penalize(peer, misbehavior)];
+        actions.extend(self.maybe_start());
+        actions
+    }
+}
+
+#[cfg(test)]
+#[derive(Clone, Debug, PartialEq)]
+struct MockHeader {
+    hash: H256,
+    parent: H256,
+    height: u64,
+}
+
+#[cfg(test)]
+impl ChainHeader for MockHeader {
+    fn hash(&self) -> H256 {
+        self.hash
+    }
+
+    fn parent_hash(&self) -> H256 {
+        self.parent
+    }
+
+    fn height(&self) -> u64 {
+        self.height
+    }
+}
+
+#[cfg(test)]
+#[derive(Clone, Debug, PartialEq)]
+struct MockBlock {
+    header: MockHeader,
+    valid: bool,
+}
+
+#[cfg(test)]
+impl ChainBlock for MockBlock {
+    type Header = MockHeader;
+
+    fn header(&self) -> &MockHeader {
+        &self.header
+    }
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct MockChain {
+    known: std::collections::HashSet<H256>,
+}
+
+#[cfg(test)]
+impl LocalChain<MockBlock> for MockChain {
+    fn is_known(&self, hash: H256) -> bool {
+        self.known.contains(&hash)
+    }
+
+    fn import(&mut self, block: MockBlock) -> bool {
+        if !block.valid || !self.known.contains(&block.header.parent) {
+            return false;
+        }
+        self.known.insert(block.header.hash)
+    }
+}
+
+/// Build the blocks of a branch that starts right after genesis (whose hash is 1).
+/// Block hashes are `branch * 1000 + height` so that different branches never collide.
+#[cfg(test)]
+fn mock_branch(branch: u64, length: u64) -> Vec<MockBlock> {
+    (1..=length)
+        .map(|height| MockBlock {
+            header: MockHeader {
+                hash: H256::from(branch * 1000 + height),
+                parent: H256::from(if height == 1 {
+                    1
+                } else {
+                    branch * 1000 + height - 1
+                }),
+                height,
+            },
+            valid: true,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn genesis_only() -> (MockChain, ChainSync<MockHeader>) {
+    let chain = MockChain {
+        known: [H256::from(1)].into(),
+    };
+    (
+        chain,
+        ChainSync::new(ChainHead {
+            hash: 1.into(),
+            height: 0,
+        }),
+    )
+}
+
+#[cfg(test)]
+fn newest_first_headers(blocks: &[MockBlock]) -> Vec<MockHeader> {
+    blocks.iter().rev().map(|b| b.header.clone()).collect()
+}
+
+#[test]
+fn sync_requests_headers_from_better_peer_only() {
+    let (_, mut sync) = genesis_only();
+
+    assert!(sync
+        .peer_head(
+            PeerId(1),
+            ChainHead {
+                hash: 1.into(),
+                height: 0
+            }
+        )
+        .is_empty());
+    assert_eq!(
+        sync.peer_head(
+            PeerId(2),
+            ChainHead {
+                hash: 1003.into(),
+                height: 3
+            }
+        ),
+        vec![SyncAction::Request(
+            PeerId(2),
+            SyncRequest::Headers {
+                end: 1003.into(),
+                max: MAX_HEADERS_PER_REQUEST
+            }
+        )]
+    );
+    assert_eq!(sync.syncing_from(), Some(PeerId(2)));
+}
+
+#[test]
+fn sync_downloads_headers_then_bodies() {
+    let (mut chain, mut sync) = genesis_only();
+    let blocks = mock_branch(1, 3);
+    let peer = PeerId(7);
+
+    sync.peer_head(
+        peer,
+        ChainHead {
+            hash: 1003.into(),
+            height: 3,
+        },
+    );
+    let actions = sync.on_headers(peer, newest_first_headers(&blocks), &chain);
+    assert_eq!(
+        actions,
+        vec![SyncAction::Request(
+            peer,
+            SyncRequest::Bodies {
+                hashes: [1001, 1002, 1003].map(H256::from).to_vec()
+            }
+        )]
+    );
+
+    assert!(sync.on_bodies(peer, blocks, &mut chain).is_empty());
+    assert!(!sync.is_syncing());
+    assert_eq!(
+        sync.best(),
+        ChainHead {
+            hash: 1003.into(),
+            height: 3
+        }
+    );
+    assert!(chain.is_known(H256::from(1003)));
+}
+
+#[test]
+fn sync_only_downloads_the_unknown_part_of_a_fork() {
+    let (mut chain, mut sync) = genesis_only();
+    let ours = mock_branch(1, 2);
+    for block in ours.iter().cloned() {
+        chain.import(block);
+    }
+    sync.set_best(ChainHead {
+        hash: 1002.into(),
+        height: 2,
+    });
+
+    // The peer's chain shares our first block, then forks off.
+    let mut theirs = vec![ours[0].clone()];
+    for height in 2..=4 {
+        theirs.push(MockBlock {
+            header: MockHeader {
+                hash: H256::from(2000 + height),
+                parent: theirs.last().unwrap().header.hash,
+                height,
+            },
+            valid: true,
+        });
+    }
+
+    let peer = PeerId(3);
+    sync.peer_head(
+        peer,
+        ChainHead {
+            hash: 2004.into(),
+            height: 4,
+        },
+    );
+    let actions = sync.on_headers(peer, newest_first_headers(&theirs), &chain);
+    assert_eq!(
+        actions,
+        vec![SyncAction::Request(
+            peer,
+            SyncRequest::Bodies {
+                hashes: [2002, 2003, 2004].map(H256::from).to_vec()
+            }
+        )]
+    );
+}
+
+#[test]
+fn sync_penalizes_disconnected_headers() {
+    let (chain, mut sync) = genesis_only();
+    let mut headers = newest_first_headers(&mock_branch(1, 3));
+    headers.remove(1);
+
+    let peer = PeerId(1);
+    sync.peer_head(
+        peer,
+        ChainHead {
+            hash: 1003.into(),
+            height: 3,
+        },
+    );
+    assert_eq!(
+        sync.on_headers(peer, headers, &chain),
+        vec![SyncAction::Penalize(peer, Misbehavior::DisconnectedHeaders)]
+    );
+    assert!(!sync.is_syncing());
+}
+
+#[test]
+fn sync_penalizes_invalid_block_and_moves_to_next_peer() {
+    let (mut chain, mut sync) = genesis_only();
+    let mut blocks = mock_branch(1, 3);
+    blocks[1].valid = false;
+
+    let liar = PeerId(1);
+    let honest = PeerId(2);
+    sync.peer_head(
+        liar,
+        ChainHead {
+            hash: 1003.into(),
+            height: 3,
+        },
+    );
+    sync.peer_head(
+        honest,
+        ChainHead {
+            hash: 2002.into(),
+            height: 2,
+        },
+    );
+    sync.on_headers(liar, newest_first_headers(&blocks), &chain);
+
+    assert_eq!(
+        sync.on_bodies(liar, blocks, &mut chain),
+        vec![
+            SyncAction::Penalize(liar, Misbehavior::InvalidBlock),
+            SyncAction::Request(
+                honest,
+                SyncRequest::Headers {
+                    end: 2002.into(),
+                    max: MAX_HEADERS_PER_REQUEST
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn sync_penalizes_unrequested_responses() {
+    let (mut chain, mut sync) = genesis_only();
+
+    assert_eq!(
+        sync.on_bodies(PeerId(4), mock_branch(1, 1), &mut chain),
+        vec![SyncAction::Penalize(
+            PeerId(4),
+            Misbehavior::UnrequestedResponse
+        )]
+    );
+}
+
+#[test]
+fn sync_moves_on_when_peer_disconnects() {
+    let (_, mut sync) = genesis_only();
+    sync.peer_head(
+        PeerId(1),
+        ChainHead {
+            hash: 1005.into(),
+            height: 5,
+        },
+    );
+    sync.peer_head(
+        PeerId(2),
+        ChainHead {
+            hash: 2004.into(),
+            height: 4,
+        },
+    );
+    assert_eq!(sync.syncing_from(), Some(PeerId(1)));
+
+    let actions = sync.peer_disconnected(PeerId(1));
+    assert_eq!(sync.syncing_from(), Some(PeerId(2)));
+    assert_eq!(actions.len(), 1);
+}
+
+#[test]
+fn sync_health_tracks_target() {
+    let (_, mut sync) = genesis_only();
+    assert!(sync.health().is_ready());
+
+    sync.peer_head(
+        PeerId(1),
+        ChainHead {
+            hash: 1005.into(),
+            height: 5,
+        },
+    );
+    let health = sync.health();
+    assert_eq!(
+        (health.peers, health.best_height, health.target_height),
+        (1, 0, 5)
+    );
+    assert!(health.is_syncing);
+    assert!(!health.is_ready());
+}
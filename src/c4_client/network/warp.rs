@@ -0,0 +1,360 @@
+//! A full sync downloads and executes every block since genesis. That is the most trustless way
+//! to join a network, but it also takes longer and longer the older the chain gets. Warp sync
+//! is the shortcut.
+//!
+//! On a chain with finality, a set of authorities votes on which blocks are final. Whenever the
+//! set changes, the outgoing authorities sign off on the block that hands over to the incoming
+//! ones. Those sign-offs form a chain of their own: starting from the genesis authorities, which
+//! every node knows, each fragment of the proof tells us who the next authorities are, signed by
+//! a supermajority of the current ones. A new node
+//! 1. downloads these fragments and checks their signatures, up to the latest finalized block,
+//! 2. downloads the state of that block and checks it against the state root in its header,
+//! 3. and continues with an ordinary sync from there.
+//!
+//! The trust model is different from a full sync. A full sync trusts nothing but the genesis
+//! block and the rules of the state machine. A warp sync never executes a single block, so it
+//! trusts every authority set along the way to have only finalized valid blocks. If more than
+//! a third of any of those sets was dishonest, a warp syncing node can be fooled, while a fully
+//! syncing node would notice. This is the price for syncing in minutes rather than days.
+//!
+//! As in the rest of the client, the signatures are made with well-known development keys. See
+//! the keystore module.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::ChainHeader;
+use crate::c1_state_machine::User;
+use crate::c3_consensus::Header;
+use crate::c4_client::keystore::{Keystore, Signature};
+use crate::hashing::{hash, H256};
+
+/// A header that commits to the state after its block.
+pub trait StateRootHeader: ChainHeader {
+    fn state_root(&self) -> H256;
+}
+
+impl<Digest: std::hash::Hash> StateRootHeader for Header<Digest> {
+    fn state_root(&self) -> H256 {
+        Header::state_root(self)
+    }
+}
+
+/// One step of a warp proof: a finalized block at which the authority set changes, signed by
+/// the outgoing authorities.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WarpFragment<H> {
+    /// The finalized block.
+    pub header: H,
+    /// The authorities that take over after this block.
+    pub next_authorities: Vec<User>,
+    /// The signatures of the outgoing authorities over `fragment_payload`.
+    pub signatures: Vec<(User, Signature)>,
+}
+
+/// A chain of fragments, starting from the genesis authorities and ending at the latest
+/// finalized block. Fragments are in ascending order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WarpProof<H> {
+    pub fragments: Vec<WarpFragment<H>>,
+}
+
+/// What a warp syncing node asks its peers for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WarpRequest {
+    /// The warp proof from genesis up to the latest finalized block.
+    Proof,
+    /// The state after the block with the given hash.
+    State { at: H256 },
+}
+
+/// Why a warp proof or state snapshot was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarpError {
+    /// The proof holds no fragments at all.
+    EmptyProof,
+    /// A fragment is not higher than the one before it.
+    NotAscending { height: u64 },
+    /// Too few of the current authorities signed the fragment at the given height.
+    InsufficientSignatures {
+        height: u64,
+        signed: usize,
+        required: usize,
+    },
+    /// The state does not match the state root of the finalized header.
+    StateMismatch { expected: H256, actual: H256 },
+    /// A response arrived that we did not ask for.
+    Unexpected,
+}
+
+/// The hash that authorities sign to hand over to the next authority set.
+pub fn fragment_payload(block_hash: H256, next_authorities: &[User]) -> H256 {
+    hash(&("warp", block_hash, next_authorities))
+}
+
+/// The number of signatures needed out of a set of the given size: strictly more than two
+/// thirds, as in BFT finality gadgets.
+pub fn supermajority(set_size: usize) -> usize {
+    set_size * 2 / 3 + 1
+}
+
+/// Sign the hand over to `next_authorities` at the given header, with every key of the given
+/// authorities that the keystore holds.
+pub fn sign_fragment<H: ChainHeader>(
+    keystore: &Keystore,
+    authorities: &[User],
+    header: H,
+    next_authorities: Vec<User>,
+) -> WarpFragment<H> {
+    let payload = fragment_payload(header.hash(), &next_authorities);
+    let signatures = authorities
+        .iter()
+        .filter_map(|&who| keystore.sign(who, payload).map(|s| (who, s)))
+        .collect();
+    WarpFragment {
+        header,
+        next_authorities,
+        signatures,
+    }
+}
+
+/// The outcome of a verified warp proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WarpTarget<H> {
+    /// The latest finalized header.
+    pub header: H,
+    /// The authorities in charge after that header.
+    pub authorities: Vec<User>,
+}
+
+/// Check the given proof, starting from the given genesis authorities.
+pub fn verify_warp_proof<H: ChainHeader>(
+    genesis_authorities: &[User],
+    proof: WarpProof<H>,
+) -> Result<WarpTarget<H>, WarpError> {
+    let mut authorities = genesis_authorities.to_vec();
+    let mut last: Option<H> = None;
+    for fragment in proof.fragments {
+        let height = fragment.header.height();
+        if last.as_ref().is_some_and(|last| last.height() >= height) {
+            return Err(WarpError::NotAscending { height });
+        }
+
+        let payload = fragment_payload(fragment.header.hash(), &fragment.next_authorities);
+        // An authority that signed twice still only counts once.
+        let signers: BTreeSet<User> = fragment
+            .signatures
+            .iter()
+            .filter(|(who, signature)| authorities.contains(who) && signature.verify(*who, payload))
+            .map(|(who, _)| *who)
+            .collect();
+        let required = supermajority(authorities.len());
+        if signers.len() < required {
+            return Err(WarpError::InsufficientSignatures {
+                height,
+                signed: signers.len(),
+                required,
+            });
+        }
+
+        authorities = fragment.next_authorities;
+        last = Some(fragment.header);
+    }
+
+    let header = last.ok_or(WarpError::EmptyProof)?;
+    Ok(WarpTarget {
+        header,
+        authorities,
+    })
+}
+
+/// Check that the given state is the state after the given header.
+pub fn verify_state<H: StateRootHeader, S: std::hash::Hash>(
+    header: &H,
+    state: &S,
+) -> Result<(), WarpError> {
+    let actual = hash(state);
+    if actual != header.state_root() {
+        return Err(WarpError::StateMismatch {
+            expected: header.state_root(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+enum Phase<H> {
+    /// We are waiting for the warp proof.
+    Proof,
+    /// We have verified the proof, and are waiting for the state of its target.
+    State(WarpTarget<H>),
+    /// We are done.
+    Done,
+}
+
+/// Drives a warp sync: first the proof, then the state. Like the other protocols, it only
+/// decides what to ask for next and checks the answers. Sending them is up to the caller.
+pub struct WarpSync<H> {
+    genesis_authorities: Vec<User>,
+    phase: Phase<H>,
+}
+
+impl<H: StateRootHeader> WarpSync<H> {
+    /// Start a warp sync of the chain with the given genesis authorities.
+    pub fn new(genesis_authorities: Vec<User>) -> Self {
+        WarpSync {
+            genesis_authorities,
+            phase: Phase::Proof,
+        }
+    }
+
+    /// What to ask a peer for next, or None once the sync is complete. If a peer's answer was
+    /// rejected, ask the next peer for the same thing.
+    pub fn next_request(&self) -> Option<WarpRequest> {
+        match &self.phase {
+            Phase::Proof => Some(WarpRequest::Proof),
+            Phase::State(target) => Some(WarpRequest::State {
+                at: target.header.hash(),
+            }),
+            Phase::Done => None,
+        }
+    }
+
+    /// A peer sent the warp proof.
+    pub fn on_proof(&mut self, proof: WarpProof<H>) -> Result<(), WarpError> {
+        if !matches!(self.phase, Phase::Proof) {
+            return Err(WarpError::Unexpected);
+        }
+        self.phase = Phase::State(verify_warp_proof(&self.genesis_authorities, proof)?);
+        Ok(())
+    }
+
+    /// A peer sent the state. On success, returns the finalized header to continue an ordinary
+    /// sync from, along with the authorities that are in charge after it.
+    pub fn on_state<S: std::hash::Hash>(&mut self, state: &S) -> Result<WarpTarget<H>, WarpError> {
+        let Phase::State(target) = &self.phase else {
+            return Err(WarpError::Unexpected);
+        };
+        verify_state(&target.header, state)?;
+        match std::mem::replace(&mut self.phase, Phase::Done) {
+            Phase::State(target) => Ok(target),
+            _ => unreachable!("checked above"),
+        }
+    }
+}
+
+/// A chain of three fragments. The genesis authorities Alice, Bob and Charlie hand over to
+/// Alice and Bob at height 10, who in turn hand over to Charlie alone at height 20, who
+/// finalizes height 30 with a state of `[30]`.
+#[cfg(test)]
+fn test_proof() -> WarpProof<Header<()>> {
+    let keystore = Keystore::dev();
+    let header = |height: u64| {
+        Header::new(
+            H256::from(height - 1),
+            height,
+            hash(&[height]),
+            H256::zero(),
+            (),
+        )
+    };
+    WarpProof {
+        fragments: vec![
+            sign_fragment(
+                &keystore,
+                &[User::Alice, User::Bob, User::Charlie],
+                header(10),
+                vec![User::Alice, User::Bob],
+            ),
+            sign_fragment(
+                &keystore,
+                &[User::Alice, User::Bob],
+                header(20),
+                vec![User::Charlie],
+            ),
+            sign_fragment(&keystore, &[User::Charlie], header(30), vec![User::Charlie]),
+        ],
+    }
+}
+
+#[cfg(test)]
+const GENESIS_AUTHORITIES: [User; 3] = [User::Alice, User::Bob, User::Charlie];
+
+#[test]
+fn warp_sync_verifies_proof_and_state() {
+    let mut sync = WarpSync::new(GENESIS_AUTHORITIES.to_vec());
+    assert_eq!(sync.next_request(), Some(WarpRequest::Proof));
+
+    let proof = test_proof();
+    let target_hash = proof.fragments[2].header.hash();
+    sync.on_proof(proof).unwrap();
+    assert_eq!(
+        sync.next_request(),
+        Some(WarpRequest::State { at: target_hash })
+    );
+
+    // A state that does not match the header is rejected, and we keep waiting for the right one.
+    assert!(matches!(
+        sync.on_state(&[31u64]),
+        Err(WarpError::StateMismatch { .. })
+    ));
+    let target = sync.on_state(&[30u64]).unwrap();
+    assert_eq!(target.header.height(), 30);
+    assert_eq!(target.authorities, vec![User::Charlie]);
+    assert_eq!(sync.next_request(), None);
+}
+
+#[test]
+fn warp_proof_needs_supermajority_of_current_set() {
+    let mut proof = test_proof();
+    // Two out of three genesis authorities are not enough.
+    // Nor does signing twice help.
+    let signatures = &mut proof.fragments[0].signatures;
+    signatures.pop();
+    signatures.push(signatures[0]);
+    assert_eq!(
+        verify_warp_proof(&GENESIS_AUTHORITIES, proof),
+        Err(WarpError::InsufficientSignatures {
+            height: 10,
+            signed: 2,
+            required: 3
+        })
+    );
+
+    // After the first hand over, Charlie is no longer an authority, so his signature on the
+    // second fragment does not count.
+    let mut proof = test_proof();
+    let charlie = sign_fragment(
+        &Keystore::dev(),
+        &[User::Charlie],
+        proof.fragments[1].header.clone(),
+        vec![User::Charlie],
+    );
+    proof.fragments[1].signatures = charlie.signatures;
+    assert!(matches!(
+        verify_warp_proof(&GENESIS_AUTHORITIES, proof),
+        Err(WarpError::InsufficientSignatures { height: 20, .. })
+    ));
+}
+
+#[test]
+fn warp_proof_rejects_bad_structure() {
+    let empty: WarpProof<Header<()>> = WarpProof { fragments: vec![] };
+    assert_eq!(
+        verify_warp_proof(&GENESIS_AUTHORITIES, empty),
+        Err(WarpError::EmptyProof)
+    );
+
+    let mut proof = test_proof();
+    proof.fragments.swap(0, 1);
+    assert!(verify_warp_proof(&GENESIS_AUTHORITIES, proof).is_err());
+
+    // Changing who takes over invalidates the signatures.
+    let mut proof = test_proof();
+    proof.fragments[0].next_authorities = vec![User::Bob];
+    assert!(matches!(
+        verify_warp_proof(&GENESIS_AUTHORITIES, proof),
+        Err(WarpError::InsufficientSignatures { height: 10, .. })
+    ));
+}
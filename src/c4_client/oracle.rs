@@ -0,0 +1,207 @@
+//! State machines can only see what is on chain. Plenty of applications need to know about the
+//! outside world though, like a lending protocol that needs the price of the coins it lends.
+//! An _oracle_ brings such facts on chain. Here, a whitelist of oracles report prices with
+//! signed extrinsics, and the runtime combines their reports into a single feed.
+//!
+//! Any single oracle might lie, or just have a bad source. So the feed is the median of the
+//! reports: as long as more than half of the oracles are honest, it lies between two honest
+//! reports. Reports also go stale. A report that is more than `FEED_TTL` blocks old no longer
+//! counts, and a feed without fresh reports has no price at all, rather than an outdated one.
+//!
+//! Oracles fetch their prices in an _offchain worker_: code that a node runs after importing a
+//! block, outside of the runtime. Unlike the runtime it may be nondeterministic and talk to the
+//! outside world, and it feeds what it learned back on chain as an ordinary signed extrinsic.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::keystore::{Keystore, Signature};
+use super::runtime::{Runtime, RuntimeState, SignedExtrinsic};
+use crate::c1_state_machine::{StateMachine, User};
+use crate::hashing::{hash_of, H256};
+
+/// For how many blocks a report counts towards the feed.
+pub const FEED_TTL: u64 = 10;
+
+/// An extrinsic that can be applied to a chain with a price oracle.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OracleExtrinsic {
+    /// An ordinary extrinsic.
+    Apply(SignedExtrinsic),
+    /// An oracle reports the price it saw at the given height.
+    Report {
+        oracle: User,
+        height: u64,
+        price: u64,
+        signature: Signature,
+    },
+}
+
+/// Calculate the hash that an oracle must sign to report a price.
+pub fn report_payload(oracle: User, height: u64, price: u64) -> H256 {
+    hash_of("price report", &(oracle, height, price))
+}
+
+/// A report that counts towards the feed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PriceReport {
+    pub height: u64,
+    pub price: u64,
+}
+
+/// The runtime state, along with the price feed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OracleState {
+    pub runtime: RuntimeState,
+    /// The oracles whose reports count.
+    pub oracles: BTreeSet<User>,
+    /// The latest report of every oracle, until it goes stale.
+    pub reports: BTreeMap<User, PriceReport>,
+    /// The median of the fresh reports, after the latest block.
+    pub price: Option<u64>,
+}
+
+impl OracleState {
+    /// A genesis state with the given runtime state and oracles.
+    pub fn genesis(runtime: RuntimeState, oracles: BTreeSet<User>) -> Self {
+        OracleState {
+            runtime,
+            oracles,
+            ..OracleState::default()
+        }
+    }
+
+    /// Execute the block at the given height on top of this state, then drop stale reports and
+    /// update the feed. Reports by oracles off the whitelist, with a bad signature, from the
+    /// future, or no newer than the oracle's last report, are invalid.
+    pub fn execute_block(&self, height: u64, extrinsics: &[OracleExtrinsic]) -> OracleState {
+        let mut state = self.clone();
+        for extrinsic in extrinsics {
+            match extrinsic {
+                OracleExtrinsic::Apply(t) => {
+                    state.runtime = Runtime::next_state(&state.runtime, t);
+                }
+                OracleExtrinsic::Report {
+                    oracle,
+                    height: reported_at,
+                    price,
+                    signature,
+                } => {
+                    let newer = state
+                        .reports
+                        .get(oracle)
+                        .is_none_or(|last| *reported_at > last.height);
+                    if state.oracles.contains(oracle)
+                        && *reported_at <= height
+                        && newer
+                        && signature.verify(*oracle, report_payload(*oracle, *reported_at, *price))
+                    {
+                        let report = PriceReport {
+                            height: *reported_at,
+                            price: *price,
+                        };
+                        state.reports.insert(*oracle, report);
+                    }
+                }
+            }
+        }
+
+        state
+            .reports
+            .retain(|_, report| height.saturating_sub(report.height) < FEED_TTL);
+        state.price = median(state.reports.values().map(|report| report.price).collect());
+        state
+    }
+}
+
+/// The median of the given prices. Of the two middle prices of an even number of them, the
+/// lower one.
+fn median(mut prices: Vec<u64>) -> Option<u64> {
+    prices.sort_unstable();
+    prices.get(prices.len().checked_sub(1)? / 2).copied()
+}
+
+/// The offchain worker of an oracle node, run after the block at the given height was
+/// imported. It fetches the current price with the given function, which may for example ask
+/// an exchange, and signs a report to submit to the pool. Returns None if the node does not
+/// hold the oracle's key, or the price could not be fetched.
+pub fn offchain_worker(
+    keystore: &Keystore,
+    oracle: User,
+    height: u64,
+    fetch_price: impl FnOnce() -> Option<u64>,
+) -> Option<OracleExtrinsic> {
+    if !keystore.contains(oracle) {
+        return None;
+    }
+    let price = fetch_price()?;
+    let signature = keystore.sign(oracle, report_payload(oracle, height, price))?;
+    Some(OracleExtrinsic::Report {
+        oracle,
+        height,
+        price,
+        signature,
+    })
+}
+
+#[cfg(test)]
+fn report(oracle: User, height: u64, price: u64) -> OracleExtrinsic {
+    offchain_worker(&Keystore::dev(), oracle, height, || Some(price)).unwrap()
+}
+
+/// Alice and Bob are oracles, Charlie is not.
+#[cfg(test)]
+fn genesis() -> OracleState {
+    OracleState::genesis(
+        RuntimeState::default(),
+        BTreeSet::from([User::Alice, User::Bob]),
+    )
+}
+
+#[test]
+fn oracle_feed_is_median_of_reports() {
+    assert_eq!(median(vec![]), None);
+    assert_eq!(median(vec![5, 1, 3]), Some(3));
+    assert_eq!(median(vec![4, 1, 3, 2]), Some(2));
+
+    let state =
+        genesis().execute_block(1, &[report(User::Alice, 1, 100), report(User::Bob, 1, 110)]);
+    assert_eq!(state.price, Some(100));
+    let state = state.execute_block(2, &[report(User::Alice, 2, 120)]);
+    assert_eq!(state.price, Some(110));
+}
+
+#[test]
+fn oracle_rejects_invalid_reports() {
+    let state = genesis().execute_block(5, &[report(User::Alice, 5, 100)]);
+
+    let outsider = report(User::Charlie, 5, 1);
+    let from_the_future = report(User::Bob, 7, 1);
+    let replayed = report(User::Alice, 5, 100);
+    let mut forged = report(User::Bob, 5, 100);
+    if let OracleExtrinsic::Report { price, .. } = &mut forged {
+        *price = 1;
+    }
+    for invalid in [outsider, from_the_future, replayed, forged] {
+        assert_eq!(state.execute_block(6, &[invalid]).reports, state.reports);
+    }
+}
+
+#[test]
+fn oracle_stale_reports_expire() {
+    let state = genesis().execute_block(1, &[report(User::Alice, 1, 100)]);
+    let state = state.execute_block(FEED_TTL, &[report(User::Bob, FEED_TTL, 200)]);
+    assert_eq!(state.price, Some(100));
+
+    let state = state.execute_block(FEED_TTL + 1, &[]);
+    assert_eq!(state.price, Some(200));
+    let state = state.execute_block(2 * FEED_TTL, &[]);
+    assert_eq!(state.price, None);
+}
+
+#[test]
+fn oracle_offchain_worker_needs_key_and_price() {
+    assert!(offchain_worker(&Keystore::new(), User::Alice, 1, || Some(1)).is_none());
+    assert!(offchain_worker(&Keystore::dev(), User::Alice, 1, || None).is_none());
+}
@@ -16,62 +16,148 @@
 //!
 //! This abstraction is the key idea behind blockchain _frameworks_ like Substrate or the Cosmos SDK.
 
-use super::{Consensus, ForkChoice, Header, StateMachine};
+use alloc::vec::Vec;
 
-use super::FullClient;
-type Hash = u64;
+use serde::{Deserialize, Serialize};
+
+use super::{Consensus, ForkChoice, Header, StateMachine, TransactionPool};
+
+use super::{FullClient, Hash};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c4_client/p1_data_structure.rs"]
+mod solution;
 
 impl<Digest> Header<Digest> {
-    /// Returns a new valid genesis header.
-    fn genesis(genesis_state_root: Hash) -> Self {
-        todo!("Exercise 1")
+    /// Returns a new valid genesis header. Its consensus digest is the default one, because
+    /// nobody seals genesis.
+    fn genesis(genesis_state_root: Hash) -> Self
+    where
+        Digest: Default,
+    {
+        exercise!("Exercise 1", solution::genesis_header(genesis_state_root))
     }
 
-    /// Create and return a valid child header.
-    fn child(&self, state_root: Hash, extrinsics_root: Hash) -> Self {
-        todo!("Exercise 2")
+    /// Create and return a valid child header, that is only missing its seal. Children refer to
+    /// their parent by its `crate::hashing::header_hash`. The consensus engine seals them.
+    fn child(&self, state_root: Hash, extrinsics_root: Hash) -> Header<()>
+    where
+        Digest: core::hash::Hash,
+    {
+        exercise!(
+            "Exercise 2",
+            solution::child_header(self, state_root, extrinsics_root)
+        )
     }
 
-    /// Verify a single child header.
-    fn verify_child(&self, child: &Self) -> bool {
-        todo!("Exercise 3")
+    /// Verify a single child header. The seal is up to the consensus engine.
+    fn verify_child(&self, child: &Self) -> bool
+    where
+        Digest: core::hash::Hash,
+    {
+        exercise!("Exercise 3", solution::verify_child(self, child))
     }
 
     /// Verify that all the given headers form a valid chain from this header to the tip.
-    fn verify_sub_chain(&self, chain: &[Self]) -> bool {
-        todo!("Exercise 4")
+    fn verify_sub_chain(&self, chain: &[Self]) -> bool
+    where
+        Digest: core::hash::Hash,
+    {
+        exercise!("Exercise 4", solution::verify_header_sub_chain(self, chain))
     }
 }
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "C::Digest: Serialize, SM::Transition: Serialize",
+    deserialize = "C::Digest: Deserialize<'de>, SM::Transition: Deserialize<'de>"
+))]
 pub struct Block<C: Consensus, SM: StateMachine> {
     header: Header<C::Digest>,
     body: Vec<SM::Transition>,
 }
 
 impl<C: Consensus, SM: StateMachine> Block<C, SM> {
+    /// Assemble a block from its parts, without checking that they fit together. The client
+    /// builds its blocks as children of their parents instead, but test harnesses need to make
+    /// up invalid blocks on purpose.
+    pub fn new(header: Header<C::Digest>, body: Vec<SM::Transition>) -> Self {
+        Block { header, body }
+    }
+
+    /// The header of this block.
+    pub fn header(&self) -> &Header<C::Digest> {
+        &self.header
+    }
+
+    /// The extrinsics in the body of this block.
+    pub fn body(&self) -> &[SM::Transition] {
+        &self.body
+    }
+
     /// Returns a new valid genesis block. By convention this block has no extrinsics.
-    pub fn genesis(genesis_state: &SM::State) -> Self {
-        todo!("Exercise 5")
+    /// The state root in its header is the `crate::hashing::hash` of the genesis state.
+    pub fn genesis(genesis_state: &SM::State) -> Self
+    where
+        C::Digest: Default,
+        SM::State: core::hash::Hash,
+    {
+        exercise!("Exercise 5", solution::genesis_block(genesis_state))
     }
 
-    /// Create and return a valid child block.
-    pub fn child(&self, pre_state: &SM::State, extrinsics: Vec<u8>) -> Self {
-        todo!("Exercise 6")
+    /// Create and return a valid child block, sealed by the given consensus engine.
+    /// The extrinsics root in its header is the `crate::merkle::merkle_root` of its body.
+    /// Returns None if the engine does not seal it.
+    pub fn child(
+        &self,
+        consensus_engine: &C,
+        pre_state: &SM::State,
+        extrinsics: Vec<SM::Transition>,
+    ) -> Option<Self>
+    where
+        SM::State: Clone + core::hash::Hash,
+        SM::Transition: core::hash::Hash,
+    {
+        exercise!(
+            "Exercise 6",
+            solution::child_block(self, consensus_engine, pre_state, extrinsics)
+        )
     }
 
-    /// Verify that all the given blocks form a valid chain from this block to the tip.
-    pub fn verify_sub_chain(&self, pre_state: &SM::State, chain: &[Self]) -> bool {
-        todo!("Exercise 7")
+    /// Verify that all the given blocks form a valid chain from this block to the tip, sealed
+    /// according to the given consensus engine.
+    pub fn verify_sub_chain(
+        &self,
+        consensus_engine: &C,
+        pre_state: &SM::State,
+        chain: &[Self],
+    ) -> bool
+    where
+        SM::State: Clone + core::hash::Hash,
+        SM::Transition: core::hash::Hash,
+    {
+        exercise!(
+            "Exercise 7",
+            solution::verify_block_sub_chain(self, consensus_engine, pre_state, chain)
+        )
     }
 }
 
 /// Create and return a block chain that is n blocks long starting from the given genesis state.
 /// The blocks should not contain any transactions.
 fn create_empty_chain<C: Consensus, SM: StateMachine>(
+    consensus_engine: &C,
     n: u64,
     genesis_state: &SM::State,
-) -> Vec<Block<C, SM>> {
-    todo!("Exercise 8")
+) -> Vec<Block<C, SM>>
+where
+    C::Digest: Default,
+    SM::State: Clone + core::hash::Hash,
+    SM::Transition: core::hash::Hash,
+{
+    exercise!(
+        "Exercise 8",
+        solution::create_empty_chain(consensus_engine, n, genesis_state)
+    )
 }
 
 // To wrap this section up, we will implement the first two simple methods on our client.
@@ -79,10 +165,15 @@ fn create_empty_chain<C: Consensus, SM: StateMachine>(
 // genesis block.
 impl<C, SM, FC, P> FullClient<C, SM, FC, P>
 where
-    SM: StateMachine,
+    C: Consensus + Default,
+    C::Digest: Default,
+    SM: StateMachine + Default,
+    SM::State: core::hash::Hash,
+    FC: ForkChoice<C> + Default,
+    P: TransactionPool<SM> + Default,
 {
-    fn new(genesis_state: SM::State) -> Self {
-        todo!("Exercise 9")
+    pub fn new(genesis_state: SM::State) -> Self {
+        exercise!("Exercise 9", solution::new(genesis_state))
     }
 }
 
@@ -92,13 +183,74 @@ where
 // default genesis state.
 impl<C, SM, FC, P> Default for FullClient<C, SM, FC, P>
 where
-    C: Consensus,
-    SM: StateMachine,
-    FC: ForkChoice<C>,
+    C: Consensus + Default,
+    C::Digest: Default,
+    SM: StateMachine + Default,
+    SM::State: Default + core::hash::Hash,
+    FC: ForkChoice<C> + Default,
+    P: TransactionPool<SM> + Default,
 {
     fn default() -> Self {
-        todo!("Exerise 10")
+        exercise!("Exerise 10", Self::new(SM::State::default()))
     }
 }
 
-//TODO tests
+/// A state machine for the tests of this chapter: the state is a number, and every transition
+/// adds to it. Adding zero leaves the state alone.
+#[cfg(test)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct Adder;
+
+#[cfg(test)]
+impl StateMachine for Adder {
+    type State = u64;
+    type Transition = u64;
+
+    fn next_state(starting_state: &u64, t: &u64) -> u64 {
+        starting_state.saturating_add(*t)
+    }
+}
+
+#[cfg(test)]
+use crate::hashing::{hash, header_hash};
+
+#[test]
+fn client_1_genesis_and_child_headers() {
+    let genesis = Header::<()>::genesis(hash(&0u64));
+    assert_eq!(genesis.height(), 0);
+    assert_eq!(genesis.state_root(), hash(&0u64));
+
+    let child = genesis.child(hash(&5u64), Hash::zero());
+    assert_eq!(child.parent(), header_hash(&genesis));
+    assert_eq!(child.height(), 1);
+    assert!(genesis.verify_child(&child));
+    assert!(!child.verify_child(&genesis));
+
+    let grandchild = child.child(hash(&5u64), Hash::zero());
+    assert!(genesis.verify_sub_chain(&[child.clone(), grandchild.clone()]));
+    assert!(!genesis.verify_sub_chain(&[grandchild]));
+}
+
+#[test]
+fn client_1_blocks_commit_to_state_and_body() {
+    let genesis = Block::<(), Adder>::genesis(&0);
+    let child = genesis.child(&(), &0, vec![2, 3]).unwrap();
+    assert_eq!(child.header().state_root(), hash(&5u64));
+    assert_eq!(child.body(), &[2, 3]);
+    assert!(genesis.verify_sub_chain(&(), &0, core::slice::from_ref(&child)));
+
+    // A block executed on top of the wrong state does not verify.
+    assert!(!genesis.verify_sub_chain(&(), &1, core::slice::from_ref(&child)));
+    // Neither does one whose body does not match its extrinsics root.
+    let tampered = Block::<(), Adder>::new(child.header().clone(), vec![5]);
+    assert!(!genesis.verify_sub_chain(&(), &0, &[tampered]));
+}
+
+#[test]
+fn client_1_empty_chain() {
+    let chain = create_empty_chain::<(), Adder>(&(), 4, &7);
+    assert_eq!(chain.len(), 4);
+    assert_eq!(chain[0], Block::genesis(&7));
+    assert!(chain[0].verify_sub_chain(&(), &7, &chain[1..]));
+    assert!(chain.iter().all(|block| block.body().is_empty()));
+}
@@ -1,7 +1,38 @@
 //! We being implementing our client with the most fundamental task, which is importing
 //! blocks and headers. Full clients import entire blocks while light clients only import headers.
 
-use super::{Block, Consensus, FullClient, StateMachine};
+use alloc::vec::Vec;
+
+use super::{Block, Consensus, ForkChoice, FullClient, Hash, StateMachine, TransactionPool};
+use crate::hashing::header_hash;
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c4_client/p2_importing_blocks.rs"]
+mod solution;
+
+/// Why a block was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    /// The parent is not a block we know, or we have pruned its state.
+    UnknownParent,
+    /// The height is not one more than the parent's.
+    WrongHeight,
+    /// The consensus engine does not accept the seal.
+    BadSeal,
+    /// The extrinsics root does not match the body.
+    ExtrinsicsRootMismatch,
+    /// The state root does not match the state after executing the body.
+    StateRootMismatch,
+    /// The body holds too many extrinsics.
+    BodyTooLarge,
+    /// The extrinsics of the body weigh more than a block may weigh.
+    Overweight,
+    /// The block was executed with another runtime version than the one that is active, or
+    /// with one that this node does not know.
+    WrongVersion,
+    /// The block is on a fork that does not include the finalized block.
+    Finalized,
+}
 
 /// A trait that represents the ability to import complete blocks of the chain.
 ///
@@ -14,64 +45,198 @@ pub trait ImportBlock<C: Consensus, SM: StateMachine> {
 
     /// Retrieve the full body of an imported block.
     /// Returns None if the block is not known.
-    fn get_block(&self, block_hash: u64) -> Option<Block<C, SM>>;
+    fn get_block(&self, block_hash: Hash) -> Option<Block<C, SM>>;
 
     /// Retrieve the state associated with a given block.
     /// Returns None if the block is not known.
-    fn get_state(&self, block_hash: u64) -> Option<SM::State>;
+    fn get_state(&self, block_hash: Hash) -> Option<SM::State>;
 
     /// Check whether a given block is a leaf (aka tip) of the chain.
     /// A leaf block has no known children.
     /// Returns None if the block is not known.
-    fn is_leaf(&self, block_hash: u64) -> Option<bool>;
+    fn is_leaf(&self, block_hash: Hash) -> Option<bool>;
 
     /// Get a list of all the leaf nodes in the chain.
-    fn all_leaves(&self) -> Vec<u64>;
+    fn all_leaves(&self) -> Vec<Hash>;
 }
 
 impl<C, SM, FC, P> ImportBlock<C, SM> for FullClient<C, SM, FC, P>
     where
     C: Consensus,
     SM: StateMachine,
+    SM::State: Clone + PartialEq + core::hash::Hash,
+    SM::Transition: Clone + PartialEq + core::hash::Hash,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
 {
-    fn import_block(&mut self, _: Block<C, SM>) -> bool {
-        todo!("Exercise 1")
+    // The span tags everything that happens during the import, in the consensus engine and the
+    // pool included, with the block it happened for.
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(hash = %header_hash(block.header()), height = block.header().height())
+    )]
+    fn import_block(&mut self, block: Block<C, SM>) -> bool {
+        exercise!("Exercise 1", solution::import_block(self, block))
     }
 
-    fn get_block(&self, block_hash: u64) -> Option<Block<C, SM>> {
-        todo!("Exercise 2")
+    fn get_block(&self, block_hash: Hash) -> Option<Block<C, SM>> {
+        exercise!("Exercise 2", solution::get_block(self, block_hash))
     }
 
-    fn get_state(&self, block_hash: u64) -> Option<<SM as StateMachine>::State> {
-        todo!("Exercise 3")
+    fn get_state(&self, block_hash: Hash) -> Option<<SM as StateMachine>::State> {
+        exercise!("Exercise 3", solution::get_state(self, block_hash))
     }
 
-    fn is_leaf(&self, block_hash: u64) -> Option<bool> {
-        todo!("Exercise 4")
+    fn is_leaf(&self, block_hash: Hash) -> Option<bool> {
+        exercise!("Exercise 4", solution::is_leaf(self, block_hash))
     }
 
-    fn all_leaves(&self) -> Vec<u64> {
-        todo!("Exercise 5")
+    fn all_leaves(&self) -> Vec<Hash> {
+        exercise!("Exercise 5", solution::all_leaves(self))
     }
 }
 
-// TODO Write these tests.
+impl<C, SM, FC, P> FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + PartialEq + core::hash::Hash,
+    SM::Transition: Clone + PartialEq + core::hash::Hash,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+{
+    /// Attempt to import a block, just like `import_block`, but say why the block was rejected.
+    /// Importing a block that is already known succeeds.
+    pub fn try_import_block(&mut self, block: Block<C, SM>) -> Result<(), ImportError> {
+        exercise!("Exercise 6", solution::try_import_block(self, block))
+    }
+}
 
-// Test ideas:
-// import valid block
-// import block with unknown parent
-// import block with invalid height
-// import block with invalid state root
-// import block with invalid transactions root
-// import block with invalid seal
+#[cfg(test)]
+use super::p1_data_structure::Adder;
+#[cfg(test)]
+use super::{Header, LongestChain, SimplePool};
+#[cfg(test)]
+use crate::hashing::hash;
+
+#[cfg(test)]
+type TestClient = FullClient<(), Adder, LongestChain, SimplePool<Adder>>;
+
+#[test]
+fn client_2_import_valid_and_invalid_blocks() {
+    let mut client = TestClient::new(0);
+    let genesis = Block::genesis(&0);
+    let child = genesis.child(&(), &0, vec![1, 2]).unwrap();
+    let child_hash = header_hash(child.header());
+    assert!(client.get_block(child_hash).is_none());
+
+    // A child of a block the client has never seen.
+    let orphan = child.child(&(), &3, vec![4]).unwrap();
+    assert!(!client.import_block(orphan));
+
+    // Blocks that lie about their height, their state or their body.
+    let header = child.header();
+    let lie = |height, state_root, extrinsics_root| {
+        let header = Header::new(header.parent(), height, state_root, extrinsics_root, ());
+        Block::new(header, vec![1, 2])
+    };
+    for invalid in [
+        lie(2, header.state_root(), header.extrinsics_root()),
+        lie(1, hash(&4u64), header.extrinsics_root()),
+        lie(1, header.state_root(), Hash::zero()),
+    ] {
+        assert!(!client.import_block(invalid));
+    }
+    assert_eq!(client.all_leaves(), vec![header_hash(genesis.header())]);
 
-// Try to get_block genesis block
-// Try to get_block an unknown block
-// Import a valid block then make sure you can get it
+    assert!(client.import_block(child.clone()));
+    assert_eq!(client.get_block(child_hash), Some(child));
+    assert_eq!(client.get_state(child_hash), Some(3));
+    assert_eq!(client.get_state(Hash::zero()), None);
+}
 
-// Check whether genesis is a leaf right away
-// Import a block then check whether genesis is a leaf
-// Import two blocks in a single chain and make sure the leaf statuses is right.
-// Import a forked chain and make sure both leaves' statuses are right.
+#[test]
+fn client_2_leaves_follow_imports() {
+    let mut client = TestClient::new(0);
+    let genesis = Block::genesis(&0);
+    let genesis_hash = header_hash(genesis.header());
+    assert_eq!(client.is_leaf(genesis_hash), Some(true));
+    assert_eq!(client.is_leaf(Hash::zero()), None);
+
+    let a1 = genesis.child(&(), &0, vec![1]).unwrap();
+    let a2 = a1.child(&(), &1, vec![1]).unwrap();
+    let b1 = genesis.child(&(), &0, vec![2]).unwrap();
+    let [a1_hash, a2_hash, b1_hash] = [&a1, &a2, &b1].map(|block| header_hash(block.header()));
+
+    assert!(client.import_block(a1));
+    assert_eq!(client.is_leaf(genesis_hash), Some(false));
+    assert_eq!(client.all_leaves(), vec![a1_hash]);
+
+    assert!(client.import_block(a2));
+    assert_eq!(client.is_leaf(a1_hash), Some(false));
+    assert_eq!(client.all_leaves(), vec![a2_hash]);
+
+    assert!(client.import_block(b1));
+    let mut leaves = client.all_leaves();
+    leaves.sort();
+    let mut expected = vec![a2_hash, b1_hash];
+    expected.sort();
+    assert_eq!(leaves, expected);
+}
+
+/// The extended tests of the `check` binary, which `cargo test` leaves out.
+#[cfg(all(test, feature = "check"))]
+mod check {
+    use super::*;
+
+    #[test]
+    fn client_2_check_importing_twice() {
+        let mut client = TestClient::new(0);
+        let child = Block::genesis(&0).child(&(), &0, vec![1]).unwrap();
+        assert!(client.import_block(child.clone()));
+        assert!(client.import_block(child.clone()));
+        assert_eq!(client.all_leaves(), vec![header_hash(child.header())]);
+    }
 
-// Same previous 4 scenarios except with the `all_leaves` method.
+    #[test]
+    fn client_2_check_states_follow_each_fork() {
+        let mut client = TestClient::new(0);
+        let genesis = Block::genesis(&0);
+        let a1 = genesis.child(&(), &0, vec![1, 2]).unwrap();
+        let a2 = a1.child(&(), &3, vec![4]).unwrap();
+        let b1 = genesis.child(&(), &0, vec![10]).unwrap();
+        let b2 = b1.child(&(), &10, vec![]).unwrap();
+        let hashes = [&a2, &b2].map(|block| header_hash(block.header()));
+        for block in [a1, a2, b1, b2] {
+            assert!(client.import_block(block));
+        }
+
+        assert_eq!(client.get_state(hashes[0]), Some(7));
+        assert_eq!(client.get_state(hashes[1]), Some(10));
+        let mut leaves = client.all_leaves();
+        leaves.sort();
+        let mut expected = hashes.to_vec();
+        expected.sort();
+        assert_eq!(leaves, expected);
+    }
+
+    #[test]
+    fn client_2_check_child_of_an_invalid_block() {
+        let mut client = TestClient::new(0);
+        let child = Block::<(), Adder>::genesis(&0).child(&(), &0, vec![1]).unwrap();
+        let header = child.header();
+        let lie = Header::new(
+            header.parent(),
+            1,
+            hash(&5u64),
+            header.extrinsics_root(),
+            (),
+        );
+        let invalid = Block::new(lie, vec![1]);
+        let grandchild = invalid.child(&(), &5, vec![1]).unwrap();
+
+        assert!(!client.import_block(invalid));
+        assert!(!client.import_block(grandchild));
+    }
+}
@@ -4,9 +4,13 @@
 //! The concepts are identical here, but now that we have a client tracking a proper block database,
 //! we can explore more advanced fork choice algorithms. In particular, we can now explore GHOST.
 
-use super::{Header, FullClient, Consensus};
+use super::{Header, FullClient, Consensus, Hash, StateMachine};
 use crate::c3_consensus::{Pow, SimplePoa, ConsensusAuthority};
 
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c4_client/p3_fork_choice.rs"]
+mod solution;
+
 /// A means for a blockchain client to decide which chain is best among the many
 /// that it potentially knows about.
 ///
@@ -17,88 +21,239 @@ use crate::c3_consensus::{Pow, SimplePoa, ConsensusAuthority};
 /// Others are more complex and associate additional logic with block import, like GHOST.
 pub trait ForkChoice<C: Consensus> {
     /// Return the hash of the best block currently known according to this fork choice rule.
-    fn best_block(&self, header: Header<C::Digest>) -> Option<u64>;
+    fn best_block(&self, header: Header<C::Digest>) -> Option<Hash>;
 
     /// Perform some bookkeeping activities when importing a new block.
     fn import_hook(&mut self, header: Header<C::Digest>);
 }
 
 /// The chain with the highest block height is the best
+#[derive(Default)]
 pub struct LongestChain {
     // You may add fields here if you need to.
+    #[cfg(feature = "solutions")]
+    tree: solution::Tree,
 }
 
+// In all of the fork choice rules, the header passed to `best_block` is the block to start
+// from. The best block is that block itself, or one of its descendants. None if the block
+// was never imported.
 impl<C: Consensus> ForkChoice<C> for LongestChain {
-    fn best_block(&self, header: Header<C::Digest>) -> Option<u64> {
-        todo!("Exercise 1")
+    fn best_block(&self, header: Header<C::Digest>) -> Option<Hash> {
+        exercise!("Exercise 1", solution::heaviest_chain(&self.tree, &header))
     }
 
     fn import_hook(&mut self, header: Header<C::Digest>) {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", solution::insert(&mut self.tree, &header, 1))
     }
 }
 
 /// The chain with the most accumulated proof of work is the best.
 /// This fork choice rule only makes sense with the PoW consensus engine
 /// and the generics reflect that.
+/// The work of a block is the difficulty its own hash would have met: `u64::MAX` divided by one
+/// more than the last eight bytes of its hash, see `crate::difficulty::meets_threshold`.
+#[derive(Default)]
 pub struct HeaviestChain {
     // You may add fields here if you need to.
+    #[cfg(feature = "solutions")]
+    tree: solution::Tree,
 }
 
 impl ForkChoice<Pow> for HeaviestChain {
-    fn best_block(&self, header: Header<u64>) -> Option<u64> {
-        todo!("Exercise 3")
+    fn best_block(&self, header: Header<u64>) -> Option<Hash> {
+        exercise!("Exercise 3", solution::heaviest_chain(&self.tree, &header))
     }
 
     fn import_hook(&mut self, header: Header<u64>) {
-        todo!("Exercise 4")
+        exercise!(
+            "Exercise 4",
+            solution::insert(&mut self.tree, &header, solution::work(&header))
+        )
     }
 }
 
 /// The chain with the most signatures from the Alice authority is the best.
 /// This fork choice rule only makes sense with the PoA consensus engine
 /// and the generics reflect that.
+#[derive(Default)]
 pub struct MostAliceSigs {
     // You may add fields here if you need to.
+    #[cfg(feature = "solutions")]
+    tree: solution::Tree,
 }
 
 impl ForkChoice<SimplePoa> for MostAliceSigs {
-    fn best_block(&self, header: Header<ConsensusAuthority>) -> Option<u64> {
-        todo!("Exercise 5")
+    fn best_block(&self, header: Header<ConsensusAuthority>) -> Option<Hash> {
+        exercise!("Exercise 5", solution::heaviest_chain(&self.tree, &header))
     }
 
     fn import_hook(&mut self, header: Header<ConsensusAuthority>) {
-        todo!("Exercise 6")
+        exercise!(
+            "Exercise 6",
+            solution::insert(&mut self.tree, &header, solution::alice_weight(&header))
+        )
     }
 }
 
 /// In the Greedy Heaviest Observed Subtree rule, the fork choice is iterative.
 /// You start from the genesis block, and at each fork, you choose the side of the fork
 /// that has the most accumulated proof of work on _all_ of its descendants.
+#[derive(Default)]
 pub struct Ghost {
     // You may add fields here if you need to.
+    #[cfg(feature = "solutions")]
+    tree: solution::Tree,
 }
 
 impl ForkChoice<Pow> for Ghost {
-    fn best_block(&self, header: Header<u64>) -> Option<u64> {
-        todo!("Exercise 7")
+    fn best_block(&self, header: Header<u64>) -> Option<Hash> {
+        exercise!("Exercise 7", solution::ghost(&self.tree, &header))
     }
 
     fn import_hook(&mut self, header: Header<u64>) {
-        todo!("Exercise 8")
+        exercise!(
+            "Exercise 8",
+            solution::insert(&mut self.tree, &header, solution::work(&header))
+        )
     }
 }
 
 // Finally, we will provide a convenience method directly on our client that simply calls
 // into the corresponding method on the ForkChoice rule. You may need to add some trait
 // bounds to make this work.
-impl<C, SM, FC, P> FullClient<C, SM, FC, P> {
+impl<C, SM, FC, P> FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    FC: ForkChoice<C>,
+{
     /// Return the hash of the best block currently known to the client
-    fn best_block(&self) -> u64 {
-        todo!("Exercise 9")
+    pub fn best_block(&self) -> Hash {
+        exercise!("Exercise 9", solution::best_block(self))
+    }
+}
+
+#[cfg(test)]
+use crate::difficulty::meets_threshold;
+#[cfg(test)]
+use crate::hashing::header_hash;
+
+/// A child of the given header. The salt tells siblings apart.
+#[cfg(test)]
+fn child<Digest: core::hash::Hash>(
+    parent: &Header<Digest>,
+    salt: u64,
+    digest: Digest,
+) -> Header<Digest> {
+    Header::new(
+        header_hash(parent),
+        parent.height() + 1,
+        Hash::from(salt),
+        Hash::zero(),
+        digest,
+    )
+}
+
+/// Mine a child of the given header. Light ones have a work of exactly 1, heavy ones at least 16.
+#[cfg(test)]
+fn mine(parent: &Header<u64>, salt: u64, heavy: bool) -> Header<u64> {
+    let threshold = if heavy { u64::MAX / 16 } else { 1 << 63 };
+    (0..)
+        .map(|nonce| child(parent, salt, nonce))
+        .find(|header| meets_threshold(header_hash(header), threshold) == heavy)
+        .unwrap()
+}
+
+#[cfg(test)]
+fn import_all<C: Consensus>(fork_choice: &mut impl ForkChoice<C>, headers: &[&Header<C::Digest>])
+where
+    C::Digest: Clone,
+{
+    for header in headers {
+        fork_choice.import_hook((*header).clone());
     }
 }
 
-//TODO lots of tests for all the algos.
-// Especially a subtle one in Ghost, where importing a new
-// header causes a re-org to a different header than the one that was imported.
+#[test]
+fn client_3_longest_chain() {
+    let genesis = Header::new(Hash::zero(), 0, Hash::zero(), Hash::zero(), ());
+    let a1 = child(&genesis, 1, ());
+    let a2 = child(&a1, 1, ());
+    let b1 = child(&genesis, 2, ());
+    let b2 = child(&b1, 2, ());
+    let b3 = child(&b2, 2, ());
+
+    let mut fork_choice = LongestChain::default();
+    import_all::<()>(&mut fork_choice, &[&genesis, &a1, &a2, &b1, &b2]);
+    let best = |root: &Header<()>| ForkChoice::<()>::best_block(&fork_choice, root.clone());
+    // On a tie, the chain that was there first stays the best.
+    assert_eq!(best(&genesis), Some(header_hash(&a2)));
+    assert_eq!(best(&b1), Some(header_hash(&b2)));
+    assert_eq!(best(&b3), None);
+
+    ForkChoice::<()>::import_hook(&mut fork_choice, b3.clone());
+    assert_eq!(
+        ForkChoice::<()>::best_block(&fork_choice, genesis),
+        Some(header_hash(&b3))
+    );
+}
+
+#[test]
+fn client_3_most_alice_sigs() {
+    use ConsensusAuthority::{Alice, Bob};
+    let genesis = Header::new(Hash::zero(), 0, Hash::zero(), Hash::zero(), Bob);
+    let a1 = child(&genesis, 1, Alice);
+    let b1 = child(&genesis, 2, Bob);
+    let b2 = child(&b1, 2, Bob);
+    let b3 = child(&b2, 2, Alice);
+
+    let mut fork_choice = MostAliceSigs::default();
+    import_all::<SimplePoa>(&mut fork_choice, &[&genesis, &b1, &b2, &a1]);
+    assert_eq!(
+        fork_choice.best_block(genesis.clone()),
+        Some(header_hash(&a1))
+    );
+
+    // As many of Alice's blocks, but a longer chain.
+    fork_choice.import_hook(b3.clone());
+    assert_eq!(fork_choice.best_block(genesis), Some(header_hash(&b3)));
+}
+
+#[test]
+fn client_3_heaviest_chain() {
+    let genesis = Header::new(Hash::zero(), 0, Hash::zero(), Hash::zero(), 0);
+    let a1 = mine(&genesis, 1, true);
+    let b1 = mine(&genesis, 2, false);
+    let b2 = mine(&b1, 2, false);
+
+    let mut fork_choice = HeaviestChain::default();
+    import_all::<Pow>(&mut fork_choice, &[&genesis, &b1, &b2, &a1]);
+    assert_eq!(fork_choice.best_block(genesis), Some(header_hash(&a1)));
+}
+
+#[test]
+fn client_3_ghost_counts_whole_subtrees() {
+    let genesis = Header::new(Hash::zero(), 0, Hash::zero(), Hash::zero(), 0);
+    let a1 = mine(&genesis, 1, false);
+    let a2 = mine(&a1, 1, false);
+    let b1 = mine(&genesis, 2, false);
+    let b2 = mine(&b1, 2, false);
+    let b2_sibling = mine(&b1, 3, false);
+
+    let mut ghost = Ghost::default();
+    let mut heaviest = HeaviestChain::default();
+    for header in [&genesis, &a1, &a2, &b1, &b2] {
+        ghost.import_hook(header.clone());
+        heaviest.import_hook(header.clone());
+    }
+    assert_eq!(ghost.best_block(genesis.clone()), Some(header_hash(&a2)));
+
+    // Importing a sibling of b2 makes b1's subtree the heaviest. That moves the best block to
+    // b2, which was there first, rather than to the block we just imported. The heaviest
+    // chain rule still sees two chains that weigh the same.
+    ghost.import_hook(b2_sibling.clone());
+    heaviest.import_hook(b2_sibling);
+    assert_eq!(ghost.best_block(genesis.clone()), Some(header_hash(&b2)));
+    assert_eq!(heaviest.best_block(genesis), Some(header_hash(&a2)));
+}
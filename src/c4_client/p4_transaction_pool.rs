@@ -7,11 +7,19 @@
 //! * Accepting transactions from users
 //! * Removing transactions that are included in blocks as they are imported
 //! * Making the current transactions available for a block authoring process
-//! * Re-queueing transactions from orphaned blocks when re-orgs happen (This one happens IRL; might not cover it in BFS; TBD)
+//! * Re-queueing transactions from orphaned blocks when re-orgs happen (see the `reorg` module)
+//!
+//! The pools in this section are deliberately simple. For a pool that runs a fee market, with
+//! eviction and replace-by-fee, see the `fee_pool` module.
+
+use alloc::collections::VecDeque;
+use core::marker::PhantomData;
 
-use std::{collections::VecDeque, marker::PhantomData};
+use super::{Consensus, FullClient, StateMachine};
 
-use super::{FullClient, StateMachine};
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c4_client/p4_transaction_pool.rs"]
+mod solution;
 
 /// An abstraction over the notion of transaction pool.
 pub trait TransactionPool<SM: StateMachine> {
@@ -41,48 +49,62 @@ pub trait TransactionPool<SM: StateMachine> {
 // These are basically wrappers around methods that the pool itself provides.
 impl<C, SM, FC, P> FullClient<C, SM, FC, P>           
     where
+    C: Consensus,
     SM: StateMachine,
+    P: TransactionPool<SM>,
 {
     /// Submit a transaction to the client's transaction pool to hopefully
     /// be included in a future block.
     pub fn submit_transaction(&mut self, t: SM::Transition) {
-        todo!("Exercise 1")
+        exercise!("Exercise 1", {
+            self.transaction_pool.try_insert(t);
+        })
     }
 
     /// Get the total number of transactions in the node's
     /// transaction pool.
     pub fn pool_size(&self) -> usize {
-        todo!("Exercise 2")
+        exercise!("Exercise 2", self.transaction_pool.size())
     }
 
     /// Check whether a a given transaction is in the client's transaction pool.
     pub fn pool_contains(&self, t: SM::Transition) -> bool {
-        todo!("Exercise 3")
+        exercise!("Exercise 3", self.transaction_pool.contains(t))
     }
 }
 
 /// A simple state machine that is just a first-in-first-out queue.
+/// It refuses to queue a transaction that is already queued.
 pub struct SimplePool<SM: StateMachine>(VecDeque<SM::Transition>);
 
-impl<SM: StateMachine> TransactionPool<SM> for SimplePool<SM> {
+impl<SM: StateMachine> Default for SimplePool<SM> {
+    fn default() -> Self {
+        SimplePool(VecDeque::new())
+    }
+}
+
+impl<SM: StateMachine> TransactionPool<SM> for SimplePool<SM>
+where
+    SM::Transition: PartialEq,
+{
     fn try_insert(&mut self, t: <SM as StateMachine>::Transition) -> bool {
-        todo!()
+        exercise!("Exercise 4", solution::push_new(&mut self.0, t))
     }
 
     fn remove(&mut self, t: <SM as StateMachine>::Transition) {
-        todo!()
+        exercise!("Exercise 4", self.0.retain(|queued| *queued != t))
     }
 
     fn size(&self) -> usize {
-        todo!()
+        exercise!("Exercise 4", self.0.len())
     }
 
     fn contains(&self, t: <SM as StateMachine>::Transition) -> bool {
-        todo!()
+        exercise!("Exercise 4", self.0.contains(&t))
     }
 
     fn next_from_pool(&mut self) -> Option<<SM as StateMachine>::Transition> {
-        todo!()
+        exercise!("Exercise 4", self.0.pop_front())
     }
 }
 
@@ -99,32 +121,55 @@ pub struct PriorityPool<T, P: Fn(T) -> u64> {
     /// The minimum priority that will be accepted. Any transaction with a
     /// priority below this value will be rejected.
     minimum_priority: u64,
-    ph_data: PhantomData<T>
+    ph_data: PhantomData<T>,
+    /// The queued transactions, along with their priorities, in the order they came in.
+    #[cfg(feature = "solutions")]
+    queued: alloc::vec::Vec<(u64, T)>,
 }
 
+impl<T, P: Fn(T) -> u64> PriorityPool<T, P> {
+    pub fn new(prioritizer: P, minimum_priority: u64) -> Self {
+        PriorityPool {
+            prioritizer,
+            minimum_priority,
+            ph_data: PhantomData,
+            #[cfg(feature = "solutions")]
+            queued: alloc::vec::Vec::new(),
+        }
+    }
+}
+
+// Transactions of the same priority come out in the order they went in.
 impl<SM, P> TransactionPool<SM> for PriorityPool<SM::Transition, P>
 where
     SM: StateMachine,
+    SM::Transition: Clone + PartialEq,
     P: Fn(SM::Transition) -> u64
 {
     fn try_insert(&mut self, t: <SM as StateMachine>::Transition) -> bool {
-        todo!()
+        exercise!("Exercise 5", solution::try_insert_prioritized(self, t))
     }
 
     fn remove(&mut self, t: <SM as StateMachine>::Transition) {
-        todo!()
+        exercise!("Exercise 5", self.queued.retain(|(_, queued)| *queued != t))
     }
 
     fn size(&self) -> usize {
-        todo!()
+        exercise!("Exercise 5", self.queued.len())
     }
 
     fn contains(&self, t: <SM as StateMachine>::Transition) -> bool {
-        todo!()
+        exercise!(
+            "Exercise 5",
+            self.queued.iter().any(|(_, queued)| *queued == t)
+        )
     }
 
     fn next_from_pool(&mut self) -> Option<<SM as StateMachine>::Transition> {
-        todo!()
+        exercise!(
+            "Exercise 5",
+            solution::take_highest_priority(&mut self.queued)
+        )
     }
 }
 
@@ -134,60 +179,110 @@ where
 pub struct CensoringPool<T, P: Fn(T) -> bool> {
     /// A means of determining whether a transaction may be from a terrorist
     might_be_terrorist: P,
-    ph_data: PhantomData<T>
+    ph_data: PhantomData<T>,
+    /// The queued transactions, first in first out.
+    #[cfg(feature = "solutions")]
+    queued: VecDeque<T>,
+}
+
+impl<T, P: Fn(T) -> bool> CensoringPool<T, P> {
+    pub fn new(might_be_terrorist: P) -> Self {
+        CensoringPool {
+            might_be_terrorist,
+            ph_data: PhantomData,
+            #[cfg(feature = "solutions")]
+            queued: VecDeque::new(),
+        }
+    }
 }
 
 impl<SM, P> TransactionPool<SM> for CensoringPool<SM::Transition, P>
 where
     SM: StateMachine,
+    SM::Transition: Clone + PartialEq,
     P: Fn(SM::Transition) -> bool
 {
     fn try_insert(&mut self, t: <SM as StateMachine>::Transition) -> bool {
-        todo!()
+        exercise!(
+            "Exercise 6",
+            !(self.might_be_terrorist)(t.clone()) && solution::push_new(&mut self.queued, t)
+        )
     }
 
     fn remove(&mut self, t: <SM as StateMachine>::Transition) {
-        todo!()
+        exercise!("Exercise 6", self.queued.retain(|queued| *queued != t))
     }
 
     fn size(&self) -> usize {
-        todo!()
+        exercise!("Exercise 6", self.queued.len())
     }
 
     fn contains(&self, t: <SM as StateMachine>::Transition) -> bool {
-        todo!()
+        exercise!("Exercise 6", self.queued.contains(&t))
     }
 
     fn next_from_pool(&mut self) -> Option<<SM as StateMachine>::Transition> {
-        todo!()
+        exercise!("Exercise 6", self.queued.pop_front())
     }
 }
 
-//TODO tests
-
-// #[test]
-// fn simple_pool_starts_empty() {
-//     let client = FullClient::<(), (), ()>::default();
-
-//     assert!(client.pool_contains(t));
-//     assert_eq!(client.next_from_pool(), None);
-
-// }
-
-// #[test]
-// fn simple_pool_submitting_transaction_works() {
-//     let mut client = FullClient::<(), (), ()>::default();
-
-//     let t = Transition::default();
-
-//     client.submit_transaction(t.clone());
-
-//     assert!(client.pool_contains(t));
-//     assert_eq!(client.next_from_pool(), t);
+#[cfg(test)]
+use super::p1_data_structure::Adder;
+#[cfg(test)]
+use super::{Block, ImportBlock, LongestChain};
+
+#[test]
+fn client_4_simple_pool_submitting_transaction_works() {
+    let mut client = FullClient::<(), Adder, LongestChain, SimplePool<Adder>>::default();
+    assert_eq!(client.pool_size(), 0);
+    assert!(!client.pool_contains(1));
+
+    client.submit_transaction(1);
+    client.submit_transaction(2);
+    client.submit_transaction(1);
+    assert!(client.pool_contains(1));
+    assert_eq!(client.pool_size(), 2);
+
+    let pool = &mut client.transaction_pool;
+    assert_eq!(pool.next_from_pool(), Some(1));
+    assert_eq!(pool.next_from_pool(), Some(2));
+    assert_eq!(pool.next_from_pool(), None);
+}
 
-// }
+#[test]
+fn client_4_imported_transactions_leave_the_pool() {
+    let mut client = FullClient::<(), Adder, LongestChain, SimplePool<Adder>>::default();
+    for t in [1, 2, 3] {
+        client.submit_transaction(t);
+    }
+    let block = Block::genesis(&0).child(&(), &0, vec![3, 1]).unwrap();
+    assert!(client.import_block(block));
+    assert_eq!(client.pool_size(), 1);
+    assert!(client.pool_contains(2));
+}
 
+#[test]
+fn client_4_priority_pool_serves_highest_priority_first() {
+    let mut pool = PriorityPool::new(|t: u64| t % 10, 2);
+    let pool: &mut dyn TransactionPool<Adder> = &mut pool;
+    assert!(!pool.try_insert(11));
+    for t in [2, 5, 15, 3] {
+        assert!(pool.try_insert(t));
+    }
+    assert!(!pool.try_insert(5));
 
+    pool.remove(3);
+    let order: alloc::vec::Vec<_> = core::iter::from_fn(|| pool.next_from_pool()).collect();
+    assert_eq!(order, [5, 15, 2]);
+}
 
-// More tests for block importing to make sure that transactions that are imported
-// to the chain are correctly removed from the pool.
\ No newline at end of file
+#[test]
+fn client_4_censoring_pool_refuses_suspects() {
+    let mut pool = CensoringPool::new(|t: u64| t == 13);
+    let pool: &mut dyn TransactionPool<Adder> = &mut pool;
+    assert!(!pool.try_insert(13));
+    assert!(pool.try_insert(7));
+    assert!(pool.contains(7));
+    assert!(!pool.contains(13));
+    assert_eq!(pool.size(), 1);
+}
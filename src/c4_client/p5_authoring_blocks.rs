@@ -1,24 +1,92 @@
 //! We are now ready to give out client the ability to author blocks.
 //! Clients that perform this task are usually known as "miners", "authors", or "authorities".
 
-use super::{FullClient, StateMachine};
+use alloc::vec::Vec;
+
+use super::{Consensus, ForkChoice, FullClient, Hash, StateMachine, TransactionPool};
+
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c4_client/p5_authoring_blocks.rs"]
+mod solution;
 
 // You may need to add trait bounds to make this work.
 impl<C, SM, FC, P> FullClient<C, SM, FC, P>
     where
+    C: Consensus,
     SM: StateMachine,
+    SM::State: Clone + PartialEq + core::hash::Hash,
+    SM::Transition: Clone + PartialEq + core::hash::Hash,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
 {
     /// Author a new block with the given transactions on top of the given parent
     /// and import the new block into the local database.
-    pub fn author_and_import_manual_block(&mut self, transactions: Vec<SM::Transition>, parent_hash: u64) {
-        todo!("Exercise 1")
+    pub fn author_and_import_manual_block(&mut self, transactions: Vec<SM::Transition>, parent_hash: Hash) {
+        exercise!(
+            "Exercise 1",
+            solution::author_and_import_manual_block(self, transactions, parent_hash)
+        )
     }
 
     /// Author a new block with the transactions from the pool on top of the "best" block
     /// and import the new block into the local database.
-    pub fn author_and_import_automatic_block(&self) {
-        todo!("Exercise 2")
+    ///
+    /// This involves pulling extrinsics from the pool, executing them against the best
+    /// block's state, and asking the consensus engine to seal the resulting header.
+    /// Real blocks have a limit on how much they may hold, which the client's executive
+    /// enforces, see the `executive` module. The first extrinsic that does not fit ends the
+    /// block, and it goes back into the pool along with every extrinsic after it, in the same
+    /// order. Extrinsics that would not change the state are left out of the block, and go
+    /// back into the pool too. Importing the block maintains the pool, which drops those that are still
+    /// invalid, see the `reorg` module. Returns the hash of the new block, or None if the
+    /// consensus engine did not let us seal a block right now (for example because it is not
+    /// our turn to author).
+    pub fn author_and_import_automatic_block(&mut self) -> Option<Hash> {
+        exercise!(
+            "Exercise 2",
+            solution::author_and_import_automatic_block(self)
+        )
     }
 }
 
-//TODO tests
\ No newline at end of file
+#[cfg(test)]
+use super::p1_data_structure::Adder;
+#[cfg(test)]
+use super::{ImportBlock, LongestChain, SimplePool};
+
+#[cfg(test)]
+type TestClient = FullClient<(), Adder, LongestChain, SimplePool<Adder>>;
+
+#[test]
+fn client_5_manual_blocks_build_forks() {
+    let mut client = TestClient::new(0);
+    let genesis = client.best_block();
+    client.author_and_import_manual_block(vec![1], genesis);
+    let a1 = client.best_block();
+    client.author_and_import_manual_block(vec![2], a1);
+    let a2 = client.best_block();
+    client.author_and_import_manual_block(vec![5], genesis);
+
+    assert_eq!(client.get_state(a2), Some(3));
+    assert_eq!(client.all_leaves().len(), 2);
+    assert_eq!(client.best_block(), a2);
+
+    // Blocks on unknown parents are not authored at all.
+    client.author_and_import_manual_block(vec![5], Hash::zero());
+    assert_eq!(client.all_leaves().len(), 2);
+}
+
+#[test]
+fn client_5_automatic_blocks_drain_the_pool() {
+    let mut client = TestClient::new(0);
+    for t in [1, 0, 2] {
+        client.submit_transaction(t);
+    }
+    let hash = client.author_and_import_automatic_block().unwrap();
+    assert_eq!(client.best_block(), hash);
+    assert_eq!(client.get_block(hash).unwrap().body(), &[1, 2]);
+    assert_eq!(client.get_state(hash), Some(3));
+
+    // Adding zero does nothing, so it is left out, and dropped when the pool is maintained.
+    assert_eq!(client.pool_size(), 0);
+}
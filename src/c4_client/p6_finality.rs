@@ -5,14 +5,97 @@
 //! Although we elide the details of the game itself, this model still allows us to explore
 //! the consequences of having some blocks that are never reverted.
 
-use super::FullClient;
+use super::storage::StorageMode;
+use super::{Consensus, FullClient, Hash, StateMachine};
 
-impl<C, SM, FC, P> FullClient<C, SM, FC, P> {
+#[cfg(feature = "solutions")]
+#[path = "../solutions/c4_client/p6_finality.rs"]
+mod solution;
+
+impl<C, SM, FC, P> FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+{
     /// Mark the given block as final so that it will never be reverted.
     /// Returns whether or not the block was known and marked successfully.
-    pub fn manually_finalize_block(&mut self, block_hash: u64) -> bool {
-        todo!("Exercise 1")
+    /// Only the finalized block itself and its descendants can be finalized. From then on, the
+    /// best block is the finalized block or one of its descendants, and blocks that do not
+    /// descend from it are not imported.
+    pub fn manually_finalize_block(&mut self, block_hash: Hash) -> bool {
+        exercise!(
+            "Exercise 1",
+            solution::manually_finalize_block(self, block_hash)
+        )
+    }
+
+    /// The hash of the most recently finalized block. Genesis is final from the start.
+    pub fn finalized_block(&self) -> Hash {
+        exercise!("Exercise 2", self.chain.finalized)
     }
+
+    /// Store states in the given mode, see the `storage` module. A pruned client keeps the
+    /// states of the `keep` most recent heights, and the state of the finalized block.
+    pub fn with_state_pruning(self, mode: StorageMode, keep: u64) -> Self {
+        exercise!("Exercise 3", solution::with_state_pruning(self, mode, keep))
+    }
+}
+
+#[cfg(test)]
+use super::p1_data_structure::Adder;
+#[cfg(test)]
+use super::{Block, ImportBlock, LongestChain, SimplePool};
+#[cfg(test)]
+use crate::hashing::header_hash;
+
+#[test]
+fn client_6_finality_prunes_other_forks() {
+    let mut client = FullClient::<(), Adder, LongestChain, SimplePool<Adder>>::new(0);
+    let genesis = Block::genesis(&0);
+    assert_eq!(client.finalized_block(), header_hash(genesis.header()));
+
+    let a1 = genesis.child(&(), &0, vec![1]).unwrap();
+    let b1 = genesis.child(&(), &0, vec![2]).unwrap();
+    let b2 = b1.child(&(), &2, vec![2]).unwrap();
+    let b3 = b2.child(&(), &4, vec![2]).unwrap();
+    let a2 = a1.child(&(), &1, vec![1]).unwrap();
+    let [a1_hash, b1_hash, b2_hash] = [&a1, &b1, &b2].map(|block| header_hash(block.header()));
+    assert!(client.import_block(a1));
+    assert!(client.import_block(b1));
+    assert!(client.import_block(b2));
+    assert!(!client.manually_finalize_block(Hash::zero()));
+
+    // The shorter fork is final, so the longer one is not the best chain anymore.
+    assert!(client.manually_finalize_block(a1_hash));
+    assert_eq!(client.finalized_block(), a1_hash);
+    assert_eq!(client.best_block(), a1_hash);
+
+    // Blocks that would revert it are neither finalized nor imported.
+    assert!(!client.manually_finalize_block(b1_hash));
+    assert!(!client.manually_finalize_block(b2_hash));
+    assert!(!client.import_block(b3));
+    assert!(client.import_block(a2));
 }
 
-//TODO tests
\ No newline at end of file
+#[test]
+fn client_6_finality_keeps_the_state_of_the_finalized_block() {
+    let mut client = FullClient::<(), Adder, LongestChain, SimplePool<Adder>>::new(0)
+        .with_state_pruning(StorageMode::Pruned, 2);
+    let mut block = Block::genesis(&0);
+    let mut hashes = vec![header_hash(block.header())];
+    for state in 0..5 {
+        block = block.child(&(), &state, vec![1]).unwrap();
+        hashes.push(header_hash(block.header()));
+        assert!(client.import_block(block.clone()));
+        if hashes.len() == 3 {
+            assert!(client.manually_finalize_block(hashes[2]));
+        }
+    }
+
+    // Genesis is not final anymore, block 2 still is, and only the last two states are recent.
+    assert_eq!(client.get_state(hashes[0]), None);
+    assert_eq!(client.get_state(hashes[1]), None);
+    assert_eq!(client.get_state(hashes[2]), Some(2));
+    assert_eq!(client.get_state(hashes[3]), None);
+    assert_eq!(client.get_state(hashes[5]), Some(5));
+}
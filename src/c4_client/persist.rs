@@ -0,0 +1,100 @@
+//! A node that is stopped should be able to pick up where it left off when it is started again.
+//! This module contains the helpers the node uses to save its state to its data directory on
+//! shutdown, and to load it back on startup.
+//!
+//! The most important rule when saving state is to never leave a half written file behind. A
+//! node that is killed in the middle of a write would otherwise find a corrupted file on restart.
+//! We therefore always write to a temporary file first, and only move it into place once the
+//! write has completed.
+//!
+//! For now only the transaction pool is saved. The blocks themselves still live in memory.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Consensus, FullClient, StateMachine, TransactionPool};
+
+/// The name of the file in the data directory that the transaction pool is saved to.
+pub const POOL_FILE: &str = "pool.json";
+
+/// Write the given bytes to the given path, such that the file either has the old contents
+/// or the new contents, but never anything in between.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let temporary = path.with_extension("tmp");
+    let mut file = fs::File::create(&temporary)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+/// Save the given transactions to the pool file in the given data directory.
+pub fn save_pool<T: Serialize>(data_dir: &Path, transactions: &[T]) -> io::Result<()> {
+    let json = serde_json::to_vec_pretty(transactions).map_err(io::Error::other)?;
+    write_atomically(&data_dir.join(POOL_FILE), &json)
+}
+
+/// Load the transactions from the pool file in the given data directory. A missing file is
+/// not an error, it just means there was nothing to save.
+pub fn load_pool<T: for<'de> Deserialize<'de>>(data_dir: &Path) -> io::Result<Vec<T>> {
+    match fs::read(data_dir.join(POOL_FILE)) {
+        Ok(json) => serde_json::from_slice(&json).map_err(io::Error::other),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+impl<C, SM, FC, P> FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    P: TransactionPool<SM>,
+{
+    /// Take every transaction out of the pool, in the order the pool would have offered them
+    /// for inclusion. This is used when shutting down, so that the transactions can be saved
+    /// and resubmitted after a restart.
+    pub fn drain_pool(&mut self) -> Vec<SM::Transition> {
+        std::iter::from_fn(|| self.transaction_pool.next_from_pool()).collect()
+    }
+}
+
+/// A fresh data directory for a test, so that tests running in parallel do not interfere.
+#[cfg(test)]
+fn test_data_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("persist-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn persist_pool_round_trip() {
+    let dir = test_data_dir("round-trip");
+    save_pool(&dir, &[3u64, 1, 2]).unwrap();
+
+    assert_eq!(load_pool::<u64>(&dir).unwrap(), vec![3, 1, 2]);
+    assert!(!dir.join("pool.tmp").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn persist_missing_pool_is_empty() {
+    let dir = test_data_dir("missing");
+
+    assert_eq!(load_pool::<u64>(&dir).unwrap(), Vec::<u64>::new());
+}
+
+#[test]
+fn persist_overwrites_previous_save() {
+    let dir = test_data_dir("overwrite");
+    save_pool(&dir, &[1u64, 2, 3]).unwrap();
+    save_pool(&dir, &[4u64]).unwrap();
+
+    assert_eq!(load_pool::<u64>(&dir).unwrap(), vec![4]);
+    fs::remove_dir_all(&dir).unwrap();
+}
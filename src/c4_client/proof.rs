@@ -0,0 +1,125 @@
+//! A light client follows the chain by importing headers only. It never executes blocks, so it
+//! has no state of its own. When it wants to know an account's balance, it has to ask a full
+//! node. But the full node might lie.
+//!
+//! The answer is a storage proof. The header's state root commits to the whole state, which is
+//! kept in a Patricia trie. See the trie module. Alongside the account info, the full node sends
+//! the trie nodes on the path from the root down to that account. The light client hashes the
+//! nodes itself, and checks that they lead from the state root of a header it already trusts to
+//! the claimed value. A node that lies about the account can not produce such a path.
+//!
+//! The header stores `hash(&state)`, and the runtime state hashes as its trie root. So the state
+//! root in the header is the hash of the trie root, and that is what we check against.
+//!
+//! Light clients can check that an extrinsic made it into a block in the same way, against the
+//! extrinsics root. That one is a plain Merkle tree over the body.
+
+use serde::{Deserialize, Serialize};
+
+use super::runtime::{account_key, AccountInfo, RuntimeState};
+use super::{Block, Consensus, Header, StateMachine};
+use crate::c1_state_machine::User;
+use crate::hashing::{hash, H256};
+use crate::merkle::{build_proof, verify_proof, MerkleProof};
+use crate::trie::{self, TrieProof};
+
+/// A proof that an account holds some value, as of some state root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageProof {
+    /// The value of the account, as claimed by the node.
+    pub value: AccountInfo,
+    /// The trie nodes from the root down to the account.
+    pub proof: TrieProof<AccountInfo>,
+}
+
+impl RuntimeState {
+    /// The root of the trie that holds every account.
+    pub fn storage_root(&self) -> H256 {
+        self.accounts().root()
+    }
+
+    /// Prove the current value of the given account.
+    pub fn storage_proof(&self, who: User) -> StorageProof {
+        StorageProof {
+            value: self.account(who),
+            proof: self.accounts().prove(account_key(who).as_bytes()),
+        }
+    }
+}
+
+/// Check that the proof shows the claimed value for the given account, in the state with the
+/// given state root. The state root must come from a header that the caller already trusts.
+/// Accounts that hold nothing are not in the trie, so for those the proof shows absence.
+pub fn verify_storage_proof(state_root: H256, who: User, proof: &StorageProof) -> bool {
+    let root = trie::proof_root(&proof.proof);
+    let value = (proof.value != AccountInfo::default()).then_some(&proof.value);
+    hash(&root) == state_root
+        && trie::verify_proof(root, account_key(who).as_bytes(), value, &proof.proof)
+}
+
+impl<C: Consensus, SM: StateMachine> Block<C, SM>
+where
+    SM::Transition: std::hash::Hash,
+{
+    /// Prove that the extrinsic at the given index is in this block. Returns None if the block
+    /// has no such extrinsic.
+    pub fn extrinsic_proof(&self, index: usize) -> Option<MerkleProof> {
+        build_proof(self.body(), index)
+    }
+}
+
+/// Check that the proof shows the given extrinsic to be in the block with the given header. This
+/// lets a light client confirm that its transaction made it into a block without downloading
+/// the whole body.
+pub fn verify_extrinsic_proof<Digest, T: std::hash::Hash>(
+    header: &Header<Digest>,
+    extrinsic: &T,
+    proof: &MerkleProof,
+) -> bool {
+    verify_proof(header.extrinsics_root(), extrinsic, proof)
+}
+
+#[test]
+fn proof_verifies_against_state_root() {
+    let state = RuntimeState::genesis(&[(User::Alice, 10), (User::Charlie, 30)]);
+    let root = hash(&state);
+
+    for who in super::runtime::ACCOUNTS {
+        let proof = state.storage_proof(who);
+        assert_eq!(proof.value, state.account(who));
+        assert!(verify_storage_proof(root, who, &proof));
+    }
+}
+
+#[test]
+fn proof_rejects_forged_values() {
+    let state = RuntimeState::genesis(&[(User::Alice, 10)]);
+    let root = hash(&state);
+
+    let mut lie = state.storage_proof(User::Alice);
+    lie.value.balance = 1_000;
+    assert!(!verify_storage_proof(root, User::Alice, &lie));
+
+    // A valid proof for one account says nothing about another.
+    let bobs = state.storage_proof(User::Bob);
+    assert!(!verify_storage_proof(root, User::Alice, &bobs));
+    // Nor does it hold against a different state.
+    let other = RuntimeState::genesis(&[(User::Alice, 11)]);
+    assert!(!verify_storage_proof(hash(&other), User::Bob, &bobs));
+}
+
+#[test]
+fn proof_shows_extrinsic_in_header() {
+    let body = [10u64, 20, 30];
+    let header = Header::new(
+        H256::zero(),
+        1,
+        H256::zero(),
+        crate::merkle::merkle_root(&body),
+        (),
+    );
+
+    let proof = build_proof(&body, 2).unwrap();
+    assert!(verify_extrinsic_proof(&header, &30u64, &proof));
+    assert!(!verify_extrinsic_proof(&header, &40u64, &proof));
+}
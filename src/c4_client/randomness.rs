@@ -0,0 +1,159 @@
+//! Lotteries, leader elections and games all need randomness, but a blockchain is
+//! deterministic: every node must compute the same state, so nobody can just roll a die. What
+//! a chain can do is derive randomness from data that nobody could predict before it was on
+//! chain. This is what the `RandomnessBeacon` does. After every block, it mixes a fresh piece
+//! of entropy into a window of recent ones, and hashes the window into a seed that other state
+//! machines can draw from.
+//!
+//! Where the entropy comes from matters a lot, and the tests below demonstrate why:
+//! * Block hashes are free, but the author of a block chooses its hash. By trying different
+//!   sets of extrinsics, or just different timestamps, they can grind through as many hashes
+//!   as they like and publish the one that makes them win. This is how Substrate's
+//!   `RandomnessCollectiveFlip` works, and why it is only fit for tests.
+//! * A VRF output over the current seed, like in BABE, leaves the author a single possible
+//!   output. All they can still do is not author at all, and hand the choice to the next
+//!   author, which costs them their block reward.
+//!
+//! Mixing in many recent pieces of entropy, rather than the latest one only, means that the
+//! seed only changes a little with every block. It does not keep a single author from
+//! biasing it though: they still choose the newest piece.
+
+use std::collections::VecDeque;
+
+use crate::crypto::sig::PublicKey;
+use crate::crypto::vrf::{self, VrfProof};
+use crate::hashing::{hash_of, H256};
+
+/// How many recent pieces of entropy make up the seed.
+pub const RANDOMNESS_WINDOW: usize = 16;
+
+/// A source of randomness that every node of a chain agrees on, see the module docs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RandomnessBeacon {
+    recent: VecDeque<H256>,
+}
+
+impl RandomnessBeacon {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mix the hash of a new block into the seed. Anybody who authors a block can bias this.
+    pub fn note_block_hash(&mut self, hash: H256) {
+        self.note(hash);
+    }
+
+    /// The input that the next author must evaluate their VRF on.
+    pub fn vrf_input(&self) -> H256 {
+        self.seed()
+    }
+
+    /// Mix the VRF output of the next author into the seed, if its proof checks out. Returns
+    /// whether it did.
+    pub fn note_vrf_output(&mut self, author: &PublicKey, output: H256, proof: &VrfProof) -> bool {
+        if !vrf::verify(author, self.vrf_input().as_bytes(), output, proof) {
+            return false;
+        }
+        self.note(output);
+        true
+    }
+
+    fn note(&mut self, entropy: H256) {
+        if self.recent.len() == RANDOMNESS_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(entropy);
+    }
+
+    /// The current random seed.
+    pub fn seed(&self) -> H256 {
+        hash_of("randomness", &self.recent)
+    }
+
+    /// A random value for the given subject. Different subjects, like different lotteries,
+    /// get independent values out of the same seed.
+    pub fn random(&self, subject: &[u8]) -> H256 {
+        hash_of("random", &(self.seed(), subject))
+    }
+
+    /// A random index below `len` for the given subject, for example the winner of a lottery
+    /// or the leader of an election. None if there is nothing to pick from.
+    pub fn random_index(&self, subject: &[u8], len: usize) -> Option<usize> {
+        let bytes: [u8; 8] = self.random(subject).as_bytes()[..8].try_into().ok()?;
+        let value = u64::from_le_bytes(bytes);
+        (len > 0).then(|| (value % len as u64) as usize)
+    }
+}
+
+#[cfg(test)]
+use crate::crypto::sig::Keypair;
+
+/// A beacon that saw a few blocks already.
+#[cfg(test)]
+fn warmed_up() -> RandomnessBeacon {
+    let mut beacon = RandomnessBeacon::new();
+    for i in 0..RANDOMNESS_WINDOW as u64 {
+        beacon.note_block_hash(H256::from(i));
+    }
+    beacon
+}
+
+#[test]
+fn randomness_is_deterministic_and_changes_every_block() {
+    let (mut a, mut b) = (warmed_up(), warmed_up());
+    assert_eq!(a.seed(), b.seed());
+
+    let before = a.seed();
+    a.note_block_hash(H256::from(100));
+    b.note_block_hash(H256::from(100));
+    assert_ne!(a.seed(), before);
+    assert_eq!(a.seed(), b.seed());
+    assert_ne!(a.random(b"lottery"), a.random(b"election"));
+    assert_eq!(RandomnessBeacon::new().random_index(b"lottery", 0), None);
+}
+
+#[test]
+fn randomness_window_forgets_old_entropy() {
+    let mut a = warmed_up();
+    let mut b = RandomnessBeacon::new();
+    b.note_block_hash(H256::from(999));
+    for i in 1..RANDOMNESS_WINDOW as u64 {
+        b.note_block_hash(H256::from(i));
+    }
+    assert_ne!(a.seed(), b.seed());
+
+    a.note_block_hash(H256::from(7));
+    b.note_block_hash(H256::from(7));
+    assert_eq!(a.seed(), b.seed());
+}
+
+/// An author who mixes in a block hash can try one block after another until they win the
+/// next lottery, so block hashes are not safe randomness.
+#[test]
+fn randomness_from_block_hashes_can_be_ground() {
+    let beacon = warmed_up();
+    let author = 2;
+
+    let winning_block = (0..64u64).map(H256::from).find(|&candidate| {
+        let mut next = beacon.clone();
+        next.note_block_hash(candidate);
+        next.random_index(b"lottery", 3) == Some(author)
+    });
+    assert!(winning_block.is_some());
+}
+
+/// With a VRF, the author has one possible output. Anything else is rejected.
+#[test]
+fn randomness_from_vrf_leaves_no_choice() {
+    let mut beacon = warmed_up();
+    let author = Keypair::from_seed([7; 32]);
+
+    let (output, proof) = vrf::prove(&author, beacon.vrf_input().as_bytes());
+    let (again, _) = vrf::prove(&author, beacon.vrf_input().as_bytes());
+    assert_eq!(output, again);
+
+    assert!(!beacon.note_vrf_output(&author.public(), H256::from(1), &proof));
+    let impostor = Keypair::from_seed([8; 32]);
+    assert!(!beacon.note_vrf_output(&impostor.public(), output, &proof));
+    assert!(beacon.note_vrf_output(&author.public(), output, &proof));
+}
@@ -0,0 +1,567 @@
+//! Some bugs only show up after a devnet has been running for a while, and are gone again as soon
+//! as we try to look at them. The timing of the network, the order in which the tasks happen to
+//! run, and the order in which transactions arrive all play a part, and none of them repeat on
+//! the next run.
+//!
+//! This module takes those out of the picture. A `Recorder` wraps a client and writes everything
+//! that changes its chain or its pool to a trace file, one line of JSON per event: every submitted
+//! transaction, every imported and authored block, and every decision of the fork choice. Replaying
+//! the trace into a fresh client repeats the exact same events in the exact same order, without
+//! any of the timing that led to them, and checks at every step that the client decides the same
+//! way it did the first time. A bug that was seen once can then be replayed as often as it takes
+//! to find it.
+//!
+//! A node also hands its `ChainDb` to the recorder, which then stores every block the client
+//! accepts, whether it was imported or authored, along with the state after it. Given the node's
+//! `Metrics`, the recorder also times every import, and given its `Telemetry`, it reports every
+//! imported block and every reorg to the collector.
+//!
+//! ```ignore
+//! let mut client = Recorder::create(FullClient::new(genesis), Path::new("trace.jsonl"))?;
+//! // Run the node as usual...
+//!
+//! let mut fresh = FullClient::new(genesis);
+//! replay(&mut fresh, BufReader::new(File::open("trace.jsonl")?))?;
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufRead, LineWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::authoring::AuthorBlocks;
+use super::backend::{Backend, ChainDb};
+use super::forks::{ForkInfo, ForkTree};
+use super::metrics::Metrics;
+use super::network::reputation::PeerScore;
+use super::network::sync::LocalChain;
+use super::proof::StorageProof;
+use super::reorg::tree_route;
+use super::rpc::{Health, NodeApi, RpcError};
+use super::runtime::{AccountInfo, Runtime, SignedExtrinsic};
+use super::telemetry::{Telemetry, TelemetryEvent};
+use super::{Block, Consensus, ForkChoice, FullClient, ImportBlock, StateMachine, TransactionPool};
+use crate::c1_state_machine::User;
+use crate::hashing::{header_hash, H256};
+
+/// A single event in a trace.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEntry<B, T> {
+    /// The recording started, with a client whose best block was the given genesis block.
+    Started { genesis: H256 },
+    /// A transaction was submitted to the pool.
+    Submitted { transaction: T },
+    /// A block from elsewhere was offered for import, and was imported or not.
+    Imported { block: B, accepted: bool },
+    /// The client authored and imported a block of its own. It is imported like any other block on
+    /// replay, since authoring it again would not give the same block.
+    Authored { block: B },
+    /// The fork choice picked a new best block.
+    BestBlock { hash: H256 },
+    /// A block was offered for finality, and was finalized or not.
+    Finalized { hash: H256, accepted: bool },
+    /// The pool was emptied, and held the given number of transactions.
+    Drained { count: usize },
+}
+
+/// The entries of a trace that was recorded from a full client.
+pub type ClientTraceEntry<C, SM> = TraceEntry<Block<C, SM>, <SM as StateMachine>::Transition>;
+
+/// A client that writes everything that happens to it to a trace.
+pub struct Recorder<C: Consensus, SM: StateMachine, FC, P> {
+    client: FullClient<C, SM, FC, P>,
+    sink: Option<Box<dyn Write + Send>>,
+    /// The best block as of the last entry, so that we only record when it changes.
+    best: H256,
+    error: Option<io::Error>,
+    /// Where accepted blocks are stored, if anywhere.
+    chain_db: Option<ChainDb<Box<dyn Backend + Send>>>,
+    /// Where the time it takes to import a block is recorded, if anywhere.
+    metrics: Option<Arc<Metrics>>,
+    telemetry: Telemetry,
+}
+
+impl<C, SM, FC, P> Recorder<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    C::Digest: Serialize,
+    SM::State: Serialize,
+    SM::Transition: Serialize,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+    FullClient<C, SM, FC, P>: ImportBlock<C, SM>,
+{
+    /// Start recording the given client to the given sink. Without a sink, nothing is recorded,
+    /// which lets a node wrap its client the same way whether it records or not.
+    pub fn new(client: FullClient<C, SM, FC, P>, sink: Option<Box<dyn Write + Send>>) -> Self {
+        let best = client.best_block();
+        let mut recorder = Recorder {
+            client,
+            sink,
+            best,
+            error: None,
+            chain_db: None,
+            metrics: None,
+            telemetry: Telemetry::disabled(),
+        };
+        recorder.record(TraceEntry::<(), ()>::Started { genesis: best });
+        recorder
+    }
+
+    /// Start recording the given client to a new trace file at the given path. Every entry is
+    /// flushed as soon as it is written, so a node that crashes still leaves a complete trace
+    /// behind.
+    pub fn create(client: FullClient<C, SM, FC, P>, path: &Path) -> io::Result<Self> {
+        let file = LineWriter::new(File::create(path)?);
+        Ok(Self::new(client, Some(Box::new(file))))
+    }
+
+    /// Store every block that the client accepts from now on in the given database.
+    pub fn with_chain_db(mut self, chain_db: ChainDb<Box<dyn Backend + Send>>) -> Self {
+        self.chain_db = Some(chain_db);
+        self
+    }
+
+    /// Time every import from now on in the given metrics.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Report imported blocks and reorgs to the given telemetry from now on.
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = telemetry;
+        self
+    }
+
+    /// The database that accepted blocks are stored in, if any.
+    pub fn chain_db(&mut self) -> Option<&mut ChainDb<Box<dyn Backend + Send>>> {
+        self.chain_db.as_mut()
+    }
+
+    /// The client that is being recorded.
+    pub fn client(&self) -> &FullClient<C, SM, FC, P> {
+        &self.client
+    }
+
+    /// Stop recording and hand back the client.
+    pub fn into_inner(self) -> FullClient<C, SM, FC, P> {
+        self.client
+    }
+
+    /// The error that stopped the recording, if any. A node should not stop just because its
+    /// trace could not be written, so the first failed write only ends the trace.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Write an entry to the trace. Entries are written as they are made, so they may borrow
+    /// the blocks and transactions they are about instead of owning them.
+    fn record<B: Serialize, T: Serialize>(&mut self, entry: TraceEntry<B, T>) {
+        let Some(sink) = &mut self.sink else {
+            return;
+        };
+        let written = serde_json::to_writer(&mut *sink, &entry)
+            .map_err(io::Error::other)
+            .and_then(|()| sink.write_all(b"\n"));
+        if let Err(e) = written {
+            self.sink = None;
+            self.error = Some(e);
+        }
+    }
+
+    /// Store a block that the client just accepted, and the client's best block. A block whose
+    /// state is already pruned is not stored, since none of its children can be imported.
+    fn store(&mut self, hash: H256, block: &Block<C, SM>) {
+        let Some(chain_db) = &mut self.chain_db else {
+            return;
+        };
+        let Some(state) = self.client.get_state(hash) else {
+            return;
+        };
+        let height = block.header().height();
+        if let Err(e) = chain_db
+            .insert_block(hash, height, block, &state)
+            .and_then(|()| chain_db.set_best(self.client.best_block()))
+        {
+            tracing::error!(%hash, height, "failed to store block: {e:?}");
+        }
+    }
+
+    /// Record the fork choice's decision, if it changed its mind since the last entry. Moving
+    /// to a block that does not descend from the previous best block is a reorg.
+    fn record_best(&mut self) {
+        let best = self.client.best_block();
+        if best == self.best {
+            return;
+        }
+        let route = tree_route(self.best, best, |hash| {
+            self.client
+                .get_block(hash)
+                .map(|block| block.header().clone())
+        });
+        if let Some(route) = route.filter(|route| !route.retracted.is_empty()) {
+            self.telemetry.emit(TelemetryEvent::Reorg {
+                from: self.best,
+                to: best,
+                retracted: route.retracted.len(),
+                enacted: route.enacted.len(),
+            });
+        }
+        self.best = best;
+        self.record(TraceEntry::<(), ()>::BestBlock { hash: best });
+    }
+
+    pub fn submit_transaction(&mut self, transaction: SM::Transition) {
+        self.record(TraceEntry::<(), _>::Submitted {
+            transaction: &transaction,
+        });
+        self.client.submit_transaction(transaction);
+    }
+
+    pub fn import_block(&mut self, block: Block<C, SM>) -> bool {
+        // Importing takes the block, and we only know whether it was accepted afterwards.
+        let json = self.sink.is_some().then(|| serde_json::to_value(&block));
+        let hash = header_hash(block.header());
+        let accepted = match &self.metrics {
+            Some(metrics) => metrics
+                .block_import_time
+                .time(|| self.client.import_block(block)),
+            None => self.client.import_block(block),
+        };
+        match json {
+            Some(Ok(block)) => self.record(TraceEntry::<_, ()>::Imported { block, accepted }),
+            Some(Err(e)) => {
+                self.sink = None;
+                self.error = Some(io::Error::other(e));
+            }
+            None => {}
+        }
+        if let Some(block) = accepted.then(|| self.client.get_block(hash)).flatten() {
+            let height = block.header().height();
+            self.telemetry
+                .emit(TelemetryEvent::BlockImported { hash, height });
+            self.store(hash, &block);
+        }
+        self.record_best();
+        accepted
+    }
+
+    pub fn manually_finalize_block(&mut self, hash: H256) -> bool {
+        let accepted = self.client.manually_finalize_block(hash);
+        self.record(TraceEntry::<(), ()>::Finalized { hash, accepted });
+        if let Some(chain_db) = self.chain_db.as_mut().filter(|_| accepted) {
+            if let Err(e) = chain_db.finalize(hash) {
+                tracing::error!(%hash, "failed to keep the state of the finalized block: {e:?}");
+            }
+        }
+        accepted
+    }
+
+    pub fn drain_pool(&mut self) -> Vec<SM::Transition> {
+        let drained = self.client.drain_pool();
+        self.record(TraceEntry::<(), ()>::Drained {
+            count: drained.len(),
+        });
+        drained
+    }
+}
+
+impl<C, SM, FC, P> AuthorBlocks for Recorder<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    C::Digest: Serialize,
+    SM::State: Serialize,
+    SM::Transition: Serialize,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+    FullClient<C, SM, FC, P>: ImportBlock<C, SM> + AuthorBlocks<Block = Block<C, SM>>,
+{
+    type Block = Block<C, SM>;
+
+    fn try_author_block(&mut self) -> Option<Block<C, SM>> {
+        let block = self.client.try_author_block()?;
+        self.record(TraceEntry::<_, ()>::Authored { block: &block });
+        self.store(header_hash(block.header()), &block);
+        self.record_best();
+        Some(block)
+    }
+}
+
+impl<C, SM, FC, P> LocalChain<Block<C, SM>> for Recorder<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    C::Digest: Serialize,
+    SM::State: Serialize,
+    SM::Transition: Serialize,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+    FullClient<C, SM, FC, P>: ImportBlock<C, SM>,
+{
+    fn is_known(&self, hash: H256) -> bool {
+        self.client.is_known(hash)
+    }
+
+    fn import(&mut self, block: Block<C, SM>) -> bool {
+        self.import_block(block)
+    }
+}
+
+impl<C, FC, P> NodeApi for Recorder<C, Runtime, FC, P>
+where
+    C: Consensus,
+    C::Digest: Serialize,
+    FC: ForkChoice<C>,
+    P: TransactionPool<Runtime>,
+    FullClient<C, Runtime, FC, P>: ImportBlock<C, Runtime> + NodeApi,
+{
+    fn best_block_hash(&mut self) -> Result<H256, RpcError> {
+        self.client.best_block_hash()
+    }
+
+    fn account_info(&mut self, who: User) -> Result<AccountInfo, RpcError> {
+        self.client.account_info(who)
+    }
+
+    fn storage_proof(&mut self, who: User, at: H256) -> Result<StorageProof, RpcError> {
+        self.client.storage_proof(who, at)
+    }
+
+    fn submit_extrinsic(&mut self, extrinsic: SignedExtrinsic) -> Result<(), RpcError> {
+        self.submit_transaction(extrinsic);
+        Ok(())
+    }
+
+    fn health(&mut self) -> Result<Health, RpcError> {
+        self.client.health()
+    }
+
+    fn peer_scores(&mut self) -> Result<Vec<PeerScore>, RpcError> {
+        self.client.peer_scores()
+    }
+
+    fn forks(&mut self) -> Result<Vec<ForkInfo>, RpcError> {
+        NodeApi::forks(&mut self.client)
+    }
+
+    fn block_tree(&mut self) -> Result<ForkTree, RpcError> {
+        NodeApi::block_tree(&mut self.client)
+    }
+
+    fn insert_key(&mut self, who: User) -> Result<(), RpcError> {
+        self.client.insert_key(who)
+    }
+
+    fn purge_pool(&mut self) -> Result<usize, RpcError> {
+        Ok(self.drain_pool().len())
+    }
+}
+
+/// Why a replay stopped before the end of the trace.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The trace could not be read.
+    Io(io::Error),
+    /// The given line of the trace is not a valid entry.
+    Malformed { line: usize, error: String },
+    /// The client decided differently than it did when the given line was recorded.
+    Diverged {
+        line: usize,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// Replay a trace into the given client, which should be fresh from the same genesis state as the
+/// recorded one. Lines are counted from one. Returns the number of entries replayed.
+pub fn replay<C, SM, FC, P>(
+    client: &mut FullClient<C, SM, FC, P>,
+    trace: impl BufRead,
+) -> Result<usize, ReplayError>
+where
+    C: Consensus,
+    SM: StateMachine,
+    C::Digest: for<'de> Deserialize<'de>,
+    SM::Transition: for<'de> Deserialize<'de>,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+    FullClient<C, SM, FC, P>: ImportBlock<C, SM>,
+{
+    let mut replayed = 0;
+    for (index, line) in trace.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(ReplayError::Io)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: ClientTraceEntry<C, SM> =
+            serde_json::from_str(&line).map_err(|e| ReplayError::Malformed {
+                line: line_number,
+                error: e.to_string(),
+            })?;
+        let diverged =
+            |expected: &dyn std::fmt::Debug, actual: &dyn std::fmt::Debug| ReplayError::Diverged {
+                line: line_number,
+                expected: format!("{expected:?}"),
+                actual: format!("{actual:?}"),
+            };
+
+        match entry {
+            TraceEntry::Started { genesis } => {
+                let best = client.best_block();
+                if best != genesis {
+                    return Err(diverged(&genesis, &best));
+                }
+            }
+            TraceEntry::Submitted { transaction } => client.submit_transaction(transaction),
+            TraceEntry::Imported { block, accepted } => {
+                let imported = client.import_block(block);
+                if imported != accepted {
+                    return Err(diverged(&accepted, &imported));
+                }
+            }
+            TraceEntry::Authored { block } => {
+                if !client.import_block(block) {
+                    return Err(diverged(&"our own block imported", &"it was rejected"));
+                }
+            }
+            TraceEntry::BestBlock { hash } => {
+                let best = client.best_block();
+                if best != hash {
+                    return Err(diverged(&hash, &best));
+                }
+            }
+            TraceEntry::Finalized { hash, accepted } => {
+                let finalized = client.manually_finalize_block(hash);
+                if finalized != accepted {
+                    return Err(diverged(&accepted, &finalized));
+                }
+            }
+            TraceEntry::Drained { count } => {
+                let drained = client.drain_pool().len();
+                if drained != count {
+                    return Err(diverged(&count, &drained));
+                }
+            }
+        }
+        replayed += 1;
+    }
+    Ok(replayed)
+}
+
+#[test]
+fn recorder_entries_are_one_line_of_json_each() {
+    let entries: Vec<TraceEntry<u64, u64>> = vec![
+        TraceEntry::Started {
+            genesis: H256::from(1),
+        },
+        TraceEntry::Submitted { transaction: 7 },
+        TraceEntry::Imported {
+            block: 2,
+            accepted: true,
+        },
+        TraceEntry::BestBlock {
+            hash: H256::from(2),
+        },
+        TraceEntry::Drained { count: 0 },
+    ];
+
+    for entry in &entries {
+        let line = serde_json::to_string(entry).unwrap();
+        assert!(!line.contains('\n'));
+        assert_eq!(
+            &serde_json::from_str::<TraceEntry<u64, u64>>(&line).unwrap(),
+            entry
+        );
+    }
+    let line = serde_json::to_string(&entries[1]).unwrap();
+    assert_eq!(line, r#"{"event":"submitted","transaction":7}"#);
+}
+
+#[test]
+fn recorder_stores_every_accepted_block() {
+    use super::backend::{self, BackendKind};
+    use super::p1_data_structure::Adder;
+    use super::{LongestChain, SimplePool};
+
+    type AdderClient = FullClient<(), Adder, LongestChain, SimplePool<Adder>>;
+    let chain_db = ChainDb::new(backend::open(BackendKind::Memory, Path::new("unused")).unwrap());
+    let mut recorder = Recorder::new(AdderClient::new(0), None).with_chain_db(chain_db);
+
+    // One block from elsewhere, and one of our own on top of it.
+    let imported = Block::genesis(&0).child(&(), &0, vec![3]).unwrap();
+    assert!(recorder.import_block(imported));
+    recorder.submit_transaction(4);
+    let authored = header_hash(recorder.try_author_block().unwrap().header());
+
+    let chain_db = recorder.chain_db().unwrap();
+    assert_eq!(chain_db.best(), Ok(Some(authored)));
+    let mut restored = AdderClient::new(0);
+    assert_eq!(chain_db.restore(&mut restored), Ok(2));
+    assert_eq!(restored.best_block(), authored);
+    assert_eq!(restored.get_state(authored), Some(7));
+}
+
+#[test]
+fn recorder_times_every_import() {
+    use super::p1_data_structure::Adder;
+    use super::{LongestChain, SimplePool};
+
+    let metrics = Arc::new(Metrics::new());
+    let client = FullClient::<(), Adder, LongestChain, SimplePool<Adder>>::new(0);
+    let mut recorder = Recorder::new(client, None).with_metrics(metrics.clone());
+    let genesis = Block::genesis(&0);
+
+    assert!(recorder.import_block(genesis.child(&(), &0, vec![1]).unwrap()));
+    assert!(!recorder.import_block(genesis.child(&(), &5, vec![1]).unwrap()));
+    assert_eq!(metrics.block_import_time.count(), 2);
+}
+
+#[test]
+fn recorder_reports_imports_and_reorgs() {
+    use super::p1_data_structure::Adder;
+    use super::{LongestChain, SimplePool};
+
+    let (telemetry, events) = Telemetry::captured();
+    let client = FullClient::<(), Adder, LongestChain, SimplePool<Adder>>::new(0);
+    let mut recorder = Recorder::new(client, None).with_telemetry(telemetry);
+    let genesis = Block::genesis(&0);
+    let a1 = genesis.child(&(), &0, vec![1]).unwrap();
+    let b1 = genesis.child(&(), &0, vec![2]).unwrap();
+    let b2 = b1.child(&(), &2, vec![2]).unwrap();
+    let [a1_hash, b1_hash, b2_hash] = [&a1, &b1, &b2].map(|block| header_hash(block.header()));
+    for block in [a1, b1, b2] {
+        assert!(recorder.import_block(block));
+    }
+
+    let events: Vec<TelemetryEvent> = events.try_iter().map(|message| message.event).collect();
+    assert_eq!(
+        events,
+        [
+            TelemetryEvent::BlockImported {
+                hash: a1_hash,
+                height: 1
+            },
+            TelemetryEvent::BlockImported {
+                hash: b1_hash,
+                height: 1
+            },
+            TelemetryEvent::BlockImported {
+                hash: b2_hash,
+                height: 2
+            },
+            TelemetryEvent::Reorg {
+                from: a1_hash,
+                to: b2_hash,
+                retracted: 1,
+                enacted: 2
+            },
+        ]
+    );
+}
@@ -0,0 +1,307 @@
+//! A small chain has a hard time securing itself: with few validators, a few bribes are enough
+//! to finalize whatever an attacker likes. Polkadot's answer is _shared security_. Many small
+//! chains, the parachains, hand their blocks to one big relay chain, whose validators check
+//! them and record which ones are valid. A parachain block is only final once the relay chain
+//! block that includes it is final, so attacking a parachain means attacking the relay chain.
+//!
+//! This is a toy model with a single parachain:
+//! * A collator of the parachain builds a block, and hands it to the relay chain as a
+//!   `Candidate`. Relay validators do not keep the parachain's state, so the candidate comes
+//!   with the state that the block was built on, its proof of validity.
+//! * The relay chain's state machine checks the candidate: it must build on the parachain head
+//!   that was included last, its proof of validity must match that head's state root, and
+//!   re-executing it must give the state root that it claims. Only then is it included.
+//! * The parachain's client follows the relay chain. It builds on the head that the relay chain
+//!   included, and it treats a block as final once a final relay chain block included it.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::runtime::{Runtime, RuntimeState, SignedExtrinsic};
+use crate::c1_state_machine::StateMachine;
+use crate::hashing::{hash_of, H256};
+
+/// A block of the parachain.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ParaBlock {
+    pub parent: H256,
+    pub height: u64,
+    /// The storage root of the parachain's state after this block.
+    pub state_root: H256,
+    pub extrinsics: Vec<SignedExtrinsic>,
+}
+
+impl ParaBlock {
+    /// The genesis block of a parachain that starts out with the given state.
+    pub fn genesis(state: &RuntimeState) -> Self {
+        ParaBlock {
+            parent: H256::default(),
+            height: 0,
+            state_root: state.storage_root(),
+            extrinsics: Vec::new(),
+        }
+    }
+
+    pub fn hash(&self) -> H256 {
+        hash_of("para block", self)
+    }
+}
+
+/// Execute the extrinsics of a parachain block on top of the given state.
+pub fn execute_para_block(state: &RuntimeState, extrinsics: &[SignedExtrinsic]) -> RuntimeState {
+    extrinsics
+        .iter()
+        .fold(state.clone(), |state, t| Runtime::next_state(&state, t))
+}
+
+/// A parachain block, as a collator hands it to the relay chain.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Candidate {
+    pub block: ParaBlock,
+    /// The proof of validity: the state the block was built on.
+    pub parent_state: RuntimeState,
+}
+
+/// What the relay chain knows about the parachain.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RelayState {
+    /// The hash of the parachain block that was included last.
+    pub para_head: H256,
+    pub para_height: u64,
+    /// The state root after the parachain block that was included last.
+    pub para_state_root: H256,
+}
+
+impl RelayState {
+    /// A relay chain that starts tracking the parachain at the given genesis block.
+    pub fn genesis(para_genesis: &ParaBlock) -> Self {
+        RelayState {
+            para_head: para_genesis.hash(),
+            para_height: para_genesis.height,
+            para_state_root: para_genesis.state_root,
+        }
+    }
+}
+
+/// The relay chain's state machine. Its transitions are parachain candidates.
+pub struct Relay;
+
+impl StateMachine for Relay {
+    type State = RelayState;
+    type Transition = Candidate;
+
+    /// Candidates that don't build on the last included head, whose proof of validity does
+    /// not match that head, or whose block does not execute to the state root it claims, are
+    /// not included.
+    fn next_state(starting_state: &RelayState, candidate: &Candidate) -> RelayState {
+        let block = &candidate.block;
+        if block.parent != starting_state.para_head
+            || block.height != starting_state.para_height + 1
+            || candidate.parent_state.storage_root() != starting_state.para_state_root
+        {
+            return starting_state.clone();
+        }
+        let state = execute_para_block(&candidate.parent_state, &block.extrinsics);
+        if state.storage_root() != block.state_root {
+            return starting_state.clone();
+        }
+        RelayState {
+            para_head: block.hash(),
+            para_height: block.height,
+            para_state_root: block.state_root,
+        }
+    }
+
+    fn human_name() -> String {
+        "Relay".into()
+    }
+}
+
+/// The client of a parachain node, which collates blocks and follows the relay chain.
+pub struct ParaClient {
+    /// Every parachain block this node knows, with the state after it.
+    blocks: BTreeMap<H256, (ParaBlock, RuntimeState)>,
+    /// The head that the best relay chain block included.
+    included: H256,
+    /// The head that the latest final relay chain block included.
+    finalized: H256,
+}
+
+impl ParaClient {
+    /// A client of a parachain that starts out with the given state.
+    pub fn new(genesis_state: RuntimeState) -> Self {
+        let genesis = ParaBlock::genesis(&genesis_state);
+        let hash = genesis.hash();
+        ParaClient {
+            blocks: BTreeMap::from([(hash, (genesis, genesis_state))]),
+            included: hash,
+            finalized: hash,
+        }
+    }
+
+    /// Build a block with the given extrinsics on top of the included head, and wrap it up as
+    /// a candidate for the relay chain. The block is imported locally as well.
+    pub fn collate(&mut self, extrinsics: Vec<SignedExtrinsic>) -> Candidate {
+        let (parent, parent_state) = self.blocks[&self.included].clone();
+        let state = execute_para_block(&parent_state, &extrinsics);
+        let block = ParaBlock {
+            parent: parent.hash(),
+            height: parent.height + 1,
+            state_root: state.storage_root(),
+            extrinsics,
+        };
+        self.blocks.insert(block.hash(), (block.clone(), state));
+        Candidate {
+            block,
+            parent_state,
+        }
+    }
+
+    /// Import a parachain block that another collator built, for example one that the relay
+    /// chain included. Returns whether it was valid and its parent was known.
+    pub fn import(&mut self, block: ParaBlock) -> bool {
+        let Some((parent, parent_state)) = self.blocks.get(&block.parent) else {
+            return false;
+        };
+        let state = execute_para_block(parent_state, &block.extrinsics);
+        if block.height != parent.height + 1 || state.storage_root() != block.state_root {
+            return false;
+        }
+        self.blocks.insert(block.hash(), (block, state));
+        true
+    }
+
+    /// Follow a new relay chain block with the given state, which may or may not be final.
+    /// Returns false if it included a parachain block that this node does not know yet, which
+    /// must be imported first.
+    pub fn follow(&mut self, relay: &RelayState, finalized: bool) -> bool {
+        if !self.blocks.contains_key(&relay.para_head) {
+            return false;
+        }
+        self.included = relay.para_head;
+        if finalized {
+            self.finalized = relay.para_head;
+        }
+        true
+    }
+
+    /// The parachain block that the best relay chain block included.
+    pub fn included(&self) -> &ParaBlock {
+        &self.blocks[&self.included].0
+    }
+
+    /// The latest final parachain block.
+    pub fn finalized(&self) -> &ParaBlock {
+        &self.blocks[&self.finalized].0
+    }
+
+    /// The state after the latest final parachain block.
+    pub fn finalized_state(&self) -> &RuntimeState {
+        &self.blocks[&self.finalized].1
+    }
+}
+
+#[cfg(test)]
+use super::keystore::Keystore;
+#[cfg(test)]
+use super::runtime::signing_payload;
+#[cfg(test)]
+use crate::c1_state_machine::{AccountingTransaction, User};
+
+/// Alice's transfer of the given amount to Bob, with the given nonce.
+#[cfg(test)]
+fn transfer(nonce: u64, amount: u64) -> SignedExtrinsic {
+    let call = AccountingTransaction::Transfer {
+        sender: User::Alice,
+        receiver: User::Bob,
+        amount,
+    };
+    let signature = Keystore::dev()
+        .sign(User::Alice, signing_payload(User::Alice, nonce, &call))
+        .unwrap();
+    SignedExtrinsic {
+        signer: User::Alice,
+        nonce,
+        call,
+        signature,
+    }
+}
+
+#[cfg(test)]
+fn para() -> ParaClient {
+    ParaClient::new(RuntimeState::genesis(&[(User::Alice, 100)]))
+}
+
+#[test]
+fn relay_includes_valid_candidates() {
+    let mut client = para();
+    let relay = RelayState::genesis(client.finalized());
+    let candidate = client.collate(vec![transfer(0, 10)]);
+
+    let included = Relay::next_state(&relay, &candidate);
+    assert_eq!(included.para_head, candidate.block.hash());
+    assert_eq!(included.para_height, 1);
+
+    // The same candidate does not build on the new head anymore.
+    assert_eq!(Relay::next_state(&included, &candidate), included);
+}
+
+#[test]
+fn relay_rejects_invalid_candidates() {
+    let mut client = para();
+    let relay = RelayState::genesis(client.finalized());
+    let candidate = client.collate(vec![transfer(0, 10)]);
+
+    // A collator that claims a state it did not execute to.
+    let mut lying = candidate.clone();
+    lying.block.state_root = RuntimeState::genesis(&[(User::Bob, 100)]).storage_root();
+    assert_eq!(Relay::next_state(&relay, &lying), relay);
+
+    // A collator that executes on top of a state that is not the parachain's.
+    let mut forged = candidate.clone();
+    forged.parent_state = RuntimeState::genesis(&[(User::Alice, 1_000)]);
+    forged.block.state_root =
+        execute_para_block(&forged.parent_state, &forged.block.extrinsics).storage_root();
+    assert_eq!(Relay::next_state(&relay, &forged), relay);
+
+    let mut orphan = candidate;
+    orphan.block.parent = H256::from(1);
+    assert_eq!(Relay::next_state(&relay, &orphan), relay);
+}
+
+#[test]
+fn relay_para_client_follows_relay_finality() {
+    let mut client = para();
+    let genesis = RelayState::genesis(client.finalized());
+    let ten = client.collate(vec![transfer(0, 10)]);
+    let twenty = client.collate(vec![transfer(0, 20)]);
+
+    // Two relay forks include different candidates. The client builds on whichever is best.
+    let fork_ten = Relay::next_state(&genesis, &ten);
+    let fork_twenty = Relay::next_state(&genesis, &twenty);
+    assert!(client.follow(&fork_ten, false));
+    assert_eq!(client.included(), &ten.block);
+    assert_eq!(client.finalized().height, 0);
+
+    // The other fork is finalized, and the client switches over.
+    assert!(client.follow(&fork_twenty, true));
+    assert_eq!(client.finalized(), &twenty.block);
+    assert_eq!(client.finalized_state().account(User::Bob).balance, 20);
+    let next = client.collate(vec![transfer(1, 5)]);
+    assert_eq!(next.block.parent, twenty.block.hash());
+}
+
+#[test]
+fn relay_para_client_imports_other_collators_blocks() {
+    let mut collator = para();
+    let mut follower = para();
+    let relay = RelayState::genesis(collator.finalized());
+    let candidate = collator.collate(vec![transfer(0, 10)]);
+    let relay = Relay::next_state(&relay, &candidate);
+
+    assert!(!follower.follow(&relay, true));
+    assert!(follower.import(candidate.block.clone()));
+    assert!(follower.follow(&relay, true));
+    assert_eq!(follower.finalized(), &candidate.block);
+}
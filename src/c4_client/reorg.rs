@@ -0,0 +1,320 @@
+//! When the fork choice rule decides that a different fork is now the best one, the client
+//! performs a re-organization, or reorg. The blocks on the old fork, back to the point where the
+//! two forks meet, are _retracted_ and the blocks on the new fork are _enacted_.
+//!
+//! Reorgs are dangerous for users. A transaction that was included in a retracted block is no
+//! longer on the canonical chain, and unless somebody puts it back in the pool, it is silently
+//! lost. At the same time, transactions that are still waiting in the pool may no longer make
+//! sense on top of the new best block. Perhaps the new fork already contains them, or perhaps it
+//! contains a different transaction that uses up the same nonce.
+//!
+//! So after every reorg, the pool must be maintained:
+//! 1. Transactions from retracted blocks go back into the pool.
+//! 2. Transactions from enacted blocks are removed from the pool.
+//! 3. Whatever is left is re-validated against the state of the new best block, and
+//!    transactions that are no longer valid are dropped.
+//!
+//! Our state machines have no way of saying that a transaction is invalid. Instead, they leave
+//! the state untouched. We therefore treat a transaction that does not change the state as
+//! invalid. This works well for the signed runtime, where every valid extrinsic at least bumps
+//! its signer's nonce.
+
+use super::network::ChainHeader;
+use super::{Consensus, FullClient, ImportBlock, StateMachine, TransactionPool};
+use crate::hashing::H256;
+
+/// How to get from one block to another through the block tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TreeRoute {
+    /// The blocks that are no longer canonical, newest first.
+    pub retracted: Vec<H256>,
+    /// The last block that both forks have in common.
+    pub common_ancestor: H256,
+    /// The blocks that have become canonical, oldest first.
+    pub enacted: Vec<H256>,
+}
+
+/// Find the route from the block `from` to the block `to`, looking headers up with the given
+/// function. Returns None if either block, or any of their ancestors, is unknown.
+pub fn tree_route<H: ChainHeader>(
+    from: H256,
+    to: H256,
+    header: impl Fn(H256) -> Option<H>,
+) -> Option<TreeRoute> {
+    let mut route = TreeRoute::default();
+    let mut from = header(from)?;
+    let mut to = header(to)?;
+
+    // First walk back on whichever fork is longer, until both are at the same height.
+    while from.height() > to.height() {
+        route.retracted.push(from.hash());
+        from = header(from.parent_hash())?;
+    }
+    while to.height() > from.height() {
+        route.enacted.push(to.hash());
+        to = header(to.parent_hash())?;
+    }
+
+    // Then walk back on both, until they meet.
+    while from.hash() != to.hash() {
+        route.retracted.push(from.hash());
+        route.enacted.push(to.hash());
+        from = header(from.parent_hash())?;
+        to = header(to.parent_hash())?;
+    }
+
+    route.common_ancestor = from.hash();
+    route.enacted.reverse();
+    Some(route)
+}
+
+/// The transactions to keep in the pool after a reorg, and how many were dropped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Revalidated<T> {
+    pub valid: Vec<T>,
+    pub dropped: usize,
+}
+
+/// Decide which transactions belong in the pool after a reorg.
+///
+/// `retracted` holds the bodies of the retracted blocks, oldest first, and `pending` holds the
+/// transactions that were already in the pool. Retracted transactions come first, because they
+/// were originally submitted first, and pending transactions may depend on them.
+pub fn revalidate<SM>(
+    state: &SM::State,
+    retracted: Vec<Vec<SM::Transition>>,
+    pending: Vec<SM::Transition>,
+    enacted: &[SM::Transition],
+) -> Revalidated<SM::Transition>
+where
+    SM: StateMachine,
+    SM::State: PartialEq,
+    SM::Transition: PartialEq,
+{
+    // The state after applying every transaction we decided to keep so far. None until the
+    // first transaction has been kept, so that we do not need to clone the starting state.
+    let mut current: Option<SM::State> = None;
+    let mut valid = Vec::new();
+    let mut dropped = 0;
+    for t in retracted.into_iter().flatten().chain(pending) {
+        // Transactions that the new fork already includes are not dropped, they made it.
+        if enacted.contains(&t) {
+            continue;
+        }
+
+        let base = current.as_ref().unwrap_or(state);
+        let next = SM::next_state(base, &t);
+        if next == *base {
+            dropped += 1;
+        } else {
+            current = Some(next);
+            valid.push(t);
+        }
+    }
+    Revalidated { valid, dropped }
+}
+
+impl<C, SM, FC, P> FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: PartialEq,
+    SM::Transition: Clone + PartialEq,
+    P: TransactionPool<SM>,
+    Self: ImportBlock<C, SM>,
+{
+    /// Maintain the transaction pool after the best block changed from `old_best` to
+    /// `new_best`. Call this from `import_block` whenever an import changes the best block.
+    ///
+    /// Returns the number of transactions that were dropped because they are no longer valid,
+    /// or None if either block is unknown.
+    #[tracing::instrument(level = "debug", skip_all, fields(%old_best, %new_best))]
+    pub fn maintain_pool(&mut self, old_best: H256, new_best: H256) -> Option<usize> {
+        let route = tree_route(old_best, new_best, |hash| {
+            self.get_block(hash).map(|block| block.header().clone())
+        })?;
+        let state = self.get_state(new_best)?;
+        let body = |hash: &H256| self.get_block(*hash).map(|block| block.body().to_vec());
+
+        let retracted: Vec<_> = route.retracted.iter().rev().filter_map(body).collect();
+        let enacted: Vec<_> = route.enacted.iter().filter_map(body).flatten().collect();
+
+        let pending = self.drain_pool();
+        let revalidated = revalidate::<SM>(&state, retracted, pending, &enacted);
+        tracing::debug!(
+            retracted = route.retracted.len(),
+            enacted = route.enacted.len(),
+            dropped = revalidated.dropped,
+            "maintained the pool"
+        );
+        for t in revalidated.valid {
+            self.transaction_pool.try_insert(t);
+        }
+        Some(revalidated.dropped)
+    }
+}
+
+#[cfg(test)]
+#[derive(Clone)]
+struct TestHeader {
+    hash: H256,
+    parent: H256,
+    height: u64,
+}
+
+#[cfg(test)]
+impl ChainHeader for TestHeader {
+    fn hash(&self) -> H256 {
+        self.hash
+    }
+
+    fn parent_hash(&self) -> H256 {
+        self.parent
+    }
+
+    fn height(&self) -> u64 {
+        self.height
+    }
+}
+
+/// A small block tree. Genesis is 1, the first fork is 11 -> 12 -> 13, and the second fork
+/// is 21 -> 22, both built directly on genesis.
+#[cfg(test)]
+fn test_tree(hash: H256) -> Option<TestHeader> {
+    let (parent, height) = match hash.low_u64() {
+        1 => (0, 0),
+        11 | 21 => (1, 1),
+        12 => (11, 2),
+        13 => (12, 3),
+        22 => (21, 2),
+        _ => return None,
+    };
+    Some(TestHeader {
+        hash,
+        parent: H256::from(parent),
+        height,
+    })
+}
+
+/// Accounts that each hold nothing but a nonce. A transaction `(account, nonce, tag)` is valid
+/// when its nonce is the account's next one. The tag tells apart transactions that use the
+/// same nonce.
+#[cfg(test)]
+#[derive(Default)]
+struct Nonces;
+
+#[cfg(test)]
+impl StateMachine for Nonces {
+    type State = std::collections::BTreeMap<char, u64>;
+    type Transition = (char, u64, &'static str);
+
+    fn next_state(state: &Self::State, t: &Self::Transition) -> Self::State {
+        let mut next = state.clone();
+        let nonce = next.entry(t.0).or_insert(0);
+        if *nonce == t.1 {
+            *nonce += 1;
+        }
+        next
+    }
+}
+
+#[test]
+fn reorg_route_between_forks() {
+    assert_eq!(
+        tree_route(H256::from(13), H256::from(22), test_tree),
+        Some(TreeRoute {
+            retracted: [13, 12, 11].map(H256::from).to_vec(),
+            common_ancestor: 1.into(),
+            enacted: [21, 22].map(H256::from).to_vec(),
+        })
+    );
+}
+
+#[test]
+fn reorg_route_along_one_fork() {
+    assert_eq!(
+        tree_route(H256::from(11), H256::from(13), test_tree),
+        Some(TreeRoute {
+            retracted: vec![],
+            common_ancestor: 11.into(),
+            enacted: [12, 13].map(H256::from).to_vec(),
+        })
+    );
+    assert_eq!(tree_route(H256::from(11), H256::from(99), test_tree), None);
+}
+
+#[test]
+fn reorg_returns_retracted_transactions_to_the_pool() {
+    let state = Default::default();
+    let retracted = vec![vec![('a', 0, "x")], vec![('a', 1, "x")]];
+    let pending = vec![('a', 2, "x")];
+
+    let revalidated = revalidate::<Nonces>(&state, retracted, pending, &[]);
+    assert_eq!(
+        revalidated.valid,
+        vec![('a', 0, "x"), ('a', 1, "x"), ('a', 2, "x")]
+    );
+    assert_eq!(revalidated.dropped, 0);
+}
+
+#[test]
+fn reorg_drops_transactions_invalidated_by_the_new_fork() {
+    // The new fork spent account a's nonce 0 on a different transaction.
+    let on_new_fork = ('a', 0, "new");
+    let state = Nonces::next_state(&Default::default(), &on_new_fork);
+
+    let retracted = vec![vec![('a', 0, "old"), ('b', 0, "old")]];
+    let pending = vec![('a', 1, "old"), on_new_fork];
+
+    let revalidated = revalidate::<Nonces>(&state, retracted, pending, &[on_new_fork]);
+    assert_eq!(revalidated.valid, vec![('b', 0, "old"), ('a', 1, "old")]);
+    assert_eq!(revalidated.dropped, 1);
+}
+
+#[cfg(test)]
+use super::{LongestChain, SimplePool};
+#[cfg(test)]
+use crate::hashing::header_hash;
+
+#[cfg(test)]
+type NoncesClient = FullClient<(), Nonces, LongestChain, SimplePool<Nonces>>;
+
+/// Build a child of the given block with the given body, and import it. Returns its hash.
+#[cfg(test)]
+fn extend(client: &mut NoncesClient, parent: H256, body: Vec<(char, u64, &'static str)>) -> H256 {
+    let state = client.get_state(parent).unwrap();
+    let child = client
+        .get_block(parent)
+        .unwrap()
+        .child(&(), &state, body)
+        .unwrap();
+    let hash = header_hash(child.header());
+    assert!(client.import_block(child));
+    hash
+}
+
+#[test]
+fn reorg_maintains_the_pool_on_import() {
+    let mut client = NoncesClient::default();
+    let genesis = client.best_block();
+    client.submit_transaction(('a', 0, "old"));
+    client.submit_transaction(('b', 0, "old"));
+    let a1 = client.author_and_import_automatic_block().unwrap();
+    assert_eq!(client.pool_size(), 0);
+
+    // A longer fork that spent account a's nonce 0 on a different transaction. Once it is the
+    // best chain, only b's transaction is still valid, and it is back in the pool.
+    let b1 = extend(&mut client, genesis, vec![('a', 0, "new")]);
+    assert_eq!(client.best_block(), a1);
+    let b2 = extend(&mut client, b1, vec![]);
+    assert_eq!(client.best_block(), b2);
+    assert_eq!(client.pool_size(), 1);
+    assert!(client.pool_contains(('b', 0, "old")));
+
+    // The retracted fork grows longer again, and is re-imported as the best chain. It already
+    // includes b's transaction, and a's transaction from the other fork is no longer valid.
+    let a2 = extend(&mut client, a1, vec![]);
+    let a3 = extend(&mut client, a2, vec![]);
+    assert_eq!(client.best_block(), a3);
+    assert_eq!(client.pool_size(), 0);
+}
@@ -0,0 +1,899 @@
+//! Users rarely run their own node on the same machine as their wallet. Instead, they talk to
+//! a node over Remote Procedure Calls (RPC). Like most blockchain nodes, ours speaks JSON-RPC 2.0.
+//!
+//! The set of calls a node supports is captured by the `NodeApi` trait. The client implements it
+//! directly, and `RpcClient` implements it by forwarding every call to a remote node. This means
+//! code such as the wallet can be written once and work both in-process and over the network.
+//!
+//! Most calls only read the chain or submit transactions, and are safe to offer to anybody. A
+//! few, such as inserting keys or purging the pool, change how the node itself behaves. These
+//! unsafe methods are only served when the RPC server is bound to a loopback address, or when
+//! the caller presents the node's secret token. To keep a node that is exposed on a shared
+//! network responsive, every client address is also limited to a number of requests per second.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::forks::{ForkInfo, ForkTree};
+use super::http::{self, Request, Response};
+use super::keystore::{dev_address, dev_user};
+use super::network::reputation::PeerScore;
+use super::proof::StorageProof;
+use super::runtime::{AccountInfo, Runtime, SignedExtrinsic};
+use super::storage::StateError;
+use super::{Consensus, ForkChoice, FullClient, ImportBlock, TransactionPool};
+use crate::c1_state_machine::User;
+use crate::clock::{Clock, SystemClock};
+use crate::crypto::address::Address;
+use crate::hashing::H256;
+
+/// Everything that can go wrong when calling into a node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RpcError {
+    /// The node does not have the state for the block in question.
+    UnknownBlock,
+    /// The node had the state for the block in question, but has pruned it since.
+    StatePruned,
+    /// The node does not support the requested method.
+    MethodNotFound(String),
+    /// The parameters could not be decoded for the requested method.
+    InvalidParams(String),
+    /// The node could not be reached, or replied with something that is not JSON-RPC.
+    Transport(String),
+    /// The method is unsafe, and the caller is not allowed to call unsafe methods.
+    Unauthorized,
+    /// The caller has made too many requests recently.
+    RateLimited,
+    /// Any other error reported by the node.
+    Remote { code: i64, message: String },
+}
+
+impl RpcError {
+    /// The JSON-RPC error code for this error.
+    fn code(&self) -> i64 {
+        match self {
+            RpcError::UnknownBlock => 1000,
+            RpcError::StatePruned => 1003,
+            RpcError::MethodNotFound(_) => -32601,
+            RpcError::InvalidParams(_) => -32602,
+            RpcError::Transport(_) => -32603,
+            RpcError::Unauthorized => 1001,
+            RpcError::RateLimited => 1002,
+            RpcError::Remote { code, .. } => *code,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            RpcError::UnknownBlock => "unknown block".into(),
+            RpcError::StatePruned => "state pruned, ask an archive node".into(),
+            RpcError::MethodNotFound(method) => format!("method not found: {method}"),
+            RpcError::InvalidParams(reason) => format!("invalid params: {reason}"),
+            RpcError::Transport(reason) => format!("transport error: {reason}"),
+            RpcError::Unauthorized => "unsafe method requires authorization".into(),
+            RpcError::RateLimited => "too many requests".into(),
+            RpcError::Remote { message, .. } => message.clone(),
+        }
+    }
+
+    /// Reconstruct an error from the code and message found in a JSON-RPC error response.
+    fn from_code(code: i64, message: String) -> Self {
+        match code {
+            1000 => RpcError::UnknownBlock,
+            1001 => RpcError::Unauthorized,
+            1002 => RpcError::RateLimited,
+            1003 => RpcError::StatePruned,
+            _ => RpcError::Remote { code, message },
+        }
+    }
+}
+
+impl From<StateError> for RpcError {
+    fn from(e: StateError) -> Self {
+        match e {
+            StateError::UnknownBlock(_) => RpcError::UnknownBlock,
+            StateError::StatePruned { .. } => RpcError::StatePruned,
+        }
+    }
+}
+
+/// A summary of how the node is doing, as reported by `system_health`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Health {
+    /// The number of connected peers.
+    pub peers: usize,
+    /// Whether the node is currently catching up with its peers.
+    pub is_syncing: bool,
+    /// The height of our best block.
+    pub best_height: u64,
+    /// The height of the best block we know of on the network. Equal to `best_height` when we
+    /// have no peers, or have caught up with all of them.
+    pub target_height: u64,
+}
+
+impl Health {
+    /// Whether the node has caught up with the network and is ready to be used.
+    pub fn is_ready(&self) -> bool {
+        !self.is_syncing && self.best_height >= self.target_height
+    }
+}
+
+/// The calls that a node answers.
+pub trait NodeApi {
+    /// The hash of the node's current best block.
+    fn best_block_hash(&mut self) -> Result<H256, RpcError>;
+
+    /// The balance and nonce of the given account as of the node's best block.
+    fn account_info(&mut self, who: User) -> Result<AccountInfo, RpcError>;
+
+    /// The value of the given account as of the given block, with a Merkle proof against
+    /// that block's state root. Check it with `proof::verify_storage_proof`.
+    fn storage_proof(&mut self, who: User, at: H256) -> Result<StorageProof, RpcError>;
+
+    /// Submit an extrinsic to the node's transaction pool.
+    fn submit_extrinsic(&mut self, extrinsic: SignedExtrinsic) -> Result<(), RpcError>;
+
+    /// How the node is doing.
+    fn health(&mut self) -> Result<Health, RpcError>;
+
+    /// The reputation of every peer the node knows about. This is meant for debugging, so
+    /// nodes without networking need not support it.
+    fn peer_scores(&mut self) -> Result<Vec<PeerScore>, RpcError> {
+        Err(RpcError::MethodNotFound("system_peerScores".into()))
+    }
+
+    /// Every branch of the node's block tree. Nodes that do not keep the whole tree need not
+    /// support it.
+    fn forks(&mut self) -> Result<Vec<ForkInfo>, RpcError> {
+        Err(RpcError::MethodNotFound("chain_getForks".into()))
+    }
+
+    /// The whole block tree, for drawing. Nodes that do not keep the whole tree need not
+    /// support it.
+    fn block_tree(&mut self) -> Result<ForkTree, RpcError> {
+        Err(RpcError::MethodNotFound("chain_getBlockTree".into()))
+    }
+
+    /// Add the key for the given account to the node's keystore. This is an unsafe method.
+    fn insert_key(&mut self, _who: User) -> Result<(), RpcError> {
+        Err(RpcError::MethodNotFound("author_insertKey".into()))
+    }
+
+    /// Throw away every transaction in the pool, and return how many there were. This is an
+    /// unsafe method.
+    fn purge_pool(&mut self) -> Result<usize, RpcError> {
+        Err(RpcError::MethodNotFound("author_purgePool".into()))
+    }
+
+    /// Have the node's faucet fund the given account, and return how much it was given. Only
+    /// development nodes run a faucet, see the `faucet` module.
+    fn request_funds(&mut self, _who: User) -> Result<u64, RpcError> {
+        Err(RpcError::MethodNotFound("faucet_requestFunds".into()))
+    }
+}
+
+/// A client holds no keys of its own, so it does not support `insert_key`.
+impl<C, FC, P> NodeApi for FullClient<C, Runtime, FC, P>
+where
+    C: Consensus,
+    FC: ForkChoice<C>,
+    P: TransactionPool<Runtime>,
+{
+    fn best_block_hash(&mut self) -> Result<H256, RpcError> {
+        Ok(self.best_block())
+    }
+
+    fn account_info(&mut self, who: User) -> Result<AccountInfo, RpcError> {
+        let best = self.best_block();
+        self.get_state(best)
+            .map(|state| state.account(who))
+            .ok_or(RpcError::UnknownBlock)
+    }
+
+    /// Proofs at old blocks work even on pruned nodes, the state is replayed if it has to be.
+    fn storage_proof(&mut self, who: User, at: H256) -> Result<StorageProof, RpcError> {
+        Ok(self.state_at(at)?.storage_proof(who))
+    }
+
+    fn submit_extrinsic(&mut self, extrinsic: SignedExtrinsic) -> Result<(), RpcError> {
+        self.submit_transaction(extrinsic);
+        Ok(())
+    }
+
+    /// A client on its own has no peers, so it is never syncing and always at its target.
+    fn health(&mut self) -> Result<Health, RpcError> {
+        let best = self.best_block();
+        let best_height = self
+            .get_block(best)
+            .map(|block| block.header().height())
+            .ok_or(RpcError::UnknownBlock)?;
+        Ok(Health {
+            peers: 0,
+            is_syncing: false,
+            best_height,
+            target_height: best_height,
+        })
+    }
+
+    fn forks(&mut self) -> Result<Vec<ForkInfo>, RpcError> {
+        FullClient::forks(self).ok_or(RpcError::UnknownBlock)
+    }
+
+    fn block_tree(&mut self) -> Result<ForkTree, RpcError> {
+        FullClient::block_tree(self).ok_or(RpcError::UnknownBlock)
+    }
+
+    fn purge_pool(&mut self) -> Result<usize, RpcError> {
+        Ok(self.drain_pool().len())
+    }
+}
+
+/// A JSON-RPC request as it arrives over the wire.
+#[derive(Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Decode the single positional parameter of a call.
+fn single_param<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    let [param]: [Value; 1] = serde_json::from_value(params)
+        .map_err(|_| RpcError::InvalidParams("expected exactly one parameter".into()))?;
+    serde_json::from_value(param).map_err(|e| RpcError::InvalidParams(e.to_string()))
+}
+
+/// Decode a parameter that names an account. Accounts are named by their address, or, for
+/// convenience on development chains, by the name of the play user.
+fn account_param(param: Value) -> Result<User, RpcError> {
+    if let Ok(user) = serde_json::from_value(param.clone()) {
+        return Ok(user);
+    }
+    let address: Address =
+        serde_json::from_value(param).map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+    dev_user(&address)
+        .ok_or_else(|| RpcError::InvalidParams(format!("no account with address {address}")))
+}
+
+/// Decode the two positional parameters of a call.
+fn two_params<T, U>(params: Value) -> Result<(T, U), RpcError>
+where
+    T: for<'de> Deserialize<'de>,
+    U: for<'de> Deserialize<'de>,
+{
+    let [first, second]: [Value; 2] = serde_json::from_value(params)
+        .map_err(|_| RpcError::InvalidParams("expected exactly two parameters".into()))?;
+    let invalid = |e: serde_json::Error| RpcError::InvalidParams(e.to_string());
+    Ok((
+        serde_json::from_value(first).map_err(invalid)?,
+        serde_json::from_value(second).map_err(invalid)?,
+    ))
+}
+
+fn to_value<T: Serialize>(t: T) -> Result<Value, RpcError> {
+    serde_json::to_value(t).map_err(|e| RpcError::Transport(e.to_string()))
+}
+
+/// The methods that change how the node behaves, rather than reading the chain or submitting
+/// transactions. Only trusted callers may call these.
+pub const UNSAFE_METHODS: &[&str] = &["author_insertKey", "author_purgePool"];
+
+/// Whether the given method is one of the `UNSAFE_METHODS`.
+pub fn is_unsafe(method: &str) -> bool {
+    UNSAFE_METHODS.contains(&method)
+}
+
+/// Dispatch a single call by name to the given api. This does not check whether the caller may
+/// call unsafe methods; that is up to the caller of this function.
+pub fn dispatch<A: NodeApi>(api: &mut A, method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "chain_getBestBlockHash" => to_value(api.best_block_hash()?),
+        "chain_getForks" => to_value(api.forks()?),
+        "chain_getBlockTree" => to_value(api.block_tree()?),
+        "state_getAccount" => to_value(api.account_info(account_param(single_param(params)?)?)?),
+        "state_getStorageProof" => {
+            let (who, at) = two_params(params)?;
+            to_value(api.storage_proof(account_param(who)?, at)?)
+        }
+        "author_submitExtrinsic" => to_value(api.submit_extrinsic(single_param(params)?)?),
+        "system_health" => to_value(api.health()?),
+        "system_peerScores" => to_value(api.peer_scores()?),
+        "author_insertKey" => to_value(api.insert_key(single_param(params)?)?),
+        "author_purgePool" => to_value(api.purge_pool()?),
+        "faucet_requestFunds" => {
+            to_value(api.request_funds(account_param(single_param(params)?)?)?)
+        }
+        _ => Err(RpcError::MethodNotFound(method.to_string())),
+    }
+}
+
+/// The JSON-RPC response for a failed call.
+fn error_response(id: Value, e: &RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": e.code(), "message": e.message() },
+    })
+}
+
+/// Handle one raw JSON-RPC request body, and produce the raw response body. Unsafe methods are
+/// refused unless `allow_unsafe` is set.
+pub fn handle_request<A: NodeApi>(api: &mut A, body: &[u8], allow_unsafe: bool) -> Vec<u8> {
+    let response = match serde_json::from_slice::<RpcRequest>(body) {
+        Ok(request) if is_unsafe(&request.method) && !allow_unsafe => {
+            error_response(request.id, &RpcError::Unauthorized)
+        }
+        Ok(request) => match dispatch(api, &request.method, request.params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": request.id, "result": result }),
+            Err(e) => error_response(request.id, &e),
+        },
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32700, "message": format!("parse error: {e}") },
+        }),
+    };
+    response.to_string().into_bytes()
+}
+
+/// Answer a `GET /health` request. Orchestration scripts can poll this until the node is ready,
+/// which is signalled by status 200. A node that is not ready yet answers with status 503.
+fn health_response<A: NodeApi>(api: &mut A) -> Response {
+    let (status, body) = match api.health() {
+        Ok(health) => (
+            if health.is_ready() { 200 } else { 503 },
+            serde_json::to_vec(&health).expect("health always serializes"),
+        ),
+        Err(e) => (
+            503,
+            json!({ "error": e.message() }).to_string().into_bytes(),
+        ),
+    };
+    Response {
+        status,
+        content_type: "application/json",
+        body,
+    }
+}
+
+/// The most client addresses the rate limiter keeps track of before it forgets idle ones.
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+/// Limits how many requests each client may make, using a token bucket per client address.
+///
+/// Our HTTP server closes the connection after every request, so limiting per connection would
+/// achieve nothing. We limit per address instead.
+pub struct RateLimiter {
+    per_second: u32,
+    /// How many requests each client may still make, and when we last updated that number.
+    buckets: HashMap<IpAddr, (f64, Instant)>,
+}
+
+impl RateLimiter {
+    /// Allow every client `per_second` requests per second on average, and bursts of up to
+    /// `per_second` requests at once. A limit of zero disables rate limiting.
+    pub fn new(per_second: u32) -> Self {
+        RateLimiter {
+            per_second,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Whether the given client may make another request now. If so, the request is counted.
+    pub fn allow(&mut self, client: IpAddr, now: Instant) -> bool {
+        if self.per_second == 0 {
+            return true;
+        }
+        let capacity = f64::from(self.per_second);
+        if self.buckets.len() >= MAX_TRACKED_CLIENTS {
+            // A client that has been idle for a second has a full bucket again, so forgetting
+            // it changes nothing.
+            self.buckets
+                .retain(|_, (_, last)| now.saturating_duration_since(*last).as_secs() < 1);
+        }
+
+        let (tokens, last) = self.buckets.entry(client).or_insert((capacity, now));
+        let refill = now.saturating_duration_since(*last).as_secs_f64() * capacity;
+        *tokens = (*tokens + refill).min(capacity);
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Who may call which methods on an RPC server, and how often.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RpcPolicy {
+    /// Callers that send `Authorization: Bearer <token>` with this token may call unsafe
+    /// methods, no matter where the server is bound.
+    pub unsafe_token: Option<String>,
+    /// How many requests per second each client address may make. Zero means no limit.
+    pub rate_limit: u32,
+}
+
+impl RpcPolicy {
+    /// Whether a request with the given authorization header may call unsafe methods, on a
+    /// server that only listens on loopback addresses if `local_only` is set.
+    pub fn allows_unsafe(&self, local_only: bool, authorization: Option<&str>) -> bool {
+        let presented = authorization.and_then(|header| header.strip_prefix("Bearer "));
+        local_only || (self.unsafe_token.is_some() && presented == self.unsafe_token.as_deref())
+    }
+}
+
+/// Serve JSON-RPC over HTTP for the given api in a background thread, with the default policy:
+/// no token and no rate limit. The same server also answers `GET /health`.
+///
+/// The api is shared behind a mutex so that the rest of the node can keep using it
+/// while the server is running.
+pub fn serve<A>(
+    addr: impl ToSocketAddrs,
+    api: Arc<Mutex<A>>,
+) -> std::io::Result<(SocketAddr, JoinHandle<()>)>
+where
+    A: NodeApi + Send + 'static,
+{
+    serve_with_policy(addr, api, RpcPolicy::default())
+}
+
+/// Serve JSON-RPC over HTTP like `serve`, enforcing the given policy.
+pub fn serve_with_policy<A>(
+    addr: impl ToSocketAddrs,
+    api: Arc<Mutex<A>>,
+    policy: RpcPolicy,
+) -> std::io::Result<(SocketAddr, JoinHandle<()>)>
+where
+    A: NodeApi + Send + 'static,
+{
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+    let local_only = addrs.iter().all(|addr| addr.ip().is_loopback());
+    let limiter = Mutex::new(RateLimiter::new(policy.rate_limit));
+
+    http::serve(&addrs[..], move |request: Request| {
+        if let Some(peer) = request.peer {
+            let allowed = limiter
+                .lock()
+                .expect("rate limiter mutex poisoned")
+                .allow(peer, SystemClock.now());
+            if !allowed {
+                return Response {
+                    status: 429,
+                    content_type: "application/json",
+                    body: error_response(Value::Null, &RpcError::RateLimited)
+                        .to_string()
+                        .into_bytes(),
+                };
+            }
+        }
+
+        let allow_unsafe = policy.allows_unsafe(local_only, request.authorization.as_deref());
+        let mut api = api.lock().expect("rpc api mutex poisoned");
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/health") => health_response(&mut *api),
+            ("POST", _) => Response::ok(
+                "application/json",
+                handle_request(&mut *api, &request.body, allow_unsafe),
+            ),
+            _ => Response::not_found(),
+        }
+    })
+}
+
+/// Talks to a remote node over JSON-RPC.
+pub struct RpcClient {
+    addr: SocketAddr,
+    next_id: u64,
+    /// The token to present to the node, for calling unsafe methods.
+    token: Option<String>,
+}
+
+impl RpcClient {
+    /// Create a client for the node listening at the given address.
+    pub fn new(addr: SocketAddr) -> Self {
+        RpcClient {
+            addr,
+            next_id: 0,
+            token: None,
+        }
+    }
+
+    /// Present the given token with every call, so that unsafe methods can be called.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Make a raw call to the remote node.
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        self.next_id += 1;
+        let request =
+            json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params });
+        let authorization = self.token.as_ref().map(|token| format!("Bearer {token}"));
+        let (_, body) = http::post(
+            self.addr,
+            "/",
+            authorization.as_deref(),
+            request.to_string().as_bytes(),
+        )
+        .map_err(|e| RpcError::Transport(e.to_string()))?;
+        let mut response: Value =
+            serde_json::from_slice(&body).map_err(|e| RpcError::Transport(e.to_string()))?;
+
+        if let Some(error) = response.get("error") {
+            let code = error["code"].as_i64().unwrap_or(0);
+            let message = error["message"].as_str().unwrap_or_default().to_string();
+            return Err(RpcError::from_code(code, message));
+        }
+        Ok(response["result"].take())
+    }
+
+    fn call_typed<T: for<'de> Deserialize<'de>>(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, RpcError> {
+        let result = self.call(method, params)?;
+        serde_json::from_value(result).map_err(|e| RpcError::Transport(e.to_string()))
+    }
+}
+
+impl NodeApi for RpcClient {
+    fn best_block_hash(&mut self) -> Result<H256, RpcError> {
+        self.call_typed("chain_getBestBlockHash", json!([]))
+    }
+
+    fn account_info(&mut self, who: User) -> Result<AccountInfo, RpcError> {
+        self.call_typed("state_getAccount", json!([dev_address(who)]))
+    }
+
+    fn storage_proof(&mut self, who: User, at: H256) -> Result<StorageProof, RpcError> {
+        self.call_typed("state_getStorageProof", json!([dev_address(who), at]))
+    }
+
+    fn submit_extrinsic(&mut self, extrinsic: SignedExtrinsic) -> Result<(), RpcError> {
+        self.call_typed("author_submitExtrinsic", json!([extrinsic]))
+    }
+
+    fn health(&mut self) -> Result<Health, RpcError> {
+        self.call_typed("system_health", json!([]))
+    }
+
+    fn peer_scores(&mut self) -> Result<Vec<PeerScore>, RpcError> {
+        self.call_typed("system_peerScores", json!([]))
+    }
+
+    fn forks(&mut self) -> Result<Vec<ForkInfo>, RpcError> {
+        self.call_typed("chain_getForks", json!([]))
+    }
+
+    fn block_tree(&mut self) -> Result<ForkTree, RpcError> {
+        self.call_typed("chain_getBlockTree", json!([]))
+    }
+
+    fn insert_key(&mut self, who: User) -> Result<(), RpcError> {
+        self.call_typed("author_insertKey", json!([who]))
+    }
+
+    fn purge_pool(&mut self) -> Result<usize, RpcError> {
+        self.call_typed("author_purgePool", json!([]))
+    }
+
+    fn request_funds(&mut self, who: User) -> Result<u64, RpcError> {
+        self.call_typed("faucet_requestFunds", json!([dev_address(who)]))
+    }
+}
+
+/// A node api with canned answers so that we can test the RPC layer without a working client.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MockNode {
+    pub accounts: std::collections::BTreeMap<User, AccountInfo>,
+    /// The state of the best block, whose hash is always 7.
+    pub state: super::runtime::RuntimeState,
+    pub submitted: Vec<SignedExtrinsic>,
+    pub peers: Vec<PeerScore>,
+    pub forks: Vec<ForkInfo>,
+    pub health: Health,
+    pub keys: Vec<User>,
+}
+
+#[cfg(test)]
+impl NodeApi for MockNode {
+    fn best_block_hash(&mut self) -> Result<H256, RpcError> {
+        Ok(H256::from(7))
+    }
+
+    fn account_info(&mut self, who: User) -> Result<AccountInfo, RpcError> {
+        Ok(self.accounts.get(&who).copied().unwrap_or_default())
+    }
+
+    fn storage_proof(&mut self, who: User, at: H256) -> Result<StorageProof, RpcError> {
+        if at != H256::from(7) {
+            return Err(RpcError::UnknownBlock);
+        }
+        Ok(self.state.storage_proof(who))
+    }
+
+    fn submit_extrinsic(&mut self, extrinsic: SignedExtrinsic) -> Result<(), RpcError> {
+        self.submitted.push(extrinsic);
+        Ok(())
+    }
+
+    fn health(&mut self) -> Result<Health, RpcError> {
+        Ok(self.health)
+    }
+
+    fn peer_scores(&mut self) -> Result<Vec<PeerScore>, RpcError> {
+        Ok(self.peers.clone())
+    }
+
+    fn forks(&mut self) -> Result<Vec<ForkInfo>, RpcError> {
+        Ok(self.forks.clone())
+    }
+
+    fn insert_key(&mut self, who: User) -> Result<(), RpcError> {
+        self.keys.push(who);
+        Ok(())
+    }
+
+    fn purge_pool(&mut self) -> Result<usize, RpcError> {
+        Ok(std::mem::take(&mut self.submitted).len())
+    }
+}
+
+#[test]
+fn rpc_unknown_method() {
+    let mut node = MockNode::default();
+    let body = handle_request(
+        &mut node,
+        br#"{"jsonrpc":"2.0","id":1,"method":"nope","params":[]}"#,
+        false,
+    );
+    let response: Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(response["error"]["code"], -32601);
+    assert_eq!(response["id"], 1);
+}
+
+#[test]
+fn rpc_invalid_params() {
+    let mut node = MockNode::default();
+    let result = dispatch(&mut node, "state_getAccount", json!(["Dave"]));
+
+    assert!(matches!(result, Err(RpcError::InvalidParams(_))));
+}
+
+#[test]
+fn rpc_names_accounts_by_address() {
+    let mut node = MockNode::default();
+    node.accounts.insert(
+        User::Bob,
+        AccountInfo {
+            balance: 5,
+            nonce: 0,
+        },
+    );
+    let bob = dev_address(User::Bob).to_string();
+    assert_eq!(
+        dispatch(&mut node, "state_getAccount", json!([bob])),
+        dispatch(&mut node, "state_getAccount", json!(["Bob"]))
+    );
+
+    // A mistyped address is reported as such.
+    let typo = bob.replacen(&bob[3..4], if &bob[3..4] == "a" { "b" } else { "a" }, 1);
+    match dispatch(&mut node, "state_getAccount", json!([typo])) {
+        Err(RpcError::InvalidParams(reason)) => assert!(reason.contains("checksum"), "{reason}"),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[test]
+fn rpc_client_round_trip() {
+    let mut node = MockNode::default();
+    node.accounts.insert(
+        User::Alice,
+        AccountInfo {
+            balance: 50,
+            nonce: 3,
+        },
+    );
+    node.peers.push(PeerScore {
+        peer: super::network::PeerId(9),
+        score: -20,
+        banned: false,
+    });
+    node.forks.push(ForkInfo {
+        head: 7.into(),
+        height: 2,
+        fork_point: 1.into(),
+        length: 2,
+        work: 2,
+        is_best: true,
+        is_finalized: true,
+    });
+    let node = Arc::new(Mutex::new(node));
+    let (addr, _) = serve("127.0.0.1:0", node.clone()).unwrap();
+
+    let mut client = RpcClient::new(addr);
+    assert_eq!(client.best_block_hash(), Ok(H256::from(7)));
+    assert_eq!(
+        client.account_info(User::Alice),
+        Ok(AccountInfo {
+            balance: 50,
+            nonce: 3
+        })
+    );
+    assert_eq!(client.peer_scores().unwrap()[0].score, -20);
+    assert_eq!(client.forks().unwrap()[0].head, H256::from(7));
+    assert_eq!(
+        client.call("nope", json!([])),
+        Err(RpcError::Remote {
+            code: -32601,
+            message: "method not found: nope".into()
+        })
+    );
+}
+
+#[test]
+fn rpc_health_endpoint_reports_readiness() {
+    let node = MockNode {
+        health: Health {
+            peers: 2,
+            is_syncing: true,
+            best_height: 3,
+            target_height: 10,
+        },
+        ..Default::default()
+    };
+    let node = Arc::new(Mutex::new(node));
+    let (addr, _) = serve("127.0.0.1:0", node.clone()).unwrap();
+
+    let (status, body) = http::get(addr, "/health").unwrap();
+    assert_eq!(status, 503);
+    let health: Health = serde_json::from_slice(&body).unwrap();
+    assert_eq!(health.target_height, 10);
+
+    node.lock().unwrap().health = Health {
+        peers: 2,
+        is_syncing: false,
+        best_height: 10,
+        target_height: 10,
+    };
+    assert_eq!(http::get(addr, "/health").unwrap().0, 200);
+    assert_eq!(RpcClient::new(addr).health().unwrap().best_height, 10);
+}
+
+#[test]
+fn rpc_unsafe_methods_need_authorization() {
+    let mut node = MockNode::default();
+    let request = br#"{"jsonrpc":"2.0","id":1,"method":"author_insertKey","params":["Bob"]}"#;
+
+    let response: Value =
+        serde_json::from_slice(&handle_request(&mut node, request, false)).unwrap();
+    assert_eq!(response["error"]["code"], 1001);
+    assert!(node.keys.is_empty());
+
+    handle_request(&mut node, request, true);
+    assert_eq!(node.keys, vec![User::Bob]);
+}
+
+#[test]
+fn rpc_policy_accepts_loopback_or_token() {
+    let policy = RpcPolicy {
+        unsafe_token: Some("secret".into()),
+        rate_limit: 0,
+    };
+
+    assert!(policy.allows_unsafe(true, None));
+    assert!(policy.allows_unsafe(false, Some("Bearer secret")));
+    assert!(!policy.allows_unsafe(false, Some("Bearer guess")));
+    assert!(!policy.allows_unsafe(false, Some("secret")));
+    assert!(!RpcPolicy::default().allows_unsafe(false, None));
+}
+
+#[test]
+fn rpc_rate_limiter_refills_over_time() {
+    let mut limiter = RateLimiter::new(2);
+    let client: IpAddr = "10.0.0.1".parse().unwrap();
+    let other: IpAddr = "10.0.0.2".parse().unwrap();
+    let now = Instant::now();
+
+    assert!(limiter.allow(client, now));
+    assert!(limiter.allow(client, now));
+    assert!(!limiter.allow(client, now));
+    assert!(limiter.allow(other, now));
+    assert!(limiter.allow(client, now + std::time::Duration::from_millis(500)));
+    assert!(RateLimiter::new(0).allow(client, now));
+}
+
+#[test]
+fn rpc_server_enforces_policy() {
+    let node = Arc::new(Mutex::new(MockNode::default()));
+    let policy = RpcPolicy {
+        unsafe_token: Some("secret".into()),
+        rate_limit: 3,
+    };
+    // Bind to every interface, so that being local alone does not grant access.
+    let (addr, _) = serve_with_policy("0.0.0.0:0", node.clone(), policy).unwrap();
+    let addr = SocketAddr::from(([127, 0, 0, 1], addr.port()));
+
+    assert_eq!(
+        RpcClient::new(addr).insert_key(User::Bob),
+        Err(RpcError::Unauthorized)
+    );
+    assert_eq!(
+        RpcClient::new(addr)
+            .with_token("secret")
+            .insert_key(User::Bob),
+        Ok(())
+    );
+    assert_eq!(RpcClient::new(addr).best_block_hash(), Ok(H256::from(7)));
+    assert_eq!(
+        RpcClient::new(addr).best_block_hash(),
+        Err(RpcError::RateLimited)
+    );
+    assert_eq!(node.lock().unwrap().keys, vec![User::Bob]);
+}
+
+#[test]
+fn rpc_storage_proof_verifies() {
+    use super::proof::verify_storage_proof;
+    use super::runtime::RuntimeState;
+
+    let state = RuntimeState::genesis(&[(User::Bob, 25)]);
+    let state_root = crate::hashing::hash(&state);
+    let node = Arc::new(Mutex::new(MockNode {
+        state,
+        ..Default::default()
+    }));
+    let (addr, _) = serve("127.0.0.1:0", node).unwrap();
+    let mut client = RpcClient::new(addr);
+
+    let proof = client.storage_proof(User::Bob, H256::from(7)).unwrap();
+    assert_eq!(proof.value.balance, 25);
+    assert!(verify_storage_proof(state_root, User::Bob, &proof));
+    assert_eq!(
+        client.storage_proof(User::Bob, H256::from(8)),
+        Err(RpcError::UnknownBlock)
+    );
+}
+
+#[test]
+fn rpc_storage_proof_before_the_checkpoint_is_pruned() {
+    use super::runtime::RuntimeState;
+    use super::storage::StorageMode;
+    use super::{LongestChain, SimplePool};
+
+    let genesis = RuntimeState::genesis(&[(User::Bob, 25)]);
+    let mut node = FullClient::<(), Runtime, LongestChain, SimplePool<Runtime>>::new(genesis)
+        .with_state_pruning(StorageMode::Pruned, 2);
+    let mut hashes = Vec::new();
+    for height in 1..=6 {
+        hashes.push(node.author_and_import_automatic_block().unwrap());
+        if height == 2 {
+            // The state of block #2 is kept from now on, and no state before it.
+            assert!(node.manually_finalize_block(hashes[1]));
+        }
+    }
+    let node = Arc::new(Mutex::new(node));
+    let (addr, _) = serve("127.0.0.1:0", node).unwrap();
+    let mut client = RpcClient::new(addr);
+
+    assert_eq!(
+        client.storage_proof(User::Bob, hashes[0]),
+        Err(RpcError::StatePruned)
+    );
+    let proof = client.storage_proof(User::Bob, hashes[3]).unwrap();
+    assert_eq!(proof.value.balance, 25);
+}
@@ -0,0 +1,257 @@
+//! Throughout this chapter the client is generic over the state machine. That is what makes it a
+//! framework. But a node that actually runs on a network has to pick one concrete state machine.
+//! In Substrate terminology, that state machine is known as the node's _runtime_.
+//!
+//! Our runtime is the accounted currency from chapter 1, extended with the two things that any
+//! public currency needs before strangers can use it:
+//! * Signatures, so that only the owner of an account can spend from it.
+//! * Nonces, so that a transaction that was signed once can not be replayed over and over.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use super::keystore::Signature;
+use crate::c1_state_machine::{AccountedCurrency, AccountingTransaction, StateMachine, User};
+use crate::hashing::{hash, H256};
+use crate::trie::Trie;
+
+/// Everything the runtime knows about a single account.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AccountInfo {
+    /// The account's balance.
+    pub balance: u64,
+    /// The number of extrinsics this account has had executed so far. The next extrinsic
+    /// signed by this account must carry exactly this nonce.
+    pub nonce: u64,
+}
+
+/// Every account that the runtime keeps storage for.
+pub const ACCOUNTS: [User; 3] = [User::Alice, User::Bob, User::Charlie];
+
+/// The key under which the given account is stored. Keys are hashed, as in Substrate, so that
+/// they spread out evenly over the trie.
+pub fn account_key(who: User) -> H256 {
+    hash(&("account", who))
+}
+
+/// The complete runtime state.
+///
+/// Every account with a balance or a nonce is stored in a Patricia trie, so that the state
+/// root commits to the full key-value state, and single accounts can be proven to light clients.
+/// Accounts that hold nothing are left out. It serializes as a map from user to account.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    from = "BTreeMap<User, AccountInfo>",
+    into = "BTreeMap<User, AccountInfo>"
+)]
+pub struct RuntimeState {
+    accounts: Trie<AccountInfo>,
+}
+
+impl From<BTreeMap<User, AccountInfo>> for RuntimeState {
+    fn from(accounts: BTreeMap<User, AccountInfo>) -> Self {
+        let mut state = RuntimeState::default();
+        for (who, info) in accounts {
+            state.set_account(who, info);
+        }
+        state
+    }
+}
+
+impl From<RuntimeState> for BTreeMap<User, AccountInfo> {
+    fn from(state: RuntimeState) -> Self {
+        ACCOUNTS
+            .iter()
+            .map(|&who| (who, state.account(who)))
+            .filter(|(_, info)| *info != AccountInfo::default())
+            .collect()
+    }
+}
+
+/// The state hashes as its trie root, so that the state root the client puts in headers can
+/// be used to verify storage proofs. See the `proof` module.
+impl std::hash::Hash for RuntimeState {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.storage_root().hash(state);
+    }
+}
+
+impl RuntimeState {
+    /// Create a genesis state in which the given users are endowed with the given balances.
+    pub fn genesis(endowments: &[(User, u64)]) -> Self {
+        let mut state = RuntimeState::default();
+        for &(who, balance) in endowments {
+            state.set_account(who, AccountInfo { balance, nonce: 0 });
+        }
+        state
+    }
+
+    /// Look up the current information about the given account.
+    pub fn account(&self, who: User) -> AccountInfo {
+        self.accounts
+            .get(account_key(who).as_bytes())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The sum of every account's balance. Only minting and burning may change it.
+    pub fn total_issuance(&self) -> u128 {
+        ACCOUNTS
+            .iter()
+            .map(|&who| u128::from(self.account(who).balance))
+            .sum()
+    }
+
+    /// Overwrite the information about the given account. Runtime extensions like the `htlc`
+    /// module use this to hold money on behalf of their users.
+    pub(super) fn set_account(&mut self, who: User, info: AccountInfo) {
+        let key = account_key(who);
+        if info == AccountInfo::default() {
+            self.accounts.remove(key.as_bytes());
+        } else {
+            self.accounts.insert(key.as_bytes(), info);
+        }
+    }
+
+    /// The trie that holds every account.
+    pub fn accounts(&self) -> &Trie<AccountInfo> {
+        &self.accounts
+    }
+}
+
+/// A currency transaction wrapped up with everything the runtime needs to authorize it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SignedExtrinsic {
+    /// The account that signed, and pays for, this extrinsic.
+    pub signer: User,
+    /// Must match the signer's current nonce for the extrinsic to be executed.
+    pub nonce: u64,
+    /// The actual currency operation to perform.
+    pub call: AccountingTransaction,
+    /// The signer's signature over the signing payload.
+    pub signature: Signature,
+}
+
+/// Calculate the hash that a signer must sign to authorize the given call with the given nonce.
+pub fn signing_payload(signer: User, nonce: u64, call: &AccountingTransaction) -> H256 {
+    hash(&(signer, nonce, call))
+}
+
+/// The account on whose behalf a call is made. A signed extrinsic is only valid when this
+/// account is also the signer.
+pub(super) fn origin(call: &AccountingTransaction) -> User {
+    match call {
+        AccountingTransaction::Mint { minter, .. } => *minter,
+        AccountingTransaction::Burn { burner, .. } => *burner,
+        AccountingTransaction::Transfer { sender, .. } => *sender,
+    }
+}
+
+/// The node runtime. Minting is still permissionless here, just like in chapter 1.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Runtime;
+
+impl StateMachine for Runtime {
+    type State = RuntimeState;
+    type Transition = SignedExtrinsic;
+
+    /// Invalid extrinsics (bad signature, wrong nonce, or signed by someone other than the
+    /// origin of the call) leave the state untouched, just like invalid transitions in chapter 1.
+    fn next_state(starting_state: &RuntimeState, t: &SignedExtrinsic) -> RuntimeState {
+        let payload = signing_payload(t.signer, t.nonce, &t.call);
+        if !t.signature.verify(t.signer, payload)
+            || t.nonce != starting_state.account(t.signer).nonce
+            || origin(&t.call) != t.signer
+        {
+            return starting_state.clone();
+        }
+
+        let balances: HashMap<User, u64> = ACCOUNTS
+            .iter()
+            .map(|&who| (who, starting_state.account(who).balance))
+            .filter(|(_, balance)| *balance > 0)
+            .collect();
+        let balances = AccountedCurrency::next_state(&balances, &t.call);
+
+        let mut state = starting_state.clone();
+        for who in ACCOUNTS {
+            let mut info = starting_state.account(who);
+            info.balance = balances.get(&who).copied().unwrap_or(0);
+            if who == t.signer {
+                info.nonce = t.nonce + 1;
+            }
+            state.set_account(who, info);
+        }
+        state
+    }
+
+    fn human_name() -> String {
+        "Signed Accounted Currency".into()
+    }
+}
+
+#[cfg(test)]
+use super::keystore::Keystore;
+
+#[cfg(test)]
+fn signed_transfer(signer: User, nonce: u64, sender: User) -> SignedExtrinsic {
+    let call = AccountingTransaction::Transfer {
+        sender,
+        receiver: User::Charlie,
+        amount: 10,
+    };
+    let signature = Keystore::dev()
+        .sign(signer, signing_payload(signer, nonce, &call))
+        .unwrap();
+    SignedExtrinsic {
+        signer,
+        nonce,
+        call,
+        signature,
+    }
+}
+
+#[test]
+fn runtime_genesis_skips_empty_accounts() {
+    let state = RuntimeState::genesis(&[(User::Alice, 100), (User::Bob, 0)]);
+
+    assert_eq!(
+        state.account(User::Alice),
+        AccountInfo {
+            balance: 100,
+            nonce: 0
+        }
+    );
+    assert_eq!(state.accounts.len(), 1);
+}
+
+#[test]
+fn runtime_rejects_wrong_nonce() {
+    let state = RuntimeState::genesis(&[(User::Alice, 100)]);
+    let end = Runtime::next_state(&state, &signed_transfer(User::Alice, 1, User::Alice));
+
+    assert_eq!(end, state);
+}
+
+#[test]
+fn runtime_rejects_spending_on_behalf_of_someone_else() {
+    let state = RuntimeState::genesis(&[(User::Alice, 100)]);
+    let end = Runtime::next_state(&state, &signed_transfer(User::Bob, 0, User::Alice));
+
+    assert_eq!(end, state);
+}
+
+#[test]
+fn runtime_rejects_tampered_call() {
+    let state = RuntimeState::genesis(&[(User::Alice, 100)]);
+    let mut extrinsic = signed_transfer(User::Alice, 0, User::Alice);
+    extrinsic.call = AccountingTransaction::Transfer {
+        sender: User::Alice,
+        receiver: User::Charlie,
+        amount: 100,
+    };
+    let end = Runtime::next_state(&state, &extrinsic);
+
+    assert_eq!(end, state);
+}
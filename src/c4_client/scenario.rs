@@ -0,0 +1,522 @@
+//! End-to-end tests of consensus and fork choice tend to be long. Set up some nodes, author a few
+//! blocks here, cut the network there, run the clock, and then dig through the nodes to check
+//! what happened. The story the test tells gets lost between the lines that make it happen.
+//!
+//! A `Scenario` tells the story instead, and leaves making it happen to the simulator:
+//!
+//! ```ignore
+//! Scenario::new()
+//!     .node("alice").mines(2)
+//!     .then_partition(&[&["alice"], &["bob", "carol"]])
+//!     .node("alice").mines(1)
+//!     .node("bob").mines(2)
+//!     .then_heal()
+//!     .then_sync()
+//!     .expect_reorg_at(3)
+//!     .expect_converged()
+//!     .run(make_node)
+//!     .unwrap();
+//! ```
+//!
+//! Nodes are named, and declared by their first mention. `node` also picks the node that the
+//! steps after it are about, until the next `node`. A scenario is compiled into a scripted
+//! simulator run: every block is authored exactly when the scenario says so, and after every
+//! block the clock moves on by one block time, so that the network can deliver it. Partitions
+//! are turned into partitions of the network conditions, which start and heal at the point of
+//! the scenario where they appear in it.
+//!
+//! The simulator has no sync protocol, so nodes on either side of a healed partition never hear
+//! about the blocks they missed. `then_sync` stands in for it, and has every node import every
+//! block it is missing.
+//!
+//! Expectations are checked as the run gets to them, and the first one that does not hold ends
+//! the run with an error.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::authoring::AuthorBlocks;
+use super::devnet::{LinkConditions, NetworkConditions, NodeInfo, Partition};
+use super::network::sync::LocalChain;
+use super::network::{ChainBlock, ChainHeader};
+use super::simulator::Simulator;
+use super::{Consensus, ForkChoice, FullClient, StateMachine};
+use crate::hashing::H256;
+
+/// A node whose best block a scenario can check.
+pub trait BestBlock {
+    fn best_block_hash(&self) -> H256;
+}
+
+impl<B: BestBlock + ?Sized> BestBlock for Box<B> {
+    fn best_block_hash(&self) -> H256 {
+        (**self).best_block_hash()
+    }
+}
+
+impl<C, SM, FC, P> BestBlock for FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+    FC: ForkChoice<C>,
+{
+    fn best_block_hash(&self) -> H256 {
+        self.best_block()
+    }
+}
+
+/// A single step of a scenario. Nodes are given by their index.
+#[derive(Clone, Debug)]
+enum Step {
+    Mine { node: usize, blocks: u64 },
+    Wait(Duration),
+    Partition(Vec<Vec<usize>>),
+    Heal,
+    Sync,
+    ExpectHeight { node: usize, height: u64 },
+    ExpectConverged,
+    ExpectReorgAt(u64),
+}
+
+/// A scripted story of nodes authoring blocks on a network, with expectations along the way.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    names: Vec<String>,
+    /// The node that the steps are currently about.
+    focus: Option<usize>,
+    steps: Vec<Step>,
+    block_time: Duration,
+    link: LinkConditions,
+    seed: u64,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            names: Vec::new(),
+            focus: None,
+            steps: Vec::new(),
+            block_time: Duration::from_secs(1),
+            link: LinkConditions::new(Duration::from_millis(100)),
+            seed: 0,
+        }
+    }
+}
+
+impl Scenario {
+    /// An empty scenario, with a block time of one second and 100ms of latency between nodes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_block_time(mut self, block_time: Duration) -> Self {
+        self.block_time = block_time;
+        self
+    }
+
+    /// Use the given conditions on every link between two nodes.
+    pub fn with_link(mut self, link: LinkConditions) -> Self {
+        self.link = link;
+        self
+    }
+
+    /// The seed of the network conditions, which only matters when they are random.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The index of the node with the given name, which is declared if it is new.
+    fn index(&mut self, name: &str) -> usize {
+        match self.names.iter().position(|known| known == name) {
+            Some(index) => index,
+            None => {
+                self.names.push(name.into());
+                self.names.len() - 1
+            }
+        }
+    }
+
+    /// The node that the steps are currently about.
+    ///
+    /// Panics if no node was picked yet, since that is a mistake in the scenario.
+    fn focus(&self, step: &str) -> usize {
+        self.focus
+            .unwrap_or_else(|| panic!("`{step}` is about a node, pick one with `node` first"))
+    }
+
+    /// Declare the node with the given name, unless it was declared already, and make the steps
+    /// that follow about it.
+    pub fn node(mut self, name: &str) -> Self {
+        self.focus = Some(self.index(name));
+        self
+    }
+
+    /// The current node authors the given number of blocks, one block time apart.
+    pub fn mines(mut self, blocks: u64) -> Self {
+        let node = self.focus("mines");
+        self.steps.push(Step::Mine { node, blocks });
+        self
+    }
+
+    /// Let the network run for the given time, without anyone authoring.
+    pub fn then_wait(mut self, time: Duration) -> Self {
+        self.steps.push(Step::Wait(time));
+        self
+    }
+
+    /// Split the network into the given groups of nodes, healing any partition that is still in
+    /// place. Nodes in none of the groups form one more group together.
+    pub fn then_partition(mut self, groups: &[&[&str]]) -> Self {
+        let groups = groups
+            .iter()
+            .map(|group| group.iter().map(|name| self.index(name)).collect())
+            .collect();
+        self.steps.push(Step::Partition(groups));
+        self
+    }
+
+    /// Heal the partition that is in place, if there is one.
+    pub fn then_heal(mut self) -> Self {
+        self.steps.push(Step::Heal);
+        self
+    }
+
+    /// Every node imports every block it has missed, as if it had synced with its peers.
+    pub fn then_sync(mut self) -> Self {
+        self.steps.push(Step::Sync);
+        self
+    }
+
+    /// Expect the best block of the current node to be at the given height.
+    pub fn expect_height(mut self, height: u64) -> Self {
+        let node = self.focus("expect_height");
+        self.steps.push(Step::ExpectHeight { node, height });
+        self
+    }
+
+    /// Expect every node to agree on the best block.
+    pub fn expect_converged(mut self) -> Self {
+        self.steps.push(Step::ExpectConverged);
+        self
+    }
+
+    /// Expect that some node has, by now, gone through a reorg that took its best block at the
+    /// given height, and none below it, off its best chain.
+    pub fn expect_reorg_at(mut self, height: u64) -> Self {
+        self.steps.push(Step::ExpectReorgAt(height));
+        self
+    }
+
+    /// The network conditions that the scenario is run under. Every partition starts when the
+    /// clock gets to its step, and heals when the clock gets to the next partition or heal.
+    fn conditions(&self) -> NetworkConditions {
+        let mut conditions = NetworkConditions::new(self.seed).with_default(self.link);
+        let mut now = Duration::ZERO;
+        let mut open: Option<Partition> = None;
+        for step in &self.steps {
+            match step {
+                Step::Mine { blocks, .. } => now += self.block_time * *blocks as u32,
+                Step::Wait(time) => now += *time,
+                Step::Partition(_) | Step::Heal => {
+                    if let Some(partition) = open.take() {
+                        conditions = conditions.with_partition(partition.heal_at(now));
+                    }
+                    if let Step::Partition(groups) = step {
+                        open = Some(Partition::new(groups.clone()).starting_at(now));
+                    }
+                }
+                _ => {}
+            }
+        }
+        match open {
+            Some(partition) => conditions.with_partition(partition),
+            None => conditions,
+        }
+    }
+
+    /// Run the scenario on nodes made by `make_node`, in the order in which they were declared.
+    pub fn run<N>(
+        self,
+        make_node: impl FnMut(&NodeInfo) -> N,
+    ) -> Result<ScenarioRun<N>, ScenarioError>
+    where
+        N: AuthorBlocks + LocalChain<N::Block> + BestBlock,
+        N::Block: ChainBlock + Clone,
+    {
+        let simulator = Simulator::scripted(self.names.len(), self.conditions(), make_node);
+        let best = (0..self.names.len())
+            .map(|index| simulator.node(index).best_block_hash())
+            .collect();
+        let mut run = ScenarioRun {
+            simulator,
+            names: self.names,
+            blocks: Vec::new(),
+            chain: BTreeMap::new(),
+            best,
+            reorgs: Vec::new(),
+        };
+        for (index, step) in self.steps.into_iter().enumerate() {
+            run.run_step(index, step, self.block_time)?;
+        }
+        Ok(run)
+    }
+}
+
+/// A node switching from one best chain to another that does not contain its old best block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reorg {
+    pub at: Duration,
+    pub node: usize,
+    /// The height of the first block that is no longer on the best chain.
+    pub height: u64,
+    /// The number of blocks that are no longer on the best chain.
+    pub depth: u64,
+}
+
+/// Why a scenario failed. Steps are counted from zero, in the order they were added.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScenarioError {
+    /// The node was told to author a block, but was not eligible to.
+    CouldNotAuthor { step: usize, node: String },
+    /// An expectation did not hold.
+    Unexpected {
+        step: usize,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// A scenario that ran to the end, for a closer look at what happened.
+pub struct ScenarioRun<N: AuthorBlocks> {
+    simulator: Simulator<N>,
+    names: Vec<String>,
+    /// Every block authored so far, in order, so that any parent comes before its children.
+    blocks: Vec<N::Block>,
+    /// The parent and height of every block authored so far.
+    chain: BTreeMap<H256, (H256, u64)>,
+    /// The best block of every node, as last seen.
+    best: Vec<H256>,
+    reorgs: Vec<Reorg>,
+}
+
+impl<N> ScenarioRun<N>
+where
+    N: AuthorBlocks + LocalChain<N::Block> + BestBlock,
+    N::Block: ChainBlock + Clone,
+{
+    pub fn simulator(&self) -> &Simulator<N> {
+        &self.simulator
+    }
+
+    /// The node with the given name.
+    ///
+    /// Panics if the scenario has no such node.
+    pub fn node(&self, name: &str) -> &N {
+        let index = self.names.iter().position(|known| known == name);
+        self.simulator
+            .node(index.unwrap_or_else(|| panic!("the scenario has no node `{name}`")))
+    }
+
+    /// Every reorg any node went through, in order.
+    pub fn reorgs(&self) -> &[Reorg] {
+        &self.reorgs
+    }
+
+    /// The height of the given block. Blocks that were not authored during the scenario, such
+    /// as genesis, are at height zero.
+    fn height(&self, hash: H256) -> u64 {
+        self.chain.get(&hash).map_or(0, |&(_, height)| height)
+    }
+
+    fn run_step(
+        &mut self,
+        index: usize,
+        step: Step,
+        block_time: Duration,
+    ) -> Result<(), ScenarioError> {
+        let unexpected = |expected: String, actual: String| ScenarioError::Unexpected {
+            step: index,
+            expected,
+            actual,
+        };
+        match step {
+            Step::Mine { node, blocks } => {
+                for _ in 0..blocks {
+                    let Some(block) = self.simulator.author(node) else {
+                        return Err(ScenarioError::CouldNotAuthor {
+                            step: index,
+                            node: self.names[node].clone(),
+                        });
+                    };
+                    let header = block.header();
+                    self.chain
+                        .insert(header.hash(), (header.parent_hash(), header.height()));
+                    self.blocks.push(block);
+                    self.observe();
+                    self.advance(block_time);
+                }
+            }
+            Step::Wait(time) => self.advance(time),
+            // The network conditions already take care of these.
+            Step::Partition(_) | Step::Heal => {}
+            Step::Sync => {
+                for node in 0..self.names.len() {
+                    for block in &self.blocks {
+                        let local = self.simulator.node_mut(node);
+                        if !local.is_known(block.header().hash()) {
+                            local.import(block.clone());
+                        }
+                    }
+                }
+                self.observe();
+            }
+            Step::ExpectHeight { node, height } => {
+                let actual = self.height(self.best[node]);
+                if actual != height {
+                    return Err(unexpected(
+                        format!("{} at height {height}", self.names[node]),
+                        format!("{} at height {actual}", self.names[node]),
+                    ));
+                }
+            }
+            Step::ExpectConverged => {
+                if self.best.iter().any(|best| *best != self.best[0]) {
+                    let bests = self.names.iter().zip(&self.best);
+                    return Err(unexpected(
+                        "every node on the same best block".into(),
+                        format!("{:?}", bests.collect::<Vec<_>>()),
+                    ));
+                }
+            }
+            Step::ExpectReorgAt(height) => {
+                if !self.reorgs.iter().any(|reorg| reorg.height == height) {
+                    return Err(unexpected(
+                        format!("a reorg at height {height}"),
+                        format!("{:?}", self.reorgs),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the simulator for the given time, looking out for reorgs after every event.
+    fn advance(&mut self, time: Duration) {
+        let end = self.simulator.now() + time;
+        while self.simulator.step(end) {
+            self.observe();
+        }
+        self.simulator.run_until(end);
+    }
+
+    /// Check every node for a new best block, and note it if that is a reorg.
+    fn observe(&mut self) {
+        for node in 0..self.best.len() {
+            let best = self.simulator.node(node).best_block_hash();
+            if best == self.best[node] {
+                continue;
+            }
+            if let Some(height) = self.retracted(self.best[node], best) {
+                self.reorgs.push(Reorg {
+                    at: self.simulator.now(),
+                    node,
+                    height,
+                    depth: self.height(self.best[node]) + 1 - height,
+                });
+            }
+            self.best[node] = best;
+        }
+    }
+
+    /// The height of the first block of the old chain that the new chain does not contain, or
+    /// None if the new chain contains the old one completely.
+    fn retracted(&self, mut old: H256, mut new: H256) -> Option<u64> {
+        let parent = |hash| self.chain.get(&hash).map(|&(parent, _)| parent);
+        let mut retracted = None;
+        while old != new {
+            let (old_height, new_height) = (self.height(old), self.height(new));
+            if old_height >= new_height {
+                retracted = Some(old_height);
+                old = parent(old)?;
+            }
+            if new_height >= old_height {
+                new = parent(new)?;
+            }
+        }
+        retracted
+    }
+}
+
+#[cfg(test)]
+use super::simulator::LongestChainNode;
+
+#[cfg(test)]
+impl BestBlock for LongestChainNode {
+    fn best_block_hash(&self) -> H256 {
+        self.best.hash
+    }
+}
+
+/// Alice builds on the common chain on her own, while Bob and Carol build a longer chain.
+#[cfg(test)]
+fn split_brain() -> Scenario {
+    Scenario::new()
+        .node("alice")
+        .mines(2)
+        .then_partition(&[&["alice"], &["bob", "carol"]])
+        .node("alice")
+        .mines(1)
+        .node("bob")
+        .mines(2)
+        .node("carol")
+        .expect_height(4)
+        .then_heal()
+}
+
+#[test]
+fn scenario_partition_heals_into_a_reorg() {
+    let run = split_brain()
+        .then_sync()
+        .expect_reorg_at(3)
+        .expect_converged()
+        .node("alice")
+        .expect_height(4)
+        .run(LongestChainNode::new)
+        .unwrap();
+
+    // Only Alice had to give up a block.
+    assert_eq!(run.reorgs().len(), 1);
+    let reorg = run.reorgs()[0];
+    assert_eq!((reorg.node, reorg.height, reorg.depth), (0, 3, 1));
+    assert_eq!(run.node("alice").best.author, 1);
+    assert_eq!(run.simulator().now(), Duration::from_secs(5));
+}
+
+#[test]
+fn scenario_reports_the_first_failed_expectation() {
+    // Without a sync, Alice never hears about the blocks she missed.
+    let error = split_brain()
+        .expect_converged()
+        .expect_reorg_at(3)
+        .run(LongestChainNode::new)
+        .err()
+        .unwrap();
+    assert!(matches!(error, ScenarioError::Unexpected { step: 6, .. }));
+
+    let error = Scenario::new()
+        .node("alice")
+        .mines(1)
+        .expect_height(2)
+        .run(LongestChainNode::new)
+        .err()
+        .unwrap();
+    assert_eq!(
+        error,
+        ScenarioError::Unexpected {
+            step: 1,
+            expected: "alice at height 2".into(),
+            actual: "alice at height 1".into(),
+        }
+    );
+}
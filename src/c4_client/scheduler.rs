@@ -0,0 +1,347 @@
+//! Some things should happen later: a payment that is due next month, or a parameter change
+//! that governance wants to give everybody time to prepare for. Rather than trusting somebody
+//! to submit the extrinsic at the right time, users can hand it to the chain's scheduler, which
+//! executes it on its own once its block comes.
+//!
+//! A user schedules a call for a future height with a signed `Schedule` extrinsic. When that
+//! height is reached, the call is executed on the user's behalf, before any of the block's
+//! extrinsics, like Substrate's scheduler does in `on_initialize`. Its owner can cancel it until
+//! then.
+//!
+//! Scheduled calls take up block space like any other, and there is no limit to how many of them
+//! can be due at the same height. So every block only runs as many of them as fit into
+//! `MAX_SCHEDULED_WEIGHT`, in the order in which they became due. The rest stay on the agenda,
+//! overdue, and run in the following blocks.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use super::keystore::Signature;
+use super::runtime::{origin, Runtime, RuntimeState, SignedExtrinsic, ACCOUNTS};
+use super::weight::{Weighed, Weight, MAX_BLOCK_WEIGHT};
+use crate::c1_state_machine::{AccountedCurrency, AccountingTransaction, StateMachine, User};
+use crate::hashing::{hash_of, H256};
+
+/// How much the scheduled calls that run in a single block may weigh together.
+pub const MAX_SCHEDULED_WEIGHT: Weight = MAX_BLOCK_WEIGHT / 4;
+
+/// A call on the agenda.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Task {
+    /// The user who scheduled the call, and on whose behalf it is made.
+    pub owner: User,
+    /// The height at which the call is due.
+    pub when: u64,
+    pub call: AccountingTransaction,
+}
+
+/// An extrinsic that can be applied to a chain with a scheduler.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SchedulerExtrinsic {
+    /// An ordinary extrinsic.
+    Apply(SignedExtrinsic),
+    /// Schedule a call to be made on behalf of the signer at the given height. The nonce is the
+    /// signer's account nonce, just like in a signed extrinsic.
+    Schedule {
+        who: User,
+        nonce: u64,
+        when: u64,
+        call: AccountingTransaction,
+        signature: Signature,
+    },
+    /// Cancel a task that the signer scheduled and that did not run yet.
+    Cancel {
+        who: User,
+        nonce: u64,
+        id: u64,
+        signature: Signature,
+    },
+}
+
+/// Calculate the hash that a user must sign to schedule a call.
+pub fn schedule_payload(who: User, nonce: u64, when: u64, call: &AccountingTransaction) -> H256 {
+    hash_of("schedule", &(who, nonce, when, call))
+}
+
+/// Calculate the hash that a user must sign to cancel a task.
+pub fn cancel_payload(who: User, nonce: u64, id: u64) -> H256 {
+    hash_of("cancel task", &(who, nonce, id))
+}
+
+/// The runtime state, along with the agenda of the scheduler.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SchedulerState {
+    pub runtime: RuntimeState,
+    /// The tasks that did not run yet, by their id.
+    pub agenda: BTreeMap<u64, Task>,
+    /// The id the next task gets.
+    pub next_task: u64,
+}
+
+impl SchedulerState {
+    /// A genesis state with the given runtime state and nothing on the agenda.
+    pub fn genesis(runtime: RuntimeState) -> Self {
+        SchedulerState {
+            runtime,
+            ..SchedulerState::default()
+        }
+    }
+
+    /// Execute the block at the given height on top of this state. First, the tasks that are
+    /// due run, for as long as they fit into `MAX_SCHEDULED_WEIGHT`. Then the block's extrinsics
+    /// are executed. Invalid extrinsics leave the state untouched, just like in the runtime:
+    /// calls scheduled for the past or on behalf of somebody else, and cancellations of tasks
+    /// of somebody else.
+    ///
+    /// A task runs even if its call fails, like a transfer of more than its owner has by then.
+    /// Either way, it is taken off the agenda.
+    pub fn execute_block(&self, height: u64, extrinsics: &[SchedulerExtrinsic]) -> SchedulerState {
+        let mut state = self.clone();
+        state.run_agenda(height);
+        for extrinsic in extrinsics {
+            state.execute(height, extrinsic);
+        }
+        state
+    }
+
+    /// Run the tasks that are due at the given height, the oldest first. A task that does not
+    /// fit into the remaining weight anymore stops the run, so that no later task jumps ahead.
+    fn run_agenda(&mut self, height: u64) {
+        let mut due: Vec<(u64, u64)> = self
+            .agenda
+            .iter()
+            .filter(|(_, task)| task.when <= height)
+            .map(|(&id, task)| (task.when, id))
+            .collect();
+        due.sort_unstable();
+
+        let mut remaining = MAX_SCHEDULED_WEIGHT;
+        for (_, id) in due {
+            let Some(rest) = remaining.checked_sub(self.agenda[&id].call.weight()) else {
+                break;
+            };
+            remaining = rest;
+            if let Some(task) = self.agenda.remove(&id) {
+                self.runtime = dispatch(&self.runtime, &task.call);
+            }
+        }
+    }
+
+    fn execute(&mut self, height: u64, extrinsic: &SchedulerExtrinsic) {
+        match extrinsic {
+            SchedulerExtrinsic::Apply(t) => {
+                self.runtime = Runtime::next_state(&self.runtime, t);
+            }
+            SchedulerExtrinsic::Schedule {
+                who,
+                nonce,
+                when,
+                call,
+                signature,
+            } => {
+                if *when <= height
+                    || origin(call) != *who
+                    || !self.authorize(
+                        *who,
+                        *nonce,
+                        schedule_payload(*who, *nonce, *when, call),
+                        signature,
+                    )
+                {
+                    return;
+                }
+                let task = Task {
+                    owner: *who,
+                    when: *when,
+                    call: call.clone(),
+                };
+                self.agenda.insert(self.next_task, task);
+                self.next_task += 1;
+            }
+            SchedulerExtrinsic::Cancel {
+                who,
+                nonce,
+                id,
+                signature,
+            } => {
+                if self.agenda.get(id).is_none_or(|task| task.owner != *who)
+                    || !self.authorize(*who, *nonce, cancel_payload(*who, *nonce, *id), signature)
+                {
+                    return;
+                }
+                self.agenda.remove(id);
+            }
+        }
+    }
+
+    /// Check the signature and the nonce of an extrinsic of the given user, and if they are
+    /// valid, bump the user's nonce.
+    fn authorize(&mut self, who: User, nonce: u64, payload: H256, signature: &Signature) -> bool {
+        let mut account = self.runtime.account(who);
+        if nonce != account.nonce || !signature.verify(who, payload) {
+            return false;
+        }
+        account.nonce += 1;
+        self.runtime.set_account(who, account);
+        true
+    }
+}
+
+/// Execute a call on behalf of its origin, without a signature. Nonces are left alone.
+fn dispatch(state: &RuntimeState, call: &AccountingTransaction) -> RuntimeState {
+    let balances: HashMap<User, u64> = ACCOUNTS
+        .iter()
+        .map(|&who| (who, state.account(who).balance))
+        .filter(|(_, balance)| *balance > 0)
+        .collect();
+    let balances = AccountedCurrency::next_state(&balances, call);
+
+    let mut state = state.clone();
+    for who in ACCOUNTS {
+        let mut info = state.account(who);
+        info.balance = balances.get(&who).copied().unwrap_or(0);
+        state.set_account(who, info);
+    }
+    state
+}
+
+#[cfg(test)]
+use super::keystore::Keystore;
+#[cfg(test)]
+use super::runtime::signing_payload;
+
+#[cfg(test)]
+fn transfer(sender: User, receiver: User, amount: u64) -> AccountingTransaction {
+    AccountingTransaction::Transfer {
+        sender,
+        receiver,
+        amount,
+    }
+}
+
+/// The given user schedules the given call, with the given nonce.
+#[cfg(test)]
+fn schedule(who: User, nonce: u64, when: u64, call: AccountingTransaction) -> SchedulerExtrinsic {
+    let signature = Keystore::dev()
+        .sign(who, schedule_payload(who, nonce, when, &call))
+        .unwrap();
+    SchedulerExtrinsic::Schedule {
+        who,
+        nonce,
+        when,
+        call,
+        signature,
+    }
+}
+
+#[cfg(test)]
+fn cancel(who: User, nonce: u64, id: u64) -> SchedulerExtrinsic {
+    let signature = Keystore::dev()
+        .sign(who, cancel_payload(who, nonce, id))
+        .unwrap();
+    SchedulerExtrinsic::Cancel {
+        who,
+        nonce,
+        id,
+        signature,
+    }
+}
+
+/// Alice has 100.
+#[cfg(test)]
+fn genesis() -> SchedulerState {
+    SchedulerState::genesis(RuntimeState::genesis(&[(User::Alice, 100)]))
+}
+
+#[test]
+fn scheduler_runs_tasks_when_due() {
+    let state = genesis().execute_block(
+        1,
+        &[schedule(
+            User::Alice,
+            0,
+            3,
+            transfer(User::Alice, User::Bob, 30),
+        )],
+    );
+    assert_eq!(state.agenda.len(), 1);
+    assert_eq!(state.runtime.account(User::Alice).nonce, 1);
+
+    let state = state.execute_block(2, &[]);
+    assert_eq!(state.runtime.account(User::Bob).balance, 0);
+    let state = state.execute_block(3, &[]);
+    assert_eq!(state.runtime.account(User::Bob).balance, 30);
+    assert!(state.agenda.is_empty());
+    assert_eq!(state.runtime.account(User::Alice).nonce, 1);
+}
+
+#[test]
+fn scheduler_runs_before_user_extrinsics() {
+    let state = genesis().execute_block(
+        1,
+        &[schedule(
+            User::Alice,
+            0,
+            2,
+            transfer(User::Alice, User::Bob, 100),
+        )],
+    );
+
+    // Alice tries to get her money out first, but the task already took it.
+    let call = transfer(User::Alice, User::Charlie, 100);
+    let signature = Keystore::dev()
+        .sign(User::Alice, signing_payload(User::Alice, 1, &call))
+        .unwrap();
+    let escape = SignedExtrinsic {
+        signer: User::Alice,
+        nonce: 1,
+        call,
+        signature,
+    };
+    let state = state.execute_block(2, &[SchedulerExtrinsic::Apply(escape)]);
+    assert_eq!(state.runtime.account(User::Bob).balance, 100);
+    assert_eq!(state.runtime.account(User::Charlie).balance, 0);
+}
+
+#[test]
+fn scheduler_limits_weight_per_block() {
+    let tasks: Vec<SchedulerExtrinsic> = (0..3)
+        .map(|nonce| schedule(User::Alice, nonce, 2, transfer(User::Alice, User::Bob, 10)))
+        .collect();
+    let state = genesis().execute_block(1, &tasks);
+
+    // Only one transfer fits into a block, the others are overdue and run later, in order.
+    let state = state.execute_block(2, &[]);
+    assert_eq!(state.agenda.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+    let state = state.execute_block(3, &[]);
+    assert_eq!(state.agenda.keys().copied().collect::<Vec<_>>(), vec![2]);
+    let state = state.execute_block(4, &[]);
+    assert!(state.agenda.is_empty());
+    assert_eq!(state.runtime.account(User::Bob).balance, 30);
+}
+
+#[test]
+fn scheduler_tasks_can_be_cancelled_by_owner() {
+    let state = genesis();
+    let past = schedule(User::Alice, 0, 1, transfer(User::Alice, User::Bob, 10));
+    let theft = schedule(User::Bob, 0, 2, transfer(User::Alice, User::Bob, 10));
+    for invalid in [past, theft] {
+        assert_eq!(state.execute_block(1, &[invalid]), state);
+    }
+
+    let state = state.execute_block(
+        1,
+        &[schedule(
+            User::Alice,
+            0,
+            5,
+            transfer(User::Alice, User::Bob, 10),
+        )],
+    );
+    assert_eq!(state.execute_block(2, &[cancel(User::Bob, 0, 0)]), state);
+    let state = state.execute_block(2, &[cancel(User::Alice, 1, 0)]);
+    assert!(state.agenda.is_empty());
+    let state = state.execute_block(5, &[]);
+    assert_eq!(state.runtime.account(User::Alice).balance, 100);
+}
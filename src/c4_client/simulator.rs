@@ -0,0 +1,466 @@
+//! The devnet runs its nodes on real tasks, with real timers. That makes it realistic, but also
+//! slow and a little different on every run, since the operating system decides which task runs
+//! when. To study how consensus and fork choice cope with a bad network, we would rather replay
+//! the exact same run as often as we like, and fast forward through the boring parts.
+//!
+//! This module is a discrete-event simulator for that. The nodes live on a virtual clock, and
+//! nothing happens between events: the simulator pops the earliest pending event, advances the
+//! clock to it, and lets the node in question react. A node's reaction may schedule more events,
+//! such as the delivery of a block it authored to every peer. The network between the nodes is
+//! described by the same `NetworkConditions` as in the devnet, so latencies, jitter, drops and
+//! partitions all work the same way.
+//!
+//! Everything that happens is written to a trace. All randomness comes from the seed of the
+//! network conditions, and events that are due at the same moment run in the order they were
+//! scheduled, so the same seed always gives the same trace.
+//!
+//! Nodes can also be scripted to author only when they are told to. The `scenario` module builds
+//! on that to tell a whole story of blocks and partitions in a few lines.
+//!
+//! ```ignore
+//! let conditions = NetworkConditions::new(42)
+//!     .with_default(LinkConditions::new(Duration::from_millis(50)).with_drop_rate(0.1));
+//! let mut simulator = Simulator::new(3, Duration::from_secs(1), conditions, make_client);
+//! simulator.run_until(Duration::from_secs(60));
+//! for event in simulator.trace() {
+//!     println!("{event:?}");
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::authoring::AuthorBlocks;
+use super::devnet::{NetworkConditions, NodeInfo};
+use super::network::sync::LocalChain;
+use super::network::{ChainBlock, ChainHeader};
+#[cfg(test)]
+use crate::clock::Clock;
+use crate::clock::ManualClock;
+use crate::hashing::H256;
+use crate::test_rng::TestRng;
+
+/// Something that happened during a simulation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// A node authored a block and sent it to its peers.
+    Authored {
+        at: Duration,
+        node: usize,
+        block: H256,
+        parent: H256,
+        height: u64,
+    },
+    /// A block arrived at a node, which imported it or not.
+    Delivered {
+        at: Duration,
+        from: usize,
+        to: usize,
+        block: H256,
+        imported: bool,
+    },
+    /// The network lost a block on its way from one node to another.
+    Dropped {
+        at: Duration,
+        from: usize,
+        to: usize,
+        block: H256,
+    },
+}
+
+/// An event that is waiting for its time to come.
+enum Pending<B> {
+    /// The node tries to author a block.
+    Tick { node: usize },
+    /// A block arrives at a node.
+    Deliver { from: usize, to: usize, block: B },
+}
+
+/// A set of nodes on a virtual clock.
+pub struct Simulator<N: AuthorBlocks> {
+    nodes: Vec<(NodeInfo, N)>,
+    block_time: Duration,
+    conditions: NetworkConditions,
+    /// The virtual clock. It starts at zero, and only moves from one event to the next.
+    clock: ManualClock,
+    /// Pending events by when they are due. The second part of the key is the order in which
+    /// they were scheduled, which breaks ties.
+    pending: BTreeMap<(Duration, u64), Pending<N::Block>>,
+    scheduled: u64,
+    /// How many blocks each node has sent, which the network conditions use to decide their
+    /// fates.
+    sent: Vec<u64>,
+    trace: Vec<TraceEvent>,
+}
+
+impl<N> Simulator<N>
+where
+    N: AuthorBlocks + LocalChain<N::Block>,
+    N::Block: ChainBlock + Clone,
+{
+    /// Set up `count` fully connected nodes, each of which tries to author a block once every
+    /// `block_time`. The nodes are created by `make_node`, just as in the devnet. The first
+    /// attempt of every node comes at a random point during the first block time, so that the
+    /// nodes do not all author at the very same moment.
+    pub fn new(
+        count: usize,
+        block_time: Duration,
+        conditions: NetworkConditions,
+        make_node: impl FnMut(&NodeInfo) -> N,
+    ) -> Self {
+        let mut simulator = Simulator::with_nodes(count, block_time, conditions, make_node);
+        let mut random = TestRng::seeded(simulator.conditions.seed());
+        for index in 0..count {
+            let offset = random.below(block_time.as_nanos().max(1) as u64);
+            simulator.schedule(Duration::from_nanos(offset), Pending::Tick { node: index });
+        }
+        simulator
+    }
+
+    /// Set up `count` fully connected nodes that never author on their own, only when told to
+    /// with `author`. Scenarios use this to script exactly who authors when.
+    pub fn scripted(
+        count: usize,
+        conditions: NetworkConditions,
+        make_node: impl FnMut(&NodeInfo) -> N,
+    ) -> Self {
+        Simulator::with_nodes(count, Duration::ZERO, conditions, make_node)
+    }
+
+    fn with_nodes(
+        count: usize,
+        block_time: Duration,
+        conditions: NetworkConditions,
+        mut make_node: impl FnMut(&NodeInfo) -> N,
+    ) -> Self {
+        let nodes = (0..count)
+            .map(|index| {
+                let info = NodeInfo::new(index, block_time);
+                let node = make_node(&info);
+                (info, node)
+            })
+            .collect();
+        Simulator {
+            nodes,
+            block_time,
+            conditions,
+            clock: ManualClock::default(),
+            pending: BTreeMap::new(),
+            scheduled: 0,
+            sent: vec![0; count],
+            trace: Vec::new(),
+        }
+    }
+
+    fn schedule(&mut self, at: Duration, event: Pending<N::Block>) {
+        self.pending.insert((at, self.scheduled), event);
+        self.scheduled += 1;
+    }
+
+    /// The current time on the virtual clock.
+    pub fn now(&self) -> Duration {
+        self.clock.elapsed()
+    }
+
+    /// A handle on the virtual clock, for nodes that need to know the time. It moves along with
+    /// the simulation, and its wall clock time starts at the unix epoch.
+    pub fn clock(&self) -> ManualClock {
+        self.clock.clone()
+    }
+
+    /// The node with the given index.
+    pub fn node(&self, index: usize) -> &N {
+        &self.nodes[index].1
+    }
+
+    pub fn node_mut(&mut self, index: usize) -> &mut N {
+        &mut self.nodes[index].1
+    }
+
+    /// What the node with the given index was told about itself.
+    pub fn info(&self, index: usize) -> &NodeInfo {
+        &self.nodes[index].0
+    }
+
+    /// Everything that happened so far, in order.
+    pub fn trace(&self) -> &[TraceEvent] {
+        &self.trace
+    }
+
+    /// Run the next pending event, as long as it is due no later than `end`. Returns whether
+    /// there was such an event.
+    pub fn step(&mut self, end: Duration) -> bool {
+        let Some(entry) = self.pending.first_entry() else {
+            return false;
+        };
+        let (at, _) = *entry.key();
+        if at > end {
+            return false;
+        }
+        let event = entry.remove();
+        self.clock.advance_to(at);
+
+        match event {
+            Pending::Tick { node } => {
+                self.author(node);
+                self.schedule(at + self.block_time, Pending::Tick { node });
+            }
+            Pending::Deliver { from, to, block } => {
+                let hash = block.header().hash();
+                let imported = self.nodes[to].1.import(block);
+                self.trace.push(TraceEvent::Delivered {
+                    at,
+                    from,
+                    to,
+                    block: hash,
+                    imported,
+                });
+            }
+        }
+        true
+    }
+
+    /// Let the given node try to author a block right now, and send it to its peers. Returns
+    /// the block, if the node authored one.
+    pub fn author(&mut self, node: usize) -> Option<N::Block> {
+        let block = self.nodes[node].1.try_author_block()?;
+        let header = block.header();
+        self.trace.push(TraceEvent::Authored {
+            at: self.now(),
+            node,
+            block: header.hash(),
+            parent: header.parent_hash(),
+            height: header.height(),
+        });
+        self.broadcast(node, block.clone());
+        Some(block)
+    }
+
+    /// Send a block from the given node to every other node, as far as the network lets it
+    /// through.
+    fn broadcast(&mut self, from: usize, block: N::Block) {
+        let sequence = self.sent[from];
+        self.sent[from] += 1;
+        for to in (0..self.nodes.len()).filter(|&to| to != from) {
+            // A node that keeps quiet towards a peer is none of the network's business.
+            let Some(block) = self.nodes[from].1.announcement(&block, to) else {
+                continue;
+            };
+            match self.conditions.delivery(from, to, sequence, self.now()) {
+                Some(delay) => {
+                    let event = Pending::Deliver { from, to, block };
+                    self.schedule(self.now() + delay, event);
+                }
+                None => self.trace.push(TraceEvent::Dropped {
+                    at: self.now(),
+                    from,
+                    to,
+                    block: block.header().hash(),
+                }),
+            }
+        }
+    }
+
+    /// Run every event that is due no later than `end`, and move the clock to `end`.
+    pub fn run_until(&mut self, end: Duration) {
+        while self.step(end) {}
+        self.clock.advance_to(end);
+    }
+}
+
+#[cfg(test)]
+use super::devnet::{LinkConditions, Partition};
+
+/// A block that is sealed by its hash, which commits to its author and a nonce. The tests of
+/// the `byzantine` module use these as well.
+#[cfg(test)]
+#[derive(Clone, Debug)]
+pub(super) struct TestBlock {
+    pub(super) hash: H256,
+    pub(super) parent: H256,
+    pub(super) height: u64,
+    pub(super) author: usize,
+    pub(super) nonce: u64,
+}
+
+#[cfg(test)]
+impl TestBlock {
+    pub(super) fn genesis() -> Self {
+        TestBlock {
+            hash: H256::zero(),
+            parent: H256::zero(),
+            height: 0,
+            author: 0,
+            nonce: 0,
+        }
+    }
+
+    /// A properly sealed child of the given block.
+    pub(super) fn child(parent: &TestBlock, author: usize, nonce: u64) -> Self {
+        TestBlock {
+            hash: crate::hashing::hash(&(author, parent.hash, nonce)),
+            parent: parent.hash,
+            height: parent.height + 1,
+            author,
+            nonce,
+        }
+    }
+
+    pub(super) fn is_sealed(&self) -> bool {
+        self.hash == crate::hashing::hash(&(self.author, self.parent, self.nonce))
+    }
+}
+
+#[cfg(test)]
+impl ChainHeader for TestBlock {
+    fn hash(&self) -> H256 {
+        self.hash
+    }
+
+    fn parent_hash(&self) -> H256 {
+        self.parent
+    }
+
+    fn height(&self) -> u64 {
+        self.height
+    }
+}
+
+#[cfg(test)]
+impl ChainBlock for TestBlock {
+    type Header = TestBlock;
+
+    fn header(&self) -> &TestBlock {
+        self
+    }
+}
+
+/// A node that follows the longest chain of sealed blocks it knows, and always authors on top
+/// of it.
+#[cfg(test)]
+pub(super) struct LongestChainNode {
+    pub(super) index: usize,
+    pub(super) blocks: BTreeMap<H256, TestBlock>,
+    pub(super) best: TestBlock,
+}
+
+#[cfg(test)]
+impl LongestChainNode {
+    pub(super) fn new(info: &NodeInfo) -> Self {
+        let genesis = TestBlock::genesis();
+        LongestChainNode {
+            index: info.index,
+            blocks: BTreeMap::from([(genesis.hash, genesis.clone())]),
+            best: genesis,
+        }
+    }
+}
+
+#[cfg(test)]
+impl AuthorBlocks for LongestChainNode {
+    type Block = TestBlock;
+
+    fn try_author_block(&mut self) -> Option<TestBlock> {
+        let block = TestBlock::child(&self.best, self.index, 0);
+        self.import(block.clone());
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+impl LocalChain<TestBlock> for LongestChainNode {
+    fn is_known(&self, hash: H256) -> bool {
+        self.blocks.contains_key(&hash)
+    }
+
+    fn import(&mut self, block: TestBlock) -> bool {
+        if !block.is_sealed() || self.is_known(block.hash) || !self.is_known(block.parent) {
+            return false;
+        }
+        if block.height > self.best.height {
+            self.best = block.clone();
+        }
+        self.blocks.insert(block.hash, block);
+        true
+    }
+}
+
+#[cfg(test)]
+fn lossy_network(seed: u64) -> NetworkConditions {
+    NetworkConditions::new(seed).with_default(
+        LinkConditions::new(Duration::from_millis(300))
+            .with_jitter(Duration::from_millis(200))
+            .with_drop_rate(0.1),
+    )
+}
+
+#[test]
+fn simulator_traces_are_deterministic() {
+    let run = |seed| {
+        let mut simulator = Simulator::new(
+            4,
+            Duration::from_secs(1),
+            lossy_network(seed),
+            LongestChainNode::new,
+        );
+        simulator.run_until(Duration::from_secs(30));
+        simulator.trace().to_vec()
+    };
+
+    let trace = run(7);
+    assert_eq!(trace, run(7));
+    assert_ne!(trace, run(8));
+    assert!(trace
+        .iter()
+        .any(|event| matches!(event, TraceEvent::Dropped { .. })));
+    // The trace is in the order of the virtual clock.
+    let at = |event: &TraceEvent| match event {
+        TraceEvent::Authored { at, .. }
+        | TraceEvent::Delivered { at, .. }
+        | TraceEvent::Dropped { at, .. } => *at,
+    };
+    assert!(trace.windows(2).all(|pair| at(&pair[0]) <= at(&pair[1])));
+}
+
+#[test]
+fn simulator_delivers_after_the_latency() {
+    let conditions =
+        NetworkConditions::new(0).with_default(LinkConditions::new(Duration::from_millis(250)));
+    let mut simulator =
+        Simulator::new(2, Duration::from_secs(1), conditions, LongestChainNode::new);
+    let clock = simulator.clock();
+    simulator.run_until(Duration::from_secs(10));
+    assert_eq!(simulator.now(), Duration::from_secs(10));
+    assert_eq!(clock.unix_time(), Duration::from_secs(10));
+
+    let mut authored = BTreeMap::new();
+    for event in simulator.trace() {
+        match event {
+            TraceEvent::Authored { at, block, .. } => {
+                authored.insert(*block, *at);
+            }
+            TraceEvent::Delivered { at, block, .. } => {
+                assert_eq!(*at, authored[block] + Duration::from_millis(250));
+            }
+            TraceEvent::Dropped { .. } => panic!("a perfect network dropped a block"),
+        }
+    }
+    assert!(authored.len() >= 18);
+}
+
+#[test]
+fn simulator_partition_forks_the_chain() {
+    let conditions =
+        NetworkConditions::new(3).with_partition(Partition::new(vec![vec![0, 1], vec![2, 3]]));
+    let mut simulator =
+        Simulator::new(4, Duration::from_secs(1), conditions, LongestChainNode::new);
+    simulator.run_until(Duration::from_secs(20));
+
+    // Each side of the partition agrees within itself, but the two sides do not.
+    let best = |index| simulator.node(index).best.hash;
+    assert!(simulator.node(0).best.height > 10);
+    assert!(simulator.node(2).best.height > 10);
+    assert_ne!(best(0), best(2));
+    assert!(!simulator.node(0).is_known(best(2)));
+    assert!(!simulator.node(3).is_known(best(1)));
+}
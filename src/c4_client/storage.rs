@@ -0,0 +1,244 @@
+//! The client needs the state of a block to import that block's children, and to answer queries
+//! about it. Keeping the state of every block ever imported is simple, but the storage grows
+//! forever. Most nodes do not need that. Only the states near the tip of the chain are useful
+//! for importing new blocks, and users rarely ask about anything older.
+//!
+//! Real nodes therefore offer two storage modes:
+//! * An _archive_ node keeps the state of every block. Block explorers and indexers run these.
+//! * A _pruned_ node keeps only the states of the most recent blocks, and throws the rest away.
+//!   It does keep the state of the most recently finalized block, its _checkpoint_, so that it
+//!   can always serve that, and replay the blocks after it, see the `history` module.
+//!
+//! Asking a pruned node for a state it has thrown away is not a bug, so it must not panic. The
+//! query fails with a `StatePruned` error instead, which tells the user to ask an archive node.
+//!
+//! The client keeps its states in a `StateStore`, in archive mode unless it is built with
+//! `FullClient::with_state_pruning`.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::H256;
+
+type Hash = H256;
+
+/// Which states a node keeps around.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageMode {
+    /// Keep the state of every block.
+    #[default]
+    Archive,
+    /// Keep only the states of recent blocks and of the most recently finalized block.
+    Pruned,
+}
+
+impl core::str::FromStr for StorageMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "archive" => Ok(StorageMode::Archive),
+            "pruned" => Ok(StorageMode::Pruned),
+            _ => Err(format!(
+                "unknown storage mode {s:?}, expected archive or pruned"
+            )),
+        }
+    }
+}
+
+/// Why a state could not be returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// We have never seen a block with this hash.
+    UnknownBlock(Hash),
+    /// We imported this block once, but have since pruned its state.
+    StatePruned { hash: Hash, height: u64 },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::UnknownBlock(hash) => write!(f, "unknown block {hash}"),
+            StateError::StatePruned { hash, height } => write!(
+                f,
+                "the state of block #{height} ({hash}) has been pruned, ask an archive node"
+            ),
+        }
+    }
+}
+
+/// The states of imported blocks, stored according to a `StorageMode`.
+///
+/// This backs the client's `get_state`. The client inserts the post state of every block as it
+/// is imported, and marks blocks as finalized as they are finalized.
+pub struct StateStore<S> {
+    mode: StorageMode,
+    /// How many of the most recent heights keep their states in pruned mode.
+    keep: u64,
+    /// The height and state of every block whose state we still have.
+    states: BTreeMap<Hash, (u64, S)>,
+    /// The most recently finalized block, whose state is never pruned.
+    checkpoint: Option<Hash>,
+    /// The height of every block whose state we have pruned. We remember these so that we can
+    /// tell a pruned state apart from an unknown block.
+    pruned: BTreeMap<Hash, u64>,
+    /// The greatest height inserted so far.
+    best_height: u64,
+}
+
+impl<S> StateStore<S> {
+    /// Create an empty store. In pruned mode, the states of the `keep` most recent heights are
+    /// kept, and at least the most recent height is always kept.
+    pub fn new(mode: StorageMode, keep: u64) -> Self {
+        StateStore {
+            mode,
+            keep: keep.max(1),
+            states: BTreeMap::new(),
+            checkpoint: None,
+            pruned: BTreeMap::new(),
+            best_height: 0,
+        }
+    }
+
+    pub fn mode(&self) -> StorageMode {
+        self.mode
+    }
+
+    /// Switch to another mode, pruning right away if necessary. Returns the blocks whose
+    /// states were pruned.
+    pub fn set_mode(&mut self, mode: StorageMode, keep: u64) -> Vec<Hash> {
+        self.mode = mode;
+        self.keep = keep.max(1);
+        self.prune()
+    }
+
+    /// Store the state of a newly imported block, pruning old states if necessary. Returns the
+    /// blocks whose states were pruned.
+    pub fn insert(&mut self, hash: Hash, height: u64, state: S) -> Vec<Hash> {
+        self.states.insert(hash, (height, state));
+        self.best_height = self.best_height.max(height);
+        self.prune()
+    }
+
+    /// The state of the given block.
+    pub fn get(&self, hash: Hash) -> Result<&S, StateError> {
+        if let Some((_, state)) = self.states.get(&hash) {
+            return Ok(state);
+        }
+        match self.pruned.get(&hash) {
+            Some(&height) => Err(StateError::StatePruned { hash, height }),
+            None => Err(StateError::UnknownBlock(hash)),
+        }
+    }
+
+    /// Whether the state of the given block is still available.
+    pub fn contains(&self, hash: Hash) -> bool {
+        self.states.contains_key(&hash)
+    }
+
+    /// The number of states currently stored.
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Mark the given block as finalized, so that its state is never pruned. The state of the
+    /// previously finalized block may be pruned from now on, and the blocks whose states were
+    /// pruned are returned. Fails if the state is no longer available.
+    pub fn finalize(&mut self, hash: Hash) -> Result<Vec<Hash>, StateError> {
+        self.get(hash)?;
+        self.checkpoint = Some(hash);
+        Ok(self.prune())
+    }
+
+    /// Throw away every state that is too old to keep, unless it is the checkpoint, and return
+    /// the blocks whose states were thrown away.
+    fn prune(&mut self) -> Vec<Hash> {
+        let mut newly_pruned = Vec::new();
+        if self.mode == StorageMode::Archive || self.best_height < self.keep {
+            return newly_pruned;
+        }
+        let oldest_kept = self.best_height + 1 - self.keep;
+        let checkpoint = self.checkpoint;
+        let pruned = &mut self.pruned;
+        self.states.retain(|hash, (height, _)| {
+            let keep = *height >= oldest_kept || checkpoint == Some(*hash);
+            if !keep {
+                pruned.insert(*hash, *height);
+                newly_pruned.push(*hash);
+            }
+            keep
+        });
+        newly_pruned
+    }
+}
+
+#[test]
+fn storage_archive_keeps_everything() {
+    let mut store = StateStore::new(StorageMode::Archive, 2);
+    for height in 0..10 {
+        store.insert(H256::from(100 + height), height, height);
+    }
+
+    assert_eq!(store.len(), 10);
+    assert_eq!(store.get(H256::from(100)), Ok(&0));
+    assert_eq!(
+        store.get(H256::from(7)),
+        Err(StateError::UnknownBlock(H256::from(7)))
+    );
+}
+
+#[test]
+fn storage_pruned_keeps_recent_and_finalized_states() {
+    let mut store = StateStore::new(StorageMode::Pruned, 3);
+    store.insert(H256::from(100), 0, 0);
+    store.finalize(H256::from(100)).unwrap();
+    for height in 1..10 {
+        store.insert(H256::from(100 + height), height, height);
+    }
+
+    assert_eq!(store.get(H256::from(100)), Ok(&0));
+    assert_eq!(
+        store.get(H256::from(106)),
+        Err(StateError::StatePruned {
+            hash: H256::from(106),
+            height: 6
+        })
+    );
+    assert_eq!(store.get(H256::from(107)), Ok(&7));
+    assert_eq!(store.get(H256::from(109)), Ok(&9));
+    assert_eq!(store.len(), 4);
+    assert!(store.finalize(H256::from(101)).is_err());
+}
+
+#[test]
+fn storage_mode_parses() {
+    assert_eq!("pruned".parse(), Ok(StorageMode::Pruned));
+    assert!("lazy".parse::<StorageMode>().is_err());
+}
+
+#[test]
+fn storage_pruned_keeps_only_the_latest_checkpoint() {
+    let mut store = StateStore::new(StorageMode::Pruned, 2);
+    store.insert(H256::from(100), 0, 0);
+    store.finalize(H256::from(100)).unwrap();
+    for height in 1..5 {
+        store.insert(H256::from(100 + height), height, height);
+    }
+    store.finalize(H256::from(103)).unwrap();
+    store.insert(H256::from(105), 5, 5);
+    store.insert(H256::from(106), 6, 6);
+
+    assert!(!store.contains(H256::from(100)));
+    assert_eq!(store.get(H256::from(103)), Ok(&3));
+    assert_eq!(store.len(), 3);
+}
@@ -0,0 +1,432 @@
+//! In a classroom, every student runs their own node, and it is hard to see what all of them
+//! are doing at once. Telemetry solves this. Each node streams structured events, such as
+//! "imported block #12" or "connected to a peer", to a central collector, which can then show
+//! every node on a single dashboard.
+//!
+//! Events are sent as JSON text messages over a WebSocket. We only need the client side of the
+//! protocol, and only ever send text, so we implement that small part by hand rather than
+//! pulling in a WebSocket library.
+//!
+//! A collector that anyone can reach would show whatever anyone sends it. When the node and the
+//! collector share a secret, every message carries an HMAC tag made with it, from
+//! `crate::crypto::hmac`, and the collector can drop messages that do not check out.
+//!
+//! Telemetry must never slow the node down or take it down. Events are handed to a background
+//! thread, which drops them whenever the collector can not be reached.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::network::PeerId;
+use crate::clock::{Clock, SystemClock};
+use crate::crypto::hmac::{hmac_hex, verify_hex};
+use crate::crypto::Secret;
+use crate::hashing::{hash_u64, H256};
+
+/// How long to wait before trying to reach the collector again after it could not be reached.
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Something noteworthy that happened on the node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TelemetryEvent {
+    /// A block from the network was imported.
+    BlockImported { hash: H256, height: u64 },
+    /// The node authored a block of its own.
+    BlockAuthored { hash: H256, height: u64 },
+    /// The best block moved to a different fork.
+    Reorg {
+        from: H256,
+        to: H256,
+        retracted: usize,
+        enacted: usize,
+    },
+    /// A peer connection was established.
+    PeerConnected { peer: PeerId },
+}
+
+/// An event as it is sent to the collector.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TelemetryMessage {
+    /// The name the node reports itself under.
+    pub node: String,
+    /// When the event happened, in milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    #[serde(flatten)]
+    pub event: TelemetryEvent,
+    /// The HMAC tag of the rest of the message, as hex, if the node shares a secret with the
+    /// collector.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+}
+
+impl TelemetryMessage {
+    /// The bytes that the tag covers: the message as JSON, without the tag.
+    fn authenticated_bytes(&self) -> Vec<u8> {
+        let untagged = TelemetryMessage {
+            mac: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&untagged).expect("messages always serialize")
+    }
+
+    /// Tag the message with the given shared secret.
+    pub fn authenticate(&mut self, secret: &[u8]) {
+        self.mac = Some(hmac_hex(secret, &self.authenticated_bytes()));
+    }
+
+    /// Check that the message was tagged with the given shared secret, and not changed since.
+    pub fn verify(&self, secret: &[u8]) -> bool {
+        self.mac
+            .as_ref()
+            .is_some_and(|mac| verify_hex(secret, &self.authenticated_bytes(), mac))
+    }
+}
+
+/// A handle for emitting telemetry events. Cheap to clone, so every part of the node can have
+/// its own.
+#[derive(Clone)]
+pub struct Telemetry {
+    node: String,
+    /// The secret shared with the collector, if messages are to be tagged.
+    secret: Option<Secret<Vec<u8>>>,
+    /// None if telemetry is disabled.
+    events: Option<Sender<TelemetryMessage>>,
+    /// Where event timestamps come from.
+    clock: Arc<dyn Clock + Send + Sync>,
+}
+
+impl Telemetry {
+    /// A handle that throws every event away.
+    pub fn disabled() -> Self {
+        Telemetry {
+            node: String::new(),
+            secret: None,
+            events: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Start streaming events to the collector at the given `ws://` url, under the given
+    /// node name. Messages are tagged with the secret, if there is one. The connection is made,
+    /// and remade, in the background.
+    pub fn connect(url: &str, node: &str, secret: Option<&str>) -> Result<Self, String> {
+        let url = url.to_string();
+        parse_ws_url(&url)?;
+
+        let (events, inbox) = mpsc::channel::<TelemetryMessage>();
+        std::thread::spawn(move || {
+            let mut socket = None;
+            let mut last_attempt: Option<Instant> = None;
+            for message in inbox {
+                let retry_due = last_attempt.is_none_or(|t| t.elapsed() >= RECONNECT_DELAY);
+                if socket.is_none() && retry_due {
+                    last_attempt = Some(Instant::now());
+                    socket = WebSocket::connect(&url).ok();
+                }
+                let Some(ws) = socket.as_mut() else {
+                    continue;
+                };
+                let text = serde_json::to_string(&message).expect("messages always serialize");
+                if ws.send_text(&text).is_err() {
+                    socket = None;
+                }
+            }
+        });
+
+        Ok(Telemetry {
+            node: node.to_string(),
+            secret: secret.map(|secret| Secret::new(secret.as_bytes().to_vec())),
+            events: Some(events),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// A handle that hands every event to the returned receiver instead of a collector.
+    #[cfg(test)]
+    pub(super) fn captured() -> (Self, mpsc::Receiver<TelemetryMessage>) {
+        let (events, inbox) = mpsc::channel();
+        let telemetry = Telemetry {
+            events: Some(events),
+            ..Telemetry::disabled()
+        };
+        (telemetry, inbox)
+    }
+
+    /// Timestamp events with the given clock instead of the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Emit an event. This never blocks.
+    pub fn emit(&self, event: TelemetryEvent) {
+        let Some(events) = &self.events else {
+            return;
+        };
+        let timestamp_ms = self.clock.unix_time().as_millis() as u64;
+        let mut message = TelemetryMessage {
+            node: self.node.clone(),
+            timestamp_ms,
+            event,
+            mac: None,
+        };
+        if let Some(secret) = &self.secret {
+            message.authenticate(secret);
+        }
+        // If the background thread is gone, there is nobody to send the event to anyway.
+        let _ = events.send(message);
+    }
+}
+
+/// Split a `ws://host:port/path` url into the address to connect to and the path to request.
+pub fn parse_ws_url(url: &str) -> Result<(String, String), String> {
+    let rest = url
+        .strip_prefix("ws://")
+        .ok_or_else(|| format!("unsupported telemetry url {url:?}, expected ws://"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    if authority.is_empty() {
+        return Err(format!("telemetry url {url:?} has no host"));
+    }
+    let addr = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+    Ok((addr, path.to_string()))
+}
+
+/// Some bytes that nobody can guess in advance. WebSocket clients must use these for the
+/// handshake key and to mask their frames. This is not cryptographically secure, but the
+/// masking only exists to confuse misbehaving proxies, not to keep secrets.
+fn unpredictable_bytes<const N: usize>() -> [u8; N] {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut bytes = [0; N];
+    for chunk in bytes.chunks_mut(8) {
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let random = hash_u64(&(SystemTime::now(), count)).to_le_bytes();
+        chunk.copy_from_slice(&random[..chunk.len()]);
+    }
+    bytes
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (u32::from(b) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Encode a single, final, text frame. Frames sent by clients must be masked with the given
+/// key.
+pub fn encode_text_frame(text: &str, mask: [u8; 4]) -> Vec<u8> {
+    let payload = text.as_bytes();
+    let mut frame = vec![0x81];
+    match payload.len() {
+        len @ 0..=125 => frame.push(0x80 | len as u8),
+        len @ 126..=0xffff => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+    frame
+}
+
+/// The client end of a WebSocket connection, which can only send text.
+pub struct WebSocket {
+    stream: TcpStream,
+}
+
+impl WebSocket {
+    /// Connect to the given `ws://` url and perform the opening handshake.
+    pub fn connect(url: &str) -> io::Result<Self> {
+        let (addr, path) =
+            parse_ws_url(url).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut stream = TcpStream::connect(&addr)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: {addr}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            base64(&unpredictable_bytes::<16>())
+        )?;
+
+        // The server answers with a status line and headers, and nothing else until we send
+        // something. We check the status, and skip the headers.
+        let mut reader = BufReader::new(&stream);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if line.split_whitespace().nth(1) != Some("101") {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("collector refused the websocket upgrade: {}", line.trim()),
+            ));
+        }
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+        }
+
+        Ok(WebSocket { stream })
+    }
+
+    /// Send a single text message.
+    pub fn send_text(&mut self, text: &str) -> io::Result<()> {
+        self.stream
+            .write_all(&encode_text_frame(text, unpredictable_bytes()))
+    }
+}
+
+/// Decode a single masked text frame, as a collector would.
+#[cfg(test)]
+fn decode_text_frame(frame: &[u8]) -> String {
+    let (len, rest) = match frame[1] & 0x7f {
+        126 => (
+            u16::from_be_bytes([frame[2], frame[3]]) as usize,
+            &frame[4..],
+        ),
+        127 => (
+            u64::from_be_bytes(frame[2..10].try_into().unwrap()) as usize,
+            &frame[10..],
+        ),
+        len => (len as usize, &frame[2..]),
+    };
+    let (mask, payload) = rest.split_at(4);
+    let text: Vec<u8> = payload[..len]
+        .iter()
+        .zip(mask.iter().cycle())
+        .map(|(b, m)| b ^ m)
+        .collect();
+    String::from_utf8(text).unwrap()
+}
+
+#[test]
+fn telemetry_frames_are_masked() {
+    let frame = encode_text_frame("hi", [1, 2, 3, 4]);
+    assert_eq!(frame, vec![0x81, 0x82, 1, 2, 3, 4, b'h' ^ 1, b'i' ^ 2]);
+
+    let long = "x".repeat(300);
+    assert_eq!(decode_text_frame(&encode_text_frame(&long, [9; 4])), long);
+}
+
+#[test]
+fn telemetry_url_parsing() {
+    assert_eq!(
+        parse_ws_url("ws://dashboard.local:8000/submit"),
+        Ok(("dashboard.local:8000".into(), "/submit".into()))
+    );
+    assert_eq!(
+        parse_ws_url("ws://dashboard.local"),
+        Ok(("dashboard.local:80".into(), "/".into()))
+    );
+    assert!(parse_ws_url("wss://dashboard.local").is_err());
+    assert_eq!(base64(b"hello"), "aGVsbG8=");
+}
+
+#[cfg(test)]
+use crate::clock::ManualClock;
+
+#[test]
+fn telemetry_events_reach_collector() {
+    use std::io::Read;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}/submit", listener.local_addr().unwrap());
+    let clock = ManualClock::new(Duration::from_secs(1_700_000_000));
+    let telemetry = Telemetry::connect(&url, "student-1", Some("classroom"))
+        .unwrap()
+        .with_clock(clock.clone());
+    clock.advance(Duration::from_millis(1_500));
+    telemetry.emit(TelemetryEvent::BlockAuthored {
+        hash: H256::from(5),
+        height: 1,
+    });
+
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "GET /submit HTTP/1.1\r\n");
+    while line.trim() != "" {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+    stream
+        .write_all(b"HTTP/1.1 101 Switching Protocols\r\n\r\n")
+        .unwrap();
+
+    // The event is longer than 125 bytes, so the frame carries a 16 bit length.
+    let mut header = [0; 4];
+    reader.read_exact(&mut header).unwrap();
+    assert_eq!(header[1] & 0x7f, 126);
+    let mut rest = vec![0; 4 + u16::from_be_bytes([header[2], header[3]]) as usize];
+    reader.read_exact(&mut rest).unwrap();
+    let text = decode_text_frame(&[&header[..], &rest[..]].concat());
+
+    let message: TelemetryMessage = serde_json::from_str(&text).unwrap();
+    assert_eq!(message.node, "student-1");
+    assert_eq!(message.timestamp_ms, 1_700_000_001_500);
+    assert!(message.verify(b"classroom"));
+    assert_eq!(
+        message.event,
+        TelemetryEvent::BlockAuthored {
+            hash: H256::from(5),
+            height: 1,
+        }
+    );
+}
+
+#[test]
+fn telemetry_tags_catch_tampering() {
+    let mut message = TelemetryMessage {
+        node: "student-1".into(),
+        timestamp_ms: 1000,
+        event: TelemetryEvent::BlockImported {
+            hash: H256::from(5),
+            height: 3,
+        },
+        mac: None,
+    };
+    assert!(!message.verify(b"classroom"));
+    message.authenticate(b"classroom");
+    assert!(message.verify(b"classroom"));
+    assert!(!message.verify(b"guess"));
+
+    // The tag survives the trip through JSON, and covers every field.
+    let text = serde_json::to_string(&message).unwrap();
+    let received: TelemetryMessage = serde_json::from_str(&text).unwrap();
+    assert!(received.verify(b"classroom"));
+    let forged: TelemetryMessage =
+        serde_json::from_str(&text.replace("student-1", "student-2")).unwrap();
+    assert!(!forged.verify(b"classroom"));
+}
@@ -0,0 +1,277 @@
+//! A Graphviz graph of the block tree shows its shape, but not who authored what, and it leaves
+//! the layout to Graphviz. For slides, and for a quick look at what a simulation did, a plain
+//! timeline is often clearer: blocks laid out by height from left to right, colored by their
+//! author, with every fork branching off onto a lane of its own.
+//!
+//! This module draws such a timeline as a standalone SVG image, which any browser can show. A
+//! timeline can be made from a `ForkTree`, where the authors are unknown, or from the trace of
+//! a simulation, where they are not.
+//!
+//! ```ignore
+//! simulator.run_until(Duration::from_secs(60));
+//! Timeline::from_trace(simulator.trace()).write_svg(Path::new("run.svg"))?;
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+use super::forks::ForkTree;
+use super::simulator::TraceEvent;
+use crate::hashing::H256;
+
+/// The colors that authors are told apart by, in order of their index.
+const PALETTE: [&str; 8] = [
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+];
+
+/// The color of blocks whose author is unknown.
+const UNKNOWN: &str = "#bab0ac";
+
+/// The horizontal distance between two heights, and the vertical distance between two lanes.
+const COLUMN: u64 = 70;
+const LANE: u64 = 50;
+
+/// The size of a single block.
+const WIDTH: u64 = 44;
+const HEIGHT: u64 = 28;
+
+/// A single block on a timeline.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimelineBlock {
+    pub hash: H256,
+    pub parent: H256,
+    pub height: u64,
+    /// The index of the node that authored the block, if known.
+    pub author: Option<usize>,
+}
+
+/// Blocks to be laid out on a timeline.
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    blocks: Vec<TimelineBlock>,
+    /// The block whose chain runs along the top lane. Without one, the highest block is used.
+    best: Option<H256>,
+}
+
+impl Timeline {
+    /// A timeline of the given blocks. A block whose parent is not among them starts a chain of
+    /// its own.
+    pub fn new(blocks: Vec<TimelineBlock>) -> Self {
+        Timeline { blocks, best: None }
+    }
+
+    /// Run the chain of the given block along the top lane.
+    pub fn with_best(mut self, best: H256) -> Self {
+        self.best = Some(best);
+        self
+    }
+
+    /// A timeline of a client's block tree, with its best chain along the top.
+    pub fn from_tree(tree: &ForkTree) -> Self {
+        let blocks = tree
+            .blocks
+            .iter()
+            .map(|block| TimelineBlock {
+                hash: block.hash,
+                parent: block.parent,
+                height: block.height,
+                author: None,
+            })
+            .collect();
+        Timeline::new(blocks).with_best(tree.best)
+    }
+
+    /// A timeline of every block that was authored during a simulation.
+    pub fn from_trace(trace: &[TraceEvent]) -> Self {
+        let blocks = trace
+            .iter()
+            .filter_map(|event| match event {
+                TraceEvent::Authored {
+                    node,
+                    block,
+                    parent,
+                    height,
+                    ..
+                } => Some(TimelineBlock {
+                    hash: *block,
+                    parent: *parent,
+                    height: *height,
+                    author: Some(*node),
+                }),
+                _ => None,
+            })
+            .collect();
+        Timeline::new(blocks)
+    }
+
+    /// Assign every block a lane, such that the best chain runs along lane 0, and every block
+    /// stays on the lane of its parent unless that is taken at its height.
+    fn lanes(&self) -> BTreeMap<H256, u64> {
+        let by_hash: BTreeMap<H256, &TimelineBlock> = self
+            .blocks
+            .iter()
+            .map(|block| (block.hash, block))
+            .collect();
+        let best = self
+            .best
+            .filter(|best| by_hash.contains_key(best))
+            .or_else(|| {
+                let highest = self.blocks.iter().max_by_key(|b| (b.height, b.hash));
+                highest.map(|block| block.hash)
+            });
+        let mut best_chain = BTreeSet::new();
+        let mut current = best.and_then(|hash| by_hash.get(&hash));
+        while let Some(block) = current {
+            best_chain.insert(block.hash);
+            current = by_hash.get(&block.parent).filter(|_| block.height > 0);
+        }
+
+        // Place the best chain first, then everything else by height.
+        let mut order: Vec<&TimelineBlock> = self.blocks.iter().collect();
+        order.sort_by_key(|block| (!best_chain.contains(&block.hash), block.height, block.hash));
+        let mut lanes = BTreeMap::new();
+        let mut taken = BTreeSet::new();
+        for block in order {
+            if lanes.contains_key(&block.hash) {
+                continue;
+            }
+            let mut lane = lanes.get(&block.parent).copied().unwrap_or(0);
+            while taken.contains(&(block.height, lane)) {
+                lane += 1;
+            }
+            taken.insert((block.height, lane));
+            lanes.insert(block.hash, lane);
+        }
+        lanes
+    }
+
+    /// Render the timeline as a standalone SVG image.
+    pub fn to_svg(&self) -> String {
+        let lanes = self.lanes();
+        let lowest = self.blocks.iter().map(|b| b.height).min().unwrap_or(0);
+        let highest = self.blocks.iter().map(|b| b.height).max().unwrap_or(0);
+        let lane_count = lanes.values().max().map_or(1, |lane| lane + 1);
+        let authors: BTreeSet<usize> = self.blocks.iter().filter_map(|b| b.author).collect();
+        // Every block is centered in its cell of the grid, and the legend goes below the grid.
+        let center = |block: &TimelineBlock| {
+            let x = (block.height - lowest) * COLUMN + COLUMN / 2;
+            (x, lanes[&block.hash] * LANE + LANE / 2)
+        };
+        let width = (highest - lowest + 1) * COLUMN;
+        let height = lane_count * LANE + if authors.is_empty() { 0 } else { LANE };
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+             viewBox=\"0 0 {width} {height}\" font-family=\"monospace\" font-size=\"11\">"
+        );
+        let _ = writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>");
+
+        let by_hash: BTreeMap<H256, &TimelineBlock> = self
+            .blocks
+            .iter()
+            .map(|block| (block.hash, block))
+            .collect();
+        for block in &self.blocks {
+            let Some(parent) = by_hash.get(&block.parent).filter(|_| block.height > 0) else {
+                continue;
+            };
+            let ((x1, y1), (x2, y2)) = (center(parent), center(block));
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{}\" y1=\"{y1}\" x2=\"{}\" y2=\"{y2}\" stroke=\"#888\"/>",
+                x1 + WIDTH / 2,
+                x2 - WIDTH / 2
+            );
+        }
+        for block in &self.blocks {
+            let (x, y) = center(block);
+            let color = block.author.map_or(UNKNOWN, |a| PALETTE[a % PALETTE.len()]);
+            let _ = writeln!(
+                svg,
+                "<g><title>{}</title><rect x=\"{}\" y=\"{}\" width=\"{WIDTH}\" \
+                 height=\"{HEIGHT}\" rx=\"4\" fill=\"{color}\"/><text x=\"{x}\" y=\"{}\" \
+                 text-anchor=\"middle\" fill=\"white\">#{}</text></g>",
+                block.hash,
+                x - WIDTH / 2,
+                y - HEIGHT / 2,
+                y + 4,
+                block.height
+            );
+        }
+        for (i, author) in authors.iter().enumerate() {
+            let (x, y) = (i as u64 * COLUMN + 10, lane_count * LANE + LANE / 2);
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{x}\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\"/>\
+                 <text x=\"{}\" y=\"{}\">node {author}</text>",
+                y - 6,
+                PALETTE[author % PALETTE.len()],
+                x + 16,
+                y + 4
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Write the timeline to an SVG file at the given path.
+    pub fn write_svg(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, self.to_svg())
+    }
+}
+
+/// Block `n` at height `n % 10`, with the given parent and author.
+#[cfg(test)]
+fn block(n: u64, parent: u64, author: usize) -> TimelineBlock {
+    TimelineBlock {
+        hash: H256::from(n),
+        parent: H256::from(parent),
+        height: n % 10,
+        author: Some(author),
+    }
+}
+
+#[test]
+fn timeline_forks_branch_onto_lanes_of_their_own() {
+    // 10 -> 11 -> 12 -> 13 is the best chain, 21 -> 22 leaves it after genesis and 32 after 11.
+    let timeline = Timeline::new(vec![
+        block(10, 0, 0),
+        block(11, 10, 1),
+        block(21, 10, 2),
+        block(12, 11, 0),
+        block(32, 11, 1),
+        block(22, 21, 2),
+        block(13, 12, 1),
+    ]);
+    let lanes = timeline.lanes();
+    let lane = |n: u64| lanes[&H256::from(n)];
+
+    assert_eq!([10, 11, 12, 13].map(lane), [0; 4]);
+    assert_eq!(lane(21), 1);
+    assert_eq!(lane(22), 1);
+    assert_eq!(lane(32), 2);
+
+    // Preferring another best block swaps the lanes.
+    let lanes = timeline.with_best(H256::from(22)).lanes();
+    assert_eq!(lanes[&H256::from(22)], 0);
+    assert_eq!(lanes[&H256::from(13)], 1);
+}
+
+#[test]
+fn timeline_renders_every_block_and_author() {
+    let timeline = Timeline::new(vec![block(10, 0, 0), block(11, 10, 3), block(21, 10, 9)]);
+    let svg = timeline.to_svg();
+
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert_eq!(svg.matches("<title>").count(), 3);
+    assert_eq!(svg.matches("<line ").count(), 2);
+    assert!(svg.contains(PALETTE[0]) && svg.contains(PALETTE[3]));
+    // Authors beyond the palette wrap around.
+    assert!(svg.contains(&format!("fill=\"{}\"/><text", PALETTE[9 % PALETTE.len()])));
+    assert!(svg.contains("node 9"));
+}
@@ -0,0 +1,217 @@
+//! The fee of an extrinsic only depends on its weight, so when the pool holds more extrinsics
+//! than fit in the next block, every user pays the same rate and waits their turn. Users in a
+//! hurry need a way to jump the queue. With a _tip_, they pay the block's author extra, on top
+//! of the fee.
+//!
+//! Tips work like they do in Substrate:
+//! * The fee is still burned, but the tip goes to the author of the block that includes the
+//!   extrinsic. That is what makes authors include well tipping extrinsics first.
+//! * The pool prices extrinsics by their fee plus their tip per unit of weight, their
+//!   _effective priority_. Use `fee_info` with the pool of the `fee_pool` module.
+//! * The tip is signed by the signer of the extrinsic, so that nobody else can raise it and
+//!   drain their account.
+
+use serde::{Deserialize, Serialize};
+
+use super::fee_pool::FeeInfo;
+use super::keystore::Signature;
+use super::runtime::{signing_payload, AccountInfo, RuntimeState, SignedExtrinsic};
+use super::weight::{fee, MeteredRuntime, Weight, Weighed};
+use crate::c1_state_machine::{StateMachine, User};
+use crate::hashing::{hash_of, H256};
+
+/// A tip for the author, signed by the signer of the extrinsic it comes with.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Tip {
+    pub amount: u64,
+    pub signature: Signature,
+}
+
+/// A signed extrinsic with an optional tip.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TippedExtrinsic {
+    pub extrinsic: SignedExtrinsic,
+    pub tip: Option<Tip>,
+}
+
+impl TippedExtrinsic {
+    /// How much the extrinsic tips, zero if it does not.
+    pub fn tip_amount(&self) -> u64 {
+        self.tip.as_ref().map_or(0, |tip| tip.amount)
+    }
+}
+
+impl Weighed for TippedExtrinsic {
+    fn weight(&self) -> Weight {
+        self.extrinsic.weight()
+    }
+}
+
+/// Calculate the hash that the signer must sign to tip the given amount for the given
+/// extrinsic. It commits to the whole extrinsic, so a tip can't be moved to another one.
+pub fn tip_payload(extrinsic: &SignedExtrinsic, amount: u64) -> H256 {
+    let payload = signing_payload(extrinsic.signer, extrinsic.nonce, &extrinsic.call);
+    hash_of("tip", &(payload, amount))
+}
+
+/// Price a tipped extrinsic for the fee market of the `fee_pool` module. Its tip counts as
+/// part of its fee.
+pub fn fee_info(t: &TippedExtrinsic) -> FeeInfo<User> {
+    let weight = t.weight();
+    FeeInfo {
+        sender: t.extrinsic.signer,
+        nonce: t.extrinsic.nonce,
+        fee: fee(weight).saturating_add(t.tip_amount()),
+        weight,
+    }
+}
+
+/// Apply a tipped extrinsic in a block authored by the given author. The signer pays the tip
+/// on top of the fee of the `MeteredRuntime`, and the author gets it. Extrinsics whose tip is
+/// not signed by their signer are invalid, and so are those whose signer can't pay both the
+/// tip and the fee.
+pub fn apply(starting_state: &RuntimeState, author: User, t: &TippedExtrinsic) -> RuntimeState {
+    let signer = t.extrinsic.signer;
+    if let Some(tip) = &t.tip {
+        if !tip
+            .signature
+            .verify(signer, tip_payload(&t.extrinsic, tip.amount))
+        {
+            return starting_state.clone();
+        }
+    }
+
+    let mut payer = starting_state.account(signer);
+    let Some(balance) = payer.balance.checked_sub(t.tip_amount()) else {
+        return starting_state.clone();
+    };
+    payer.balance = balance;
+    let mut charged = starting_state.clone();
+    charged.set_account(signer, payer);
+
+    let mut state = MeteredRuntime::next_state(&charged, &t.extrinsic);
+    if state == charged {
+        return starting_state.clone();
+    }
+    let info = state.account(author);
+    state.set_account(
+        author,
+        AccountInfo {
+            balance: info.balance.saturating_add(t.tip_amount()),
+            ..info
+        },
+    );
+    state
+}
+
+/// Execute the extrinsics of a block authored by the given author, who collects their tips.
+pub fn execute_block(
+    state: &RuntimeState,
+    author: User,
+    extrinsics: &[TippedExtrinsic],
+) -> RuntimeState {
+    extrinsics
+        .iter()
+        .fold(state.clone(), |state, t| apply(&state, author, t))
+}
+
+#[cfg(test)]
+use super::fee_pool::{FeePool, DEFAULT_CAPACITY};
+#[cfg(test)]
+use super::keystore::Keystore;
+#[cfg(test)]
+use super::TransactionPool;
+#[cfg(test)]
+use crate::c1_state_machine::AccountingTransaction;
+
+/// The pool only needs a transaction type, so this state machine is never run.
+#[cfg(test)]
+struct Tipping;
+
+#[cfg(test)]
+impl StateMachine for Tipping {
+    type State = ();
+    type Transition = TippedExtrinsic;
+
+    fn next_state(_: &(), _: &TippedExtrinsic) {}
+}
+
+/// The given signer's transfer of 10 to Charlie with the given nonce and tip. It weighs 200.
+#[cfg(test)]
+pub(super) fn tipped(signer: User, nonce: u64, tip: Option<u64>) -> TippedExtrinsic {
+    let call = AccountingTransaction::Transfer {
+        sender: signer,
+        receiver: User::Charlie,
+        amount: 10,
+    };
+    let keystore = Keystore::dev();
+    let signature = keystore
+        .sign(signer, signing_payload(signer, nonce, &call))
+        .unwrap();
+    let extrinsic = SignedExtrinsic {
+        signer,
+        nonce,
+        call,
+        signature,
+    };
+    let tip = tip.map(|amount| Tip {
+        amount,
+        signature: keystore
+            .sign(signer, tip_payload(&extrinsic, amount))
+            .unwrap(),
+    });
+    TippedExtrinsic { extrinsic, tip }
+}
+
+#[test]
+fn tip_raises_priority_in_pool() {
+    let mut pool = FeePool::new(fee_info, DEFAULT_CAPACITY);
+    let plain = tipped(User::Alice, 0, None);
+    let generous = tipped(User::Bob, 0, Some(50));
+    for t in [plain.clone(), generous.clone()] {
+        assert!(TransactionPool::<Tipping>::try_insert(&mut pool, t));
+    }
+
+    assert_eq!(fee_info(&generous).fee, 250);
+    assert_eq!(
+        TransactionPool::<Tipping>::next_from_pool(&mut pool),
+        Some(generous)
+    );
+    assert_eq!(
+        TransactionPool::<Tipping>::next_from_pool(&mut pool),
+        Some(plain)
+    );
+}
+
+#[test]
+fn tip_goes_to_author() {
+    let state = RuntimeState::genesis(&[(User::Alice, 1_000), (User::Bob, 1_000)]);
+    let block = [tipped(User::Alice, 0, Some(30)), tipped(User::Bob, 0, None)];
+
+    let next = execute_block(&state, User::Charlie, &block);
+    assert_eq!(next.account(User::Alice).balance, 1_000 - 200 - 30 - 10);
+    assert_eq!(next.account(User::Bob).balance, 1_000 - 200 - 10);
+    assert_eq!(next.account(User::Charlie).balance, 10 + 30 + 10);
+}
+
+#[test]
+fn tip_must_be_signed_and_affordable() {
+    let state = RuntimeState::genesis(&[(User::Alice, 1_000)]);
+
+    // Bob can't make Alice tip more than she signed for.
+    let mut raised = tipped(User::Alice, 0, Some(30));
+    raised.tip = Some(Tip {
+        amount: 300,
+        ..raised.tip.unwrap()
+    });
+    assert_eq!(apply(&state, User::Bob, &raised), state);
+
+    // Alice can pay the fee, but not the fee and the tip.
+    let greedy = tipped(User::Alice, 0, Some(801));
+    assert_eq!(apply(&state, User::Bob, &greedy), state);
+
+    // A replayed extrinsic neither charges Alice again, nor pays Bob again.
+    let honest = tipped(User::Alice, 0, Some(30));
+    let included = apply(&state, User::Bob, &honest);
+    assert_eq!(apply(&included, User::Bob, &honest), included);
+}
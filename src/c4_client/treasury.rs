@@ -0,0 +1,385 @@
+//! A chain needs work that nobody is paid for by any single user: audits, documentation, tools,
+//! and the occasional bug bounty. A treasury pays for it out of the protocol itself. Instead of
+//! burning every fee, the runtime sets a share of each fee aside in the treasury, and governance
+//! decides what the money is spent on.
+//!
+//! Spending works like in Substrate's treasury pallet:
+//! 1. Anybody proposes a spend: an amount, and who should get it. To keep the proposals worth
+//!    reading, the proposer puts down a bond.
+//! 2. Governance approves or rejects the proposal. A rejected proposal forfeits its bond to the
+//!    treasury, and an approved one gets its bond back.
+//! 3. Approved spends are not paid right away, but at the end of every spend period, in the
+//!    order they were approved, for as long as the treasury has the money. The rest wait for
+//!    the next period.
+//!
+//! Governance here is the root account of the `upgrade` module, which signs its decisions. The
+//! spend period is counted in blocks, so extrinsics are executed a whole block at a time, like
+//! those of the `upgrade` and `htlc` modules.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::keystore::Signature;
+use super::runtime::{RuntimeState, SignedExtrinsic};
+use super::upgrade::ROOT;
+use super::weight::{fee, MeteredRuntime, Weighed};
+use crate::c1_state_machine::{StateMachine, User};
+use crate::hashing::{hash_of, H256};
+
+/// How many percent of every fee go to the treasury. The rest is burned.
+pub const TREASURY_SHARE_PERCENT: u64 = 20;
+
+/// How many percent of the amount a proposer puts down as a bond.
+pub const PROPOSAL_BOND_PERCENT: u64 = 5;
+
+/// The smallest bond, so that even tiny proposals are not free.
+pub const PROPOSAL_BOND_MINIMUM: u64 = 10;
+
+/// Approved spends are paid out at every block whose height is a multiple of this.
+pub const SPEND_PERIOD: u64 = 10;
+
+/// The bond for a proposal to spend the given amount.
+pub fn proposal_bond(amount: u64) -> u64 {
+    (amount.saturating_mul(PROPOSAL_BOND_PERCENT) / 100).max(PROPOSAL_BOND_MINIMUM)
+}
+
+/// A proposal to spend money from the treasury.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SpendProposal {
+    pub proposer: User,
+    pub beneficiary: User,
+    pub amount: u64,
+    /// The proposer's reserved bond.
+    pub bond: u64,
+    pub approved: bool,
+}
+
+/// An extrinsic that can be applied to a chain with a treasury.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TreasuryExtrinsic {
+    /// An ordinary extrinsic. Its fee is split between the treasury and the void.
+    Apply(SignedExtrinsic),
+    /// Propose to pay the given amount to the beneficiary. The nonce is the proposer's account
+    /// nonce, just like in a signed extrinsic.
+    ProposeSpend {
+        proposer: User,
+        nonce: u64,
+        beneficiary: User,
+        amount: u64,
+        signature: Signature,
+    },
+    /// Approve a proposal. Must be signed by the root account.
+    Approve { id: u64, signature: Signature },
+    /// Reject a proposal that was not approved yet. Must be signed by the root account.
+    Reject { id: u64, signature: Signature },
+}
+
+/// Calculate the hash that the proposer must sign to propose a spend.
+pub fn propose_payload(proposer: User, nonce: u64, beneficiary: User, amount: u64) -> H256 {
+    hash_of("propose spend", &(proposer, nonce, beneficiary, amount))
+}
+
+/// Calculate the hash that the root account must sign to approve, or to reject, a proposal.
+pub fn decision_payload(id: u64, approve: bool) -> H256 {
+    hash_of("spend decision", &(id, approve))
+}
+
+/// The runtime state, along with the treasury.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TreasuryState {
+    pub runtime: RuntimeState,
+    /// The money in the treasury.
+    pub treasury: u64,
+    /// The open proposals, by their id. Proposals are removed when they are rejected or paid.
+    pub proposals: BTreeMap<u64, SpendProposal>,
+    /// The id the next proposal gets.
+    pub next_proposal: u64,
+    /// The ids of the approved proposals, in the order they were approved.
+    pub approvals: Vec<u64>,
+}
+
+impl TreasuryState {
+    /// A genesis state with the given runtime state and an empty treasury.
+    pub fn genesis(runtime: RuntimeState) -> Self {
+        TreasuryState {
+            runtime,
+            ..TreasuryState::default()
+        }
+    }
+
+    /// Execute the block at the given height on top of this state. Invalid extrinsics leave the
+    /// state untouched, just like in the runtime. At the end of a spend period, approved spends
+    /// are paid out after the block's extrinsics.
+    pub fn execute_block(&self, height: u64, extrinsics: &[TreasuryExtrinsic]) -> TreasuryState {
+        let mut state = self.clone();
+        for extrinsic in extrinsics {
+            state.execute(extrinsic);
+        }
+        if height.is_multiple_of(SPEND_PERIOD) {
+            state.pay_out();
+        }
+        state
+    }
+
+    fn execute(&mut self, extrinsic: &TreasuryExtrinsic) {
+        match extrinsic {
+            TreasuryExtrinsic::Apply(t) => {
+                let runtime = MeteredRuntime::next_state(&self.runtime, t);
+                let share = fee(t.weight())
+                    .checked_mul(TREASURY_SHARE_PERCENT)
+                    .map(|share| share / 100);
+                let Some(treasury) = share.and_then(|share| self.treasury.checked_add(share))
+                else {
+                    return;
+                };
+                if runtime == self.runtime {
+                    return;
+                }
+                self.treasury = treasury;
+                self.runtime = runtime;
+            }
+            TreasuryExtrinsic::ProposeSpend {
+                proposer,
+                nonce,
+                beneficiary,
+                amount,
+                signature,
+            } => {
+                let payload = propose_payload(*proposer, *nonce, *beneficiary, *amount);
+                let mut account = self.runtime.account(*proposer);
+                let bond = proposal_bond(*amount);
+                if !signature.verify(*proposer, payload)
+                    || *nonce != account.nonce
+                    || account.balance < bond
+                {
+                    return;
+                }
+                account.balance -= bond;
+                account.nonce += 1;
+                self.runtime.set_account(*proposer, account);
+                let proposal = SpendProposal {
+                    proposer: *proposer,
+                    beneficiary: *beneficiary,
+                    amount: *amount,
+                    bond,
+                    approved: false,
+                };
+                self.proposals.insert(self.next_proposal, proposal);
+                self.next_proposal += 1;
+            }
+            TreasuryExtrinsic::Approve { id, signature } => {
+                let Some(proposal) = self.proposals.get(id).copied() else {
+                    return;
+                };
+                if proposal.approved || !signature.verify(ROOT, decision_payload(*id, true)) {
+                    return;
+                }
+                let (proposer, bond) = (proposal.proposer, proposal.bond);
+                if !self.credit(proposer, bond) {
+                    return;
+                }
+                if let Some(proposal) = self.proposals.get_mut(id) {
+                    proposal.approved = true;
+                }
+                self.approvals.push(*id);
+            }
+            TreasuryExtrinsic::Reject { id, signature } => {
+                let Some(proposal) = self.proposals.get(id).copied() else {
+                    return;
+                };
+                if proposal.approved || !signature.verify(ROOT, decision_payload(*id, false)) {
+                    return;
+                }
+                let Some(treasury) = self.treasury.checked_add(proposal.bond) else {
+                    return;
+                };
+                self.treasury = treasury;
+                self.proposals.remove(id);
+            }
+        }
+    }
+
+    /// Pay the approved spends in the order they were approved, until one does not fit into the
+    /// treasury anymore.
+    fn pay_out(&mut self) {
+        while let Some(&id) = self.approvals.first() {
+            let proposal = self.proposals[&id];
+            if proposal.amount > self.treasury
+                || !self.credit(proposal.beneficiary, proposal.amount)
+            {
+                break;
+            }
+            self.treasury -= proposal.amount;
+            self.proposals.remove(&id);
+            self.approvals.remove(0);
+        }
+    }
+
+    /// Give the amount to the user, unless their balance would overflow.
+    fn credit(&mut self, who: User, amount: u64) -> bool {
+        let mut account = self.runtime.account(who);
+        let Some(balance) = account.balance.checked_add(amount) else {
+            return false;
+        };
+        account.balance = balance;
+        self.runtime.set_account(who, account);
+        true
+    }
+}
+
+#[cfg(test)]
+use super::keystore::Keystore;
+#[cfg(test)]
+use super::runtime::signing_payload;
+#[cfg(test)]
+use crate::c1_state_machine::AccountingTransaction;
+
+#[cfg(test)]
+fn propose(proposer: User, nonce: u64, amount: u64) -> TreasuryExtrinsic {
+    let signature = Keystore::dev()
+        .sign(
+            proposer,
+            propose_payload(proposer, nonce, User::Charlie, amount),
+        )
+        .unwrap();
+    TreasuryExtrinsic::ProposeSpend {
+        proposer,
+        nonce,
+        beneficiary: User::Charlie,
+        amount,
+        signature,
+    }
+}
+
+#[cfg(test)]
+fn decide(signer: User, id: u64, approve: bool) -> TreasuryExtrinsic {
+    let signature = Keystore::dev()
+        .sign(signer, decision_payload(id, approve))
+        .unwrap();
+    if approve {
+        TreasuryExtrinsic::Approve { id, signature }
+    } else {
+        TreasuryExtrinsic::Reject { id, signature }
+    }
+}
+
+/// Bob has 1000, and the treasury already holds 500.
+#[cfg(test)]
+fn funded() -> TreasuryState {
+    TreasuryState {
+        treasury: 500,
+        ..TreasuryState::genesis(RuntimeState::genesis(&[(User::Bob, 1_000)]))
+    }
+}
+
+#[test]
+fn treasury_takes_share_of_fees() {
+    let call = AccountingTransaction::Transfer {
+        sender: User::Bob,
+        receiver: User::Alice,
+        amount: 100,
+    };
+    let signature = Keystore::dev()
+        .sign(User::Bob, signing_payload(User::Bob, 0, &call))
+        .unwrap();
+    let transfer = SignedExtrinsic {
+        signer: User::Bob,
+        nonce: 0,
+        call,
+        signature,
+    };
+    let genesis = TreasuryState::genesis(RuntimeState::genesis(&[(User::Bob, 1_000)]));
+
+    // The transfer weighs 200, so its fee is 200, and 40 of it go to the treasury.
+    let state = genesis.execute_block(1, &[TreasuryExtrinsic::Apply(transfer.clone())]);
+    assert_eq!(state.treasury, 40);
+    assert_eq!(state.runtime.account(User::Bob).balance, 700);
+
+    // Replaying it is invalid, and earns the treasury nothing.
+    let replayed = state.execute_block(2, &[TreasuryExtrinsic::Apply(transfer)]);
+    assert_eq!(replayed, state);
+}
+
+#[test]
+fn treasury_pays_approved_spends_at_spend_period() {
+    let state = funded().execute_block(1, &[propose(User::Bob, 0, 300), decide(ROOT, 0, true)]);
+    // The bond of 15 was put down and given back.
+    assert_eq!(state.runtime.account(User::Bob).balance, 1_000);
+    assert_eq!(state.approvals, vec![0]);
+
+    let waiting = state.execute_block(SPEND_PERIOD - 1, &[]);
+    assert_eq!(waiting.runtime.account(User::Charlie).balance, 0);
+
+    let paid = waiting.execute_block(SPEND_PERIOD, &[]);
+    assert_eq!(paid.runtime.account(User::Charlie).balance, 300);
+    assert_eq!(paid.treasury, 200);
+    assert!(paid.proposals.is_empty());
+    assert!(paid.approvals.is_empty());
+}
+
+#[test]
+fn treasury_spends_wait_for_funds() {
+    let state = funded().execute_block(
+        1,
+        &[
+            propose(User::Bob, 0, 600),
+            propose(User::Bob, 1, 100),
+            decide(ROOT, 0, true),
+            decide(ROOT, 1, true),
+        ],
+    );
+    // The first spend does not fit, and the second must not jump the queue.
+    let period = state.execute_block(SPEND_PERIOD, &[]);
+    assert_eq!(period.treasury, 500);
+    assert_eq!(period.approvals, vec![0, 1]);
+
+    let richer = TreasuryState {
+        treasury: 700,
+        ..period
+    };
+    let paid = richer.execute_block(2 * SPEND_PERIOD, &[]);
+    assert_eq!(paid.treasury, 0);
+    assert_eq!(paid.runtime.account(User::Charlie).balance, 700);
+}
+
+#[test]
+fn treasury_rejection_forfeits_bond() {
+    let state = funded().execute_block(1, &[propose(User::Bob, 0, 1_000)]);
+    assert_eq!(state.proposals[&0].bond, 50);
+    assert_eq!(state.runtime.account(User::Bob).balance, 950);
+
+    let rejected = state.execute_block(2, &[decide(ROOT, 0, false)]);
+    assert!(rejected.proposals.is_empty());
+    assert_eq!(rejected.treasury, 550);
+    assert_eq!(rejected.runtime.account(User::Bob).balance, 950);
+}
+
+#[test]
+fn treasury_decisions_need_root() {
+    let state = funded().execute_block(1, &[propose(User::Bob, 0, 100)]);
+    for invalid in [
+        decide(User::Bob, 0, true),
+        decide(User::Bob, 0, false),
+        decide(ROOT, 1, true),
+    ] {
+        assert_eq!(state.execute_block(2, &[invalid]), state);
+    }
+
+    // An approval can't be taken back.
+    let approved = state.execute_block(2, &[decide(ROOT, 0, true)]);
+    assert_eq!(
+        approved.execute_block(3, &[decide(ROOT, 0, false)]),
+        approved
+    );
+}
+
+#[test]
+fn treasury_rejects_overflowing_transitions() {
+    let mut full = funded().execute_block(1, &[propose(User::Bob, 0, 300)]);
+    full.treasury = u64::MAX;
+
+    // The forfeited bond does not fit into the treasury anymore.
+    let rejected = full.execute_block(2, &[decide(ROOT, 0, false)]);
+    assert_eq!(rejected, full);
+}
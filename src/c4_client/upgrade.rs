@@ -0,0 +1,535 @@
+//! A chain that runs for years will need its rules changed at some point. One way is a hard
+//! fork: everybody installs new node software that switches to the new rules at an agreed upon
+//! height. That requires every node operator to upgrade in time. Nodes that do not are left
+//! behind on a chain of their own.
+//!
+//! Substrate chains take a different route. The state transition function itself is a part of
+//! the chain, and it can be upgraded by an extrinsic like any other piece of state. Nodes only
+//! need to know every runtime version, which they look up in a registry here. Real nodes go one
+//! step further, and load the new runtime's code from the chain itself.
+//!
+//! An upgrade works like this:
+//! 1. The root account submits a `SetCode` extrinsic, naming a registered runtime version and a
+//!    future height at which it takes over.
+//! 2. At that height, the new version becomes active, and executes every block from then on.
+//! 3. Every block records the runtime version that its author executed it with. Importers check
+//!    that it matches the version that should be active, so nodes that disagree about the
+//!    upgrade notice right away, instead of silently computing different states.
+//!
+//! Substrate records the version in a digest item of the header, alongside the seal. We do the
+//! same by wrapping the chain's engine in `Versioned`. A client executes blocks with the version
+//! they record, and seals the version that is active into the blocks it authors, once it is
+//! given the `UpgradeExecutive`. The inner engine's seal does not cover the version, but it does
+//! cover the state root, and the state records the version that executed the block.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::executive::Executive;
+use super::keystore::Signature;
+use super::runtime::{Runtime, RuntimeState, SignedExtrinsic};
+use super::ImportError;
+use crate::c1_state_machine::{AccountingTransaction, StateMachine, User};
+use crate::c3_consensus::{Consensus, Header};
+use crate::hashing::{hash, H256};
+
+/// Identifies one version of the runtime.
+pub type SpecVersion = u32;
+
+/// The account that is allowed to upgrade the runtime. On a real chain this would be a
+/// governance body rather than a single account.
+pub const ROOT: User = User::Alice;
+
+/// A state transition function for a single extrinsic.
+pub type ExecuteFn = fn(&RuntimeState, &SignedExtrinsic) -> RuntimeState;
+
+/// Version 2 of the runtime. Version 1 lets anybody mint money for themselves, which was fun
+/// while it lasted. Version 2 only lets the root account mint.
+pub fn runtime_v2(state: &RuntimeState, t: &SignedExtrinsic) -> RuntimeState {
+    if matches!(t.call, AccountingTransaction::Mint { .. }) && t.signer != ROOT {
+        return state.clone();
+    }
+    Runtime::next_state(state, t)
+}
+
+/// Every runtime version this node knows how to execute.
+#[derive(Clone, Default)]
+pub struct RuntimeRegistry {
+    runtimes: BTreeMap<SpecVersion, ExecuteFn>,
+}
+
+impl RuntimeRegistry {
+    /// Create a registry that knows no runtime versions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry that knows the runtime versions shipped with this node.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(1, Runtime::next_state);
+        registry.register(2, runtime_v2);
+        registry
+    }
+
+    /// Add a runtime version to the registry.
+    pub fn register(&mut self, version: SpecVersion, execute: ExecuteFn) {
+        self.runtimes.insert(version, execute);
+    }
+
+    /// Look up a runtime version.
+    pub fn get(&self, version: SpecVersion) -> Option<ExecuteFn> {
+        self.runtimes.get(&version).copied()
+    }
+}
+
+/// An extrinsic that can be applied to the upgradable runtime.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UpgradeExtrinsic {
+    /// An ordinary extrinsic, executed by the active runtime version.
+    Apply(SignedExtrinsic),
+    /// Switch to the given runtime version at the given height. Must be signed by the root
+    /// account.
+    SetCode {
+        version: SpecVersion,
+        at: u64,
+        signature: Signature,
+    },
+}
+
+/// Calculate the hash that the root account must sign to schedule an upgrade.
+pub fn set_code_payload(version: SpecVersion, at: u64) -> H256 {
+    hash(&("set_code", version, at))
+}
+
+/// An upgrade that has been scheduled but has not happened yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScheduledUpgrade {
+    pub version: SpecVersion,
+    pub at: u64,
+}
+
+/// The runtime state, along with which version of the runtime is in charge of it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VersionedState {
+    /// The version that executed the most recent block.
+    pub version: SpecVersion,
+    /// The next upgrade, if one has been scheduled.
+    pub scheduled: Option<ScheduledUpgrade>,
+    pub runtime: RuntimeState,
+}
+
+/// Why a block was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpgradeError {
+    /// The block was executed with a different version than the one that should be active.
+    VersionMismatch {
+        expected: SpecVersion,
+        recorded: SpecVersion,
+    },
+    /// The version that should be active is not in our registry. This node must be upgraded
+    /// before it can follow the chain any further.
+    UnknownVersion(SpecVersion),
+}
+
+impl std::fmt::Display for UpgradeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpgradeError::VersionMismatch { expected, recorded } => write!(
+                f,
+                "block was executed with runtime version {recorded}, but version {expected} \
+                 is active"
+            ),
+            UpgradeError::UnknownVersion(version) => {
+                write!(f, "runtime version {version} is not known to this node")
+            }
+        }
+    }
+}
+
+impl VersionedState {
+    /// A genesis state that starts out with the given runtime version.
+    pub fn genesis(version: SpecVersion, runtime: RuntimeState) -> Self {
+        VersionedState {
+            version,
+            scheduled: None,
+            runtime,
+        }
+    }
+
+    /// The runtime version that executes the block at the given height, which must be the
+    /// child of the block this is the state after. Authors record this version in their blocks.
+    pub fn version_at(&self, height: u64) -> SpecVersion {
+        match self.scheduled {
+            Some(upgrade) if upgrade.at <= height => upgrade.version,
+            _ => self.version,
+        }
+    }
+
+    /// Execute the block at the given height, which claims to have been executed with the
+    /// `recorded` runtime version, on top of this state.
+    ///
+    /// Just like in the runtime, invalid extrinsics leave the state untouched. That includes
+    /// upgrades that are not signed by root, that are not in the future, or that name a version
+    /// we do not know. Scheduling those would leave the chain stuck.
+    pub fn execute_block(
+        &self,
+        registry: &RuntimeRegistry,
+        height: u64,
+        recorded: SpecVersion,
+        extrinsics: &[UpgradeExtrinsic],
+    ) -> Result<VersionedState, UpgradeError> {
+        let version = self.version_at(height);
+        if recorded != version {
+            return Err(UpgradeError::VersionMismatch {
+                expected: version,
+                recorded,
+            });
+        }
+        let execute = registry
+            .get(version)
+            .ok_or(UpgradeError::UnknownVersion(version))?;
+
+        let mut state = VersionedState {
+            version,
+            scheduled: self.scheduled.filter(|upgrade| upgrade.at > height),
+            runtime: self.runtime.clone(),
+        };
+        for extrinsic in extrinsics {
+            state.apply(registry, execute, Some(height), extrinsic);
+        }
+        Ok(state)
+    }
+
+    /// Apply a single extrinsic with the given runtime version. Upgrades must be scheduled
+    /// after the given height, if it is known.
+    fn apply(
+        &mut self,
+        registry: &RuntimeRegistry,
+        execute: ExecuteFn,
+        height: Option<u64>,
+        extrinsic: &UpgradeExtrinsic,
+    ) {
+        match extrinsic {
+            UpgradeExtrinsic::Apply(t) => self.runtime = execute(&self.runtime, t),
+            UpgradeExtrinsic::SetCode {
+                version,
+                at,
+                signature,
+            } => {
+                if signature.verify(ROOT, set_code_payload(*version, *at))
+                    && height.is_none_or(|height| *at > height)
+                    && registry.get(*version).is_some()
+                {
+                    self.scheduled = Some(ScheduledUpgrade {
+                        version: *version,
+                        at: *at,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// The upgradable runtime as a state machine, for clients to run. A single extrinsic does not
+/// know the height of its block, so this executes it with the version of the most recent
+/// block, out of the builtin registry. The `UpgradeExecutive` executes whole blocks properly.
+#[derive(Default)]
+pub struct Upgradable;
+
+impl StateMachine for Upgradable {
+    type State = VersionedState;
+    type Transition = UpgradeExtrinsic;
+
+    fn next_state(starting_state: &VersionedState, t: &UpgradeExtrinsic) -> VersionedState {
+        let registry = RuntimeRegistry::builtin();
+        let Some(execute) = registry.get(starting_state.version) else {
+            return starting_state.clone();
+        };
+        let mut state = starting_state.clone();
+        state.apply(&registry, execute, None, t);
+        state
+    }
+
+    fn human_name() -> String {
+        "Upgradable Signed Accounted Currency".into()
+    }
+}
+
+/// The digest of a block sealed by a `Versioned` engine.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VersionedDigest<D> {
+    /// The runtime version that the author executed the block with.
+    pub version: SpecVersion,
+    /// The seal of the wrapped engine.
+    pub inner: D,
+}
+
+/// Another engine, whose digests also record the runtime version of the block.
+#[derive(Default)]
+pub struct Versioned<C> {
+    pub inner: C,
+}
+
+/// The header as the wrapped engine sealed it, without the version.
+fn inner_header<D: Clone>(header: &Header<VersionedDigest<D>>) -> Header<D> {
+    Header::new(
+        header.parent(),
+        header.height(),
+        header.state_root(),
+        header.extrinsics_root(),
+        header.consensus_digest().inner.clone(),
+    )
+}
+
+/// The given header, sealed by the wrapped engine, with the given version recorded next to the
+/// seal.
+fn with_version<D: Clone>(header: Header<D>, version: SpecVersion) -> Header<VersionedDigest<D>> {
+    Header::new(
+        header.parent(),
+        header.height(),
+        header.state_root(),
+        header.extrinsics_root(),
+        VersionedDigest {
+            version,
+            inner: header.consensus_digest().clone(),
+        },
+    )
+}
+
+impl<C: Consensus> Consensus for Versioned<C> {
+    type Digest = VersionedDigest<C::Digest>;
+
+    fn validate(&self, parent_digest: &Self::Digest, header: &Header<Self::Digest>) -> bool {
+        self.inner
+            .validate(&parent_digest.inner, &inner_header(header))
+    }
+
+    /// Seal with the wrapped engine, and record the parent's version. That is only right until
+    /// the next upgrade, so clients author with the `UpgradeExecutive`, which knows better.
+    fn seal(
+        &self,
+        parent_digest: &Self::Digest,
+        partial_header: Header<()>,
+    ) -> Option<Header<Self::Digest>> {
+        let header = self.inner.seal(&parent_digest.inner, partial_header)?;
+        Some(with_version(header, parent_digest.version))
+    }
+
+    fn work(&self, header: &Header<Self::Digest>) -> u128 {
+        self.inner.work(&inner_header(header))
+    }
+}
+
+/// An executive that executes every block with the runtime version it records, and checks that
+/// this is the version that should be active.
+#[derive(Clone, Default)]
+pub struct UpgradeExecutive {
+    pub registry: RuntimeRegistry,
+}
+
+impl UpgradeExecutive {
+    /// An executive that knows the runtime versions shipped with this node.
+    pub fn builtin() -> Self {
+        UpgradeExecutive {
+            registry: RuntimeRegistry::builtin(),
+        }
+    }
+}
+
+impl<C: Consensus> Executive<Versioned<C>, Upgradable> for UpgradeExecutive {
+    fn execute_block(
+        &self,
+        _engine: &Versioned<C>,
+        pre_state: VersionedState,
+        height: u64,
+        digest: Option<&VersionedDigest<C::Digest>>,
+        body: &[UpgradeExtrinsic],
+    ) -> Result<VersionedState, ImportError> {
+        let recorded = digest.map_or_else(|| pre_state.version_at(height), |d| d.version);
+        pre_state
+            .execute_block(&self.registry, height, recorded, body)
+            .map_err(|error| {
+                tracing::debug!(%error, "rejected a block");
+                ImportError::WrongVersion
+            })
+    }
+
+    fn seal(
+        &self,
+        engine: &Versioned<C>,
+        parent_digest: &VersionedDigest<C::Digest>,
+        pre_state: &VersionedState,
+        partial_header: Header<()>,
+    ) -> Option<Header<VersionedDigest<C::Digest>>> {
+        let version = pre_state.version_at(partial_header.height());
+        let header = engine.inner.seal(&parent_digest.inner, partial_header)?;
+        Some(with_version(header, version))
+    }
+}
+
+#[cfg(test)]
+use super::keystore::Keystore;
+#[cfg(test)]
+use super::runtime::signing_payload;
+
+#[cfg(test)]
+fn set_code(signer: User, version: SpecVersion, at: u64) -> UpgradeExtrinsic {
+    UpgradeExtrinsic::SetCode {
+        version,
+        at,
+        signature: Keystore::dev()
+            .sign(signer, set_code_payload(version, at))
+            .unwrap(),
+    }
+}
+
+#[cfg(test)]
+fn bob_mints(nonce: u64) -> SignedExtrinsic {
+    let call = AccountingTransaction::Mint {
+        minter: User::Bob,
+        amount: 10,
+    };
+    let signature = Keystore::dev()
+        .sign(User::Bob, signing_payload(User::Bob, nonce, &call))
+        .unwrap();
+    SignedExtrinsic {
+        signer: User::Bob,
+        nonce,
+        call,
+        signature,
+    }
+}
+
+/// A registry of two made up runtime versions: version 1 gives Bob some money for every
+/// extrinsic, and version 2 ignores every extrinsic.
+#[cfg(test)]
+fn test_registry() -> RuntimeRegistry {
+    let mut registry = RuntimeRegistry::new();
+    registry.register(1, |state, _| {
+        RuntimeState::genesis(&[(User::Bob, state.account(User::Bob).balance + 10)])
+    });
+    registry.register(2, |state, _| state.clone());
+    registry
+}
+
+#[test]
+fn upgrade_switches_runtime_at_scheduled_height() {
+    let registry = test_registry();
+    let genesis = VersionedState::genesis(1, RuntimeState::genesis(&[]));
+    let apply = UpgradeExtrinsic::Apply(bob_mints(0));
+
+    let one = genesis
+        .execute_block(&registry, 1, 1, &[set_code(ROOT, 2, 3), apply.clone()])
+        .unwrap();
+    assert_eq!(one.runtime.account(User::Bob).balance, 10);
+    assert_eq!(one.version_at(2), 1);
+    assert_eq!(one.version_at(3), 2);
+
+    let two = one
+        .execute_block(&registry, 2, 1, std::slice::from_ref(&apply))
+        .unwrap();
+    assert_eq!(two.runtime.account(User::Bob).balance, 20);
+
+    let three = two.execute_block(&registry, 3, 2, &[apply]).unwrap();
+    assert_eq!(three.version, 2);
+    assert_eq!(three.scheduled, None);
+    assert_eq!(three.runtime, two.runtime);
+}
+
+#[test]
+fn upgrade_rejects_blocks_with_wrong_version() {
+    let genesis = VersionedState::genesis(1, RuntimeState::genesis(&[]));
+    let one = genesis
+        .execute_block(&test_registry(), 1, 1, &[set_code(ROOT, 2, 2)])
+        .unwrap();
+
+    assert_eq!(
+        one.execute_block(&test_registry(), 2, 1, &[]),
+        Err(UpgradeError::VersionMismatch {
+            expected: 2,
+            recorded: 1
+        })
+    );
+    assert_eq!(
+        one.execute_block(&RuntimeRegistry::new(), 2, 2, &[]),
+        Err(UpgradeError::UnknownVersion(2))
+    );
+}
+
+#[test]
+fn upgrade_requires_root_and_known_future_version() {
+    let registry = test_registry();
+    let genesis = VersionedState::genesis(1, RuntimeState::genesis(&[]));
+
+    for invalid in [
+        set_code(User::Bob, 2, 5),
+        set_code(ROOT, 3, 5),
+        set_code(ROOT, 2, 1),
+    ] {
+        let one = genesis.execute_block(&registry, 1, 1, &[invalid]).unwrap();
+        assert_eq!(one.scheduled, None);
+    }
+}
+
+#[test]
+fn upgrade_v2_only_lets_root_mint() {
+    let state = RuntimeState::genesis(&[]);
+    assert_eq!(runtime_v2(&state, &bob_mints(0)), state);
+}
+
+#[cfg(test)]
+use super::{FullClient, ImportBlock, LongestChain, SimplePool};
+
+#[cfg(test)]
+type UpgradableClient = FullClient<Versioned<()>, Upgradable, LongestChain, SimplePool<Upgradable>>;
+
+#[cfg(test)]
+fn upgradable_client() -> UpgradableClient {
+    let genesis = VersionedState::genesis(1, RuntimeState::genesis(&[]));
+    UpgradableClient::new(genesis).with_executive(UpgradeExecutive::builtin())
+}
+
+#[test]
+fn upgrade_client_records_the_active_version() {
+    let mut client = upgradable_client();
+    client.submit_transaction(set_code(ROOT, 2, 2));
+    let one = client.author_and_import_automatic_block().unwrap();
+    client.submit_transaction(UpgradeExtrinsic::Apply(bob_mints(0)));
+    let two = client.author_and_import_automatic_block().unwrap();
+
+    let version = |hash| {
+        client
+            .get_block(hash)
+            .unwrap()
+            .header()
+            .consensus_digest()
+            .version
+    };
+    assert_eq!((version(one), version(two)), (1, 2));
+    // Version 2 does not let Bob mint.
+    let state = client.get_state(two).unwrap();
+    assert_eq!(state.version, 2);
+    assert_eq!(state.runtime.account(User::Bob).balance, 0);
+}
+
+#[test]
+fn upgrade_client_rejects_blocks_with_the_wrong_version() {
+    let mut client = upgradable_client();
+    client.submit_transaction(set_code(ROOT, 2, 2));
+    let one = client.author_and_import_automatic_block().unwrap();
+
+    // A node that does not know about upgrades keeps sealing version 1.
+    let mut outdated =
+        UpgradableClient::new(VersionedState::genesis(1, RuntimeState::genesis(&[])));
+    assert!(outdated.import_block(client.get_block(one).unwrap()));
+    let stale = outdated.author_and_import_automatic_block().unwrap();
+    let stale = outdated.get_block(stale).unwrap();
+    assert_eq!(stale.header().consensus_digest().version, 1);
+
+    assert_eq!(
+        client.try_import_block(stale),
+        Err(ImportError::WrongVersion)
+    );
+}
@@ -0,0 +1,471 @@
+//! The engines of chapter 3 have a fixed set of authorities. On a real chain, the set changes:
+//! new validators join by bonding some of their money, and old ones leave and get their bond
+//! back. This module lets them do so with extrinsics.
+//!
+//! Changing the set in the middle of an epoch would leave nodes unsure which set a block should
+//! be checked against, since they might not have executed the block with the change yet. So
+//! joins and leaves are only queued, and the queue takes effect at the start of the next epoch,
+//! like the session key rotations of chapter 3. Every past set is kept around, so that old
+//! blocks are always checked against the set of their own epoch:
+//! * The `EpochPoa` engine accepts a block sealed by any validator of the block's epoch.
+//! * The `EquivocationDetector` reports validators who sealed two different blocks at the same
+//!   height. An equivocation is an offense of whoever was a validator back then, even if they
+//!   left since.
+//!
+//! A leaving validator gets their bond back when the leave takes effect.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::keystore::Signature;
+use super::runtime::{Runtime, RuntimeState, SignedExtrinsic};
+use crate::c1_state_machine::{StateMachine, User};
+use crate::c3_consensus::{epoch_of, unsealed, Consensus, Header, EPOCH_LENGTH};
+use crate::crypto::sig::{self, Keypair, PublicKey};
+use crate::hashing::{hash_of, header_hash, H256};
+
+/// The least a validator must bond to join the set.
+pub const MIN_VALIDATOR_BOND: u64 = 100;
+
+/// A member of the validator set, and the session key they seal blocks with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Validator {
+    pub who: User,
+    pub session_key: PublicKey,
+}
+
+/// An extrinsic that can be applied to a chain with a changing validator set.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ValidatorExtrinsic {
+    /// An ordinary extrinsic.
+    Apply(SignedExtrinsic),
+    /// Bond the given amount, and join the validator set from the next epoch on. The nonce is
+    /// the account nonce, just like in a signed extrinsic.
+    Join {
+        who: User,
+        nonce: u64,
+        bond: u64,
+        session_key: PublicKey,
+        signature: Signature,
+    },
+    /// Leave the validator set from the next epoch on.
+    Leave {
+        who: User,
+        nonce: u64,
+        signature: Signature,
+    },
+}
+
+/// Calculate the hash that a validator must sign to join the set.
+pub fn join_payload(who: User, nonce: u64, bond: u64, session_key: &PublicKey) -> H256 {
+    hash_of("join validators", &(who, nonce, bond, session_key))
+}
+
+/// Calculate the hash that a validator must sign to leave the set.
+pub fn leave_payload(who: User, nonce: u64) -> H256 {
+    hash_of("leave validators", &(who, nonce))
+}
+
+/// The validator set of every epoch so far.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ValidatorSets {
+    /// The sets, by the epoch they took effect in. A set stays in effect until the next one.
+    sets: BTreeMap<u64, Vec<Validator>>,
+}
+
+impl ValidatorSets {
+    /// The history of a chain that starts out with the given validators.
+    pub fn genesis(validators: Vec<Validator>) -> Self {
+        ValidatorSets {
+            sets: BTreeMap::from([(0, validators)]),
+        }
+    }
+
+    /// The validator set of the epoch that the block at the given height belongs to.
+    pub fn at(&self, height: u64) -> &[Validator] {
+        self.sets
+            .range(..=epoch_of(height))
+            .next_back()
+            .map_or(&[], |(_, set)| set)
+    }
+}
+
+/// The runtime state, along with the validator sets.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ValidatorState {
+    pub runtime: RuntimeState,
+    /// The bonds of every validator, and of those that are queued to join.
+    pub bonds: BTreeMap<User, u64>,
+    /// The sets of the current and every past epoch.
+    pub sets: ValidatorSets,
+    /// The set that the next epoch starts with, with every queued join and leave applied.
+    pub next: Vec<Validator>,
+}
+
+impl ValidatorState {
+    /// A genesis state with the given runtime state and validators. Genesis validators have
+    /// nothing bonded.
+    pub fn genesis(runtime: RuntimeState, validators: Vec<Validator>) -> Self {
+        ValidatorState {
+            runtime,
+            bonds: BTreeMap::new(),
+            sets: ValidatorSets::genesis(validators.clone()),
+            next: validators,
+        }
+    }
+
+    /// Execute the block at the given height on top of this state. The first block of every
+    /// epoch puts the queued set into effect before its extrinsics. Invalid extrinsics leave
+    /// the state untouched, just like in the runtime.
+    pub fn execute_block(&self, height: u64, extrinsics: &[ValidatorExtrinsic]) -> ValidatorState {
+        let mut state = self.clone();
+        if height > 0 && height.is_multiple_of(EPOCH_LENGTH) {
+            state.start_epoch(height);
+        }
+        for extrinsic in extrinsics {
+            state.execute(extrinsic);
+        }
+        state
+    }
+
+    fn start_epoch(&mut self, height: u64) {
+        if self.sets.at(height) != self.next.as_slice() {
+            self.sets.sets.insert(epoch_of(height), self.next.clone());
+        }
+        let left: Vec<User> = self
+            .bonds
+            .keys()
+            .copied()
+            .filter(|who| !self.next.iter().any(|v| v.who == *who))
+            .collect();
+        for who in left {
+            let bond = self.bonds.remove(&who).unwrap_or(0);
+            let mut account = self.runtime.account(who);
+            account.balance += bond;
+            self.runtime.set_account(who, account);
+        }
+    }
+
+    fn execute(&mut self, extrinsic: &ValidatorExtrinsic) {
+        match extrinsic {
+            ValidatorExtrinsic::Apply(t) => {
+                self.runtime = Runtime::next_state(&self.runtime, t);
+            }
+            ValidatorExtrinsic::Join {
+                who,
+                nonce,
+                bond,
+                session_key,
+                signature,
+            } => {
+                let mut account = self.runtime.account(*who);
+                if !signature.verify(*who, join_payload(*who, *nonce, *bond, session_key))
+                    || *nonce != account.nonce
+                    || *bond < MIN_VALIDATOR_BOND
+                    || account.balance < *bond
+                    || self.next.iter().any(|v| v.who == *who)
+                {
+                    return;
+                }
+                account.balance -= bond;
+                account.nonce += 1;
+                self.runtime.set_account(*who, account);
+                *self.bonds.entry(*who).or_default() += bond;
+                self.next.push(Validator {
+                    who: *who,
+                    session_key: *session_key,
+                });
+            }
+            ValidatorExtrinsic::Leave {
+                who,
+                nonce,
+                signature,
+            } => {
+                let mut account = self.runtime.account(*who);
+                let Some(position) = self.next.iter().position(|v| v.who == *who) else {
+                    return;
+                };
+                // A chain without validators could never author another block.
+                if !signature.verify(*who, leave_payload(*who, *nonce))
+                    || *nonce != account.nonce
+                    || self.next.len() == 1
+                {
+                    return;
+                }
+                account.nonce += 1;
+                self.runtime.set_account(*who, account);
+                self.next.remove(position);
+            }
+        }
+    }
+}
+
+/// The seal of a block: the position of the sealing validator in the set of the block's epoch,
+/// and their session key's signature over the hash of the header without its seal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EpochSeal {
+    pub validator: usize,
+    pub signature: sig::Signature,
+}
+
+/// A header sealed by a validator. Genesis has no seal.
+pub type EpochHeader = Header<Option<EpochSeal>>;
+
+/// The validator that sealed the given header, if its seal is valid for the header's epoch.
+pub fn sealed_by(sets: &ValidatorSets, header: &EpochHeader) -> Option<User> {
+    let seal = (*header.consensus_digest())?;
+    let validator = sets.at(header.height()).get(seal.validator)?;
+    validator
+        .session_key
+        .verify(header_hash(&unsealed(header)).as_bytes(), &seal.signature)
+        .then_some(validator.who)
+}
+
+/// A Proof of Authority engine that accepts blocks sealed by any validator of the block's
+/// epoch, see the module docs.
+pub struct EpochPoa {
+    pub sets: ValidatorSets,
+    /// The session key this node seals with. Nodes that do not author leave this out.
+    pub local: Option<Keypair>,
+}
+
+impl Consensus for EpochPoa {
+    type Digest = Option<EpochSeal>;
+
+    fn validate(&self, _: &Self::Digest, header: &EpochHeader) -> bool {
+        sealed_by(&self.sets, header).is_some()
+    }
+
+    /// Sealing fails unless the local session key belongs to a validator of the block's epoch.
+    fn seal(&self, _: &Self::Digest, partial_header: Header<()>) -> Option<EpochHeader> {
+        let keypair = self.local.as_ref()?;
+        let validator = self
+            .sets
+            .at(partial_header.height())
+            .iter()
+            .position(|v| v.session_key == keypair.public())?;
+        let signature = keypair.sign(header_hash(&partial_header).as_bytes());
+        Some(Header::new(
+            partial_header.parent(),
+            partial_header.height(),
+            partial_header.state_root(),
+            partial_header.extrinsics_root(),
+            Some(EpochSeal {
+                validator,
+                signature,
+            }),
+        ))
+    }
+
+    fn human_name() -> String {
+        "Epoch PoA".into()
+    }
+}
+
+/// Proof that a validator sealed two different blocks at the same height.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Equivocation {
+    pub offender: User,
+    pub height: u64,
+    pub first: EpochHeader,
+    pub second: EpochHeader,
+}
+
+/// Watches the headers a node sees for validators that equivocate.
+#[derive(Clone, Debug, Default)]
+pub struct EquivocationDetector {
+    /// The first header every validator sealed at every height.
+    seen: BTreeMap<(u64, User), EpochHeader>,
+}
+
+impl EquivocationDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look at a header, and report its sealer if they sealed a different header at the same
+    /// height before. Headers without a valid seal for their epoch are ignored, so that nobody
+    /// can be framed with a forged one.
+    pub fn observe(&mut self, sets: &ValidatorSets, header: &EpochHeader) -> Option<Equivocation> {
+        let offender = sealed_by(sets, header)?;
+        let first = self
+            .seen
+            .entry((header.height(), offender))
+            .or_insert_with(|| header.clone());
+        (unsealed(first) != unsealed(header)).then(|| Equivocation {
+            offender,
+            height: header.height(),
+            first: first.clone(),
+            second: header.clone(),
+        })
+    }
+
+    /// Forget the headers below the given height, once equivocations there can no longer be
+    /// punished.
+    pub fn prune(&mut self, height: u64) {
+        self.seen.retain(|(at, _), _| *at >= height);
+    }
+}
+
+#[cfg(test)]
+use super::keystore::Keystore;
+
+#[cfg(test)]
+fn session(who: User) -> Keypair {
+    Keypair::from_seed([who as u8; 32])
+}
+
+#[cfg(test)]
+fn validator(who: User) -> Validator {
+    Validator {
+        who,
+        session_key: session(who).public(),
+    }
+}
+
+#[cfg(test)]
+fn join(who: User, nonce: u64, bond: u64) -> ValidatorExtrinsic {
+    let session_key = session(who).public();
+    let signature = Keystore::dev()
+        .sign(who, join_payload(who, nonce, bond, &session_key))
+        .unwrap();
+    ValidatorExtrinsic::Join {
+        who,
+        nonce,
+        bond,
+        session_key,
+        signature,
+    }
+}
+
+#[cfg(test)]
+fn leave(who: User, nonce: u64) -> ValidatorExtrinsic {
+    let signature = Keystore::dev()
+        .sign(who, leave_payload(who, nonce))
+        .unwrap();
+    ValidatorExtrinsic::Leave {
+        who,
+        nonce,
+        signature,
+    }
+}
+
+/// Alice validates from genesis, and Bob has 1000 to bond.
+#[cfg(test)]
+fn genesis() -> ValidatorState {
+    ValidatorState::genesis(
+        RuntimeState::genesis(&[(User::Bob, 1_000)]),
+        vec![validator(User::Alice)],
+    )
+}
+
+/// A header at the given height, sealed by the given user's session key in the given sets.
+#[cfg(test)]
+fn sealed(sets: &ValidatorSets, who: User, height: u64, state_root: H256) -> Option<EpochHeader> {
+    let engine = EpochPoa {
+        sets: sets.clone(),
+        local: Some(session(who)),
+    };
+    let partial = Header::new(H256::default(), height, state_root, H256::default(), ());
+    engine.seal(&None, partial)
+}
+
+#[test]
+fn validators_join_at_next_epoch() {
+    let state = genesis().execute_block(3, &[join(User::Bob, 0, 300)]);
+    assert_eq!(state.runtime.account(User::Bob).balance, 700);
+    assert_eq!(state.bonds[&User::Bob], 300);
+    assert_eq!(state.sets.at(3), [validator(User::Alice)]);
+
+    let state = state.execute_block(EPOCH_LENGTH, &[]);
+    assert_eq!(
+        state.sets.at(EPOCH_LENGTH),
+        [validator(User::Alice), validator(User::Bob)]
+    );
+    assert_eq!(state.sets.at(EPOCH_LENGTH - 1), [validator(User::Alice)]);
+}
+
+#[test]
+fn validators_join_needs_bond() {
+    let state = genesis();
+    let too_small = join(User::Bob, 0, MIN_VALIDATOR_BOND - 1);
+    assert_eq!(state.execute_block(1, &[too_small]), state);
+    let too_big = join(User::Bob, 0, 1_001);
+    assert_eq!(state.execute_block(1, &[too_big]), state);
+    let joined = state.execute_block(1, &[join(User::Bob, 0, 100)]);
+    assert_eq!(joined.execute_block(2, &[join(User::Bob, 1, 100)]), joined);
+}
+
+#[test]
+fn validators_leave_refunds_bond_at_next_epoch() {
+    let state = genesis()
+        .execute_block(1, &[join(User::Bob, 0, 300)])
+        .execute_block(EPOCH_LENGTH, &[])
+        .execute_block(EPOCH_LENGTH + 1, &[leave(User::Bob, 1)]);
+    assert_eq!(state.runtime.account(User::Bob).balance, 700);
+
+    let state = state.execute_block(2 * EPOCH_LENGTH, &[]);
+    assert_eq!(state.runtime.account(User::Bob).balance, 1_000);
+    assert!(state.bonds.is_empty());
+    assert_eq!(state.sets.at(2 * EPOCH_LENGTH), [validator(User::Alice)]);
+
+    // The last validator can't leave.
+    assert_eq!(state.execute_block(21, &[leave(User::Alice, 0)]), state);
+}
+
+#[test]
+fn validators_engine_checks_set_of_block_epoch() {
+    let state = genesis()
+        .execute_block(1, &[join(User::Bob, 0, 300)])
+        .execute_block(EPOCH_LENGTH, &[]);
+    let engine = EpochPoa {
+        sets: state.sets.clone(),
+        local: None,
+    };
+
+    let early_bob = sealed(
+        &ValidatorSets::genesis(vec![validator(User::Bob)]),
+        User::Bob,
+        5,
+        H256::default(),
+    )
+    .unwrap();
+    assert!(!engine.validate(&None, &early_bob));
+    assert!(sealed(&state.sets, User::Bob, 5, H256::default()).is_none());
+
+    let bob = sealed(&state.sets, User::Bob, EPOCH_LENGTH + 1, H256::default()).unwrap();
+    assert!(engine.validate(&None, &bob));
+    let alice = sealed(&state.sets, User::Alice, 5, H256::default()).unwrap();
+    assert!(engine.validate(&None, &alice));
+}
+
+#[test]
+fn validators_equivocation_uses_historical_set() {
+    let state = genesis()
+        .execute_block(1, &[join(User::Bob, 0, 300)])
+        .execute_block(EPOCH_LENGTH, &[])
+        .execute_block(EPOCH_LENGTH + 1, &[leave(User::Alice, 0)])
+        .execute_block(2 * EPOCH_LENGTH, &[]);
+    assert_eq!(state.sets.at(2 * EPOCH_LENGTH), [validator(User::Bob)]);
+
+    // Alice equivocated back when she was a validator.
+    let mut detector = EquivocationDetector::new();
+    let first = sealed(&state.sets, User::Alice, 5, H256::from(1)).unwrap();
+    let second = sealed(&state.sets, User::Alice, 5, H256::from(2)).unwrap();
+    assert_eq!(detector.observe(&state.sets, &first), None);
+    assert_eq!(detector.observe(&state.sets, &first), None);
+    let equivocation = detector.observe(&state.sets, &second).unwrap();
+    assert_eq!(equivocation.offender, User::Alice);
+    assert_eq!((equivocation.first, equivocation.second), (first, second));
+
+    // Seals by somebody who was not a validator at the time are ignored.
+    let forged = sealed(
+        &ValidatorSets::genesis(vec![validator(User::Charlie)]),
+        User::Charlie,
+        5,
+        H256::from(3),
+    )
+    .unwrap();
+    assert_eq!(detector.observe(&state.sets, &forged), None);
+}
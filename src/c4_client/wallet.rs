@@ -0,0 +1,236 @@
+//! A wallet is the piece of software that end users interact with. It does not follow the chain
+//! itself. Instead it asks a node about the state of the user's account, builds and signs
+//! extrinsics on the user's behalf, and submits them to the node's transaction pool.
+//!
+//! The trickiest part of the wallet's job is nonce tracking. The chain only knows about the
+//! extrinsics that have already been included in blocks. If a user sends several transfers in
+//! quick succession, the wallet must remember which nonces it has already used, or else the
+//! later transfers will be rejected as replays of the earlier ones.
+
+use super::keystore::Keystore;
+use super::rpc::{NodeApi, RpcError};
+use super::runtime::{signing_payload, SignedExtrinsic};
+use crate::c1_state_machine::{AccountingTransaction, User};
+
+/// Everything that can go wrong while using the wallet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WalletError {
+    /// The keystore does not hold the key for the wallet's account.
+    MissingKey(User),
+    /// The account can not afford the requested transfer. At least as far as the wallet knows.
+    InsufficientBalance { available: u64, required: u64 },
+    /// Talking to the node failed.
+    Rpc(RpcError),
+}
+
+impl From<RpcError> for WalletError {
+    fn from(e: RpcError) -> Self {
+        WalletError::Rpc(e)
+    }
+}
+
+/// A wallet for a single account.
+pub struct Wallet {
+    /// The account this wallet manages.
+    account: User,
+    /// The keys used to sign extrinsics.
+    keystore: Keystore,
+    /// The nonce to use for the next extrinsic. This runs ahead of the on-chain nonce while
+    /// submitted extrinsics are waiting in the pool.
+    nonce: u64,
+    /// The balance as far as the wallet knows. That is the on-chain balance at the last sync,
+    /// minus everything that has been sent since.
+    balance: u64,
+}
+
+impl Wallet {
+    /// Create a wallet for the given account. The keystore must hold the account's key.
+    ///
+    /// The wallet starts out knowing nothing about the chain, so you will want to `sync` it
+    /// before using it.
+    pub fn new(account: User, keystore: Keystore) -> Result<Self, WalletError> {
+        if !keystore.contains(account) {
+            return Err(WalletError::MissingKey(account));
+        }
+
+        Ok(Wallet {
+            account,
+            keystore,
+            nonce: 0,
+            balance: 0,
+        })
+    }
+
+    /// The account this wallet manages.
+    pub fn account(&self) -> User {
+        self.account
+    }
+
+    /// The nonce that will be used for the next extrinsic.
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// The balance as far as the wallet knows.
+    pub fn balance(&self) -> u64 {
+        self.balance
+    }
+
+    /// Refresh the nonce and balance from the node.
+    ///
+    /// The wallet adopts the on-chain values, which forgets about any extrinsics that are still
+    /// waiting in the pool. The one exception is the nonce, which never moves backwards, so
+    /// that pending extrinsics are not accidentally replaced.
+    pub fn sync(&mut self, node: &mut impl NodeApi) -> Result<(), WalletError> {
+        let info = node.account_info(self.account)?;
+        self.balance = info.balance;
+        self.nonce = self.nonce.max(info.nonce);
+        Ok(())
+    }
+
+    /// Sign the given call with the wallet's next nonce. This does not submit anything.
+    pub fn sign(&self, call: AccountingTransaction) -> Result<SignedExtrinsic, WalletError> {
+        let payload = signing_payload(self.account, self.nonce, &call);
+        let signature = self
+            .keystore
+            .sign(self.account, payload)
+            .ok_or(WalletError::MissingKey(self.account))?;
+
+        Ok(SignedExtrinsic {
+            signer: self.account,
+            nonce: self.nonce,
+            call,
+            signature,
+        })
+    }
+
+    /// Sign the given call and submit it to the node. On success, the wallet's nonce moves
+    /// forward and the submitted extrinsic is returned.
+    pub fn submit(
+        &mut self,
+        node: &mut impl NodeApi,
+        call: AccountingTransaction,
+    ) -> Result<SignedExtrinsic, WalletError> {
+        let extrinsic = self.sign(call)?;
+        node.submit_extrinsic(extrinsic.clone())?;
+        self.nonce += 1;
+        Ok(extrinsic)
+    }
+
+    /// Transfer the given amount to the receiver.
+    pub fn transfer(
+        &mut self,
+        node: &mut impl NodeApi,
+        receiver: User,
+        amount: u64,
+    ) -> Result<SignedExtrinsic, WalletError> {
+        if amount > self.balance {
+            return Err(WalletError::InsufficientBalance {
+                available: self.balance,
+                required: amount,
+            });
+        }
+
+        let extrinsic = self.submit(
+            node,
+            AccountingTransaction::Transfer {
+                sender: self.account,
+                receiver,
+                amount,
+            },
+        )?;
+        self.balance -= amount;
+        Ok(extrinsic)
+    }
+
+    /// Ask the node's faucet to fund this wallet's account, and return how much it was given.
+    /// The money only arrives once the faucet's transfer is included, so `sync` after that.
+    pub fn request_funds(&mut self, node: &mut impl NodeApi) -> Result<u64, WalletError> {
+        Ok(node.request_funds(self.account)?)
+    }
+}
+
+#[cfg(test)]
+use super::rpc::MockNode;
+#[cfg(test)]
+use super::runtime::AccountInfo;
+
+#[test]
+fn wallet_requires_key() {
+    assert_eq!(
+        Wallet::new(User::Alice, Keystore::new()).err(),
+        Some(WalletError::MissingKey(User::Alice))
+    );
+}
+
+#[test]
+fn wallet_sync_adopts_chain_state() {
+    let mut node = MockNode::default();
+    node.accounts.insert(
+        User::Alice,
+        AccountInfo {
+            balance: 100,
+            nonce: 4,
+        },
+    );
+
+    let mut wallet = Wallet::new(User::Alice, Keystore::dev()).unwrap();
+    wallet.sync(&mut node).unwrap();
+
+    assert_eq!(wallet.balance(), 100);
+    assert_eq!(wallet.nonce(), 4);
+}
+
+#[test]
+fn wallet_transfers_use_consecutive_nonces() {
+    let mut node = MockNode::default();
+    node.accounts.insert(
+        User::Alice,
+        AccountInfo {
+            balance: 100,
+            nonce: 0,
+        },
+    );
+
+    let mut wallet = Wallet::new(User::Alice, Keystore::dev()).unwrap();
+    wallet.sync(&mut node).unwrap();
+    wallet.transfer(&mut node, User::Bob, 10).unwrap();
+    wallet.transfer(&mut node, User::Bob, 20).unwrap();
+
+    let nonces: Vec<u64> = node.submitted.iter().map(|x| x.nonce).collect();
+    assert_eq!(nonces, vec![0, 1]);
+    assert_eq!(wallet.balance(), 70);
+
+    // Syncing before the transfers are included must not reuse their nonces.
+    wallet.sync(&mut node).unwrap();
+    assert_eq!(wallet.nonce(), 2);
+}
+
+#[test]
+fn wallet_signs_valid_extrinsics() {
+    let wallet = Wallet::new(User::Bob, Keystore::dev()).unwrap();
+    let call = AccountingTransaction::Burn {
+        burner: User::Bob,
+        amount: 5,
+    };
+    let extrinsic = wallet.sign(call.clone()).unwrap();
+
+    assert!(extrinsic
+        .signature
+        .verify(User::Bob, signing_payload(User::Bob, 0, &call)));
+}
+
+#[test]
+fn wallet_refuses_overspending() {
+    let mut node = MockNode::default();
+    let mut wallet = Wallet::new(User::Alice, Keystore::dev()).unwrap();
+
+    assert_eq!(
+        wallet.transfer(&mut node, User::Bob, 1),
+        Err(WalletError::InsufficientBalance {
+            available: 0,
+            required: 1
+        })
+    );
+    assert!(node.submitted.is_empty());
+}
@@ -0,0 +1,409 @@
+//! Blocks can't be arbitrarily large. Every node has to execute every block, and a block that
+//! takes longer to execute than the time between blocks would leave the whole network behind.
+//! So every extrinsic is given a _weight_, an estimate of the resources it uses up, and the
+//! extrinsics of a block may weigh no more than `MAX_BLOCK_WEIGHT` together.
+//!
+//! Both sides of a block enforce the limit:
+//! * The author stops filling a block as soon as the next extrinsic does not fit anymore, and
+//!   leaves it in the pool for a later block. See `BlockBuilder`.
+//! * Importers reject blocks that weigh too much, since an author might ignore the limit on
+//!   purpose. See `check_block_weight`.
+//!
+//! A client does both once it is given the `WeightLimit` executive.
+//!
+//! Block space is scarce, so it is not free either. The `MeteredRuntime` charges every extrinsic
+//! a fee in proportion to its weight, and the fee market of the `fee_pool` module prices
+//! extrinsics by fee per weight with `fee_info`. Substrate calls this weight, Ethereum calls it
+//! gas.
+
+use super::executive::Executive;
+use super::fee_pool::FeeInfo;
+use super::runtime::{origin, signing_payload, Runtime, RuntimeState, SignedExtrinsic};
+use super::ImportError;
+use crate::c1_state_machine::{AccountingTransaction, StateMachine, User};
+use crate::c3_consensus::Consensus;
+
+/// An estimate of the resources an extrinsic uses up.
+pub type Weight = u64;
+
+/// How much the extrinsics of a single block may weigh together.
+pub const MAX_BLOCK_WEIGHT: Weight = 1_000;
+
+/// The weight of every signed extrinsic on top of the weight of its call, for checking the
+/// signature and the nonce.
+pub const BASE_EXTRINSIC_WEIGHT: Weight = 50;
+
+/// The fee for every unit of weight.
+pub const FEE_PER_WEIGHT: u64 = 1;
+
+/// Anything that can be weighed.
+pub trait Weighed {
+    fn weight(&self) -> Weight;
+}
+
+impl Weighed for AccountingTransaction {
+    /// A transfer touches two accounts, mints and burns only one.
+    fn weight(&self) -> Weight {
+        match self {
+            AccountingTransaction::Mint { .. } | AccountingTransaction::Burn { .. } => 100,
+            AccountingTransaction::Transfer { .. } => 150,
+        }
+    }
+}
+
+impl Weighed for SignedExtrinsic {
+    fn weight(&self) -> Weight {
+        BASE_EXTRINSIC_WEIGHT + self.call.weight()
+    }
+}
+
+/// The fee for an extrinsic of the given weight.
+pub fn fee(weight: Weight) -> u64 {
+    weight.saturating_mul(FEE_PER_WEIGHT)
+}
+
+/// Price a signed extrinsic for the fee market of the `fee_pool` module.
+pub fn fee_info(t: &SignedExtrinsic) -> FeeInfo<User> {
+    let weight = t.weight();
+    FeeInfo {
+        sender: t.signer,
+        nonce: t.nonce,
+        fee: fee(weight),
+        weight,
+    }
+}
+
+/// Whether the runtime accepts the extrinsic on top of the given state: whether it carries the
+/// signer's current nonce, and a valid signature of the origin of its call. Only extrinsics that
+/// pass this check may be charged a fee, or a replayed extrinsic would be charged again.
+pub fn is_authorized(state: &RuntimeState, t: &SignedExtrinsic) -> bool {
+    t.nonce == state.account(t.signer).nonce
+        && origin(&t.call) == t.signer
+        && t.signature
+            .verify(t.signer, signing_payload(t.signer, t.nonce, &t.call))
+}
+
+/// The runtime, with fees. The signer pays the fee for their extrinsic before it is executed,
+/// and the fee is burned. Extrinsics whose signer can't pay the fee are invalid, and so are
+/// those that the runtime rejects. A call that fails, like a transfer of more than the sender
+/// has, still used up its weight, so its fee is not refunded.
+pub struct MeteredRuntime;
+
+impl StateMachine for MeteredRuntime {
+    type State = RuntimeState;
+    type Transition = SignedExtrinsic;
+
+    fn next_state(starting_state: &RuntimeState, t: &SignedExtrinsic) -> RuntimeState {
+        if !is_authorized(starting_state, t) {
+            return starting_state.clone();
+        }
+        let mut payer = starting_state.account(t.signer);
+        let Some(balance) = payer.balance.checked_sub(fee(t.weight())) else {
+            return starting_state.clone();
+        };
+        payer.balance = balance;
+        let mut state = starting_state.clone();
+        state.set_account(t.signer, payer);
+        Runtime::next_state(&state, t)
+    }
+
+    fn human_name() -> String {
+        "Metered Signed Accounted Currency".into()
+    }
+}
+
+/// The total weight of the given extrinsics.
+pub fn block_weight<T: Weighed>(extrinsics: &[T]) -> Weight {
+    extrinsics
+        .iter()
+        .fold(0, |total, t| total.saturating_add(t.weight()))
+}
+
+/// A block whose extrinsics weigh more than the limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overweight {
+    pub weight: Weight,
+    pub limit: Weight,
+}
+
+impl std::fmt::Display for Overweight {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "block weighs {}, but the limit is {}",
+            self.weight, self.limit
+        )
+    }
+}
+
+/// Check that the given extrinsics weigh no more than the limit together. Returns their weight.
+pub fn check_block_weight<T: Weighed>(
+    extrinsics: &[T],
+    limit: Weight,
+) -> Result<Weight, Overweight> {
+    let weight = block_weight(extrinsics);
+    if weight > limit {
+        return Err(Overweight { weight, limit });
+    }
+    Ok(weight)
+}
+
+/// Collects the extrinsics for a new block, up to a weight limit.
+#[derive(Clone, Debug)]
+pub struct BlockBuilder<T> {
+    limit: Weight,
+    weight: Weight,
+    extrinsics: Vec<T>,
+}
+
+impl<T: Weighed> BlockBuilder<T> {
+    /// Start an empty block that may weigh up to the given limit.
+    pub fn new(limit: Weight) -> Self {
+        BlockBuilder {
+            limit,
+            weight: 0,
+            extrinsics: Vec::new(),
+        }
+    }
+
+    /// Add the extrinsic to the block if it still fits, and hand it back otherwise.
+    pub fn try_push(&mut self, t: T) -> Result<(), T> {
+        match self.weight.checked_add(t.weight()) {
+            Some(weight) if weight <= self.limit => {
+                self.weight = weight;
+                self.extrinsics.push(t);
+                Ok(())
+            }
+            _ => Err(t),
+        }
+    }
+
+    /// The weight of the extrinsics so far.
+    pub fn weight(&self) -> Weight {
+        self.weight
+    }
+
+    pub fn into_extrinsics(self) -> Vec<T> {
+        self.extrinsics
+    }
+}
+
+/// An executive that holds blocks to a weight limit, and executes them as usual.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeightLimit(pub Weight);
+
+impl<C, SM> Executive<C, SM> for WeightLimit
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::Transition: Weighed,
+{
+    fn check_body(&self, body: &[SM::Transition]) -> Result<(), ImportError> {
+        check_block_weight(body, self.0).map_err(|overweight| {
+            tracing::debug!(%overweight, "rejected an overweight block");
+            ImportError::Overweight
+        })?;
+        Ok(())
+    }
+
+    fn fits(&self, body: &[SM::Transition], next: &SM::Transition) -> bool {
+        block_weight(body).saturating_add(next.weight()) <= self.0
+    }
+}
+
+#[cfg(test)]
+use super::keystore::Keystore;
+#[cfg(test)]
+use super::{Block, FullClient, ImportBlock, LongestChain, SimplePool};
+
+#[cfg(test)]
+fn signed(signer: User, nonce: u64, call: AccountingTransaction) -> SignedExtrinsic {
+    let signature = Keystore::dev()
+        .sign(signer, signing_payload(signer, nonce, &call))
+        .unwrap();
+    SignedExtrinsic {
+        signer,
+        nonce,
+        call,
+        signature,
+    }
+}
+
+/// Alice's transfer of 10 to Bob with the given nonce. It weighs 200.
+#[cfg(test)]
+fn transfer(nonce: u64) -> SignedExtrinsic {
+    let call = AccountingTransaction::Transfer {
+        sender: User::Alice,
+        receiver: User::Bob,
+        amount: 10,
+    };
+    signed(User::Alice, nonce, call)
+}
+
+#[test]
+fn weight_of_extrinsics() {
+    assert_eq!(transfer(0).weight(), 200);
+    let mint = AccountingTransaction::Mint {
+        minter: User::Bob,
+        amount: 5,
+    };
+    assert_eq!(signed(User::Bob, 0, mint).weight(), 150);
+
+    let info = fee_info(&transfer(3));
+    assert_eq!((info.sender, info.nonce), (User::Alice, 3));
+    assert_eq!((info.fee, info.weight), (200, 200));
+}
+
+#[test]
+fn weight_limit_is_inclusive() {
+    let five: Vec<_> = (0..5).map(transfer).collect();
+    assert_eq!(check_block_weight(&five, 1_000), Ok(1_000));
+    assert_eq!(
+        check_block_weight(&five, 999),
+        Err(Overweight {
+            weight: 1_000,
+            limit: 999
+        })
+    );
+    assert_eq!(check_block_weight::<SignedExtrinsic>(&[], 0), Ok(0));
+}
+
+#[test]
+fn weight_builder_stops_at_limit() {
+    let mut builder = BlockBuilder::new(MAX_BLOCK_WEIGHT);
+    for nonce in 0..5 {
+        assert!(builder.try_push(transfer(nonce)).is_ok());
+    }
+    assert_eq!(builder.weight(), MAX_BLOCK_WEIGHT);
+    assert_eq!(builder.try_push(transfer(5)), Err(transfer(5)));
+    assert_eq!(builder.into_extrinsics().len(), 5);
+
+    // Something lighter may still fit, up to the last unit of weight.
+    let mut builder = BlockBuilder::new(349);
+    assert!(builder.try_push(transfer(0)).is_ok());
+    let burn = AccountingTransaction::Burn {
+        burner: User::Alice,
+        amount: 1,
+    };
+    assert!(builder
+        .try_push(signed(User::Alice, 1, burn.clone()))
+        .is_err());
+    let mut builder = BlockBuilder::new(350);
+    assert!(builder.try_push(transfer(0)).is_ok());
+    assert!(builder.try_push(signed(User::Alice, 1, burn)).is_ok());
+}
+
+#[test]
+fn weight_metered_runtime_charges_fees() {
+    let state = RuntimeState::genesis(&[(User::Alice, 1_000)]);
+    let paid = MeteredRuntime::next_state(&state, &transfer(0));
+    assert_eq!(paid.account(User::Alice).balance, 1_000 - 200 - 10);
+    assert_eq!(paid.account(User::Bob).balance, 10);
+
+    // Alice can pay the fee, but not the transfer. The fee is gone all the same.
+    let poor = RuntimeState::genesis(&[(User::Alice, 205)]);
+    let failed = MeteredRuntime::next_state(&poor, &transfer(0));
+    assert_eq!(failed.account(User::Alice).balance, 5);
+    assert_eq!(failed.account(User::Alice).nonce, 1);
+
+    // Without enough for the fee, or with the wrong nonce, nothing happens at all.
+    let broke = RuntimeState::genesis(&[(User::Alice, 199)]);
+    assert_eq!(MeteredRuntime::next_state(&broke, &transfer(0)), broke);
+    assert_eq!(MeteredRuntime::next_state(&state, &transfer(1)), state);
+}
+
+#[test]
+fn weight_metered_runtime_rejects_replays_before_charging() {
+    let state = RuntimeState::genesis(&[(User::Alice, 1_000)]);
+    let paid = MeteredRuntime::next_state(&state, &transfer(0));
+    assert_eq!(MeteredRuntime::next_state(&paid, &transfer(0)), paid);
+
+    // A forged signature is not charged either.
+    let mut forged = transfer(0);
+    forged.call = AccountingTransaction::Transfer {
+        sender: User::Alice,
+        receiver: User::Bob,
+        amount: 999,
+    };
+    assert_eq!(MeteredRuntime::next_state(&state, &forged), state);
+}
+
+#[test]
+fn weight_metered_runtime_survives_largest_nonce() {
+    let state = RuntimeState::genesis(&[(User::Alice, 1_000)]);
+    assert_eq!(
+        MeteredRuntime::next_state(&state, &transfer(u64::MAX)),
+        state
+    );
+}
+
+#[cfg(test)]
+type TestClient = FullClient<(), Runtime, LongestChain, SimplePool<Runtime>>;
+
+#[cfg(test)]
+fn weight_limited_client() -> TestClient {
+    TestClient::new(RuntimeState::genesis(&[(User::Alice, 10_000)]))
+        .with_executive(WeightLimit(MAX_BLOCK_WEIGHT))
+}
+
+#[test]
+fn weight_client_authors_up_to_the_limit() {
+    let mut client = weight_limited_client();
+    for nonce in 0..6 {
+        client.submit_transaction(transfer(nonce));
+    }
+
+    // Five transfers weigh exactly as much as a block may, so the sixth waits for the next one.
+    let first = client.author_and_import_automatic_block().unwrap();
+    assert_eq!(client.get_block(first).unwrap().body().len(), 5);
+    assert_eq!(client.pool_size(), 1);
+    let second = client.author_and_import_automatic_block().unwrap();
+    assert_eq!(client.get_block(second).unwrap().body(), &[transfer(5)]);
+    let state = client.get_state(second).unwrap();
+    assert_eq!(state.account(User::Bob).balance, 60);
+}
+
+#[test]
+fn weight_client_keeps_nonce_order_when_a_block_is_full() {
+    let mut client = TestClient::new(RuntimeState::genesis(&[(User::Alice, 10_000)]))
+        .with_executive(WeightLimit(950));
+    for nonce in 0..5 {
+        client.submit_transaction(transfer(nonce));
+    }
+    // Only 150 are left after the first four transfers, so the fifth does not fit. The mint
+    // after it would, but it has to wait for the transfer before it.
+    let mint = signed(
+        User::Alice,
+        5,
+        AccountingTransaction::Mint {
+            minter: User::Alice,
+            amount: 10,
+        },
+    );
+    client.submit_transaction(mint.clone());
+
+    let first = client.author_and_import_automatic_block().unwrap();
+    assert_eq!(client.get_block(first).unwrap().body().len(), 4);
+    let second = client.author_and_import_automatic_block().unwrap();
+    assert_eq!(
+        client.get_block(second).unwrap().body(),
+        &[transfer(4), mint]
+    );
+}
+
+#[test]
+fn weight_client_rejects_overweight_blocks() {
+    let mut client = weight_limited_client();
+    let genesis_state = RuntimeState::genesis(&[(User::Alice, 10_000)]);
+    let genesis = Block::<(), Runtime>::genesis(&genesis_state);
+    let block = |transfers: u64| {
+        let body = (0..transfers).map(transfer).collect();
+        genesis.child(&(), &genesis_state, body).unwrap()
+    };
+
+    assert_eq!(
+        client.try_import_block(block(6)),
+        Err(ImportError::Overweight)
+    );
+    assert_eq!(client.try_import_block(block(5)), Ok(()));
+}
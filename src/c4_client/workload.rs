@@ -0,0 +1,255 @@
+//! Stress tests of the pool, authoring and import need a lot of transactions, and random ones
+//! from `TestRng::extrinsic` are not much use for that. Their nonces are whatever the test asks
+//! for, and their amounts have nothing to do with any balance, so the runtime rejects most of
+//! them and the pipeline under test hardly does any work.
+//!
+//! A `Workload` generates the kind of transaction stream a busy chain would see instead. It
+//! keeps track of what every account will hold once its earlier extrinsics are executed, so
+//! that every extrinsic it generates carries the signer's next nonce and never spends more than
+//! the signer has. On top of that, a configurable share of the extrinsics conflict with an
+//! earlier one: they reuse the nonce of the signer's last extrinsic with a different call, as a
+//! double spend or a replacement would. At most one extrinsic of each conflicting set can ever
+//! be executed.
+//!
+//! ```ignore
+//! let mut workload = Workload::new(seed, &genesis).with_conflict_rate(0.1);
+//! for extrinsic in workload.by_ref().take(10_000) {
+//!     client.submit_transaction(extrinsic);
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+
+use super::keystore::Keystore;
+use super::runtime::{signing_payload, AccountInfo, RuntimeState, SignedExtrinsic, ACCOUNTS};
+use crate::c1_state_machine::{AccountingTransaction, User};
+use crate::test_rng::TestRng;
+
+/// A deterministic stream of valid, and occasionally conflicting, extrinsics.
+pub struct Workload {
+    rng: TestRng,
+    keystore: Keystore,
+    /// What every account holds once every extrinsic generated so far, except for the
+    /// conflicting ones, is executed in order.
+    accounts: BTreeMap<User, AccountInfo>,
+    /// The last extrinsic of every signer that is not a conflict itself.
+    last: BTreeMap<User, SignedExtrinsic>,
+    conflict_rate: f64,
+    max_amount: u64,
+    conflicts: u64,
+}
+
+impl Workload {
+    /// A workload on top of the given state, with no conflicts and amounts of up to 1000. The
+    /// same seed and state always give the same stream.
+    pub fn new(seed: u64, state: &RuntimeState) -> Self {
+        Workload {
+            rng: TestRng::seeded(seed),
+            keystore: Keystore::dev(),
+            accounts: ACCOUNTS
+                .iter()
+                .map(|&who| (who, state.account(who)))
+                .collect(),
+            last: BTreeMap::new(),
+            conflict_rate: 0.0,
+            max_amount: 1_000,
+            conflicts: 0,
+        }
+    }
+
+    /// Make the given share of the extrinsics conflict with an earlier one.
+    pub fn with_conflict_rate(mut self, conflict_rate: f64) -> Self {
+        self.conflict_rate = conflict_rate;
+        self
+    }
+
+    /// Mint, burn and transfer no more than the given amount at once. It must not be zero.
+    pub fn with_max_amount(mut self, max_amount: u64) -> Self {
+        assert!(max_amount > 0, "the maximum amount must not be zero");
+        self.max_amount = max_amount;
+        self
+    }
+
+    /// The number of conflicting extrinsics generated so far.
+    pub fn conflicts(&self) -> u64 {
+        self.conflicts
+    }
+
+    /// The state after executing every extrinsic generated so far, except for the conflicting
+    /// ones, in order.
+    pub fn expected_state(&self) -> RuntimeState {
+        RuntimeState::from(self.accounts.clone())
+    }
+
+    /// Generate the next extrinsic.
+    pub fn next_extrinsic(&mut self) -> SignedExtrinsic {
+        let signer = self.rng.user();
+        if self.rng.chance(self.conflict_rate) {
+            if let Some(last) = self.last.get(&signer) {
+                let (nonce, call) = (last.nonce, last.call.clone());
+                // The state is left alone, since the conflict can only replace the original.
+                self.conflicts += 1;
+                let mut conflicting = self.call(signer);
+                while conflicting == call {
+                    conflicting = self.call(signer);
+                }
+                return self.sign(signer, nonce, conflicting);
+            }
+        }
+
+        let call = self.call(signer);
+        let nonce = self.accounts[&signer].nonce;
+        self.execute(signer, &call);
+        let extrinsic = self.sign(signer, nonce, call);
+        self.last.insert(signer, extrinsic.clone());
+        extrinsic
+    }
+
+    /// A random call on behalf of the given signer that it can afford.
+    fn call(&mut self, signer: User) -> AccountingTransaction {
+        let balance = self.accounts[&signer].balance;
+        // Accounts without a balance can only mint, and a transfer to oneself hardly tests
+        // anything.
+        let kind = if balance == 0 { 0 } else { self.rng.below(4) };
+        let max_amount = self.max_amount;
+        let affordable = |rng: &mut TestRng| rng.below(max_amount.min(balance)) + 1;
+        match kind {
+            0 => AccountingTransaction::Mint {
+                minter: signer,
+                amount: self.rng.below(max_amount) + 1,
+            },
+            1 => AccountingTransaction::Burn {
+                burner: signer,
+                amount: affordable(&mut self.rng),
+            },
+            _ => {
+                let others: Vec<User> = ACCOUNTS.into_iter().filter(|&who| who != signer).collect();
+                AccountingTransaction::Transfer {
+                    sender: signer,
+                    receiver: *self.rng.pick(&others).expect("there are other accounts"),
+                    amount: affordable(&mut self.rng),
+                }
+            }
+        }
+    }
+
+    /// Update the expected state of the accounts as the runtime would execute the given call.
+    fn execute(&mut self, signer: User, call: &AccountingTransaction) {
+        match *call {
+            AccountingTransaction::Mint { minter, amount } => {
+                self.account(minter).balance += amount
+            }
+            AccountingTransaction::Burn { burner, amount } => {
+                self.account(burner).balance -= amount
+            }
+            AccountingTransaction::Transfer {
+                sender,
+                receiver,
+                amount,
+            } => {
+                self.account(sender).balance -= amount;
+                self.account(receiver).balance += amount;
+            }
+        }
+        self.account(signer).nonce += 1;
+    }
+
+    fn account(&mut self, who: User) -> &mut AccountInfo {
+        self.accounts.get_mut(&who).expect("every account is known")
+    }
+
+    fn sign(&self, signer: User, nonce: u64, call: AccountingTransaction) -> SignedExtrinsic {
+        let signature = self
+            .keystore
+            .sign(signer, signing_payload(signer, nonce, &call))
+            .expect("the dev keystore holds every user's key");
+        SignedExtrinsic {
+            signer,
+            nonce,
+            call,
+            signature,
+        }
+    }
+}
+
+impl Iterator for Workload {
+    type Item = SignedExtrinsic;
+
+    fn next(&mut self) -> Option<SignedExtrinsic> {
+        Some(self.next_extrinsic())
+    }
+}
+
+#[cfg(test)]
+fn genesis() -> RuntimeState {
+    RuntimeState::genesis(&[(User::Alice, 100), (User::Bob, 10)])
+}
+
+#[test]
+fn workload_respects_nonces_and_balances() {
+    let mut workload = Workload::new(1, &genesis()).with_max_amount(50);
+    let mut accounts: BTreeMap<User, AccountInfo> = ACCOUNTS
+        .iter()
+        .map(|&who| (who, genesis().account(who)))
+        .collect();
+
+    for extrinsic in workload.by_ref().take(200) {
+        let payload = signing_payload(extrinsic.signer, extrinsic.nonce, &extrinsic.call);
+        assert!(extrinsic.signature.verify(extrinsic.signer, payload));
+        let signer = accounts.get_mut(&extrinsic.signer).unwrap();
+        assert_eq!(extrinsic.nonce, signer.nonce);
+        signer.nonce += 1;
+
+        // Execute the call with checked arithmetic, which fails on any overdraft.
+        let mut apply = |who: User, change: i64| {
+            let info = accounts.get_mut(&who).unwrap();
+            info.balance = info.balance.checked_add_signed(change).unwrap();
+        };
+        match extrinsic.call {
+            AccountingTransaction::Mint { minter, amount } => apply(minter, amount as i64),
+            AccountingTransaction::Burn { burner, amount } => apply(burner, -(amount as i64)),
+            AccountingTransaction::Transfer {
+                sender,
+                receiver,
+                amount,
+            } => {
+                assert!(amount <= 50 && sender != receiver);
+                apply(sender, -(amount as i64));
+                apply(receiver, amount as i64);
+            }
+        }
+    }
+    assert_eq!(workload.conflicts(), 0);
+    assert_eq!(workload.expected_state(), RuntimeState::from(accounts));
+}
+
+#[test]
+fn workload_conflicts_reuse_the_last_nonce() {
+    let run = |seed| {
+        let mut workload = Workload::new(seed, &genesis()).with_conflict_rate(0.25);
+        let extrinsics: Vec<_> = workload.by_ref().take(200).collect();
+        (extrinsics, workload.conflicts())
+    };
+    let (extrinsics, conflicts) = run(2);
+    assert_eq!((extrinsics.clone(), conflicts), run(2));
+    assert!((25..75).contains(&conflicts), "{conflicts} conflicts");
+
+    // Every conflict shares its signer and nonce with the signer's last extrinsic, but not its
+    // call, and the nonces of the rest count up without gaps.
+    let mut next_nonce: BTreeMap<User, u64> = BTreeMap::new();
+    let mut seen = BTreeMap::new();
+    let mut found = 0;
+    for extrinsic in &extrinsics {
+        let key = (extrinsic.signer, extrinsic.nonce);
+        let next = next_nonce.entry(extrinsic.signer).or_default();
+        if extrinsic.nonce == *next {
+            *next += 1;
+            seen.insert(key, extrinsic.call.clone());
+        } else {
+            found += 1;
+            assert_eq!(extrinsic.nonce + 1, *next);
+            assert_ne!(seen[&key], extrinsic.call);
+        }
+    }
+    assert_eq!(found, conflicts);
+}
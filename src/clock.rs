@@ -0,0 +1,154 @@
+//! Time is an input like any other. Code that reads the system clock directly behaves a little
+//! differently on every run, and code that waits for real time to pass makes its tests slow. So
+//! the logic that depends on time never reads it itself. The network's gossip, reputation and
+//! discovery bookkeeping and the RPC rate limiter are handed the current instant by their
+//! caller, and the telemetry, the simulator and the fault injectors ask a `Clock` for it.
+//!
+//! A running node uses the `SystemClock`. Tests and the simulator use a `ManualClock`, which
+//! stands still until it is told to move, so that every run sees exactly the same times.
+//!
+//! Waiting is another matter. The loops that sleep between rounds, like the authoring task and
+//! local peer discovery, use tokio's timer, because a `Clock` can tell the time but can not wake
+//! anybody up. They hold no logic of their own worth testing on a virtual clock: the simulator
+//! drives the same nodes without them.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Something that knows what time it is.
+pub trait Clock {
+    /// The current instant, for measuring how long something took or whether it timed out.
+    fn now(&self) -> Instant;
+
+    /// The current wall clock time, as the time since the unix epoch. Use this for timestamps
+    /// that other machines read.
+    fn unix_time(&self) -> Duration;
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn unix_time(&self) -> Duration {
+        (**self).unix_time()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn unix_time(&self) -> Duration {
+        (**self).unix_time()
+    }
+}
+
+/// The operating system's clock.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn unix_time(&self) -> Duration {
+        // A system clock set to before 1970 is too broken to be worth an error.
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+/// A clock that only moves when it is told to.
+///
+/// Clones share the same time, so a test can keep one and advance it while the code under test
+/// owns another.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    /// The instant at which the clock was made. `Instant`s can not be made up out of thin air,
+    /// so every instant this clock hands out is measured from this one.
+    start: Instant,
+    /// The wall clock time at which the clock was made.
+    unix_start: Duration,
+    /// How far the clock has been advanced since, in nanoseconds.
+    elapsed: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// A clock that starts at the given wall clock time.
+    pub fn new(unix_start: Duration) -> Self {
+        ManualClock {
+            start: Instant::now(),
+            unix_start,
+            elapsed: Arc::default(),
+        }
+    }
+
+    /// How far the clock has been advanced since it was made.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed.load(Ordering::SeqCst))
+    }
+
+    /// Move the clock forwards by the given amount.
+    pub fn advance(&self, by: Duration) {
+        self.elapsed
+            .fetch_add(by.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    /// Move the clock forwards until the given amount of time has passed since it was made.
+    /// Clocks never run backwards, so this does nothing if that time has already passed.
+    pub fn advance_to(&self, elapsed: Duration) {
+        self.elapsed
+            .fetch_max(elapsed.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Default for ManualClock {
+    /// A clock that starts at the unix epoch.
+    fn default() -> Self {
+        Self::new(Duration::ZERO)
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn unix_time(&self) -> Duration {
+        self.unix_start + self.elapsed()
+    }
+}
+
+#[test]
+fn clock_manual_stands_still_until_advanced() {
+    let clock = ManualClock::new(Duration::from_secs(1_000));
+    let before = clock.now();
+    assert_eq!(clock.now(), before);
+    assert_eq!(clock.unix_time(), Duration::from_secs(1_000));
+
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(clock.now() - before, Duration::from_secs(5));
+    assert_eq!(clock.unix_time(), Duration::from_secs(1_005));
+}
+
+#[test]
+fn clock_manual_clones_share_time() {
+    let clock = ManualClock::default();
+    let shared = clock.clone();
+    shared.advance(Duration::from_millis(250));
+    assert_eq!(clock.elapsed(), Duration::from_millis(250));
+    assert_eq!(clock.now(), shared.now());
+}
+
+#[test]
+fn clock_manual_never_runs_backwards() {
+    let clock = ManualClock::default();
+    clock.advance_to(Duration::from_secs(10));
+    clock.advance_to(Duration::from_secs(3));
+    assert_eq!(clock.elapsed(), Duration::from_secs(10));
+}
@@ -0,0 +1,22 @@
+//! Hash maps and sets that work with and without the standard library.
+//!
+//! `alloc` has the B-tree collections, but not the hashed ones, since those need a source of
+//! randomness to seed their hasher. With the `std` feature, these are simply the standard
+//! library's. Without it, they are hashbrown's, hashed with blake2b. Nothing about that hasher is
+//! random, which is just as well there: runtimes have no randomness to offer, and every node has
+//! to execute a block the same way.
+//!
+//! Create them with `default()` rather than `new()`, which only exists for the standard hasher.
+
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap, HashSet};
+
+#[cfg(not(feature = "std"))]
+pub type HashMap<K, V> = hashbrown::HashMap<K, V, BuildBlake2Hasher>;
+
+#[cfg(not(feature = "std"))]
+pub type HashSet<T> = hashbrown::HashSet<T, BuildBlake2Hasher>;
+
+/// Builds the hasher of the maps and sets without the standard library.
+#[cfg(not(feature = "std"))]
+pub type BuildBlake2Hasher = core::hash::BuildHasherDefault<crate::hashing::Blake2Hasher>;
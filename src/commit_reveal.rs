@@ -0,0 +1,54 @@
+//! Some decisions must be made blindly. In a sealed-bid auction, nobody should see the other
+//! bids before placing their own. On a blockchain everything is public, so a bid that is simply
+//! submitted can be read, and outbid, by everyone who comes after.
+//!
+//! A commit-reveal scheme splits the decision in two phases. First, everybody publishes a
+//! commitment: the hash of their value together with a random salt. The hash hides the value, and
+//! the salt makes sure it can not be found by hashing every likely value. Once every commitment
+//! is in, everybody reveals their value and salt, and anyone can check them against the
+//! commitment. Nobody can change their mind after seeing the others, because no other value
+//! hashes to the same commitment.
+//!
+//! The same trick is the basis of simple on-chain randomness: participants commit to random
+//! numbers, reveal them, and combine them. See the lottery and randomness exercises.
+
+#[cfg(feature = "std")]
+use crate::crypto::random_bytes;
+use crate::hashing::{hash, H256};
+
+/// The secret that keeps a commitment from being guessed. It must be drawn at random, and kept
+/// until the reveal.
+pub type Salt = [u8; 32];
+
+/// Draw a fresh salt.
+#[cfg(feature = "std")]
+pub fn random_salt() -> Salt {
+    random_bytes()
+}
+
+/// Commit to the given value.
+pub fn commit<T: core::hash::Hash>(value: &T, salt: &Salt) -> H256 {
+    hash(&("commit", value, salt))
+}
+
+/// Check that the given value and salt are the ones the commitment was made with.
+pub fn verify_reveal<T: core::hash::Hash>(commitment: H256, value: &T, salt: &Salt) -> bool {
+    commit(value, salt) == commitment
+}
+
+#[test]
+fn commit_reveal_accepts_only_the_committed_value() {
+    let salt = random_salt();
+    let commitment = commit(&42u64, &salt);
+
+    assert!(verify_reveal(commitment, &42u64, &salt));
+    assert!(!verify_reveal(commitment, &43u64, &salt));
+    assert!(!verify_reveal(commitment, &42u64, &random_salt()));
+}
+
+#[test]
+fn commit_reveal_hides_equal_values() {
+    // The same value committed with different salts gives unrelated commitments.
+    assert_ne!(commit(&1u64, &[1; 32]), commit(&1u64, &[2; 32]));
+    assert_eq!(commit(&1u64, &[1; 32]), commit(&1u64, &[1; 32]));
+}
@@ -0,0 +1,173 @@
+//! Public keys are 32 bytes of noise, and a single mistyped character sends money to a key
+//! that nobody holds. Addresses fix this. An address is the hash of a public key, encoded in
+//! base58 with a version byte and a checksum, the way Bitcoin's base58check does it. Base58
+//! leaves out characters that look alike, such as `0` and `O`.
+//!
+//! Parsing an address checks all of it: the characters, the length, the checksum and the
+//! version byte. A typo is reported as such, rather than turning into a different account.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::sig::PublicKey;
+use crate::hashing::blake2_256;
+
+/// The version byte of addresses on our chain. Different chains use different version bytes,
+/// so that an address for one can not be mistaken for an address for another.
+pub const ADDRESS_VERSION: u8 = 42;
+
+/// The number of bytes of the public key hash that an address keeps. Like Bitcoin, we keep 20,
+/// which is plenty to make collisions impractical.
+pub const KEY_HASH_LEN: usize = 20;
+
+/// The address of an account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address {
+    /// The hash of the account's public key.
+    key_hash: [u8; KEY_HASH_LEN],
+}
+
+/// Why a string is not a valid address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AddressError {
+    /// The string holds a character that is not in the base58 alphabet.
+    InvalidCharacter(char),
+    /// The string decodes to the wrong number of bytes.
+    WrongLength(usize),
+    /// The checksum does not match. Most likely the address was mistyped.
+    InvalidChecksum,
+    /// The address is for a different chain.
+    WrongVersion(u8),
+}
+
+impl std::fmt::Display for AddressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressError::InvalidCharacter(c) => write!(f, "{c:?} is not a base58 character"),
+            AddressError::WrongLength(len) => {
+                write!(f, "address holds {len} bytes, expected {KEY_HASH_LEN}")
+            }
+            AddressError::InvalidChecksum => write!(f, "address checksum does not match"),
+            AddressError::WrongVersion(version) => write!(
+                f,
+                "address has version {version}, expected {ADDRESS_VERSION}"
+            ),
+        }
+    }
+}
+
+impl Address {
+    /// The address of the account with the given public key.
+    pub fn from_public(public: &PublicKey) -> Self {
+        let mut key_hash = [0; KEY_HASH_LEN];
+        key_hash.copy_from_slice(&blake2_256(&public.0)[..KEY_HASH_LEN]);
+        Address { key_hash }
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let encoded = bs58::encode(self.key_hash)
+            .with_check_version(ADDRESS_VERSION)
+            .into_string();
+        f.write_str(&encoded)
+    }
+}
+
+impl std::str::FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = bs58::decode(s)
+            .with_check(None)
+            .into_vec()
+            .map_err(|e| match e {
+                bs58::decode::Error::InvalidCharacter { character, .. } => {
+                    AddressError::InvalidCharacter(character)
+                }
+                bs58::decode::Error::NonAsciiCharacter { index } => {
+                    AddressError::InvalidCharacter(s[index..].chars().next().unwrap_or('?'))
+                }
+                bs58::decode::Error::NoChecksum => AddressError::WrongLength(0),
+                _ => AddressError::InvalidChecksum,
+            })?;
+        // The version byte comes first, and the checksum has already been stripped.
+        let (&version, key_hash) = bytes.split_first().ok_or(AddressError::WrongLength(0))?;
+        let key_hash = key_hash
+            .try_into()
+            .map_err(|_| AddressError::WrongLength(key_hash.len()))?;
+        if version != ADDRESS_VERSION {
+            return Err(AddressError::WrongVersion(version));
+        }
+        Ok(Address { key_hash })
+    }
+}
+
+// Addresses travel as the same strings that users see.
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[test]
+fn address_differs_per_key() {
+    let a = Address::from_public(&PublicKey([1; 32]));
+    let b = Address::from_public(&PublicKey([2; 32]));
+    assert_ne!(a.to_string(), b.to_string());
+    assert_eq!(
+        a.to_string(),
+        Address::from_public(&PublicKey([1; 32])).to_string()
+    );
+    assert!(!a.to_string().contains(['0', 'O', 'I', 'l']));
+}
+
+#[test]
+fn address_round_trips() {
+    let address = Address::from_public(&PublicKey([1; 32]));
+    assert_eq!(address.to_string().parse(), Ok(address));
+
+    let json = serde_json::to_string(&address).unwrap();
+    assert_eq!(json, format!("\"{address}\""));
+    assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), address);
+}
+
+#[test]
+fn address_rejects_typos_and_other_chains() {
+    let address = Address::from_public(&PublicKey([1; 32])).to_string();
+
+    // Change one character into another valid base58 character.
+    let mut typo: Vec<char> = address.chars().collect();
+    typo[5] = if typo[5] == 'a' { 'b' } else { 'a' };
+    let typo: String = typo.into_iter().collect();
+    assert_eq!(typo.parse::<Address>(), Err(AddressError::InvalidChecksum));
+
+    assert_eq!(
+        address.replacen(&address[..1], "0", 1).parse::<Address>(),
+        Err(AddressError::InvalidCharacter('0'))
+    );
+
+    let other_chain = bs58::encode([7u8; KEY_HASH_LEN])
+        .with_check_version(0)
+        .into_string();
+    assert_eq!(
+        other_chain.parse::<Address>(),
+        Err(AddressError::WrongVersion(0))
+    );
+
+    let short = bs58::encode([7u8; 4])
+        .with_check_version(ADDRESS_VERSION)
+        .into_string();
+    assert_eq!(short.parse::<Address>(), Err(AddressError::WrongLength(4)));
+    assert_eq!(
+        "é".parse::<Address>(),
+        Err(AddressError::InvalidCharacter('é'))
+    );
+}
@@ -0,0 +1,212 @@
+//! Aggregatable signatures, in the style of BLS. A committee that seals a block with ordinary
+//! signatures has to attach one signature per member, and every node has to check all of them.
+//! With BLS signatures, the members' signatures over the same message add up to a single
+//! signature, which a verifier checks against the sum of the members' public keys. The seal is
+//! one signature and a list of who signed, however large the committee.
+//!
+//! BLS works in groups that come with a pairing: a map `e` that takes two group elements to a
+//! third group, such that `e(a * P, b * Q) = a * b * e(P, Q)`. A secret key is a number `x`, the
+//! public key is `x * G` for a fixed generator `G`, and the signature over a message is
+//! `x * H(m)`, where `H` hashes messages to group elements. Anyone can check a signature by
+//! comparing `e(signature, G)` with `e(H(m), public)`: both are `x * e(H(m), G)`. Since the
+//! pairing is linear, sums of signatures check out against sums of public keys.
+//!
+//! THIS IS A TOY. Real BLS uses elliptic curves where pairings are hard won mathematics. Here the
+//! group is simply the integers modulo a prime `Q`, and the pairing is multiplication. The
+//! algebra is the same, but anybody can divide the public key by `G` and read off the secret
+//! key. It exists to show how aggregation works, and must never protect anything of value.
+//!
+//! Real deployments must also defeat rogue key attacks, where a member picks their public key as
+//! a function of the others' so that the sum is a key they alone control. Committees usually
+//! require every member to prove possession of their secret key when they join. We leave that
+//! out and assume the committee's keys are known to be honest.
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::blake2_256;
+
+/// The order of the toy group, the Mersenne prime 2^61 - 1.
+pub const Q: u64 = (1 << 61) - 1;
+
+/// The generator that public keys are multiples of.
+const G: u64 = 7;
+
+fn add(a: u64, b: u64) -> u64 {
+    ((u128::from(a) + u128::from(b)) % u128::from(Q)) as u64
+}
+
+fn mul(a: u64, b: u64) -> u64 {
+    ((u128::from(a) * u128::from(b)) % u128::from(Q)) as u64
+}
+
+/// The toy pairing. See the module docs for why it is insecure.
+fn pairing(a: u64, b: u64) -> u64 {
+    mul(a, b)
+}
+
+/// Hash a message to a group element.
+fn hash_to_group(message: &[u8]) -> u64 {
+    let digest = blake2_256(&[&b"toy bls"[..], message].concat());
+    u64::from_le_bytes(digest[..8].try_into().expect("digest has 32 bytes")) % Q
+}
+
+/// A secret key: a number modulo `Q`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SecretKey(u64);
+
+/// A public key: the secret key times the generator.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct PublicKey(pub u64);
+
+/// A signature, or the sum of several signatures.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Signature(pub u64);
+
+impl SecretKey {
+    /// Derive a secret key from a seed. Zero is not a valid key, so it is skipped.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let x = u64::from_le_bytes(blake2_256(&seed)[..8].try_into().expect("32 bytes")) % Q;
+        SecretKey(x.max(1))
+    }
+
+    pub fn public(&self) -> PublicKey {
+        PublicKey(mul(self.0, G))
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        Signature(mul(self.0, hash_to_group(message)))
+    }
+}
+
+impl PublicKey {
+    /// Check this key's owner signed the given message. Also checks aggregate signatures over one
+    /// message against the aggregate of the signers' keys.
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        pairing(signature.0, G) == pairing(hash_to_group(message), self.0)
+    }
+}
+
+/// Add up signatures, so that they can be checked all at once.
+pub fn aggregate_signatures<'a>(signatures: impl IntoIterator<Item = &'a Signature>) -> Signature {
+    Signature(signatures.into_iter().fold(0, |sum, s| add(sum, s.0)))
+}
+
+/// Add up public keys. The sum verifies the sum of the keys' signatures over the same message.
+pub fn aggregate_public_keys<'a>(keys: impl IntoIterator<Item = &'a PublicKey>) -> PublicKey {
+    PublicKey(keys.into_iter().fold(0, |sum, k| add(sum, k.0)))
+}
+
+/// Check an aggregate of signatures over different messages, given the signers' keys along with
+/// the message each of them signed. This costs one pairing per signer, but still only needs the
+/// one signature.
+pub fn verify_aggregate(signed: &[(PublicKey, &[u8])], signature: &Signature) -> bool {
+    let expected = signed.iter().fold(0, |sum, (key, message)| {
+        add(sum, pairing(hash_to_group(message), key.0))
+    });
+    pairing(signature.0, G) == expected
+}
+
+/// The seal of a committee over a single message: which members signed, and the sum of their
+/// signatures. Real chains pack the list of signers into one bit per member.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CommitteeSeal {
+    /// Whether each member of the committee, in order, signed.
+    pub signers: Vec<bool>,
+    /// The sum of the signers' signatures.
+    pub signature: Signature,
+}
+
+impl CommitteeSeal {
+    /// Seal the message with the signatures of the given members of the committee. Each
+    /// signature comes with the signer's index in the committee.
+    pub fn new(committee_size: usize, signatures: &[(usize, Signature)]) -> Self {
+        let mut signers = vec![false; committee_size];
+        for &(index, _) in signatures {
+            signers[index] = true;
+        }
+        CommitteeSeal {
+            signers,
+            signature: aggregate_signatures(signatures.iter().map(|(_, s)| s)),
+        }
+    }
+
+    /// Check that at least `threshold` members of the committee sealed the message.
+    pub fn verify(&self, committee: &[PublicKey], threshold: usize, message: &[u8]) -> bool {
+        if self.signers.len() != committee.len() {
+            return false;
+        }
+        let keys: Vec<_> = committee
+            .iter()
+            .zip(&self.signers)
+            .filter(|(_, signed)| **signed)
+            .map(|(key, _)| key)
+            .collect();
+        keys.len() >= threshold && aggregate_public_keys(keys).verify(message, &self.signature)
+    }
+}
+
+#[cfg(test)]
+fn committee(size: u8) -> Vec<SecretKey> {
+    (0..size).map(|i| SecretKey::from_seed([i; 32])).collect()
+}
+
+#[test]
+fn aggregate_single_signatures_verify() {
+    let alice = SecretKey::from_seed([1; 32]);
+    let signature = alice.sign(b"hello");
+    assert!(alice.public().verify(b"hello", &signature));
+    assert!(!alice.public().verify(b"goodbye", &signature));
+    assert!(!SecretKey::from_seed([2; 32])
+        .public()
+        .verify(b"hello", &signature));
+}
+
+#[test]
+fn aggregate_committee_seal_needs_threshold_of_signers() {
+    let members = committee(4);
+    let publics: Vec<_> = members.iter().map(SecretKey::public).collect();
+    let signatures: Vec<_> = [0, 2, 3]
+        .into_iter()
+        .map(|i| (i, members[i].sign(b"block 7")))
+        .collect();
+    let seal = CommitteeSeal::new(4, &signatures);
+
+    assert!(seal.verify(&publics, 3, b"block 7"));
+    assert!(!seal.verify(&publics, 4, b"block 7"));
+    assert!(!seal.verify(&publics, 3, b"block 8"));
+
+    // Claiming a member signed who did not breaks the aggregate.
+    let mut forged = seal.clone();
+    forged.signers[1] = true;
+    assert!(!forged.verify(&publics, 3, b"block 7"));
+    assert!(!seal.verify(&publics[..3], 3, b"block 7"));
+}
+
+#[test]
+fn aggregate_over_different_messages_verifies() {
+    let members = committee(3);
+    let messages: [&[u8]; 3] = [b"one", b"two", b"three"];
+    let signatures: Vec<_> = members
+        .iter()
+        .zip(messages)
+        .map(|(k, m)| k.sign(m))
+        .collect();
+    let signature = aggregate_signatures(&signatures);
+
+    let signed: Vec<_> = members
+        .iter()
+        .map(SecretKey::public)
+        .zip(messages)
+        .collect();
+    assert!(verify_aggregate(&signed, &signature));
+
+    // Each key has to go with the message it signed.
+    let swapped = [
+        (signed[0].0, signed[1].1),
+        (signed[1].0, signed[0].1),
+        signed[2],
+    ];
+    assert!(!verify_aggregate(&swapped, &signature));
+}
@@ -0,0 +1,198 @@
+//! Raw key bytes are hard to write down and easy to get wrong. A mnemonic encodes the same
+//! secret as a short list of common words, following BIP39: the words come from a fixed list of
+//! 2048, and the last word includes a checksum, so that a mistyped phrase is noticed rather
+//! than silently turning into a different key.
+//!
+//! One phrase can back up many keys. A derivation path such as `//stash//0` names a child key,
+//! which is derived from the phrase's key step by step, one junction per `//`. Anybody who has
+//! the phrase and knows the path can derive the child key again, so only the phrase needs to be
+//! written down. A phrase followed by a path, like `<words>//stash`, is known as a secret URI,
+//! and it is what the node's key files hold.
+//!
+//! The derivation follows the hard key derivation that Substrate uses for ed25519 keys. Soft
+//! junctions, written with a single `/`, derive child public keys from parent public keys alone.
+//! That takes a scheme like sr25519, so they are rejected here.
+
+use bip39::Mnemonic;
+
+use super::{random_bytes, Secret};
+use super::sig::Keypair;
+use crate::hashing::blake2_256;
+
+/// Why a phrase was rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MnemonicError(pub String);
+
+impl std::fmt::Display for MnemonicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid mnemonic: {}", self.0)
+    }
+}
+
+/// Generate a fresh phrase of 12 or 24 words.
+pub fn generate(words: usize) -> Result<Secret<String>, MnemonicError> {
+    let entropy: Secret<[u8; 32]> = Secret::new(random_bytes());
+    let bytes = match words {
+        12 => 16,
+        24 => 32,
+        _ => return Err(MnemonicError(format!("{words} words, expected 12 or 24"))),
+    };
+    let mnemonic =
+        Mnemonic::from_entropy(&entropy[..bytes]).map_err(|e| MnemonicError(e.to_string()))?;
+    Ok(Secret::new(mnemonic.to_string()))
+}
+
+/// The 64 byte BIP39 seed for the given phrase, with an empty passphrase.
+pub fn to_seed(phrase: &str) -> Result<Secret<[u8; 64]>, MnemonicError> {
+    let mnemonic = Mnemonic::parse(phrase).map_err(|e| MnemonicError(e.to_string()))?;
+    Ok(Secret::new(mnemonic.to_seed("")))
+}
+
+/// The ed25519 key pair for the given phrase. Its secret seed is the first half of the BIP39
+/// seed.
+pub fn keypair(phrase: &str) -> Result<Keypair, MnemonicError> {
+    let seed = to_seed(phrase)?;
+    Ok(Keypair::from_seed(
+        seed[..32].try_into().expect("the seed is 64 bytes long"),
+    ))
+}
+
+/// Split a derivation path such as `//stash//0` into its junctions.
+fn junctions(path: &str) -> Result<Vec<&str>, MnemonicError> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(path) = path.strip_prefix("//") else {
+        return Err(MnemonicError(format!(
+            "derivation path {path:?} must start with //"
+        )));
+    };
+    path.split("//")
+        .map(|junction| match junction {
+            "" => Err(MnemonicError("empty junction in derivation path".into())),
+            soft if soft.contains('/') => Err(MnemonicError(format!(
+                "soft junction in {soft:?}, ed25519 keys only support hard junctions (//)"
+            ))),
+            hard => Ok(hard),
+        })
+        .collect()
+}
+
+/// The chain code of a junction. Numbers are encoded as little endian integers and anything
+/// else as a length prefixed string, zero padded to 32 bytes, or hashed if it is longer.
+fn chain_code(junction: &str) -> [u8; 32] {
+    let encoded = match junction.parse::<u64>() {
+        Ok(index) => index.to_le_bytes().to_vec(),
+        Err(_) => [compact_length(junction.len()), junction.as_bytes().to_vec()].concat(),
+    };
+    if encoded.len() > 32 {
+        return blake2_256(&encoded);
+    }
+    let mut code = [0; 32];
+    code[..encoded.len()].copy_from_slice(&encoded);
+    code
+}
+
+/// The SCALE compact encoding of a length, as Substrate prefixes strings with it.
+fn compact_length(len: usize) -> Vec<u8> {
+    match len {
+        0..=0x3f => vec![(len as u8) << 2],
+        0x40..=0x3fff => ((len as u16) << 2 | 0b01).to_le_bytes().to_vec(),
+        _ => ((len as u32) << 2 | 0b10).to_le_bytes().to_vec(),
+    }
+}
+
+/// Derive the hard child of the key with the given secret seed.
+fn derive_hard(seed: &[u8; 32], junction: &str) -> Secret<[u8; 32]> {
+    const DOMAIN: &str = "Ed25519HDKD";
+    let input = Secret::new(
+        [
+            compact_length(DOMAIN.len()),
+            DOMAIN.as_bytes().to_vec(),
+            seed.to_vec(),
+            chain_code(junction).to_vec(),
+        ]
+        .concat(),
+    );
+    Secret::new(blake2_256(&input))
+}
+
+/// The key pair at the given derivation path below the given phrase. The empty path gives the
+/// phrase's own key pair.
+pub fn derive(phrase: &str, path: &str) -> Result<Keypair, MnemonicError> {
+    let junctions = junctions(path)?;
+    let mut seed = keypair(phrase)?.seed();
+    for junction in junctions {
+        seed = derive_hard(&seed, junction);
+    }
+    Ok(Keypair::from_seed(*seed))
+}
+
+/// The key pair for the given secret URI: a phrase optionally followed by a derivation path,
+/// such as `<words>//stash`.
+pub fn from_uri(uri: &str) -> Result<Keypair, MnemonicError> {
+    let uri = uri.trim();
+    match uri.find('/') {
+        Some(start) => derive(uri[..start].trim_end(), &uri[start..]),
+        None => keypair(uri),
+    }
+}
+
+#[test]
+fn mnemonic_matches_bip39_test_vector() {
+    let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                  abandon about";
+    assert_eq!(
+        super::to_hex(&*to_seed(phrase).unwrap()),
+        "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc1\
+         9a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4"
+    );
+}
+
+#[test]
+fn mnemonic_generated_phrases_round_trip() {
+    for words in [12, 24] {
+        let phrase = generate(words).unwrap();
+        assert_eq!(phrase.split_whitespace().count(), words);
+        assert_eq!(
+            keypair(&phrase).unwrap().public(),
+            keypair(&phrase).unwrap().public()
+        );
+    }
+    assert!(generate(13).is_err());
+
+    // Swapping two words breaks the checksum. A 12 word phrase only has a 4 bit checksum, so
+    // this is checked on a phrase where it is known to.
+    let phrase = format!("{} about", ["abandon"; 11].join(" "));
+    assert!(keypair(&phrase).is_ok());
+    let swapped = format!("about {}", ["abandon"; 11].join(" "));
+    assert!(keypair(&swapped).is_err());
+}
+
+#[test]
+fn mnemonic_derives_distinct_children() {
+    let phrase = generate(12).unwrap();
+    let root = keypair(&phrase).unwrap().public();
+
+    assert_eq!(derive(&phrase, "").unwrap().public(), root);
+    let stash = derive(&phrase, "//stash").unwrap().public();
+    assert_ne!(stash, root);
+    assert_ne!(derive(&phrase, "//stash//0").unwrap().public(), stash);
+    assert_ne!(derive(&phrase, "//0").unwrap().public(), stash);
+    // Derivation is deterministic, and a secret URI is a phrase with a path.
+    assert_eq!(
+        from_uri(&format!("{}//stash", phrase.as_str())).unwrap().public(),
+        stash
+    );
+    assert_eq!(from_uri(&phrase).unwrap().public(), root);
+}
+
+#[test]
+fn mnemonic_rejects_bad_paths() {
+    let phrase = generate(12).unwrap();
+    for path in ["stash", "/soft", "//hard/soft", "//", "//a////b"] {
+        assert!(derive(&phrase, path).is_err(), "{path}");
+    }
+    assert_eq!(chain_code("0"), [0; 32]);
+    assert_eq!(chain_code("Alice")[..6], [20, b'A', b'l', b'i', b'c', b'e']);
+}
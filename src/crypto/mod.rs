@@ -0,0 +1,87 @@
+//! Real cryptography for the places where toy signatures are no longer enough.
+//!
+//! The tutorial chapters deliberately avoid cryptography so that the interesting parts stay in
+//! focus. A node that strangers use needs the real thing, though, starting with keys that can
+//! be backed up and shared. This module wraps a few well known crates behind small, crate-local
+//! types, so that the rest of the code never depends on any one library directly.
+
+use alloc::{format, string::String, vec::Vec};
+
+#[cfg(feature = "std")]
+pub mod address;
+#[cfg(feature = "std")]
+pub mod aggregate;
+#[cfg(feature = "std")]
+pub mod hmac;
+#[cfg(feature = "std")]
+pub mod mnemonic;
+pub mod pedersen;
+#[cfg(feature = "std")]
+pub mod sig;
+#[cfg(feature = "sr25519")]
+pub mod sr25519;
+#[cfg(feature = "std")]
+pub mod vrf;
+
+/// A secret, such as a seed or a phrase, that is overwritten with zeros when it is dropped. A
+/// secret that is simply freed stays in memory until something happens to reuse the space, and
+/// a crash dump or a swapped out page can give it away long after we are done with it. It derefs
+/// to the value it wraps.
+#[cfg(feature = "std")]
+pub type Secret<T> = zeroize::Zeroizing<T>;
+
+/// Fill an array with bytes from the operating system's secure source of randomness.
+#[cfg(feature = "std")]
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes).expect("the operating system provides randomness");
+    bytes
+}
+
+/// Compare two byte strings in constant time. `==` stops at the first byte that differs, so an
+/// attacker who can time the comparison learns how much of their guess was right, and can guess
+/// a secret one byte at a time. This takes as long for every input of the same length.
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let difference = a
+        .iter()
+        .zip(b)
+        .fold(0, |difference, (x, y)| difference | (x ^ y));
+    // Keep the compiler from noticing that it could stop early.
+    core::hint::black_box(difference) == 0
+}
+
+/// Encode bytes as lowercase hex.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Decode lowercase or uppercase hex, optionally prefixed with `0x`.
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[test]
+fn crypto_ct_eq_compares_bytes() {
+    assert!(ct_eq(b"secret", b"secret"));
+    assert!(!ct_eq(b"secret", b"secreT"));
+    assert!(!ct_eq(b"secret", b"secrets"));
+    assert!(ct_eq(b"", b""));
+}
+
+#[test]
+fn crypto_hex_round_trip() {
+    assert_eq!(to_hex(&[0, 0xab, 0x10]), "00ab10");
+    assert_eq!(from_hex("0x00AB10"), Some(vec![0, 0xab, 0x10]));
+    assert_eq!(from_hex("abc"), None);
+    assert_eq!(from_hex("zz"), None);
+}
@@ -0,0 +1,173 @@
+//! Pedersen commitments. Like the hash commitments in `crate::commit_reveal`, a Pedersen
+//! commitment hides a value until its owner opens it, and can only be opened to the value it was
+//! made with. Unlike hash commitments, they can be added up: the sum of the commitments to two
+//! values is a commitment to the sum of the values. Confidential transactions use this to prove
+//! that a transfer creates no money, without telling anybody how much was transferred.
+//!
+//! A commitment to the value `v` with the blinding factor `r` is `g^v * h^r`, where `g` and `h`
+//! are two generators of a group in which nobody knows how to write `h` as a power of `g`. The
+//! random blinding factor hides the value. Multiplying two commitments adds up both the values
+//! and the blinding factors, which is why we write the group operation as `+`.
+//!
+//! To show that a transfer is balanced, the sender reveals the excess: the blinding factors of
+//! the inputs minus those of the outputs. If the values balance, the inputs minus the outputs
+//! are exactly `h^excess`, and anyone can check that. If they don't, the sender would have to
+//! know how to write a power of `g` as a power of `h`.
+//!
+//! THIS IS A TOY. The group is the squares modulo a 62 bit prime, where anyone with a laptop can
+//! take discrete logarithms and forge openings. Real systems use elliptic curves. Real
+//! confidential transactions also need range proofs: values wrap around modulo the group order,
+//! so without them a "negative" output could print money.
+
+use core::ops::{Add, Sub};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::blake2_256;
+
+/// The modulus, a safe prime: `(P - 1) / 2` is prime too.
+const P: u64 = (1 << 62) - 10565;
+
+/// The order of the group of squares modulo `P`. Values and blinding factors live modulo this.
+pub const ORDER: u64 = (P - 1) / 2;
+
+/// The generator that carries the value.
+const G: u64 = 4;
+
+fn mul(a: u64, b: u64) -> u64 {
+    ((u128::from(a) * u128::from(b)) % u128::from(P)) as u64
+}
+
+fn pow(mut base: u64, mut exp: u64) -> u64 {
+    let mut result = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+/// The generator that carries the blinding factor. It is derived from a hash, so that nobody
+/// picked it knowing its discrete logarithm.
+fn h() -> u64 {
+    let digest = blake2_256(b"pedersen h");
+    let x = u64::from_le_bytes(digest[..8].try_into().expect("digest has 32 bytes")) % P;
+    mul(x, x)
+}
+
+/// A blinding factor. It must be drawn at random, and kept secret until the commitment is opened.
+pub type Blinding = u64;
+
+/// Draw a fresh blinding factor.
+#[cfg(feature = "std")]
+pub fn random_blinding() -> Blinding {
+    u64::from_le_bytes(super::random_bytes()) % ORDER
+}
+
+/// The excess of a transfer: the blinding factors of its inputs minus those of its outputs,
+/// modulo the group order.
+pub fn excess(inputs: &[Blinding], outputs: &[Blinding]) -> Blinding {
+    let sum = |blindings: &[Blinding]| {
+        blindings.iter().fold(0, |sum, b| {
+            ((u128::from(sum) + u128::from(*b)) % u128::from(ORDER)) as u64
+        })
+    };
+    ((u128::from(sum(inputs)) + u128::from(ORDER - sum(outputs))) % u128::from(ORDER)) as u64
+}
+
+/// A commitment to a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Commitment(u64);
+
+impl Commitment {
+    /// The commitment to no value with no blinding factor. Adding it changes nothing.
+    pub const ZERO: Commitment = Commitment(1);
+
+    /// A commitment to zero with the given blinding factor. This is what a balanced transfer
+    /// adds up to, with its excess as the blinding factor.
+    pub fn excess(blinding: Blinding) -> Self {
+        commit(0, blinding)
+    }
+}
+
+/// Commit to the given value.
+pub fn commit(value: u64, blinding: Blinding) -> Commitment {
+    Commitment(mul(pow(G, value % ORDER), pow(h(), blinding % ORDER)))
+}
+
+/// Check that the commitment was made with the given value and blinding factor.
+pub fn verify_opening(commitment: Commitment, value: u64, blinding: Blinding) -> bool {
+    commit(value, blinding) == commitment
+}
+
+/// Add up the values, and the blinding factors, of two commitments.
+impl Add for Commitment {
+    type Output = Commitment;
+
+    fn add(self, other: Commitment) -> Commitment {
+        Commitment(mul(self.0, other.0))
+    }
+}
+
+/// Subtract the values, and the blinding factors, of two commitments.
+impl Sub for Commitment {
+    type Output = Commitment;
+
+    fn sub(self, other: Commitment) -> Commitment {
+        // Every element of the group has order dividing `ORDER`, so this is the inverse.
+        self + Commitment(pow(other.0, ORDER - 1))
+    }
+}
+
+impl core::iter::Sum for Commitment {
+    fn sum<I: Iterator<Item = Commitment>>(iter: I) -> Commitment {
+        iter.fold(Commitment::ZERO, Add::add)
+    }
+}
+
+#[test]
+fn pedersen_opens_only_to_the_committed_value() {
+    let blinding = random_blinding();
+    let commitment = commit(42, blinding);
+    assert!(verify_opening(commitment, 42, blinding));
+    assert!(!verify_opening(commitment, 43, blinding));
+    assert!(!verify_opening(commitment, 42, blinding + 1));
+    // The blinding factor hides equal values.
+    assert_ne!(commit(42, 1), commit(42, 2));
+}
+
+#[test]
+fn pedersen_commitments_add_up() {
+    assert_eq!(commit(30, 5) + commit(12, 7), commit(42, 12));
+    assert_eq!(commit(42, 12) - commit(12, 7), commit(30, 5));
+    assert_eq!(commit(42, 12) - commit(42, 12), Commitment::ZERO);
+    assert_eq!(
+        [commit(1, 1), commit(2, 2), commit(3, 3)]
+            .into_iter()
+            .sum::<Commitment>(),
+        commit(6, 6)
+    );
+
+    // Balanced inputs and outputs differ by a commitment to zero.
+    let inputs = commit(50, 11) + commit(50, 21);
+    let outputs = commit(70, 3) + commit(30, 4);
+    assert_eq!(
+        inputs - outputs,
+        Commitment::excess(excess(&[11, 21], &[3, 4]))
+    );
+    let unbalanced = commit(71, 3) + commit(30, 4);
+    assert_ne!(
+        inputs - unbalanced,
+        Commitment::excess(excess(&[11, 21], &[3, 4]))
+    );
+
+    // The excess may well be "negative".
+    let outputs = commit(70, 30) + commit(30, 40);
+    assert_eq!(
+        inputs - outputs,
+        Commitment::excess(excess(&[11, 21], &[30, 40]))
+    );
+}
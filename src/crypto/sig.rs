@@ -0,0 +1,215 @@
+//! Ed25519 keys. Ed25519 is a signature scheme over a twisted Edwards curve. It is fast, its
+//! keys and signatures are small, and it is hard to misuse, which makes it the default choice
+//! for many chains.
+//!
+//! Code that wants to stay open to other schemes is written against the `SignatureScheme`
+//! trait rather than these concrete types.
+
+use ed25519_dalek::{Signer, Verifier};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{from_hex, random_bytes, to_hex, Secret};
+
+/// An ed25519 public key.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PublicKey(pub [u8; 32]);
+
+impl std::fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{}", to_hex(&self.0))
+    }
+}
+
+impl std::fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PublicKey({self})")
+    }
+}
+
+impl std::str::FromStr for PublicKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_hex(s)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(PublicKey)
+            .ok_or_else(|| format!("invalid public key {s:?}, expected 32 bytes of hex"))
+    }
+}
+
+impl PublicKey {
+    /// Check that the signature was made over the given message by the key pair that this
+    /// public key belongs to.
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        #[cfg(test)]
+        if let Some(valid) = crate::faults::forced_signature_outcome() {
+            return valid;
+        }
+        let Ok(key) = ed25519_dalek::VerifyingKey::from_bytes(&self.0) else {
+            return false;
+        };
+        key.verify(message, &ed25519_dalek::Signature::from_bytes(&signature.0))
+            .is_ok()
+    }
+}
+
+/// An ed25519 signature.
+// Equality is still equality of the bytes, so it agrees with the derived `Hash`.
+#[allow(clippy::derived_hash_with_manual_eq)]
+#[derive(Clone, Copy, Eq, Hash)]
+pub struct Signature(pub [u8; 64]);
+
+// Signatures are compared in constant time, see `ct_eq`.
+impl PartialEq for Signature {
+    fn eq(&self, other: &Self) -> bool {
+        super::ct_eq(&self.0, &other.0)
+    }
+}
+
+impl std::fmt::Debug for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Signature(0x{})", to_hex(&self.0))
+    }
+}
+
+// Serde only knows arrays of up to 32 elements, so signatures travel as hex.
+impl Serialize for Signature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_hex(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        from_hex(&hex)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(Signature)
+            .ok_or_else(|| serde::de::Error::custom("expected 64 bytes of hex"))
+    }
+}
+
+/// An ed25519 key pair.
+#[derive(Clone)]
+pub struct Keypair {
+    signing: ed25519_dalek::SigningKey,
+}
+
+// The secret must never end up in the logs.
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keypair")
+            .field("public", &self.public())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Keypair {
+    /// Generate a fresh key pair.
+    pub fn generate() -> Self {
+        Self::from_seed(random_bytes())
+    }
+
+    /// The key pair with the given 32 byte secret seed. The same seed always gives the same
+    /// key pair.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Keypair {
+            signing: ed25519_dalek::SigningKey::from_bytes(&seed),
+        }
+    }
+
+    /// The secret seed of this key pair. The signing key itself is wiped when it is dropped, and
+    /// so is the copy returned here.
+    pub fn seed(&self) -> Secret<[u8; 32]> {
+        Secret::new(self.signing.to_bytes())
+    }
+
+    /// The public half of this key pair.
+    pub fn public(&self) -> PublicKey {
+        PublicKey(self.signing.verifying_key().to_bytes())
+    }
+
+    /// Sign the given message.
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        Signature(self.signing.sign(message).to_bytes())
+    }
+}
+
+/// A signature scheme: a way to derive key pairs from a secret seed, sign messages with them,
+/// and verify the signatures with the public half of the key pair alone.
+pub trait SignatureScheme {
+    type Keypair: Clone;
+    type PublicKey: Copy + std::fmt::Debug + Eq + Ord + std::hash::Hash;
+    type Signature: Copy + std::fmt::Debug + Eq + std::hash::Hash;
+
+    /// The key pair with the given secret seed. The same seed always gives the same key pair.
+    fn from_seed(seed: [u8; 32]) -> Self::Keypair;
+
+    /// The public half of the given key pair.
+    fn public(pair: &Self::Keypair) -> Self::PublicKey;
+
+    /// Sign the given message with the given key pair.
+    fn sign(pair: &Self::Keypair, message: &[u8]) -> Self::Signature;
+
+    /// Check that the signature was made over the given message by the owner of the given
+    /// public key.
+    fn verify(public: &Self::PublicKey, message: &[u8], signature: &Self::Signature) -> bool;
+}
+
+/// The ed25519 signature scheme.
+pub struct Ed25519;
+
+impl SignatureScheme for Ed25519 {
+    type Keypair = Keypair;
+    type PublicKey = PublicKey;
+    type Signature = Signature;
+
+    fn from_seed(seed: [u8; 32]) -> Keypair {
+        Keypair::from_seed(seed)
+    }
+
+    fn public(pair: &Keypair) -> PublicKey {
+        pair.public()
+    }
+
+    fn sign(pair: &Keypair, message: &[u8]) -> Signature {
+        pair.sign(message)
+    }
+
+    fn verify(public: &PublicKey, message: &[u8], signature: &Signature) -> bool {
+        public.verify(message, signature)
+    }
+}
+
+#[test]
+fn sig_keypair_from_seed_is_deterministic() {
+    let seed = [7; 32];
+    assert_eq!(
+        Keypair::from_seed(seed).public(),
+        Keypair::from_seed(seed).public()
+    );
+    assert_eq!(*Keypair::from_seed(seed).seed(), seed);
+    assert_ne!(Keypair::generate().public(), Keypair::generate().public());
+
+    let public = Keypair::from_seed(seed).public();
+    assert_eq!(public.to_string().parse::<PublicKey>(), Ok(public));
+}
+
+#[test]
+fn sig_verifies_only_the_signed_message() {
+    let alice = Keypair::from_seed([1; 32]);
+    let bob = Keypair::from_seed([2; 32]);
+    let signature = alice.sign(b"hello");
+
+    assert!(alice.public().verify(b"hello", &signature));
+    assert!(!alice.public().verify(b"hell0", &signature));
+    assert!(!bob.public().verify(b"hello", &signature));
+    assert!(Ed25519::verify(
+        &Ed25519::public(&alice),
+        b"hello",
+        &Ed25519::sign(&alice, b"hello")
+    ));
+
+    let json = serde_json::to_string(&signature).unwrap();
+    assert_eq!(serde_json::from_str::<Signature>(&json).unwrap(), signature);
+}
@@ -0,0 +1,179 @@
+//! Sr25519 keys. Sr25519 is a Schnorr signature scheme over the Ristretto group, built on the
+//! same curve as ed25519. Substrate chains use it for account keys, because Schnorr signatures
+//! allow for tricks that ed25519 does not, such as deriving child public keys without knowing the
+//! secret, and VRFs that BABE style consensus engines draw their slots with.
+//!
+//! Signatures are made in the signing context that Substrate uses, so keys and signatures are
+//! compatible with Substrate-style chains.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::sig::SignatureScheme;
+use super::{from_hex, random_bytes, to_hex, Secret};
+
+/// The signing context that every signature is made in.
+const SIGNING_CONTEXT: &[u8] = b"substrate";
+
+/// An sr25519 public key.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PublicKey(pub [u8; 32]);
+
+impl std::fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PublicKey(0x{})", to_hex(&self.0))
+    }
+}
+
+impl PublicKey {
+    /// Check that the signature was made over the given message by the key pair that this
+    /// public key belongs to.
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        let (Ok(key), Ok(signature)) = (
+            schnorrkel::PublicKey::from_bytes(&self.0),
+            schnorrkel::Signature::from_bytes(&signature.0),
+        ) else {
+            return false;
+        };
+        key.verify_simple(SIGNING_CONTEXT, message, &signature)
+            .is_ok()
+    }
+}
+
+/// An sr25519 signature.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Signature(pub [u8; 64]);
+
+impl std::fmt::Debug for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Signature(0x{})", to_hex(&self.0))
+    }
+}
+
+// Serde only knows arrays of up to 32 elements, so signatures travel as hex.
+impl Serialize for Signature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_hex(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        from_hex(&hex)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(Signature)
+            .ok_or_else(|| serde::de::Error::custom("expected 64 bytes of hex"))
+    }
+}
+
+/// An sr25519 key pair.
+#[derive(Clone)]
+pub struct Keypair {
+    seed: Secret<[u8; 32]>,
+    pair: schnorrkel::Keypair,
+}
+
+// The secret must never end up in the logs.
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keypair")
+            .field("public", &self.public())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Keypair {
+    /// Generate a fresh key pair.
+    pub fn generate() -> Self {
+        Self::from_seed(random_bytes())
+    }
+
+    /// The key pair with the given 32 byte secret seed. The seed is expanded the way Substrate
+    /// expands it, so the same seed gives the same key pair as it would there.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let pair = schnorrkel::MiniSecretKey::from_bytes(&seed)
+            .expect("every 32 bytes are a valid mini secret key")
+            .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519);
+        Keypair {
+            seed: Secret::new(seed),
+            pair,
+        }
+    }
+
+    /// The secret seed of this key pair.
+    pub fn seed(&self) -> Secret<[u8; 32]> {
+        self.seed.clone()
+    }
+
+    /// The public half of this key pair.
+    pub fn public(&self) -> PublicKey {
+        PublicKey(self.pair.public.to_bytes())
+    }
+
+    /// Sign the given message. Unlike ed25519 signatures, sr25519 signatures are randomized, so
+    /// signing the same message twice gives two different signatures.
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        Signature(self.pair.sign_simple(SIGNING_CONTEXT, message).to_bytes())
+    }
+}
+
+/// The sr25519 signature scheme.
+pub struct Sr25519;
+
+impl SignatureScheme for Sr25519 {
+    type Keypair = Keypair;
+    type PublicKey = PublicKey;
+    type Signature = Signature;
+
+    fn from_seed(seed: [u8; 32]) -> Keypair {
+        Keypair::from_seed(seed)
+    }
+
+    fn public(pair: &Keypair) -> PublicKey {
+        pair.public()
+    }
+
+    fn sign(pair: &Keypair, message: &[u8]) -> Signature {
+        pair.sign(message)
+    }
+
+    fn verify(public: &PublicKey, message: &[u8], signature: &Signature) -> bool {
+        public.verify(message, signature)
+    }
+}
+
+#[cfg(test)]
+fn sign_and_verify<S: SignatureScheme>() -> (bool, bool) {
+    let pair = S::from_seed([3; 32]);
+    let signature = S::sign(&pair, b"hello");
+    (
+        S::verify(&S::public(&pair), b"hello", &signature),
+        S::verify(&S::public(&pair), b"bye", &signature),
+    )
+}
+
+#[test]
+fn sr25519_signs_like_any_other_scheme() {
+    assert_eq!(sign_and_verify::<Sr25519>(), (true, false));
+    assert_eq!(sign_and_verify::<super::sig::Ed25519>(), (true, false));
+
+    // The same seed gives different keys in the two schemes.
+    let seed = [3; 32];
+    assert_ne!(
+        Keypair::from_seed(seed).public().0,
+        super::sig::Keypair::from_seed(seed).public().0
+    );
+    assert_eq!(*Keypair::from_seed(seed).seed(), seed);
+}
+
+#[test]
+fn sr25519_rejects_other_signers() {
+    let alice = Keypair::from_seed([1; 32]);
+    let bob = Keypair::from_seed([2; 32]);
+    let signature = alice.sign(b"hello");
+
+    assert!(!bob.public().verify(b"hello", &signature));
+    assert!(!alice.public().verify(b"hello", &Signature([0; 64])));
+    assert_ne!(alice.sign(b"hello"), signature);
+    assert!(alice.public().verify(b"hello", &alice.sign(b"hello")));
+}
@@ -0,0 +1,66 @@
+//! A verifiable random function, or VRF, gives the holder of a secret key a random looking
+//! output for any input, along with a proof that anyone holding the public key can check. Nobody
+//! else can compute the output before the key holder reveals it, yet the key holder can not pick
+//! the output either. Consensus engines such as BABE use this to elect slot leaders in secret:
+//! every authority evaluates the VRF on the slot number, and whoever gets an output below a
+//! threshold may author.
+//!
+//! This is a toy VRF, built from ed25519 signatures. The proof is a signature over the input,
+//! and the output is the hash of the proof. Ed25519 signatures are deterministic, so an honest
+//! key holder always gets the same output for the same input. But ed25519 verifiers can not
+//! tell whether the signer followed the rules for picking the signature's nonce, so a dishonest
+//! key holder could try several signatures and keep the output they like best. Real VRFs, like
+//! the one in schnorrkel that Substrate uses, prove that the output is unique.
+
+use serde::{Deserialize, Serialize};
+
+use super::sig::{Keypair, PublicKey, Signature};
+use crate::hashing::{blake2_256, H256};
+
+/// The proof that goes along with a VRF output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VrfProof(pub Signature);
+
+/// The message that is actually signed, so that VRF proofs can never be confused with
+/// signatures over the same bytes made for some other purpose.
+fn message(input: &[u8]) -> Vec<u8> {
+    [&b"vrf"[..], input].concat()
+}
+
+/// The output that belongs to the given proof.
+fn output_of(proof: &VrfProof) -> H256 {
+    H256(blake2_256(&[&b"vrf output"[..], &proof.0 .0].concat()))
+}
+
+/// Evaluate the VRF on the given input.
+pub fn prove(secret: &Keypair, input: &[u8]) -> (H256, VrfProof) {
+    let proof = VrfProof(secret.sign(&message(input)));
+    (output_of(&proof), proof)
+}
+
+/// Check that the output is the VRF output of the given public key's owner for the given input.
+pub fn verify(public: &PublicKey, input: &[u8], output: H256, proof: &VrfProof) -> bool {
+    public.verify(&message(input), &proof.0) && output_of(proof) == output
+}
+
+#[test]
+fn vrf_outputs_verify_and_are_deterministic() {
+    let alice = Keypair::from_seed([1; 32]);
+    let (out, proof) = prove(&alice, b"slot 1");
+
+    assert!(verify(&alice.public(), b"slot 1", out, &proof));
+    assert_eq!(prove(&alice, b"slot 1"), (out, proof));
+    assert_ne!(prove(&alice, b"slot 2").0, out);
+}
+
+#[test]
+fn vrf_rejects_wrong_key_input_or_output() {
+    let alice = Keypair::from_seed([1; 32]);
+    let bob = Keypair::from_seed([2; 32]);
+    let (out, proof) = prove(&alice, b"slot 1");
+
+    assert!(!verify(&bob.public(), b"slot 1", out, &proof));
+    assert!(!verify(&alice.public(), b"slot 2", out, &proof));
+    assert!(!verify(&alice.public(), b"slot 1", H256::zero(), &proof));
+    assert_ne!(prove(&bob, b"slot 1").0, out);
+}
@@ -0,0 +1,263 @@
+//! Proof of work asks for a hash below some target. The lower the target, the more hashes an
+//! author has to try before one is low enough. This module does the arithmetic around that
+//! single comparison, so that mining, validating and adjusting the difficulty all agree on it.
+//!
+//! There are three ways to say how hard a block is to mine:
+//! * The target: a 256 bit number that the hash, read as a big endian number, must not exceed.
+//! * The difficulty: the number of hashes it takes to find a block on average.
+//! * The compact encoding: the target squeezed into 32 bits, the way Bitcoin headers store it
+//!   in their `nBits` field. It keeps the three most significant bytes of the target, along
+//!   with the target's length in bytes.
+//!
+//! The tutorial chapters use a simpler scheme: they compare only the last eight bytes of a hash
+//! with a 64 bit threshold. The `threshold` functions translate between the two.
+
+use crate::hashing::H256;
+
+/// A 256 bit unsigned integer, most significant limb first, so that the derived ordering is
+/// the numeric one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct U256([u64; 4]);
+
+impl U256 {
+    const ZERO: U256 = U256([0; 4]);
+    const ONE: U256 = U256([0, 0, 0, 1]);
+    const MAX: U256 = U256([u64::MAX; 4]);
+
+    fn from_u128(n: u128) -> Self {
+        U256([0, 0, (n >> 64) as u64, n as u64])
+    }
+
+    /// The value, or None if it does not fit in 128 bits.
+    fn to_u128(self) -> Option<u128> {
+        let [a, b, c, d] = self.0;
+        (a == 0 && b == 0).then_some((u128::from(c) << 64) | u128::from(d))
+    }
+
+    fn from_h256(hash: H256) -> Self {
+        let limb = |i: usize| u64::from_be_bytes(hash.0[8 * i..8 * i + 8].try_into().unwrap());
+        U256([limb(0), limb(1), limb(2), limb(3)])
+    }
+
+    fn to_h256(self) -> H256 {
+        let mut bytes = [0; 32];
+        for (chunk, limb) in bytes.chunks_mut(8).zip(self.0) {
+            chunk.copy_from_slice(&limb.to_be_bytes());
+        }
+        H256(bytes)
+    }
+
+    fn bit(self, i: usize) -> bool {
+        self.0[3 - i / 64] >> (i % 64) & 1 == 1
+    }
+
+    /// Shift left by one bit, and set the lowest bit to the given one.
+    fn shl1(self, low: bool) -> Self {
+        let [a, b, c, d] = self.0;
+        U256([
+            a << 1 | b >> 63,
+            b << 1 | c >> 63,
+            c << 1 | d >> 63,
+            d << 1 | u64::from(low),
+        ])
+    }
+
+    fn wrapping_add(self, other: Self) -> Self {
+        let mut limbs = [0; 4];
+        let mut carry = false;
+        for i in (0..4).rev() {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(u64::from(carry));
+            limbs[i] = sum;
+            carry = c1 || c2;
+        }
+        U256(limbs)
+    }
+
+    fn wrapping_sub(self, other: Self) -> Self {
+        let mut limbs = [0; 4];
+        let mut borrow = false;
+        for i in (0..4).rev() {
+            let (diff, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (diff, b2) = diff.overflowing_sub(u64::from(borrow));
+            limbs[i] = diff;
+            borrow = b1 || b2;
+        }
+        U256(limbs)
+    }
+
+    /// The quotient and remainder. The divisor must not be zero.
+    fn div_rem(self, divisor: Self) -> (Self, Self) {
+        let mut quotient = U256::ZERO;
+        let mut remainder = U256::ZERO;
+        for i in (0..256).rev() {
+            // The remainder is always below the divisor, so before the shift it is below
+            // 2^255 whenever the divisor fits in 256 bits, and the shift can not overflow.
+            let overflow = remainder.bit(255);
+            remainder = remainder.shl1(self.bit(i));
+            let fits = overflow || remainder >= divisor;
+            if fits {
+                remainder = remainder.wrapping_sub(divisor);
+            }
+            quotient = quotient.shl1(fits);
+        }
+        (quotient, remainder)
+    }
+}
+
+/// 2^256 divided by the given number, rounded down. The divisor must be above 1, so that the
+/// quotient fits.
+fn two_256_div(divisor: U256) -> U256 {
+    // 2^256 = MAX + 1 does not fit, so divide MAX instead and correct for the missing one.
+    let (quotient, remainder) = U256::MAX.div_rem(divisor);
+    if remainder.wrapping_add(U256::ONE) == divisor {
+        quotient.wrapping_add(U256::ONE)
+    } else {
+        quotient
+    }
+}
+
+/// The target for the given difficulty: a hash is good enough if it is at most this. A
+/// difficulty of 0 is treated like 1, so everything is good enough.
+pub fn difficulty_to_target(difficulty: u128) -> H256 {
+    if difficulty <= 1 {
+        return U256::MAX.to_h256();
+    }
+    // Leave 2^256 / difficulty hashes that are good enough, counting zero.
+    two_256_div(U256::from_u128(difficulty))
+        .wrapping_sub(U256::ONE)
+        .to_h256()
+}
+
+/// The number of hashes it takes on average to find one that meets the given target. That is
+/// 2^256 divided by the number of hashes that do, saturating at `u128::MAX`.
+pub fn target_to_difficulty(target: H256) -> u128 {
+    let target = U256::from_h256(target);
+    if target == U256::MAX {
+        return 1;
+    }
+    if target == U256::ZERO {
+        return u128::MAX;
+    }
+    two_256_div(target.wrapping_add(U256::ONE))
+        .to_u128()
+        .unwrap_or(u128::MAX)
+}
+
+/// Whether the given hash meets the target for the given difficulty.
+pub fn meets_target(hash: H256, difficulty: u128) -> bool {
+    hash <= difficulty_to_target(difficulty)
+}
+
+/// Decode a compact target: the highest byte is the length of the target in bytes, and the
+/// lower three bytes are its most significant bytes. Returns None for targets that Bitcoin
+/// considers invalid: negative ones, with the sign bit `0x0080_0000` set, and ones that do not
+/// fit in 256 bits.
+pub fn compact_to_target(compact: u32) -> Option<H256> {
+    let size = (compact >> 24) as usize;
+    let mantissa = compact & 0x007f_ffff;
+    if compact & 0x0080_0000 != 0 && mantissa != 0 {
+        return None;
+    }
+    // The mantissa's bytes are the target's bytes at positions size - 1 down to size - 3,
+    // counting from the least significant byte. Bytes below position 0 are shifted out.
+    let mut bytes = [0; 32];
+    for (k, &byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+        let position = size as isize - 1 - k as isize;
+        match position {
+            ..=-1 => {}
+            0..=31 => bytes[31 - position as usize] = byte,
+            _ if byte != 0 => return None,
+            _ => {}
+        }
+    }
+    Some(H256(bytes))
+}
+
+/// Encode a target in compact form. This only keeps the three most significant bytes, so the
+/// target may come out slightly lower when it is decoded again.
+pub fn target_to_compact(target: H256) -> u32 {
+    let bytes = target.0;
+    let mut size = 32 - bytes.iter().take_while(|&&b| b == 0).count();
+    let byte = |from_end: isize| -> u32 {
+        if (0..32).contains(&from_end) {
+            u32::from(bytes[31 - from_end as usize])
+        } else {
+            0
+        }
+    };
+    let top = size as isize - 1;
+    let mut mantissa = byte(top) << 16 | byte(top - 1) << 8 | byte(top - 2);
+    // The mantissa would read as negative, so make room for the sign bit.
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    (size as u32) << 24 | mantissa
+}
+
+/// The 64 bit threshold that the tutorial chapters compare the last eight bytes of a hash with,
+/// for the given difficulty.
+pub fn difficulty_to_threshold(difficulty: u128) -> u64 {
+    (u128::from(u64::MAX) / difficulty.max(1)) as u64
+}
+
+/// Whether the given hash meets the given 64 bit threshold, the way the tutorial chapters check
+/// their proof of work: the last eight bytes of the hash, read as a number, must be below it.
+pub fn meets_threshold(hash: H256, threshold: u64) -> bool {
+    hash.low_u64() < threshold
+}
+
+#[test]
+fn difficulty_round_trips_through_targets() {
+    assert_eq!(difficulty_to_target(1), H256([0xff; 32]));
+    assert_eq!(target_to_difficulty(H256([0xff; 32])), 1);
+
+    // Half of all hashes start with a zero bit.
+    let mut half = [0xff; 32];
+    half[0] = 0x7f;
+    assert_eq!(difficulty_to_target(2), H256(half));
+    assert_eq!(target_to_difficulty(H256(half)), 2);
+
+    for difficulty in [3, 1_000, 1 << 40, u128::from(u64::MAX) * 7] {
+        let target = difficulty_to_target(difficulty);
+        assert_eq!(target_to_difficulty(target), difficulty, "{difficulty}");
+    }
+    assert_eq!(target_to_difficulty(H256::zero()), u128::MAX);
+}
+
+#[test]
+fn difficulty_decodes_bitcoin_compact_targets() {
+    // The target of the Bitcoin genesis block.
+    let genesis = compact_to_target(0x1d00_ffff).unwrap();
+    let mut expected = [0; 32];
+    expected[4] = 0xff;
+    expected[5] = 0xff;
+    assert_eq!(genesis, H256(expected));
+    assert_eq!(target_to_compact(genesis), 0x1d00_ffff);
+    // Which takes a little over 2^32 hashes to meet.
+    assert_eq!(target_to_difficulty(genesis), 4_295_032_833);
+
+    // Small targets shift the mantissa to the right.
+    assert_eq!(compact_to_target(0x0112_3456), Some(H256::from(0x12)));
+    assert_eq!(compact_to_target(0x0300_1234), Some(H256::from(0x1234)));
+    assert_eq!(target_to_compact(H256::from(0x80)), 0x0200_8000);
+
+    // Negative and overflowing targets are invalid.
+    assert_eq!(compact_to_target(0x0480_0001), None);
+    assert_eq!(compact_to_target(0x2101_0000), None);
+}
+
+#[test]
+fn difficulty_meets_target_and_threshold() {
+    let mut hash = [0; 32];
+    hash[0] = 0x7f;
+    assert!(meets_target(H256(hash), 2));
+    hash[0] = 0x80;
+    assert!(!meets_target(H256(hash), 2));
+    assert!(meets_target(H256([0xff; 32]), 0));
+
+    assert_eq!(difficulty_to_threshold(100), u64::MAX / 100);
+    assert!(meets_threshold(H256::from(5), 6));
+    assert!(!meets_threshold(H256::from(6), 6));
+}
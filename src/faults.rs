@@ -0,0 +1,284 @@
+//! Error handling code is the code that runs least often, so it is also the code that is most
+//! likely to be broken. Disks rarely fill up and clocks rarely jump in a test run, so the paths
+//! that deal with them never run at all unless a test makes them run.
+//!
+//! This module lets tests inject those faults on purpose:
+//! * `FaultyBackend` wraps a storage backend and fails as many of its writes as asked to.
+//! * `force_signatures` makes every ed25519 signature check pass, or fail, regardless of the
+//!   signature, for as long as the returned guard lives.
+//! * `SkewedClock` is a `Clock` that runs along with the real one, but can be moved forwards
+//!   and backwards at will.
+//!
+//! None of this is compiled outside of tests.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::c1_state_machine::{AccountingTransaction, StateMachine, User};
+use crate::c4_client::backend::{Backend, BackendError, Batch, ChainDb, Entries, MemoryBackend};
+use crate::c4_client::keystore::Keystore;
+use crate::c4_client::network::gossip::{BlockGossip, GossipAction, REQUEST_TIMEOUT};
+use crate::c4_client::network::PeerId;
+use crate::c4_client::runtime::{signing_payload, Runtime, RuntimeState, SignedExtrinsic};
+use crate::clock::{Clock, SystemClock};
+use crate::hashing::H256;
+
+/// The error that injected write failures report.
+pub const INJECTED_WRITE_FAILURE: &str = "injected write failure";
+
+/// A handle that arms write failures on a `FaultyBackend`. Clones share the same failures, so
+/// a test can keep one while the backend is owned by the code under test.
+#[derive(Clone, Debug, Default)]
+pub struct WriteFaults(Arc<AtomicUsize>);
+
+impl WriteFaults {
+    /// Make the next `count` writes fail.
+    pub fn fail_next(&self, count: usize) {
+        self.0.store(count, Ordering::SeqCst);
+    }
+
+    /// How many writes will still fail.
+    pub fn pending(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Use up one failure, if any is armed. Returns whether the current write must fail.
+    fn take(&self) -> bool {
+        self.0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+
+/// A backend whose writes fail on demand, see `WriteFaults`. Reads always go through.
+///
+/// A failed write leaves the wrapped backend untouched, just like a real database that fails
+/// to commit a batch.
+#[derive(Debug, Default)]
+pub struct FaultyBackend<B> {
+    inner: B,
+    faults: WriteFaults,
+}
+
+impl<B: Backend> FaultyBackend<B> {
+    pub fn new(inner: B) -> Self {
+        FaultyBackend {
+            inner,
+            faults: WriteFaults::default(),
+        }
+    }
+
+    /// The handle that arms failures on this backend.
+    pub fn faults(&self) -> WriteFaults {
+        self.faults.clone()
+    }
+}
+
+impl<B: Backend> Backend for FaultyBackend<B> {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BackendError> {
+        self.inner.get(key)
+    }
+
+    fn commit(&mut self, batch: Batch) -> Result<(), BackendError> {
+        if self.faults.take() {
+            return Err(BackendError::Database(INJECTED_WRITE_FAILURE.into()));
+        }
+        self.inner.commit(batch)
+    }
+
+    fn iter_prefix(&self, prefix: &[u8]) -> Result<Entries, BackendError> {
+        self.inner.iter_prefix(prefix)
+    }
+}
+
+thread_local! {
+    /// The outcome every signature check on this thread is forced to, if any. Tests run on
+    /// threads of their own, so forcing signatures in one test never leaks into another.
+    static FORCED_SIGNATURES: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// The outcome that signature checks are currently forced to, if any. Consulted by
+/// `crate::crypto::sig::PublicKey::verify`.
+pub fn forced_signature_outcome() -> Option<bool> {
+    FORCED_SIGNATURES.with(Cell::get)
+}
+
+/// Make every signature check on the current thread return `valid`, until the guard is dropped.
+#[must_use = "signatures are only forced while the guard lives"]
+pub fn force_signatures(valid: bool) -> ForcedSignatures {
+    ForcedSignatures {
+        previous: FORCED_SIGNATURES.with(|forced| forced.replace(Some(valid))),
+    }
+}
+
+/// Restores the previous signature checks when dropped. See `force_signatures`.
+pub struct ForcedSignatures {
+    previous: Option<bool>,
+}
+
+impl Drop for ForcedSignatures {
+    fn drop(&mut self) {
+        FORCED_SIGNATURES.with(|forced| forced.set(self.previous));
+    }
+}
+
+/// How far a `SkewedClock` can be skewed backwards from where it starts.
+pub const MAX_BACKWARD_SKEW: Duration = Duration::from_secs(3600);
+
+/// A clock that runs along with the real one, but can be skewed forwards and backwards.
+///
+/// `Instant`s can not be made up out of thin air, so the clock starts out `MAX_BACKWARD_SKEW`
+/// ahead of the real one. That leaves it room to be skewed back by that much. Its wall clock
+/// time starts out as the real one.
+#[derive(Clone, Copy, Debug)]
+pub struct SkewedClock {
+    /// How far the clock is ahead of the real one.
+    ahead: Duration,
+}
+
+impl SkewedClock {
+    pub fn new() -> Self {
+        SkewedClock {
+            ahead: MAX_BACKWARD_SKEW,
+        }
+    }
+
+    /// Jump forwards by the given amount.
+    pub fn skew_forward(&mut self, by: Duration) {
+        self.ahead += by;
+    }
+
+    /// Jump backwards by the given amount. The clock never falls behind the real one, so a
+    /// jump that would take it there stops at the real time instead.
+    pub fn skew_backward(&mut self, by: Duration) {
+        self.ahead = self.ahead.saturating_sub(by);
+    }
+}
+
+impl Clock for SkewedClock {
+    fn now(&self) -> Instant {
+        Instant::now() + self.ahead
+    }
+
+    fn unix_time(&self) -> Duration {
+        (SystemClock.unix_time() + self.ahead).saturating_sub(MAX_BACKWARD_SKEW)
+    }
+}
+
+impl Default for SkewedClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn faults_failed_write_leaves_chain_db_untouched() {
+    let backend = FaultyBackend::new(MemoryBackend::new());
+    let faults = backend.faults();
+    let mut db = ChainDb::new(backend);
+    db.insert_block(H256::from(1), 1, &"block one".to_string(), &10u64)
+        .unwrap();
+    db.set_best(H256::from(1)).unwrap();
+
+    faults.fail_next(2);
+    assert_eq!(
+        db.insert_block(H256::from(2), 2, &"block two".to_string(), &20u64),
+        Err(BackendError::Database(INJECTED_WRITE_FAILURE.into()))
+    );
+    assert!(db.set_best(H256::from(2)).is_err());
+    assert_eq!(faults.pending(), 0);
+
+    // Neither half of the failed batch made it to disk, and the best block did not move.
+    assert_eq!(db.block::<String>(H256::from(2)).unwrap(), None);
+    assert_eq!(db.state::<u64>(H256::from(2)).unwrap(), None);
+    assert_eq!(db.best().unwrap(), Some(H256::from(1)));
+
+    // Once the fault clears, retrying the same writes succeeds.
+    db.insert_block(H256::from(2), 2, &"block two".to_string(), &20u64)
+        .unwrap();
+    db.set_best(H256::from(2)).unwrap();
+    assert_eq!(db.state::<u64>(H256::from(2)).unwrap(), Some(20));
+    assert_eq!(db.best().unwrap(), Some(H256::from(2)));
+}
+
+fn alice_pays_charlie(nonce: u64) -> SignedExtrinsic {
+    let call = AccountingTransaction::Transfer {
+        sender: User::Alice,
+        receiver: User::Charlie,
+        amount: 10,
+    };
+    let signature = Keystore::dev()
+        .sign(User::Alice, signing_payload(User::Alice, nonce, &call))
+        .unwrap();
+    SignedExtrinsic {
+        signer: User::Alice,
+        nonce,
+        call,
+        signature,
+    }
+}
+
+#[test]
+fn faults_spuriously_failing_signature_leaves_state_untouched() {
+    let state = RuntimeState::genesis(&[(User::Alice, 100)]);
+    let extrinsic = alice_pays_charlie(0);
+
+    {
+        let _forced = force_signatures(false);
+        assert_eq!(Runtime::next_state(&state, &extrinsic), state);
+    }
+
+    // With the guard gone, the very same extrinsic goes through.
+    let end = Runtime::next_state(&state, &extrinsic);
+    assert_eq!(end.account(User::Alice).balance, 90);
+    assert_eq!(end.account(User::Alice).nonce, 1);
+}
+
+#[test]
+fn faults_spuriously_passing_signature_still_checks_nonce() {
+    let state = RuntimeState::genesis(&[(User::Alice, 100)]);
+    let mut replayed = alice_pays_charlie(0);
+    replayed.nonce = 1;
+
+    let _forced = force_signatures(true);
+    assert_eq!(Runtime::next_state(&state, &replayed), state);
+}
+
+#[test]
+fn faults_forced_signatures_nest() {
+    let outer = force_signatures(true);
+    {
+        let _inner = force_signatures(false);
+        assert_eq!(forced_signature_outcome(), Some(false));
+    }
+    assert_eq!(forced_signature_outcome(), Some(true));
+    drop(outer);
+    assert_eq!(forced_signature_outcome(), None);
+}
+
+#[test]
+fn faults_clock_jumping_ahead_times_out_gossip_requests() {
+    let mut clock = SkewedClock::new();
+    let mut gossip = BlockGossip::new();
+    let hash = H256::from(7);
+    gossip.on_announce(PeerId(1), hash, false, clock.now());
+    gossip.on_announce(PeerId(2), hash, false, clock.now());
+
+    clock.skew_forward(REQUEST_TIMEOUT);
+    let actions = gossip.tick(clock.now());
+    assert!(matches!(actions[0], GossipAction::Penalize(PeerId(1), _)));
+    assert!(matches!(actions[1], GossipAction::Request(PeerId(2), _)));
+}
+
+#[test]
+fn faults_clock_jumping_back_does_not_time_out_gossip_requests() {
+    let mut clock = SkewedClock::new();
+    let mut gossip = BlockGossip::new();
+    gossip.on_announce(PeerId(1), H256::from(7), false, clock.now());
+
+    clock.skew_backward(MAX_BACKWARD_SKEW);
+    assert!(gossip.tick(clock.now()).is_empty());
+    assert!(gossip.is_requested(H256::from(7)));
+}
@@ -0,0 +1,208 @@
+//! A C interface to the verification core, so that it can be embedded in harnesses written in
+//! other languages, and tested against other implementations of the same rules.
+//!
+//! Only plain C types cross the boundary. Hashes are 32 byte arrays, proof of work headers are
+//! the `DiyPowHeader` struct, and blocks are the bincode encoding that nodes send each other.
+//! `include/diy_blockchain.h` declares everything for C callers.
+//!
+//! Every function returns one of the `DIY_*` status codes, and writes its results through out
+//! pointers. A panic, which is what an exercise that is not done yet does, is caught and
+//! reported as `DIY_ERR_PANIC` rather than unwinding into foreign code.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::c3_consensus::{Consensus, Header, Pow};
+use crate::c4_client::runtime::{Runtime, RuntimeState, ACCOUNTS};
+use crate::c4_client::{Block, FullClient, ImportBlock, LongestChain, SimplePool};
+use crate::hashing::{header_hash, H256};
+
+/// The call succeeded, and the answer is yes where there is a question.
+pub const DIY_OK: i32 = 0;
+/// The call succeeded, but the header or block is invalid.
+pub const DIY_INVALID: i32 = 1;
+/// A pointer argument was null.
+pub const DIY_ERR_NULL: i32 = -1;
+/// The bytes could not be decoded.
+pub const DIY_ERR_DECODE: i32 = -2;
+/// The Rust code panicked, most likely on an exercise that is not done yet.
+pub const DIY_ERR_PANIC: i32 = -3;
+
+/// A header sealed with proof of work, laid out for C.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiyPowHeader {
+    pub parent: [u8; 32],
+    pub height: u64,
+    pub state_root: [u8; 32],
+    pub extrinsics_root: [u8; 32],
+    pub nonce: u64,
+}
+
+impl From<&DiyPowHeader> for Header<u64> {
+    fn from(header: &DiyPowHeader) -> Self {
+        Header::new(
+            H256(header.parent),
+            header.height,
+            H256(header.state_root),
+            H256(header.extrinsics_root),
+            header.nonce,
+        )
+    }
+}
+
+/// The client behind a `DiyClient` handle: the node's, with the signed currency runtime.
+pub type DiyClient = FullClient<Pow, Runtime, LongestChain, SimplePool<Runtime>>;
+
+/// Run `f`, turning a panic into `DIY_ERR_PANIC`.
+fn guard(f: impl FnOnce() -> i32) -> i32 {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(DIY_ERR_PANIC)
+}
+
+/// Hash a header, and write the hash to `out`.
+///
+/// # Safety
+///
+/// `header` must point to a header, and `out` to 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn diy_header_hash(header: *const DiyPowHeader, out: *mut [u8; 32]) -> i32 {
+    let (Some(header), false) = (header.as_ref(), out.is_null()) else {
+        return DIY_ERR_NULL;
+    };
+    guard(|| {
+        out.write(header_hash(&Header::from(header)).0);
+        DIY_OK
+    })
+}
+
+/// Check the seal of a header against the given proof of work threshold. Returns `DIY_OK` if
+/// it is valid, and `DIY_INVALID` if not.
+///
+/// # Safety
+///
+/// `header` must point to a header.
+#[no_mangle]
+pub unsafe extern "C" fn diy_pow_verify_seal(header: *const DiyPowHeader, threshold: u64) -> i32 {
+    let Some(header) = header.as_ref() else {
+        return DIY_ERR_NULL;
+    };
+    // Proof of work does not look at the parent's digest.
+    guard(|| match Pow::new(threshold).validate(&0, &header.into()) {
+        true => DIY_OK,
+        false => DIY_INVALID,
+    })
+}
+
+/// Start a client whose genesis state endows Alice, Bob and Charlie with the given balances,
+/// and write a handle to it to `out`. Free it with `diy_client_free`.
+///
+/// # Safety
+///
+/// `balances` must point to 3 balances, and `out` to a writable handle.
+#[no_mangle]
+pub unsafe extern "C" fn diy_client_new(
+    balances: *const [u64; 3],
+    out: *mut *mut DiyClient,
+) -> i32 {
+    let (Some(balances), false) = (balances.as_ref(), out.is_null()) else {
+        return DIY_ERR_NULL;
+    };
+    out.write(ptr::null_mut());
+    guard(|| {
+        let endowments: Vec<_> = ACCOUNTS.into_iter().zip(*balances).collect();
+        let client = DiyClient::new(RuntimeState::genesis(&endowments));
+        out.write(Box::into_raw(Box::new(client)));
+        DIY_OK
+    })
+}
+
+/// Free a client. Null handles are ignored.
+///
+/// # Safety
+///
+/// `client` must be null or come from `diy_client_new`, and must not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn diy_client_free(client: *mut DiyClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+/// Decode a block and import it. Returns `DIY_OK` if the client imported it, and `DIY_INVALID`
+/// if the client rejected it.
+///
+/// # Safety
+///
+/// `client` must be a live handle, and `block` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn diy_client_import_block(
+    client: *mut DiyClient,
+    block: *const u8,
+    len: usize,
+) -> i32 {
+    let (Some(client), false) = (client.as_mut(), block.is_null()) else {
+        return DIY_ERR_NULL;
+    };
+    let bytes = std::slice::from_raw_parts(block, len);
+    let Ok(block) = bincode::deserialize::<Block<Pow, Runtime>>(bytes) else {
+        return DIY_ERR_DECODE;
+    };
+    guard(|| match client.import_block(block) {
+        true => DIY_OK,
+        false => DIY_INVALID,
+    })
+}
+
+/// Write the hash of the client's best block to `out`.
+///
+/// # Safety
+///
+/// `client` must be a live handle, and `out` must point to 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn diy_client_best_block(
+    client: *const DiyClient,
+    out: *mut [u8; 32],
+) -> i32 {
+    let (Some(client), false) = (client.as_ref(), out.is_null()) else {
+        return DIY_ERR_NULL;
+    };
+    guard(|| {
+        out.write(client.best_block().0);
+        DIY_OK
+    })
+}
+
+#[cfg(test)]
+fn header() -> DiyPowHeader {
+    DiyPowHeader {
+        parent: [1; 32],
+        height: 7,
+        state_root: [2; 32],
+        extrinsics_root: [3; 32],
+        nonce: 42,
+    }
+}
+
+#[test]
+fn ffi_header_hash_matches_the_rust_hash() {
+    let mut out = [0; 32];
+    assert_eq!(unsafe { diy_header_hash(&header(), &mut out) }, DIY_OK);
+    assert_eq!(H256(out), header_hash(&Header::from(&header())));
+    assert_eq!(
+        unsafe { diy_header_hash(ptr::null(), &mut out) },
+        DIY_ERR_NULL
+    );
+}
+
+#[test]
+fn ffi_rejects_bad_arguments() {
+    assert_eq!(unsafe { diy_pow_verify_seal(ptr::null(), 0) }, DIY_ERR_NULL);
+    let mut client = ptr::null_mut();
+    assert_eq!(
+        unsafe { diy_client_new(ptr::null(), &mut client) },
+        DIY_ERR_NULL
+    );
+    assert!(client.is_null());
+    let status = unsafe { diy_client_import_block(client, [0; 4].as_ptr(), 4) };
+    assert_eq!(status, DIY_ERR_NULL);
+}
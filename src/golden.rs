@@ -0,0 +1,244 @@
+//! Golden test vectors for everything whose bytes other code depends on. Hashes end up in
+//! headers, headers end up in other headers, and encodings go over the wire and onto disk. A
+//! refactor that changes any of them by accident breaks every existing chain, and nothing else in
+//! the tests would notice, since they only compare values made by the same code.
+//!
+//! The fixtures in `golden/v<VERSION>` pin down a few canonical values: their JSON, their binary
+//! encoding in hex, and their hashes. The tests build the same values in code and check them
+//! against the fixtures in both directions.
+//!
+//! When an encoding or a hash changes on purpose, bump `VERSION` and regenerate the fixtures with
+//! `GOLDEN_BLESS=1 cargo test golden`. The version is part of every fixture, so a stale fixture
+//! can not pass for a current one.
+
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::c1_state_machine::{AccountingTransaction, User};
+use crate::c3_consensus::{Header, Pow};
+use crate::c4_client::keystore::Keystore;
+use crate::c4_client::runtime::{signing_payload, Runtime, SignedExtrinsic};
+use crate::c4_client::Block;
+use crate::crypto::{from_hex, to_hex};
+use crate::hashing::{blake2_256, extrinsic_hash, hash, header_hash, keccak_256, H256};
+use crate::merkle::merkle_root;
+
+/// The version of the fixtures that the tests expect.
+const VERSION: u64 = 1;
+
+/// Check the given vectors against the fixture file of the given name, or write them to it when
+/// blessing.
+fn check_fixture(name: &str, vectors: Vec<Value>) {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "golden",
+        &format!("v{VERSION}"),
+        &format!("{name}.json"),
+    ]
+    .iter()
+    .collect();
+    let expected = json!({ "version": VERSION, "vectors": vectors });
+
+    if std::env::var_os("GOLDEN_BLESS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let json = serde_json::to_string_pretty(&expected).unwrap();
+        std::fs::write(&path, json + "\n").unwrap();
+        return;
+    }
+
+    let fixture: Value = serde_json::from_str(
+        &std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("missing fixture {}: {e}", path.display())),
+    )
+    .unwrap();
+    assert_eq!(
+        fixture["version"],
+        VERSION,
+        "stale fixture {}",
+        path.display()
+    );
+    let fixtures = fixture["vectors"].as_array().unwrap();
+    assert_eq!(fixtures.len(), vectors.len(), "{}", path.display());
+    for (fixture, vector) in fixtures.iter().zip(&vectors) {
+        assert_eq!(
+            fixture,
+            vector,
+            "vector {} in {}",
+            fixture["name"],
+            path.display()
+        );
+    }
+}
+
+/// The hex of a value's binary encoding.
+fn encoding<T: Serialize>(value: &T) -> String {
+    format!("0x{}", to_hex(&bincode::serialize(value).unwrap()))
+}
+
+/// Decode a value from the hex of its binary encoding, as found in a fixture.
+fn decode<T: DeserializeOwned>(vector: &Value) -> T {
+    let bytes = from_hex(vector["encoding"].as_str().unwrap()).unwrap();
+    bincode::deserialize(&bytes).unwrap()
+}
+
+fn headers() -> Vec<(&'static str, Header<u64>)> {
+    vec![
+        (
+            "genesis",
+            Header::new(H256::zero(), 0, H256::from(1), merkle_root::<u8>(&[]), 0),
+        ),
+        (
+            "child",
+            Header::new(
+                hash(&"genesis"),
+                1,
+                hash(&"state"),
+                hash(&"extrinsics"),
+                u64::MAX,
+            ),
+        ),
+    ]
+}
+
+fn extrinsics() -> Vec<(&'static str, SignedExtrinsic)> {
+    let keystore = Keystore::dev();
+    let sign = |signer, nonce, call: AccountingTransaction| SignedExtrinsic {
+        signer,
+        nonce,
+        signature: keystore
+            .sign(signer, signing_payload(signer, nonce, &call))
+            .unwrap(),
+        call,
+    };
+    vec![
+        (
+            "mint",
+            sign(
+                User::Alice,
+                0,
+                AccountingTransaction::Mint {
+                    minter: User::Alice,
+                    amount: 100,
+                },
+            ),
+        ),
+        (
+            "burn",
+            sign(
+                User::Bob,
+                7,
+                AccountingTransaction::Burn {
+                    burner: User::Bob,
+                    amount: 1,
+                },
+            ),
+        ),
+        (
+            "transfer",
+            sign(
+                User::Charlie,
+                u64::MAX,
+                AccountingTransaction::Transfer {
+                    sender: User::Charlie,
+                    receiver: User::Alice,
+                    amount: u64::MAX,
+                },
+            ),
+        ),
+    ]
+}
+
+/// Blocks can only be built by the client, which is an exercise, so the canonical blocks are
+/// put together from their JSON instead.
+fn blocks() -> Vec<(&'static str, Block<Pow, Runtime>)> {
+    let body: Vec<SignedExtrinsic> = extrinsics().into_iter().map(|(_, e)| e).collect();
+    let parent = header_hash(&headers()[0].1);
+    let header = Header::new(parent, 1, hash(&"state"), merkle_root(&body), 42);
+    let empty = Header::new(parent, 1, hash(&"state"), merkle_root::<u8>(&[]), 0);
+    [("empty", empty, vec![]), ("full", header, body)]
+        .into_iter()
+        .map(|(name, header, body)| {
+            let block = json!({ "header": header, "body": body });
+            (name, serde_json::from_value(block).unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn golden_hashes() {
+    let inputs: [(&str, &[u8]); 3] = [("empty", b""), ("abc", b"abc"), ("long", &[0xab; 200])];
+    let vectors = inputs
+        .iter()
+        .map(|(name, input)| {
+            json!({
+                "name": name,
+                "input": format!("0x{}", to_hex(input)),
+                "blake2_256": format!("0x{}", to_hex(&blake2_256(input))),
+                "keccak_256": format!("0x{}", to_hex(&keccak_256(input))),
+                "hash": hash(input),
+            })
+        })
+        .collect();
+    check_fixture("hashes", vectors);
+}
+
+#[test]
+fn golden_headers() {
+    let mut vectors = Vec::new();
+    for (name, header) in headers() {
+        let vector = json!({
+            "name": name,
+            "header": header,
+            "encoding": encoding(&header),
+            "hash": header_hash(&header),
+        });
+        assert_eq!(decode::<Header<u64>>(&vector), header);
+        assert_eq!(
+            serde_json::from_value::<Header<u64>>(vector["header"].clone()).unwrap(),
+            header
+        );
+        vectors.push(vector);
+    }
+    check_fixture("headers", vectors);
+}
+
+#[test]
+fn golden_extrinsics() {
+    let mut vectors = Vec::new();
+    for (name, extrinsic) in extrinsics() {
+        let vector = json!({
+            "name": name,
+            "extrinsic": extrinsic,
+            "signing_payload": signing_payload(extrinsic.signer, extrinsic.nonce, &extrinsic.call),
+            "encoding": encoding(&extrinsic),
+            "hash": extrinsic_hash(&extrinsic),
+        });
+        assert_eq!(decode::<SignedExtrinsic>(&vector), extrinsic);
+        vectors.push(vector);
+    }
+    check_fixture("extrinsics", vectors);
+}
+
+#[test]
+fn golden_blocks() {
+    let mut vectors = Vec::new();
+    for (name, block) in blocks() {
+        assert_eq!(block.header().extrinsics_root(), merkle_root(block.body()));
+        let vector = json!({
+            "name": name,
+            "block": serde_json::to_value(&block).unwrap(),
+            "encoding": encoding(&block),
+            "hash": header_hash(block.header()),
+        });
+        // Blocks of the PoW engine can not be compared directly, but their encodings can.
+        assert_eq!(
+            encoding(&decode::<Block<Pow, Runtime>>(&vector)),
+            vector["encoding"]
+        );
+        vectors.push(vector);
+    }
+    check_fixture("blocks", vectors);
+}
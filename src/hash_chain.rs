@@ -0,0 +1,116 @@
+//! A hash chain is a sequence of values in which every value is the hash of the one before it.
+//! Finding the value before a given one means inverting the hash, which nobody can do. So
+//! whoever knows a value in the chain is the only one who can show the values before it.
+//!
+//! This makes for one-time passwords, as in Lamport's S/KEY. The user starts from a secret seed,
+//! hashes it `n` times, and hands the last value, the anchor, to the server. To log in, the
+//! user reveals the value right before the anchor. The server checks that it hashes to the
+//! anchor, and remembers it as the new anchor. An eavesdropper who sees a password learns
+//! nothing useful: the next login needs the value before that one. After `n` logins the chain
+//! is used up, and the user sets up a new one.
+//!
+//! The anchor is a commitment to the whole chain, in the same way that a commitment in
+//! `crate::commit_reveal` binds a single value. See the one-time login exercise.
+
+use alloc::vec::Vec;
+
+use crate::hashing::{hash, H256};
+
+/// One step along the chain.
+fn step(value: &H256) -> H256 {
+    hash(&("hash chain", value))
+}
+
+/// A chain of hashes, and how far along it we have revealed.
+#[derive(Clone, Debug)]
+pub struct HashChain {
+    /// Every value in the chain, starting with the seed and ending with the anchor.
+    values: Vec<H256>,
+    /// The index of the value that was revealed last. It starts out at the anchor.
+    revealed: usize,
+}
+
+impl HashChain {
+    /// A chain of the given length, starting from the given secret seed. It can be used to
+    /// reveal `length` values before it runs out.
+    pub fn new(seed: H256, length: usize) -> Self {
+        let mut values = Vec::with_capacity(length + 1);
+        values.push(seed);
+        for _ in 0..length {
+            values.push(step(values.last().expect("the seed is always there")));
+        }
+        HashChain {
+            revealed: length,
+            values,
+        }
+    }
+
+    /// The last value of the chain, which is safe to publish.
+    pub fn anchor(&self) -> H256 {
+        self.values[self.values.len() - 1]
+    }
+
+    /// The number of values that can still be revealed.
+    pub fn remaining(&self) -> usize {
+        self.revealed
+    }
+
+    /// Reveal the next value, the one before the value that was revealed last. Returns None if
+    /// the chain is used up.
+    pub fn reveal(&mut self) -> Option<H256> {
+        self.revealed = self.revealed.checked_sub(1)?;
+        Some(self.values[self.revealed])
+    }
+}
+
+/// Check that `preimage` comes right before `current` in a hash chain.
+pub fn verify_preimage(current: H256, preimage: H256) -> bool {
+    step(&preimage) == current
+}
+
+/// The number of steps from `preimage` up to `current` in a hash chain, if it is at most
+/// `max_steps`. This lets a verifier accept a value even if some of the values in between were
+/// never revealed to it.
+pub fn steps_to(current: H256, preimage: H256, max_steps: usize) -> Option<usize> {
+    let mut value = preimage;
+    for steps in 1..=max_steps {
+        value = step(&value);
+        if value == current {
+            return Some(steps);
+        }
+    }
+    None
+}
+
+#[test]
+fn hash_chain_reveals_preimages_in_order() {
+    let mut chain = HashChain::new(H256::from(7), 3);
+    let mut current = chain.anchor();
+    for remaining in (0..3).rev() {
+        let preimage = chain.reveal().unwrap();
+        assert!(verify_preimage(current, preimage));
+        assert!(!verify_preimage(preimage, current));
+        assert_eq!(chain.remaining(), remaining);
+        current = preimage;
+    }
+    // The last value revealed is the seed itself.
+    assert_eq!(current, H256::from(7));
+    assert_eq!(chain.reveal(), None);
+}
+
+#[test]
+fn hash_chain_skips_unrevealed_values() {
+    let mut chain = HashChain::new(H256::from(7), 5);
+    let anchor = chain.anchor();
+    chain.reveal();
+    chain.reveal();
+    let third = chain.reveal().unwrap();
+
+    assert!(!verify_preimage(anchor, third));
+    assert_eq!(steps_to(anchor, third, 5), Some(3));
+    assert_eq!(steps_to(anchor, third, 2), None);
+    assert_eq!(
+        steps_to(anchor, HashChain::new(H256::from(8), 5).anchor(), 5),
+        None
+    );
+}
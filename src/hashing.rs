@@ -0,0 +1,386 @@
+//! Hashing for the whole crate.
+//!
+//! The tutorial started out hashing with the standard library's `DefaultHasher`. That was fine
+//! for learning, but it is not a cryptographic hash, so anybody can cook up two blocks with the
+//! same hash. Worse, the standard library makes no promise that it computes the same hashes in
+//! the next Rust release, so a chain that is hashed with it could stop verifying after a
+//! compiler upgrade.
+//!
+//! We hash with blake2b-256 instead, the hash that Substrate uses too. Any type that implements
+//! `std::hash::Hash` can be hashed, by feeding the bytes that its `Hash` implementation produces
+//! into blake2b. Integers are fed in little endian byte order, so the hashes are the same on
+//! every platform.
+//!
+//! Blocks, their parents and everything else that the chain links together by hash are
+//! identified by the full 32 byte digest, an `H256`. A 64 bit hash would be unrealistically
+//! small: with a few billion blocks, collisions become likely even by chance. Toy signatures and
+//! peer ids that only need a short fingerprint use `hash_u64` instead.
+//!
+//! The old behavior remains available behind the `compat-hash` feature.
+//!
+//! Values of different types can feed the same bytes into the hasher: a header and a list of
+//! extrinsics might well collide, and nothing in `hash` tells them apart. So values that the
+//! chain commits to are hashed with `hash_of`, under a domain that names what they are, or with
+//! one of the helpers for the common cases, such as `header_hash`.
+//!
+//! Ethereum hashes with keccak-256 instead, the variant of SHA3 from before it was standardized.
+//! Code that has to produce hashes an Ethereum client would agree with, such as the RLP
+//! exercises, picks its hash function through the `HashScheme` trait, with `Blake2` and
+//! `Keccak` to choose from. Those only hash bytes: Ethereum hashes encodings, not Rust values.
+
+use alloc::{format, string::{String, ToString}};
+use core::hash::{Hash, Hasher};
+
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::crypto::{from_hex, to_hex};
+
+type Blake2b256 = Blake2b<U32>;
+
+/// Calculate the blake2b-256 digest of the given bytes.
+pub fn blake2_256(data: &[u8]) -> [u8; 32] {
+    Blake2b256::digest(data).into()
+}
+
+/// The number of bytes that keccak-256 absorbs at a time. The remaining 64 bytes of its 200
+/// byte state are never touched by the input, which is what makes the sponge secure.
+const KECCAK_256_RATE: usize = 136;
+
+/// Run the keccak sponge with a 256 bit output, padding the input with the given domain byte.
+/// Keccak-256 pads with 0x01, and the SHA3-256 standard with 0x06.
+fn keccak_sponge(data: &[u8], domain: u8) -> [u8; 32] {
+    let mut padded = data.to_vec();
+    padded.push(domain);
+    padded.resize(padded.len().div_ceil(KECCAK_256_RATE) * KECCAK_256_RATE, 0);
+    *padded.last_mut().expect("padding is never empty") |= 0x80;
+
+    let mut state = [0u64; 25];
+    for block in padded.chunks(KECCAK_256_RATE) {
+        for (lane, bytes) in state.iter_mut().zip(block.chunks(8)) {
+            *lane ^= u64::from_le_bytes(bytes.try_into().expect("the rate is a multiple of 8"));
+        }
+        keccak::f1600(&mut state);
+    }
+
+    let mut digest = [0; 32];
+    for (bytes, lane) in digest.chunks_mut(8).zip(state) {
+        bytes.copy_from_slice(&lane.to_le_bytes());
+    }
+    digest
+}
+
+/// Calculate the keccak-256 digest of the given bytes, the way Ethereum does.
+pub fn keccak_256(data: &[u8]) -> [u8; 32] {
+    keccak_sponge(data, 0x01)
+}
+
+/// A hash function from bytes to 32 byte digests, so that code can be written once and run with
+/// whichever hash function the chain it talks to uses.
+pub trait HashScheme {
+    fn digest(data: &[u8]) -> H256;
+}
+
+/// blake2b-256, the hash function this crate and Substrate use.
+pub struct Blake2;
+
+impl HashScheme for Blake2 {
+    fn digest(data: &[u8]) -> H256 {
+        H256(blake2_256(data))
+    }
+}
+
+/// keccak-256, the hash function Ethereum uses.
+pub struct Keccak;
+
+impl HashScheme for Keccak {
+    fn digest(data: &[u8]) -> H256 {
+        H256(keccak_256(data))
+    }
+}
+
+/// A 32 byte hash.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct H256(pub [u8; 32]);
+
+impl H256 {
+    /// The hash that is all zeros. Genesis blocks use it as their parent.
+    pub const fn zero() -> Self {
+        H256([0; 32])
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The last eight bytes, read as a big endian number. This is the inverse of `From<u64>`.
+    pub fn low_u64(&self) -> u64 {
+        u64::from_be_bytes(self.0[24..].try_into().expect("slice has eight bytes"))
+    }
+}
+
+/// Made up hashes are handy in tests: `H256::from(7)` is 7 in big endian, padded with zeros.
+impl From<u64> for H256 {
+    fn from(n: u64) -> Self {
+        let mut bytes = [0; 32];
+        bytes[24..].copy_from_slice(&n.to_be_bytes());
+        H256(bytes)
+    }
+}
+
+impl From<[u8; 32]> for H256 {
+    fn from(bytes: [u8; 32]) -> Self {
+        H256(bytes)
+    }
+}
+
+impl core::fmt::Display for H256 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "0x{}", to_hex(&self.0))
+    }
+}
+
+impl core::fmt::Debug for H256 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "0x{}", to_hex(&self.0))
+    }
+}
+
+impl core::str::FromStr for H256 {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_hex(s)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(H256)
+            .ok_or_else(|| format!("invalid hash {s:?}, expected 32 bytes of hex"))
+    }
+}
+
+// Hashes are hex strings in JSON, and plain bytes in binary formats.
+impl Serialize for H256 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_string())
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for H256 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let hex = String::deserialize(deserializer)?;
+            hex.parse().map_err(serde::de::Error::custom)
+        } else {
+            <[u8; 32]>::deserialize(deserializer).map(H256)
+        }
+    }
+}
+
+/// A `std::hash::Hasher` that feeds everything it is given into blake2b-256.
+#[derive(Clone, Default)]
+pub struct Blake2Hasher(Blake2b256);
+
+impl Blake2Hasher {
+    /// The full digest of everything written so far.
+    pub fn finalize(self) -> [u8; 32] {
+        self.0.finalize().into()
+    }
+}
+
+impl Hasher for Blake2Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    // Sizes differ between platforms, so they are always hashed as 64 bits.
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_usize(i as usize);
+    }
+
+    fn finish(&self) -> u64 {
+        let digest: [u8; 32] = self.0.clone().finalize().into();
+        u64::from_le_bytes(digest[..8].try_into().expect("digest has 32 bytes"))
+    }
+}
+
+/// Calculate the full blake2b-256 digest of any hashable value.
+pub fn blake2_256_of<T: Hash + ?Sized>(t: &T) -> [u8; 32] {
+    let mut hasher = Blake2Hasher::default();
+    t.hash(&mut hasher);
+    hasher.finalize()
+}
+
+/// Hash any hashable value.
+#[cfg(not(feature = "compat-hash"))]
+pub fn hash<T: Hash + ?Sized>(t: &T) -> H256 {
+    H256(blake2_256_of(t))
+}
+
+/// Hash any hashable value down to a short `u64` fingerprint.
+#[cfg(not(feature = "compat-hash"))]
+pub fn hash_u64<T: Hash + ?Sized>(t: &T) -> u64 {
+    let mut hasher = Blake2Hasher::default();
+    t.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash any hashable value. The standard library's hasher only produces 64 bits, which end up
+/// in the last eight bytes.
+#[cfg(feature = "compat-hash")]
+pub fn hash<T: Hash + ?Sized>(t: &T) -> H256 {
+    H256::from(hash_u64(t))
+}
+
+/// Hash a value under the given domain. Values hashed under different domains never collide,
+/// even if they feed the same bytes into the hasher.
+pub fn hash_of<T: Hash + ?Sized>(domain: &str, value: &T) -> H256 {
+    hash(&(domain, value))
+}
+
+/// The hash of a block header, which is how blocks refer to their parents.
+pub fn header_hash<Digest: Hash>(header: &crate::c3_consensus::Header<Digest>) -> H256 {
+    hash_of("header", header)
+}
+
+/// The hash of a single extrinsic. Merkle trees over block bodies use these as their leaves.
+pub fn extrinsic_hash<T: Hash + ?Sized>(extrinsic: &T) -> H256 {
+    hash_of("extrinsic", extrinsic)
+}
+
+/// The hash of a pin, as keyed in on a keypad.
+pub fn pin_hash<K: Hash>(pin: &[K]) -> H256 {
+    hash_of("pin", pin)
+}
+
+/// Hash any hashable value down to a short `u64` fingerprint.
+#[cfg(feature = "compat-hash")]
+pub fn hash_u64<T: Hash + ?Sized>(t: &T) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    t.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn hashing_matches_blake2b_test_vector() {
+    assert_eq!(
+        crate::crypto::to_hex(&blake2_256(b"")),
+        "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8"
+    );
+    // Writing bytes through the hasher is the same as hashing them directly.
+    let mut hasher = Blake2Hasher::default();
+    hasher.write(b"abc");
+    assert_eq!(hasher.finalize(), blake2_256(b"abc"));
+}
+
+#[test]
+fn hashing_matches_keccak_test_vectors() {
+    assert_eq!(
+        Keccak::digest(b"").to_string(),
+        "0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+    );
+    assert_eq!(
+        Keccak::digest(b"abc").to_string(),
+        "0x4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+    );
+    assert_eq!(Blake2::digest(b"abc"), H256(blake2_256(b"abc")));
+
+    // Standard SHA3-256 only pads differently, and is easy to check against other libraries.
+    // These cover inputs right below, at and well past the end of the first block.
+    let sha3_256 = |data: &[u8]| crate::crypto::to_hex(&keccak_sponge(data, 0x06));
+    assert_eq!(
+        sha3_256(&[b'a'; 135]),
+        "8094bb53c44cfb1e67b7c30447f9a1c33696d2463ecc1d9c92538913392843c9"
+    );
+    assert_eq!(
+        sha3_256(&[b'a'; 136]),
+        "3fc5559f14db8e453a0a3091edbd2bc25e11528d81c66fa570a4efdcc2695ee1"
+    );
+    let bytes: Vec<u8> = (0..=255).chain(0..=255).collect();
+    assert_eq!(
+        sha3_256(&bytes),
+        "d4728ea5e9f3819f2b4760151a8f802dbe9f941fd6fb59b3715892436555772a"
+    );
+}
+
+#[cfg(not(feature = "compat-hash"))]
+#[test]
+fn hashing_is_stable() {
+    // If this ever changes, every chain hashed with an older version stops verifying.
+    assert_eq!(hash(&42u64).0, blake2_256(&42u64.to_le_bytes()));
+    assert_eq!(
+        hash_u64(&42u64).to_le_bytes(),
+        blake2_256(&42u64.to_le_bytes())[..8]
+    );
+    assert_eq!(hash_u64(&(1u8, "two", [3u64])), 6403324415803712217);
+    assert_ne!(hash(&1u32), hash(&1u64));
+}
+
+#[test]
+fn hashing_separates_domains() {
+    assert_eq!(hash_of("pin", &[1u8, 2]), pin_hash(&[1u8, 2]));
+    assert_ne!(pin_hash(&[1u8, 2]), extrinsic_hash(&[1u8, 2]));
+    assert_ne!(pin_hash(&[1u8, 2]), hash(&[1u8, 2]));
+
+    let header = crate::c3_consensus::Header::new(H256::zero(), 0, H256::zero(), H256::zero(), ());
+    assert_eq!(header_hash(&header), hash_of("header", &header));
+    assert_ne!(header_hash(&header), hash(&header));
+}
+
+#[test]
+fn hashing_h256_formats_as_hex() {
+    let h = H256::from(0xabcd);
+    let hex = format!("0x{}abcd", "0".repeat(60));
+    assert_eq!(h.to_string(), hex);
+    assert_eq!(hex.parse(), Ok(h));
+    assert_eq!(h.low_u64(), 0xabcd);
+    assert!(H256::zero() < h);
+
+    assert_eq!(serde_json::to_string(&h).unwrap(), format!("\"{hex}\""));
+    assert_eq!(
+        serde_json::from_str::<H256>(&format!("\"{hex}\"")).unwrap(),
+        h
+    );
+    let bytes = bincode::serialize(&h).unwrap();
+    assert_eq!(bytes.len(), 32);
+    assert_eq!(bincode::deserialize::<H256>(&bytes).unwrap(), h);
+}
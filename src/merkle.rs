@@ -0,0 +1,148 @@
+//! A Merkle tree commits to a list of items with a single hash, the root, while still letting
+//! anybody prove that one particular item is in the list without revealing the others.
+//!
+//! The leaves of the tree are the `extrinsic_hash`es of the items. Each level above hashes the nodes of the
+//! level below together in pairs, until only the root is left. To prove that an item is in the
+//! tree, it is enough to hand over the sibling of every node on the path from its leaf up to the
+//! root. The verifier hashes its way up the path, and checks that it arrives at the root it
+//! already trusts.
+//!
+//! Headers use a Merkle root to commit to the extrinsics of their block, and light clients check
+//! extrinsic proofs against it. The state is a key-value map rather than a list, so it is kept
+//! in a Patricia trie instead. See the trie module.
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::{extrinsic_hash, hash, H256};
+
+/// One step on the path from a leaf up to the Merkle root.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    /// The hash of the other child of the parent node.
+    pub sibling: H256,
+    /// Whether that other child is on the left.
+    pub sibling_is_left: bool,
+}
+
+/// A proof that some item is a leaf of a Merkle tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// The path from the leaf up to the root, starting at the leaf.
+    pub path: Vec<ProofStep>,
+}
+
+/// The root of a tree without any leaves.
+pub fn empty_root() -> H256 {
+    hash(&())
+}
+
+/// Hash the nodes of one level of the tree together in pairs, to get the level above. When a
+/// level has an odd number of nodes, the last one is carried up to the next level as it is.
+fn next_level(level: &[H256]) -> Vec<H256> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash(&(left, right)),
+            [carried] => *carried,
+            _ => unreachable!("chunks of two"),
+        })
+        .collect()
+}
+
+fn leaves<T: core::hash::Hash>(items: &[T]) -> Vec<H256> {
+    items.iter().map(extrinsic_hash).collect()
+}
+
+/// The Merkle root of the given items. The root of no items at all is `empty_root`.
+pub fn merkle_root<T: core::hash::Hash>(items: &[T]) -> H256 {
+    let mut level = leaves(items);
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.first().copied().unwrap_or_else(empty_root)
+}
+
+/// Prove that the item at the given index is in the tree over the given items. Returns None if
+/// there is no such item.
+pub fn build_proof<T: core::hash::Hash>(items: &[T], mut index: usize) -> Option<MerkleProof> {
+    if index >= items.len() {
+        return None;
+    }
+    let mut path = Vec::new();
+    let mut level = leaves(items);
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        // A node without a sibling is carried up as it is, so there is no step to record.
+        if let Some(&sibling_hash) = level.get(sibling) {
+            path.push(ProofStep {
+                sibling: sibling_hash,
+                sibling_is_left: sibling < index,
+            });
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    Some(MerkleProof { path })
+}
+
+/// The root that the proof arrives at, starting from the given item.
+pub fn proof_root<T: core::hash::Hash>(item: &T, proof: &MerkleProof) -> H256 {
+    proof.path.iter().fold(extrinsic_hash(item), |node, step| {
+        if step.sibling_is_left {
+            hash(&(step.sibling, node))
+        } else {
+            hash(&(node, step.sibling))
+        }
+    })
+}
+
+/// Check that the proof shows the given item to be in the tree with the given root.
+pub fn verify_proof<T: core::hash::Hash>(root: H256, item: &T, proof: &MerkleProof) -> bool {
+    proof_root(item, proof) == root
+}
+
+#[test]
+fn merkle_proofs_verify_for_every_leaf() {
+    for count in 1..=9u64 {
+        let items: Vec<u64> = (0..count).collect();
+        let root = merkle_root(&items);
+        for (index, item) in items.iter().enumerate() {
+            let proof = build_proof(&items, index).unwrap();
+            assert!(verify_proof(root, item, &proof), "{index} of {count}");
+            assert!(!verify_proof(root, &(item + 100), &proof));
+        }
+        assert_eq!(build_proof(&items, items.len()), None);
+    }
+}
+
+#[test]
+fn merkle_root_handles_small_trees() {
+    assert_eq!(merkle_root::<u64>(&[]), empty_root());
+    assert_eq!(build_proof::<u64>(&[], 0), None);
+    assert_eq!(merkle_root(&[7u64]), extrinsic_hash(&7u64));
+
+    // An odd node is carried up, so three leaves pair the first two and carry the third.
+    let pair = hash(&(extrinsic_hash(&1u64), extrinsic_hash(&2u64)));
+    assert_eq!(merkle_root(&[1u64, 2]), pair);
+    assert_eq!(
+        merkle_root(&[1u64, 2, 3]),
+        hash(&(pair, extrinsic_hash(&3u64)))
+    );
+    assert_eq!(build_proof(&[1u64, 2, 3], 2).unwrap().path.len(), 1);
+
+    // The order of the leaves matters.
+    assert_ne!(merkle_root(&[1u64, 2]), merkle_root(&[2u64, 1]));
+}
+
+#[test]
+fn merkle_proof_is_bound_to_its_position() {
+    let items = [10u64, 20, 30, 40];
+    let root = merkle_root(&items);
+    let mut proof = build_proof(&items, 1).unwrap();
+    assert!(verify_proof(root, &20u64, &proof));
+
+    proof.path[0].sibling_is_left = !proof.path[0].sibling_is_left;
+    assert!(!verify_proof(root, &20u64, &proof));
+}
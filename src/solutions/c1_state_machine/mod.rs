@@ -0,0 +1,28 @@
+use alloc::collections::BTreeMap;
+
+/// Take the amount from the account's balance, if it has enough. Empty balances are removed.
+pub(super) fn debit<K: Ord>(balances: &mut BTreeMap<K, u64>, who: K, amount: u64) -> bool {
+    let balance = balances.get(&who).copied().unwrap_or(0);
+    if balance < amount {
+        return false;
+    }
+    if balance == amount {
+        balances.remove(&who);
+    } else {
+        balances.insert(who, balance - amount);
+    }
+    true
+}
+
+/// Give the amount to the account, unless its balance would overflow.
+pub(super) fn credit<K: Ord>(balances: &mut BTreeMap<K, u64>, who: K, amount: u64) -> bool {
+    if amount == 0 {
+        return true;
+    }
+    let balance = balances.get(&who).copied().unwrap_or(0);
+    let Some(balance) = balance.checked_add(amount) else {
+        return false;
+    };
+    balances.insert(who, balance);
+    true
+}
@@ -0,0 +1,108 @@
+use alloc::vec::Vec;
+
+use super::{
+    Contract, Execution, Instruction, Storage, VmError, VmState, VmTransaction,
+    DEPLOY_GAS_PER_INSTRUCTION,
+};
+use crate::c1_state_machine::User;
+
+pub(super) fn execute(code: &[Instruction], storage: &Storage, gas_limit: u64) -> Execution {
+    let mut storage = storage.clone();
+    let mut stack = Vec::new();
+    let mut gas_used = 0;
+    for instruction in code {
+        if gas_limit - gas_used < instruction.gas() {
+            return Execution {
+                gas_used: gas_limit,
+                result: Err(VmError::OutOfGas),
+            };
+        }
+        gas_used += instruction.gas();
+        if let Err(error) = step(*instruction, &mut stack, &mut storage) {
+            return Execution {
+                gas_used,
+                result: Err(error),
+            };
+        }
+        if *instruction == Instruction::Halt {
+            break;
+        }
+    }
+    Execution {
+        gas_used,
+        result: Ok(storage),
+    }
+}
+
+/// Run a single instruction.
+fn step(
+    instruction: Instruction,
+    stack: &mut Vec<u64>,
+    storage: &mut Storage,
+) -> Result<(), VmError> {
+    let mut pop = || stack.pop().ok_or(VmError::StackUnderflow);
+    let pushed = match instruction {
+        Instruction::Push(value) => value,
+        Instruction::Add => pop()?.wrapping_add(pop()?),
+        Instruction::Eq => (pop()? == pop()?) as u64,
+        Instruction::Lt => {
+            let b = pop()?;
+            (pop()? < b) as u64
+        }
+        Instruction::Load => storage.get(&pop()?).copied().unwrap_or(0),
+        Instruction::Store => {
+            let value = pop()?;
+            storage.insert(pop()?, value);
+            return Ok(());
+        }
+        Instruction::Halt => return Ok(()),
+    };
+    stack.push(pushed);
+    Ok(())
+}
+
+pub(super) fn next_state(starting_state: &VmState, t: &VmTransaction) -> VmState {
+    let mut state = starting_state.clone();
+    match t {
+        VmTransaction::Deploy { deployer, code } => {
+            let cost = DEPLOY_GAS_PER_INSTRUCTION.saturating_mul(code.len() as u64);
+            if charge(&mut state, *deployer, cost) {
+                state.contracts.push(Contract {
+                    code: code.clone(),
+                    storage: Storage::new(),
+                });
+            }
+        }
+        VmTransaction::Call {
+            caller,
+            contract,
+            gas_limit,
+        } => {
+            let balance = state.balances.get(caller).copied().unwrap_or(0);
+            let Some(contract) = state.contracts.get_mut(*contract) else {
+                return state;
+            };
+            if balance < *gas_limit {
+                return state;
+            }
+            let execution = execute(&contract.code, &contract.storage, *gas_limit);
+            if let Ok(storage) = execution.result {
+                contract.storage = storage;
+            }
+            charge(&mut state, *caller, execution.gas_used);
+        }
+    }
+    state
+}
+
+/// Take the given amount from the user's balance, if they have that much.
+fn charge(state: &mut VmState, who: User, amount: u64) -> bool {
+    let balance = state.balances.get(&who).copied().unwrap_or(0);
+    let Some(rest) = balance.checked_sub(amount) else {
+        return false;
+    };
+    if amount > 0 {
+        state.balances.insert(who, rest);
+    }
+    true
+}
@@ -0,0 +1,128 @@
+use alloc::collections::BTreeMap;
+
+use super::{
+    GovernanceState, GovernanceTransaction, Parameter, Proposal, ENACTMENT_DELAY, VOTING_PERIOD,
+};
+use crate::c1_state_machine::User;
+
+pub(super) fn passes(proposal: &Proposal, balances: &BTreeMap<User, u64>) -> bool {
+    let (mut ayes, mut nays) = (0u64, 0u64);
+    for (voter, aye) in &proposal.votes {
+        let weight = balances.get(voter).copied().unwrap_or(0);
+        if *aye {
+            ayes = ayes.saturating_add(weight);
+        } else {
+            nays = nays.saturating_add(weight);
+        }
+    }
+    ayes > nays
+}
+
+pub(super) fn voting_power(
+    proposal: &Proposal,
+    balances: &BTreeMap<User, u64>,
+    delegations: &BTreeMap<User, User>,
+) -> BTreeMap<User, u64> {
+    let mut power = BTreeMap::new();
+    for (&holder, &balance) in balances {
+        // Without cycles, no chain is longer than all delegations together.
+        let mut current = holder;
+        for _ in 0..=delegations.len() {
+            if proposal.votes.contains_key(&current) {
+                let total: &mut u64 = power.entry(current).or_default();
+                *total = total.saturating_add(balance);
+                break;
+            }
+            match delegations.get(&current) {
+                Some(&delegate) => current = delegate,
+                None => break,
+            }
+        }
+    }
+    power
+}
+
+/// Whether the chain of delegations from `from` reaches `to`.
+fn delegates_to(delegations: &BTreeMap<User, User>, from: User, to: User) -> bool {
+    let mut current = from;
+    for _ in 0..=delegations.len() {
+        if current == to {
+            return true;
+        }
+        match delegations.get(&current) {
+            Some(&delegate) => current = delegate,
+            None => return false,
+        }
+    }
+    // Only a cycle is longer than all delegations.
+    true
+}
+
+pub(super) fn next_state(
+    starting_state: &GovernanceState,
+    t: &GovernanceTransaction,
+) -> GovernanceState {
+    let mut state = starting_state.clone();
+    let holds_tokens = |who: &User| state.balances.get(who).is_some_and(|balance| *balance > 0);
+    match t {
+        GovernanceTransaction::Propose { proposer, change } => {
+            if holds_tokens(proposer) {
+                let proposal = Proposal {
+                    proposer: *proposer,
+                    change: *change,
+                    voting_ends: state.block + VOTING_PERIOD,
+                    votes: BTreeMap::new(),
+                };
+                state.proposals.insert(state.next_proposal, proposal);
+                state.next_proposal += 1;
+            }
+        }
+        GovernanceTransaction::Vote {
+            voter,
+            proposal,
+            aye,
+        } => {
+            if holds_tokens(voter) {
+                if let Some(proposal) = state.proposals.get_mut(proposal) {
+                    proposal.votes.insert(*voter, *aye);
+                }
+            }
+        }
+        GovernanceTransaction::Delegate { who, to } => {
+            if holds_tokens(who) && holds_tokens(to) && !delegates_to(&state.delegations, *to, *who)
+            {
+                state.delegations.insert(*who, *to);
+            }
+        }
+        GovernanceTransaction::Undelegate { who } => {
+            state.delegations.remove(who);
+        }
+        GovernanceTransaction::NextBlock => {
+            state.block += 1;
+            let block = state.block;
+
+            let (closed, open): (BTreeMap<_, _>, _) = core::mem::take(&mut state.proposals)
+                .into_iter()
+                .partition(|(_, proposal)| proposal.voting_ends <= block);
+            state.proposals = open;
+            for proposal in closed.values() {
+                let power = voting_power(proposal, &state.balances, &state.delegations);
+                if passes(proposal, &power) {
+                    state
+                        .scheduled
+                        .entry(block + ENACTMENT_DELAY)
+                        .or_default()
+                        .push(proposal.change);
+                }
+            }
+
+            for change in state.scheduled.remove(&block).unwrap_or_default() {
+                match change.parameter {
+                    Parameter::BlockReward => state.parameters.block_reward = change.value,
+                    Parameter::TransactionFee => state.parameters.transaction_fee = change.value,
+                }
+            }
+        }
+    }
+    state
+}
@@ -0,0 +1,78 @@
+use super::{
+    NameRecord, RegistryState, RegistryTransaction, BASE_FEE, REGISTRATION_PERIOD, SHORT_NAME,
+};
+use crate::c1_state_machine::User;
+
+pub(super) fn registration_fee(name: &str) -> Option<u64> {
+    let length = name.chars().count();
+    if length == 0 {
+        return None;
+    }
+    let doublings = SHORT_NAME.saturating_sub(length) as u32;
+    Some(BASE_FEE << doublings)
+}
+
+pub(super) fn next_state(starting_state: &RegistryState, t: &RegistryTransaction) -> RegistryState {
+    let mut state = starting_state.clone();
+    let block = state.block;
+    match t {
+        RegistryTransaction::Register { who, name, value } => {
+            let free = state
+                .names
+                .get(name)
+                .is_none_or(|record| record.expires <= block);
+            if free && pay_fee(&mut state, *who, name) {
+                let record = NameRecord {
+                    owner: *who,
+                    value: *value,
+                    expires: block + REGISTRATION_PERIOD,
+                };
+                state.names.insert(name.clone(), record);
+            }
+        }
+        RegistryTransaction::Renew { who, name } => {
+            let owned = state
+                .names
+                .get(name)
+                .is_some_and(|record| record.owner == *who && block < record.expires);
+            if owned && pay_fee(&mut state, *who, name) {
+                if let Some(record) = state.names.get_mut(name) {
+                    record.expires += REGISTRATION_PERIOD;
+                }
+            }
+        }
+        RegistryTransaction::Transfer { from, to, name } => {
+            if let Some(record) = state.names.get_mut(name) {
+                if record.owner == *from && block < record.expires {
+                    record.owner = *to;
+                }
+            }
+        }
+        RegistryTransaction::Expire { name } => {
+            if state
+                .names
+                .get(name)
+                .is_some_and(|record| record.expires <= block)
+            {
+                state.names.remove(name);
+            }
+        }
+        RegistryTransaction::NextBlock => state.block += 1,
+    }
+    state
+}
+
+/// Take the fee for the given name from the user's balance, if the name can be registered and
+/// they can afford it.
+fn pay_fee(state: &mut RegistryState, who: User, name: &str) -> bool {
+    let Some(fee) = registration_fee(name) else {
+        return false;
+    };
+    match state.balances.get_mut(&who) {
+        Some(balance) if *balance >= fee => {
+            *balance -= fee;
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,85 @@
+use alloc::collections::BTreeMap;
+
+use super::{Auction, AuctionState, AuctionTransaction};
+use crate::c1_state_machine::{Token, User};
+
+pub(super) fn next_state(starting_state: &AuctionState, t: &AuctionTransaction) -> AuctionState {
+    let mut state = starting_state.clone();
+    match *t {
+        AuctionTransaction::Create {
+            seller,
+            item,
+            min_bid,
+            duration,
+        } => {
+            let Some(token) = state.nfts.tokens.get(&item).copied() else {
+                return state;
+            };
+            if token.owner != seller {
+                return state;
+            }
+            state.nfts.tokens.remove(&item);
+            let auction = Auction {
+                seller,
+                metadata: token.metadata,
+                min_bid,
+                ends: state.block + duration,
+                highest: None,
+            };
+            state.auctions.insert(item, auction);
+        }
+        AuctionTransaction::Bid {
+            bidder,
+            item,
+            amount,
+        } => {
+            let Some(auction) = state.auctions.get_mut(&item) else {
+                return state;
+            };
+            let beats_highest = auction.highest.is_none_or(|(_, highest)| amount > highest);
+            if bidder == auction.seller
+                || state.block >= auction.ends
+                || amount < auction.min_bid
+                || !beats_highest
+            {
+                return starting_state.clone();
+            }
+            let outbid = auction.highest.replace((bidder, amount));
+            if let Some((previous, refund)) = outbid {
+                credit(&mut state.balances, previous, refund);
+            }
+            match state.balances.get_mut(&bidder) {
+                Some(balance) if *balance >= amount => *balance -= amount,
+                _ => return starting_state.clone(),
+            }
+        }
+        AuctionTransaction::NextBlock => {
+            state.block += 1;
+            let block = state.block;
+            let (ended, running): (BTreeMap<_, _>, _) = core::mem::take(&mut state.auctions)
+                .into_iter()
+                .partition(|(_, auction)| auction.ends <= block);
+            state.auctions = running;
+            for (item, auction) in ended {
+                let owner = match auction.highest {
+                    Some((winner, amount)) => {
+                        credit(&mut state.balances, auction.seller, amount);
+                        winner
+                    }
+                    None => auction.seller,
+                };
+                let token = Token {
+                    owner,
+                    metadata: auction.metadata,
+                };
+                state.nfts.tokens.insert(item, token);
+            }
+        }
+    }
+    state
+}
+
+fn credit(balances: &mut BTreeMap<User, u64>, who: User, amount: u64) {
+    let balance = balances.entry(who).or_insert(0);
+    *balance = balance.saturating_add(amount);
+}
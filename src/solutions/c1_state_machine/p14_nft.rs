@@ -0,0 +1,32 @@
+use super::{NftState, NftTransaction, Token};
+
+pub(super) fn next_state(starting_state: &NftState, t: &NftTransaction) -> NftState {
+    let mut state = starting_state.clone();
+    match *t {
+        NftTransaction::Mint { minter, metadata } => {
+            let token = Token {
+                owner: minter,
+                metadata,
+            };
+            state.tokens.insert(state.next_id, token);
+            state.next_id += 1;
+        }
+        NftTransaction::Transfer { from, to, id } => {
+            if let Some(token) = state.tokens.get_mut(&id) {
+                if token.owner == from {
+                    token.owner = to;
+                }
+            }
+        }
+        NftTransaction::Burn { owner, id } => {
+            if state
+                .tokens
+                .get(&id)
+                .is_some_and(|token| token.owner == owner)
+            {
+                state.tokens.remove(&id);
+            }
+        }
+    }
+    state
+}
@@ -0,0 +1,127 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::{Escrow, EscrowState, EscrowTransaction};
+use crate::c1_state_machine::{Token, User};
+
+pub(super) fn next_state(starting_state: &EscrowState, t: &EscrowTransaction) -> EscrowState {
+    let mut state = starting_state.clone();
+    match *t {
+        EscrowTransaction::Open {
+            buyer,
+            seller,
+            arbiter,
+            item,
+            amount,
+            duration,
+        } => {
+            if buyer == seller || buyer == arbiter || seller == arbiter {
+                return state;
+            }
+            if state.nfts.tokens.get(&item).map(|token| token.owner) != Some(seller) {
+                return state;
+            }
+            match state.balances.get_mut(&buyer) {
+                Some(balance) if *balance >= amount => *balance -= amount,
+                _ => return state,
+            }
+            let escrow = Escrow {
+                buyer,
+                seller,
+                arbiter,
+                item,
+                amount,
+                deadline: state.block + duration,
+                delivered: None,
+                releases: BTreeMap::new(),
+            };
+            state.escrows.insert(state.next_id, escrow);
+            state.next_id += 1;
+        }
+        EscrowTransaction::MarkDelivered { seller, id } => {
+            let block = state.block;
+            let Some(escrow) = state.escrows.get_mut(&id) else {
+                return state;
+            };
+            if escrow.seller != seller || block >= escrow.deadline || escrow.delivered.is_some() {
+                return state;
+            }
+            match state.nfts.tokens.get(&escrow.item) {
+                Some(token) if token.owner == seller => {
+                    escrow.delivered = Some(token.metadata);
+                    state.nfts.tokens.remove(&escrow.item);
+                }
+                _ => return state,
+            }
+        }
+        EscrowTransaction::Release { who, id, to_seller } => {
+            let Some(escrow) = state.escrows.get_mut(&id) else {
+                return state;
+            };
+            if (who != escrow.buyer && who != escrow.seller) || to_seller > escrow.amount {
+                return state;
+            }
+            escrow.releases.insert(who, to_seller);
+            let agreed = escrow.releases.get(&escrow.buyer) == Some(&to_seller)
+                && escrow.releases.get(&escrow.seller) == Some(&to_seller);
+            if agreed {
+                pay_out(&mut state, id, to_seller);
+            }
+        }
+        EscrowTransaction::Arbitrate {
+            arbiter,
+            id,
+            to_seller,
+        } => {
+            let valid = state.escrows.get(&id).is_some_and(|escrow| {
+                escrow.arbiter == arbiter
+                    && escrow.delivered.is_some()
+                    && to_seller <= escrow.amount
+            });
+            if valid {
+                pay_out(&mut state, id, to_seller);
+            }
+        }
+        EscrowTransaction::NextBlock => {
+            state.block += 1;
+            let block = state.block;
+            let expired: Vec<u64> = state
+                .escrows
+                .iter()
+                .filter(|(_, escrow)| escrow.delivered.is_none() && escrow.deadline <= block)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in expired {
+                pay_out(&mut state, id, 0);
+            }
+        }
+    }
+    state
+}
+
+/// Close the escrow, paying `to_seller` to the seller and the rest back to the buyer. A delivered
+/// item goes to the buyer, unless they get all of their money back.
+fn pay_out(state: &mut EscrowState, id: u64, to_seller: u64) {
+    let Some(escrow) = state.escrows.remove(&id) else {
+        return;
+    };
+    credit(&mut state.balances, escrow.seller, to_seller);
+    credit(&mut state.balances, escrow.buyer, escrow.amount - to_seller);
+    if let Some(metadata) = escrow.delivered {
+        let owner = if to_seller > 0 {
+            escrow.buyer
+        } else {
+            escrow.seller
+        };
+        state
+            .nfts
+            .tokens
+            .insert(escrow.item, Token { owner, metadata });
+    }
+}
+
+fn credit(balances: &mut BTreeMap<User, u64>, who: User, amount: u64) {
+    if amount > 0 {
+        *balances.entry(who).or_insert(0) += amount;
+    }
+}
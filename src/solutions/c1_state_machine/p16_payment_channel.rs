@@ -0,0 +1,101 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::{Channel, ChannelState, ChannelTransaction, Closing, SignedUpdate, CHALLENGE_PERIOD};
+use crate::c1_state_machine::User;
+
+pub(super) fn is_valid(update: &SignedUpdate, channel: &Channel) -> bool {
+    let hash = update.update.hash();
+    let split = update
+        .update
+        .opener_balance
+        .checked_add(update.update.counterparty_balance);
+    split == Some(channel.deposit)
+        && update.by_opener.verify(channel.opener, hash)
+        && update.by_counterparty.verify(channel.counterparty, hash)
+}
+
+pub(super) fn next_state(starting_state: &ChannelState, t: &ChannelTransaction) -> ChannelState {
+    let mut state = starting_state.clone();
+    match *t {
+        ChannelTransaction::Open {
+            opener,
+            counterparty,
+            deposit,
+        } => {
+            if opener == counterparty {
+                return state;
+            }
+            match state.balances.get_mut(&opener) {
+                Some(balance) if *balance >= deposit => *balance -= deposit,
+                _ => return state,
+            }
+            let channel = Channel {
+                opener,
+                counterparty,
+                deposit,
+                closing: None,
+            };
+            state.channels.insert(state.next_id, channel);
+            state.next_id += 1;
+        }
+        ChannelTransaction::Close { who, update } => {
+            let ends = state.block + CHALLENGE_PERIOD;
+            let Some(channel) = state.channels.get_mut(&update.update.channel) else {
+                return state;
+            };
+            if !is_party(channel, who) || channel.closing.is_some() || !is_valid(&update, channel) {
+                return state;
+            }
+            channel.closing = Some(Closing {
+                update: update.update,
+                ends,
+            });
+        }
+        ChannelTransaction::Challenge { who, update } => {
+            let block = state.block;
+            let Some(channel) = state.channels.get_mut(&update.update.channel) else {
+                return state;
+            };
+            if !is_party(channel, who) || !is_valid(&update, channel) {
+                return state;
+            }
+            if let Some(closing) = &mut channel.closing {
+                if block < closing.ends && update.update.nonce > closing.update.nonce {
+                    closing.update = update.update;
+                }
+            }
+        }
+        ChannelTransaction::NextBlock => {
+            state.block += 1;
+            let block = state.block;
+            let closed: Vec<u64> = state
+                .channels
+                .iter()
+                .filter(|(_, channel)| channel.closing.is_some_and(|closing| closing.ends <= block))
+                .map(|(id, _)| *id)
+                .collect();
+            for id in closed {
+                let channel = state.channels.remove(&id).expect("the channel is closing");
+                let update = channel.closing.expect("the channel is closing").update;
+                credit(&mut state.balances, channel.opener, update.opener_balance);
+                credit(
+                    &mut state.balances,
+                    channel.counterparty,
+                    update.counterparty_balance,
+                );
+            }
+        }
+    }
+    state
+}
+
+fn is_party(channel: &Channel, who: User) -> bool {
+    who == channel.opener || who == channel.counterparty
+}
+
+fn credit(balances: &mut BTreeMap<User, u64>, who: User, amount: u64) {
+    if amount > 0 {
+        *balances.entry(who).or_insert(0) += amount;
+    }
+}
@@ -0,0 +1,60 @@
+use super::{BridgeState, BridgeTransaction};
+use crate::hashing::{header_hash, H256};
+use crate::merkle::verify_proof;
+
+pub(super) fn is_final(state: &BridgeState, hash: H256) -> bool {
+    is_ancestor(state, hash, state.finalized)
+}
+
+/// Whether the header with the given hash is the given descendant, or one of its ancestors.
+fn is_ancestor(state: &BridgeState, hash: H256, descendant: H256) -> bool {
+    let mut current = descendant;
+    loop {
+        if current == hash {
+            return true;
+        }
+        match state.headers.get(&current) {
+            Some(header) if header.height() > 0 => current = header.parent(),
+            _ => return false,
+        }
+    }
+}
+
+pub(super) fn next_state(starting_state: &BridgeState, t: &BridgeTransaction) -> BridgeState {
+    let mut state = starting_state.clone();
+    match t {
+        BridgeTransaction::ImportHeader(header) => {
+            let extends_parent = state
+                .headers
+                .get(&header.parent())
+                .is_some_and(|parent| header.height() == parent.height() + 1);
+            if extends_parent {
+                state.headers.insert(header_hash(header), header.clone());
+            }
+        }
+        BridgeTransaction::Finalize { hash, seal } => {
+            let newer = *hash != state.finalized && is_ancestor(&state, state.finalized, *hash);
+            if newer && seal.verify(&state.committee, state.threshold, hash.as_bytes()) {
+                state.finalized = *hash;
+            }
+        }
+        BridgeTransaction::Mint {
+            block,
+            event,
+            proof,
+        } => {
+            let Some(header) = state.headers.get(block) else {
+                return state;
+            };
+            if !is_final(&state, *block)
+                || state.claimed.contains(&event.id)
+                || !verify_proof(header.extrinsics_root(), event, proof)
+            {
+                return state;
+            }
+            state.claimed.insert(event.id);
+            *state.wrapped.entry(event.recipient).or_insert(0) += event.amount;
+        }
+    }
+    state
+}
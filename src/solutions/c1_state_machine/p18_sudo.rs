@@ -0,0 +1,50 @@
+use super::{PrivilegedCall, SudoState, SudoTransaction};
+use crate::c1_state_machine::solution::{credit, debit};
+
+pub(super) fn next_state(starting_state: &SudoState, t: &SudoTransaction) -> SudoState {
+    let mut state = starting_state.clone();
+    match t {
+        SudoTransaction::Transfer {
+            sender,
+            receiver,
+            amount,
+        } => {
+            let Some(total) = amount.checked_add(state.transfer_fee) else {
+                return state;
+            };
+            if state.paused
+                || !debit(&mut state.balances, *sender, total)
+                || !credit(&mut state.balances, *receiver, *amount)
+            {
+                return starting_state.clone();
+            }
+        }
+        SudoTransaction::Sudo { who, call } => {
+            if state.key != Some(*who) {
+                return state;
+            }
+            match *call {
+                PrivilegedCall::SetTransferFee(fee) => state.transfer_fee = fee,
+                PrivilegedCall::SetPaused(paused) => state.paused = paused,
+                PrivilegedCall::ForceTransfer { from, to, amount } => {
+                    if !debit(&mut state.balances, from, amount)
+                        || !credit(&mut state.balances, to, amount)
+                    {
+                        return starting_state.clone();
+                    }
+                }
+            }
+        }
+        SudoTransaction::SetKey { who, new } => {
+            if state.key == Some(*who) {
+                state.key = Some(*new);
+            }
+        }
+        SudoTransaction::RemoveKey { who } => {
+            if state.key == Some(*who) {
+                state.key = None;
+            }
+        }
+    }
+    state
+}
@@ -0,0 +1,23 @@
+use super::{FaucetState, FaucetTransaction};
+
+pub(super) fn next_state(starting_state: &FaucetState, t: &FaucetTransaction) -> FaucetState {
+    let mut state = starting_state.clone();
+    match t {
+        FaucetTransaction::NewBlock => state.block += 1,
+        FaucetTransaction::Claim { who } => {
+            let cooled_down = state
+                .last_claims
+                .get(who)
+                .is_none_or(|&last| state.block - last >= state.cooldown);
+            let balance = state.balances.get(who).copied().unwrap_or(0);
+            let Some(balance) = balance.checked_add(state.drip).filter(|_| cooled_down) else {
+                return state;
+            };
+            state.last_claims.insert(*who, state.block);
+            if balance > 0 {
+                state.balances.insert(*who, balance);
+            }
+        }
+    }
+    state
+}
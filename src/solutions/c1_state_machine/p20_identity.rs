@@ -0,0 +1,117 @@
+use alloc::vec::Vec;
+
+use super::{Identity, IdentityState, IdentityTransaction, RegistrarMotion, IDENTITY_DEPOSIT};
+use crate::c1_state_machine::solution::{credit, debit};
+
+pub(super) fn next_state(starting_state: &IdentityState, t: &IdentityTransaction) -> IdentityState {
+    let mut state = starting_state.clone();
+    match t {
+        IdentityTransaction::SetIdentity { who, info } => match state.identities.get_mut(who) {
+            Some(identity) => {
+                if identity.info != *info {
+                    identity.info = *info;
+                    identity.judgements.clear();
+                }
+            }
+            None => {
+                if !debit(&mut state.balances, *who, IDENTITY_DEPOSIT) {
+                    return state;
+                }
+                let identity = Identity {
+                    info: *info,
+                    ..Identity::default()
+                };
+                state.identities.insert(*who, identity);
+            }
+        },
+        IdentityTransaction::ClearIdentity { who } => {
+            let Some(identity) = state.identities.remove(who) else {
+                return state;
+            };
+            let held: u64 = identity.requests.values().sum();
+            if !credit(&mut state.balances, *who, IDENTITY_DEPOSIT + held) {
+                return starting_state.clone();
+            }
+        }
+        IdentityTransaction::RequestJudgement {
+            who,
+            registrar,
+            max_fee,
+        } => {
+            let Some(&fee) = state.registrars.get(registrar) else {
+                return state;
+            };
+            let asked = match state.identities.get(who) {
+                Some(identity) => identity.requests.contains_key(registrar),
+                None => return state,
+            };
+            if asked || fee > *max_fee || !debit(&mut state.balances, *who, fee) {
+                return starting_state.clone();
+            }
+            if let Some(identity) = state.identities.get_mut(who) {
+                identity.requests.insert(*registrar, fee);
+            }
+        }
+        IdentityTransaction::ProvideJudgement {
+            registrar,
+            target,
+            info,
+            judgement,
+        } => {
+            let Some(identity) = state.identities.get_mut(target) else {
+                return state;
+            };
+            if identity.info != *info || !state.registrars.contains_key(registrar) {
+                return starting_state.clone();
+            }
+            let Some(fee) = identity.requests.remove(registrar) else {
+                return starting_state.clone();
+            };
+            identity.judgements.insert(*registrar, *judgement);
+            if !credit(&mut state.balances, *registrar, fee) {
+                return starting_state.clone();
+            }
+        }
+        IdentityTransaction::RevokeJudgement { registrar, target } => {
+            if let Some(identity) = state.identities.get_mut(target) {
+                identity.judgements.remove(registrar);
+            }
+        }
+        IdentityTransaction::Approve { councillor, motion } => {
+            if !state.council.contains(councillor) {
+                return state;
+            }
+            let approvals = state.motions.entry(*motion).or_default();
+            approvals.insert(*councillor);
+            if approvals.len() * 2 > state.council.len() {
+                state.motions.remove(motion);
+                if !enact(&mut state, *motion) {
+                    return starting_state.clone();
+                }
+            }
+        }
+    }
+    state
+}
+
+/// Carry out a motion. Returns false if a refund would overflow a balance.
+fn enact(state: &mut IdentityState, motion: RegistrarMotion) -> bool {
+    match motion {
+        RegistrarMotion::Add { registrar, fee } => {
+            state.registrars.insert(registrar, fee);
+            true
+        }
+        RegistrarMotion::Remove { registrar } => {
+            state.registrars.remove(&registrar);
+            let mut refunds = Vec::new();
+            for (who, identity) in state.identities.iter_mut() {
+                if let Some(fee) = identity.requests.remove(&registrar) {
+                    refunds.push((*who, fee));
+                }
+            }
+            refunds
+                .into_iter()
+                .all(|(who, fee)| credit(&mut state.balances, who, fee))
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use super::{AssetId, DexState, DexTransaction, SWAP_FEE_PER_MILLE};
+use crate::c1_state_machine::solution::{credit, debit};
+use crate::c1_state_machine::User;
+
+pub(super) fn swap_output(reserve_in: u64, reserve_out: u64, amount_in: u64) -> u64 {
+    let amount_in = u128::from(amount_in) * u128::from(1_000 - SWAP_FEE_PER_MILLE);
+    let numerator = u128::from(reserve_out) * amount_in;
+    let denominator = u128::from(reserve_in) * 1_000 + amount_in;
+    if denominator == 0 {
+        return 0;
+    }
+    // The output is less than the reserve, so it fits.
+    (numerator / denominator) as u64
+}
+
+pub(super) fn next_state(starting_state: &DexState, t: &DexTransaction) -> DexState {
+    let mut state = starting_state.clone();
+    let valid = match *t {
+        DexTransaction::Transfer {
+            sender,
+            receiver,
+            asset,
+            amount,
+        } => {
+            debit(&mut state.balances, (sender, asset), amount)
+                && credit(&mut state.balances, (receiver, asset), amount)
+        }
+        DexTransaction::AddLiquidity {
+            who,
+            assets,
+            amounts,
+        } => add_liquidity(&mut state, who, assets, amounts),
+        DexTransaction::RemoveLiquidity {
+            who,
+            assets,
+            shares,
+        } => remove_liquidity(&mut state, who, assets, shares),
+        DexTransaction::Swap {
+            who,
+            asset_in,
+            asset_out,
+            amount_in,
+            min_out,
+        } => swap(&mut state, who, asset_in, asset_out, amount_in, min_out),
+    };
+    if valid {
+        state
+    } else {
+        starting_state.clone()
+    }
+}
+
+/// The key of the pool of the two assets, and whether they were given in reverse order. None if
+/// the two assets are the same.
+fn pool_key(assets: (AssetId, AssetId)) -> Option<((AssetId, AssetId), bool)> {
+    match assets.0.cmp(&assets.1) {
+        core::cmp::Ordering::Less => Some((assets, false)),
+        core::cmp::Ordering::Greater => Some(((assets.1, assets.0), true)),
+        core::cmp::Ordering::Equal => None,
+    }
+}
+
+fn swapped<T>((a, b): (T, T), reversed: bool) -> (T, T) {
+    if reversed {
+        (b, a)
+    } else {
+        (a, b)
+    }
+}
+
+/// `a * b / c`, rounded down, or None if it does not fit.
+fn mul_div(a: u64, b: u64, c: u64) -> Option<u64> {
+    if c == 0 {
+        return None;
+    }
+    (u128::from(a) * u128::from(b) / u128::from(c))
+        .try_into()
+        .ok()
+}
+
+/// `a * b / c`, rounded up, or None if it does not fit.
+fn mul_div_up(a: u64, b: u64, c: u64) -> Option<u64> {
+    if c == 0 {
+        return None;
+    }
+    (u128::from(a) * u128::from(b))
+        .div_ceil(u128::from(c))
+        .try_into()
+        .ok()
+}
+
+fn add_liquidity(
+    state: &mut DexState,
+    who: User,
+    assets: (AssetId, AssetId),
+    amounts: (u64, u64),
+) -> bool {
+    let Some((key, reversed)) = pool_key(assets) else {
+        return false;
+    };
+    let (amount_a, amount_b) = swapped(amounts, reversed);
+    let mut pool = state.pools.get(&key).cloned().unwrap_or_default();
+    let (reserve_a, reserve_b) = pool.reserves;
+
+    let (shares, deposit_a, deposit_b) = if pool.total_shares == 0 {
+        let shares = (u128::from(amount_a) * u128::from(amount_b)).isqrt();
+        let Ok(shares) = u64::try_from(shares) else {
+            return false;
+        };
+        (shares, amount_a, amount_b)
+    } else {
+        let total = pool.total_shares;
+        let (Some(by_a), Some(by_b)) = (
+            mul_div(amount_a, total, reserve_a),
+            mul_div(amount_b, total, reserve_b),
+        ) else {
+            return false;
+        };
+        let shares = by_a.min(by_b);
+        let (Some(deposit_a), Some(deposit_b)) = (
+            mul_div_up(shares, reserve_a, total),
+            mul_div_up(shares, reserve_b, total),
+        ) else {
+            return false;
+        };
+        (shares, deposit_a, deposit_b)
+    };
+    if shares == 0 {
+        return false;
+    }
+
+    let (Some(new_a), Some(new_b), Some(total)) = (
+        reserve_a.checked_add(deposit_a),
+        reserve_b.checked_add(deposit_b),
+        pool.total_shares.checked_add(shares),
+    ) else {
+        return false;
+    };
+    if !debit(&mut state.balances, (who, key.0), deposit_a)
+        || !debit(&mut state.balances, (who, key.1), deposit_b)
+    {
+        return false;
+    }
+    pool.reserves = (new_a, new_b);
+    pool.total_shares = total;
+    *pool.shares.entry(who).or_default() += shares;
+    state.pools.insert(key, pool);
+    true
+}
+
+fn remove_liquidity(
+    state: &mut DexState,
+    who: User,
+    assets: (AssetId, AssetId),
+    shares: u64,
+) -> bool {
+    let Some((key, _)) = pool_key(assets) else {
+        return false;
+    };
+    let Some(mut pool) = state.pools.get(&key).cloned() else {
+        return false;
+    };
+    let owned = pool.shares.get(&who).copied().unwrap_or(0);
+    if shares == 0 || shares > owned {
+        return false;
+    }
+    let (reserve_a, reserve_b) = pool.reserves;
+    let (Some(out_a), Some(out_b)) = (
+        mul_div(shares, reserve_a, pool.total_shares),
+        mul_div(shares, reserve_b, pool.total_shares),
+    ) else {
+        return false;
+    };
+    if out_a == 0 && out_b == 0 {
+        return false;
+    }
+
+    pool.reserves = (reserve_a - out_a, reserve_b - out_b);
+    pool.total_shares -= shares;
+    if owned == shares {
+        pool.shares.remove(&who);
+    } else {
+        pool.shares.insert(who, owned - shares);
+    }
+    if pool.total_shares == 0 {
+        state.pools.remove(&key);
+    } else {
+        state.pools.insert(key, pool);
+    }
+    credit(&mut state.balances, (who, key.0), out_a)
+        && credit(&mut state.balances, (who, key.1), out_b)
+}
+
+fn swap(
+    state: &mut DexState,
+    who: User,
+    asset_in: AssetId,
+    asset_out: AssetId,
+    amount_in: u64,
+    min_out: u64,
+) -> bool {
+    let Some((key, reversed)) = pool_key((asset_in, asset_out)) else {
+        return false;
+    };
+    let Some(pool) = state.pools.get_mut(&key) else {
+        return false;
+    };
+    let (reserve_in, reserve_out) = swapped(pool.reserves, reversed);
+    let amount_out = swap_output(reserve_in, reserve_out, amount_in);
+    let Some(new_in) = reserve_in.checked_add(amount_in) else {
+        return false;
+    };
+    if amount_out == 0 || amount_out < min_out {
+        return false;
+    }
+    pool.reserves = swapped((new_in, reserve_out - amount_out), reversed);
+    debit(&mut state.balances, (who, asset_in), amount_in)
+        && credit(&mut state.balances, (who, asset_out), amount_out)
+}
@@ -0,0 +1,60 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::{pick, LotteryState, LotteryTransaction, TICKET_PRICE};
+use crate::c1_state_machine::User;
+use crate::commit_reveal::verify_reveal;
+use crate::hashing::hash_of;
+
+pub(super) fn draw(reveals: &BTreeMap<User, u64>) -> Option<User> {
+    let players: Vec<User> = reveals.keys().copied().collect();
+    pick(hash_of("lottery", reveals), &players)
+}
+
+pub(super) fn next_state(starting_state: &LotteryState, t: &LotteryTransaction) -> LotteryState {
+    let mut state = starting_state.clone();
+    match *t {
+        LotteryTransaction::BuyTicket { player, commitment } => {
+            let balance = state.balances.get(&player).copied().unwrap_or(0);
+            if state.block >= state.commit_ends
+                || state.tickets.contains_key(&player)
+                || balance < TICKET_PRICE
+            {
+                return state;
+            }
+            if balance == TICKET_PRICE {
+                state.balances.remove(&player);
+            } else {
+                state.balances.insert(player, balance - TICKET_PRICE);
+            }
+            state.pot += TICKET_PRICE;
+            state.tickets.insert(player, commitment);
+        }
+        LotteryTransaction::Reveal {
+            player,
+            number,
+            ref salt,
+        } => {
+            let in_phase = state.commit_ends <= state.block && state.block < state.reveal_ends;
+            let matches = state
+                .tickets
+                .get(&player)
+                .is_some_and(|&commitment| verify_reveal(commitment, &number, salt));
+            if in_phase && matches {
+                state.reveals.insert(player, number);
+            }
+        }
+        LotteryTransaction::NewBlock => {
+            state.block += 1;
+            if state.block == state.reveal_ends {
+                state.winner = draw(&state.reveals);
+                if let Some(winner) = state.winner {
+                    let balance = state.balances.entry(winner).or_default();
+                    *balance = balance.saturating_add(state.pot);
+                }
+                state.pot = 0;
+            }
+        }
+    }
+    state
+}
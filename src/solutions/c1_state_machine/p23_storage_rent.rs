@@ -0,0 +1,104 @@
+use alloc::vec::Vec;
+
+use super::{Entry, RentState, RentTransaction, Tombstone, ENTRY_OVERHEAD, RENT_PER_BYTE};
+use crate::c1_state_machine::solution::{credit, debit};
+use crate::hashing::hash;
+
+pub(super) fn rent(value: &[u8]) -> u64 {
+    (value.len() as u64)
+        .saturating_add(ENTRY_OVERHEAD)
+        .saturating_mul(RENT_PER_BYTE)
+}
+
+pub(super) fn next_state(starting_state: &RentState, t: &RentTransaction) -> RentState {
+    let mut state = starting_state.clone();
+    let valid = match t {
+        RentTransaction::Store {
+            who,
+            key,
+            value,
+            deposit,
+        } => {
+            let entry = (*who, *key);
+            let free =
+                !state.entries.contains_key(&entry) && !state.tombstones.contains_key(&entry);
+            if free && *deposit >= rent(value) && debit(&mut state.balances, *who, *deposit) {
+                let entry_value = Entry {
+                    value: value.clone(),
+                    deposit: *deposit,
+                };
+                state.entries.insert(entry, entry_value);
+                true
+            } else {
+                false
+            }
+        }
+        RentTransaction::TopUp { who, entry, amount } => {
+            let Some(deposit) = state
+                .entries
+                .get(entry)
+                .and_then(|entry| entry.deposit.checked_add(*amount))
+            else {
+                return state;
+            };
+            if let Some(entry) = state.entries.get_mut(entry) {
+                entry.deposit = deposit;
+            }
+            debit(&mut state.balances, *who, *amount)
+        }
+        RentTransaction::Remove { who, key } => match state.entries.remove(&(*who, *key)) {
+            Some(entry) => credit(&mut state.balances, *who, entry.deposit),
+            None => false,
+        },
+        RentTransaction::Revive {
+            who,
+            entry,
+            value,
+            deposit,
+        } => {
+            let Some(tombstone) = state.tombstones.remove(entry) else {
+                return state;
+            };
+            let Some(total) = tombstone.deposit.checked_add(*deposit) else {
+                return starting_state.clone();
+            };
+            let revived = Entry {
+                value: value.clone(),
+                deposit: total,
+            };
+            state.entries.insert(*entry, revived);
+            tombstone.hash == hash(&value[..])
+                && total >= rent(value)
+                && debit(&mut state.balances, *who, *deposit)
+        }
+        RentTransaction::Reap { who, entry } => match state.tombstones.remove(entry) {
+            Some(tombstone) => credit(&mut state.balances, *who, tombstone.deposit),
+            None => false,
+        },
+        RentTransaction::NewBlock => {
+            state.block += 1;
+            let mut expired = Vec::new();
+            for (key, entry) in state.entries.iter_mut() {
+                match entry.deposit.checked_sub(rent(&entry.value)) {
+                    Some(deposit) => entry.deposit = deposit,
+                    None => expired.push(*key),
+                }
+            }
+            for key in expired {
+                if let Some(entry) = state.entries.remove(&key) {
+                    let tombstone = Tombstone {
+                        hash: hash(&entry.value[..]),
+                        deposit: entry.deposit,
+                    };
+                    state.tombstones.insert(key, tombstone);
+                }
+            }
+            true
+        }
+    };
+    if valid {
+        state
+    } else {
+        starting_state.clone()
+    }
+}
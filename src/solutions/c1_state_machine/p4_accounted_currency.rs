@@ -0,0 +1,39 @@
+use super::{AccountingTransaction, Balances, User};
+
+pub(super) fn next_state(starting_state: &Balances, t: &AccountingTransaction) -> Balances {
+    let mut balances = starting_state.clone();
+    match *t {
+        AccountingTransaction::Mint { minter, amount } => {
+            if amount > 0 {
+                *balances.entry(minter).or_default() += amount;
+            }
+        }
+        AccountingTransaction::Burn { burner, amount } => {
+            if let Some(balance) = balances.get_mut(&burner) {
+                *balance = balance.saturating_sub(amount);
+                remove_if_empty(&mut balances, burner);
+            }
+        }
+        AccountingTransaction::Transfer {
+            sender,
+            receiver,
+            amount,
+        } => {
+            let balance = starting_state.get(&sender).copied().unwrap_or(0);
+            if sender == receiver || amount == 0 || balance < amount {
+                return balances;
+            }
+            balances.insert(sender, balance - amount);
+            *balances.entry(receiver).or_default() += amount;
+            remove_if_empty(&mut balances, sender);
+        }
+    }
+    balances
+}
+
+/// Uphold the existential deposit.
+fn remove_if_empty(balances: &mut Balances, who: User) {
+    if balances.get(&who) == Some(&0) {
+        balances.remove(&who);
+    }
+}
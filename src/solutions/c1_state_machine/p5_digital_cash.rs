@@ -0,0 +1,72 @@
+use crate::collections::HashSet;
+
+use super::{Bill, CashTransaction, State};
+use crate::c4_client::keystore::Signature;
+
+pub(super) fn next_state(starting_state: &State, t: &CashTransaction) -> State {
+    match t {
+        CashTransaction::Mint { minter, amount } => {
+            let mut state = starting_state.clone();
+            state.add_bill(Bill {
+                owner: *minter,
+                amount: *amount,
+                serial: state.next_serial(),
+            });
+            state
+        }
+        CashTransaction::Transfer {
+            spends,
+            receives,
+            signatures,
+        } => transfer(starting_state, spends, receives, signatures)
+            .unwrap_or_else(|| starting_state.clone()),
+    }
+}
+
+/// The state after the transfer, or `None` if it is invalid.
+fn transfer(
+    starting_state: &State,
+    spends: &[Bill],
+    receives: &[Bill],
+    signatures: &[Signature],
+) -> Option<State> {
+    if spends.is_empty() {
+        return None;
+    }
+    // Every spend must be signed for by the owner of the bill.
+    let hash = CashTransaction::transfer_hash(spends, receives);
+    if signatures.len() != spends.len()
+        || !spends
+            .iter()
+            .zip(signatures)
+            .all(|(bill, signature)| signature.verify(bill.owner, hash))
+    {
+        return None;
+    }
+    let unique: HashSet<&Bill> = spends.iter().collect();
+    if unique.len() != spends.len() || !spends.iter().all(|b| starting_state.bills.contains(b)) {
+        return None;
+    }
+    let spent = spends
+        .iter()
+        .try_fold(0u64, |sum, b| sum.checked_add(b.amount))?;
+    let received = receives
+        .iter()
+        .try_fold(0u64, |sum, b| sum.checked_add(b.amount))?;
+    if received > spent {
+        return None;
+    }
+
+    let mut state = starting_state.clone();
+    for bill in spends {
+        state.bills.remove(bill);
+    }
+    for bill in receives {
+        // New bills must be worth something, and get the next serial numbers in order.
+        if bill.amount == 0 || bill.serial != state.next_serial() {
+            return None;
+        }
+        state.add_bill(bill.clone());
+    }
+    Some(state)
+}
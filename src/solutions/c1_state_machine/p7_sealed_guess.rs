@@ -0,0 +1,62 @@
+use super::{GameAction, GameState};
+use crate::commit_reveal::verify_reveal;
+
+pub(super) fn next_state(starting_state: &GameState, t: &GameAction) -> GameState {
+    let mut state = starting_state.clone();
+    match (&mut state, t) {
+        (GameState::Committing { commitments, .. }, GameAction::Commit { player, commitment }) => {
+            commitments.entry(*player).or_insert(*commitment);
+        }
+        (
+            GameState::Committing {
+                secret,
+                commitments,
+            },
+            GameAction::CloseCommits,
+        ) => {
+            return GameState::Revealing {
+                secret: *secret,
+                commitments: core::mem::take(commitments),
+                guesses: Default::default(),
+            };
+        }
+        (
+            GameState::Revealing {
+                commitments,
+                guesses,
+                ..
+            },
+            GameAction::Reveal {
+                player,
+                guess,
+                salt,
+            },
+        ) => {
+            let matches = commitments
+                .get(player)
+                .is_some_and(|commitment| verify_reveal(*commitment, guess, salt));
+            if matches {
+                guesses.insert(*player, *guess);
+            }
+        }
+        (
+            GameState::Revealing {
+                secret: commitment,
+                guesses,
+                ..
+            },
+            GameAction::RevealSecret { secret, salt },
+        ) if verify_reveal(*commitment, secret, salt) => {
+            let winner = guesses
+                .iter()
+                .min_by_key(|(_, guess)| (guess.abs_diff(*secret), **guess))
+                .map(|(player, _)| *player);
+            return GameState::Finished {
+                secret: *secret,
+                winner,
+            };
+        }
+        _ => {}
+    }
+    state
+}
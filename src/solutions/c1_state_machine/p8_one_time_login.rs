@@ -0,0 +1,36 @@
+use alloc::collections::BTreeMap;
+
+use super::{verify_preimage, LoginAccount, LoginAction};
+use crate::c1_state_machine::User;
+use crate::hashing::H256;
+
+pub(super) fn next_state(
+    starting_state: &BTreeMap<User, LoginAccount>,
+    t: &LoginAction,
+) -> BTreeMap<User, LoginAccount> {
+    let mut state = starting_state.clone();
+    match *t {
+        LoginAction::Register { user, anchor } => {
+            state
+                .entry(user)
+                .or_insert(LoginAccount { anchor, logins: 0 });
+        }
+        LoginAction::Login { user, password } => login(&mut state, user, password, password),
+        LoginAction::Rotate {
+            user,
+            password,
+            new_anchor,
+        } => login(&mut state, user, password, new_anchor),
+    }
+    state
+}
+
+/// Log the user in if the password is right, and move their anchor to the given one.
+fn login(state: &mut BTreeMap<User, LoginAccount>, user: User, password: H256, anchor: H256) {
+    if let Some(account) = state.get_mut(&user) {
+        if verify_preimage(account.anchor, password) {
+            account.anchor = anchor;
+            account.logins += 1;
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use alloc::collections::BTreeSet;
+
+use super::CashTransaction;
+use crate::crypto::pedersen::{commit, Blinding, Commitment};
+
+pub(super) fn is_balanced(inputs: &[Commitment], outputs: &[Commitment], excess: Blinding) -> bool {
+    let inputs: Commitment = inputs.iter().copied().sum();
+    let outputs: Commitment = outputs.iter().copied().sum();
+    inputs - outputs == Commitment::excess(excess)
+}
+
+pub(super) fn next_state(
+    starting_state: &BTreeSet<Commitment>,
+    t: &CashTransaction,
+) -> BTreeSet<Commitment> {
+    let mut state = starting_state.clone();
+    match t {
+        CashTransaction::Mint { amount, blinding } => {
+            state.insert(commit(*amount, *blinding));
+        }
+        CashTransaction::Transfer {
+            inputs,
+            outputs,
+            excess,
+        } => {
+            let valid = !inputs.is_empty()
+                && !outputs.is_empty()
+                && inputs.iter().all(|input| state.remove(input))
+                && outputs
+                    .iter()
+                    .all(|output| !starting_state.contains(output))
+                && outputs.iter().collect::<BTreeSet<_>>().len() == outputs.len()
+                && is_balanced(inputs, outputs, *excess);
+            if !valid {
+                return starting_state.clone();
+            }
+            state.extend(outputs.iter().copied());
+        }
+    }
+    state
+}
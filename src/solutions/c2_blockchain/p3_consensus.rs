@@ -0,0 +1,70 @@
+use super::{Header, FORK_HEIGHT, THRESHOLD};
+use crate::difficulty::meets_threshold;
+use crate::hashing::{hash, H256};
+
+pub(super) fn genesis() -> Header {
+    Header {
+        parent: H256::zero(),
+        height: 0,
+        extrinsic: 0,
+        state: 0,
+        consensus_digest: 0,
+    }
+}
+
+pub(super) fn child(parent: &Header, extrinsic: u64) -> Header {
+    let mut header = Header {
+        parent: hash(parent),
+        height: parent.height + 1,
+        extrinsic,
+        state: parent.state + extrinsic,
+        consensus_digest: 0,
+    };
+    while !meets_threshold(hash(&header), THRESHOLD) {
+        header.consensus_digest += 1;
+    }
+    header
+}
+
+pub(super) fn verify_sub_chain(start: &Header, chain: &[Header]) -> bool {
+    let mut parent = start;
+    for header in chain {
+        if header.parent != hash(parent)
+            || header.height != parent.height + 1
+            || header.state != parent.state + header.extrinsic
+            || !meets_threshold(hash(header), THRESHOLD)
+        {
+            return false;
+        }
+        parent = header;
+    }
+    true
+}
+
+/// Verify the chain with the original rules, and require the given parity of the state from the
+/// fork on.
+pub(super) fn verify_sub_chain_parity(start: &Header, chain: &[Header], parity: u64) -> bool {
+    verify_sub_chain(start, chain)
+        && chain
+            .iter()
+            .all(|header| header.height <= FORK_HEIGHT || header.state % 2 == parity)
+}
+
+pub(super) fn build_contentious_forked_chain() -> (Vec<Header>, Vec<Header>, Vec<Header>) {
+    let mut prefix = vec![genesis()];
+    for _ in 0..FORK_HEIGHT {
+        let header = child(prefix.last().expect("starts with genesis"), 1);
+        prefix.push(header);
+    }
+    let suffix = |first: u64| {
+        let tip = prefix.last().expect("starts with genesis");
+        // Once the parity is right, adding even numbers keeps it that way.
+        let first_child = child(tip, first);
+        let second_child = child(&first_child, 2);
+        vec![first_child, second_child]
+    };
+    let state = prefix.last().expect("starts with genesis").state;
+    let even = suffix(if state % 2 == 0 { 2 } else { 1 });
+    let odd = suffix(if state % 2 == 0 { 1 } else { 2 });
+    (prefix, even, odd)
+}
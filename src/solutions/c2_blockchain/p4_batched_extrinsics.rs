@@ -0,0 +1,76 @@
+use super::{Block, Hash, Header};
+use crate::hashing::{hash, H256};
+
+pub(super) fn genesis_header() -> Header {
+    Header {
+        parent: H256::zero(),
+        height: 0,
+        extrinsics_root: hash(&Vec::<u64>::new()),
+        state: 0,
+        consensus_digest: 0,
+    }
+}
+
+pub(super) fn child_header(parent: &Header, extrinsics_root: Hash, state: u64) -> Header {
+    Header {
+        parent: hash(parent),
+        height: parent.height + 1,
+        extrinsics_root,
+        state,
+        consensus_digest: 0,
+    }
+}
+
+pub(super) fn verify_child(parent: &Header, child: &Header) -> bool {
+    child.parent == hash(parent) && child.height == parent.height + 1
+}
+
+pub(super) fn verify_header_sub_chain(start: &Header, chain: &[Header]) -> bool {
+    let mut parent = start;
+    for header in chain {
+        if !verify_child(parent, header) {
+            return false;
+        }
+        parent = header;
+    }
+    true
+}
+
+pub(super) fn genesis_block() -> Block {
+    Block {
+        header: genesis_header(),
+        body: Vec::new(),
+    }
+}
+
+pub(super) fn child_block(parent: &Block, extrinsics: Vec<u64>) -> Block {
+    let state = parent.header.state + extrinsics.iter().sum::<u64>();
+    Block {
+        header: child_header(&parent.header, hash(&extrinsics), state),
+        body: extrinsics,
+    }
+}
+
+pub(super) fn verify_block_sub_chain(start: &Block, chain: &[Block]) -> bool {
+    let mut parent = start;
+    for block in chain {
+        let header = &block.header;
+        if !verify_child(&parent.header, header)
+            || header.extrinsics_root != hash(&block.body)
+            || header.state != parent.header.state + block.body.iter().sum::<u64>()
+        {
+            return false;
+        }
+        parent = block;
+    }
+    true
+}
+
+pub(super) fn build_invalid_child_block_with_valid_header(parent: &Header) -> Block {
+    // The header promises a state that the extrinsics do not add up to.
+    let body = vec![1, 2, 3];
+    Block {
+        header: child_header(parent, hash(&body), parent.state),
+        body,
+    }
+}
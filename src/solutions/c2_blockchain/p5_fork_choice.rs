@@ -0,0 +1,77 @@
+use super::{Block, ForkChoice, Header, THRESHOLD};
+use crate::difficulty::meets_threshold;
+use crate::hashing::hash;
+
+pub(super) fn best_chain<'a, F: ForkChoice + ?Sized>(
+    candidate_chains: &[&'a [Header]],
+) -> &'a [Header] {
+    candidate_chains
+        .iter()
+        .copied()
+        .reduce(|best, chain| {
+            if F::first_chain_is_better(chain, best) {
+                chain
+            } else {
+                best
+            }
+        })
+        .unwrap_or(&[])
+}
+
+pub(super) fn longest_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
+    chain_1.len() > chain_2.len()
+}
+
+pub(super) fn mine_extra_hard(block: &mut Block, threshold: u64) {
+    while !meets_threshold(hash(&block.header), threshold) {
+        block.header.consensus_digest += 1;
+    }
+}
+
+/// The accumulated work of a chain, with `work = THRESHOLD - block_hash` for each block.
+fn work(chain: &[Header]) -> u128 {
+    chain
+        .iter()
+        .map(|header| u128::from(THRESHOLD.saturating_sub(hash(header).low_u64())))
+        .sum()
+}
+
+pub(super) fn heaviest_chain_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
+    work(chain_1) > work(chain_2)
+}
+
+fn even_hashes(chain: &[Header]) -> usize {
+    chain
+        .iter()
+        .filter(|header| hash(header).low_u64() % 2 == 0)
+        .count()
+}
+
+pub(super) fn most_even_hashes_is_better(chain_1: &[Header], chain_2: &[Header]) -> bool {
+    even_hashes(chain_1) > even_hashes(chain_2)
+}
+
+pub(super) fn create_fork_one_side_longer_other_side_heavier(
+) -> (Vec<Header>, Vec<Header>, Vec<Header>) {
+    let genesis = Block::genesis();
+
+    // Blocks whose hash is above the threshold carry no work at all.
+    let mut longest = Vec::new();
+    let mut tip = genesis.clone();
+    for _ in 0..3 {
+        let mut extrinsic = 0;
+        let block = loop {
+            let block = tip.child(vec![extrinsic]);
+            if !meets_threshold(hash(&block.header), THRESHOLD) {
+                break block;
+            }
+            extrinsic += 1;
+        };
+        longest.push(block.header.clone());
+        tip = block;
+    }
+
+    let mut heavy = genesis.child(vec![1]);
+    mine_extra_hard(&mut heavy, THRESHOLD / 2);
+    (vec![genesis.header], longest, vec![heavy.header])
+}
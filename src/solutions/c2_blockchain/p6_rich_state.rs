@@ -0,0 +1,106 @@
+use super::{Block, Extrinsic, Hash, Header, RichState, State};
+use crate::c1_state_machine::{Nft, StateMachine};
+use crate::hashing::{hash, H256};
+
+pub(super) fn genesis_header(genesis_state_root: Hash) -> Header {
+    Header {
+        parent: H256::zero(),
+        height: 0,
+        extrinsics_root: hash(&Vec::<u64>::new()),
+        state_root: genesis_state_root,
+        consensus_digest: 0,
+    }
+}
+
+pub(super) fn child_header(parent: &Header, extrinsics_root: Hash, state_root: Hash) -> Header {
+    Header {
+        parent: hash(parent),
+        height: parent.height + 1,
+        extrinsics_root,
+        state_root,
+        consensus_digest: 0,
+    }
+}
+
+pub(super) fn verify_child(parent: &Header, child: &Header) -> bool {
+    child.parent == hash(parent) && child.height == parent.height + 1
+}
+
+pub(super) fn verify_header_sub_chain(start: &Header, chain: &[Header]) -> bool {
+    let mut parent = start;
+    for header in chain {
+        if !verify_child(parent, header) {
+            return false;
+        }
+        parent = header;
+    }
+    true
+}
+
+/// Execute the extrinsics on top of the given state.
+fn execute(pre_state: &State, extrinsics: &[u64]) -> State {
+    let mut state = pre_state.clone();
+    for extrinsic in extrinsics {
+        state.sum += extrinsic;
+        state.product *= extrinsic;
+    }
+    state
+}
+
+pub(super) fn execute_rich(pre_state: &RichState, extrinsics: &[Extrinsic]) -> RichState {
+    let mut state = pre_state.clone();
+    for extrinsic in extrinsics {
+        match extrinsic {
+            Extrinsic::Number(n) => state.numbers = execute(&state.numbers, &[*n]),
+            Extrinsic::Nft(t) => state.nfts = Nft::next_state(&state.nfts, t),
+        }
+    }
+    state
+}
+
+pub(super) fn genesis_block(genesis_state: &State) -> Block {
+    Block {
+        header: genesis_header(hash(genesis_state)),
+        body: Vec::new(),
+    }
+}
+
+pub(super) fn child_block(parent: &Block, pre_state: &State, extrinsics: Vec<u64>) -> Block {
+    let state_root = hash(&execute(pre_state, &extrinsics));
+    Block {
+        header: child_header(&parent.header, hash(&extrinsics), state_root),
+        body: extrinsics,
+    }
+}
+
+pub(super) fn verify_block_sub_chain(start: &Block, pre_state: &State, chain: &[Block]) -> bool {
+    if hash(pre_state) != start.header.state_root {
+        return false;
+    }
+    let mut parent = start;
+    let mut state = pre_state.clone();
+    for block in chain {
+        state = execute(&state, &block.body);
+        let header = &block.header;
+        if !verify_child(&parent.header, header)
+            || header.extrinsics_root != hash(&block.body)
+            || header.state_root != hash(&state)
+        {
+            return false;
+        }
+        parent = block;
+    }
+    true
+}
+
+pub(super) fn build_invalid_child_block_with_valid_header(
+    parent: &Header,
+    pre_state: &State,
+) -> Block {
+    // The header commits to the state before the extrinsics, not after them.
+    let body = vec![1, 2, 3];
+    Block {
+        header: child_header(parent, hash(&body), hash(pre_state)),
+        body,
+    }
+}
@@ -0,0 +1,38 @@
+use super::{Consensus, Header};
+
+pub(super) fn verify_sub_chain<C: Consensus + ?Sized>(
+    engine: &C,
+    parent_digest: &C::Digest,
+    chain: &[Header<C::Digest>],
+) -> bool {
+    let mut parent_digest = parent_digest;
+    for header in chain {
+        if !engine.validate(parent_digest, header) {
+            return false;
+        }
+        parent_digest = &header.consensus_digest;
+    }
+    true
+}
+
+/// Attach the given consensus digest to a partial header.
+pub(super) fn with_digest<Digest>(partial_header: Header<()>, digest: Digest) -> Header<Digest> {
+    Header {
+        parent: partial_header.parent,
+        height: partial_header.height,
+        state_root: partial_header.state_root,
+        extrinsics_root: partial_header.extrinsics_root,
+        consensus_digest: digest,
+    }
+}
+
+/// Replace the consensus digest of a header, for engines that delegate to other engines.
+pub(super) fn map_digest<From, To>(header: &Header<From>, digest: To) -> Header<To> {
+    Header {
+        parent: header.parent,
+        height: header.height,
+        state_root: header.state_root,
+        extrinsics_root: header.extrinsics_root,
+        consensus_digest: digest,
+    }
+}
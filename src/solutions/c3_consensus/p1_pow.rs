@@ -0,0 +1,25 @@
+use crate::c3_consensus::solution::with_digest;
+use crate::c3_consensus::{Header, Pow};
+use crate::difficulty::{difficulty_to_threshold, meets_threshold};
+use crate::hashing::header_hash;
+
+pub(super) fn validate(engine: &Pow, header: &Header<u64>) -> bool {
+    meets_threshold(header_hash(header), engine.threshold)
+}
+
+pub(super) fn seal(engine: &Pow, partial_header: Header<()>) -> Option<Header<u64>> {
+    let mut header = with_digest(partial_header, 0);
+    while !validate(engine, &header) {
+        header.consensus_digest = header.consensus_digest.checked_add(1)?;
+    }
+    Some(header)
+}
+
+pub(super) fn moderate_difficulty_pow() -> Pow {
+    Pow::new(difficulty_to_threshold(100))
+}
+
+pub(super) fn trivial_always_valid_pow() -> Pow {
+    // Only a hash that ends in eight bytes of 0xff fails, and nobody will ever see one.
+    Pow::new(u64::MAX)
+}
@@ -0,0 +1,14 @@
+use super::DictatorConsensus;
+use crate::c3_consensus::solution::with_digest;
+use crate::c3_consensus::{ConsensusAuthority, Header};
+
+pub(super) fn validate(engine: &DictatorConsensus, header: &Header<ConsensusAuthority>) -> bool {
+    header.consensus_digest == engine.dictator
+}
+
+pub(super) fn seal(
+    engine: &DictatorConsensus,
+    partial_header: Header<()>,
+) -> Option<Header<ConsensusAuthority>> {
+    Some(with_digest(partial_header, engine.dictator))
+}
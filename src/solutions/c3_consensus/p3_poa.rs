@@ -0,0 +1,65 @@
+use super::{PoaRoundRobinByHeight, PoaRoundRobinBySlot, SlotDigest};
+use crate::c3_consensus::solution::with_digest;
+use crate::c3_consensus::{ConsensusAuthority, Header, SimplePoa};
+
+pub(super) fn validate_simple(engine: &SimplePoa, header: &Header<ConsensusAuthority>) -> bool {
+    engine.authorities.contains(&header.consensus_digest)
+}
+
+pub(super) fn seal_simple(
+    engine: &SimplePoa,
+    partial_header: Header<()>,
+) -> Option<Header<ConsensusAuthority>> {
+    // We sign as the first authority.
+    let authority = *engine.authorities.first()?;
+    Some(with_digest(partial_header, authority))
+}
+
+/// The authority whose turn it is at the given height. Nobody signs genesis.
+fn author_at_height(authorities: &[ConsensusAuthority], height: u64) -> Option<ConsensusAuthority> {
+    let turn = height.checked_sub(1)?;
+    let count = authorities.len() as u64;
+    (count > 0).then(|| authorities[(turn % count) as usize])
+}
+
+pub(super) fn validate_by_height(
+    engine: &PoaRoundRobinByHeight,
+    header: &Header<ConsensusAuthority>,
+) -> bool {
+    author_at_height(&engine.authorities, header.height) == Some(header.consensus_digest)
+}
+
+pub(super) fn seal_by_height(
+    engine: &PoaRoundRobinByHeight,
+    partial_header: Header<()>,
+) -> Option<Header<ConsensusAuthority>> {
+    let authority = author_at_height(&engine.authorities, partial_header.height)?;
+    Some(with_digest(partial_header, authority))
+}
+
+/// The authority whose turn it is in the given slot.
+fn author_in_slot(authorities: &[ConsensusAuthority], slot: u64) -> Option<ConsensusAuthority> {
+    let count = authorities.len() as u64;
+    (count > 0).then(|| authorities[(slot % count) as usize])
+}
+
+pub(super) fn validate_by_slot(
+    engine: &PoaRoundRobinBySlot,
+    parent_digest: &SlotDigest,
+    header: &Header<SlotDigest>,
+) -> bool {
+    let digest = header.consensus_digest;
+    digest.slot > parent_digest.slot
+        && author_in_slot(&engine.authorities, digest.slot) == Some(digest.signature)
+}
+
+pub(super) fn seal_by_slot(
+    engine: &PoaRoundRobinBySlot,
+    parent_digest: &SlotDigest,
+    partial_header: Header<()>,
+) -> Option<Header<SlotDigest>> {
+    // We take the very next slot, whoever's turn that is.
+    let slot = parent_digest.slot.checked_add(1)?;
+    let signature = author_in_slot(&engine.authorities, slot)?;
+    Some(with_digest(partial_header, SlotDigest { slot, signature }))
+}
@@ -0,0 +1,52 @@
+use alloc::{vec, vec::Vec};
+
+use super::EvenOnly;
+use crate::c3_consensus::p1_pow::moderate_difficulty_pow;
+use crate::c3_consensus::{Consensus, Header};
+use crate::hashing::{header_hash, H256};
+
+/// Whether the state root, read through its last eight bytes, is even.
+fn is_even(state_root: H256) -> bool {
+    state_root.low_u64().is_multiple_of(2)
+}
+
+pub(super) fn validate<Inner: Consensus>(
+    engine: &EvenOnly<Inner>,
+    parent_digest: &Inner::Digest,
+    header: &Header<Inner::Digest>,
+) -> bool {
+    is_even(header.state_root) && engine.inner.validate(parent_digest, header)
+}
+
+pub(super) fn seal<Inner: Consensus>(
+    engine: &EvenOnly<Inner>,
+    parent_digest: &Inner::Digest,
+    partial_header: Header<()>,
+) -> Option<Header<Inner::Digest>> {
+    if !is_even(partial_header.state_root) {
+        return None;
+    }
+    engine.inner.seal(parent_digest, partial_header)
+}
+
+pub(super) fn almost_valid_but_not_all_even() -> Vec<Header<u64>> {
+    let pow = moderate_difficulty_pow();
+    let genesis = Header::new(H256::zero(), 0, H256::zero(), H256::zero(), 0);
+    let mut chain = vec![genesis];
+    // The second state root is odd.
+    for state_root in [2, 3, 4] {
+        let parent = chain.last().expect("starts with genesis");
+        let partial = Header::new(
+            header_hash(parent),
+            parent.height + 1,
+            H256::from(state_root),
+            H256::zero(),
+            (),
+        );
+        let header = pow
+            .seal(&parent.consensus_digest, partial)
+            .expect("PoW can always seal");
+        chain.push(header);
+    }
+    chain
+}
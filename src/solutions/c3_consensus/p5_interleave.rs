@@ -0,0 +1,65 @@
+use alloc::vec;
+
+use super::PowOrPoaDigest;
+use crate::c3_consensus::p1_pow::moderate_difficulty_pow;
+use crate::c3_consensus::solution::map_digest;
+use crate::c3_consensus::{Consensus, ConsensusAuthority, Header, SimplePoa};
+
+pub(super) fn from_nonce(nonce: u64) -> PowOrPoaDigest {
+    PowOrPoaDigest::Pow(nonce)
+}
+
+pub(super) fn to_nonce(digest: PowOrPoaDigest) -> Result<u64, ()> {
+    match digest {
+        PowOrPoaDigest::Pow(nonce) => Ok(nonce),
+        PowOrPoaDigest::Poa(_) => Err(()),
+    }
+}
+
+pub(super) fn from_authority(authority: ConsensusAuthority) -> PowOrPoaDigest {
+    PowOrPoaDigest::Poa(authority)
+}
+
+pub(super) fn to_authority(digest: PowOrPoaDigest) -> Result<ConsensusAuthority, ()> {
+    match digest {
+        PowOrPoaDigest::Poa(authority) => Ok(authority),
+        PowOrPoaDigest::Pow(_) => Err(()),
+    }
+}
+
+/// The authorities that may seal the even blocks.
+fn poa() -> SimplePoa {
+    SimplePoa {
+        authorities: vec![
+            ConsensusAuthority::Alice,
+            ConsensusAuthority::Bob,
+            ConsensusAuthority::Charlie,
+        ],
+    }
+}
+
+/// Odd blocks are sealed with work, even blocks with a signature. The inner engines do not look
+/// at the parent digest, so we hand them the header's own.
+pub(super) fn validate(header: &Header<PowOrPoaDigest>) -> bool {
+    match (header.height % 2, header.consensus_digest) {
+        (1, PowOrPoaDigest::Pow(nonce)) => {
+            moderate_difficulty_pow().validate(&nonce, &map_digest(header, nonce))
+        }
+        (0, PowOrPoaDigest::Poa(authority)) => {
+            poa().validate(&authority, &map_digest(header, authority))
+        }
+        _ => false,
+    }
+}
+
+pub(super) fn seal(partial_header: Header<()>) -> Option<Header<PowOrPoaDigest>> {
+    if partial_header.height % 2 == 1 {
+        let header = moderate_difficulty_pow().seal(&0, partial_header)?;
+        let nonce = header.consensus_digest;
+        Some(map_digest(&header, PowOrPoaDigest::Pow(nonce)))
+    } else {
+        let header = poa().seal(&ConsensusAuthority::Alice, partial_header)?;
+        let authority = header.consensus_digest;
+        Some(map_digest(&header, PowOrPoaDigest::Poa(authority)))
+    }
+}
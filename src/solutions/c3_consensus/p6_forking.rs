@@ -0,0 +1,146 @@
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use super::Forked;
+use crate::c3_consensus::p4_even_only::EvenOnly;
+use crate::c3_consensus::solution::map_digest;
+use crate::c3_consensus::{Consensus, ConsensusAuthority, Header, Pow, SimplePoa};
+use crate::difficulty::difficulty_to_threshold;
+
+pub(super) fn validate<D, B, A>(
+    engine: &Forked<D, B, A>,
+    parent_digest: &D,
+    header: &Header<D>,
+) -> bool
+where
+    B: Consensus<Digest = D>,
+    A: Consensus<Digest = D>,
+{
+    if header.height < engine.fork_height {
+        engine.before.validate(parent_digest, header)
+    } else {
+        engine.after.validate(parent_digest, header)
+    }
+}
+
+pub(super) fn seal<D, B, A>(
+    engine: &Forked<D, B, A>,
+    parent_digest: &D,
+    partial_header: Header<()>,
+) -> Option<Header<D>>
+where
+    B: Consensus<Digest = D>,
+    A: Consensus<Digest = D>,
+{
+    if partial_header.height < engine.fork_height {
+        engine.before.seal(parent_digest, partial_header)
+    } else {
+        engine.after.seal(parent_digest, partial_header)
+    }
+}
+
+/// The given engines, switching from one to the other at the fork height.
+fn forked<B, A>(fork_height: u64, before: B, after: A) -> Forked<B::Digest, B, A>
+where
+    B: Consensus,
+    A: Consensus<Digest = B::Digest>,
+{
+    Forked {
+        fork_height,
+        before,
+        after,
+        phdata: PhantomData,
+    }
+}
+
+pub(super) fn change_authorities(
+    fork_height: u64,
+    initial_authorities: Vec<ConsensusAuthority>,
+    final_authorities: Vec<ConsensusAuthority>,
+) -> impl Consensus {
+    forked(
+        fork_height,
+        SimplePoa {
+            authorities: initial_authorities,
+        },
+        SimplePoa {
+            authorities: final_authorities,
+        },
+    )
+}
+
+pub(super) fn change_difficulty(
+    fork_height: u64,
+    initial_difficulty: u64,
+    final_difficulty: u64,
+) -> impl Consensus {
+    forked(
+        fork_height,
+        Pow::new(difficulty_to_threshold(initial_difficulty.into())),
+        Pow::new(difficulty_to_threshold(final_difficulty.into())),
+    )
+}
+
+pub(super) fn even_after_given_height<Original: Consensus + Clone>(
+    fork_height: u64,
+    original: Original,
+) -> impl Consensus {
+    let even = EvenOnly {
+        inner: original.clone(),
+    };
+    forked(fork_height, original, even)
+}
+
+/// The digest of a chain that starts out with work and switches to signatures.
+#[derive(Hash, Debug, PartialEq, Eq, Clone, Copy)]
+enum PowOrPoa {
+    Pow(u64),
+    Poa(ConsensusAuthority),
+}
+
+/// Proof of Work until the fork, and Proof of Authority from then on.
+struct PowToPoa {
+    fork_height: u64,
+    pow: Pow,
+    poa: SimplePoa,
+}
+
+impl Consensus for PowToPoa {
+    type Digest = PowOrPoa;
+
+    fn validate(&self, _: &PowOrPoa, header: &Header<PowOrPoa>) -> bool {
+        let before_fork = header.height < self.fork_height;
+        match (before_fork, header.consensus_digest) {
+            (true, PowOrPoa::Pow(nonce)) => self.pow.validate(&nonce, &map_digest(header, nonce)),
+            // The first block after the fork has a PoW parent, but PoA never looks at the parent.
+            (false, PowOrPoa::Poa(authority)) => self
+                .poa
+                .validate(&authority, &map_digest(header, authority)),
+            _ => false,
+        }
+    }
+
+    fn seal(&self, _: &PowOrPoa, partial_header: Header<()>) -> Option<Header<PowOrPoa>> {
+        if partial_header.height < self.fork_height {
+            let header = self.pow.seal(&0, partial_header)?;
+            let nonce = header.consensus_digest;
+            Some(map_digest(&header, PowOrPoa::Pow(nonce)))
+        } else {
+            let authority = *self.poa.authorities.first()?;
+            let header = self.poa.seal(&authority, partial_header)?;
+            Some(map_digest(&header, PowOrPoa::Poa(authority)))
+        }
+    }
+}
+
+pub(super) fn pow_to_poa(
+    fork_height: u64,
+    difficulty: u64,
+    authorities: Vec<ConsensusAuthority>,
+) -> impl Consensus {
+    PowToPoa {
+        fork_height,
+        pow: Pow::new(difficulty_to_threshold(difficulty.into())),
+        poa: SimplePoa { authorities },
+    }
+}
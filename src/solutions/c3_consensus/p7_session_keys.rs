@@ -0,0 +1,63 @@
+use super::{epoch_of, set_keys_payload, unsealed, SessionKeys, SessionPoa, SessionSeal, SetKeys};
+use crate::c3_consensus::solution::with_digest;
+use crate::c3_consensus::Header;
+use crate::crypto::sig::PublicKey;
+use crate::hashing::header_hash;
+
+pub(super) fn set_keys(state: &SessionKeys, height: u64, request: &SetKeys) -> SessionKeys {
+    let Some(stash) = state.stashes.get(request.authority) else {
+        return state.clone();
+    };
+    let payload = set_keys_payload(request.authority, &request.session_key, request.rotation);
+    if request.rotation != state.rotations[request.authority]
+        || !stash.verify(payload.as_bytes(), &request.signature)
+    {
+        return state.clone();
+    }
+
+    let mut next = state.clone();
+    // A second rotation within the same epoch replaces the first one.
+    next.keys[request.authority].insert(epoch_of(height) + 1, request.session_key);
+    next.rotations[request.authority] += 1;
+    next
+}
+
+pub(super) fn session_key(state: &SessionKeys, authority: usize, epoch: u64) -> Option<PublicKey> {
+    state
+        .keys
+        .get(authority)?
+        .range(..=epoch)
+        .next_back()
+        .map(|(_, key)| *key)
+}
+
+pub(super) fn validate(engine: &SessionPoa, header: &Header<Option<SessionSeal>>) -> bool {
+    let Some(seal) = header.consensus_digest else {
+        return false;
+    };
+    engine
+        .sessions
+        .session_key(seal.authority, epoch_of(header.height))
+        .is_some_and(|key| key.verify(header_hash(&unsealed(header)).as_bytes(), &seal.signature))
+}
+
+pub(super) fn seal(
+    engine: &SessionPoa,
+    partial_header: Header<()>,
+) -> Option<Header<Option<SessionSeal>>> {
+    let (authority, keypair) = engine.local.as_ref()?;
+    let active = engine
+        .sessions
+        .session_key(*authority, epoch_of(partial_header.height))?;
+    if active != keypair.public() {
+        return None;
+    }
+    let signature = keypair.sign(header_hash(&partial_header).as_bytes());
+    Some(with_digest(
+        partial_header,
+        Some(SessionSeal {
+            authority: *authority,
+            signature,
+        }),
+    ))
+}
@@ -0,0 +1,159 @@
+use alloc::vec::Vec;
+
+use super::{
+    author_in_slot, seal_payload, sudo_payload, Aura, AuraHeader, AuraSeal, AuthorityRegistry,
+    RegistryCall, RegistryTransaction, AUTHORITY_BOND,
+};
+use crate::c3_consensus::solution::with_digest;
+use crate::c3_consensus::{epoch_of, unsealed, Header, EPOCH_LENGTH};
+use crate::crypto::sig::PublicKey;
+
+pub(super) fn execute_block(
+    registry: &AuthorityRegistry,
+    height: u64,
+    transactions: &[RegistryTransaction],
+) -> AuthorityRegistry {
+    let mut registry = registry.clone();
+    if height > 0 && height.is_multiple_of(EPOCH_LENGTH) {
+        start_epoch(&mut registry, height);
+    }
+    for t in transactions {
+        execute(&mut registry, t);
+    }
+    registry
+}
+
+fn start_epoch(registry: &mut AuthorityRegistry, height: u64) {
+    if registry.authorities_at(height) != registry.next.as_slice() {
+        registry
+            .sets
+            .insert(epoch_of(height), registry.next.clone());
+    }
+    let out: Vec<PublicKey> = registry
+        .bonds
+        .keys()
+        .copied()
+        .filter(|key| !registry.next.contains(key))
+        .collect();
+    for key in out {
+        let bond = registry.bonds.remove(&key).unwrap_or(0);
+        *registry.balances.entry(key).or_default() += bond;
+    }
+}
+
+fn execute(registry: &mut AuthorityRegistry, t: &RegistryTransaction) {
+    match t {
+        RegistryTransaction::Sudo {
+            call,
+            nonce,
+            signature,
+        } => {
+            if *nonce != registry.sudo_nonce
+                || !registry
+                    .sudo
+                    .verify(sudo_payload(call, *nonce).as_bytes(), signature)
+            {
+                return;
+            }
+            match *call {
+                RegistryCall::AddAuthority(key) => {
+                    let balance = registry.balances.get(&key).copied().unwrap_or(0);
+                    if registry.next.contains(&key) || balance < AUTHORITY_BOND {
+                        return;
+                    }
+                    if balance == AUTHORITY_BOND {
+                        registry.balances.remove(&key);
+                    } else {
+                        registry.balances.insert(key, balance - AUTHORITY_BOND);
+                    }
+                    *registry.bonds.entry(key).or_default() += AUTHORITY_BOND;
+                    registry.next.push(key);
+                }
+                RegistryCall::RemoveAuthority(key) => {
+                    // A chain without authorities could never author another block.
+                    if !registry.next.contains(&key) || registry.next.len() == 1 {
+                        return;
+                    }
+                    registry.next.retain(|&a| a != key);
+                }
+            }
+            registry.sudo_nonce += 1;
+        }
+        RegistryTransaction::ReportEquivocation { first, second } => {
+            let Some(offender) = equivocator(registry, first, second) else {
+                return;
+            };
+            let Some(seal) = first.consensus_digest else {
+                return;
+            };
+            if !registry.punished.insert((offender, seal.slot)) {
+                return;
+            }
+            registry.bonds.remove(&offender);
+            // Even an offender is better than no authority at all.
+            if registry.next.len() > 1 {
+                registry.next.retain(|&a| a != offender);
+            }
+        }
+    }
+}
+
+/// The authority that sealed the given header, if its seal is valid among the authorities of
+/// the header's epoch.
+fn sealed_by(registry: &AuthorityRegistry, header: &AuraHeader) -> Option<PublicKey> {
+    let seal = header.consensus_digest?;
+    let author = author_in_slot(registry.authorities_at(header.height), seal.slot)?;
+    author
+        .verify(
+            seal_payload(&unsealed(header), seal.slot).as_bytes(),
+            &seal.signature,
+        )
+        .then_some(author)
+}
+
+pub(super) fn equivocator(
+    registry: &AuthorityRegistry,
+    first: &AuraHeader,
+    second: &AuraHeader,
+) -> Option<PublicKey> {
+    let (a, b) = (first.consensus_digest?, second.consensus_digest?);
+    if a.slot != b.slot || unsealed(first) == unsealed(second) {
+        return None;
+    }
+    let offender = sealed_by(registry, first)?;
+    (sealed_by(registry, second)? == offender).then_some(offender)
+}
+
+pub(super) fn validate(
+    engine: &Aura,
+    parent_digest: &Option<AuraSeal>,
+    header: &AuraHeader,
+) -> bool {
+    let Some(seal) = header.consensus_digest else {
+        return false;
+    };
+    let after_parent = parent_digest.is_none_or(|parent| seal.slot > parent.slot);
+    after_parent && sealed_by(&engine.registry, header).is_some()
+}
+
+pub(super) fn seal(
+    engine: &Aura,
+    parent_digest: &Option<AuraSeal>,
+    partial_header: Header<()>,
+) -> Option<AuraHeader> {
+    let keypair = engine.local.as_ref()?;
+    let authorities = engine.registry.authorities_at(partial_header.height);
+    let turn = authorities.iter().position(|&a| a == keypair.public())? as u64;
+    let count = authorities.len() as u64;
+
+    // The first slot after the parent's whose number leaves the remainder of our turn.
+    let first = parent_digest
+        .map_or(0, |parent| parent.slot)
+        .checked_add(1)?;
+    let slot = first + (turn + count - first % count) % count;
+    let signature = keypair.sign(seal_payload(&partial_header, slot).as_bytes());
+    Some(with_digest(
+        partial_header,
+        Some(AuraSeal { slot, signature }),
+    ))
+}
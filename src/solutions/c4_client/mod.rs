@@ -0,0 +1,187 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use super::executive::Executive;
+use super::storage::{StateStore, StorageMode};
+use super::{Block, Consensus, Hash, Header, ImportError, StateMachine};
+use crate::hashing::{hash, header_hash};
+use crate::merkle::merkle_root;
+
+/// Everything the client knows about the chain.
+pub(super) struct Chain<C: Consensus, SM: StateMachine> {
+    pub(super) blocks: BTreeMap<Hash, Block<C, SM>>,
+    /// The state after every known block, unless it was pruned.
+    pub(super) states: StateStore<SM::State>,
+    /// The known blocks that have no known children.
+    pub(super) leaves: BTreeSet<Hash>,
+    /// The most recently finalized block.
+    pub(super) finalized: Hash,
+}
+
+impl<C: Consensus, SM: StateMachine> Chain<C, SM> {
+    /// A chain that knows nothing but the given genesis block and its state.
+    pub(super) fn new(genesis: Block<C, SM>, state: SM::State) -> Self {
+        let hash = header_hash(genesis.header());
+        let mut states = StateStore::new(StorageMode::Archive, 1);
+        states.insert(hash, genesis.header().height(), state);
+        states
+            .finalize(hash)
+            .expect("the genesis state was just inserted");
+        Chain {
+            blocks: BTreeMap::from([(hash, genesis)]),
+            states,
+            leaves: BTreeSet::from([hash]),
+            finalized: hash,
+        }
+    }
+
+    /// Add a block that was already checked, along with the state after it.
+    pub(super) fn insert(&mut self, block: Block<C, SM>, state: SM::State) -> Hash {
+        let hash = header_hash(block.header());
+        self.leaves.remove(&block.header().parent());
+        self.leaves.insert(hash);
+        self.states.insert(hash, block.header().height(), state);
+        self.blocks.insert(hash, block);
+        hash
+    }
+
+    /// Whether the given block is known, and is the given ancestor or one of its descendants.
+    pub(super) fn descends_from(&self, ancestor: Hash, hash: Hash) -> bool {
+        let Some(ancestor_height) = self.blocks.get(&ancestor).map(|b| b.header().height()) else {
+            return false;
+        };
+        let mut hash = hash;
+        while let Some(block) = self.blocks.get(&hash) {
+            if block.header().height() <= ancestor_height {
+                return hash == ancestor;
+            }
+            hash = block.header().parent();
+        }
+        false
+    }
+}
+
+/// The state after executing the given extrinsics on top of the given state.
+fn execute<SM>(pre_state: &SM::State, extrinsics: &[SM::Transition]) -> SM::State
+where
+    SM: StateMachine,
+    SM::State: Clone,
+{
+    extrinsics
+        .iter()
+        .fold(pre_state.clone(), |state, t| SM::next_state(&state, t))
+}
+
+pub(super) fn verify_child<Digest: core::hash::Hash>(
+    parent: &Header<Digest>,
+    child: &Header<Digest>,
+) -> bool {
+    child.parent() == header_hash(parent) && child.height() == parent.height() + 1
+}
+
+/// The state after executing the given block body on top of the given state, by the executive
+/// if there is one. The digest is None while the block is being authored.
+fn execute_block<C, SM>(
+    consensus_engine: &C,
+    executive: Option<&(dyn Executive<C, SM> + Send)>,
+    pre_state: &SM::State,
+    height: u64,
+    digest: Option<&C::Digest>,
+    body: &[SM::Transition],
+) -> Result<SM::State, ImportError>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone,
+{
+    match executive {
+        Some(executive) => {
+            executive.check_body(body)?;
+            executive.execute_block(consensus_engine, pre_state.clone(), height, digest, body)
+        }
+        None => Ok(execute::<SM>(pre_state, body)),
+    }
+}
+
+/// Author a child of the given block with the given body. None if the executive refuses the
+/// body, or the engine does not seal the block.
+pub(super) fn author_child<C, SM>(
+    consensus_engine: &C,
+    executive: Option<&(dyn Executive<C, SM> + Send)>,
+    parent: &Block<C, SM>,
+    pre_state: &SM::State,
+    extrinsics: Vec<SM::Transition>,
+) -> Option<Block<C, SM>>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + core::hash::Hash,
+    SM::Transition: core::hash::Hash,
+{
+    let height = parent.header().height() + 1;
+    let state = execute_block(
+        consensus_engine,
+        executive,
+        pre_state,
+        height,
+        None,
+        &extrinsics,
+    )
+    .ok()?;
+    let partial_header = Header::new(
+        header_hash(parent.header()),
+        height,
+        hash(&state),
+        merkle_root(&extrinsics),
+        (),
+    );
+    let parent_digest = parent.header().consensus_digest();
+    let header = match executive {
+        Some(executive) => {
+            executive.seal(consensus_engine, parent_digest, pre_state, partial_header)?
+        }
+        None => consensus_engine.seal(parent_digest, partial_header)?,
+    };
+    Some(Block::new(header, extrinsics))
+}
+
+/// Check a single child block, and return the state after it if it is valid.
+pub(super) fn check_child<C, SM>(
+    consensus_engine: &C,
+    executive: Option<&(dyn Executive<C, SM> + Send)>,
+    parent: &Block<C, SM>,
+    pre_state: &SM::State,
+    child: &Block<C, SM>,
+) -> Result<SM::State, ImportError>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + core::hash::Hash,
+    SM::Transition: core::hash::Hash,
+{
+    let header = child.header();
+    if header.parent() != header_hash(parent.header()) {
+        return Err(ImportError::UnknownParent);
+    }
+    if header.height() != parent.header().height() + 1 {
+        return Err(ImportError::WrongHeight);
+    }
+    if !consensus_engine.validate(parent.header().consensus_digest(), header) {
+        return Err(ImportError::BadSeal);
+    }
+    if header.extrinsics_root() != merkle_root(child.body()) {
+        return Err(ImportError::ExtrinsicsRootMismatch);
+    }
+    let state = execute_block(
+        consensus_engine,
+        executive,
+        pre_state,
+        header.height(),
+        Some(header.consensus_digest()),
+        child.body(),
+    )?;
+    if header.state_root() != hash(&state) {
+        return Err(ImportError::StateRootMismatch);
+    }
+    Ok(state)
+}
@@ -0,0 +1,131 @@
+use alloc::vec::Vec;
+
+use super::{Block, Consensus, ForkChoice, FullClient, Hash, Header, StateMachine};
+use crate::c4_client::solution::{author_child, check_child, Chain};
+use crate::hashing::{hash, header_hash};
+use crate::merkle::empty_root;
+
+pub(super) use crate::c4_client::solution::verify_child;
+
+pub(super) fn genesis_header<Digest: Default>(state_root: Hash) -> Header<Digest> {
+    Header::new(Hash::zero(), 0, state_root, empty_root(), Digest::default())
+}
+
+pub(super) fn child_header<Digest: core::hash::Hash>(
+    parent: &Header<Digest>,
+    state_root: Hash,
+    extrinsics_root: Hash,
+) -> Header<()> {
+    Header::new(
+        header_hash(parent),
+        parent.height() + 1,
+        state_root,
+        extrinsics_root,
+        (),
+    )
+}
+
+pub(super) fn verify_header_sub_chain<Digest: core::hash::Hash>(
+    start: &Header<Digest>,
+    chain: &[Header<Digest>],
+) -> bool {
+    let mut parent = start;
+    for header in chain {
+        if !verify_child(parent, header) {
+            return false;
+        }
+        parent = header;
+    }
+    true
+}
+
+pub(super) fn genesis_block<C, SM>(genesis_state: &SM::State) -> Block<C, SM>
+where
+    C: Consensus,
+    C::Digest: Default,
+    SM: StateMachine,
+    SM::State: core::hash::Hash,
+{
+    Block::new(Header::genesis(hash(genesis_state)), Vec::new())
+}
+
+pub(super) fn child_block<C, SM>(
+    parent: &Block<C, SM>,
+    consensus_engine: &C,
+    pre_state: &SM::State,
+    extrinsics: Vec<SM::Transition>,
+) -> Option<Block<C, SM>>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + core::hash::Hash,
+    SM::Transition: core::hash::Hash,
+{
+    author_child(consensus_engine, None, parent, pre_state, extrinsics)
+}
+
+pub(super) fn verify_block_sub_chain<C, SM>(
+    start: &Block<C, SM>,
+    consensus_engine: &C,
+    pre_state: &SM::State,
+    chain: &[Block<C, SM>],
+) -> bool
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + core::hash::Hash,
+    SM::Transition: core::hash::Hash,
+{
+    let mut parent = start;
+    let mut state = pre_state.clone();
+    for block in chain {
+        match check_child(consensus_engine, None, parent, &state, block) {
+            Ok(next) => state = next,
+            Err(_) => return false,
+        }
+        parent = block;
+    }
+    true
+}
+
+pub(super) fn create_empty_chain<C, SM>(
+    consensus_engine: &C,
+    n: u64,
+    genesis_state: &SM::State,
+) -> Vec<Block<C, SM>>
+where
+    C: Consensus,
+    C::Digest: Default,
+    SM: StateMachine,
+    SM::State: Clone + core::hash::Hash,
+    SM::Transition: core::hash::Hash,
+{
+    // Empty blocks leave the state alone, so every block builds on the genesis state.
+    core::iter::successors(Some(Block::genesis(genesis_state)), |parent| {
+        parent.child(consensus_engine, genesis_state, Vec::new())
+    })
+    .take(n as usize)
+    .collect()
+}
+
+pub(super) fn new<C, SM, FC, P>(genesis_state: SM::State) -> FullClient<C, SM, FC, P>
+where
+    C: Consensus + Default,
+    C::Digest: Default,
+    SM: StateMachine + Default,
+    SM::State: core::hash::Hash,
+    FC: ForkChoice<C> + Default,
+    P: Default,
+{
+    let genesis = Block::<C, SM>::genesis(&genesis_state);
+    let mut fork_choice = FC::default();
+    fork_choice.import_hook(genesis.header().clone());
+    FullClient {
+        consensus_engine: C::default(),
+        state_machine: SM::default(),
+        fork_choice,
+        transaction_pool: P::default(),
+        executive: None,
+        chain: Chain::new(genesis, genesis_state),
+    }
+}
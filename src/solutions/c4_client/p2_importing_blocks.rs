@@ -0,0 +1,151 @@
+use alloc::vec::Vec;
+
+use super::{
+    Block, Consensus, ForkChoice, FullClient, Hash, ImportError, StateMachine, TransactionPool,
+};
+use crate::c4_client::solution::check_child;
+use crate::hashing::header_hash;
+
+pub(super) fn import_block<C, SM, FC, P>(
+    client: &mut FullClient<C, SM, FC, P>,
+    block: Block<C, SM>,
+) -> bool
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + PartialEq + core::hash::Hash,
+    SM::Transition: Clone + PartialEq + core::hash::Hash,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+{
+    match client.try_import_block(block) {
+        Ok(()) => true,
+        Err(error) => {
+            tracing::debug!(?error, "the block was rejected");
+            false
+        }
+    }
+}
+
+pub(super) fn try_import_block<C, SM, FC, P>(
+    client: &mut FullClient<C, SM, FC, P>,
+    block: Block<C, SM>,
+) -> Result<(), ImportError>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + PartialEq + core::hash::Hash,
+    SM::Transition: Clone + PartialEq + core::hash::Hash,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+{
+    let chain = &client.chain;
+    if chain.blocks.contains_key(&header_hash(block.header())) {
+        return Ok(());
+    }
+    let parent_hash = block.header().parent();
+    let (Some(parent), Some(parent_state)) = (
+        chain.blocks.get(&parent_hash),
+        chain.states.get(parent_hash).ok(),
+    ) else {
+        return Err(ImportError::UnknownParent);
+    };
+    if !chain.descends_from(chain.finalized, parent_hash) {
+        return Err(ImportError::Finalized);
+    }
+    let state = check_child(
+        &client.consensus_engine,
+        client.executive.as_deref(),
+        parent,
+        parent_state,
+        &block,
+    )?;
+
+    let old_best = client.best_block();
+    client.fork_choice.import_hook(block.header().clone());
+    client.chain.insert(block, state);
+    let new_best = client.best_block();
+    if new_best != old_best {
+        maintain_pool(client, old_best, new_best);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+fn maintain_pool<C, SM, FC, P>(
+    client: &mut FullClient<C, SM, FC, P>,
+    old_best: Hash,
+    new_best: Hash,
+) where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + PartialEq + core::hash::Hash,
+    SM::Transition: Clone + PartialEq + core::hash::Hash,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+{
+    client.maintain_pool(old_best, new_best);
+}
+
+/// Without the standard library there is no `reorg` module. Only the transactions of the new
+/// best block leave the pool, and those of retracted blocks are lost.
+#[cfg(not(feature = "std"))]
+fn maintain_pool<C, SM, FC, P>(client: &mut FullClient<C, SM, FC, P>, _: Hash, new_best: Hash)
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::Transition: Clone,
+    P: TransactionPool<SM>,
+{
+    for t in client.chain.blocks[&new_best].body() {
+        client.transaction_pool.remove(t.clone());
+    }
+}
+
+pub(super) fn get_block<C, SM, FC, P>(
+    client: &FullClient<C, SM, FC, P>,
+    block_hash: Hash,
+) -> Option<Block<C, SM>>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::Transition: Clone,
+{
+    let block = client.chain.blocks.get(&block_hash)?;
+    Some(Block::new(block.header().clone(), block.body().to_vec()))
+}
+
+pub(super) fn get_state<C, SM, FC, P>(
+    client: &FullClient<C, SM, FC, P>,
+    block_hash: Hash,
+) -> Option<SM::State>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone,
+{
+    client.chain.states.get(block_hash).ok().cloned()
+}
+
+pub(super) fn is_leaf<C, SM, FC, P>(
+    client: &FullClient<C, SM, FC, P>,
+    block_hash: Hash,
+) -> Option<bool>
+where
+    C: Consensus,
+    SM: StateMachine,
+{
+    let chain = &client.chain;
+    chain
+        .blocks
+        .contains_key(&block_hash)
+        .then(|| chain.leaves.contains(&block_hash))
+}
+
+pub(super) fn all_leaves<C, SM, FC, P>(client: &FullClient<C, SM, FC, P>) -> Vec<Hash>
+where
+    C: Consensus,
+    SM: StateMachine,
+{
+    client.chain.leaves.iter().copied().collect()
+}
@@ -0,0 +1,120 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::{Consensus, ConsensusAuthority, ForkChoice, FullClient, Hash, Header, StateMachine};
+use crate::hashing::header_hash;
+
+/// Every header that a fork choice rule has seen, in the order it saw them, so that parents
+/// always come before their children.
+#[derive(Default)]
+pub(super) struct Tree {
+    blocks: Vec<Node>,
+}
+
+struct Node {
+    hash: Hash,
+    parent: Hash,
+    /// What the block itself adds to the weight of a chain.
+    weight: u128,
+}
+
+impl Tree {
+    fn contains(&self, hash: Hash) -> bool {
+        self.blocks.iter().any(|node| node.hash == hash)
+    }
+}
+
+pub(super) fn insert<Digest: core::hash::Hash>(
+    tree: &mut Tree,
+    header: &Header<Digest>,
+    weight: u128,
+) {
+    tree.blocks.push(Node {
+        hash: header_hash(header),
+        parent: header.parent(),
+        weight,
+    });
+}
+
+/// The work that went into a PoW header, estimated from its hash. The lower the hash, the more
+/// hashes it takes on average to find one that low.
+pub(super) fn work(header: &Header<u64>) -> u128 {
+    u128::from(u64::MAX) / (u128::from(header_hash(header).low_u64()) + 1)
+}
+
+/// A single block of Alice's outweighs any number of blocks by other authorities. Among chains
+/// with as many of Alice's blocks, the longest one wins.
+pub(super) fn alice_weight(header: &Header<ConsensusAuthority>) -> u128 {
+    let alice = *header.consensus_digest() == ConsensusAuthority::Alice;
+    (u128::from(alice) << 64) + 1
+}
+
+/// The tip of the chain with the most weight in total, starting from the given header. On a
+/// tie, the block that was imported first wins, so the best block does not flip back and forth.
+pub(super) fn heaviest_chain<Digest: core::hash::Hash>(
+    tree: &Tree,
+    root: &Header<Digest>,
+) -> Option<Hash> {
+    let root = header_hash(root);
+    if !tree.contains(root) {
+        return None;
+    }
+    let mut totals = BTreeMap::from([(root, 0u128)]);
+    let mut best = (root, 0);
+    for node in &tree.blocks {
+        let Some(&total) = totals.get(&node.parent) else {
+            continue;
+        };
+        let total = total.saturating_add(node.weight);
+        totals.insert(node.hash, total);
+        if total > best.1 {
+            best = (node.hash, total);
+        }
+    }
+    Some(best.0)
+}
+
+/// Walk down from the given header, and at every fork, follow the child whose subtree weighs
+/// the most. On a tie, the child that was imported first wins.
+pub(super) fn ghost(tree: &Tree, root: &Header<u64>) -> Option<Hash> {
+    let root = header_hash(root);
+    if !tree.contains(root) {
+        return None;
+    }
+    // Children come after their parents, so walking backwards sees every subtree completed
+    // before it is added to its parent's.
+    let mut subtrees = BTreeMap::<Hash, u128>::new();
+    for node in tree.blocks.iter().rev() {
+        let subtree = subtrees.entry(node.hash).or_default();
+        *subtree = subtree.saturating_add(node.weight);
+        let subtree = *subtree;
+        let parent = subtrees.entry(node.parent).or_default();
+        *parent = parent.saturating_add(subtree);
+    }
+
+    let mut best = root;
+    loop {
+        let mut heaviest: Option<(Hash, u128)> = None;
+        for child in tree.blocks.iter().filter(|node| node.parent == best) {
+            let weight = subtrees[&child.hash];
+            if heaviest.is_none_or(|(_, most)| weight > most) {
+                heaviest = Some((child.hash, weight));
+            }
+        }
+        match heaviest {
+            Some((child, _)) => best = child,
+            None => return Some(best),
+        }
+    }
+}
+
+pub(super) fn best_block<C, SM, FC, P>(client: &FullClient<C, SM, FC, P>) -> Hash
+where
+    C: Consensus,
+    SM: StateMachine,
+    FC: ForkChoice<C>,
+{
+    let finalized = client.chain.finalized;
+    let header = client.chain.blocks[&finalized].header().clone();
+    client.fork_choice.best_block(header).unwrap_or(finalized)
+}
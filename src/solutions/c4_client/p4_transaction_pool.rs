@@ -0,0 +1,37 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use super::PriorityPool;
+
+/// Queue the transaction at the back, unless it is queued already.
+pub(super) fn push_new<T: PartialEq>(queue: &mut VecDeque<T>, t: T) -> bool {
+    if queue.contains(&t) {
+        return false;
+    }
+    queue.push_back(t);
+    true
+}
+
+pub(super) fn try_insert_prioritized<T, P>(pool: &mut PriorityPool<T, P>, t: T) -> bool
+where
+    T: Clone + PartialEq,
+    P: Fn(T) -> u64,
+{
+    let priority = (pool.prioritizer)(t.clone());
+    if priority < pool.minimum_priority || pool.queued.iter().any(|(_, queued)| *queued == t) {
+        return false;
+    }
+    pool.queued.push((priority, t));
+    true
+}
+
+/// Take out the transaction with the highest priority, and of those, the one that came first.
+pub(super) fn take_highest_priority<T>(queued: &mut Vec<(u64, T)>) -> Option<T> {
+    let mut highest: Option<(usize, u64)> = None;
+    for (index, &(priority, _)) in queued.iter().enumerate() {
+        if highest.is_none_or(|(_, most)| priority > most) {
+            highest = Some((index, priority));
+        }
+    }
+    highest.map(|(index, _)| queued.remove(index).1)
+}
@@ -0,0 +1,97 @@
+use alloc::vec::Vec;
+
+use super::{Consensus, ForkChoice, FullClient, Hash, StateMachine, TransactionPool};
+use crate::c4_client::solution::author_child;
+use crate::c4_client::ImportBlock;
+use crate::hashing::header_hash;
+
+pub(super) fn author_and_import_manual_block<C, SM, FC, P>(
+    client: &mut FullClient<C, SM, FC, P>,
+    transactions: Vec<SM::Transition>,
+    parent_hash: Hash,
+) where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + PartialEq + core::hash::Hash,
+    SM::Transition: Clone + PartialEq + core::hash::Hash,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+{
+    let (Some(parent), Some(state)) = (
+        client.chain.blocks.get(&parent_hash),
+        client.chain.states.get(parent_hash).ok(),
+    ) else {
+        return;
+    };
+    let block = author_child(
+        &client.consensus_engine,
+        client.executive.as_deref(),
+        parent,
+        state,
+        transactions,
+    );
+    if let Some(block) = block {
+        client.import_block(block);
+    }
+}
+
+pub(super) fn author_and_import_automatic_block<C, SM, FC, P>(
+    client: &mut FullClient<C, SM, FC, P>,
+) -> Option<Hash>
+where
+    C: Consensus,
+    SM: StateMachine,
+    SM::State: Clone + PartialEq + core::hash::Hash,
+    SM::Transition: Clone + PartialEq + core::hash::Hash,
+    FC: ForkChoice<C>,
+    P: TransactionPool<SM>,
+{
+    let best = client.best_block();
+    let mut state = client.chain.states.get(best).ok()?.clone();
+
+    let mut included = Vec::new();
+    let mut skipped = Vec::new();
+    // Once an extrinsic does not fit, neither it nor any after it make it into the block.
+    // Skipping ahead to one that does fit could execute a sender's nonces out of order.
+    let mut full = false;
+    while let Some(t) = client.transaction_pool.next_from_pool() {
+        full = full
+            || client
+                .executive
+                .as_ref()
+                .is_some_and(|executive| !executive.fits(&included, &t));
+        if full {
+            skipped.push(t);
+            continue;
+        }
+        let next = SM::next_state(&state, &t);
+        if next == state {
+            skipped.push(t);
+        } else {
+            state = next;
+            included.push(t);
+        }
+    }
+
+    let parent = &client.chain.blocks[&best];
+    let pre_state = client.chain.states.get(best).ok()?;
+    let block = author_child(
+        &client.consensus_engine,
+        client.executive.as_deref(),
+        parent,
+        pre_state,
+        included.clone(),
+    );
+    // Whatever does not make it into a block goes back into the pool, in the same order.
+    let leftover = match block {
+        Some(_) => skipped,
+        None => included.into_iter().chain(skipped).collect(),
+    };
+    for t in leftover {
+        client.transaction_pool.try_insert(t);
+    }
+
+    let block = block?;
+    let hash = header_hash(block.header());
+    client.import_block(block).then_some(hash)
+}
@@ -0,0 +1,34 @@
+use super::{Consensus, FullClient, Hash, StateMachine};
+use crate::c4_client::storage::StorageMode;
+
+pub(super) fn manually_finalize_block<C, SM, FC, P>(
+    client: &mut FullClient<C, SM, FC, P>,
+    block_hash: Hash,
+) -> bool
+where
+    C: Consensus,
+    SM: StateMachine,
+{
+    let chain = &mut client.chain;
+    // Finalizing a block on another fork would revert the finalized one.
+    if !chain.descends_from(chain.finalized, block_hash) {
+        return false;
+    }
+    // A pruned node may have thrown the state away already, but the block is final all the same.
+    let _ = chain.states.finalize(block_hash);
+    chain.finalized = block_hash;
+    true
+}
+
+pub(super) fn with_state_pruning<C, SM, FC, P>(
+    mut client: FullClient<C, SM, FC, P>,
+    mode: StorageMode,
+    keep: u64,
+) -> FullClient<C, SM, FC, P>
+where
+    C: Consensus,
+    SM: StateMachine,
+{
+    client.chain.states.set_mode(mode, keep);
+    client
+}
@@ -0,0 +1,244 @@
+//! Randomized tests and simulations find bugs that hand written cases miss, but a failure is
+//! only useful if it can be seen again. `TestRng` is a pseudo random number generator that is
+//! fully determined by a 64 bit seed, so the same seed gives the same keys, extrinsics and
+//! headers on every run and every platform.
+//!
+//! Tests that want fresh randomness on every run use `TestRng::from_env`. It prints the seed
+//! it picked, and a failing run can be replayed by setting the `TEST_SEED` environment
+//! variable to that seed.
+//!
+//! ```ignore
+//! let mut rng = TestRng::from_env();
+//! let calls: Vec<_> = (0..100).map(|_| rng.call()).collect();
+//! ```
+//!
+//! The generator is ChaCha20, which is plenty random for tests. Never use it for real keys:
+//! anyone who knows the seed knows every key it generates.
+
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::c1_state_machine::{AccountingTransaction, User};
+use crate::c3_consensus::Header;
+use crate::c4_client::keystore::Keystore;
+use crate::c4_client::runtime::{signing_payload, SignedExtrinsic};
+use crate::crypto::sig::Keypair;
+use crate::hashing::{header_hash, H256};
+
+/// The environment variable that `TestRng::from_env` reads its seed from.
+pub const SEED_VAR: &str = "TEST_SEED";
+
+/// A seeded, deterministic random number generator for tests and simulations.
+#[derive(Clone, Debug)]
+pub struct TestRng {
+    seed: u64,
+    inner: ChaCha20Rng,
+}
+
+impl TestRng {
+    /// A generator that always produces the same output for the same seed.
+    pub fn seeded(seed: u64) -> Self {
+        TestRng {
+            seed,
+            inner: ChaCha20Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// A generator seeded from the `TEST_SEED` environment variable, or from a random seed if
+    /// it is not set. Either way, the seed is printed so that the run can be replayed.
+    pub fn from_env() -> Self {
+        let seed = match std::env::var(SEED_VAR) {
+            Ok(seed) => seed
+                .parse()
+                .unwrap_or_else(|_| panic!("{SEED_VAR} must be a number, not {seed:?}")),
+            Err(_) => u64::from_le_bytes(crate::crypto::random_bytes()),
+        };
+        eprintln!("using random seed {seed}, replay with {SEED_VAR}={seed}");
+        Self::seeded(seed)
+    }
+
+    /// The seed this generator was created with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// A number in 0..n. The number must not be zero.
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "can not pick a number below zero");
+        // Reject the top end of the range that would make some numbers more likely than others.
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+
+    /// A number in 0..1.
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// True with the given probability.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.unit() < probability
+    }
+
+    /// An element of the given slice, or None if it is empty.
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get(self.below(items.len() as u64) as usize)
+    }
+
+    /// Some random bytes.
+    pub fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0; N];
+        self.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    /// A random hash.
+    pub fn h256(&mut self) -> H256 {
+        H256(self.bytes())
+    }
+
+    /// One of the tutorial's users.
+    pub fn user(&mut self) -> User {
+        *self
+            .pick(&[User::Alice, User::Bob, User::Charlie])
+            .expect("there are users")
+    }
+
+    /// A fresh keypair.
+    pub fn keypair(&mut self) -> Keypair {
+        Keypair::from_seed(self.bytes())
+    }
+
+    /// A currency call made on behalf of the given user, with an amount below 1000.
+    pub fn call_by(&mut self, origin: User) -> AccountingTransaction {
+        let amount = self.below(1_000);
+        match self.below(3) {
+            0 => AccountingTransaction::Mint {
+                minter: origin,
+                amount,
+            },
+            1 => AccountingTransaction::Burn {
+                burner: origin,
+                amount,
+            },
+            _ => AccountingTransaction::Transfer {
+                sender: origin,
+                receiver: self.user(),
+                amount,
+            },
+        }
+    }
+
+    /// A currency call made on behalf of a random user.
+    pub fn call(&mut self) -> AccountingTransaction {
+        let origin = self.user();
+        self.call_by(origin)
+    }
+
+    /// An extrinsic with the given nonce, signed with its signer's dev key, so that the
+    /// runtime accepts it whenever the nonce is right.
+    pub fn extrinsic(&mut self, nonce: u64) -> SignedExtrinsic {
+        let signer = self.user();
+        let call = self.call_by(signer);
+        let signature = Keystore::dev()
+            .sign(signer, signing_payload(signer, nonce, &call))
+            .expect("the dev keystore holds every user's key");
+        SignedExtrinsic {
+            signer,
+            nonce,
+            call,
+            signature,
+        }
+    }
+
+    /// A chain of headers on top of the given parent, with random state and extrinsics roots.
+    /// Only the links between the headers are meaningful.
+    pub fn headers(&mut self, parent: &Header<()>, count: usize) -> Vec<Header<()>> {
+        let mut headers: Vec<Header<()>> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let parent = headers.last().unwrap_or(parent);
+            let header = Header::new(
+                header_hash(parent),
+                parent.height() + 1,
+                self.h256(),
+                self.h256(),
+                (),
+            );
+            headers.push(header);
+        }
+        headers
+    }
+}
+
+// Implementing the `rand` traits lets a `TestRng` drive anything else that takes an RNG.
+impl RngCore for TestRng {
+    fn next_u32(&mut self) -> u32 {
+        self.inner.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.inner.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_chacha::rand_core::Error> {
+        self.inner.try_fill_bytes(dest)
+    }
+}
+
+#[test]
+fn test_rng_replays_from_its_seed() {
+    let mut a = TestRng::seeded(7);
+    let mut b = TestRng::seeded(7);
+    assert_eq!(a.extrinsic(0), b.extrinsic(0));
+    assert_eq!(a.keypair().public(), b.keypair().public());
+    assert_eq!(a.h256(), b.h256());
+
+    let mut c = TestRng::seeded(8);
+    assert_ne!(TestRng::seeded(7).h256(), c.h256());
+    assert_eq!(c.seed(), 8);
+}
+
+#[test]
+fn test_rng_stays_in_range() {
+    let mut rng = TestRng::seeded(1);
+    for n in [1, 2, 3, 10, u64::MAX] {
+        assert!((0..100).all(|_| rng.below(n) < n));
+    }
+    assert!((0..100).all(|_| (0.0..1.0).contains(&rng.unit())));
+    assert!(!rng.chance(0.0));
+    assert_eq!(rng.pick::<u8>(&[]), None);
+}
+
+#[test]
+fn test_rng_generates_valid_extrinsics_and_chains() {
+    let mut rng = TestRng::seeded(3);
+    for nonce in 0..10 {
+        let extrinsic = rng.extrinsic(nonce);
+        let payload = signing_payload(extrinsic.signer, nonce, &extrinsic.call);
+        assert!(extrinsic.signature.verify(extrinsic.signer, payload));
+        let origin = match extrinsic.call {
+            AccountingTransaction::Mint { minter, .. } => minter,
+            AccountingTransaction::Burn { burner, .. } => burner,
+            AccountingTransaction::Transfer { sender, .. } => sender,
+        };
+        assert_eq!(origin, extrinsic.signer);
+    }
+
+    let genesis = Header::new(H256::zero(), 0, H256::zero(), H256::zero(), ());
+    let chain = rng.headers(&genesis, 3);
+    assert_eq!(chain[0].parent(), header_hash(&genesis));
+    assert_eq!(chain[2].parent(), header_hash(&chain[1]));
+    assert_eq!(chain[2].height(), 3);
+}
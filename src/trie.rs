@@ -0,0 +1,478 @@
+//! A Merkle tree commits to a list. State is not a list though, it is a key-value map, and a
+//! block usually only touches a handful of its keys. A Patricia trie commits to a map in a way
+//! that makes both lookups and updates cheap, and still lets us prove single entries.
+//!
+//! Keys are split into nibbles, half bytes, and every nibble picks one of sixteen children on
+//! the way down from the root. To keep the trie from growing a node per nibble, runs of nibbles
+//! that no other key shares are stored in a single node:
+//! * A leaf holds the rest of its key along with the value.
+//! * An extension holds a run of nibbles that every key below it shares.
+//! * A branch has a child for every nibble that some key below it continues with, and a value
+//!   if a key ends right there.
+//!
+//! Every map has exactly one trie, no matter in which order its entries were inserted, so the
+//! root hash only depends on the contents. A proof is the list of nodes on the path from the root
+//! towards a key, with every child replaced by its hash. It proves absence as well as presence.
+//!
+//! Real tries, such as the ones in Ethereum and Substrate, cache the hash of every node and keep
+//! their nodes in a database. Ours recomputes hashes every time and lives in memory.
+
+use alloc::{boxed::Box, vec::Vec};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::{hash, H256};
+use crate::merkle::empty_root;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node<V> {
+    Leaf {
+        path: Vec<u8>,
+        value: V,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: Box<Node<V>>,
+    },
+    Branch {
+        children: Box<[Option<Node<V>>; 16]>,
+        value: Option<V>,
+    },
+}
+
+/// A node as it appears in a proof, and as it is hashed: with its children replaced by their
+/// hashes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ProofNode<V> {
+    Leaf {
+        path: Vec<u8>,
+        value: V,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: H256,
+    },
+    Branch {
+        children: Box<[Option<H256>; 16]>,
+        value: Option<V>,
+    },
+}
+
+/// The nodes on the path from the root towards a key, starting at the root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrieProof<V> {
+    pub nodes: Vec<ProofNode<V>>,
+}
+
+/// A map from byte strings to values, committed to by a single root hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trie<V> {
+    root: Option<Node<V>>,
+    len: usize,
+}
+
+impl<V> Default for Trie<V> {
+    fn default() -> Self {
+        Trie { root: None, len: 0 }
+    }
+}
+
+/// Split the key into nibbles, high nibble first.
+fn nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|b| [b >> 4, b & 0xf]).collect()
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+fn empty_branch<V>() -> Node<V> {
+    Node::Branch {
+        children: Box::new(core::array::from_fn(|_| None)),
+        value: None,
+    }
+}
+
+/// Prepend the given nibbles to the path of the node.
+fn join<V>(prefix: &[u8], node: Node<V>) -> Node<V> {
+    match node {
+        Node::Leaf { path, value } => Node::Leaf {
+            path: [prefix, &path].concat(),
+            value,
+        },
+        Node::Extension { path, child } => Node::Extension {
+            path: [prefix, &path].concat(),
+            child,
+        },
+        branch if prefix.is_empty() => branch,
+        branch => Node::Extension {
+            path: prefix.to_vec(),
+            child: Box::new(branch),
+        },
+    }
+}
+
+/// Put a node that continues with the given nibbles below a fresh branch.
+fn attach<V>(branch: &mut Node<V>, path: &[u8], node: impl FnOnce(&[u8]) -> Node<V>) {
+    let Node::Branch { children, value } = branch else {
+        unreachable!("only called on branches")
+    };
+    match path.split_first() {
+        Some((&nibble, rest)) => children[nibble as usize] = Some(node(rest)),
+        None => match node(path) {
+            Node::Leaf { value: v, .. } => *value = Some(v),
+            _ => unreachable!("only leaves can end at a branch"),
+        },
+    }
+}
+
+fn insert<V>(node: Option<Node<V>>, path: &[u8], value: V) -> (Node<V>, Option<V>) {
+    let leaf = |rest: &[u8], value| Node::Leaf {
+        path: rest.to_vec(),
+        value,
+    };
+    match node {
+        None => (leaf(path, value), None),
+        Some(Node::Leaf { path: p, value: v }) if p == path => (leaf(path, value), Some(v)),
+        Some(Node::Leaf { path: p, value: v }) => {
+            let shared = common_prefix(&p, path);
+            let mut branch = empty_branch();
+            attach(&mut branch, &p[shared..], |rest| leaf(rest, v));
+            attach(&mut branch, &path[shared..], |rest| leaf(rest, value));
+            (join(&path[..shared], branch), None)
+        }
+        Some(Node::Extension { path: p, child }) if path.starts_with(&p) => {
+            let (child, old) = insert(Some(*child), &path[p.len()..], value);
+            (join(&p, child), old)
+        }
+        Some(Node::Extension { path: p, child }) => {
+            let shared = common_prefix(&p, path);
+            let mut branch = empty_branch();
+            attach(&mut branch, &p[shared..], |rest| join(rest, *child));
+            attach(&mut branch, &path[shared..], |rest| leaf(rest, value));
+            (join(&path[..shared], branch), None)
+        }
+        Some(Node::Branch {
+            mut children,
+            value: v,
+        }) => match path.split_first() {
+            None => (
+                Node::Branch {
+                    children,
+                    value: Some(value),
+                },
+                v,
+            ),
+            Some((&nibble, rest)) => {
+                let slot = &mut children[nibble as usize];
+                let (child, old) = insert(slot.take(), rest, value);
+                *slot = Some(child);
+                (Node::Branch { children, value: v }, old)
+            }
+        },
+    }
+}
+
+fn remove<V>(node: Node<V>, path: &[u8]) -> (Option<Node<V>>, Option<V>) {
+    match node {
+        Node::Leaf { path: p, value } if p == path => (None, Some(value)),
+        Node::Extension { path: p, child } if path.starts_with(&p) => {
+            let (child, old) = remove(*child, &path[p.len()..]);
+            (child.map(|child| join(&p, child)), old)
+        }
+        Node::Branch {
+            mut children,
+            mut value,
+        } => {
+            let old = match path.split_first() {
+                None => value.take(),
+                Some((&nibble, rest)) => {
+                    let slot = &mut children[nibble as usize];
+                    match slot.take() {
+                        Some(child) => {
+                            let (child, old) = remove(child, rest);
+                            *slot = child;
+                            old
+                        }
+                        None => None,
+                    }
+                }
+            };
+            (normalize(children, value), old)
+        }
+        other => (Some(other), None),
+    }
+}
+
+/// Collapse a branch that no longer needs to be one, so the trie stays the same as if the
+/// removed key had never been inserted.
+fn normalize<V>(mut children: Box<[Option<Node<V>>; 16]>, value: Option<V>) -> Option<Node<V>> {
+    let mut occupied = (0..16).filter(|&i| children[i].is_some());
+    match (occupied.next(), occupied.next(), value) {
+        (None, _, None) => None,
+        (None, _, Some(value)) => Some(Node::Leaf {
+            path: Vec::new(),
+            value,
+        }),
+        (Some(only), None, None) => {
+            let child = children[only].take().expect("occupied");
+            Some(join(&[only as u8], child))
+        }
+        (_, _, value) => Some(Node::Branch { children, value }),
+    }
+}
+
+impl<V: core::hash::Hash + Clone> Node<V> {
+    fn proof_node(&self) -> ProofNode<V> {
+        match self {
+            Node::Leaf { path, value } => ProofNode::Leaf {
+                path: path.clone(),
+                value: value.clone(),
+            },
+            Node::Extension { path, child } => ProofNode::Extension {
+                path: path.clone(),
+                child: child.hash(),
+            },
+            Node::Branch { children, value } => ProofNode::Branch {
+                children: Box::new(core::array::from_fn(|i| {
+                    children[i].as_ref().map(Node::hash)
+                })),
+                value: value.clone(),
+            },
+        }
+    }
+
+    fn hash(&self) -> H256 {
+        hash(&self.proof_node())
+    }
+}
+
+impl<V> Trie<V> {
+    /// Create an empty trie.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of entries in the trie.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the trie has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Look up the value at the given key.
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        let path = nibbles(key);
+        let mut rest = &path[..];
+        let mut node = self.root.as_ref()?;
+        loop {
+            match node {
+                Node::Leaf { path, value } => return (path == rest).then_some(value),
+                Node::Extension { path, child } => {
+                    rest = rest.strip_prefix(&path[..])?;
+                    node = child;
+                }
+                Node::Branch { children, value } => match rest.split_first() {
+                    None => return value.as_ref(),
+                    Some((&nibble, tail)) => {
+                        rest = tail;
+                        node = children[nibble as usize].as_ref()?;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Insert the value at the given key, and return the value that was there before.
+    pub fn insert(&mut self, key: &[u8], value: V) -> Option<V> {
+        let (root, old) = insert(self.root.take(), &nibbles(key), value);
+        self.root = Some(root);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Remove the value at the given key, and return it.
+    pub fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let root = self.root.take()?;
+        let (root, old) = remove(root, &nibbles(key));
+        self.root = root;
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+}
+
+impl<V: core::hash::Hash + Clone> Trie<V> {
+    /// The root hash, which commits to every entry in the trie. The root of an empty trie is
+    /// the same as that of an empty Merkle tree.
+    pub fn root(&self) -> H256 {
+        self.root.as_ref().map_or_else(empty_root, Node::hash)
+    }
+
+    /// Prove the value at the given key, or that there is none.
+    pub fn prove(&self, key: &[u8]) -> TrieProof<V> {
+        let path = nibbles(key);
+        let mut rest = &path[..];
+        let mut nodes = Vec::new();
+        let mut next = self.root.as_ref();
+        while let Some(node) = next {
+            nodes.push(node.proof_node());
+            next = match node {
+                Node::Leaf { .. } => None,
+                Node::Extension { path, child } => rest.strip_prefix(&path[..]).map(|tail| {
+                    rest = tail;
+                    &**child
+                }),
+                Node::Branch { children, .. } => rest.split_first().and_then(|(&nibble, tail)| {
+                    rest = tail;
+                    children[nibble as usize].as_ref()
+                }),
+            };
+        }
+        TrieProof { nodes }
+    }
+}
+
+/// The root hash that the proof starts from.
+pub fn proof_root<V: core::hash::Hash>(proof: &TrieProof<V>) -> H256 {
+    proof.nodes.first().map_or_else(empty_root, hash)
+}
+
+/// Check that the proof shows the given value at the given key, in the trie with the given root.
+/// A value of None checks that there is no entry at the key.
+pub fn verify_proof<V: core::hash::Hash + PartialEq>(
+    root: H256,
+    key: &[u8],
+    value: Option<&V>,
+    proof: &TrieProof<V>,
+) -> bool {
+    if proof.nodes.is_empty() {
+        return root == empty_root() && value.is_none();
+    }
+
+    let path = nibbles(key);
+    let mut rest = &path[..];
+    let mut expected = root;
+    for (i, node) in proof.nodes.iter().enumerate() {
+        if hash(node) != expected {
+            return false;
+        }
+        // Only the last node may answer the question. Anything after it is padding.
+        let last = i + 1 == proof.nodes.len();
+        expected = match node {
+            ProofNode::Leaf { path, value: v } => {
+                return last && value == (path == rest).then_some(v);
+            }
+            ProofNode::Extension { path, child } => match rest.strip_prefix(&path[..]) {
+                Some(tail) => {
+                    rest = tail;
+                    *child
+                }
+                None => return last && value.is_none(),
+            },
+            ProofNode::Branch { children, value: v } => match rest.split_first() {
+                None => return last && value == v.as_ref(),
+                Some((&nibble, tail)) => match children[nibble as usize] {
+                    Some(child) => {
+                        rest = tail;
+                        child
+                    }
+                    None => return last && value.is_none(),
+                },
+            },
+        };
+    }
+    // The proof ended before it reached the key.
+    false
+}
+
+#[cfg(test)]
+const TEST_KEYS: [&[u8]; 6] = [b"", b"a", b"ab", b"abc", b"b", b"\xff\x00"];
+
+#[test]
+fn trie_inserts_gets_and_removes() {
+    let mut trie = Trie::new();
+    for (i, key) in TEST_KEYS.iter().enumerate() {
+        assert_eq!(trie.insert(key, i), None);
+    }
+    assert_eq!(trie.len(), TEST_KEYS.len());
+    for (i, key) in TEST_KEYS.iter().enumerate() {
+        assert_eq!(trie.get(key), Some(&i));
+    }
+    assert_eq!(trie.get(b"abcd"), None);
+    assert_eq!(trie.get(b"\xff"), None);
+
+    assert_eq!(trie.insert(b"ab", 10), Some(2));
+    assert_eq!(trie.remove(b"a"), Some(1));
+    assert_eq!(trie.remove(b"a"), None);
+    assert_eq!(trie.get(b"a"), None);
+    assert_eq!(trie.get(b"ab"), Some(&10));
+    assert_eq!(trie.len(), TEST_KEYS.len() - 1);
+
+    for key in TEST_KEYS {
+        trie.remove(key);
+    }
+    assert!(trie.is_empty());
+    assert_eq!(trie, Trie::new());
+}
+
+#[test]
+fn trie_root_only_depends_on_contents() {
+    let mut forward = Trie::new();
+    for (i, key) in TEST_KEYS.iter().enumerate() {
+        forward.insert(key, i);
+    }
+    let mut backward = Trie::new();
+    for (i, key) in TEST_KEYS.iter().enumerate().rev() {
+        backward.insert(key, i);
+    }
+    // Inserting and removing an extra key must leave no trace either.
+    backward.insert(b"abd", 99);
+    backward.remove(b"abd");
+
+    assert_eq!(forward, backward);
+    assert_eq!(forward.root(), backward.root());
+    assert_eq!(Trie::<u64>::new().root(), empty_root());
+
+    backward.insert(b"ab", 10);
+    assert_ne!(forward.root(), backward.root());
+}
+
+#[test]
+fn trie_proves_presence_and_absence() {
+    let mut trie = Trie::new();
+    for (i, key) in TEST_KEYS.iter().enumerate() {
+        trie.insert(key, i);
+    }
+    let root = trie.root();
+
+    for (i, key) in TEST_KEYS.iter().enumerate() {
+        let proof = trie.prove(key);
+        assert!(verify_proof(root, key, Some(&i), &proof));
+        assert!(!verify_proof(root, key, Some(&(i + 1)), &proof));
+        assert!(!verify_proof(root, key, None, &proof));
+    }
+    for missing in [&b"abcd"[..], b"c", b"\xff"] {
+        let proof = trie.prove(missing);
+        assert!(verify_proof(root, missing, None, &proof));
+        assert!(!verify_proof(root, missing, Some(&0), &proof));
+    }
+
+    // A proof is only good for the trie it came from, and can not be cut short.
+    let mut proof = trie.prove(b"abc");
+    assert!(!verify_proof(
+        Trie::<usize>::new().root(),
+        b"abc",
+        Some(&3),
+        &proof
+    ));
+    proof.nodes.pop();
+    assert!(!verify_proof(root, b"abc", Some(&3), &proof));
+    assert!(!verify_proof(root, b"abc", None, &proof));
+}
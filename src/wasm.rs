@@ -0,0 +1,245 @@
+//! JavaScript bindings for the browser playground, where students can poke the state machines
+//! and build toy chains without installing Rust.
+//!
+//! The bindings only cover what a web page needs to drive a few of the chapters: every class
+//! wraps one state machine, or a chain of headers, with methods named after the commands of the
+//! learner TUI. They run whatever the exercises do, so a playground built from a student's
+//! checkout shows their own code at work. Code that is not written yet traps, and the page sees
+//! a `RuntimeError`. Build with the `solutions` feature to show the reference behavior instead.
+//!
+//! ```js
+//! import init, { Atm } from "./pkg/diy_blockchain_playground.js";
+//! await init();
+//! const atm = new Atm(100n);
+//! atm.swipe("1234");
+//! atm.press("1234");
+//! atm.enter();
+//! ```
+//!
+//! Amounts, heights and nonces are `u64`s, which are `BigInt`s on the JavaScript side.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use wasm_bindgen::prelude::*;
+
+use crate::c1_state_machine::{self as c1, AccountedCurrency, AccountingTransaction};
+use crate::c1_state_machine::{Action, Key, StateMachine, User};
+use crate::c3_consensus::{Consensus, Header, Pow};
+use crate::difficulty::difficulty_to_threshold;
+use crate::hashing::{header_hash, pin_hash, H256};
+
+/// The ATM from chapter 1.
+#[wasm_bindgen]
+pub struct Atm {
+    atm: c1::Atm,
+}
+
+#[wasm_bindgen]
+impl Atm {
+    /// An ATM with the given amount of cash inside.
+    #[wasm_bindgen(constructor)]
+    pub fn new(cash_inside: u64) -> Self {
+        Atm {
+            atm: c1::Atm::new(cash_inside),
+        }
+    }
+
+    /// Swipe a card whose pin is made of the keys 1 to 4, such as `"1234"`.
+    pub fn swipe(&mut self, pin: &str) -> Result<(), JsError> {
+        let pin = parse_keys(pin)?;
+        self.apply(&Action::SwipeCard(pin_hash(&pin)));
+        Ok(())
+    }
+
+    /// Press the given number keys, such as `"42"`, one after the other.
+    pub fn press(&mut self, keys: &str) -> Result<(), JsError> {
+        for key in parse_keys(keys)? {
+            self.apply(&Action::PressKey(key));
+        }
+        Ok(())
+    }
+
+    /// Press the enter key.
+    pub fn enter(&mut self) {
+        self.apply(&Action::PressKey(Key::Enter));
+    }
+
+    #[wasm_bindgen(js_name = cashInside)]
+    pub fn cash_inside(&self) -> u64 {
+        self.atm.cash_inside()
+    }
+
+    /// The whole state of the ATM, as Rust's debug output.
+    pub fn state(&self) -> String {
+        format!("{:#?}", self.atm)
+    }
+
+    fn apply(&mut self, action: &Action) {
+        self.atm = c1::Atm::next_state(&self.atm, action);
+    }
+}
+
+/// Parse a string of digits into keypad keys.
+fn parse_keys(digits: &str) -> Result<Vec<Key>, JsError> {
+    digits
+        .chars()
+        .map(|digit| match digit {
+            '1' => Ok(Key::One),
+            '2' => Ok(Key::Two),
+            '3' => Ok(Key::Three),
+            '4' => Ok(Key::Four),
+            _ => Err(JsError::new(&format!("the keypad has no key `{digit}`"))),
+        })
+        .collect()
+}
+
+/// The accounted currency from chapter 1. Users are named by strings, such as `"alice"`.
+#[wasm_bindgen]
+pub struct Currency {
+    balances: <AccountedCurrency as StateMachine>::State,
+}
+
+#[wasm_bindgen]
+impl Currency {
+    /// A currency in which nobody has an account yet.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Currency {
+            balances: Default::default(),
+        }
+    }
+
+    pub fn mint(&mut self, minter: &str, amount: u64) -> Result<(), JsError> {
+        let minter = parse_user(minter)?;
+        self.apply(&AccountingTransaction::Mint { minter, amount });
+        Ok(())
+    }
+
+    pub fn burn(&mut self, burner: &str, amount: u64) -> Result<(), JsError> {
+        let burner = parse_user(burner)?;
+        self.apply(&AccountingTransaction::Burn { burner, amount });
+        Ok(())
+    }
+
+    pub fn transfer(&mut self, sender: &str, receiver: &str, amount: u64) -> Result<(), JsError> {
+        let (sender, receiver) = (parse_user(sender)?, parse_user(receiver)?);
+        self.apply(&AccountingTransaction::Transfer {
+            sender,
+            receiver,
+            amount,
+        });
+        Ok(())
+    }
+
+    /// The balance of the given user, or `undefined` if they have no account.
+    pub fn balance(&self, user: &str) -> Result<Option<u64>, JsError> {
+        Ok(self.balances.get(&parse_user(user)?).copied())
+    }
+
+    fn apply(&mut self, call: &AccountingTransaction) {
+        self.balances = AccountedCurrency::next_state(&self.balances, call);
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_user(name: &str) -> Result<User, JsError> {
+    name.parse().map_err(|e: String| JsError::new(&e))
+}
+
+/// A chain of empty headers sealed with proof of work, from chapter 3. Mine it, tamper with
+/// it, and see whether it still verifies.
+#[wasm_bindgen]
+pub struct PowChain {
+    engine: Pow,
+    /// Every header from genesis to the tip.
+    headers: Vec<Header<u64>>,
+}
+
+#[wasm_bindgen]
+impl PowChain {
+    /// A chain that only has a genesis header, whose blocks take about the given number of
+    /// attempts to mine.
+    #[wasm_bindgen(constructor)]
+    pub fn new(difficulty: u64) -> Self {
+        PowChain {
+            engine: Pow::new(difficulty_to_threshold(difficulty.into())),
+            headers: alloc::vec![Header::new(H256::zero(), 0, H256::zero(), H256::zero(), 0)],
+        }
+    }
+
+    /// Mine a header on top of the tip, and return its hash.
+    pub fn mine(&mut self) -> Result<String, JsError> {
+        let tip = self
+            .headers
+            .last()
+            .expect("there is always a genesis header");
+        let partial = Header::new(
+            header_hash(tip),
+            tip.height() + 1,
+            H256::zero(),
+            H256::zero(),
+            (),
+        );
+        let header = self
+            .engine
+            .seal(tip.consensus_digest(), partial)
+            .ok_or_else(|| JsError::new("the engine could not seal the header"))?;
+        let hash = header_hash(&header).to_string();
+        self.headers.push(header);
+        Ok(hash)
+    }
+
+    /// The height of the tip.
+    pub fn height(&self) -> u64 {
+        self.headers.len() as u64 - 1
+    }
+
+    /// The hash of the header at the given height.
+    pub fn hash(&self, height: u64) -> Option<String> {
+        let header = self.headers.get(height as usize)?;
+        Some(header_hash(header).to_string())
+    }
+
+    /// The nonce of the header at the given height.
+    pub fn nonce(&self, height: u64) -> Option<u64> {
+        Some(*self.headers.get(height as usize)?.consensus_digest())
+    }
+
+    /// Replace the nonce of the header at the given height, which most likely breaks its seal.
+    #[wasm_bindgen(js_name = setNonce)]
+    pub fn set_nonce(&mut self, height: u64, nonce: u64) -> Result<(), JsError> {
+        let header = self
+            .headers
+            .get_mut(height as usize)
+            .ok_or_else(|| JsError::new(&format!("there is no header at height {height}")))?;
+        *header = Header::new(
+            header.parent(),
+            header.height(),
+            header.state_root(),
+            header.extrinsics_root(),
+            nonce,
+        );
+        Ok(())
+    }
+
+    /// Whether every header is the child of the one before it, and sealed according to the
+    /// proof of work rules.
+    pub fn verify(&self) -> bool {
+        let linked = self.headers.windows(2).all(|pair| {
+            pair[1].parent() == header_hash(&pair[0]) && pair[1].height() == pair[0].height() + 1
+        });
+        linked
+            && self
+                .engine
+                .verify_sub_chain(self.headers[0].consensus_digest(), &self.headers[1..])
+    }
+}